@@ -0,0 +1,154 @@
+//! Central app state for the frontend
+//!
+//! After a webview reload the UI rebuilds itself from one `get_app_state`
+//! snapshot instead of calling a getter per area. Backend code that changes
+//! an area calls `changed()`; changes are coalesced for a moment and sent as
+//! one "state-delta" event carrying the new value of every changed area.
+//! Each delta bumps the revision, so the UI can drop deltas that are older
+//! than the snapshot it loaded.
+
+use crate::commands::{AppSettings, SelfInfo};
+use crate::network::discovery::{self, DiscoveredDevice};
+use crate::transfer::{self, FileTransfer};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::Notify;
+
+/// Changes closer together than this go out in one delta
+const COALESCE: Duration = Duration::from_millis(50);
+
+/// Area of the app state that changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slice {
+    Devices,
+    Transfers,
+    Settings,
+    Sharing,
+    Service,
+    SelfInfo,
+}
+
+impl Slice {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Local sharing state, across the full and simple pipelines
+#[derive(Debug, Clone, Serialize)]
+pub struct SharingState {
+    pub is_sharing: bool,
+    pub display_ids: Vec<u32>,
+    pub paused: bool,
+    pub simple_sharing: bool,
+}
+
+/// Everything the main window shows
+#[derive(Debug, Clone, Serialize)]
+pub struct AppState {
+    pub revision: u64,
+    pub service_running: bool,
+    pub self_info: Option<SelfInfo>,
+    pub devices: Vec<DiscoveredDevice>,
+    pub transfers: Vec<FileTransfer>,
+    pub settings: AppSettings,
+    pub sharing: SharingState,
+}
+
+/// New values of the areas that changed since the previous delta
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StateDelta {
+    pub revision: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub devices: Option<Vec<DiscoveredDevice>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfers: Option<Vec<FileTransfer>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<AppSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharing: Option<SharingState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_running: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_info: Option<SelfInfo>,
+}
+
+static REVISION: AtomicU64 = AtomicU64::new(0);
+
+/// Bit set of slices changed since the last delta
+static DIRTY: AtomicU8 = AtomicU8::new(0);
+
+static WAKE: once_cell::sync::Lazy<Notify> = once_cell::sync::Lazy::new(Notify::new);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Note that a slice changed; a delta follows shortly
+pub fn changed(slice: Slice) {
+    DIRTY.fetch_or(slice.bit(), Ordering::SeqCst);
+    WAKE.notify_one();
+}
+
+/// Start sending deltas. Called once the app handle is set.
+pub fn start() {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tauri::async_runtime::spawn(async {
+        loop {
+            WAKE.notified().await;
+            tokio::time::sleep(COALESCE).await;
+            let dirty = DIRTY.swap(0, Ordering::SeqCst);
+            if dirty == 0 {
+                continue;
+            }
+            // Peers see whether we share in our mDNS record
+            if dirty & Slice::Sharing.bit() != 0 {
+                discovery::publish_sharing();
+            }
+            let delta = build_delta(dirty);
+            if let Some(app) = crate::APP_HANDLE.get() {
+                let _ = app.emit("state-delta", &delta);
+            }
+        }
+    });
+}
+
+/// Full snapshot at the current revision
+pub fn snapshot() -> AppState {
+    AppState {
+        revision: REVISION.load(Ordering::SeqCst),
+        service_running: crate::commands::is_service_running(),
+        self_info: crate::commands::get_self_info().ok(),
+        devices: discovery::get_devices(),
+        transfers: transfer::get_transfer_manager().get_all_transfers(),
+        settings: crate::commands::get_settings(),
+        sharing: sharing_state(),
+    }
+}
+
+fn build_delta(dirty: u8) -> StateDelta {
+    let has = |slice: Slice| dirty & slice.bit() != 0;
+    StateDelta {
+        revision: REVISION.fetch_add(1, Ordering::SeqCst) + 1,
+        devices: has(Slice::Devices).then(discovery::get_devices),
+        transfers: has(Slice::Transfers).then(|| transfer::get_transfer_manager().get_all_transfers()),
+        settings: has(Slice::Settings).then(crate::commands::get_settings),
+        sharing: has(Slice::Sharing).then(sharing_state),
+        service_running: has(Slice::Service).then(crate::commands::is_service_running),
+        self_info: has(Slice::SelfInfo).then(|| crate::commands::get_self_info().ok()).flatten(),
+    }
+}
+
+fn sharing_state() -> SharingState {
+    let manager = crate::streaming::get_streaming_manager();
+    let manager = manager.read();
+    let streaming = manager.as_ref().filter(|m| m.is_streaming());
+    SharingState {
+        is_sharing: crate::commands::is_sharing(),
+        display_ids: streaming.map(|m| m.display_ids()).unwrap_or_default(),
+        paused: streaming.is_some_and(|m| m.is_frozen()),
+        simple_sharing: crate::simple_streaming::is_simple_sharing(),
+    }
+}
@@ -0,0 +1,220 @@
+//! Archive mode
+//!
+//! Saves a JPEG of the shared screen every few seconds while viewers are
+//! watching, as a visual record of what was presented. Snapshots are taken
+//! after redaction, cursor drawing and cropping, so they show exactly what
+//! viewers saw. Each share gets its own folder under the archive directory;
+//! the oldest snapshots are deleted once the archive exceeds its count or
+//! age limit. Snapshots can also be indexed for text search (see `ocr`).
+
+use super::convert::convert_data;
+use super::ocr;
+use super::{CapturedFrame, FrameFormat};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// JPEG quality of saved snapshots
+const JPEG_QUALITY: u8 = 80;
+
+/// How often snapshots are taken and how long they are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveSettings {
+    pub interval: Duration,
+    /// Most snapshots kept across all shares (0 = no limit)
+    pub max_snapshots: usize,
+    /// Snapshots older than this are deleted (`None` = kept forever)
+    pub max_age: Option<Duration>,
+    /// Index the text of each snapshot for search
+    pub ocr: bool,
+}
+
+/// Where snapshots of every share are stored
+pub fn archive_root() -> Option<PathBuf> {
+    dirs::picture_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|p| p.join("LAN Meeting").join("Archive"))
+}
+
+/// Snapshot writer for one shared source
+pub struct SnapshotArchive {
+    root: PathBuf,
+    dir: PathBuf,
+    settings: ArchiveSettings,
+    last_saved: Option<Instant>,
+    /// Set while a snapshot is being encoded on the blocking pool
+    saving: Arc<AtomicBool>,
+}
+
+impl SnapshotArchive {
+    /// Start archiving a share named `label` (e.g. "display-1"), if archive
+    /// mode is turned on
+    pub fn start(label: &str) -> Option<Self> {
+        let settings = crate::commands::archive_settings()?;
+        let root = archive_root()?;
+        let dir = root.join(format!("{}-{}", utc_stamp(now_secs()), label));
+        log::info!("Archiving snapshots every {:?} to {}", settings.interval, dir.display());
+        Some(Self {
+            root,
+            dir,
+            settings,
+            last_saved: None,
+            saving: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Offer a frame; saved in the background if a snapshot is due
+    pub fn offer(&mut self, frame: &CapturedFrame) {
+        let now = Instant::now();
+        let due = self.last_saved.is_none_or(|t| now.saturating_duration_since(t) >= self.settings.interval);
+        if !due || self.saving.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.last_saved = Some(now);
+
+        let rgba = convert_data(&frame.data, frame.width, frame.height, frame.format, FrameFormat::Rgba).into_owned();
+        let (width, height) = (frame.width, frame.height);
+        let captured_at = now_secs();
+        let path = self.dir.join(format!("{}.jpg", utc_stamp(captured_at)));
+        let root = self.root.clone();
+        let settings = self.settings;
+        let saving = self.saving.clone();
+        tokio::task::spawn_blocking(move || {
+            match save_jpeg(&path, rgba, width, height) {
+                Ok(()) => {
+                    log::debug!("Saved snapshot {}", path.display());
+                    if settings.ocr {
+                        if let Err(e) = ocr::index_snapshot(&root, &path, captured_at) {
+                            log::warn!("Failed to index snapshot {}: {}", path.display(), e);
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Failed to save snapshot {}: {}", path.display(), e),
+            }
+            prune(&root, &settings);
+            saving.store(false, Ordering::SeqCst);
+        });
+    }
+}
+
+fn save_jpeg(path: &Path, rgba: Vec<u8>, width: u32, height: u32) -> Result<(), String> {
+    use image::codecs::jpeg::JpegEncoder;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let image = image::RgbaImage::from_raw(width, height, rgba).ok_or("frame size mismatch")?;
+    let rgb = image::DynamicImage::ImageRgba8(image).into_rgb8();
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    JpegEncoder::new_with_quality(std::io::BufWriter::new(file), JPEG_QUALITY)
+        .encode_image(&rgb)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete snapshots beyond the archive's limits, and share folders left empty
+fn prune(root: &Path, settings: &ArchiveSettings) {
+    let Ok(shares) = std::fs::read_dir(root) else {
+        return;
+    };
+    let share_dirs: Vec<PathBuf> = shares.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect();
+    let snapshots: Vec<(PathBuf, SystemTime)> = share_dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "jpg"))
+        .filter_map(|e| Some((e.path(), e.metadata().ok()?.modified().ok()?)))
+        .collect();
+
+    let expired = expired(snapshots, SystemTime::now(), settings);
+    for path in &expired {
+        if let Err(e) = std::fs::remove_file(path) {
+            log::warn!("Failed to delete old snapshot {}: {}", path.display(), e);
+        }
+    }
+    if !expired.is_empty() {
+        ocr::forget_missing(root);
+    }
+    for dir in share_dirs {
+        // Only succeeds if the folder is empty
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+/// Snapshots to delete: too old, or the oldest beyond the count limit
+fn expired(mut snapshots: Vec<(PathBuf, SystemTime)>, now: SystemTime, settings: &ArchiveSettings) -> Vec<PathBuf> {
+    snapshots.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+    snapshots
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, modified))| {
+            let too_many = settings.max_snapshots > 0 && *i >= settings.max_snapshots;
+            let too_old = settings
+                .max_age
+                .is_some_and(|age| now.duration_since(*modified).unwrap_or_default() > age);
+            too_many || too_old
+        })
+        .map(|(_, (path, _))| path)
+        .collect()
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// UTC time as `YYYYMMDD-HHMMSSZ`, sortable and safe in file names
+pub(crate) fn utc_stamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_stamp() {
+        assert_eq!(utc_stamp(0), "19700101-000000Z");
+        assert_eq!(utc_stamp(951_782_400), "20000229-000000Z");
+        assert_eq!(utc_stamp(1_792_154_705), "20261016-124505Z");
+    }
+
+    #[test]
+    fn test_expired_by_count_and_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(10_000);
+        let snap = |name: &str, age: u64| (PathBuf::from(name), now - Duration::from_secs(age));
+        let snapshots = vec![snap("c", 300), snap("a", 100), snap("d", 5000), snap("b", 200)];
+
+        let settings = ArchiveSettings {
+            interval: Duration::from_secs(60),
+            max_snapshots: 2,
+            max_age: None,
+            ocr: false,
+        };
+        assert_eq!(expired(snapshots.clone(), now, &settings), vec![PathBuf::from("c"), PathBuf::from("d")]);
+
+        let settings = ArchiveSettings { max_snapshots: 0, max_age: Some(Duration::from_secs(1000)), ..settings };
+        assert_eq!(expired(snapshots, now, &settings), vec![PathBuf::from("d")]);
+    }
+}
@@ -0,0 +1,441 @@
+//! Pixel format negotiation and conversion
+//!
+//! Capture backends deliver their native format (BGRA, RGBA or NV12) and the
+//! pipeline converts once, at the point where the next stage needs something
+//! else: BGRA for the CPU stages (redaction, cursor, crop, scaling) and the
+//! encoder's input format right before encoding.
+//!
+//! Luma and red/blue swaps use SSE2 on x86_64 and NEON on aarch64, with a
+//! scalar fallback for other targets and row tails. YUV uses the stream's
+//! color space (see `encoder::color`) with top-left chroma siting, matching
+//! the encoders' own conversion. NV12 from capture backends is BT.709
+//! limited range, so the backend is only asked for NV12 when the stream
+//! uses that too.
+
+use super::{pool, CapturedFrame, FrameFormat, ScreenCapture};
+use crate::encoder::color::{ColorSpace, RgbToYuv, YuvToRgb};
+use crate::encoder::threading::{self, BAND_ROWS};
+use crate::encoder::VideoEncoder;
+use rayon::prelude::*;
+use std::borrow::Cow;
+
+/// Color space of NV12 frames from capture backends (the macOS display
+/// stream default)
+pub const CAPTURE_NV12: ColorSpace = ColorSpace::BT709;
+
+/// Formats chosen for one capture → encoder pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatPlan {
+    /// Whether any CPU stage before the encoder works on BGRA pixels
+    pub needs_bgra: bool,
+    /// Format passed to the encoder
+    pub encoder: FrameFormat,
+    /// Color space BGRA frames are converted to NV12 with
+    pub color: ColorSpace,
+}
+
+impl FormatPlan {
+    /// Pick formats for a capture backend and encoder.
+    ///
+    /// The encoder gets NV12 whenever it accepts it (converting here is
+    /// cheaper than the encoder's scalar BGRA path). The backend is asked for
+    /// NV12 too when nothing needs BGRA in between and `color` is the one it
+    /// captures in; if it refuses, its native format is converted instead.
+    pub fn negotiate(
+        capture: &mut dyn ScreenCapture,
+        encoder: &dyn VideoEncoder,
+        needs_bgra: bool,
+        color: ColorSpace,
+    ) -> Self {
+        let encoder_format = if encoder.supports_nv12_input() {
+            FrameFormat::Nv12
+        } else {
+            FrameFormat::Bgra
+        };
+
+        let wanted = if needs_bgra || color != CAPTURE_NV12 {
+            FrameFormat::Bgra
+        } else {
+            encoder_format
+        };
+        let native = capture.set_output_format(wanted);
+
+        log::info!(
+            "Pixel formats: capture {:?}{}, encoder {:?} ({})",
+            wanted,
+            if native { "" } else { " (converted from native)" },
+            encoder_format,
+            color.name()
+        );
+
+        Self {
+            needs_bgra,
+            encoder: encoder_format,
+            color,
+        }
+    }
+
+    /// Bring a captured frame into the format the CPU stages expect
+    pub fn prepare(&self, frame: CapturedFrame) -> CapturedFrame {
+        if self.needs_bgra {
+            convert(frame, FrameFormat::Bgra)
+        } else {
+            frame
+        }
+    }
+
+    /// Convert pixel data to the encoder's input format
+    pub fn for_encoder<'a>(
+        &self,
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        format: FrameFormat,
+    ) -> Cow<'a, [u8]> {
+        let color = if format == FrameFormat::Nv12 { CAPTURE_NV12 } else { self.color };
+        convert_data_with(data, width, height, format, self.encoder, color)
+    }
+}
+
+/// Convert a frame to `target`, returning it unchanged if it already matches
+pub fn convert(mut frame: CapturedFrame, target: FrameFormat) -> CapturedFrame {
+    match (frame.format, target) {
+        (from, to) if from == to => frame,
+        (FrameFormat::Bgra, FrameFormat::Rgba) | (FrameFormat::Rgba, FrameFormat::Bgra) => {
+            swap_red_blue(&mut frame.data);
+            frame.format = target;
+            frame
+        }
+        (from, to) => {
+            let data = convert_data(&frame.data, frame.width, frame.height, from, to).into_owned();
+            pool::recycle(frame.data);
+            CapturedFrame {
+                width: frame.width,
+                height: frame.height,
+                timestamp: frame.timestamp,
+                data,
+                format: to,
+            }
+        }
+    }
+}
+
+/// Convert raw pixel data between formats (borrowed when no conversion is
+/// needed), with NV12 in the capture backends' color space
+pub fn convert_data(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    from: FrameFormat,
+    to: FrameFormat,
+) -> Cow<'_, [u8]> {
+    convert_data_with(data, width, height, from, to, CAPTURE_NV12)
+}
+
+/// Convert raw pixel data between formats, with NV12 in `color`
+pub fn convert_data_with(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    from: FrameFormat,
+    to: FrameFormat,
+    color: ColorSpace,
+) -> Cow<'_, [u8]> {
+    let (w, h) = (width as usize, height as usize);
+    let (to_yuv, to_rgb) = (color.rgb_to_yuv(), color.yuv_to_rgb());
+    match (from, to) {
+        (from, to) if from == to => Cow::Borrowed(data),
+        (FrameFormat::Bgra, FrameFormat::Rgba) | (FrameFormat::Rgba, FrameFormat::Bgra) => {
+            let mut out = pool::take_empty(data.len());
+            out.extend_from_slice(data);
+            swap_red_blue(&mut out);
+            Cow::Owned(out)
+        }
+        (FrameFormat::Bgra, FrameFormat::Nv12) => Cow::Owned(rgb_to_nv12(data, w, h, ChannelOrder::Bgra, &to_yuv)),
+        (FrameFormat::Rgba, FrameFormat::Nv12) => Cow::Owned(rgb_to_nv12(data, w, h, ChannelOrder::Rgba, &to_yuv)),
+        (FrameFormat::Nv12, FrameFormat::Bgra) => Cow::Owned(nv12_to_rgb(data, w, h, ChannelOrder::Bgra, &to_rgb)),
+        (FrameFormat::Nv12, FrameFormat::Rgba) => Cow::Owned(nv12_to_rgb(data, w, h, ChannelOrder::Rgba, &to_rgb)),
+        _ => unreachable!("all format pairs are covered above"),
+    }
+}
+
+/// Byte order of a packed 4-byte pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelOrder {
+    Bgra,
+    Rgba,
+}
+
+impl ChannelOrder {
+    /// (r, g, b) of a pixel
+    fn rgb(self, px: &[u8]) -> (i32, i32, i32) {
+        match self {
+            ChannelOrder::Bgra => (px[2] as i32, px[1] as i32, px[0] as i32),
+            ChannelOrder::Rgba => (px[0] as i32, px[1] as i32, px[2] as i32),
+        }
+    }
+}
+
+/// Swap the first and third byte of every 4-byte pixel (BGRA ↔ RGBA)
+fn swap_red_blue(data: &mut [u8]) {
+    let done = simd::swap_red_blue(data);
+    for px in data[done..].chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+}
+
+/// Packed 4-byte pixels → NV12 (Y plane, then interleaved CbCr rows)
+fn rgb_to_nv12(src: &[u8], w: usize, h: usize, order: ChannelOrder, to_yuv: &RgbToYuv) -> Vec<u8> {
+    let y_size = w * h;
+    let uv_row = (w / 2) * 2;
+    let mut out = pool::take(y_size + uv_row * (h / 2));
+    let (y_plane, uv_plane) = out.split_at_mut(y_size);
+    let stride = w * 4;
+
+    // Both planes in bands of rows on the frame pool
+    threading::in_frame_pool(|| {
+        y_plane
+            .par_chunks_exact_mut(w.max(1))
+            .with_min_len(BAND_ROWS)
+            .enumerate()
+            .for_each(|(row, dst)| {
+                let src_row = &src[row * stride..(row + 1) * stride];
+                let done = simd::luma_row(src_row, dst, order, to_yuv);
+                for (px, y) in src_row[done * 4..].chunks_exact(4).zip(&mut dst[done..]) {
+                    let (r, g, b) = order.rgb(px);
+                    *y = to_yuv.luma(r, g, b);
+                }
+            });
+
+        // Chroma from the top-left pixel of each 2x2 block
+        uv_plane
+            .par_chunks_exact_mut(uv_row.max(1))
+            .with_min_len(BAND_ROWS / 2)
+            .enumerate()
+            .for_each(|(by, dst)| {
+                let src_row = &src[by * 2 * stride..];
+                for (bx, uv) in dst.chunks_exact_mut(2).enumerate() {
+                    let (r, g, b) = order.rgb(&src_row[bx * 8..bx * 8 + 4]);
+                    (uv[0], uv[1]) = to_yuv.chroma(r, g, b);
+                }
+            });
+    });
+
+    out
+}
+
+/// NV12 → packed 4-byte pixels (opaque alpha)
+fn nv12_to_rgb(src: &[u8], w: usize, h: usize, order: ChannelOrder, to_rgb: &YuvToRgb) -> Vec<u8> {
+    let y_size = w * h;
+    let uv_row = (w / 2) * 2;
+    let mut out = pool::take(y_size * 4);
+
+    for row in 0..h {
+        // Odd trailing rows/columns reuse the last chroma sample
+        let uv_base = y_size + (row / 2).min((h / 2).saturating_sub(1)) * uv_row;
+        for col in 0..w {
+            let uv = uv_base + (col / 2).min((w / 2).saturating_sub(1)) * 2;
+            let u = src.get(uv).copied().unwrap_or(128);
+            let v = src.get(uv + 1).copied().unwrap_or(128);
+            let (r, g, b) = to_rgb.rgb(src[row * w + col], u, v);
+
+            let i = (row * w + col) * 4;
+            let px = match order {
+                ChannelOrder::Bgra => [b, g, r, 255],
+                ChannelOrder::Rgba => [r, g, b, 255],
+            };
+            out[i..i + 4].copy_from_slice(&px);
+        }
+    }
+
+    out
+}
+
+/// SIMD kernels. Each returns how many bytes/pixels it handled; the caller
+/// finishes the tail with scalar code.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use super::{ChannelOrder, RgbToYuv};
+    use std::arch::x86_64::*;
+
+    /// Returns the number of bytes processed
+    pub fn swap_red_blue(data: &mut [u8]) -> usize {
+        let blocks = data.len() / 16;
+        // SSE2 is part of the x86_64 baseline
+        unsafe {
+            let ga = _mm_set1_epi32(0xFF00FF00u32 as i32);
+            let rb = _mm_set1_epi32(0x00FF00FF);
+            for i in 0..blocks {
+                let ptr = data.as_mut_ptr().add(i * 16) as *mut __m128i;
+                let px = _mm_loadu_si128(ptr);
+                let keep = _mm_and_si128(px, ga);
+                let swap = _mm_and_si128(px, rb);
+                let swapped = _mm_or_si128(_mm_slli_epi32(swap, 16), _mm_srli_epi32(swap, 16));
+                _mm_storeu_si128(ptr, _mm_or_si128(keep, swapped));
+            }
+        }
+        blocks * 16
+    }
+
+    /// Returns the number of pixels processed
+    pub fn luma_row(src: &[u8], dst: &mut [u8], order: ChannelOrder, to_yuv: &RgbToYuv) -> usize {
+        let blocks = dst.len().min(src.len() / 4) / 16;
+        let (r_shift, b_shift) = match order {
+            ChannelOrder::Bgra => (16, 0),
+            ChannelOrder::Rgba => (0, 16),
+        };
+
+        unsafe {
+            let mask = _mm_set1_epi32(0xFF);
+            let r_count = _mm_cvtsi32_si128(r_shift);
+            let b_count = _mm_cvtsi32_si128(b_shift);
+            let cr = _mm_set1_epi32(to_yuv.y[0]);
+            let cg = _mm_set1_epi32(to_yuv.y[1]);
+            let cb = _mm_set1_epi32(to_yuv.y[2]);
+            let round = _mm_set1_epi32(128);
+            let offset = _mm_set1_epi32(to_yuv.y_offset);
+
+            for i in 0..blocks {
+                let mut lanes = [_mm_setzero_si128(); 4];
+                for (j, lane) in lanes.iter_mut().enumerate() {
+                    let px = _mm_loadu_si128(src.as_ptr().add((i * 16 + j * 4) * 4) as *const __m128i);
+                    let r = _mm_and_si128(_mm_srl_epi32(px, r_count), mask);
+                    let g = _mm_and_si128(_mm_srli_epi32(px, 8), mask);
+                    let b = _mm_and_si128(_mm_srl_epi32(px, b_count), mask);
+
+                    // Luma coefficients add up to at most 256, so the max sum
+                    // is 256 * 255 + 128, which fits the low 16 bits of each lane
+                    let sum = _mm_add_epi16(
+                        _mm_add_epi16(_mm_mullo_epi16(r, cr), _mm_mullo_epi16(g, cg)),
+                        _mm_add_epi16(_mm_mullo_epi16(b, cb), round),
+                    );
+                    *lane = _mm_add_epi32(_mm_srli_epi32(sum, 8), offset);
+                }
+
+                let lo = _mm_packs_epi32(lanes[0], lanes[1]);
+                let hi = _mm_packs_epi32(lanes[2], lanes[3]);
+                _mm_storeu_si128(
+                    dst.as_mut_ptr().add(i * 16) as *mut __m128i,
+                    _mm_packus_epi16(lo, hi),
+                );
+            }
+        }
+
+        blocks * 16
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod simd {
+    use super::{ChannelOrder, RgbToYuv};
+    use std::arch::aarch64::*;
+
+    /// Returns the number of bytes processed
+    pub fn swap_red_blue(data: &mut [u8]) -> usize {
+        let blocks = data.len() / 64;
+        // NEON is part of the aarch64 baseline
+        unsafe {
+            for i in 0..blocks {
+                let ptr = data.as_mut_ptr().add(i * 64);
+                let px = vld4q_u8(ptr);
+                vst4q_u8(ptr, uint8x16x4_t(px.2, px.1, px.0, px.3));
+            }
+        }
+        blocks * 64
+    }
+
+    /// Returns the number of pixels processed
+    pub fn luma_row(src: &[u8], dst: &mut [u8], order: ChannelOrder, to_yuv: &RgbToYuv) -> usize {
+        let blocks = dst.len().min(src.len() / 4) / 8;
+        let [cr, cg, cb] = to_yuv.y.map(|c| c as u8);
+
+        unsafe {
+            for i in 0..blocks {
+                let px = vld4_u8(src.as_ptr().add(i * 32));
+                let (r, b) = match order {
+                    ChannelOrder::Bgra => (px.2, px.0),
+                    ChannelOrder::Rgba => (px.0, px.2),
+                };
+                let mut acc = vmull_u8(r, vdup_n_u8(cr));
+                acc = vmlal_u8(acc, px.1, vdup_n_u8(cg));
+                acc = vmlal_u8(acc, b, vdup_n_u8(cb));
+                acc = vaddq_u16(acc, vdupq_n_u16(128));
+                let y = vadd_u8(vshrn_n_u16::<8>(acc), vdup_n_u8(to_yuv.y_offset as u8));
+                vst1_u8(dst.as_mut_ptr().add(i * 8), y);
+            }
+        }
+
+        blocks * 8
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd {
+    use super::{ChannelOrder, RgbToYuv};
+
+    pub fn swap_red_blue(_data: &mut [u8]) -> usize {
+        0
+    }
+
+    pub fn luma_row(_src: &[u8], _dst: &mut [u8], _order: ChannelOrder, _to_yuv: &RgbToYuv) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32, data: Vec<u8>, format: FrameFormat) -> CapturedFrame {
+        CapturedFrame {
+            width,
+            height,
+            timestamp: 0,
+            data,
+            format,
+        }
+    }
+
+    #[test]
+    fn test_swap_red_blue_round_trip() {
+        // 37 pixels: exercises the SIMD blocks and the scalar tail
+        let data: Vec<u8> = (0..37u8).flat_map(|i| [i, 100, 200, 255]).collect();
+        let rgba = convert(frame(37, 1, data.clone(), FrameFormat::Bgra), FrameFormat::Rgba);
+        assert_eq!(&rgba.data[..4], &[200, 100, 0, 255]);
+        assert_eq!(&rgba.data[36 * 4..], &[200, 100, 36, 255]);
+
+        let bgra = convert(rgba, FrameFormat::Bgra);
+        assert_eq!(bgra.data, data);
+    }
+
+    #[test]
+    fn test_simd_luma_matches_scalar() {
+        // 34x2 gradient: SIMD handles whole blocks, scalar the 2-pixel tail
+        let (w, h) = (34, 2);
+        let data: Vec<u8> = (0..w * h)
+            .flat_map(|i| [(i * 7) as u8, (i * 13) as u8, (i * 29) as u8, 255])
+            .collect();
+        for color in ColorSpace::ALL {
+            let nv12 = convert_data_with(&data, w as u32, h as u32, FrameFormat::Bgra, FrameFormat::Nv12, color);
+            let to_yuv = color.rgb_to_yuv();
+            for (i, px) in data.chunks_exact(4).enumerate() {
+                let (r, g, b) = ChannelOrder::Bgra.rgb(px);
+                assert_eq!(nv12[i], to_yuv.luma(r, g, b), "{} pixel {}", color.name(), i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nv12_round_trip_grey() {
+        let data = [128u8, 128, 128, 255].repeat(4 * 2);
+        let nv12 = convert(frame(4, 2, data, FrameFormat::Bgra), FrameFormat::Nv12);
+        assert_eq!(nv12.data.len(), 4 * 2 * 3 / 2);
+        assert_eq!(&nv12.data[8..], &[128, 128, 128, 128]);
+
+        let back = convert(nv12, FrameFormat::Bgra);
+        for px in back.data.chunks_exact(4) {
+            assert!(px[..3].iter().all(|&c| (127..=129).contains(&c)), "{:?}", px);
+            assert_eq!(px[3], 255);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Cursor compositing
+//!
+//! The capture APIs we use return the desktop without the mouse pointer.
+//! Backends report the pointer position instead, and a standard arrow
+//! sprite is drawn into the frame before encoding, or viewers draw it
+//! themselves from the position sent alongside the video.
+
+use super::{CapturedFrame, FrameFormat};
+
+/// Arrow sprite: 'X' = outline, '.' = fill, ' ' = transparent
+pub(crate) const ARROW: [&str; 19] = [
+    "X           ",
+    "XX          ",
+    "X.X         ",
+    "X..X        ",
+    "X...X       ",
+    "X....X      ",
+    "X.....X     ",
+    "X......X    ",
+    "X.......X   ",
+    "X........X  ",
+    "X.........X ",
+    "X......XXXXX",
+    "X...X..X    ",
+    "X..XX..X    ",
+    "X.X  X..X   ",
+    "XX   X..X   ",
+    "X     X..X  ",
+    "      X..X  ",
+    "       XX   ",
+];
+
+/// Draws the pointer into captured frames
+pub struct CursorOverlay {
+    /// Integer sprite scale (2 on Retina displays)
+    scale: usize,
+}
+
+impl CursorOverlay {
+    pub fn new(scale_factor: f32) -> Self {
+        Self {
+            scale: (scale_factor.round() as usize).max(1),
+        }
+    }
+
+    /// Draw the arrow with its hotspot at (x, y) in frame pixels (BGRA/RGBA frames only)
+    pub fn draw(&self, frame: &mut CapturedFrame, x: i32, y: i32) {
+        if matches!(frame.format, FrameFormat::Nv12) {
+            return;
+        }
+
+        let width = frame.width as i64;
+        let height = frame.height as i64;
+        let stride = frame.width as usize * 4;
+
+        for (row, line) in ARROW.iter().enumerate() {
+            for (col, ch) in line.bytes().enumerate() {
+                let color = match ch {
+                    b'X' => [0u8, 0, 0, 255],
+                    b'.' => [255u8, 255, 255, 255],
+                    _ => continue,
+                };
+
+                for dy in 0..self.scale {
+                    for dx in 0..self.scale {
+                        let px = x as i64 + (col * self.scale + dx) as i64;
+                        let py = y as i64 + (row * self.scale + dy) as i64;
+                        if px < 0 || py < 0 || px >= width || py >= height {
+                            continue;
+                        }
+                        let i = py as usize * stride + px as usize * 4;
+                        frame.data[i..i + 4].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the primary mouse button is held, anywhere on the desktop. Always
+/// false where the platform cannot tell without a capture session (Linux).
+pub fn primary_button_down() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        #[link(name = "ApplicationServices", kind = "framework")]
+        unsafe extern "C" {
+            fn CGEventSourceButtonState(state_id: i32, button: u32) -> bool;
+        }
+        // kCGEventSourceStateCombinedSessionState, kCGMouseButtonLeft
+        unsafe { CGEventSourceButtonState(0, 0) }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON};
+        (unsafe { GetAsyncKeyState(VK_LBUTTON.0 as i32) }) < 0
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        false
+    }
+}
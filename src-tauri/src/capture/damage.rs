@@ -0,0 +1,254 @@
+//! Damage detection
+//!
+//! Compares each frame against the previous one tile by tile so that
+//! unchanged frames on a static desktop can be skipped instead of encoded.
+//! Backends that know the frame is unchanged (e.g. DXGI frame info) can
+//! short-circuit the comparison via `ScreenCapture::last_frame_changed`.
+//!
+//! This makes the stream's frame rate follow the content: while the screen
+//! is static nothing is encoded except a refresh keyframe now and then, and
+//! captures are polled less often, so an idle share costs almost no CPU or
+//! bandwidth.
+
+use super::{CapturedFrame, FrameFormat};
+use std::time::{Duration, Instant};
+
+/// Tile size in pixels
+const TILE_SIZE: usize = 64;
+
+/// Send a keyframe at least this often even if nothing changed, so viewers
+/// that lost data still converge
+pub const IDLE_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Unchanged frames in a row after which captures are polled more slowly
+const IDLE_AFTER_FRAMES: u32 = 15;
+
+/// Capture interval while the screen is static; bounds how late the first
+/// change after an idle spell is picked up
+const IDLE_CAPTURE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do with a captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameAction {
+    /// Unchanged: don't encode it
+    Skip,
+    /// Changed: encode it
+    Encode,
+    /// Unchanged, but a refresh is due: encode it as a keyframe
+    Refresh,
+}
+
+/// Result of comparing a frame with the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Damage {
+    pub changed_tiles: usize,
+    pub total_tiles: usize,
+}
+
+impl Damage {
+    pub fn is_empty(&self) -> bool {
+        self.changed_tiles == 0
+    }
+}
+
+/// Tracks per-tile hashes across frames
+pub struct DamageTracker {
+    width: u32,
+    height: u32,
+    tile_hashes: Vec<u64>,
+    last_sent: Option<Instant>,
+    /// Unchanged frames since the last change
+    idle_frames: u32,
+}
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            tile_hashes: Vec::new(),
+            last_sent: None,
+            idle_frames: 0,
+        }
+    }
+
+    /// Compare a packed 4-byte-per-pixel frame against the previous one
+    pub fn update(&mut self, frame: &CapturedFrame) -> Damage {
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tiles_y = height.div_ceil(TILE_SIZE);
+        let total_tiles = tiles_x * tiles_y;
+
+        // Planar formats are not tiled; treat every frame as changed
+        if matches!(frame.format, FrameFormat::Nv12) {
+            return Damage {
+                changed_tiles: total_tiles,
+                total_tiles,
+            };
+        }
+
+        // Size change (or first frame) counts as full damage
+        let resized = frame.width != self.width || frame.height != self.height;
+        if resized {
+            self.width = frame.width;
+            self.height = frame.height;
+            self.tile_hashes = vec![0; total_tiles];
+        }
+
+        let stride = width * 4;
+        let mut changed_tiles = 0;
+
+        for ty in 0..tiles_y {
+            for tx in 0..tiles_x {
+                let x0 = tx * TILE_SIZE * 4;
+                let x1 = ((tx + 1) * TILE_SIZE).min(width) * 4;
+                let y0 = ty * TILE_SIZE;
+                let y1 = ((ty + 1) * TILE_SIZE).min(height);
+
+                let mut hash = FNV_OFFSET;
+                for y in y0..y1 {
+                    let row = y * stride;
+                    hash = fnv1a(hash, &frame.data[row + x0..row + x1]);
+                }
+
+                let slot = &mut self.tile_hashes[ty * tiles_x + tx];
+                if resized || *slot != hash {
+                    *slot = hash;
+                    changed_tiles += 1;
+                }
+            }
+        }
+
+        Damage {
+            changed_tiles,
+            total_tiles,
+        }
+    }
+
+    /// Decide what to do with a frame with the given damage
+    pub fn decide(&mut self, damage: &Damage) -> FrameAction {
+        self.decide_at(damage, Instant::now())
+    }
+
+    fn decide_at(&mut self, damage: &Damage, now: Instant) -> FrameAction {
+        let Some(last_sent) = self.last_sent.filter(|_| damage.is_empty()) else {
+            self.last_sent = Some(now);
+            self.idle_frames = 0;
+            return FrameAction::Encode;
+        };
+
+        self.idle_frames = self.idle_frames.saturating_add(1);
+        if now.saturating_duration_since(last_sent) >= IDLE_REFRESH_INTERVAL {
+            self.last_sent = Some(now);
+            FrameAction::Refresh
+        } else {
+            FrameAction::Skip
+        }
+    }
+
+    /// Time to wait before the next capture: `interval` while the screen
+    /// changes, longer once it has been static for a while
+    pub fn capture_interval(&self, interval: Duration) -> Duration {
+        if self.idle_frames >= IDLE_AFTER_FRAMES {
+            interval.max(IDLE_CAPTURE_INTERVAL)
+        } else {
+            interval
+        }
+    }
+
+    /// Force full damage on the next frame (e.g. after an encoder reset)
+    pub fn reset(&mut self) {
+        self.width = 0;
+        self.height = 0;
+        self.tile_hashes.clear();
+        self.last_sent = None;
+        self.idle_frames = 0;
+    }
+}
+
+impl Default for DamageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a over 8-byte words (tail bytes folded in individually)
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        hash = (hash ^ word).wrapping_mul(FNV_PRIME);
+    }
+    for &b in chunks.remainder() {
+        hash = (hash ^ b as u64).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32, data: Vec<u8>) -> CapturedFrame {
+        CapturedFrame {
+            width,
+            height,
+            timestamp: 0,
+            data,
+            format: FrameFormat::Bgra,
+        }
+    }
+
+    #[test]
+    fn test_static_frame_has_no_damage() {
+        let mut tracker = DamageTracker::new();
+        let first = tracker.update(&frame(128, 128, vec![7; 128 * 128 * 4]));
+        assert_eq!(first.changed_tiles, 4);
+
+        let second = tracker.update(&frame(128, 128, vec![7; 128 * 128 * 4]));
+        assert!(second.is_empty());
+        assert_eq!(second.total_tiles, 4);
+    }
+
+    #[test]
+    fn test_single_pixel_change_damages_one_tile() {
+        let mut tracker = DamageTracker::new();
+        tracker.update(&frame(128, 128, vec![0; 128 * 128 * 4]));
+
+        let mut data = vec![0; 128 * 128 * 4];
+        // Pixel (100, 100) lives in the bottom-right tile
+        let i = (100 * 128 + 100) * 4;
+        data[i] = 255;
+        let damage = tracker.update(&frame(128, 128, data));
+        assert_eq!(damage.changed_tiles, 1);
+    }
+
+    #[test]
+    fn test_idle_frames_skipped_until_refresh() {
+        let mut tracker = DamageTracker::new();
+        let start = Instant::now();
+        let changed = Damage { changed_tiles: 1, total_tiles: 4 };
+        let unchanged = Damage { changed_tiles: 0, total_tiles: 4 };
+        let frame_interval = Duration::from_millis(33);
+
+        // The first frame always goes out
+        assert_eq!(tracker.decide_at(&unchanged, start), FrameAction::Encode);
+        assert_eq!(tracker.decide_at(&changed, start), FrameAction::Encode);
+
+        for i in 1..=IDLE_AFTER_FRAMES {
+            assert_eq!(tracker.decide_at(&unchanged, start + frame_interval * i), FrameAction::Skip);
+        }
+        assert_eq!(tracker.capture_interval(frame_interval), IDLE_CAPTURE_INTERVAL);
+
+        assert_eq!(tracker.decide_at(&unchanged, start + IDLE_REFRESH_INTERVAL), FrameAction::Refresh);
+        assert_eq!(tracker.capture_interval(frame_interval), IDLE_CAPTURE_INTERVAL);
+
+        // A change brings back the full frame rate
+        assert_eq!(tracker.decide_at(&changed, start + IDLE_REFRESH_INTERVAL), FrameAction::Encode);
+        assert_eq!(tracker.capture_interval(frame_interval), frame_interval);
+    }
+}
@@ -0,0 +1,89 @@
+//! Capture exclusions
+//!
+//! Apps whose windows are left out of the shared stream: LAN Meeting itself
+//! (so viewers do not see their own stream mirrored back) and password
+//! managers. Only backends that can filter content honour the list; today
+//! that is ScreenCaptureKit on macOS.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Password managers hidden by default
+pub const DEFAULT_EXCLUDED_APPS: &[&str] = &[
+    "com.1password.1password",
+    "com.agilebits.onepassword7",
+    "com.bitwarden.desktop",
+    "com.apple.keychainaccess",
+    "com.apple.Passwords",
+];
+
+/// Apps to hide from capture
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureExclusions {
+    /// Hide LAN Meeting's own windows
+    pub exclude_self: bool,
+    /// Bundle identifiers of other apps to hide
+    pub bundle_ids: Vec<String>,
+}
+
+impl Default for CaptureExclusions {
+    fn default() -> Self {
+        Self {
+            exclude_self: true,
+            bundle_ids: DEFAULT_EXCLUDED_APPS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl CaptureExclusions {
+    /// Whether an app with this process id and bundle identifier is hidden
+    pub fn excludes(&self, pid: i32, bundle_id: &str) -> bool {
+        if self.exclude_self && pid == std::process::id() as i32 {
+            return true;
+        }
+        // Bundle identifiers are case-insensitive
+        self.bundle_ids.iter().any(|id| id.eq_ignore_ascii_case(bundle_id))
+    }
+}
+
+static EXCLUSIONS: once_cell::sync::Lazy<RwLock<CaptureExclusions>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(CaptureExclusions::default()));
+
+/// Bumped on every change so running captures can update their filter
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// Current exclusions
+pub fn get() -> CaptureExclusions {
+    EXCLUSIONS.read().clone()
+}
+
+/// Replace the exclusions. Running captures pick them up on the next frame.
+pub fn set(exclusions: CaptureExclusions) {
+    *EXCLUSIONS.write() = exclusions;
+    VERSION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Changes every time the exclusions are set
+pub fn version() -> u64 {
+    VERSION.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_excludes_self_and_password_managers() {
+        let exclusions = CaptureExclusions::default();
+        assert!(exclusions.excludes(std::process::id() as i32, "com.example.whatever"));
+        assert!(exclusions.excludes(1, "com.1Password.1password"));
+        assert!(!exclusions.excludes(1, "com.apple.Safari"));
+    }
+
+    #[test]
+    fn test_self_can_be_shown() {
+        let exclusions = CaptureExclusions { exclude_self: false, bundle_ids: Vec::new() };
+        assert!(!exclusions.excludes(std::process::id() as i32, "com.lan-meeting.app"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Foreground window title
+//!
+//! While a whole display is shared, the title of the window in front is
+//! sent to viewers as metadata so their screen readers can announce when
+//! the sharer switches to another window. On macOS only the application
+//! name is available without the accessibility permission.
+
+/// Longest title sent to viewers, in characters
+const MAX_TITLE_CHARS: usize = 200;
+
+/// Title of the window that has the focus, if it can be found
+pub fn foreground_window_title() -> Option<String> {
+    clean_title(&platform_title()?)
+}
+
+fn clean_title(title: &str) -> Option<String> {
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.chars().take(MAX_TITLE_CHARS).collect())
+}
+
+#[cfg(target_os = "windows")]
+fn platform_title() -> Option<String> {
+    use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW};
+
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_invalid() {
+            return None;
+        }
+        let mut buf = [0u16; 512];
+        let len = GetWindowTextW(hwnd, &mut buf);
+        (len > 0).then(|| String::from_utf16_lossy(&buf[..len as usize]))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn platform_title() -> Option<String> {
+    use objc2::msg_send;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::NSString;
+
+    objc2::rc::autoreleasepool(|_| unsafe {
+        let workspace: *mut AnyObject = msg_send![AnyClass::get(c"NSWorkspace")?, sharedWorkspace];
+        let app: *mut AnyObject = msg_send![workspace.as_ref()?, frontmostApplication];
+        let name: *mut NSString = msg_send![app.as_ref()?, localizedName];
+        name.as_ref().map(|name| name.to_string())
+    })
+}
+
+#[cfg(all(target_os = "linux", feature = "x11"))]
+fn platform_title() -> Option<String> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let (conn, screen_num) = x11rb::connect(None).ok()?;
+    let root = conn.setup().roots.get(screen_num)?.root;
+    let atom = |name: &[u8]| Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom);
+    let (active, wm_name, utf8) = (atom(b"_NET_ACTIVE_WINDOW")?, atom(b"_NET_WM_NAME")?, atom(b"UTF8_STRING")?);
+
+    let window = conn
+        .get_property(false, root, active, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()?;
+    let name = conn.get_property(false, window, wm_name, utf8, 0, 1024).ok()?.reply().ok()?;
+    String::from_utf8(name.value).ok()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", all(target_os = "linux", feature = "x11"))))]
+fn platform_title() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_title() {
+        assert_eq!(clean_title("  main.rs - Editor \n"), Some("main.rs - Editor".to_string()));
+        assert_eq!(clean_title("   "), None);
+        assert_eq!(clean_title(&"标".repeat(300)).map(|t| t.chars().count()), Some(MAX_TITLE_CHARS));
+    }
+}
@@ -156,6 +156,22 @@ impl LinuxCapture {
         })
     }
 
+    /// Query the pointer position on the captured X11 root window
+    #[cfg(feature = "x11")]
+    fn query_x11_pointer(&self) -> Option<(i32, i32)> {
+        use x11rb::protocol::xproto::ConnectionExt;
+
+        let state_guard = self.x11_state.read();
+        let state = state_guard.as_ref()?;
+
+        let reply = state.conn.query_pointer(state.root).ok()?.reply().ok()?;
+        if !reply.same_screen {
+            return None;
+        }
+
+        Some((reply.root_x as i32, reply.root_y as i32))
+    }
+
     /// Enumerate displays on X11
     #[cfg(feature = "x11")]
     fn enumerate_x11_displays(&self) -> Result<Vec<Display>, CaptureError> {
@@ -307,6 +323,14 @@ impl ScreenCapture for LinuxCapture {
     fn is_capturing(&self) -> bool {
         self.is_capturing.load(Ordering::SeqCst)
     }
+
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        match self.backend {
+            #[cfg(feature = "x11")]
+            LinuxBackend::X11 => self.query_x11_pointer(),
+            _ => None,
+        }
+    }
 }
 
 impl Default for LinuxCapture {
@@ -0,0 +1,733 @@
+// macOS screen capture using CoreGraphics
+// Uses CGDisplayCreateImage for reliable cross-version compatibility
+// GPU frames come from a CGDisplayStream, which delivers IOSurfaces without a CPU copy
+// On macOS 12.3+ the ScreenCaptureKit backend in `sck` is preferred
+
+pub mod sck;
+
+use super::{pool, CaptureError, CapturedFrame, Display, FrameFormat, GpuFrame, GpuSurface, ScreenCapture};
+use block2::RcBlock;
+use core_graphics::display::{CGDirectDisplayID, CGDisplay, CGMainDisplayID};
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// External C functions for screen capture
+unsafe extern "C" {
+    fn CGPreflightScreenCaptureAccess() -> bool;
+    fn CGRequestScreenCaptureAccess() -> bool;
+    fn CGGetActiveDisplayList(
+        max_displays: u32,
+        active_displays: *mut CGDirectDisplayID,
+        display_count: *mut u32,
+    ) -> i32;
+    fn CGDisplayCreateImage(display: CGDirectDisplayID)
+        -> *mut core_foundation::base::CFTypeRef;
+
+    fn CGDisplayStreamCreateWithDispatchQueue(
+        display: CGDirectDisplayID,
+        output_width: usize,
+        output_height: usize,
+        pixel_format: i32,
+        properties: *const c_void,
+        queue: *mut c_void,
+        handler: *const c_void,
+    ) -> *mut c_void;
+    fn CGDisplayStreamStart(stream: *mut c_void) -> i32;
+    fn CGDisplayStreamStop(stream: *mut c_void) -> i32;
+
+    fn CVPixelBufferCreateWithIOSurface(
+        allocator: *const c_void,
+        surface: *mut c_void,
+        attributes: *const c_void,
+        pixel_buffer_out: *mut *mut c_void,
+    ) -> i32;
+
+    fn dispatch_queue_create(label: *const std::ffi::c_char, attr: *const c_void) -> *mut c_void;
+    fn dispatch_release(object: *mut c_void);
+
+    fn CFRetain(cf: *const c_void) -> *const c_void;
+    fn CFRelease(cf: *const c_void);
+}
+
+#[link(name = "IOSurface", kind = "framework")]
+unsafe extern "C" {
+    fn IOSurfaceLock(surface: *mut c_void, options: u32, seed: *mut u32) -> i32;
+    fn IOSurfaceUnlock(surface: *mut c_void, options: u32, seed: *mut u32) -> i32;
+    fn IOSurfaceGetBaseAddressOfPlane(surface: *mut c_void, plane: usize) -> *mut c_void;
+    fn IOSurfaceGetBytesPerRowOfPlane(surface: *mut c_void, plane: usize) -> usize;
+    fn IOSurfaceGetBaseAddress(surface: *mut c_void) -> *mut c_void;
+    fn IOSurfaceGetBytesPerRow(surface: *mut c_void) -> usize;
+}
+
+/// kIOSurfaceLockReadOnly
+const IOSURFACE_LOCK_READ_ONLY: u32 = 1;
+
+/// 'BGRA' FourCC for CGDisplayStream output
+const PIXEL_FORMAT_BGRA: i32 = 0x42475241;
+
+/// '420v' FourCC (bi-planar Y + interleaved CbCr, video range), i.e. NV12
+const PIXEL_FORMAT_NV12: i32 = 0x34323076;
+
+/// kCGDisplayStreamFrameStatusFrameComplete
+const FRAME_STATUS_COMPLETE: i32 = 0;
+
+/// How long capture_gpu_frame waits for a new surface before reusing the last one
+const GPU_FRAME_WAIT: Duration = Duration::from_millis(100);
+
+/// Retained CVPixelBufferRef backed by an IOSurface. Released on drop.
+pub struct PixelBufferHandle(*mut c_void);
+
+impl PixelBufferHandle {
+    /// Raw CVPixelBufferRef (still owned by this handle)
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Drop for PixelBufferHandle {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0) };
+    }
+}
+
+/// Retained IOSurfaceRef shared between the stream callback and capture
+struct SurfaceRef(*mut c_void);
+
+// IOSurfaces are reference counted and safe to pass between threads
+unsafe impl Send for SurfaceRef {}
+
+impl Clone for SurfaceRef {
+    fn clone(&self) -> Self {
+        unsafe { CFRetain(self.0) };
+        Self(self.0)
+    }
+}
+
+impl Drop for SurfaceRef {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.0) };
+    }
+}
+
+/// Latest surface delivered by the display stream
+#[derive(Default)]
+struct LatestSurface {
+    surface: Mutex<(Option<SurfaceRef>, bool)>,
+    updated: Condvar,
+}
+
+impl LatestSurface {
+    /// Store a surface from the stream callback. Takes ownership of one retain.
+    fn publish(&self, surface: SurfaceRef) {
+        let mut slot = self.surface.lock();
+        *slot = (Some(surface), true);
+        self.updated.notify_one();
+    }
+
+    /// Wait briefly for a new surface, returning it and whether it is new
+    fn next(&self) -> Result<(SurfaceRef, bool), CaptureError> {
+        let mut slot = self.surface.lock();
+        if !slot.1 {
+            self.updated.wait_for(&mut slot, GPU_FRAME_WAIT);
+        }
+        let changed = std::mem::replace(&mut slot.1, false);
+        let surface = slot.0.clone()
+            .ok_or(CaptureError::Timeout)?;
+        Ok((surface, changed))
+    }
+}
+
+/// Running CGDisplayStream and the resources it needs to stay alive
+struct DisplayStream {
+    stream: *mut c_void,
+    queue: *mut c_void,
+    _handler: RcBlock<dyn Fn(i32, u64, *mut c_void, *const c_void)>,
+    latest: Arc<LatestSurface>,
+    format: FrameFormat,
+    width: u32,
+    height: u32,
+}
+
+impl DisplayStream {
+    /// Wait briefly for a new surface. The stream only delivers surfaces when
+    /// the screen changes; on a static desktop the last surface is reused and
+    /// reported as unchanged.
+    fn next_surface(&self) -> Result<(SurfaceRef, bool), CaptureError> {
+        self.latest.next()
+    }
+}
+
+impl Drop for DisplayStream {
+    fn drop(&mut self) {
+        unsafe {
+            CGDisplayStreamStop(self.stream);
+            CFRelease(self.stream);
+            dispatch_release(self.queue);
+        }
+    }
+}
+
+/// macOS screen capture implementation using CoreGraphics
+pub struct MacOSCapture {
+    is_capturing: AtomicBool,
+    current_display: RwLock<Option<u32>>,
+    cached_displays: RwLock<Vec<Display>>,
+    display_stream: Mutex<Option<DisplayStream>>,
+    gpu_frame_changed: RwLock<Option<bool>>,
+    /// Format requested via set_output_format (BGRA uses CGDisplayCreateImage)
+    output_format: FrameFormat,
+}
+
+// Manual Send + Sync implementation since we only use thread-safe primitives
+unsafe impl Send for MacOSCapture {}
+unsafe impl Sync for MacOSCapture {}
+
+impl MacOSCapture {
+    pub fn new() -> Result<Self, CaptureError> {
+        // Check for screen recording permission
+        if !Self::has_permission() {
+            log::warn!("Screen recording permission not granted, will request on first capture");
+        }
+
+        Ok(Self {
+            is_capturing: AtomicBool::new(false),
+            current_display: RwLock::new(None),
+            cached_displays: RwLock::new(Vec::new()),
+            display_stream: Mutex::new(None),
+            gpu_frame_changed: RwLock::new(None),
+            output_format: FrameFormat::Bgra,
+        })
+    }
+
+    /// Start a CGDisplayStream that keeps the latest IOSurface of the display
+    fn start_display_stream(display_id: u32, format: FrameFormat) -> Result<DisplayStream, CaptureError> {
+        let cg_display = CGDisplay::new(display_id);
+        let width = cg_display.pixels_wide() as u32;
+        let height = cg_display.pixels_high() as u32;
+
+        let latest = Arc::new(LatestSurface::default());
+        let latest_cb = latest.clone();
+        let handler = RcBlock::new(
+            move |status: i32, _time: u64, surface: *mut c_void, _update: *const c_void| {
+                if status != FRAME_STATUS_COMPLETE || surface.is_null() {
+                    return;
+                }
+                unsafe { CFRetain(surface) };
+                latest_cb.publish(SurfaceRef(surface));
+            },
+        );
+
+        unsafe {
+            let queue = dispatch_queue_create(c"lan-meeting.display-stream".as_ptr(), std::ptr::null());
+            let stream = CGDisplayStreamCreateWithDispatchQueue(
+                display_id,
+                width as usize,
+                height as usize,
+                match format {
+                    FrameFormat::Nv12 => PIXEL_FORMAT_NV12,
+                    _ => PIXEL_FORMAT_BGRA,
+                },
+                std::ptr::null(),
+                queue,
+                &*handler as *const _ as *const c_void,
+            );
+            if stream.is_null() {
+                dispatch_release(queue);
+                return Err(CaptureError::InitError(
+                    "CGDisplayStreamCreateWithDispatchQueue returned null".to_string(),
+                ));
+            }
+
+            let display_stream = DisplayStream {
+                stream,
+                queue,
+                _handler: handler,
+                latest,
+                format,
+                width,
+                height,
+            };
+            let result = CGDisplayStreamStart(stream);
+            if result != 0 {
+                return Err(CaptureError::InitError(format!(
+                    "CGDisplayStreamStart failed with code: {}",
+                    result
+                )));
+            }
+
+            log::info!(
+                "Started CGDisplayStream for display {} ({}x{}, {:?})",
+                display_id, width, height, format
+            );
+            Ok(display_stream)
+        }
+    }
+
+    /// Get the display stream for the current display, (re)starting it if the
+    /// requested pixel format changed
+    fn display_stream(
+        &self,
+        format: FrameFormat,
+    ) -> Result<parking_lot::MappedMutexGuard<'_, DisplayStream>, CaptureError> {
+        let display_id = self
+            .current_display
+            .read()
+            .ok_or_else(|| CaptureError::CaptureError("No display selected".to_string()))?;
+
+        let mut guard = self.display_stream.lock();
+        if guard.as_ref().is_none_or(|s| s.format != format) {
+            *guard = None;
+            *guard = Some(Self::start_display_stream(display_id, format)?);
+        }
+        Ok(parking_lot::MutexGuard::map(guard, |s| s.as_mut().expect("display stream started")))
+    }
+
+    /// Copy an NV12 IOSurface into a packed frame (Y plane, then interleaved CbCr)
+    fn copy_nv12_surface(surface: &SurfaceRef, width: u32, height: u32) -> Result<Vec<u8>, CaptureError> {
+        let w = width as usize;
+        let h = height as usize;
+        let mut data = pool::take_empty(w * h * 3 / 2);
+
+        unsafe {
+            let result = IOSurfaceLock(surface.0, IOSURFACE_LOCK_READ_ONLY, std::ptr::null_mut());
+            if result != 0 {
+                return Err(CaptureError::CaptureError(format!(
+                    "IOSurfaceLock failed with code: {}",
+                    result
+                )));
+            }
+
+            // Plane 0: Y at full resolution, plane 1: CbCr pairs at half height
+            for (plane, rows) in [(0usize, h), (1usize, h / 2)] {
+                let base = IOSurfaceGetBaseAddressOfPlane(surface.0, plane) as *const u8;
+                let stride = IOSurfaceGetBytesPerRowOfPlane(surface.0, plane);
+                for row in 0..rows {
+                    data.extend_from_slice(std::slice::from_raw_parts(base.add(row * stride), w));
+                }
+            }
+
+            IOSurfaceUnlock(surface.0, IOSURFACE_LOCK_READ_ONLY, std::ptr::null_mut());
+        }
+
+        Ok(data)
+    }
+
+    /// Copy a BGRA IOSurface into a packed frame, dropping row padding
+    fn copy_bgra_surface(surface: &SurfaceRef, width: u32, height: u32) -> Result<Vec<u8>, CaptureError> {
+        let row_bytes = width as usize * 4;
+        let mut data = pool::take_empty(row_bytes * height as usize);
+
+        unsafe {
+            let result = IOSurfaceLock(surface.0, IOSURFACE_LOCK_READ_ONLY, std::ptr::null_mut());
+            if result != 0 {
+                return Err(CaptureError::CaptureError(format!(
+                    "IOSurfaceLock failed with code: {}",
+                    result
+                )));
+            }
+
+            let base = IOSurfaceGetBaseAddress(surface.0) as *const u8;
+            let stride = IOSurfaceGetBytesPerRow(surface.0);
+            for row in 0..height as usize {
+                data.extend_from_slice(std::slice::from_raw_parts(base.add(row * stride), row_bytes));
+            }
+
+            IOSurfaceUnlock(surface.0, IOSURFACE_LOCK_READ_ONLY, std::ptr::null_mut());
+        }
+
+        Ok(data)
+    }
+
+    /// Wrap a surface in a CVPixelBuffer for the VideoToolbox encoder
+    fn pixel_buffer(surface: &SurfaceRef) -> Result<PixelBufferHandle, CaptureError> {
+        let mut pixel_buffer: *mut c_void = std::ptr::null_mut();
+        let result = unsafe {
+            CVPixelBufferCreateWithIOSurface(std::ptr::null(), surface.0, std::ptr::null(), &mut pixel_buffer)
+        };
+        if result != 0 || pixel_buffer.is_null() {
+            return Err(CaptureError::CaptureError(format!(
+                "CVPixelBufferCreateWithIOSurface failed with code: {}",
+                result
+            )));
+        }
+        Ok(PixelBufferHandle(pixel_buffer))
+    }
+
+    /// Pointer position in pixels relative to a display, if it is on it
+    fn display_cursor_position(display_id: u32) -> Option<(i32, i32)> {
+        use core_graphics::event::CGEvent;
+        use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+        // CGEvent location is in global points; convert to display pixels
+        let source = CGEventSource::new(CGEventSourceStateID::CombinedSessionState).ok()?;
+        let location = CGEvent::new(source).ok()?.location();
+
+        let cg_display = CGDisplay::new(display_id);
+        let bounds = cg_display.bounds();
+        if bounds.size.width <= 0.0 {
+            return None;
+        }
+        let scale = cg_display.pixels_wide() as f64 / bounds.size.width;
+
+        let x = location.x - bounds.origin.x;
+        let y = location.y - bounds.origin.y;
+        if x < 0.0 || y < 0.0 || x >= bounds.size.width || y >= bounds.size.height {
+            return None;
+        }
+
+        Some(((x * scale) as i32, (y * scale) as i32))
+    }
+
+    /// Check if screen recording permission is granted
+    pub fn has_permission() -> bool {
+        unsafe { CGPreflightScreenCaptureAccess() }
+    }
+
+    /// Request screen recording permission (shows system dialog)
+    pub fn request_permission() -> bool {
+        unsafe { CGRequestScreenCaptureAccess() }
+    }
+
+    /// Check and request permission if needed
+    fn ensure_permission() -> Result<(), CaptureError> {
+        if !Self::has_permission() {
+            log::info!("Requesting screen recording permission...");
+            Self::request_permission();
+
+            // Give system time to show and process permission dialog
+            std::thread::sleep(std::time::Duration::from_millis(500));
+
+            if !Self::has_permission() {
+                return Err(CaptureError::PermissionDenied);
+            }
+        }
+        Ok(())
+    }
+
+    /// Enumerate all active displays using CoreGraphics
+    fn enumerate_displays() -> Result<Vec<Display>, CaptureError> {
+        let mut displays = Vec::new();
+
+        const MAX_DISPLAYS: u32 = 16;
+        let mut display_ids: [CGDirectDisplayID; 16] = [0; 16];
+        let mut display_count: u32 = 0;
+
+        unsafe {
+            let result = CGGetActiveDisplayList(
+                MAX_DISPLAYS,
+                display_ids.as_mut_ptr(),
+                &mut display_count,
+            );
+
+            if result != 0 {
+                return Err(CaptureError::InitError(format!(
+                    "CGGetActiveDisplayList failed with code: {}",
+                    result
+                )));
+            }
+        }
+
+        let main_display_id = unsafe { CGMainDisplayID() };
+
+        for i in 0..display_count as usize {
+            let display_id = display_ids[i];
+            let cg_display = CGDisplay::new(display_id);
+
+            let width = cg_display.pixels_wide() as u32;
+            let height = cg_display.pixels_high() as u32;
+
+            // Calculate scale factor for Retina displays
+            let bounds = cg_display.bounds();
+            let scale_factor = if bounds.size.width > 0.0 {
+                width as f32 / bounds.size.width as f32
+            } else {
+                1.0
+            };
+
+            let is_primary = display_id == main_display_id;
+
+            let name = if is_primary {
+                "主显示器".to_string()
+            } else {
+                format!("显示器 {}", i + 1)
+            };
+
+            displays.push(Display {
+                id: display_id,
+                name,
+                width,
+                height,
+                scale_factor,
+                primary: is_primary,
+            });
+        }
+
+        // Sort so primary display is first
+        displays.sort_by(|a, b| b.primary.cmp(&a.primary));
+
+        Ok(displays)
+    }
+
+    /// Explain why CGDisplayCreateImage returned null: permission revoked in
+    /// System Settings, the display was unplugged, or something transient
+    fn image_failure(display_id: u32) -> CaptureError {
+        if !Self::has_permission() {
+            return CaptureError::PermissionRevoked;
+        }
+        match Self::enumerate_displays() {
+            Ok(displays) if !displays.iter().any(|d| d.id == display_id) => {
+                CaptureError::DisplayRemoved(display_id)
+            }
+            _ => CaptureError::CaptureError("CGDisplayCreateImage returned null".to_string()),
+        }
+    }
+
+    /// Capture a single frame using CGDisplayCreateImage
+    fn capture_display(display_id: u32) -> Result<CapturedFrame, CaptureError> {
+        // Type aliases for C types
+        type CGImageRef = *const std::ffi::c_void;
+        type CGDataProviderRef = *const std::ffi::c_void;
+        type CFDataRef = *const std::ffi::c_void;
+
+        // Additional FFI declarations for CGImage operations
+        unsafe extern "C" {
+            fn CGImageGetWidth(image: CGImageRef) -> usize;
+            fn CGImageGetHeight(image: CGImageRef) -> usize;
+            fn CGImageGetBitsPerPixel(image: CGImageRef) -> usize;
+            fn CGImageGetDataProvider(image: CGImageRef) -> CGDataProviderRef;
+            fn CGDataProviderCopyData(provider: CGDataProviderRef) -> CFDataRef;
+            fn CFDataGetLength(data: CFDataRef) -> isize;
+            fn CFDataGetBytePtr(data: CFDataRef) -> *const u8;
+            fn CFRelease(cf: *const std::ffi::c_void);
+        }
+
+        unsafe {
+            let image_ref: CGImageRef = CGDisplayCreateImage(display_id) as CGImageRef;
+            if image_ref.is_null() {
+                return Err(Self::image_failure(display_id));
+            }
+
+            let width = CGImageGetWidth(image_ref) as u32;
+            let height = CGImageGetHeight(image_ref) as u32;
+            let bits_per_pixel = CGImageGetBitsPerPixel(image_ref);
+
+            // Get pixel data from the image
+            let data_provider = CGImageGetDataProvider(image_ref);
+            if data_provider.is_null() {
+                CFRelease(image_ref);
+                return Err(CaptureError::CaptureError(
+                    "Failed to get data provider".to_string(),
+                ));
+            }
+
+            let cf_data = CGDataProviderCopyData(data_provider);
+            if cf_data.is_null() {
+                CFRelease(image_ref);
+                return Err(CaptureError::CaptureError(
+                    "Failed to copy image data".to_string(),
+                ));
+            }
+
+            let data_len = CFDataGetLength(cf_data) as usize;
+            let data_ptr = CFDataGetBytePtr(cf_data);
+
+            let frame_data = if !data_ptr.is_null() && data_len > 0 {
+                let mut frame_data = pool::take_empty(data_len);
+                frame_data.extend_from_slice(std::slice::from_raw_parts(data_ptr, data_len));
+                frame_data
+            } else {
+                CFRelease(cf_data);
+                CFRelease(image_ref);
+                return Err(CaptureError::CaptureError(
+                    "Failed to get image bytes".to_string(),
+                ));
+            };
+
+            // Release CoreFoundation objects
+            CFRelease(cf_data);
+            CFRelease(image_ref);
+
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+
+            // Determine pixel format based on bits per pixel
+            let format = if bits_per_pixel == 32 {
+                // macOS typically uses BGRA
+                FrameFormat::Bgra
+            } else {
+                FrameFormat::Rgba
+            };
+
+            Ok(CapturedFrame {
+                width,
+                height,
+                timestamp,
+                data: frame_data,
+                format,
+            })
+        }
+    }
+}
+
+impl ScreenCapture for MacOSCapture {
+    fn get_displays(&self) -> Result<Vec<Display>, CaptureError> {
+        let displays = Self::enumerate_displays()?;
+        *self.cached_displays.write() = displays.clone();
+        Ok(displays)
+    }
+
+    fn start(&mut self, display_id: u32) -> Result<(), CaptureError> {
+        Self::ensure_permission()?;
+
+        // Verify display exists
+        let displays = Self::enumerate_displays()?;
+        if !displays.iter().any(|d| d.id == display_id) {
+            return Err(CaptureError::DisplayNotFound(display_id));
+        }
+
+        // Stop any existing capture
+        self.stop()?;
+
+        // Set the current display and mark as capturing
+        *self.current_display.write() = Some(display_id);
+        self.is_capturing.store(true, Ordering::SeqCst);
+
+        log::info!("Started macOS screen capture for display {}", display_id);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CaptureError> {
+        *self.display_stream.lock() = None;
+        *self.gpu_frame_changed.write() = None;
+        *self.current_display.write() = None;
+        self.is_capturing.store(false, Ordering::SeqCst);
+        log::info!("Stopped macOS screen capture");
+        Ok(())
+    }
+
+    fn capture_frame(&mut self) -> Result<CapturedFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        let display_id = self
+            .current_display
+            .read()
+            .ok_or_else(|| CaptureError::CaptureError("No display selected".to_string()))?;
+
+        if self.output_format != FrameFormat::Nv12 {
+            return Self::capture_display(display_id);
+        }
+
+        // NV12 comes straight from the display stream, no BGRA→YUV conversion
+        let (surface, width, height) = {
+            let stream = self.display_stream(FrameFormat::Nv12)?;
+            let (surface, changed) = stream.next_surface()?;
+            *self.gpu_frame_changed.write() = Some(changed);
+            (surface, stream.width, stream.height)
+        };
+        let data = Self::copy_nv12_surface(&surface, width, height)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Ok(CapturedFrame {
+            width,
+            height,
+            timestamp,
+            data,
+            format: FrameFormat::Nv12,
+        })
+    }
+
+    fn set_output_format(&mut self, format: FrameFormat) -> bool {
+        match format {
+            FrameFormat::Bgra | FrameFormat::Nv12 => {
+                self.output_format = format;
+                true
+            }
+            FrameFormat::Rgba => false,
+        }
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.is_capturing.load(Ordering::SeqCst)
+    }
+
+    fn supports_gpu_frames(&self) -> bool {
+        true
+    }
+
+    fn capture_gpu_frame(&mut self) -> Result<GpuFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        // GPU frames keep the stream's native format (BGRA unless NV12 was requested)
+        let format = match self.output_format {
+            FrameFormat::Nv12 => FrameFormat::Nv12,
+            _ => FrameFormat::Bgra,
+        };
+        let stream = self.display_stream(format)?;
+        let (surface, changed) = stream.next_surface()?;
+        *self.gpu_frame_changed.write() = Some(changed);
+        let pixel_buffer = Self::pixel_buffer(&surface)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Ok(GpuFrame {
+            width: stream.width,
+            height: stream.height,
+            format: stream.format,
+            timestamp,
+            surface: GpuSurface::IOSurface(pixel_buffer),
+        })
+    }
+
+    fn last_frame_changed(&self) -> Option<bool> {
+        *self.gpu_frame_changed.read()
+    }
+
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        let display_id = (*self.current_display.read())?;
+        Self::display_cursor_position(display_id)
+    }
+}
+
+impl Default for MacOSCapture {
+    fn default() -> Self {
+        Self::new().expect("Failed to create MacOSCapture")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enumerate_displays() {
+        let result = MacOSCapture::enumerate_displays();
+        assert!(result.is_ok());
+        let displays = result.unwrap();
+        assert!(!displays.is_empty(), "Should find at least one display");
+
+        // First display should be primary
+        assert!(displays[0].primary);
+    }
+
+    #[test]
+    fn test_permission_check() {
+        // This just tests that the function doesn't panic
+        let _has_perm = MacOSCapture::has_permission();
+    }
+}
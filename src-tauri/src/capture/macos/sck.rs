@@ -0,0 +1,515 @@
+// macOS screen capture using ScreenCaptureKit (macOS 12.3+)
+// Streams a display with excluded apps (LAN Meeting itself, password managers)
+// filtered out by the window server, so their windows never reach viewers.
+//
+// Frames arrive as IOSurfaces on a dispatch queue, the same way the
+// CGDisplayStream path in the parent module receives them.
+
+use super::{
+    CFRetain, LatestSurface, MacOSCapture, SurfaceRef, PIXEL_FORMAT_BGRA, PIXEL_FORMAT_NV12,
+};
+use crate::capture::exclusions;
+use crate::capture::{
+    CaptureError, CapturedFrame, Display, FrameFormat, GpuFrame, GpuSurface, ScreenCapture,
+};
+use block2::{DynBlock, RcBlock};
+use core_graphics::display::CGDisplay;
+use dispatch2::{DispatchQueue, DispatchRetained};
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, ProtocolObject};
+use objc2::{define_class, msg_send, AllocAnyThread, DefinedClass};
+use objc2_core_media::{CMSampleBuffer, CMTime};
+use objc2_foundation::{NSArray, NSError, NSObject, NSObjectProtocol};
+use objc2_screen_capture_kit::{
+    SCContentFilter, SCRunningApplication, SCShareableContent, SCStream, SCStreamConfiguration,
+    SCStreamOutput, SCStreamOutputType,
+};
+use parking_lot::Mutex;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long to wait for ScreenCaptureKit completion handlers
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Upper bound on the stream frame rate
+const STREAM_FPS: i32 = 60;
+
+/// How often the running stream re-checks which apps to hide, so apps
+/// launched after sharing started are excluded too
+const FILTER_REFRESH: Duration = Duration::from_secs(2);
+
+#[link(name = "CoreMedia", kind = "framework")]
+unsafe extern "C" {
+    fn CMSampleBufferGetImageBuffer(sbuf: *const c_void) -> *mut c_void;
+    fn CMTimeMake(value: i64, timescale: i32) -> CMTime;
+}
+
+#[link(name = "CoreVideo", kind = "framework")]
+unsafe extern "C" {
+    fn CVPixelBufferGetIOSurface(pixel_buffer: *mut c_void) -> *mut c_void;
+}
+
+/// Whether ScreenCaptureKit content filtering is available (macOS 12.3+)
+pub fn is_supported() -> bool {
+    AnyClass::get(c"SCStream").is_some() && AnyClass::get(c"SCContentFilter").is_some()
+}
+
+/// Lets Objective-C objects cross into a completion handler's thread
+struct Sendable<T>(T);
+
+// ScreenCaptureKit objects are safe to use from any thread
+unsafe impl<T> Send for Sendable<T> {}
+
+define_class!(
+    // Receives sample buffers from the stream and keeps the latest surface
+    #[unsafe(super(NSObject))]
+    #[name = "LanMeetingStreamOutput"]
+    #[ivars = Arc<LatestSurface>]
+    struct StreamOutput;
+
+    unsafe impl NSObjectProtocol for StreamOutput {}
+
+    unsafe impl SCStreamOutput for StreamOutput {
+        #[unsafe(method(stream:didOutputSampleBuffer:ofType:))]
+        fn stream_did_output(
+            &self,
+            _stream: &SCStream,
+            sample_buffer: &CMSampleBuffer,
+            output_type: SCStreamOutputType,
+        ) {
+            if output_type != SCStreamOutputType::Screen {
+                return;
+            }
+            unsafe {
+                // Idle frames (nothing changed) carry no image buffer
+                let image = CMSampleBufferGetImageBuffer(sample_buffer as *const _ as *const c_void);
+                if image.is_null() {
+                    return;
+                }
+                let surface = CVPixelBufferGetIOSurface(image);
+                if surface.is_null() {
+                    return;
+                }
+                CFRetain(surface);
+                self.ivars().publish(SurfaceRef(surface));
+            }
+        }
+    }
+);
+
+impl StreamOutput {
+    fn new(latest: Arc<LatestSurface>) -> Retained<Self> {
+        let this = Self::alloc().set_ivars(latest);
+        unsafe { msg_send![super(this), init] }
+    }
+}
+
+/// Call a ScreenCaptureKit method that reports completion through an
+/// `NSError` handler, and wait for it
+fn wait_for_completion(call: impl FnOnce(&DynBlock<dyn Fn(*mut NSError)>)) -> Result<(), String> {
+    let (tx, rx) = mpsc::channel();
+    let handler = RcBlock::new(move |error: *mut NSError| {
+        let result = match unsafe { error.as_ref() } {
+            Some(error) => Err(error.localizedDescription().to_string()),
+            None => Ok(()),
+        };
+        let _ = tx.send(result);
+    });
+    call(&handler);
+    rx.recv_timeout(COMPLETION_TIMEOUT)
+        .unwrap_or_else(|_| Err("timed out".to_string()))
+}
+
+/// Fetch the displays, apps and windows ScreenCaptureKit can see
+fn shareable_content() -> Result<Retained<SCShareableContent>, CaptureError> {
+    let (tx, rx) = mpsc::channel();
+    let handler = RcBlock::new(move |content: *mut SCShareableContent, error: *mut NSError| {
+        let result = match unsafe { Retained::retain(content) } {
+            Some(content) => Ok(Sendable(content)),
+            None => Err(unsafe { error.as_ref() }
+                .map(|e| e.localizedDescription().to_string())
+                .unwrap_or_default()),
+        };
+        let _ = tx.send(result);
+    });
+    unsafe { SCShareableContent::getShareableContentWithCompletionHandler(&handler) };
+
+    match rx.recv_timeout(COMPLETION_TIMEOUT) {
+        Ok(Ok(content)) => Ok(content.0),
+        Ok(Err(_)) if !MacOSCapture::has_permission() => Err(CaptureError::PermissionDenied),
+        Ok(Err(e)) => Err(CaptureError::InitError(format!(
+            "Failed to get shareable content: {}",
+            e
+        ))),
+        Err(_) => Err(CaptureError::InitError(
+            "Timed out getting shareable content".to_string(),
+        )),
+    }
+}
+
+/// Build a filter for a display that hides the excluded apps. Also returns
+/// the hidden process ids, to tell whether a later filter differs.
+fn content_filter(
+    content: &SCShareableContent,
+    display_id: u32,
+) -> Result<(Retained<SCContentFilter>, Vec<i32>), CaptureError> {
+    let exclusions = exclusions::get();
+    unsafe {
+        let display = content
+            .displays()
+            .iter()
+            .find(|d| d.displayID() == display_id)
+            .ok_or(CaptureError::DisplayNotFound(display_id))?;
+
+        let apps: Vec<Retained<SCRunningApplication>> = content
+            .applications()
+            .iter()
+            .filter(|app| exclusions.excludes(app.processID(), &app.bundleIdentifier().to_string()))
+            .collect();
+        let mut pids: Vec<i32> = apps.iter().map(|app| app.processID()).collect();
+        pids.sort_unstable();
+
+        let filter = SCContentFilter::initWithDisplay_excludingApplications_exceptingWindows(
+            SCContentFilter::alloc(),
+            &display,
+            &NSArray::from_retained_slice(&apps),
+            &NSArray::new(),
+        );
+        Ok((filter, pids))
+    }
+}
+
+/// State shared with the asynchronous filter refresh
+#[derive(Default)]
+struct FilterState {
+    /// Process ids hidden by the filter currently applied
+    excluded_pids: Mutex<Vec<i32>>,
+    /// Set while a refresh is in flight
+    refreshing: AtomicBool,
+    /// Set when a refresh finds the stream can no longer continue
+    failure: Mutex<Option<CaptureError>>,
+}
+
+/// A running SCStream and the objects it needs to stay alive
+struct SckStream {
+    stream: Retained<SCStream>,
+    _output: Retained<StreamOutput>,
+    _queue: DispatchRetained<DispatchQueue>,
+    latest: Arc<LatestSurface>,
+    filter_state: Arc<FilterState>,
+    display_id: u32,
+    format: FrameFormat,
+    width: u32,
+    height: u32,
+    exclusions_version: u64,
+    last_refresh: Instant,
+}
+
+impl SckStream {
+    fn start(display_id: u32, format: FrameFormat) -> Result<Self, CaptureError> {
+        let exclusions_version = exclusions::version();
+        let content = shareable_content()?;
+        let (filter, excluded_pids) = content_filter(&content, display_id)?;
+
+        let cg_display = CGDisplay::new(display_id);
+        let width = cg_display.pixels_wide() as u32;
+        let height = cg_display.pixels_high() as u32;
+
+        let latest = Arc::new(LatestSurface::default());
+        let output = StreamOutput::new(latest.clone());
+        let queue = DispatchQueue::new("lan-meeting.screencapturekit", None);
+
+        let stream = unsafe {
+            let config = SCStreamConfiguration::new();
+            config.setWidth(width as usize);
+            config.setHeight(height as usize);
+            config.setPixelFormat(match format {
+                FrameFormat::Nv12 => PIXEL_FORMAT_NV12 as u32,
+                _ => PIXEL_FORMAT_BGRA as u32,
+            });
+            config.setMinimumFrameInterval(CMTimeMake(1, STREAM_FPS));
+            // Like CGDisplayCreateImage, leave the pointer out; CursorOverlay draws it
+            config.setShowsCursor(false);
+
+            let stream = SCStream::initWithFilter_configuration_delegate(
+                SCStream::alloc(),
+                &filter,
+                &config,
+                None,
+            );
+            stream
+                .addStreamOutput_type_sampleHandlerQueue_error(
+                    ProtocolObject::from_ref(&*output),
+                    SCStreamOutputType::Screen,
+                    Some(&queue),
+                )
+                .map_err(|e| {
+                    CaptureError::InitError(format!(
+                        "SCStream addStreamOutput failed: {}",
+                        e.localizedDescription()
+                    ))
+                })?;
+            wait_for_completion(|handler| stream.startCaptureWithCompletionHandler(Some(handler)))
+                .map_err(|e| CaptureError::InitError(format!("SCStream start failed: {}", e)))?;
+            stream
+        };
+
+        log::info!(
+            "Started ScreenCaptureKit stream for display {} ({}x{}, {:?}), hiding {} app(s)",
+            display_id, width, height, format, excluded_pids.len()
+        );
+
+        Ok(Self {
+            stream,
+            _output: output,
+            _queue: queue,
+            latest,
+            filter_state: Arc::new(FilterState {
+                excluded_pids: Mutex::new(excluded_pids),
+                ..Default::default()
+            }),
+            display_id,
+            format,
+            width,
+            height,
+            exclusions_version,
+            last_refresh: Instant::now(),
+        })
+    }
+
+    /// Re-apply the exclusions when they changed, and periodically so newly
+    /// launched apps are hidden. Runs asynchronously on ScreenCaptureKit's
+    /// queue; the filter is only updated when the hidden apps differ.
+    fn refresh_filter(&mut self) {
+        let version = exclusions::version();
+        if version == self.exclusions_version && self.last_refresh.elapsed() < FILTER_REFRESH {
+            return;
+        }
+        if self.filter_state.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.exclusions_version = version;
+        self.last_refresh = Instant::now();
+
+        let stream = Sendable(self.stream.clone());
+        let state = self.filter_state.clone();
+        let display_id = self.display_id;
+        let handler = RcBlock::new(move |content: *mut SCShareableContent, _error: *mut NSError| {
+            let stream = &stream.0;
+            match unsafe { content.as_ref() } {
+                Some(content) => match content_filter(content, display_id) {
+                    Ok((filter, pids)) => {
+                        let mut excluded = state.excluded_pids.lock();
+                        if *excluded != pids {
+                            log::info!("Updating capture filter, hiding {} app(s)", pids.len());
+                            unsafe { stream.updateContentFilter_completionHandler(&filter, None) };
+                            *excluded = pids;
+                        }
+                    }
+                    Err(CaptureError::DisplayNotFound(id)) => {
+                        *state.failure.lock() = Some(CaptureError::DisplayRemoved(id));
+                    }
+                    Err(e) => log::warn!("Failed to refresh capture filter: {}", e),
+                },
+                None if !MacOSCapture::has_permission() => {
+                    *state.failure.lock() = Some(CaptureError::PermissionRevoked);
+                }
+                None => {}
+            }
+            state.refreshing.store(false, Ordering::SeqCst);
+        });
+        unsafe { SCShareableContent::getShareableContentWithCompletionHandler(&handler) };
+    }
+
+    /// Wait for a surface, reporting why the stream stopped if it did
+    fn next_surface(&self) -> Result<(SurfaceRef, bool), CaptureError> {
+        if let Some(failure) = self.filter_state.failure.lock().take() {
+            return Err(failure);
+        }
+        match self.latest.next() {
+            Err(CaptureError::Timeout) if !MacOSCapture::has_permission() => {
+                Err(CaptureError::PermissionRevoked)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Drop for SckStream {
+    fn drop(&mut self) {
+        // Wait so no sample buffer reaches the output after it is released
+        if let Err(e) = wait_for_completion(|handler| unsafe {
+            self.stream.stopCaptureWithCompletionHandler(Some(handler))
+        }) {
+            log::warn!("Failed to stop ScreenCaptureKit stream: {}", e);
+        }
+    }
+}
+
+/// macOS screen capture implementation using ScreenCaptureKit
+pub struct SckCapture {
+    is_capturing: AtomicBool,
+    current_display: Option<u32>,
+    stream: Option<SckStream>,
+    /// Format requested via set_output_format
+    output_format: FrameFormat,
+    // Whether the last capture call got a new frame (the stream only sends changes)
+    last_frame_changed: Option<bool>,
+}
+
+// Only accessed from one thread at a time, behind the capture lock
+unsafe impl Send for SckCapture {}
+unsafe impl Sync for SckCapture {}
+
+impl SckCapture {
+    pub fn new() -> Result<Self, CaptureError> {
+        if !is_supported() {
+            return Err(CaptureError::InitError(
+                "ScreenCaptureKit requires macOS 12.3 or later".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            is_capturing: AtomicBool::new(false),
+            current_display: None,
+            stream: None,
+            output_format: FrameFormat::Bgra,
+            last_frame_changed: None,
+        })
+    }
+
+    /// Get the stream for the current display, (re)starting it if the
+    /// requested pixel format changed
+    fn stream(&mut self, format: FrameFormat) -> Result<&mut SckStream, CaptureError> {
+        let display_id = self
+            .current_display
+            .ok_or_else(|| CaptureError::CaptureError("No display selected".to_string()))?;
+
+        if self.stream.as_ref().is_none_or(|s| s.format != format) {
+            self.stream = None;
+            self.stream = Some(SckStream::start(display_id, format)?);
+        }
+        let stream = self.stream.as_mut().expect("stream started");
+        stream.refresh_filter();
+        Ok(stream)
+    }
+
+    fn now_ms() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+impl ScreenCapture for SckCapture {
+    fn get_displays(&self) -> Result<Vec<Display>, CaptureError> {
+        MacOSCapture::enumerate_displays()
+    }
+
+    fn start(&mut self, display_id: u32) -> Result<(), CaptureError> {
+        MacOSCapture::ensure_permission()?;
+
+        let displays = MacOSCapture::enumerate_displays()?;
+        if !displays.iter().any(|d| d.id == display_id) {
+            return Err(CaptureError::DisplayNotFound(display_id));
+        }
+
+        self.stop()?;
+
+        // The stream itself starts on the first frame, once the pixel format is known
+        self.current_display = Some(display_id);
+        self.is_capturing.store(true, Ordering::SeqCst);
+
+        log::info!("Started ScreenCaptureKit capture for display {}", display_id);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), CaptureError> {
+        self.stream = None;
+        self.current_display = None;
+        self.last_frame_changed = None;
+        self.is_capturing.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn capture_frame(&mut self) -> Result<CapturedFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        let format = match self.output_format {
+            FrameFormat::Nv12 => FrameFormat::Nv12,
+            _ => FrameFormat::Bgra,
+        };
+        let stream = self.stream(format)?;
+        let (surface, changed) = stream.next_surface()?;
+        let (width, height) = (stream.width, stream.height);
+        self.last_frame_changed = Some(changed);
+
+        let data = match format {
+            FrameFormat::Nv12 => MacOSCapture::copy_nv12_surface(&surface, width, height)?,
+            _ => MacOSCapture::copy_bgra_surface(&surface, width, height)?,
+        };
+
+        Ok(CapturedFrame {
+            width,
+            height,
+            timestamp: Self::now_ms(),
+            data,
+            format,
+        })
+    }
+
+    fn set_output_format(&mut self, format: FrameFormat) -> bool {
+        match format {
+            FrameFormat::Bgra | FrameFormat::Nv12 => {
+                self.output_format = format;
+                true
+            }
+            FrameFormat::Rgba => false,
+        }
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.is_capturing.load(Ordering::SeqCst)
+    }
+
+    fn supports_gpu_frames(&self) -> bool {
+        true
+    }
+
+    fn capture_gpu_frame(&mut self) -> Result<GpuFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        let format = match self.output_format {
+            FrameFormat::Nv12 => FrameFormat::Nv12,
+            _ => FrameFormat::Bgra,
+        };
+        let stream = self.stream(format)?;
+        let (surface, changed) = stream.next_surface()?;
+        let (width, height) = (stream.width, stream.height);
+        self.last_frame_changed = Some(changed);
+        let pixel_buffer = MacOSCapture::pixel_buffer(&surface)?;
+
+        Ok(GpuFrame {
+            width,
+            height,
+            format,
+            timestamp: Self::now_ms(),
+            surface: GpuSurface::IOSurface(pixel_buffer),
+        })
+    }
+
+    fn last_frame_changed(&self) -> Option<bool> {
+        self.last_frame_changed
+    }
+
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        MacOSCapture::display_cursor_position(self.current_display?)
+    }
+}
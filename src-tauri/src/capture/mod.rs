@@ -10,6 +10,19 @@ pub mod windows;
 #[cfg(target_os = "linux")]
 pub mod linux;
 
+pub mod archive;
+pub mod convert;
+pub mod cursor;
+pub mod damage;
+pub mod exclusions;
+pub mod focus;
+pub mod ocr;
+pub mod pool;
+pub mod recovery;
+pub mod redaction;
+pub mod screenshot;
+
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,6 +35,57 @@ pub enum CaptureError {
     DisplayNotFound(u32),
     #[error("Capture failed: {0}")]
     CaptureError(String),
+    #[error("Capture permission was revoked")]
+    PermissionRevoked,
+    #[error("Display {0} was removed")]
+    DisplayRemoved(u32),
+    #[error("GPU busy: {0}")]
+    GpuBusy(String),
+    #[error("Timed out waiting for a frame")]
+    Timeout,
+}
+
+impl CaptureError {
+    /// Stable identifier for the frontend
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CaptureError::InitError(_) => "init",
+            CaptureError::PermissionDenied => "permission_denied",
+            CaptureError::DisplayNotFound(_) => "display_not_found",
+            CaptureError::CaptureError(_) => "capture",
+            CaptureError::PermissionRevoked => "permission_revoked",
+            CaptureError::DisplayRemoved(_) => "display_removed",
+            CaptureError::GpuBusy(_) => "gpu_busy",
+            CaptureError::Timeout => "timeout",
+        }
+    }
+
+    /// Whether retrying (possibly after restarting capture) can succeed
+    /// without the user doing anything
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            CaptureError::CaptureError(_) | CaptureError::GpuBusy(_) | CaptureError::Timeout
+        )
+    }
+
+    /// What the user can do about it, if anything
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            CaptureError::PermissionDenied | CaptureError::PermissionRevoked => {
+                if cfg!(target_os = "macos") {
+                    Some("请在 系统设置 > 隐私与安全性 > 屏幕录制 中重新授权 LAN Meeting，然后重新开始共享")
+                } else {
+                    Some("请授予屏幕录制权限后重新开始共享")
+                }
+            }
+            CaptureError::DisplayNotFound(_) | CaptureError::DisplayRemoved(_) => {
+                Some("共享的显示器已断开，请选择其他显示器重新共享")
+            }
+            CaptureError::GpuBusy(_) => Some("显卡暂时被占用（如全屏游戏、显示模式切换或锁屏），正在自动重试"),
+            CaptureError::InitError(_) | CaptureError::CaptureError(_) | CaptureError::Timeout => None,
+        }
+    }
 }
 
 /// Display information
@@ -35,6 +99,16 @@ pub struct Display {
     pub primary: bool,
 }
 
+/// Top-level application window that can be captured on its own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureWindow {
+    /// Platform window handle
+    pub id: u64,
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+}
+
 /// Captured frame data
 #[derive(Debug)]
 pub struct CapturedFrame {
@@ -45,13 +119,144 @@ pub struct CapturedFrame {
     pub format: FrameFormat,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl CapturedFrame {
+    /// Hand the pixel buffer back to the frame pool once the frame is encoded
+    pub fn recycle(self) {
+        pool::recycle(self.data);
+    }
+
+    /// Darken the picture to 40% brightness, e.g. to show the stream is paused
+    pub fn dim(&mut self) {
+        match self.format {
+            FrameFormat::Bgra | FrameFormat::Rgba => {
+                for px in self.data.chunks_exact_mut(4) {
+                    for c in &mut px[..3] {
+                        *c = (*c as u16 * 2 / 5) as u8;
+                    }
+                }
+            }
+            FrameFormat::Nv12 => {
+                // Scale luma towards video black and leave chroma alone
+                let luma = (self.width * self.height) as usize;
+                for y in self.data.iter_mut().take(luma) {
+                    *y = 16 + (y.saturating_sub(16) as u16 * 2 / 5) as u8;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FrameFormat {
     Bgra,
     Rgba,
+    /// Y plane (width x height) followed by interleaved CbCr at half resolution
     Nv12,
 }
 
+/// Frame that stays in GPU memory, for hardware encoders that can read it
+/// directly. Skips the readback into a `Vec` and the software BGRA→YUV
+/// conversion, which dominate frame time at 4K.
+pub struct GpuFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Pixel layout of the surface (BGRA or NV12)
+    pub format: FrameFormat,
+    pub timestamp: u64,
+    pub surface: GpuSurface,
+}
+
+/// Platform handle of a GPU-resident frame
+pub enum GpuSurface {
+    /// IOSurface-backed CVPixelBuffer (retained, released on drop)
+    #[cfg(target_os = "macos")]
+    IOSurface(macos::PixelBufferHandle),
+    /// Texture on the capture device. The encoder must use the same device.
+    #[cfg(target_os = "windows")]
+    D3D11Texture {
+        device: ::windows::Win32::Graphics::Direct3D11::ID3D11Device,
+        texture: ::windows::Win32::Graphics::Direct3D11::ID3D11Texture2D,
+    },
+}
+
+impl std::fmt::Debug for GpuFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GpuFrame")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("format", &self.format)
+            .field("timestamp", &self.timestamp)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Rectangle of a display to share, in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CaptureRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Clamp the region to display bounds, rounding the size down to even
+    /// numbers as required by the encoders. Returns None if nothing is left.
+    pub fn clamp_to(&self, display_width: u32, display_height: u32) -> Option<Self> {
+        if self.x >= display_width || self.y >= display_height {
+            return None;
+        }
+
+        let width = self.width.min(display_width - self.x) & !1;
+        let height = self.height.min(display_height - self.y) & !1;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some(Self { x: self.x, y: self.y, width, height })
+    }
+
+    /// Crop a packed 4-byte-per-pixel frame (BGRA/RGBA) to this region.
+    /// NV12 frames are returned unchanged.
+    pub fn crop(&self, frame: CapturedFrame) -> CapturedFrame {
+        if matches!(frame.format, FrameFormat::Nv12) {
+            return frame;
+        }
+
+        let Some(region) = self.clamp_to(frame.width, frame.height) else {
+            return frame;
+        };
+        if region.width == frame.width && region.height == frame.height {
+            return frame;
+        }
+
+        let src_stride = frame.width as usize * 4;
+        let dst_stride = region.width as usize * 4;
+        let x_offset = region.x as usize * 4;
+        let mut data = pool::take(dst_stride * region.height as usize);
+
+        for row in 0..region.height as usize {
+            let src_offset = (region.y as usize + row) * src_stride + x_offset;
+            let dst_offset = row * dst_stride;
+            data[dst_offset..dst_offset + dst_stride]
+                .copy_from_slice(&frame.data[src_offset..src_offset + dst_stride]);
+        }
+        pool::recycle(frame.data);
+
+        CapturedFrame {
+            width: region.width,
+            height: region.height,
+            timestamp: frame.timestamp,
+            data,
+            format: frame.format,
+        }
+    }
+}
+
 /// Screen capture trait - implemented per platform
 pub trait ScreenCapture: Send + Sync {
     /// Get list of available displays
@@ -60,6 +265,52 @@ pub trait ScreenCapture: Send + Sync {
     /// Start capturing a specific display
     fn start(&mut self, display_id: u32) -> Result<(), CaptureError>;
 
+    /// Start capturing a rectangle of a display.
+    /// Returns the region clamped to the display bounds. Backends without
+    /// native region support capture the full display, so callers crop each
+    /// frame with `CaptureRegion::crop` before scaling.
+    fn start_region(
+        &mut self,
+        display_id: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<CaptureRegion, CaptureError> {
+        let displays = self.get_displays()?;
+        let display = displays
+            .iter()
+            .find(|d| d.id == display_id)
+            .ok_or(CaptureError::DisplayNotFound(display_id))?;
+
+        let region = CaptureRegion::new(x, y, width, height)
+            .clamp_to(display.width, display.height)
+            .ok_or_else(|| {
+                CaptureError::InitError(format!(
+                    "Region {}x{} at ({}, {}) is outside display {}",
+                    width, height, x, y, display_id
+                ))
+            })?;
+
+        self.start(display_id)?;
+        Ok(region)
+    }
+
+    /// List windows that `start_window` can capture. Empty for backends
+    /// without per-window capture.
+    fn get_windows(&self) -> Result<Vec<CaptureWindow>, CaptureError> {
+        Ok(Vec::new())
+    }
+
+    /// Start capturing a single window from `get_windows`.
+    /// Returns the frame size in pixels.
+    fn start_window(&mut self, window_id: u64) -> Result<(u32, u32), CaptureError> {
+        Err(CaptureError::InitError(format!(
+            "Window capture not supported by this backend (window {})",
+            window_id
+        )))
+    }
+
     /// Stop capturing
     fn stop(&mut self) -> Result<(), CaptureError>;
 
@@ -68,17 +319,73 @@ pub trait ScreenCapture: Send + Sync {
 
     /// Check if currently capturing
     fn is_capturing(&self) -> bool;
+
+    /// Ask the backend to deliver `capture_frame` output in `format`.
+    /// Returns false if it cannot, in which case frames keep the backend's
+    /// native format.
+    fn set_output_format(&mut self, _format: FrameFormat) -> bool {
+        false
+    }
+
+    /// Whether `capture_gpu_frame` can deliver frames without a CPU copy
+    fn supports_gpu_frames(&self) -> bool {
+        false
+    }
+
+    /// Get the next frame as a GPU surface (blocking). Only valid for
+    /// backends that report `supports_gpu_frames`. Cursor compositing,
+    /// redaction and cropping need CPU pixels and are not applied.
+    fn capture_gpu_frame(&mut self) -> Result<GpuFrame, CaptureError> {
+        Err(CaptureError::CaptureError(
+            "GPU frames not supported by this backend".to_string(),
+        ))
+    }
+
+    /// Current pointer position in pixels relative to the captured display.
+    /// Returns None if the pointer is hidden, off this display, or the backend
+    /// cannot report it.
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    /// Whether the desktop image changed in the last captured frame, if the
+    /// backend knows (e.g. DXGI frame metadata). None means callers should
+    /// diff frames with `damage::DamageTracker`.
+    fn last_frame_changed(&self) -> Option<bool> {
+        None
+    }
 }
 
 /// Create platform-specific screen capture instance
 pub fn create_capture() -> Result<Box<dyn ScreenCapture>, CaptureError> {
+    use crate::safe_mode::{self, Subsystem};
+
+    // Safe mode skips the compositor-integrated backends for the older,
+    // simpler ones. Linux backends already copy frames on the CPU.
+    let safe = safe_mode::is_enabled();
+    safe_mode::record(Subsystem::Capture, safe);
+
     #[cfg(target_os = "macos")]
     {
+        // Prefer ScreenCaptureKit (can hide excluded apps), fall back to CoreGraphics
+        if !safe && macos::sck::is_supported() {
+            match macos::sck::SckCapture::new() {
+                Ok(capture) => return Ok(Box::new(capture)),
+                Err(e) => log::warn!("ScreenCaptureKit unavailable, using CoreGraphics: {}", e),
+            }
+        }
         Ok(Box::new(macos::MacOSCapture::new()?))
     }
 
     #[cfg(target_os = "windows")]
     {
+        // Prefer Windows Graphics Capture, fall back to Desktop Duplication
+        if !safe && windows::wgc::is_supported() {
+            match windows::wgc::WgcCapture::new() {
+                Ok(capture) => return Ok(Box::new(capture)),
+                Err(e) => log::warn!("Windows Graphics Capture unavailable, using DXGI: {}", e),
+            }
+        }
         Ok(Box::new(windows::WindowsCapture::new()?))
     }
 
@@ -92,3 +399,57 @@ pub fn create_capture() -> Result<Box<dyn ScreenCapture>, CaptureError> {
         Err(CaptureError::InitError("Unsupported platform".to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_clamp_to_display() {
+        let region = CaptureRegion::new(1800, 1000, 400, 201).clamp_to(1920, 1080).unwrap();
+        assert_eq!(region, CaptureRegion::new(1800, 1000, 120, 80));
+        assert!(CaptureRegion::new(1920, 0, 100, 100).clamp_to(1920, 1080).is_none());
+    }
+
+    #[test]
+    fn test_region_crop_pixels() {
+        // 4x2 BGRA frame where each pixel's first byte is its index
+        let data: Vec<u8> = (0..8u8).flat_map(|i| [i, 0, 0, 255]).collect();
+        let frame = CapturedFrame {
+            width: 4,
+            height: 2,
+            timestamp: 0,
+            data,
+            format: FrameFormat::Bgra,
+        };
+
+        let cropped = CaptureRegion::new(2, 0, 2, 2).crop(frame);
+        assert_eq!((cropped.width, cropped.height), (2, 2));
+        let firsts: Vec<u8> = cropped.data.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(firsts, vec![2, 3, 6, 7]);
+    }
+
+    #[test]
+    fn test_dim_keeps_alpha_and_chroma() {
+        let mut bgra = CapturedFrame {
+            width: 1,
+            height: 1,
+            timestamp: 0,
+            data: vec![200, 100, 10, 255],
+            format: FrameFormat::Bgra,
+        };
+        bgra.dim();
+        assert_eq!(bgra.data, vec![80, 40, 4, 255]);
+
+        // 2x2 NV12: four luma bytes then one CbCr pair
+        let mut nv12 = CapturedFrame {
+            width: 2,
+            height: 2,
+            timestamp: 0,
+            data: vec![235, 16, 10, 126, 90, 240],
+            format: FrameFormat::Nv12,
+        };
+        nv12.dim();
+        assert_eq!(nv12.data, vec![103, 16, 16, 60, 90, 240]);
+    }
+}
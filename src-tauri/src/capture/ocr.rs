@@ -0,0 +1,207 @@
+//! Text search over archive snapshots
+//!
+//! With archive OCR turned on, every saved snapshot is run through a local
+//! Tesseract and its text appended to an index next to the snapshots, so
+//! past sessions can be searched for something that was on screen ("where
+//! did we see that error code"). Nothing leaves the machine. Recognition
+//! needs the `ocr` feature (libtesseract); without it snapshots are still
+//! saved, just not indexed.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Index file in the archive root, one JSON entry per line
+const INDEX_FILE: &str = "ocr-index.jsonl";
+
+/// Characters of context on each side of a match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Most matches returned by one search
+const MAX_MATCHES: usize = 100;
+
+/// Serializes appends and rewrites of the index
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Text recognized in one snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub path: String,
+    /// Folder of the share the snapshot belongs to
+    pub session: String,
+    /// Seconds since the Unix epoch
+    pub captured_at: u64,
+    pub text: String,
+}
+
+/// A snapshot whose text matches a search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArchiveMatch {
+    pub path: String,
+    pub session: String,
+    pub captured_at: u64,
+    /// The matched text with some context around it
+    pub snippet: String,
+}
+
+/// Whether this build can recognize text
+pub fn is_available() -> bool {
+    cfg!(feature = "ocr")
+}
+
+/// Recognize the text of a saved snapshot and add it to the index
+pub fn index_snapshot(root: &Path, path: &Path, captured_at: u64) -> Result<(), String> {
+    let text = recognize(path)?;
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return Ok(());
+    }
+    let entry = IndexEntry {
+        path: path.display().to_string(),
+        session: path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        captured_at,
+        text,
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    let _guard = INDEX_LOCK.lock();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(root.join(INDEX_FILE))
+        .map_err(|e| e.to_string())?;
+    file.write_all(line.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "ocr")]
+fn recognize(path: &Path) -> Result<String, String> {
+    // Chinese and English when both language packs are installed
+    let mut tess = leptess::LepTess::new(None, "chi_sim+eng")
+        .or_else(|_| leptess::LepTess::new(None, "eng"))
+        .map_err(|e| format!("Failed to start Tesseract: {}", e))?;
+    tess.set_image(path).map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+    tess.get_utf8_text().map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "ocr"))]
+fn recognize(_path: &Path) -> Result<String, String> {
+    Err("OCR is not available in this build".to_string())
+}
+
+/// Drop index entries whose snapshot was deleted
+pub fn forget_missing(root: &Path) {
+    let _guard = INDEX_LOCK.lock();
+    let index = root.join(INDEX_FILE);
+    let Ok(contents) = std::fs::read_to_string(&index) else {
+        return;
+    };
+    let kept: String = contents
+        .lines()
+        .filter(|line| {
+            serde_json::from_str::<IndexEntry>(line).is_ok_and(|e| Path::new(&e.path).exists())
+        })
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    if kept.len() != contents.len() {
+        if let Err(e) = std::fs::write(&index, kept) {
+            log::warn!("Failed to update OCR index {}: {}", index.display(), e);
+        }
+    }
+}
+
+/// Snapshots whose text contains every word of `query`, newest first
+pub fn search(root: &Path, query: &str) -> Result<Vec<ArchiveMatch>, String> {
+    let contents = match std::fs::read_to_string(root.join(INDEX_FILE)) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let entries = contents.lines().filter_map(|line| serde_json::from_str(line).ok());
+    Ok(search_entries(entries, query))
+}
+
+fn search_entries(entries: impl Iterator<Item = IndexEntry>, query: &str) -> Vec<ArchiveMatch> {
+    let terms: Vec<Vec<char>> = query.split_whitespace().map(lowercase_chars).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches: Vec<ArchiveMatch> = entries
+        .filter_map(|entry| {
+            let text: Vec<char> = entry.text.chars().collect();
+            let lower = lowercase_chars(&entry.text);
+            let positions: Option<Vec<usize>> = terms.iter().map(|term| find(&lower, term)).collect();
+            let first = positions?[0];
+            let start = first.saturating_sub(SNIPPET_CONTEXT);
+            let end = (first + terms[0].len() + SNIPPET_CONTEXT).min(text.len());
+            let mut snippet: String = text[start..end].iter().collect();
+            if start > 0 {
+                snippet.insert(0, '…');
+            }
+            if end < text.len() {
+                snippet.push('…');
+            }
+            Some(ArchiveMatch {
+                path: entry.path,
+                session: entry.session,
+                captured_at: entry.captured_at,
+                snippet,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| b.captured_at.cmp(&a.captured_at));
+    matches.truncate(MAX_MATCHES);
+    matches
+}
+
+/// Lowercase per character, so indices line up with the original text
+fn lowercase_chars(s: &str) -> Vec<char> {
+    s.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect()
+}
+
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(captured_at: u64, text: &str) -> IndexEntry {
+        IndexEntry {
+            path: format!("{}.jpg", captured_at),
+            session: "s".to_string(),
+            captured_at,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_matches_all_terms_newest_first() {
+        let entries = vec![
+            entry(1, "Build failed: error E0425 in main.rs"),
+            entry(2, "All tests passed"),
+            entry(3, "ERROR e0425 again, cannot find value"),
+        ];
+        let matches = search_entries(entries.into_iter(), "e0425 error");
+        let times: Vec<u64> = matches.iter().map(|m| m.captured_at).collect();
+        assert_eq!(times, vec![3, 1]);
+        assert_eq!(matches[1].snippet, "Build failed: error E0425 in main.rs");
+        assert!(search_entries(std::iter::empty(), "  ").is_empty());
+    }
+
+    #[test]
+    fn test_snippet_is_trimmed_around_match() {
+        let text = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let matches = search_entries(vec![entry(1, &text)].into_iter(), "NEEDLE");
+        let snippet = &matches[0].snippet;
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(snippet.chars().count(), SNIPPET_CONTEXT * 2 + "needle".len() + 2);
+    }
+}
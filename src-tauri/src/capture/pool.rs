@@ -0,0 +1,145 @@
+//! Frame buffer pool
+//!
+//! Capture, conversion, scaling and YUV packing each need a buffer of
+//! several megabytes per frame. Stages take buffers from a small shared pool
+//! and hand them back once the frame is encoded, so a 4K/60 stream keeps
+//! reusing the same few allocations instead of hitting the allocator
+//! hundreds of times a second.
+
+use parking_lot::Mutex;
+use std::borrow::Cow;
+
+/// Buffers kept for reuse. A frame passes through at most four stages
+/// (capture, convert, scale, YUV), with one frame in flight per pipeline.
+const POOL_SIZE: usize = 8;
+
+/// Set of reusable byte buffers of mixed sizes
+pub struct FramePool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+impl FramePool {
+    pub const fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Smallest pooled buffer that can hold `len` bytes
+    fn best_fit(&self, len: usize) -> Option<Vec<u8>> {
+        let mut buffers = self.buffers.lock();
+        let index = buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.capacity() >= len)
+            .min_by_key(|(_, b)| b.capacity())
+            .map(|(i, _)| i)?;
+        Some(buffers.swap_remove(index))
+    }
+
+    /// Buffer of exactly `len` bytes. Reused buffers keep their old contents,
+    /// so callers must overwrite every byte.
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        match self.best_fit(len) {
+            Some(mut buf) => {
+                if buf.len() >= len {
+                    buf.truncate(len);
+                } else {
+                    buf.resize(len, 0);
+                }
+                buf
+            }
+            None => vec![0u8; len],
+        }
+    }
+
+    /// Empty buffer with room for `capacity` bytes, for filling with `extend`
+    pub fn take_empty(&self, capacity: usize) -> Vec<u8> {
+        match self.best_fit(capacity) {
+            Some(mut buf) => {
+                buf.clear();
+                buf
+            }
+            None => Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Hand a buffer back. Dropped if the pool is full.
+    pub fn recycle(&self, buf: Vec<u8>) {
+        if buf.capacity() == 0 {
+            return;
+        }
+        let mut buffers = self.buffers.lock();
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+
+    /// Number of buffers waiting for reuse
+    pub fn len(&self) -> usize {
+        self.buffers.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Pool shared by all capture and encode pipelines
+static POOL: FramePool = FramePool::new(POOL_SIZE);
+
+/// Take a buffer of `len` bytes with unspecified contents
+pub fn take(len: usize) -> Vec<u8> {
+    POOL.take(len)
+}
+
+/// Take an empty buffer with room for `capacity` bytes
+pub fn take_empty(capacity: usize) -> Vec<u8> {
+    POOL.take_empty(capacity)
+}
+
+/// Return a buffer to the shared pool
+pub fn recycle(buf: Vec<u8>) {
+    POOL.recycle(buf)
+}
+
+/// Return the buffer behind a `Cow` if the stage allocated one
+pub fn recycle_cow(data: Cow<'_, [u8]>) {
+    if let Cow::Owned(buf) = data {
+        POOL.recycle(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycled_buffer_is_reused() {
+        let pool = FramePool::new(4);
+        let buf = pool.take(1024);
+        let ptr = buf.as_ptr();
+        pool.recycle(buf);
+
+        let again = pool.take(512);
+        assert_eq!(again.as_ptr(), ptr);
+        assert_eq!(again.len(), 512);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_best_fit_and_limit() {
+        let pool = FramePool::new(2);
+        pool.recycle(Vec::with_capacity(4096));
+        pool.recycle(Vec::with_capacity(256));
+        pool.recycle(Vec::with_capacity(128)); // pool full, dropped
+        assert_eq!(pool.len(), 2);
+
+        // Small requests leave the large buffer for large frames
+        assert_eq!(pool.take_empty(100).capacity(), 256);
+        assert!(pool.take(2048).capacity() >= 4096);
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Capture error recovery
+//!
+//! Decides what a streaming loop does after `capture_frame` fails: skip the
+//! frame, restart the capture source, or give up. Errors the user has to act
+//! on are reported to the frontend as a `capture-error` event with a hint.
+
+use super::CaptureError;
+use tauri::Emitter;
+
+/// Consecutive transient failures before the capture source is restarted
+const FAILURES_BEFORE_RESTART: u32 = 5;
+
+/// Restarts without a successful frame in between before giving up
+const MAX_RESTARTS: u32 = 3;
+
+/// What the streaming loop should do after a capture error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// Drop this frame and try again next tick
+    Skip,
+    /// Stop and start the capture source again, then continue
+    Restart,
+    /// Stop sharing; the frontend has been told why
+    Stop,
+}
+
+/// Tracks failures of one capture session
+#[derive(Debug, Default)]
+pub struct CaptureRecovery {
+    consecutive_failures: u32,
+    restarts: u32,
+}
+
+impl CaptureRecovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call after every successful frame
+    pub fn on_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.restarts = 0;
+    }
+
+    /// Classify a capture error. Emits `capture-error` when the user needs
+    /// to know (sharing stops, or a GPU stall is being retried).
+    pub fn on_error(&mut self, err: &CaptureError) -> RecoveryAction {
+        let action = self.classify(err);
+        match action {
+            RecoveryAction::Stop => {
+                log::error!("Capture failed, stopping: {}", err);
+                emit_capture_error(err, true);
+            }
+            RecoveryAction::Restart => {
+                log::warn!("Capture failing repeatedly, restarting source: {}", err);
+                if matches!(err, CaptureError::GpuBusy(_)) {
+                    emit_capture_error(err, false);
+                }
+            }
+            RecoveryAction::Skip => {}
+        }
+        action
+    }
+
+    fn classify(&mut self, err: &CaptureError) -> RecoveryAction {
+        // A static screen times out without anything being wrong
+        if matches!(err, CaptureError::Timeout) {
+            return RecoveryAction::Skip;
+        }
+        if !err.is_transient() && !matches!(err, CaptureError::InitError(_)) {
+            return RecoveryAction::Stop;
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures < FAILURES_BEFORE_RESTART {
+            return RecoveryAction::Skip;
+        }
+
+        self.consecutive_failures = 0;
+        self.restarts += 1;
+        if self.restarts > MAX_RESTARTS {
+            RecoveryAction::Stop
+        } else {
+            RecoveryAction::Restart
+        }
+    }
+}
+
+/// Payload of the `capture-error` event
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CaptureErrorEvent {
+    pub kind: &'static str,
+    pub message: String,
+    pub hint: Option<&'static str>,
+    /// Sharing has stopped
+    pub fatal: bool,
+}
+
+/// Tell the frontend about a capture error
+pub fn emit_capture_error(err: &CaptureError, fatal: bool) {
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit(
+            "capture-error",
+            CaptureErrorEvent {
+                kind: err.kind(),
+                message: err.to_string(),
+                hint: err.hint(),
+                fatal,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeouts_never_escalate() {
+        let mut recovery = CaptureRecovery::new();
+        for _ in 0..100 {
+            assert_eq!(recovery.classify(&CaptureError::Timeout), RecoveryAction::Skip);
+        }
+    }
+
+    #[test]
+    fn test_transient_errors_restart_then_stop() {
+        let mut recovery = CaptureRecovery::new();
+        let busy = CaptureError::GpuBusy("access lost".to_string());
+
+        let mut actions = Vec::new();
+        for _ in 0..FAILURES_BEFORE_RESTART * (MAX_RESTARTS + 1) {
+            actions.push(recovery.classify(&busy));
+        }
+        let restarts = actions.iter().filter(|a| **a == RecoveryAction::Restart).count();
+        assert_eq!(restarts as u32, MAX_RESTARTS);
+        assert_eq!(actions.last(), Some(&RecoveryAction::Stop));
+
+        // A good frame resets the budget
+        recovery.on_success();
+        assert_eq!(recovery.classify(&busy), RecoveryAction::Skip);
+    }
+
+    #[test]
+    fn test_user_actionable_errors_stop_immediately() {
+        let mut recovery = CaptureRecovery::new();
+        assert_eq!(recovery.classify(&CaptureError::PermissionRevoked), RecoveryAction::Stop);
+        assert_eq!(recovery.classify(&CaptureError::DisplayRemoved(1)), RecoveryAction::Stop);
+    }
+}
@@ -0,0 +1,107 @@
+//! Notification redaction
+//!
+//! Pixelates the screen areas where OS notification banners appear so that
+//! private messages popping up during a presentation are not readable by viewers.
+//! Banner positions are platform heuristics, not queried from the notification center.
+
+use super::{CaptureRegion, CapturedFrame, FrameFormat};
+
+/// Banner size in logical points (roughly the size of a two-line banner)
+const BANNER_WIDTH: f32 = 400.0;
+const BANNER_HEIGHT: f32 = 180.0;
+
+/// Distance from the screen edge to the banner, in logical points
+const BANNER_MARGIN: f32 = 8.0;
+
+/// Pixelation block size in physical pixels
+const BLOCK_SIZE: usize = 24;
+
+/// Pixelates notification areas of captured frames
+pub struct NotificationRedactor {
+    rects: Vec<CaptureRegion>,
+}
+
+impl NotificationRedactor {
+    /// Build the redaction areas for a display of the given physical size
+    pub fn for_display(width: u32, height: u32, scale_factor: f32) -> Self {
+        let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+        let banner_w = ((BANNER_WIDTH + BANNER_MARGIN) * scale) as u32;
+        let banner_h = ((BANNER_HEIGHT + BANNER_MARGIN) * scale) as u32;
+
+        let rect = Self::banner_rect(width, height, banner_w, banner_h);
+        let rects = CaptureRegion::new(rect.0, rect.1, banner_w, banner_h)
+            .clamp_to(width, height)
+            .into_iter()
+            .collect();
+
+        Self { rects }
+    }
+
+    /// Top-left corner of the banner area for the current platform
+    fn banner_rect(width: u32, height: u32, banner_w: u32, banner_h: u32) -> (u32, u32) {
+        if cfg!(target_os = "windows") {
+            // Toasts stack above the taskbar in the bottom-right corner
+            (width.saturating_sub(banner_w), height.saturating_sub(banner_h * 2))
+        } else if cfg!(target_os = "linux") {
+            // GNOME shows banners at the top center
+            (width.saturating_sub(banner_w) / 2, 0)
+        } else {
+            // macOS banners slide in at the top-right corner
+            (width.saturating_sub(banner_w), 0)
+        }
+    }
+
+    /// Redaction areas in display coordinates
+    pub fn rects(&self) -> &[CaptureRegion] {
+        &self.rects
+    }
+
+    /// Pixelate the redaction areas in place (BGRA/RGBA frames only)
+    pub fn apply(&self, frame: &mut CapturedFrame) {
+        if matches!(frame.format, FrameFormat::Nv12) {
+            return;
+        }
+
+        for rect in &self.rects {
+            if let Some(rect) = rect.clamp_to(frame.width, frame.height) {
+                pixelate(&mut frame.data, frame.width as usize, &rect);
+            }
+        }
+    }
+}
+
+/// Replace each block inside `rect` with its average color
+fn pixelate(data: &mut [u8], frame_width: usize, rect: &CaptureRegion) {
+    let stride = frame_width * 4;
+    let x0 = rect.x as usize;
+    let y0 = rect.y as usize;
+    let x1 = x0 + rect.width as usize;
+    let y1 = y0 + rect.height as usize;
+
+    for by in (y0..y1).step_by(BLOCK_SIZE) {
+        let by_end = (by + BLOCK_SIZE).min(y1);
+        for bx in (x0..x1).step_by(BLOCK_SIZE) {
+            let bx_end = (bx + BLOCK_SIZE).min(x1);
+
+            let mut sum = [0u32; 4];
+            for y in by..by_end {
+                for x in bx..bx_end {
+                    let i = y * stride + x * 4;
+                    for (s, &v) in sum.iter_mut().zip(&data[i..i + 4]) {
+                        *s += v as u32;
+                    }
+                }
+            }
+
+            let count = ((by_end - by) * (bx_end - bx)) as u32;
+            let avg = sum.map(|s| (s / count) as u8);
+
+            for y in by..by_end {
+                for x in bx..bx_end {
+                    let i = y * stride + x * 4;
+                    data[i..i + 4].copy_from_slice(&avg);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Screenshot and send
+//!
+//! Captures one frame of a display (or a rectangle of it), saves it as a
+//! PNG and offers it to a peer through the normal file transfer. When the
+//! user wants to mark something up first, the frontend shows the PNG with a
+//! pen, and the strokes it collects are drawn onto the image here before
+//! the file is sent, so the receiver gets a plain PNG either way.
+
+use super::convert::convert_data;
+use super::{create_capture, CaptureError, CaptureRegion, FrameFormat};
+use image::{Rgba, RgbaImage};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Pen width used when a stroke does not give a usable one
+const DEFAULT_PEN_WIDTH: f32 = 4.0;
+
+/// Tries at getting a frame when the backend reports a transient error
+const FRAME_ATTEMPTS: usize = 3;
+
+/// Where screenshots are saved before sending
+pub fn screenshot_root() -> Option<PathBuf> {
+    dirs::picture_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|p| p.join("LAN Meeting").join("Screenshots"))
+}
+
+/// Capture one frame of `display_id`, cropped to `region` if given, and
+/// save it as a PNG. Returns the file path.
+pub fn capture_png(display_id: u32, region: Option<CaptureRegion>) -> Result<PathBuf, CaptureError> {
+    let mut capture = create_capture()?;
+    capture.set_output_format(FrameFormat::Rgba);
+    let region = match region {
+        Some(r) => Some(capture.start_region(display_id, r.x, r.y, r.width, r.height)?),
+        None => {
+            capture.start(display_id)?;
+            None
+        }
+    };
+    // The first frame can take a moment on compositor-based backends
+    let mut frame = capture.capture_frame();
+    for _ in 1..FRAME_ATTEMPTS {
+        match &frame {
+            Err(e) if e.is_transient() => frame = capture.capture_frame(),
+            _ => break,
+        }
+    }
+    let _ = capture.stop();
+    let mut frame = frame?;
+    if let Some(region) = region {
+        frame = region.crop(frame);
+    }
+
+    let rgba = convert_data(&frame.data, frame.width, frame.height, frame.format, FrameFormat::Rgba).into_owned();
+    let (width, height) = (frame.width, frame.height);
+    frame.recycle();
+
+    let root = screenshot_root().ok_or_else(|| CaptureError::CaptureError("No picture folder".to_string()))?;
+    std::fs::create_dir_all(&root).map_err(|e| CaptureError::CaptureError(e.to_string()))?;
+    let stamp = super::archive::utc_stamp(super::archive::now_secs());
+    let path = root.join(format!("screenshot-{}.png", stamp));
+    image::save_buffer(&path, &rgba, width, height, image::ExtendedColorType::Rgba8)
+        .map_err(|e| CaptureError::CaptureError(format!("{}: {}", path.display(), e)))?;
+
+    log::info!("Saved {}x{} screenshot of display {} to {}", width, height, display_id, path.display());
+    Ok(path)
+}
+
+/// A pen stroke drawn over a screenshot, in image pixels
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stroke {
+    /// CSS hex color, "#rrggbb"
+    pub color: String,
+    pub width: f32,
+    pub points: Vec<[f32; 2]>,
+}
+
+/// Draw `strokes` onto the PNG at `path`, overwriting it
+pub fn annotate_png(path: &Path, strokes: &[Stroke]) -> Result<(), String> {
+    if strokes.is_empty() {
+        return Ok(());
+    }
+    let mut image = image::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?
+        .into_rgba8();
+    draw_strokes(&mut image, strokes);
+    image
+        .save(path)
+        .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
+
+fn draw_strokes(image: &mut RgbaImage, strokes: &[Stroke]) {
+    for stroke in strokes {
+        let color = parse_color(&stroke.color).unwrap_or(Rgba([255, 0, 0, 255]));
+        let radius = if stroke.width > 0.0 { stroke.width / 2.0 } else { DEFAULT_PEN_WIDTH / 2.0 };
+        let Some(&first) = stroke.points.first() else {
+            continue;
+        };
+        stamp(image, first, radius, color);
+        for pair in stroke.points.windows(2) {
+            let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+            // Stamp dots closely enough along the segment that they join up
+            let steps = ((x1 - x0).hypot(y1 - y0) / (radius / 2.0).max(0.5)).ceil() as usize;
+            for i in 1..=steps {
+                let t = i as f32 / steps as f32;
+                stamp(image, [x0 + (x1 - x0) * t, y0 + (y1 - y0) * t], radius, color);
+            }
+        }
+    }
+}
+
+/// Fill a disk of `radius` around `center`
+fn stamp(image: &mut RgbaImage, [cx, cy]: [f32; 2], radius: f32, color: Rgba<u8>) {
+    let (w, h) = (image.width() as i64, image.height() as i64);
+    let x_range = ((cx - radius).floor() as i64).max(0)..=((cx + radius).ceil() as i64).min(w - 1);
+    let y_range = ((cy - radius).floor() as i64).max(0)..=((cy + radius).ceil() as i64).min(h - 1);
+    for y in y_range {
+        for x in x_range.clone() {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+fn parse_color(hex: &str) -> Option<Rgba<u8>> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Rgba([(value >> 16) as u8, (value >> 8) as u8, value as u8, 255]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strokes_drawn_along_path() {
+        let mut image = RgbaImage::new(40, 20);
+        let stroke = Stroke {
+            color: "#00ff00".to_string(),
+            width: 2.0,
+            points: vec![[2.0, 10.0], [38.0, 10.0]],
+        };
+        draw_strokes(&mut image, &[stroke]);
+        let green = Rgba([0, 255, 0, 255]);
+        assert!((2..38).all(|x| *image.get_pixel(x, 9) == green || *image.get_pixel(x, 10) == green));
+        assert_eq!(*image.get_pixel(20, 2), Rgba([0, 0, 0, 0]));
+        assert_eq!(*image.get_pixel(20, 17), Rgba([0, 0, 0, 0]));
+
+        assert_eq!(parse_color("#102030"), Some(Rgba([0x10, 0x20, 0x30, 255])));
+        assert_eq!(parse_color("red"), None);
+    }
+}
@@ -1,9 +1,12 @@
 // Windows screen capture using DXGI Desktop Duplication API
 // High-performance GPU-accelerated screen capture for Windows 8+
+// Windows Graphics Capture (Windows 10 1903+) lives in `wgc`
 
-use super::{CaptureError, CapturedFrame, Display, FrameFormat, ScreenCapture};
+pub mod wgc;
+
+use super::{pool, CaptureError, CapturedFrame, Display, FrameFormat, GpuFrame, GpuSurface, ScreenCapture};
 use parking_lot::RwLock;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use windows::{
@@ -26,6 +29,32 @@ pub struct WindowsCapture {
     duplication: RwLock<Option<IDXGIOutputDuplication>>,
     staging_texture: RwLock<Option<ID3D11Texture2D>>,
     output_desc: RwLock<Option<DXGI_OUTPUT_DESC>>,
+    // Last pointer position reported by the duplication API (None = hidden)
+    pointer_position: RwLock<Option<(i32, i32)>>,
+    // Whether the last acquired frame contained a desktop image update
+    last_frame_changed: RwLock<Option<bool>>,
+    // Ring of GPU textures handed to the encoder by capture_gpu_frame
+    gpu_textures: RwLock<Vec<ID3D11Texture2D>>,
+    gpu_texture_index: AtomicUsize,
+}
+
+// DXGI error codes that need specific handling
+const DXGI_ERROR_DEVICE_REMOVED: u32 = 0x887A0005;
+const DXGI_ERROR_DEVICE_RESET: u32 = 0x887A0007;
+const DXGI_ERROR_NOT_CURRENTLY_AVAILABLE: u32 = 0x887A0022;
+const DXGI_ERROR_ACCESS_LOST: u32 = 0x887A0026;
+const DXGI_ERROR_WAIT_TIMEOUT: u32 = 0x887A0027;
+const E_ACCESSDENIED: u32 = 0x80070005;
+
+/// Textures in the GPU frame ring, so the encoder can still be reading one
+/// while the next frame is copied
+const GPU_TEXTURE_RING_SIZE: usize = 3;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 // Send + Sync is safe because we use proper synchronization
@@ -43,6 +72,10 @@ impl WindowsCapture {
             duplication: RwLock::new(None),
             staging_texture: RwLock::new(None),
             output_desc: RwLock::new(None),
+            pointer_position: RwLock::new(None),
+            last_frame_changed: RwLock::new(None),
+            gpu_textures: RwLock::new(Vec::new()),
+            gpu_texture_index: AtomicUsize::new(0),
         })
     }
 
@@ -112,27 +145,36 @@ impl WindowsCapture {
         Ok(displays)
     }
 
-    /// Initialize DXGI resources for capturing a specific display
-    fn init_capture_resources(&self, display_id: u32) -> Result<(), CaptureError> {
-        let adapter_idx = (display_id >> 16) as u32;
-        let output_idx = (display_id & 0xFFFF) as u32;
+    /// Find the adapter and output behind a display ID from `enumerate_displays`
+    fn find_output(display_id: u32) -> Result<(IDXGIAdapter1, IDXGIOutput), CaptureError> {
+        let adapter_idx = display_id >> 16;
+        let output_idx = display_id & 0xFFFF;
 
         unsafe {
-            // Create DXGI factory
             let factory: IDXGIFactory1 = CreateDXGIFactory1()
                 .map_err(|e| CaptureError::InitError(format!("CreateDXGIFactory1 failed: {}", e)))?;
 
-            // Get the adapter
-            let adapter: IDXGIAdapter1 = factory.EnumAdapters1(adapter_idx).map_err(|_| {
-                CaptureError::DisplayNotFound(display_id)
-            })?;
+            let adapter: IDXGIAdapter1 = factory
+                .EnumAdapters1(adapter_idx)
+                .map_err(|_| CaptureError::DisplayNotFound(display_id))?;
+            let output: IDXGIOutput = adapter
+                .EnumOutputs(output_idx)
+                .map_err(|_| CaptureError::DisplayNotFound(display_id))?;
 
-            // Create D3D11 device
-            let mut device: Option<ID3D11Device> = None;
-            let mut context: Option<ID3D11DeviceContext> = None;
+            Ok((adapter, output))
+        }
+    }
 
+    /// Create a D3D11 device on `adapter` (BGRA support is needed for capture)
+    fn create_device(
+        adapter: &IDXGIAdapter1,
+    ) -> Result<(ID3D11Device, ID3D11DeviceContext), CaptureError> {
+        let mut device: Option<ID3D11Device> = None;
+        let mut context: Option<ID3D11DeviceContext> = None;
+
+        unsafe {
             D3D11CreateDevice(
-                &adapter,
+                adapter,
                 D3D_DRIVER_TYPE_UNKNOWN,
                 HMODULE(std::ptr::null_mut()),
                 D3D11_CREATE_DEVICE_BGRA_SUPPORT,
@@ -143,19 +185,23 @@ impl WindowsCapture {
                 Some(&mut context),
             )
             .map_err(|e| CaptureError::InitError(format!("D3D11CreateDevice failed: {}", e)))?;
+        }
 
-            let device = device.ok_or_else(|| {
-                CaptureError::InitError("D3D11CreateDevice returned null device".to_string())
-            })?;
-            let context = context.ok_or_else(|| {
-                CaptureError::InitError("D3D11CreateDevice returned null context".to_string())
-            })?;
+        let device = device.ok_or_else(|| {
+            CaptureError::InitError("D3D11CreateDevice returned null device".to_string())
+        })?;
+        let context = context.ok_or_else(|| {
+            CaptureError::InitError("D3D11CreateDevice returned null context".to_string())
+        })?;
+        Ok((device, context))
+    }
 
-            // Get the output
-            let output: IDXGIOutput = adapter.EnumOutputs(output_idx).map_err(|_| {
-                CaptureError::DisplayNotFound(display_id)
-            })?;
+    /// Initialize DXGI resources for capturing a specific display
+    fn init_capture_resources(&self, display_id: u32) -> Result<(), CaptureError> {
+        let (adapter, output) = Self::find_output(display_id)?;
+        let (device, context) = Self::create_device(&adapter)?;
 
+        unsafe {
             // Get output description
             let output_desc = output.GetDesc().map_err(|e| {
                 CaptureError::InitError(format!("GetDesc failed: {}", e))
@@ -171,10 +217,17 @@ impl WindowsCapture {
 
             // Create output duplication
             let duplication = output1.DuplicateOutput(&device).map_err(|e| {
-                CaptureError::InitError(format!(
-                    "DuplicateOutput failed - another app may be capturing: {}",
-                    e
-                ))
+                match e.code().0 as u32 {
+                    DXGI_ERROR_NOT_CURRENTLY_AVAILABLE => CaptureError::GpuBusy(
+                        "too many applications are duplicating this output".to_string(),
+                    ),
+                    // Secure desktop (UAC prompt, lock screen) is active
+                    E_ACCESSDENIED => CaptureError::GpuBusy("secure desktop is active".to_string()),
+                    _ => CaptureError::InitError(format!(
+                        "DuplicateOutput failed - another app may be capturing: {}",
+                        e
+                    )),
+                }
             })?;
 
             // Create staging texture for CPU read
@@ -227,8 +280,143 @@ impl WindowsCapture {
         Ok(())
     }
 
+    /// Acquire the next desktop frame. The caller must copy the texture and
+    /// then call `release_frame`. Reinitializes resources on ACCESS_LOST.
+    fn acquire_next_frame(&self) -> Result<ID3D11Texture2D, CaptureError> {
+        let duplication_guard = self.duplication.read();
+        let duplication = duplication_guard
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Duplication not initialized".to_string()))?;
+
+        unsafe {
+            // Acquire next frame with timeout
+            let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+            let mut desktop_resource: Option<IDXGIResource> = None;
+
+            let result = duplication.AcquireNextFrame(100, &mut frame_info, &mut desktop_resource);
+
+            if let Err(err) = result {
+                let code = err.code().0 as u32;
+
+                if code == DXGI_ERROR_WAIT_TIMEOUT {
+                    // Normal, no new frame
+                    return Err(CaptureError::Timeout);
+                }
+
+                if code == DXGI_ERROR_ACCESS_LOST {
+                    // Duplication invalidated (mode change, desktop switch, output removed)
+                    // Drop the read guard before reinitializing
+                    drop(duplication_guard);
+
+                    log::warn!("DXGI_ERROR_ACCESS_LOST: reinitializing capture resources");
+                    if let Some(display_id) = *self.current_display.read() {
+                        self.release_resources();
+                        match self.init_capture_resources(display_id) {
+                            Ok(()) => log::info!("Capture resources reinitialized after ACCESS_LOST"),
+                            Err(CaptureError::DisplayNotFound(id)) => {
+                                return Err(CaptureError::DisplayRemoved(id));
+                            }
+                            Err(e) => {
+                                log::error!("Failed to reinitialize capture after ACCESS_LOST: {}", e);
+                            }
+                        }
+                    }
+                    return Err(CaptureError::GpuBusy("access lost, reinitializing".to_string()));
+                }
+
+                if code == DXGI_ERROR_DEVICE_REMOVED || code == DXGI_ERROR_DEVICE_RESET {
+                    return Err(CaptureError::GpuBusy(format!("GPU device lost: {}", err)));
+                }
+
+                return Err(CaptureError::CaptureError(format!(
+                    "AcquireNextFrame failed: {}",
+                    err
+                )));
+            }
+
+            // LastPresentTime is zero when only the pointer moved
+            *self.last_frame_changed.write() = Some(frame_info.LastPresentTime != 0);
+
+            // Pointer info is only valid when the mouse was updated in this frame
+            if frame_info.LastMouseUpdateTime != 0 {
+                let pointer = frame_info.PointerPosition;
+                *self.pointer_position.write() = if pointer.Visible.as_bool() {
+                    Some((pointer.Position.x, pointer.Position.y))
+                } else {
+                    None
+                };
+            }
+
+            let desktop_resource = desktop_resource.ok_or_else(|| {
+                CaptureError::CaptureError("AcquireNextFrame returned null resource".to_string())
+            })?;
+
+            // Get the texture from the resource
+            desktop_resource.cast().map_err(|e| {
+                CaptureError::CaptureError(format!("Failed to cast to ID3D11Texture2D: {}", e))
+            })
+        }
+    }
+
+    /// Release the frame acquired by `acquire_next_frame`
+    fn release_frame(&self) -> Result<(), CaptureError> {
+        let duplication_guard = self.duplication.read();
+        let duplication = duplication_guard
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Duplication not initialized".to_string()))?;
+
+        unsafe {
+            duplication.ReleaseFrame().map_err(|e| {
+                CaptureError::CaptureError(format!("ReleaseFrame failed: {}", e))
+            })
+        }
+    }
+
+    /// Size of the captured output in pixels
+    fn output_size(&self) -> Result<(u32, u32), CaptureError> {
+        let output_desc_guard = self.output_desc.read();
+        let output_desc = output_desc_guard
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Output desc not initialized".to_string()))?;
+
+        let rect = output_desc.DesktopCoordinates;
+        Ok(((rect.right - rect.left) as u32, (rect.bottom - rect.top) as u32))
+    }
+
+    /// Create a GPU texture that hardware encoders can read directly
+    fn create_gpu_texture(
+        device: &ID3D11Device,
+        width: u32,
+        height: u32,
+    ) -> Result<ID3D11Texture2D, CaptureError> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: (D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0) as u32,
+            CPUAccessFlags: 0,
+            MiscFlags: D3D11_RESOURCE_MISC_FLAG(0).0 as u32,
+        };
+
+        let mut texture: Option<ID3D11Texture2D> = None;
+        unsafe {
+            device
+                .CreateTexture2D(&desc, None, Some(&mut texture))
+                .map_err(|e| CaptureError::InitError(format!("CreateTexture2D failed: {}", e)))?;
+        }
+        texture.ok_or_else(|| CaptureError::InitError("CreateTexture2D returned null".to_string()))
+    }
+
     /// Release DXGI resources
     fn release_resources(&self) {
+        self.gpu_textures.write().clear();
         *self.duplication.write() = None;
         *self.staging_texture.write() = None;
         *self.context.write() = None;
@@ -272,10 +460,8 @@ impl ScreenCapture for WindowsCapture {
             return Err(CaptureError::CaptureError("Not capturing".to_string()));
         }
 
-        let duplication_guard = self.duplication.read();
-        let duplication = duplication_guard
-            .as_ref()
-            .ok_or_else(|| CaptureError::CaptureError("Duplication not initialized".to_string()))?;
+        // Acquire first: on ACCESS_LOST this reinitializes the resources below
+        let desktop_texture = self.acquire_next_frame()?;
 
         let context_guard = self.context.read();
         let context = context_guard
@@ -287,73 +473,14 @@ impl ScreenCapture for WindowsCapture {
             .as_ref()
             .ok_or_else(|| CaptureError::CaptureError("Staging texture not initialized".to_string()))?;
 
-        let output_desc_guard = self.output_desc.read();
-        let output_desc = output_desc_guard
-            .as_ref()
-            .ok_or_else(|| CaptureError::CaptureError("Output desc not initialized".to_string()))?;
-
-        let rect = output_desc.DesktopCoordinates;
-        let width = (rect.right - rect.left) as u32;
-        let height = (rect.bottom - rect.top) as u32;
+        let (width, height) = self.output_size()?;
 
         unsafe {
-            // Acquire next frame with timeout
-            let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
-            let mut desktop_resource: Option<IDXGIResource> = None;
-
-            let result = duplication.AcquireNextFrame(100, &mut frame_info, &mut desktop_resource);
-
-            if result.is_err() {
-                let err = result.unwrap_err();
-                let code = err.code().0 as u32;
-
-                if code == 0x887A0027 {
-                    // DXGI_ERROR_WAIT_TIMEOUT - normal, no new frame
-                    return Err(CaptureError::CaptureError("Frame timeout".to_string()));
-                }
-
-                if code == 0x887A0026 {
-                    // DXGI_ERROR_ACCESS_LOST - duplication invalidated
-                    // Drop all read guards before reinitializing
-                    drop(output_desc_guard);
-                    drop(staging_guard);
-                    drop(context_guard);
-                    drop(duplication_guard);
-
-                    log::warn!("DXGI_ERROR_ACCESS_LOST: reinitializing capture resources");
-                    if let Some(display_id) = *self.current_display.read() {
-                        self.release_resources();
-                        if let Err(e) = self.init_capture_resources(display_id) {
-                            log::error!("Failed to reinitialize capture after ACCESS_LOST: {}", e);
-                        } else {
-                            log::info!("Capture resources reinitialized after ACCESS_LOST");
-                        }
-                    }
-                    return Err(CaptureError::CaptureError("Access lost, reinitializing".to_string()));
-                }
-
-                return Err(CaptureError::CaptureError(format!(
-                    "AcquireNextFrame failed: {}",
-                    err
-                )));
-            }
-
-            let desktop_resource = desktop_resource.ok_or_else(|| {
-                CaptureError::CaptureError("AcquireNextFrame returned null resource".to_string())
-            })?;
-
-            // Get the texture from the resource
-            let desktop_texture: ID3D11Texture2D = desktop_resource.cast().map_err(|e| {
-                CaptureError::CaptureError(format!("Failed to cast to ID3D11Texture2D: {}", e))
-            })?;
-
             // Copy to staging texture
             context.CopyResource(staging_texture, &desktop_texture);
 
             // Release the frame
-            duplication.ReleaseFrame().map_err(|e| {
-                CaptureError::CaptureError(format!("ReleaseFrame failed: {}", e))
-            })?;
+            self.release_frame()?;
 
             // Map staging texture to read pixels
             let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
@@ -370,7 +497,7 @@ impl ScreenCapture for WindowsCapture {
             // Copy pixel data
             let row_pitch = mapped.RowPitch as usize;
             let data_size = (width * height * 4) as usize;
-            let mut frame_data = Vec::with_capacity(data_size);
+            let mut frame_data = pool::take_empty(data_size);
 
             let src_ptr = mapped.pData as *const u8;
             for y in 0..height as usize {
@@ -382,24 +509,77 @@ impl ScreenCapture for WindowsCapture {
             // Unmap
             context.Unmap(staging_texture, 0);
 
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_millis() as u64)
-                .unwrap_or(0);
-
             Ok(CapturedFrame {
                 width,
                 height,
-                timestamp,
+                timestamp: now_ms(),
                 data: frame_data,
                 format: FrameFormat::Bgra,
             })
         }
     }
 
+    fn supports_gpu_frames(&self) -> bool {
+        true
+    }
+
+    fn capture_gpu_frame(&mut self) -> Result<GpuFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        let desktop_texture = self.acquire_next_frame()?;
+        let (width, height) = self.output_size()?;
+
+        let device = self
+            .device
+            .read()
+            .clone()
+            .ok_or_else(|| CaptureError::CaptureError("Device not initialized".to_string()))?;
+        let context = self
+            .context
+            .read()
+            .clone()
+            .ok_or_else(|| CaptureError::CaptureError("Context not initialized".to_string()))?;
+
+        // The desktop texture is only valid until ReleaseFrame, so copy it
+        // into the next texture of the ring (GPU-to-GPU, no readback)
+        let texture = {
+            let mut ring = self.gpu_textures.write();
+            if ring.is_empty() {
+                for _ in 0..GPU_TEXTURE_RING_SIZE {
+                    ring.push(Self::create_gpu_texture(&device, width, height)?);
+                }
+            }
+            let index = self.gpu_texture_index.fetch_add(1, Ordering::Relaxed) % ring.len();
+            ring[index].clone()
+        };
+
+        unsafe {
+            context.CopyResource(&texture, &desktop_texture);
+        }
+        self.release_frame()?;
+
+        Ok(GpuFrame {
+            width,
+            height,
+            format: FrameFormat::Bgra,
+            timestamp: now_ms(),
+            surface: GpuSurface::D3D11Texture { device, texture },
+        })
+    }
+
     fn is_capturing(&self) -> bool {
         self.is_capturing.load(Ordering::SeqCst)
     }
+
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        *self.pointer_position.read()
+    }
+
+    fn last_frame_changed(&self) -> Option<bool> {
+        *self.last_frame_changed.read()
+    }
 }
 
 impl Default for WindowsCapture {
@@ -0,0 +1,792 @@
+// Windows screen capture using Windows Graphics Capture (WGC)
+// Captures a monitor or a single window on Windows 10 1903+.
+//
+// Unlike Desktop Duplication, WGC can capture one window even when it is
+// partly covered. On HDR displays it captures FP16 scRGB and tone maps to
+// 8-bit here, instead of taking the compositor's clipped SDR output.
+
+use super::{now_ms, WindowsCapture, GPU_TEXTURE_RING_SIZE};
+use crate::capture::{
+    pool, CaptureError, CaptureWindow, CapturedFrame, Display, FrameFormat, GpuFrame, GpuSurface,
+    ScreenCapture,
+};
+use parking_lot::{Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use windows::{
+    core::{factory, Interface, BOOL, HSTRING},
+    Foundation::Metadata::ApiInformation,
+    Foundation::TypedEventHandler,
+    Graphics::Capture::{
+        Direct3D11CaptureFrame, Direct3D11CaptureFramePool, GraphicsCaptureItem,
+        GraphicsCaptureSession,
+    },
+    Graphics::DirectX::Direct3D11::IDirect3DDevice,
+    Graphics::DirectX::DirectXPixelFormat,
+    Win32::Foundation::{HWND, LPARAM, RECT},
+    Win32::Graphics::Direct3D11::*,
+    Win32::Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS},
+    Win32::Graphics::Dxgi::Common::*,
+    Win32::Graphics::Dxgi::*,
+    Win32::Graphics::Gdi::{MonitorFromWindow, HMONITOR, MONITOR_DEFAULTTONEAREST},
+    Win32::System::WinRT::Direct3D11::{
+        CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess,
+    },
+    Win32::System::WinRT::Graphics::Capture::IGraphicsCaptureItemInterop,
+    Win32::System::WinRT::{RoInitialize, RO_INIT_MULTITHREADED},
+    Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetCursorInfo, GetWindowLongW, GetWindowTextLengthW, GetWindowTextW,
+        GetWindowThreadProcessId, IsIconic, IsWindowVisible, CURSORINFO, CURSOR_SHOWING,
+        GWL_EXSTYLE, WS_EX_TOOLWINDOW,
+    },
+};
+
+/// How long `capture_frame` waits for the compositor before timing out
+const FRAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Buffers in the frame pool
+const FRAME_POOL_BUFFERS: i32 = 2;
+
+/// SDR white level assumed on HDR displays (Windows' default "SDR content
+/// brightness" is close to this). Brighter content rolls off into highlights.
+const SDR_WHITE_NITS: f32 = 200.0;
+
+/// scRGB 1.0 is defined as 80 nits
+const SCRGB_UNIT_NITS: f32 = 80.0;
+
+/// Relative to SDR white, values above this are compressed instead of clipped
+const HIGHLIGHT_KNEE: f32 = 0.8;
+
+/// Entries in the linear → sRGB lookup table
+const ENCODE_LUT_SIZE: usize = 4096;
+
+/// Whether WGC with monitor and window capture is available (Windows 10 1903+)
+pub fn is_supported() -> bool {
+    ensure_winrt();
+    // CreateForMonitor/CreateForWindow arrived with UniversalApiContract 8 (1903)
+    let contract = HSTRING::from("Windows.Foundation.UniversalApiContract");
+    ApiInformation::IsApiContractPresent(&contract, 8).unwrap_or(false)
+        && GraphicsCaptureSession::IsSupported().unwrap_or(false)
+}
+
+/// WinRT needs the thread in an apartment; an MTA is shared by all threads
+fn ensure_winrt() {
+    unsafe {
+        let _ = RoInitialize(RO_INIT_MULTITHREADED);
+    }
+}
+
+/// What the session captures, for translating pointer coordinates
+#[derive(Clone, Copy)]
+enum Target {
+    Monitor { left: i32, top: i32 },
+    Window(HWND),
+}
+
+/// Set by the FrameArrived handler, waited on by `capture_frame`
+#[derive(Default)]
+struct FrameSignal {
+    ready: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl FrameSignal {
+    fn notify(&self) {
+        *self.ready.lock() = true;
+        self.cond.notify_one();
+    }
+
+    /// Wait up to `timeout` for a frame. Returns whether one arrived.
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut ready = self.ready.lock();
+        if !*ready {
+            self.cond.wait_for(&mut ready, timeout);
+        }
+        std::mem::replace(&mut *ready, false)
+    }
+}
+
+/// An active capture session and the D3D resources it copies into
+struct Session {
+    pool: Direct3D11CaptureFramePool,
+    session: GraphicsCaptureSession,
+    arrived_token: i64,
+    device: ID3D11Device,
+    context: ID3D11DeviceContext,
+    staging: ID3D11Texture2D,
+    width: u32,
+    height: u32,
+    target: Target,
+    /// Set when capturing an HDR display in FP16
+    tone_mapper: Option<ToneMapper>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.pool.RemoveFrameArrived(self.arrived_token);
+        let _ = self.session.Close();
+        let _ = self.pool.Close();
+    }
+}
+
+/// Windows screen capture implementation using Windows Graphics Capture
+pub struct WgcCapture {
+    is_capturing: AtomicBool,
+    session: Option<Session>,
+    signal: Arc<FrameSignal>,
+    // Whether the last capture call got a new frame (WGC only sends changes)
+    last_frame_changed: Option<bool>,
+    // Ring of GPU textures handed to the encoder by capture_gpu_frame
+    gpu_textures: Vec<ID3D11Texture2D>,
+    gpu_texture_index: AtomicUsize,
+}
+
+// Send + Sync is safe: the WinRT objects are agile and the pool is free-threaded
+unsafe impl Send for WgcCapture {}
+unsafe impl Sync for WgcCapture {}
+
+impl WgcCapture {
+    pub fn new() -> Result<Self, CaptureError> {
+        if !is_supported() {
+            return Err(CaptureError::InitError(
+                "Windows Graphics Capture requires Windows 10 1903 or later".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            is_capturing: AtomicBool::new(false),
+            session: None,
+            signal: Arc::new(FrameSignal::default()),
+            last_frame_changed: None,
+            gpu_textures: Vec::new(),
+            gpu_texture_index: AtomicUsize::new(0),
+        })
+    }
+
+    /// Create the frame pool and session for `item` and start capturing
+    fn start_item(
+        &mut self,
+        item: GraphicsCaptureItem,
+        adapter: &IDXGIAdapter1,
+        hdr: bool,
+        target: Target,
+    ) -> Result<(u32, u32), CaptureError> {
+        let (device, context) = WindowsCapture::create_device(adapter)?;
+
+        let winrt_device: IDirect3DDevice = unsafe {
+            let dxgi_device: IDXGIDevice = device.cast().map_err(init_err("IDXGIDevice"))?;
+            CreateDirect3D11DeviceFromDXGIDevice(&dxgi_device)
+                .map_err(init_err("CreateDirect3D11DeviceFromDXGIDevice"))?
+                .cast()
+                .map_err(init_err("IDirect3DDevice"))?
+        };
+
+        let size = item.Size().map_err(init_err("GraphicsCaptureItem::Size"))?;
+        let width = size.Width.max(0) as u32;
+        let height = size.Height.max(0) as u32;
+        if width == 0 || height == 0 {
+            return Err(CaptureError::InitError("Capture target has no size".to_string()));
+        }
+
+        let (pixel_format, staging_format) = if hdr {
+            (DirectXPixelFormat::R16G16B16A16Float, DXGI_FORMAT_R16G16B16A16_FLOAT)
+        } else {
+            (DirectXPixelFormat::B8G8R8A8UIntNormalized, DXGI_FORMAT_B8G8R8A8_UNORM)
+        };
+
+        // Frames keep the size the target had at start; a resized window is
+        // clipped or padded rather than changing the stream resolution
+        let pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+            &winrt_device,
+            pixel_format,
+            FRAME_POOL_BUFFERS,
+            size,
+        )
+        .map_err(init_err("CreateFreeThreaded"))?;
+
+        let signal = self.signal.clone();
+        let arrived_token = pool
+            .FrameArrived(&TypedEventHandler::new(move |_, _| {
+                signal.notify();
+                Ok(())
+            }))
+            .map_err(init_err("FrameArrived"))?;
+
+        let session = pool
+            .CreateCaptureSession(&item)
+            .map_err(init_err("CreateCaptureSession"))?;
+        // The pointer is composited by the pipeline (see cursor_position);
+        // both setters are missing on older builds, which is fine
+        let _ = session.SetIsCursorCaptureEnabled(false);
+        let _ = session.SetIsBorderRequired(false);
+
+        let staging = create_staging_texture(&device, width, height, staging_format)?;
+
+        let session = Session {
+            pool,
+            session,
+            arrived_token,
+            device,
+            context,
+            staging,
+            width,
+            height,
+            target,
+            tone_mapper: hdr.then(|| ToneMapper::new(SDR_WHITE_NITS)),
+        };
+        session.session.StartCapture().map_err(init_err("StartCapture"))?;
+
+        self.session = Some(session);
+        self.is_capturing.store(true, Ordering::SeqCst);
+
+        log::info!(
+            "WGC capture started ({}x{}{})",
+            width,
+            height,
+            if hdr { ", HDR tone mapped" } else { "" }
+        );
+        Ok((width, height))
+    }
+
+    /// Take the newest frame from the pool, waiting up to FRAME_TIMEOUT.
+    /// Older queued frames are dropped so the stream never lags behind.
+    fn next_frame(&mut self) -> Result<Direct3D11CaptureFrame, CaptureError> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Not capturing".to_string()))?;
+
+        let mut latest = drain_pool(&session.pool);
+        if latest.is_none() && self.signal.wait(FRAME_TIMEOUT) {
+            latest = drain_pool(&session.pool);
+        }
+
+        // WGC only delivers frames when the target changed
+        self.last_frame_changed = Some(latest.is_some());
+        latest.ok_or(CaptureError::Timeout)
+    }
+}
+
+impl ScreenCapture for WgcCapture {
+    fn get_displays(&self) -> Result<Vec<Display>, CaptureError> {
+        // Same IDs as Desktop Duplication so either backend accepts them
+        WindowsCapture::enumerate_displays()
+    }
+
+    fn start(&mut self, display_id: u32) -> Result<(), CaptureError> {
+        self.stop()?;
+        ensure_winrt();
+
+        let (adapter, output) = WindowsCapture::find_output(display_id)?;
+        let desc = unsafe { output.GetDesc() }.map_err(init_err("GetDesc"))?;
+
+        let item: GraphicsCaptureItem = unsafe {
+            capture_item_interop()?
+                .CreateForMonitor(desc.Monitor)
+                .map_err(init_err("CreateForMonitor"))?
+        };
+
+        let rect = desc.DesktopCoordinates;
+        let target = Target::Monitor { left: rect.left, top: rect.top };
+        self.start_item(item, &adapter, is_hdr_output(&output), target)?;
+
+        log::info!("Started WGC capture for display {}", display_id);
+        Ok(())
+    }
+
+    fn get_windows(&self) -> Result<Vec<CaptureWindow>, CaptureError> {
+        let mut windows: Vec<CaptureWindow> = Vec::new();
+        unsafe {
+            EnumWindows(
+                Some(collect_window),
+                LPARAM(&mut windows as *mut Vec<CaptureWindow> as isize),
+            )
+            .map_err(|e| CaptureError::CaptureError(format!("EnumWindows failed: {}", e)))?;
+        }
+        Ok(windows)
+    }
+
+    fn start_window(&mut self, window_id: u64) -> Result<(u32, u32), CaptureError> {
+        self.stop()?;
+        ensure_winrt();
+
+        let hwnd = HWND(window_id as usize as *mut std::ffi::c_void);
+
+        // Use the adapter and HDR state of the monitor the window is on
+        let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) };
+        let (adapter, hdr) = match output_for_monitor(monitor) {
+            Some((adapter, output)) => (adapter, is_hdr_output(&output)),
+            None => (WindowsCapture::find_output(0)?.0, false),
+        };
+
+        let item: GraphicsCaptureItem = unsafe {
+            capture_item_interop()?
+                .CreateForWindow(hwnd)
+                .map_err(|e| {
+                    CaptureError::InitError(format!("Cannot capture window {:#x}: {}", window_id, e))
+                })?
+        };
+
+        let size = self.start_item(item, &adapter, hdr, Target::Window(hwnd))?;
+        log::info!("Started WGC capture for window {:#x}", window_id);
+        Ok(size)
+    }
+
+    fn stop(&mut self) -> Result<(), CaptureError> {
+        self.is_capturing.store(false, Ordering::SeqCst);
+        self.gpu_textures.clear();
+        if self.session.take().is_some() {
+            log::info!("Stopped WGC capture");
+        }
+        Ok(())
+    }
+
+    fn capture_frame(&mut self) -> Result<CapturedFrame, CaptureError> {
+        if !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("Not capturing".to_string()));
+        }
+
+        let frame = self.next_frame()?;
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Not capturing".to_string()))?;
+
+        let texture = frame_texture(&frame)?;
+        unsafe {
+            session.context.CopyResource(&session.staging, &texture);
+        }
+        let _ = frame.Close();
+
+        let (width, height) = (session.width as usize, session.height as usize);
+        let bytes_per_pixel = if session.tone_mapper.is_some() { 8 } else { 4 };
+
+        unsafe {
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            session
+                .context
+                .Map(&session.staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))
+                .map_err(|e| CaptureError::CaptureError(format!("Map failed: {}", e)))?;
+
+            let row_pitch = mapped.RowPitch as usize;
+            let src = std::slice::from_raw_parts(
+                mapped.pData as *const u8,
+                row_pitch * (height - 1) + width * bytes_per_pixel,
+            );
+
+            let data = match session.tone_mapper {
+                Some(ref tone_mapper) => tone_mapper.map_rows(src, row_pitch, width, height),
+                None => {
+                    let mut data = pool::take_empty(width * height * 4);
+                    for row in src.chunks(row_pitch).take(height) {
+                        data.extend_from_slice(&row[..width * 4]);
+                    }
+                    data
+                }
+            };
+
+            session.context.Unmap(&session.staging, 0);
+
+            Ok(CapturedFrame {
+                width: session.width,
+                height: session.height,
+                timestamp: now_ms(),
+                data,
+                format: FrameFormat::Bgra,
+            })
+        }
+    }
+
+    fn supports_gpu_frames(&self) -> bool {
+        // HDR frames are FP16 and need the CPU tone mapper
+        self.session.as_ref().is_some_and(|s| s.tone_mapper.is_none())
+    }
+
+    fn capture_gpu_frame(&mut self) -> Result<GpuFrame, CaptureError> {
+        if !self.supports_gpu_frames() || !self.is_capturing.load(Ordering::SeqCst) {
+            return Err(CaptureError::CaptureError("GPU frames not available".to_string()));
+        }
+
+        let frame = self.next_frame()?;
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| CaptureError::CaptureError("Not capturing".to_string()))?;
+
+        // Pool buffers are recycled once the frame is closed, so copy into
+        // the next texture of the ring (GPU-to-GPU, no readback)
+        if self.gpu_textures.is_empty() {
+            for _ in 0..GPU_TEXTURE_RING_SIZE {
+                self.gpu_textures.push(WindowsCapture::create_gpu_texture(
+                    &session.device,
+                    session.width,
+                    session.height,
+                )?);
+            }
+        }
+        let index = self.gpu_texture_index.fetch_add(1, Ordering::Relaxed) % self.gpu_textures.len();
+        let texture = self.gpu_textures[index].clone();
+
+        let source = frame_texture(&frame)?;
+        unsafe {
+            session.context.CopyResource(&texture, &source);
+        }
+        let _ = frame.Close();
+
+        Ok(GpuFrame {
+            width: session.width,
+            height: session.height,
+            format: FrameFormat::Bgra,
+            timestamp: now_ms(),
+            surface: GpuSurface::D3D11Texture {
+                device: session.device.clone(),
+                texture,
+            },
+        })
+    }
+
+    fn is_capturing(&self) -> bool {
+        self.is_capturing.load(Ordering::SeqCst)
+    }
+
+    fn cursor_position(&self) -> Option<(i32, i32)> {
+        let session = self.session.as_ref()?;
+
+        let mut info = CURSORINFO {
+            cbSize: std::mem::size_of::<CURSORINFO>() as u32,
+            ..Default::default()
+        };
+        unsafe { GetCursorInfo(&mut info) }.ok()?;
+        if info.flags.0 & CURSOR_SHOWING.0 == 0 {
+            return None;
+        }
+
+        let (left, top) = match session.target {
+            Target::Monitor { left, top } => (left, top),
+            Target::Window(hwnd) => {
+                let bounds = window_bounds(hwnd)?;
+                (bounds.left, bounds.top)
+            }
+        };
+        let x = info.ptScreenPos.x - left;
+        let y = info.ptScreenPos.y - top;
+        if x < 0 || y < 0 || x >= session.width as i32 || y >= session.height as i32 {
+            return None;
+        }
+        Some((x, y))
+    }
+
+    fn last_frame_changed(&self) -> Option<bool> {
+        self.last_frame_changed
+    }
+}
+
+/// Map a WinRT error to a capture init error naming the failed call
+fn init_err(what: &'static str) -> impl Fn(windows::core::Error) -> CaptureError {
+    move |e| CaptureError::InitError(format!("{} failed: {}", what, e))
+}
+
+fn capture_item_interop() -> Result<IGraphicsCaptureItemInterop, CaptureError> {
+    factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()
+        .map_err(init_err("IGraphicsCaptureItemInterop"))
+}
+
+/// Take every queued frame, closing all but the newest
+fn drain_pool(pool: &Direct3D11CaptureFramePool) -> Option<Direct3D11CaptureFrame> {
+    let mut latest: Option<Direct3D11CaptureFrame> = None;
+    while let Ok(frame) = pool.TryGetNextFrame() {
+        if let Some(older) = latest.replace(frame) {
+            let _ = older.Close();
+        }
+    }
+    latest
+}
+
+/// D3D11 texture behind a captured frame
+fn frame_texture(frame: &Direct3D11CaptureFrame) -> Result<ID3D11Texture2D, CaptureError> {
+    let surface = frame
+        .Surface()
+        .map_err(|e| CaptureError::CaptureError(format!("Frame surface: {}", e)))?;
+    let access: IDirect3DDxgiInterfaceAccess = surface
+        .cast()
+        .map_err(|e| CaptureError::CaptureError(format!("IDirect3DDxgiInterfaceAccess: {}", e)))?;
+    unsafe { access.GetInterface() }
+        .map_err(|e| CaptureError::CaptureError(format!("Frame texture: {}", e)))
+}
+
+fn create_staging_texture(
+    device: &ID3D11Device,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+) -> Result<ID3D11Texture2D, CaptureError> {
+    let desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_STAGING,
+        BindFlags: D3D11_BIND_FLAG(0).0 as u32,
+        CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+        MiscFlags: D3D11_RESOURCE_MISC_FLAG(0).0 as u32,
+    };
+
+    let mut texture: Option<ID3D11Texture2D> = None;
+    unsafe {
+        device
+            .CreateTexture2D(&desc, None, Some(&mut texture))
+            .map_err(|e| CaptureError::InitError(format!("CreateTexture2D failed: {}", e)))?;
+    }
+    texture.ok_or_else(|| CaptureError::InitError("CreateTexture2D returned null".to_string()))
+}
+
+/// Whether the output runs in HDR (advanced color) mode
+fn is_hdr_output(output: &IDXGIOutput) -> bool {
+    unsafe {
+        output
+            .cast::<IDXGIOutput6>()
+            .and_then(|output6| output6.GetDesc1())
+            .map(|desc| desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020)
+            .unwrap_or(false)
+    }
+}
+
+/// Adapter and output that drive `monitor`
+fn output_for_monitor(monitor: HMONITOR) -> Option<(IDXGIAdapter1, IDXGIOutput)> {
+    unsafe {
+        let factory: IDXGIFactory1 = CreateDXGIFactory1().ok()?;
+        let mut adapter_idx = 0u32;
+        while let Ok(adapter) = factory.EnumAdapters1(adapter_idx) {
+            let mut output_idx = 0u32;
+            while let Ok(output) = adapter.EnumOutputs(output_idx) {
+                if output.GetDesc().is_ok_and(|desc| desc.Monitor == monitor) {
+                    return Some((adapter, output));
+                }
+                output_idx += 1;
+            }
+            adapter_idx += 1;
+        }
+    }
+    None
+}
+
+/// Visible bounds of a window in screen pixels (excludes the invisible
+/// resize border, matching what WGC captures)
+fn window_bounds(hwnd: HWND) -> Option<RECT> {
+    let mut rect = RECT::default();
+    unsafe {
+        DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RECT as *mut std::ffi::c_void,
+            std::mem::size_of::<RECT>() as u32,
+        )
+        .ok()?;
+    }
+    Some(rect)
+}
+
+/// EnumWindows callback collecting capturable top-level windows
+unsafe extern "system" fn collect_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = unsafe { &mut *(lparam.0 as *mut Vec<CaptureWindow>) };
+    if let Some(window) = unsafe { capturable_window(hwnd) } {
+        windows.push(window);
+    }
+    true.into()
+}
+
+/// Describe `hwnd` if it is a visible, titled application window of another process
+unsafe fn capturable_window(hwnd: HWND) -> Option<CaptureWindow> {
+    unsafe {
+        if !IsWindowVisible(hwnd).as_bool() || IsIconic(hwnd).as_bool() {
+            return None;
+        }
+        if GetWindowLongW(hwnd, GWL_EXSTYLE) as u32 & WS_EX_TOOLWINDOW.0 != 0 {
+            return None;
+        }
+
+        // Skip our own windows (capturing the viewer would feed back into itself)
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        if pid == std::process::id() {
+            return None;
+        }
+
+        // Cloaked windows (other virtual desktops, suspended apps) render nothing
+        let mut cloaked = 0u32;
+        if DwmGetWindowAttribute(
+            hwnd,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut u32 as *mut std::ffi::c_void,
+            std::mem::size_of::<u32>() as u32,
+        )
+        .is_ok()
+            && cloaked != 0
+        {
+            return None;
+        }
+
+        let len = GetWindowTextLengthW(hwnd);
+        if len <= 0 {
+            return None;
+        }
+        let mut title = vec![0u16; len as usize + 1];
+        let copied = GetWindowTextW(hwnd, &mut title).max(0) as usize;
+        let title = String::from_utf16_lossy(&title[..copied]);
+
+        let bounds = window_bounds(hwnd)?;
+        let width = (bounds.right - bounds.left).max(0) as u32;
+        let height = (bounds.bottom - bounds.top).max(0) as u32;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        Some(CaptureWindow {
+            id: hwnd.0 as usize as u64,
+            title,
+            width,
+            height,
+        })
+    }
+}
+
+/// Maps linear FP16 scRGB to sRGB-encoded BGRA8.
+///
+/// SDR white maps to 1.0; brighter values are compressed above
+/// `HIGHLIGHT_KNEE` using the brightest channel, so highlights keep their hue
+/// instead of clipping to white or shifting towards yellow/cyan.
+struct ToneMapper {
+    /// scRGB value → multiple of SDR white
+    scale: f32,
+    /// Linear [0, 1] → sRGB byte
+    encode: Vec<u8>,
+}
+
+impl ToneMapper {
+    fn new(sdr_white_nits: f32) -> Self {
+        let encode = (0..ENCODE_LUT_SIZE)
+            .map(|i| {
+                let linear = i as f32 / (ENCODE_LUT_SIZE - 1) as f32;
+                (srgb_encode(linear) * 255.0 + 0.5) as u8
+            })
+            .collect();
+
+        Self {
+            scale: SCRGB_UNIT_NITS / sdr_white_nits,
+            encode,
+        }
+    }
+
+    /// Map one linear scRGB pixel to BGRA8
+    fn map_pixel(&self, r: f32, g: f32, b: f32) -> [u8; 4] {
+        // max() also turns NaN into 0
+        let mut r = r.max(0.0) * self.scale;
+        let mut g = g.max(0.0) * self.scale;
+        let mut b = b.max(0.0) * self.scale;
+
+        let peak = r.max(g).max(b);
+        if peak > HIGHLIGHT_KNEE {
+            let k = roll_off(peak) / peak;
+            r *= k;
+            g *= k;
+            b *= k;
+        }
+
+        [self.encode_channel(b), self.encode_channel(g), self.encode_channel(r), 255]
+    }
+
+    fn encode_channel(&self, linear: f32) -> u8 {
+        let index = (linear.clamp(0.0, 1.0) * (ENCODE_LUT_SIZE - 1) as f32 + 0.5) as usize;
+        self.encode[index]
+    }
+
+    /// Tone map rows of R16G16B16A16_FLOAT pixels into packed BGRA8
+    fn map_rows(&self, src: &[u8], row_pitch: usize, width: usize, height: usize) -> Vec<u8> {
+        let mut out = pool::take_empty(width * height * 4);
+        for y in 0..height {
+            let row = &src[y * row_pitch..y * row_pitch + width * 8];
+            for px in row.chunks_exact(8) {
+                let channel = |i: usize| f16_to_f32(u16::from_le_bytes([px[i * 2], px[i * 2 + 1]]));
+                out.extend_from_slice(&self.map_pixel(channel(0), channel(1), channel(2)));
+            }
+        }
+        out
+    }
+}
+
+/// Compress [HIGHLIGHT_KNEE, ∞) into [HIGHLIGHT_KNEE, 1)
+fn roll_off(x: f32) -> f32 {
+    let t = (x - HIGHLIGHT_KNEE) / (1.0 - HIGHLIGHT_KNEE);
+    HIGHLIGHT_KNEE + (1.0 - HIGHLIGHT_KNEE) * t / (1.0 + t)
+}
+
+/// sRGB transfer function (linear → encoded)
+fn srgb_encode(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// IEEE 754 half → single precision
+fn f16_to_f32(bits: u16) -> f32 {
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    let magnitude = match exponent {
+        // Subnormal: mantissa * 2^-24
+        0 => mantissa as f32 / 16_777_216.0,
+        0x1f if mantissa == 0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits(((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    };
+
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f16_to_f32() {
+        assert_eq!(f16_to_f32(0x3C00), 1.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0xC000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert!(f16_to_f32(0x7C00).is_infinite());
+        assert!(f16_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn test_tone_map_sdr_range_and_highlights() {
+        let tone_mapper = ToneMapper::new(SDR_WHITE_NITS);
+        let white = SDR_WHITE_NITS / SCRGB_UNIT_NITS;
+
+        assert_eq!(tone_mapper.map_pixel(0.0, 0.0, 0.0), [0, 0, 0, 255]);
+        assert_eq!(tone_mapper.map_pixel(f32::NAN, -1.0, 0.0), [0, 0, 0, 255]);
+
+        // Mid grey below the knee is passed through (sRGB 0.25 ≈ 137)
+        let grey = tone_mapper.map_pixel(white * 0.25, white * 0.25, white * 0.25);
+        assert!((grey[0] as i32 - 137).abs() <= 1, "grey = {:?}", grey);
+
+        // Highlights brighten monotonically but never clip, and keep their hue
+        let bright = tone_mapper.map_pixel(white * 2.0, 0.0, 0.0);
+        let brighter = tone_mapper.map_pixel(white * 10.0, 0.0, 0.0);
+        assert!(bright[2] < brighter[2] && brighter[2] <= 255);
+        assert_eq!((bright[0], bright[1]), (0, 0));
+        assert_eq!((brighter[0], brighter[1]), (0, 0));
+    }
+}
@@ -0,0 +1,456 @@
+// Clipboard module
+// Clipboard history shared with connected peers. Text and images a peer
+// copies also go straight onto the local clipboard, so copying on one
+// machine and pasting on the other works; each direction can be turned off.
+// Copied files are offered as file transfers, which trusted peers accept
+// without asking.
+
+use crate::network::protocol::{self, ClipboardContent, ClipboardFile, Message};
+use crate::network::quic;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+
+/// Items kept per peer
+pub const MAX_HISTORY_ITEMS: usize = 20;
+
+/// Largest text item synced, in bytes
+pub const MAX_TEXT_BYTES: usize = 64 * 1024;
+
+/// Largest image item synced, as encoded PNG
+pub const MAX_IMAGE_BYTES: usize = 2 * 1024 * 1024;
+
+/// Most files synced from one copy
+pub const MAX_FILES: usize = 20;
+
+/// Largest image side read from the clipboard
+const MAX_IMAGE_SIDE: usize = 4096;
+
+/// How often the local clipboard is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Clipboard sync settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipboardSettings {
+    /// Share local copies with connected peers and keep theirs
+    pub sync: bool,
+    /// Include images, not just text
+    pub images: bool,
+    /// Include copied files
+    pub files: bool,
+    /// Send local copies to peers
+    pub send: bool,
+    /// Keep what peers copy, and put their text and images on the local
+    /// clipboard
+    pub receive: bool,
+}
+
+/// One clipboard entry shared between us and a peer
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardItem {
+    pub id: String,
+    /// Device name of whoever copied it
+    pub from_name: String,
+    pub content: ClipboardContent,
+    /// Unix milliseconds
+    pub timestamp: u64,
+    /// Copied on this device
+    pub is_local: bool,
+}
+
+/// Recent clipboard items, newest first
+#[derive(Debug, Default)]
+struct History {
+    items: VecDeque<ClipboardItem>,
+}
+
+impl History {
+    /// Add an item; copying the same content again moves it to the top
+    fn push(&mut self, item: ClipboardItem) {
+        self.items.retain(|i| i.content != item.content);
+        self.items.push_front(item);
+        self.items.truncate(MAX_HISTORY_ITEMS);
+    }
+}
+
+/// History per peer IP
+static HISTORIES: once_cell::sync::Lazy<RwLock<HashMap<String, History>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Hash of the raw clipboard content last seen or written by us
+static LAST_SEEN: once_cell::sync::Lazy<parking_lot::Mutex<Option<u64>>> =
+    once_cell::sync::Lazy::new(|| parking_lot::Mutex::new(None));
+
+/// Transfer offers announced by a peer's clipboard item that have not
+/// arrived yet
+static EXPECTED_FILES: once_cell::sync::Lazy<parking_lot::Mutex<HashSet<String>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Hash of text or RGBA pixels, cheap enough to compare on every poll
+fn raw_hash(data: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether an item is within the sync size limits
+pub fn within_limits(content: &ClipboardContent) -> bool {
+    match content {
+        ClipboardContent::Text(text) => !text.trim().is_empty() && text.len() <= MAX_TEXT_BYTES,
+        ClipboardContent::Image { png_base64, .. } => png_base64.len() / 4 * 3 <= MAX_IMAGE_BYTES,
+        ClipboardContent::Files(files) => !files.is_empty() && files.len() <= MAX_FILES,
+    }
+}
+
+/// Clipboard history shared with a peer, newest first
+pub fn get_history(peer_ip: &str) -> Vec<ClipboardItem> {
+    HISTORIES
+        .read()
+        .get(peer_ip)
+        .map(|h| h.items.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Forget the history shared with one peer, or with everyone
+pub fn clear_history(peer_ip: Option<&str>) {
+    match peer_ip {
+        Some(ip) => {
+            HISTORIES.write().remove(ip);
+        }
+        None => HISTORIES.write().clear(),
+    }
+}
+
+fn record(peer_ip: &str, item: ClipboardItem) {
+    HISTORIES.write().entry(peer_ip.to_string()).or_default().push(item);
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("clipboard-history", peer_ip);
+    }
+}
+
+/// A peer copied something
+pub fn receive_item(peer_ip: &str, id: String, from_name: String, content: ClipboardContent, timestamp: u64) {
+    let settings = crate::commands::clipboard_settings();
+    if !settings.sync || !settings.receive || !within_limits(&content) {
+        return;
+    }
+    match content {
+        ClipboardContent::Text(_) => paste(content.clone()),
+        ClipboardContent::Image { .. } if settings.images => paste(content.clone()),
+        ClipboardContent::Image { .. } => {}
+        ClipboardContent::Files(ref files) if settings.files => {
+            for file in files {
+                expect_file(peer_ip, &file.file_id);
+            }
+        }
+        ClipboardContent::Files(_) => {}
+    }
+    record(peer_ip, ClipboardItem { id, from_name, content, timestamp, is_local: false });
+}
+
+/// Put what a peer copied on the local clipboard, ready to paste
+fn paste(content: ClipboardContent) {
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = set_clipboard(content) {
+            log::warn!("Failed to put received clipboard content on the clipboard: {}", e);
+        }
+    });
+}
+
+/// A peer's clipboard item announced the transfer `file_id`: accept it
+/// if the offer is already here, else once it arrives
+fn expect_file(peer_ip: &str, file_id: &str) {
+    let offered = crate::transfer::get_transfer_manager()
+        .get_transfer(file_id)
+        .is_some_and(|t| t.status == crate::transfer::TransferStatus::Offered);
+    if !offered {
+        EXPECTED_FILES.lock().insert(file_id.to_string());
+    } else if crate::network::trust::is_trusted(peer_ip) {
+        accept_file(file_id.to_string());
+    }
+}
+
+/// A transfer offer arrived from `peer_ip`. Returns true if it carries a
+/// file the peer copied and is accepted without asking (trusted peers).
+pub fn offer_arrived(peer_ip: &str, file_id: &str) -> bool {
+    if !EXPECTED_FILES.lock().remove(file_id) || !crate::network::trust::is_trusted(peer_ip) {
+        return false;
+    }
+    accept_file(file_id.to_string());
+    true
+}
+
+fn accept_file(file_id: String) {
+    log::info!("Accepting copied file {}", file_id);
+    tokio::spawn(async move {
+        if let Err(e) = crate::commands::accept_file_transfer(file_id.clone(), None).await {
+            log::warn!("Failed to accept copied file {}: {}", file_id, e);
+        }
+    });
+}
+
+/// Put a history item back on the local clipboard
+pub fn copy_item(peer_ip: &str, item_id: &str) -> Result<(), String> {
+    let item = get_history(peer_ip)
+        .into_iter()
+        .find(|i| i.id == item_id)
+        .ok_or_else(|| "Clipboard item not found".to_string())?;
+    set_clipboard(item.content)
+}
+
+/// Put clipboard content on the local clipboard
+fn set_clipboard(content: ClipboardContent) -> Result<(), String> {
+    // Not a new copy: keep the monitor from sending it again
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    match content {
+        ClipboardContent::Text(text) => {
+            *LAST_SEEN.lock() = Some(raw_hash(text.as_bytes()));
+            clipboard.set_text(text).map_err(|e| e.to_string())
+        }
+        ClipboardContent::Image { png_base64, .. } => {
+            use base64::Engine;
+            let png = base64::engine::general_purpose::STANDARD
+                .decode(png_base64)
+                .map_err(|e| e.to_string())?;
+            let image = image::load_from_memory(&png).map_err(|e| e.to_string())?.into_rgba8();
+            *LAST_SEEN.lock() = Some(raw_hash(image.as_raw()));
+            clipboard
+                .set_image(arboard::ImageData {
+                    width: image.width() as usize,
+                    height: image.height() as usize,
+                    bytes: image.into_raw().into(),
+                })
+                .map_err(|e| e.to_string())
+        }
+        // Received files are in the download folder
+        ClipboardContent::Files(_) => Err("Copied files are saved to the download folder".to_string()),
+    }
+}
+
+/// Start watching the local clipboard (idles while sync is off)
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        tokio::spawn(monitor_loop());
+    }
+}
+
+async fn monitor_loop() {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let settings = crate::commands::clipboard_settings();
+        if !settings.sync || !settings.send {
+            // Whatever was copied meanwhile stays private
+            *LAST_SEEN.lock() = None;
+            continue;
+        }
+
+        // Only copies made while sync is on are shared, not what was on the
+        // clipboard when it was turned on
+        let last_seen = *LAST_SEEN.lock();
+        let read = tokio::task::spawn_blocking(move || read_clipboard(settings, last_seen)).await;
+        let Ok(Some((hash, copied))) = read else {
+            continue;
+        };
+        *LAST_SEEN.lock() = Some(hash);
+        let Some(copied) = copied.filter(|_| last_seen.is_some()) else {
+            continue;
+        };
+
+        let peers: Vec<String> = quic::get_all_connections()
+            .iter()
+            .filter(|c| c.is_alive())
+            .map(|c| c.peer_ip())
+            .collect();
+        if peers.is_empty() {
+            continue;
+        }
+
+        let Ok(self_info) = crate::commands::get_self_info() else {
+            continue;
+        };
+        match copied {
+            Copied::Content(content) if within_limits(&content) => {
+                let item = local_item(&self_info.name, content);
+                for peer in peers {
+                    share_item(&peer, item.clone()).await;
+                }
+            }
+            Copied::Content(_) => {}
+            Copied::Files(paths) => {
+                for peer in peers {
+                    share_files(&peer, &paths, &self_info.name).await;
+                }
+            }
+        }
+    }
+}
+
+/// Something copied on this device, for the history
+fn local_item(from_name: &str, content: ClipboardContent) -> ClipboardItem {
+    ClipboardItem {
+        id: uuid::Uuid::new_v4().to_string(),
+        from_name: from_name.to_string(),
+        content,
+        timestamp: now_ms(),
+        is_local: true,
+    }
+}
+
+/// Add a local copy to the history shared with a peer and send it
+async fn share_item(peer: &str, item: ClipboardItem) {
+    let msg = Message::ClipboardItem {
+        id: item.id.clone(),
+        from: item.from_name.clone(),
+        content: item.content.clone(),
+        timestamp: item.timestamp,
+    };
+    let Ok(encoded) = protocol::encode(&msg) else {
+        return;
+    };
+    record(peer, item);
+    if let Err(e) = quic::send_to_peer(peer, &encoded).await {
+        log::debug!("Failed to send clipboard item to {}: {}", peer, e);
+    }
+}
+
+/// Offer copied files to a peer. The item naming them goes first, so the
+/// peer can tell the offers that follow came from its clipboard.
+async fn share_files(peer: &str, paths: &[PathBuf], from_name: &str) {
+    let manager = crate::transfer::get_transfer_manager();
+    let transfers: Vec<_> = paths
+        .iter()
+        .take(MAX_FILES)
+        .filter_map(|path| match manager.offer_file(path, peer) {
+            Ok(transfer) => Some(transfer),
+            Err(e) => {
+                log::warn!("Failed to offer copied file {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    if transfers.is_empty() {
+        return;
+    }
+
+    let files = transfers
+        .iter()
+        .map(|t| ClipboardFile { file_id: t.info.id.clone(), name: t.info.name.clone(), size: t.info.size })
+        .collect();
+    share_item(peer, local_item(from_name, ClipboardContent::Files(files))).await;
+    for transfer in transfers {
+        let offer = Message::FileOffer {
+            file_id: transfer.info.id.clone(),
+            name: transfer.info.name.clone(),
+            size: transfer.info.size,
+            checksum: transfer.info.checksum.clone(),
+        };
+        let Ok(encoded) = protocol::encode(&offer) else {
+            continue;
+        };
+        if let Err(e) = quic::send_to_peer(peer, &encoded).await {
+            log::warn!("Failed to offer copied file {} to {}: {}", transfer.info.name, peer, e);
+        }
+    }
+    log::info!("Offered {} copied files to {}", paths.len().min(MAX_FILES), peer);
+}
+
+/// Something copied locally
+enum Copied {
+    Content(ClipboardContent),
+    /// Files, offered to each peer on its own
+    Files(Vec<PathBuf>),
+}
+
+/// Hash of the current clipboard content (files or an image if allowed,
+/// else text), and the content itself if it changed since `last_seen`
+fn read_clipboard(settings: ClipboardSettings, last_seen: Option<u64>) -> Option<(u64, Option<Copied>)> {
+    let mut clipboard = arboard::Clipboard::new().ok()?;
+    // Copied files often come with their names as text too
+    if settings.files {
+        let paths: Vec<PathBuf> = clipboard.get().file_list().unwrap_or_default().into_iter().filter(|p| p.is_file()).collect();
+        if !paths.is_empty() {
+            let names: Vec<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+            let hash = raw_hash(names.join("\n").as_bytes());
+            return Some((hash, (last_seen != Some(hash)).then_some(Copied::Files(paths))));
+        }
+    }
+    if let Ok(text) = clipboard.get_text() {
+        let hash = raw_hash(text.as_bytes());
+        return Some((hash, (last_seen != Some(hash)).then_some(Copied::Content(ClipboardContent::Text(text)))));
+    }
+    if !settings.images {
+        return None;
+    }
+    let image = clipboard.get_image().ok()?;
+    let hash = raw_hash(&image.bytes);
+    // Only encode images that are new and not too large
+    if last_seen == Some(hash) || image.width > MAX_IMAGE_SIDE || image.height > MAX_IMAGE_SIDE {
+        return Some((hash, None));
+    }
+    let rgba = image::RgbaImage::from_raw(image.width as u32, image.height as u32, image.bytes.into_owned())?;
+    let mut png = Vec::new();
+    rgba.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).ok()?;
+    use base64::Engine;
+    let content = ClipboardContent::Image {
+        width: image.width as u32,
+        height: image.height as u32,
+        png_base64: base64::engine::general_purpose::STANDARD.encode(png),
+    };
+    Some((hash, Some(Copied::Content(content))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(text: &str) -> ClipboardItem {
+        ClipboardItem {
+            id: text.to_string(),
+            from_name: "peer".to_string(),
+            content: ClipboardContent::Text(text.to_string()),
+            timestamp: 0,
+            is_local: false,
+        }
+    }
+
+    #[test]
+    fn test_history_is_capped_and_deduplicated() {
+        let mut history = History::default();
+        for i in 0..MAX_HISTORY_ITEMS + 5 {
+            history.push(item(&i.to_string()));
+        }
+        assert_eq!(history.items.len(), MAX_HISTORY_ITEMS);
+        assert_eq!(history.items[0].id, (MAX_HISTORY_ITEMS + 4).to_string());
+
+        history.push(item("10"));
+        assert_eq!(history.items.len(), MAX_HISTORY_ITEMS);
+        assert_eq!(history.items.iter().filter(|i| i.id == "10").count(), 1);
+        assert_eq!(history.items[0].id, "10");
+    }
+
+    #[test]
+    fn test_size_limits() {
+        assert!(within_limits(&ClipboardContent::Text("hello".to_string())));
+        assert!(!within_limits(&ClipboardContent::Text("  ".to_string())));
+        assert!(!within_limits(&ClipboardContent::Text("x".repeat(MAX_TEXT_BYTES + 1))));
+
+        let file = ClipboardFile { file_id: "1".to_string(), name: "a.png".to_string(), size: 1 };
+        assert!(within_limits(&ClipboardContent::Files(vec![file.clone()])));
+        assert!(!within_limits(&ClipboardContent::Files(Vec::new())));
+        assert!(!within_limits(&ClipboardContent::Files(vec![file; MAX_FILES + 1])));
+    }
+}
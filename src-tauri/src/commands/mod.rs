@@ -1,11 +1,12 @@
 //! Tauri commands - Frontend API
 
+use crate::app_state::Slice;
 use crate::capture::ScreenCapture;
 use crate::network::discovery::{self, DeviceStatus, DiscoveredDevice};
 use crate::network::quic;
+use crate::network::{ConnectErrorKind, ConnectFailure, NetworkError};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
 use tauri::Manager;
 
 /// Display information for screen capture
@@ -62,6 +63,409 @@ pub async fn get_displays() -> Result<Vec<DisplayInfo>, String> {
         .collect())
 }
 
+/// Get application windows that can be shared on their own.
+/// Empty when the capture backend has no per-window support.
+#[tauri::command]
+pub async fn get_capture_windows() -> Result<Vec<crate::capture::CaptureWindow>, String> {
+    ensure_capture_initialized()?;
+
+    let capture = CAPTURE.lock();
+    let capture = capture
+        .as_ref()
+        .ok_or_else(|| "Capture not initialized".to_string())?;
+
+    capture
+        .get_windows()
+        .map_err(|e| format!("Failed to get windows: {}", e))
+}
+
+/// Set which apps are hidden from the shared screen: LAN Meeting's own
+/// windows and the given bundle identifiers (e.g. password managers).
+/// Applies to running captures; only the ScreenCaptureKit backend on macOS
+/// can hide windows, other backends ignore the list.
+#[tauri::command]
+pub async fn set_capture_exclusions(exclude_self: bool, bundle_ids: Vec<String>) -> Result<(), String> {
+    log::info!(
+        "Capture exclusions: self={}, apps={:?}",
+        exclude_self, bundle_ids
+    );
+    crate::capture::exclusions::set(crate::capture::exclusions::CaptureExclusions {
+        exclude_self,
+        bundle_ids,
+    });
+    Ok(())
+}
+
+/// Get the software encoder thread settings
+#[tauri::command]
+pub fn get_encode_threading() -> crate::encoder::threading::EncodeThreading {
+    crate::encoder::threading::get()
+}
+
+/// Set software encoder threads (0 = all cores but `reserved_cores`), and
+/// optionally pin encoding off the reserved cores and lower its priority.
+/// Thread counts apply to the next encoder; pinning and priority right away.
+#[tauri::command]
+pub fn set_encode_threading(
+    threads: u32,
+    reserved_cores: u32,
+    pin_cores: bool,
+    low_priority: bool,
+) -> Result<(), String> {
+    let settings = crate::encoder::threading::EncodeThreading {
+        threads,
+        reserved_cores,
+        pin_cores,
+        low_priority,
+    };
+    log::info!(
+        "Encode threading: {:?} ({} cores, {} encoder threads)",
+        settings,
+        crate::encoder::threading::available_cores(),
+        settings.worker_threads(crate::encoder::threading::available_cores())
+    );
+    crate::encoder::threading::set(settings);
+    Ok(())
+}
+
+/// Get per-stage frame timing percentiles (capture/scale/encode/send on the
+/// sharer, receive/decode/upload/render on the viewer)
+#[tauri::command]
+pub fn get_timing_histograms() -> Vec<crate::streaming::timing::StageTiming> {
+    crate::streaming::timing::summary()
+}
+
+/// Glass-to-glass latency of the stream being watched and the clock offset
+/// to its sharer
+#[tauri::command]
+pub fn get_stream_stats() -> crate::streaming::latency::StreamStats {
+    crate::streaming::latency::stream_stats()
+}
+
+/// Encode/send rates and drops of the screens we share, over the last second
+#[tauri::command]
+pub fn get_sharer_stats() -> crate::streaming::stats::SharerStats {
+    crate::streaming::stats::sharer_stats()
+}
+
+/// Receive/render rates, drops and decode time of the streams we watch,
+/// over the last second
+#[tauri::command]
+pub fn get_viewer_stats() -> crate::streaming::stats::ViewerStats {
+    crate::streaming::stats::viewer_stats()
+}
+
+/// Connect attempts and failures by kind on our QUIC endpoint
+#[tauri::command]
+pub fn get_quic_stats() -> Option<quic::EndpointStats> {
+    crate::get_quic_endpoint().map(|endpoint| endpoint.stats())
+}
+
+/// Whether viewer windows show the latency HUD
+pub fn latency_hud_enabled() -> bool {
+    SETTINGS.read().latency_hud
+}
+
+/// Color filter new viewer windows start with
+pub fn viewer_color_filter() -> crate::renderer::ColorFilter {
+    crate::renderer::ColorFilter::from_name(&SETTINGS.read().color_filter).unwrap_or_default()
+}
+
+/// Fit mode new viewer windows start with
+pub fn viewer_fit_mode() -> crate::renderer::FitMode {
+    crate::renderer::FitMode::from_name(&SETTINGS.read().fit_mode).unwrap_or_default()
+}
+
+/// Encoder forced in settings, if any
+pub fn forced_encoder() -> Option<String> {
+    let name = SETTINGS.read().forced_encoder.clone();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Whether encoded frames are validated before sending
+pub fn bitstream_validation_enabled() -> bool {
+    SETTINGS.read().validate_bitstream
+}
+
+/// Whether the area around the pointer is encoded at higher quality
+pub fn cursor_roi_enabled() -> bool {
+    SETTINGS.read().cursor_roi
+}
+
+/// H.264 decoders to try first, by `DecoderKind::name` (empty = default order)
+pub fn decoder_preference() -> Vec<String> {
+    SETTINGS.read().decoder_preference.clone()
+}
+
+/// Name we introduce ourselves to peers with: the one set in settings,
+/// else the hostname
+pub fn display_name() -> String {
+    let name = SETTINGS.read().device_name.trim().to_string();
+    if !name.is_empty() {
+        return name;
+    }
+    hostname::get()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "Unknown".to_string())
+}
+
+/// Our avatar PNG, base64 encoded (empty = none)
+pub fn avatar_setting() -> String {
+    SETTINGS.read().avatar.clone()
+}
+
+/// Whether captures are scaled on the GPU before encoding
+pub fn gpu_preprocess_enabled() -> bool {
+    SETTINGS.read().gpu_preprocess
+}
+
+/// Encoder preset, keyframe interval and peak bitrate from settings
+pub fn encoder_tuning() -> crate::encoder::EncoderTuning {
+    let s = SETTINGS.read();
+    crate::encoder::EncoderTuning {
+        preset: crate::encoder::EncoderPreset::from_name(&s.encoder_preset).unwrap_or_default(),
+        keyframe_interval_secs: s.keyframe_interval_secs.max(1),
+        max_bitrate: (s.max_bitrate_mbps > 0).then(|| s.max_bitrate_mbps.saturating_mul(1_000_000)),
+    }
+}
+
+/// Change the encoder preset, keyframe interval and peak bitrate, and apply
+/// them to a share that is running. Viewers get a new stream start, so the
+/// change costs one keyframe.
+#[tauri::command]
+pub fn update_stream_settings(
+    preset: String,
+    keyframe_interval_secs: u32,
+    max_bitrate_mbps: u32,
+) -> Result<(), String> {
+    if crate::encoder::EncoderPreset::from_name(&preset).is_none() {
+        return Err(format!("Unknown encoder preset: {}", preset));
+    }
+    if !(1..=60).contains(&keyframe_interval_secs) {
+        return Err("Keyframe interval must be 1 to 60 seconds".to_string());
+    }
+
+    let settings = {
+        let mut s = SETTINGS.write();
+        s.encoder_preset = preset;
+        s.keyframe_interval_secs = keyframe_interval_secs;
+        s.max_bitrate_mbps = max_bitrate_mbps;
+        s.clone()
+    };
+    save_settings_to_disk(&settings);
+    crate::app_state::changed(Slice::Settings);
+    apply_encoder_tuning();
+    Ok(())
+}
+
+/// Hand the current encoder settings to whatever is being shared
+fn apply_encoder_tuning() {
+    let tuning = encoder_tuning();
+    let manager = crate::streaming::get_streaming_manager();
+    if let Some(manager) = manager.write().as_mut().filter(|m| m.is_streaming()) {
+        if let Err(e) = manager.set_tuning(tuning) {
+            log::warn!("Failed to apply encoder settings: {}", e);
+        }
+    }
+    crate::simple_streaming::set_encoder_tuning(tuning);
+}
+
+/// Codec new screen shares are encoded with
+pub fn preferred_codec() -> crate::encoder::Codec {
+    crate::encoder::Codec::from_name(&SETTINGS.read().video_codec).unwrap_or_default()
+}
+
+/// Color space new screen shares are converted to YUV with
+pub fn preferred_color_space() -> crate::encoder::color::ColorSpace {
+    crate::encoder::color::ColorSpace::from_name(&SETTINGS.read().color_space)
+        .unwrap_or(crate::encoder::color::ColorSpace::BT709)
+}
+
+/// Encoders this build knows of and whether each works on this machine.
+/// The first call opens every encoder once, which can take a few seconds.
+#[tauri::command]
+pub async fn list_encoders() -> Result<Vec<crate::encoder::EncoderInfo>, String> {
+    tokio::task::spawn_blocking(crate::encoder::list_encoders)
+        .await
+        .map_err(|e| format!("Encoder probe failed: {}", e))
+}
+
+/// Decoders this build knows of and whether each opens on this machine
+#[tauri::command]
+pub async fn list_decoders() -> Result<Vec<crate::decoder::DecoderInfo>, String> {
+    tokio::task::spawn_blocking(crate::decoder::list_decoders)
+        .await
+        .map_err(|e| format!("Decoder probe failed: {}", e))
+}
+
+/// Save the next `count` decoded frames of the streams we watch (or only
+/// `peer_ip`'s) for codec debugging, as "png" or raw "yuv", with the
+/// encoded bitstream if `bitstream` is set. Returns the folder; a
+/// "frame-dump-finished" event follows once everything is written.
+#[tauri::command]
+pub fn dump_frames(
+    count: u32,
+    format: String,
+    bitstream: bool,
+    peer_ip: Option<String>,
+    dir: Option<String>,
+) -> Result<String, String> {
+    use crate::streaming::frame_dump::{self, DumpFormat};
+
+    let format = DumpFormat::from_name(&format).ok_or_else(|| format!("Unknown dump format: {}", format))?;
+    let root = dir
+        .map(std::path::PathBuf::from)
+        .or_else(frame_dump::default_root)
+        .ok_or("No folder to save frames to")?;
+    frame_dump::start(&root, count, format, bitstream, peer_ip).map(|dir| dir.to_string_lossy().to_string())
+}
+
+/// Stop a frame dump before it has all its frames
+#[tauri::command]
+pub fn stop_frame_dump() {
+    crate::streaming::frame_dump::stop();
+}
+
+/// Record the screen we share to an MP4 file under `dir` (the download
+/// folder by default) from the frames encoded for viewers; returns the
+/// file. "recording-changed" events follow when it starts and stops.
+#[tauri::command]
+pub fn start_recording(dir: Option<String>) -> Result<String, String> {
+    use crate::streaming::recording;
+
+    let dir = dir.map(std::path::PathBuf::from).unwrap_or_else(recording::default_dir);
+    recording::start(&dir).map(|path| path.to_string_lossy().to_string())
+}
+
+/// Stop recording the screen we share; returns the file, if it was recorded
+#[tauri::command]
+pub fn stop_recording() -> Option<String> {
+    crate::streaming::recording::stop().map(|path| path.to_string_lossy().to_string())
+}
+
+/// File the screen we share is being recorded to, if it is
+#[tauri::command]
+pub fn get_recording() -> Option<String> {
+    crate::streaming::recording::current().map(|path| path.to_string_lossy().to_string())
+}
+
+/// Record `peer_ip`'s screen, which we watch, to an MP4 under `dir` (the
+/// download folder by default) as the frames arrive; returns the file. The
+/// viewer window shows a REC badge and the sharer is told.
+#[tauri::command]
+pub async fn start_viewer_recording(peer_ip: String, dir: Option<String>) -> Result<String, String> {
+    use crate::streaming::recording;
+
+    let dir = dir.map(std::path::PathBuf::from).unwrap_or_else(recording::default_dir);
+    let path = recording::start_viewer(&peer_ip, &dir)?;
+    if let Err(e) = crate::streaming::notify_recording(&peer_ip, true).await {
+        log::warn!("Failed to tell {} we record its screen: {}", peer_ip, e);
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Stop recording `peer_ip`'s screen; returns the file, if it was recorded
+#[tauri::command]
+pub async fn stop_viewer_recording(peer_ip: String) -> Option<String> {
+    let path = crate::streaming::recording::stop_viewer(&peer_ip)?;
+    if let Err(e) = crate::streaming::notify_recording(&peer_ip, false).await {
+        log::warn!("Failed to tell {} we stopped recording its screen: {}", peer_ip, e);
+    }
+    Some(path.to_string_lossy().to_string())
+}
+
+/// Save the last `seconds` (30 by default, and at most) of `peer_ip`'s
+/// screen, which we watch, as an MP4 under `dir` (the download folder by
+/// default); returns the file. No recording has to be running.
+#[tauri::command]
+pub async fn save_clip(peer_ip: String, seconds: Option<u64>, dir: Option<String>) -> Result<String, String> {
+    use crate::streaming::clip;
+
+    let span = seconds.map_or(clip::KEPT, std::time::Duration::from_secs);
+    let dir = dir.map(std::path::PathBuf::from).unwrap_or_else(crate::streaming::recording::default_dir);
+    tokio::task::spawn_blocking(move || clip::save(&peer_ip, span, &dir))
+        .await
+        .map_err(|e| format!("Clip task failed: {}", e))?
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Files the screens we watch are being recorded to, by sharer
+#[tauri::command]
+pub fn get_viewer_recordings() -> std::collections::HashMap<String, String> {
+    crate::streaming::recording::viewer_recordings()
+        .into_iter()
+        .map(|(peer_ip, path)| (peer_ip, path.to_string_lossy().to_string()))
+        .collect()
+}
+
+/// Save the frame the viewer window of `peer_ip`'s screen last drew as a
+/// PNG in the download folder; returns the file. A "viewer-snapshot" event
+/// with the path follows.
+#[tauri::command]
+pub async fn capture_viewer_snapshot(peer_ip: String) -> Result<String, String> {
+    use crate::renderer::snapshot;
+
+    tokio::task::spawn_blocking(move || snapshot::capture(&peer_ip, &snapshot::default_dir()))
+    .await
+    .map_err(|e| format!("Snapshot task failed: {}", e))?
+    .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Shrink `peer_ip`'s viewer window into the picture-in-picture mini viewer,
+/// or restore it
+#[tauri::command]
+pub fn set_viewer_pip(peer_ip: String, enabled: bool) -> Result<(), String> {
+    crate::renderer::viewers::get(&peer_ip)
+        .ok_or_else(|| format!("Not viewing {}", peer_ip))?
+        .set_pip(enabled)
+        .map_err(|e| e.to_string())
+}
+
+/// The viewer windows we have open, oldest first
+#[tauri::command]
+pub fn list_viewer_windows() -> Vec<crate::renderer::viewers::ViewerWindow> {
+    crate::renderer::viewers::list()
+}
+
+/// Lay every viewer window out in a grid on the primary screen, to watch
+/// several screens at once; returns how many there are
+#[tauri::command]
+pub fn tile_viewer_windows() -> usize {
+    crate::renderer::viewers::tile()
+}
+
+/// Draw a shape over the screen we share, for every viewer; a "clear"
+/// annotation takes all shapes off
+#[tauri::command]
+pub fn annotate_shared_screen(annotation: crate::renderer::Annotation) -> Result<(), String> {
+    crate::simple_streaming::annotate(&annotation)
+}
+
+/// Draw a shape over `peer_ip`'s screen in our viewer window, for the sharer
+/// and the other viewers too
+#[tauri::command]
+pub fn annotate_viewer(peer_ip: String, annotation: crate::renderer::Annotation) -> Result<(), String> {
+    crate::renderer::viewers::get(&peer_ip)
+        .ok_or_else(|| format!("Not viewing {}", peer_ip))?
+        .annotate(annotation)
+        .map_err(|e| e.to_string())
+}
+
+/// Clear the frame timing histograms, e.g. before measuring a change
+#[tauri::command]
+pub fn reset_timing_histograms() {
+    crate::streaming::timing::reset();
+}
+
+/// Write the frame timing percentiles to a CSV file
+#[tauri::command]
+pub async fn export_timing_csv(path: String) -> Result<(), String> {
+    let csv = crate::streaming::timing::to_csv(&crate::streaming::timing::summary());
+    std::fs::write(&path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    log::info!("Frame timing exported to {}", path);
+    Ok(())
+}
+
 /// Start screen capture for a specific display
 #[tauri::command]
 pub async fn start_capture(display_id: u32) -> Result<(), String> {
@@ -132,19 +536,189 @@ pub async fn get_devices() -> Result<Vec<DiscoveredDevice>, String> {
     Ok(discovery::get_devices())
 }
 
+/// Devices whose name (or its pinyin), or IP, fuzzily matches `query`:
+/// favorites first, then reachable devices, then by name
+#[tauri::command]
+pub fn search_devices(query: String) -> Vec<crate::network::device_search::DeviceEntry> {
+    crate::network::quic::cleanup_dead_connections();
+    let favorites = SETTINGS.read().favorite_devices.clone();
+    let devices = crate::network::known::with_offline(discovery::get_devices());
+    crate::network::device_search::search(devices, &query, &favorites)
+}
+
+/// Devices seen before, most recent first
+#[tauri::command]
+pub fn get_known_devices() -> Vec<crate::network::known::KnownDevice> {
+    crate::network::known::list()
+}
+
+/// Give a known device a nickname; an empty one clears it
+#[tauri::command]
+pub fn rename_device(device_id: String, nickname: String) -> Result<(), String> {
+    crate::network::known::rename(&device_id, &nickname)?;
+    crate::app_state::changed(Slice::Devices);
+    Ok(())
+}
+
+/// Forget a known device, along with its trust and favorite
+#[tauri::command]
+pub async fn forget_device(device_id: String) -> Result<(), String> {
+    crate::network::known::forget(&device_id);
+    crate::network::trust::untrust(&device_id).await;
+    crate::app_state::changed(Slice::Devices);
+    set_device_favorite(device_id, false)
+}
+
+/// Block a device: whatever it is doing here stops, its handshakes are
+/// turned away and discovery no longer lists it
+#[tauri::command]
+pub async fn block_device(device_id: String) -> Result<(), String> {
+    let device = discovery::get_devices().into_iter().find(|d| d.id == device_id);
+    let conn = device.as_ref().and_then(|d| quic::find_connection(&d.ip));
+    crate::network::known::block(&device_id, conn.as_ref().and_then(|c| c.peer_fingerprint()))?;
+    log::info!("Blocked device {}", device_id);
+
+    crate::network::trust::untrust(&device_id).await;
+    if let Some(device) = device {
+        crate::network::access::kick(&device.ip).await;
+        for conn in quic::get_all_connections().into_iter().filter(|c| c.peer_ip() == device.ip) {
+            conn.close();
+        }
+        quic::remove_connection_by_ip(&device.ip);
+        discovery::remove_device(&device_id);
+        if let Some(handle) = crate::APP_HANDLE.get() {
+            use tauri::Emitter;
+            let _ = handle.emit("device-removed", &device_id);
+        }
+    }
+    crate::app_state::changed(Slice::Devices);
+    Ok(())
+}
+
+/// Lift the block on a device; discovery lists it again once it announces
+#[tauri::command]
+pub fn unblock_device(device_id: String) -> Result<(), String> {
+    crate::network::known::unblock(&device_id)?;
+    log::info!("Unblocked device {}", device_id);
+    crate::app_state::changed(Slice::Devices);
+    Ok(())
+}
+
+/// Pin a device to the top of the device list, or unpin it
+#[tauri::command]
+pub fn set_device_favorite(device_id: String, favorite: bool) -> Result<(), String> {
+    let settings = {
+        let mut s = SETTINGS.write();
+        s.favorite_devices.retain(|id| *id != device_id);
+        if favorite {
+            s.favorite_devices.push(device_id);
+        }
+        s.clone()
+    };
+    save_settings_to_disk(&settings);
+    crate::app_state::changed(Slice::Settings);
+    Ok(())
+}
+
+/// Devices trusted permanently
+pub fn trusted_devices() -> Vec<String> {
+    SETTINGS.read().trusted_devices.clone()
+}
+
+/// Add a device to the permanently trusted ones, or remove it
+pub fn set_trusted_permanently(device_id: &str, trusted: bool) {
+    let settings = {
+        let mut s = SETTINGS.write();
+        let before = s.trusted_devices.len();
+        s.trusted_devices.retain(|id| id != device_id);
+        if trusted {
+            s.trusted_devices.push(device_id.to_string());
+        } else if s.trusted_devices.len() == before {
+            return;
+        }
+        s.clone()
+    };
+    save_settings_to_disk(&settings);
+    crate::app_state::changed(Slice::Settings);
+}
+
+/// Answer a "viewer-requested": let the peer watch our screen or not
+#[tauri::command]
+pub fn answer_viewer_request(id: String, allow: bool) -> Result<(), String> {
+    crate::network::access::answer(&id, allow)
+}
+
+/// Stop streaming to a viewer; they have to be let in again to come back
+#[tauri::command]
+pub async fn kick_viewer(peer_ip: String) -> Result<(), String> {
+    if crate::network::access::kick(&peer_ip).await {
+        Ok(())
+    } else {
+        Err(format!("{} is not watching", peer_ip))
+    }
+}
+
+/// Devices trusted permanently, until restart or for the current meeting
+#[tauri::command]
+pub fn list_trusted_devices() -> Vec<crate::network::trust::TrustedDevice> {
+    crate::network::trust::list()
+}
+
+/// Stop trusting a device, taking back control it holds
+#[tauri::command]
+pub async fn untrust_device(device_id: String) -> Result<(), String> {
+    crate::network::trust::untrust(&device_id).await;
+    Ok(())
+}
+
+/// Re-query the network for devices now (also works in passive mode)
+#[tauri::command]
+pub fn refresh_devices_now() -> Result<(), String> {
+    log::info!("Refreshing devices");
+    discovery::refresh_now().map_err(|e| e.to_string())
+}
+
 /// Add a device manually by IP address
 #[tauri::command]
-pub async fn add_manual_device(ip: String) -> Result<DiscoveredDevice, String> {
+pub async fn add_manual_device(ip: String, password: Option<String>) -> Result<DiscoveredDevice, ConnectFailure> {
     log::info!("Adding manual device: {}", ip);
-    discovery::add_manual_device(ip, 19876)
-        .await
-        .map_err(|e| e.to_string())
+    if let Some(password) = password {
+        crate::network::password::remember(ip.trim(), &password);
+    }
+    Ok(discovery::add_manual_device(ip, 19876).await?)
 }
 
-/// Connect to a remote device
+/// Probe every host of an IPv4 subnet (e.g. "192.168.1.0/24") for the
+/// service, for networks where mDNS does not work; returns the addresses that
+/// answer, to be added with `add_manual_device`
 #[tauri::command]
-pub async fn connect_to_device(device_id: String) -> Result<(), String> {
-    use crate::network::protocol;
+pub async fn scan_subnet(cidr: String) -> Result<Vec<String>, ConnectFailure> {
+    Ok(crate::network::scan::scan(&cidr).await?)
+}
+
+/// Answer a "pairing-request": whether the code shown matches the peer's
+#[tauri::command]
+pub fn answer_pairing(id: String, accept: bool) -> Result<(), String> {
+    crate::network::pairing::answer(&id, accept)
+}
+
+/// Link quality of the connection to a peer, by device id or IP
+#[tauri::command]
+pub fn get_connection_stats(peer: String) -> Result<crate::network::link::ConnectionStats, String> {
+    crate::network::link::connection_stats(&peer).ok_or_else(|| format!("Not connected to {}", peer))
+}
+
+/// Peers watching our screen, with either pipeline
+#[tauri::command]
+pub fn get_current_viewers() -> Vec<crate::streaming::audience::Viewer> {
+    crate::streaming::audience::current()
+}
+
+/// Connect to a remote device, with the password of the meeting it holds
+/// if it asked for one
+#[tauri::command]
+pub async fn connect_to_device(device_id: String, password: Option<String>) -> Result<(), ConnectFailure> {
+    use crate::network::{pairing, protocol};
 
     log::info!("Connecting to device {}", device_id);
 
@@ -153,21 +727,21 @@ pub async fn connect_to_device(device_id: String) -> Result<(), String> {
         .into_iter()
         .find(|d| d.id == device_id)
         .ok_or_else(|| format!("Device not found: {}", device_id))?;
+    if let Some(password) = password {
+        crate::network::password::remember(&device.ip, &password);
+    }
 
     // Parse address
-    let addr: SocketAddr = format!("{}:{}", device.ip, device.port)
-        .parse()
-        .map_err(|e| format!("Invalid address: {}", e))?;
+    let addr = crate::network::address::socket_addr(&device.ip, device.port)
+        .ok_or_else(|| format!("Invalid address: {}", device.ip))?;
 
     // Get QUIC endpoint
-    let endpoint = crate::get_quic_endpoint()
-        .ok_or_else(|| "QUIC endpoint not initialized".to_string())?;
+    let endpoint = crate::get_quic_endpoint().ok_or_else(|| {
+        NetworkError::Connect(ConnectErrorKind::ServiceStopped, "QUIC endpoint not initialized".to_string())
+    })?;
 
     // Connect to device
-    let conn = endpoint
-        .connect(addr)
-        .await
-        .map_err(|e| format!("Connection failed: {}", e))?;
+    let conn = endpoint.connect_with_retry(addr, std::time::Duration::from_secs(5)).await?;
 
     log::info!("Connected to {} at {}", device.name, conn.remote_addr());
 
@@ -182,14 +756,13 @@ pub async fn connect_to_device(device_id: String) -> Result<(), String> {
 
     // Create and send proper protocol handshake
     let our_id = discovery::get_our_device_id();
-    let our_name = hostname::get()
-        .map(|h| h.to_string_lossy().to_string())
-        .unwrap_or_else(|_| "Unknown".to_string());
-
-    let handshake = protocol::create_handshake(&our_id, &our_name);
-    let encoded = protocol::encode(&handshake)
+    let handshake = protocol::create_handshake(&our_id, &display_name());
+    let encoded = protocol::encode_as(&handshake, protocol::LEGACY_VERSION)
         .map_err(|e| format!("Failed to encode handshake: {}", e))?;
 
+    crate::network::password::send_proof(&conn, &mut stream)
+        .await
+        .map_err(|e| format!("Failed to send meeting password: {}", e))?;
     stream
         .send_framed(&encoded)
         .await
@@ -197,28 +770,20 @@ pub async fn connect_to_device(device_id: String) -> Result<(), String> {
 
     log::info!("Handshake sent to {}", device.name);
 
-    // Wait for handshake acknowledgment
-    let response = stream
-        .recv_framed()
-        .await
-        .map_err(|e| format!("Failed to receive handshake ack: {}", e))?;
-
-    let ack = protocol::decode(&response)
-        .map_err(|e| format!("Failed to decode handshake ack: {}", e))?;
-
-    match ack {
-        protocol::Message::HandshakeAck { accepted, reason, name, .. } => {
-            if accepted {
-                log::info!("Connection accepted by {}", name);
-                Ok(())
-            } else {
-                let err_msg = reason.unwrap_or_else(|| "Unknown reason".to_string());
-                log::warn!("Connection rejected by {}: {}", name, err_msg);
-                Err(format!("Connection rejected: {}", err_msg))
-            }
-        }
-        _ => Err("Unexpected response to handshake".to_string()),
+    // Wait for handshake acknowledgment, pairing first if we never met
+    let answer = pairing::await_answer(&conn, &mut stream, &device.name, Some(&device_id)).await?;
+    log::info!("Connection accepted by {}", answer.name);
+    pairing::complete(&conn, &device_id);
+    crate::network::password::admit(&conn);
+    discovery::set_device_profile(
+        &device_id,
+        &answer.name,
+        crate::network::profile::checked_avatar(answer.avatar.as_deref()),
+    );
+    if let Err(e) = crate::network::profile::send_avatar(&mut stream).await {
+        log::warn!("Failed to send our avatar to {}: {}", answer.name, e);
     }
+    Ok(())
 }
 
 /// Disconnect from current session
@@ -229,7 +794,9 @@ pub async fn disconnect(device_id: Option<String>) -> Result<(), String> {
     if let Some(id) = &device_id {
         // Get device to find connection ID
         if let Some(device) = discovery::get_devices().into_iter().find(|d| d.id == *id) {
-            let conn_id = format!("{}:{}", device.ip, device.port);
+            let conn_id = crate::network::address::socket_addr(&device.ip, device.port)
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
 
             // Close and remove connection
             if let Some(conn) = quic::get_connection(&conn_id) {
@@ -258,22 +825,21 @@ pub async fn disconnect(device_id: Option<String>) -> Result<(), String> {
 /// Get our own device info
 #[tauri::command]
 pub fn get_self_info() -> Result<SelfInfo, String> {
-    let hostname = hostname::get()
-        .map(|h| h.to_string_lossy().to_string())
-        .unwrap_or_else(|_| "Unknown".to_string());
-
     // Get local IP address
     let ip = get_local_ip().unwrap_or_else(|| "127.0.0.1".to_string());
 
     Ok(SelfInfo {
         id: discovery::get_our_device_id().to_string(),
-        name: hostname,
+        name: display_name(),
         ip,
+        avatar: Some(avatar_setting()).filter(|a| !a.is_empty()),
+        fingerprint: quic::our_fingerprint(),
     })
 }
 
-/// Check if an IPv4 address is a real private LAN IP
-/// (not a VPN/proxy virtual interface like 198.18.0.0/15)
+/// Check if an address is a real private LAN IP (not a VPN/proxy virtual
+/// interface like 198.18.0.0/15). For IPv6 that is a unique local
+/// (fc00::/7) or link-local (fe80::/10) address.
 pub fn is_real_lan_ip(ip: &std::net::IpAddr) -> bool {
     match ip {
         std::net::IpAddr::V4(v4) => {
@@ -305,7 +871,9 @@ pub fn is_real_lan_ip(ip: &std::net::IpAddr) -> bool {
             }
             false
         }
-        std::net::IpAddr::V6(_) => false,
+        std::net::IpAddr::V6(v6) => {
+            (v6.segments()[0] & 0xfe00) == 0xfc00 || crate::network::address::is_link_local_v6(v6)
+        }
     }
 }
 
@@ -347,30 +915,38 @@ pub fn is_same_subnet(ip: &std::net::IpAddr, subnets: &[(u32, u32)]) -> bool {
 }
 
 /// Get local IP address by enumerating network interfaces.
-/// Prefers real LAN IPs (192.168/10/172.16) over VPN/proxy virtual interfaces.
+/// Prefers real LAN IPs (192.168/10/172.16) over VPN/proxy virtual interfaces,
+/// and IPv4 over IPv6; on an IPv6-only segment a unique local address, else
+/// a link-local one with its interface (`fe80::1%3`).
 fn get_local_ip() -> Option<String> {
+    use std::net::IpAddr;
+
     let ifaces = if_addrs::get_if_addrs().ok()?;
 
-    let mut candidates: Vec<std::net::IpAddr> = ifaces
+    let mut candidates: Vec<(IpAddr, u32)> = ifaces
         .iter()
         .filter(|iface| !iface.is_loopback())
-        .map(|iface| iface.ip())
-        .filter(|ip| ip.is_ipv4())
+        .map(|iface| (iface.ip(), iface.index.unwrap_or(0)))
         .collect();
 
     // Deduplicate
-    candidates.sort_by_key(|ip| ip.to_string());
-    candidates.dedup();
+    candidates.sort_by_key(|(ip, _)| ip.to_string());
+    candidates.dedup_by_key(|(ip, _)| *ip);
 
     log::debug!("Network interface IPs: {:?}", candidates);
 
-    // Prefer real LAN IPs over VPN IPs
-    if let Some(lan_ip) = candidates.iter().find(|ip| is_real_lan_ip(ip)) {
-        return Some(lan_ip.to_string());
-    }
-
-    // Fall back to any non-loopback IPv4
-    candidates.first().map(|ip| ip.to_string())
+    let rank = |ip: &IpAddr| match ip {
+        IpAddr::V4(_) if is_real_lan_ip(ip) => 0,
+        IpAddr::V4(_) => 1,
+        IpAddr::V6(v6) if crate::network::address::is_link_local_v6(v6) => 3,
+        IpAddr::V6(_) => 2,
+    };
+    let (ip, index) = candidates.into_iter().min_by_key(|(ip, _)| rank(ip))?;
+    let addr = match ip {
+        IpAddr::V6(v6) => std::net::SocketAddr::V6(std::net::SocketAddrV6::new(v6, 0, 0, index)),
+        IpAddr::V4(v4) => std::net::SocketAddr::new(v4.into(), 0),
+    };
+    Some(crate::network::address::peer_ip(addr))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -378,6 +954,10 @@ pub struct SelfInfo {
     pub id: String,
     pub name: String,
     pub ip: String,
+    /// Avatar PNG, base64 encoded
+    pub avatar: Option<String>,
+    /// Fingerprint of our certificate, what paired devices pin
+    pub fingerprint: Option<String>,
 }
 
 // ===== Chat commands =====
@@ -437,6 +1017,11 @@ pub async fn offer_file(file_path: String, peer_id: String) -> Result<FileTransf
     use crate::network::protocol;
 
     log::info!("Offering file {} to {}", file_path, peer_id);
+    // Negotiated by the IP of the connection, whatever `peer_id` names it by
+    let peer_ip = quic::find_connection(&peer_id).map(|c| c.peer_ip());
+    if peer_ip.is_some_and(|ip| !crate::network::capability::peer_supports(&ip, crate::network::capability::Capability::FileTransfer)) {
+        return Err(format!("{} does not accept files", peer_id));
+    }
 
     let path = Path::new(&file_path);
     let transfer = transfer::get_transfer_manager()
@@ -451,10 +1036,15 @@ pub async fn offer_file(file_path: String, peer_id: String) -> Result<FileTransf
         checksum: transfer.info.checksum.clone(),
     };
 
-    if let Ok(encoded) = protocol::encode(&offer_msg) {
-        if let Err(e) = quic::send_to_peer(&peer_id, &encoded).await {
-            log::warn!("Failed to send file offer to peer: {}", e);
-        }
+    // An offer the peer never got is no offer
+    let sent = match protocol::encode(&offer_msg) {
+        Ok(encoded) => quic::send_to_peer(&peer_id, &encoded).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = sent {
+        log::warn!("Failed to send file offer to peer: {}", e);
+        let _ = transfer::get_transfer_manager().cancel_transfer(&transfer.info.id);
+        return Err(format!("Failed to send file offer: {}", e));
     }
 
     log::info!("File offer created: {} ({} bytes)", transfer.info.name, transfer.info.size);
@@ -617,6 +1207,18 @@ pub async fn start_service(app_handle: tauri::AppHandle) -> Result<(), String> {
         }
     });
 
+    // Start meeting reminders
+    crate::meeting::start_reminder_task(app_handle.clone());
+
+    // Send files dropped into the watch folder
+    transfer::watch::start();
+
+    // Share clipboard copies with peers once opted in
+    crate::clipboard::start();
+
+    // Emit "stream-stats" once a second while streaming
+    crate::streaming::stats::start();
+
     // Start QUIC endpoint
     match QuicEndpoint::new(QuicConfig::default()).await {
         Ok(endpoint) => {
@@ -624,7 +1226,9 @@ pub async fn start_service(app_handle: tauri::AppHandle) -> Result<(), String> {
             log::info!("QUIC endpoint initialized on {}", endpoint.local_addr());
 
             // Store globally
-            let _ = crate::QUIC_ENDPOINT.set(endpoint.clone());
+            if let Some(old) = crate::set_quic_endpoint(Some(endpoint.clone())) {
+                old.close();
+            }
 
             // Start accepting connections
             endpoint.start_server(|conn| {
@@ -633,6 +1237,11 @@ pub async fn start_service(app_handle: tauri::AppHandle) -> Result<(), String> {
                     crate::handle_incoming_connection(conn).await;
                 });
             });
+
+            // Tell peers when our address changes
+            crate::network::address::start();
+            crate::network::liveness::start();
+            crate::network::link::start();
         }
         Err(e) => {
             log::error!("Failed to initialize QUIC endpoint: {}", e);
@@ -641,6 +1250,7 @@ pub async fn start_service(app_handle: tauri::AppHandle) -> Result<(), String> {
     }
 
     *SERVICE_RUNNING.write() = true;
+    crate::app_state::changed(Slice::Service);
     log::info!("Network service started");
 
     Ok(())
@@ -651,13 +1261,34 @@ pub async fn start_service(app_handle: tauri::AppHandle) -> Result<(), String> {
 pub async fn stop_service() -> Result<(), String> {
     log::info!("Stopping network service");
 
+    // Say goodbye on mDNS so peers drop us now rather than on timeout, then
+    // stop the responder; the next start creates a new one
+    tokio::task::spawn_blocking(|| {
+        discovery::withdraw();
+        discovery::shutdown();
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
     // Disconnect all peers
     disconnect(None).await?;
+    crate::network::address::stop();
+    crate::network::liveness::stop();
+    crate::network::link::stop();
+
+    // Close the endpoint: its accept loop ends and the port is freed for
+    // the next start
+    if let Some(endpoint) = crate::set_quic_endpoint(None) {
+        endpoint.close();
+        endpoint.wait_idle(std::time::Duration::from_secs(1)).await;
+        log::info!("QUIC endpoint closed");
+    }
 
     // Clear device list
     discovery::clear_devices();
 
     *SERVICE_RUNNING.write() = false;
+    crate::app_state::changed(Slice::Service);
 
     Ok(())
 }
@@ -668,19 +1299,176 @@ pub fn is_service_running() -> bool {
     *SERVICE_RUNNING.read()
 }
 
-// ===== Settings commands =====
+// ===== Settings commands =====
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppSettings {
+    pub device_name: String,
+    pub quality: String,
+    pub fps: u32,
+    /// Default resolution index for viewer toolbar (0=720p, 1=1080p, 2=1440p, 3=Original)
+    #[serde(default)]
+    pub default_resolution: u32,
+    /// Default bitrate index for viewer toolbar (0=2M, 1=4M, 2=8M, 3=12M, 4=near-lossless text)
+    #[serde(default)]
+    pub default_bitrate: u32,
+    /// Pixelate the notification banner area while sharing
+    #[serde(default)]
+    pub redact_notifications: bool,
+    /// Show the mouse pointer to viewers
+    #[serde(default = "default_true")]
+    pub show_cursor: bool,
+    /// Send the pointer beside the video for viewers to draw, instead of
+    /// drawing it into the frames
+    #[serde(default = "default_true")]
+    pub cursor_channel: bool,
+    /// Ripple around the sharer's pointer when they click
+    #[serde(default = "default_true")]
+    pub click_ripple: bool,
+    /// Bypass hardware capture, codecs and low-latency presentation
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// Seconds between mDNS queries (0 = library default backoff)
+    #[serde(default)]
+    pub discovery_interval_secs: u32,
+    /// Only advertise; query for devices on manual refresh only
+    #[serde(default)]
+    pub discovery_passive: bool,
+    /// Total upload budget for all viewers of a share in Mbps (0 = no cap)
+    #[serde(default)]
+    pub uplink_cap_mbps: u32,
+    /// Most peers that may watch a share at once (0 = no limit)
+    #[serde(default)]
+    pub max_viewers: u32,
+    /// Save a JPEG of the shared screen periodically
+    #[serde(default)]
+    pub archive_enabled: bool,
+    /// Seconds between archive snapshots
+    #[serde(default = "default_archive_interval")]
+    pub archive_interval_secs: u32,
+    /// Most archive snapshots kept (0 = no limit)
+    #[serde(default = "default_archive_max_snapshots")]
+    pub archive_max_snapshots: u32,
+    /// Days archive snapshots are kept (0 = forever)
+    #[serde(default = "default_archive_max_age_days")]
+    pub archive_max_age_days: u32,
+    /// Recognize the text of archive snapshots so they can be searched
+    #[serde(default)]
+    pub archive_ocr: bool,
+    /// Folder whose new files are sent automatically (empty = off)
+    #[serde(default)]
+    pub watch_folder: String,
+    /// Device that receives the watch folder's files
+    #[serde(default)]
+    pub watch_folder_device: String,
+    /// Share clipboard copies with connected peers (off until opted in)
+    #[serde(default)]
+    pub clipboard_sync: bool,
+    /// Include copied images in clipboard sync
+    #[serde(default)]
+    pub clipboard_images: bool,
+    /// Include copied files in clipboard sync, sent as file transfers
+    #[serde(default)]
+    pub clipboard_files: bool,
+    /// With clipboard sync on, send what is copied here to peers
+    #[serde(default = "default_true")]
+    pub clipboard_send: bool,
+    /// With clipboard sync on, put text peers copy on our clipboard
+    #[serde(default = "default_true")]
+    pub clipboard_receive: bool,
+    /// Show glass-to-glass latency in the corner of viewer windows
+    #[serde(default)]
+    pub latency_hud: bool,
+    /// Codec for shared screens: "h264" or "av1" (AV1 falls back to H.264
+    /// where it cannot be encoded)
+    #[serde(default = "default_video_codec")]
+    pub video_codec: String,
+    /// YUV matrix and range of shared screens (see `ColorSpace::name`)
+    #[serde(default = "default_color_space")]
+    pub color_space: String,
+    /// Color filter viewer windows open with (see `ColorFilter::name`)
+    #[serde(default)]
+    pub color_filter: String,
+    /// How viewer windows open fitting the frame (see `FitMode::name`)
+    #[serde(default)]
+    pub fit_mode: String,
+    /// Encoder to use instead of the automatic choice, by the name
+    /// `list_encoders` gives it (empty = automatic)
+    #[serde(default)]
+    pub forced_encoder: String,
+    /// Check the structure of every encoded H.264 frame before sending,
+    /// logging what a misbehaving encoder gets wrong
+    #[serde(default)]
+    pub validate_bitstream: bool,
+    /// Scale and convert high-resolution captures with a compute shader
+    /// instead of on the CPU
+    #[serde(default = "default_true")]
+    pub gpu_preprocess: bool,
+    /// Encoder speed/quality trade-off (see `EncoderPreset::name`)
+    #[serde(default = "default_encoder_preset")]
+    pub encoder_preset: String,
+    /// Seconds between keyframes; shorter lets viewers join and recover
+    /// sooner at the cost of bitrate
+    #[serde(default = "default_keyframe_interval")]
+    pub keyframe_interval_secs: u32,
+    /// Peak bitrate in Mbps (0 = twice the target bitrate)
+    #[serde(default)]
+    pub max_bitrate_mbps: u32,
+    /// Spend more bits around the pointer, where encoders support it
+    #[serde(default)]
+    pub cursor_roi: bool,
+    /// H.264 decoders to try first, by the name `list_decoders` gives them;
+    /// the rest follow in the default order
+    #[serde(default)]
+    pub decoder_preference: Vec<String>,
+    /// Avatar shown to peers: a small square PNG, base64 encoded (empty = none)
+    #[serde(default)]
+    pub avatar: String,
+    /// IDs of devices listed first in the device list
+    #[serde(default)]
+    pub favorite_devices: Vec<String>,
+    /// IDs of devices whose requests are approved without asking
+    #[serde(default)]
+    pub trusted_devices: Vec<String>,
+    /// Who may watch or ask for control without being let in (see
+    /// `ViewerPolicy::name`; empty = ask)
+    #[serde(default)]
+    pub viewer_policy: String,
+    /// Password a device must know to connect to us; empty = none
+    #[serde(default)]
+    pub meeting_password: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_encoder_preset() -> String {
+    "ultrafast".to_string()
+}
+
+fn default_keyframe_interval() -> u32 {
+    1
+}
+
+fn default_video_codec() -> String {
+    "h264".to_string()
+}
+
+fn default_color_space() -> String {
+    "bt709".to_string()
+}
+
+fn default_archive_interval() -> u32 {
+    60
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppSettings {
-    pub device_name: String,
-    pub quality: String,
-    pub fps: u32,
-    /// Default resolution index for viewer toolbar (0=720p, 1=1080p, 2=1440p, 3=Original)
-    #[serde(default)]
-    pub default_resolution: u32,
-    /// Default bitrate index for viewer toolbar (0=2M, 1=4M, 2=8M, 3=12M)
-    #[serde(default)]
-    pub default_bitrate: u32,
+fn default_archive_max_snapshots() -> u32 {
+    500
+}
+
+fn default_archive_max_age_days() -> u32 {
+    30
 }
 
 /// Settings file path
@@ -700,6 +1488,45 @@ fn load_settings_from_disk() -> AppSettings {
         fps: 30,
         default_resolution: 1, // 1080p
         default_bitrate: 1,    // 4 Mbps
+        redact_notifications: false,
+        show_cursor: true,
+        cursor_channel: true,
+        click_ripple: true,
+        safe_mode: false,
+        discovery_interval_secs: 0,
+        discovery_passive: false,
+        uplink_cap_mbps: 0,
+        max_viewers: 0,
+        archive_enabled: false,
+        archive_interval_secs: default_archive_interval(),
+        archive_max_snapshots: default_archive_max_snapshots(),
+        archive_max_age_days: default_archive_max_age_days(),
+        archive_ocr: false,
+        watch_folder: String::new(),
+        watch_folder_device: String::new(),
+        clipboard_sync: false,
+        clipboard_images: false,
+        clipboard_files: false,
+        clipboard_send: true,
+        clipboard_receive: true,
+        latency_hud: false,
+        video_codec: default_video_codec(),
+        color_space: default_color_space(),
+        color_filter: String::new(),
+        fit_mode: String::new(),
+        forced_encoder: String::new(),
+        validate_bitstream: cfg!(debug_assertions),
+        gpu_preprocess: true,
+        encoder_preset: default_encoder_preset(),
+        keyframe_interval_secs: default_keyframe_interval(),
+        max_bitrate_mbps: 0,
+        cursor_roi: false,
+        decoder_preference: Vec::new(),
+        avatar: String::new(),
+        favorite_devices: Vec::new(),
+        trusted_devices: Vec::new(),
+        viewer_policy: String::new(),
+        meeting_password: String::new(),
     };
 
     let Some(path) = settings_path() else {
@@ -761,31 +1588,258 @@ pub fn get_settings() -> AppSettings {
 pub fn save_settings(settings: AppSettings) -> Result<(), String> {
     log::info!("Saving settings: {:?}", settings);
     save_settings_to_disk(&settings);
-    *SETTINGS.write() = settings;
+    let (retune, renamed) = {
+        let mut current = SETTINGS.write();
+        let retune = current.encoder_preset != settings.encoder_preset
+            || current.keyframe_interval_secs != settings.keyframe_interval_secs
+            || current.max_bitrate_mbps != settings.max_bitrate_mbps;
+        let renamed = current.device_name != settings.device_name;
+        *current = settings;
+        (retune, renamed)
+    };
+    if retune {
+        apply_encoder_tuning();
+    }
+    if renamed {
+        // The mDNS announcement carries our name
+        if let Err(e) = discovery::reregister() {
+            log::warn!("Failed to announce new device name: {}", e);
+        }
+        crate::app_state::changed(Slice::SelfInfo);
+    }
+    discovery::settings_changed();
+    transfer::watch::settings_changed();
+    crate::app_state::changed(Slice::Settings);
     Ok(())
 }
 
+/// Set our avatar from an image file, or clear it with `None`. The image
+/// is cropped and scaled down to a small PNG; returns it base64 encoded.
+#[tauri::command]
+pub async fn set_avatar(path: Option<String>) -> Result<String, String> {
+    use base64::Engine;
+
+    let encoded = match path {
+        Some(path) => {
+            let png = tokio::task::spawn_blocking(move || {
+                crate::network::profile::avatar_from_file(std::path::Path::new(&path))
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+            base64::engine::general_purpose::STANDARD.encode(png)
+        }
+        None => String::new(),
+    };
+    let settings = {
+        let mut s = SETTINGS.write();
+        s.avatar = encoded.clone();
+        s.clone()
+    };
+    save_settings_to_disk(&settings);
+    crate::app_state::changed(Slice::Settings);
+    crate::app_state::changed(Slice::SelfInfo);
+    Ok(encoded)
+}
+
 /// Get default resolution and bitrate indices for viewer toolbar
 pub fn get_default_streaming_indices() -> (usize, usize) {
     let s = SETTINGS.read();
     (s.default_resolution as usize, s.default_bitrate as usize)
 }
 
+/// Whether notification banners should be redacted while sharing
+pub fn redact_notifications_enabled() -> bool {
+    SETTINGS.read().redact_notifications
+}
+
+/// Whether the mouse pointer should be shown to viewers
+pub fn show_cursor_enabled() -> bool {
+    SETTINGS.read().show_cursor
+}
+
+/// Whether the mouse pointer should be composited into the shared stream
+pub fn cursor_in_frame() -> bool {
+    let s = SETTINGS.read();
+    s.show_cursor && !s.cursor_channel
+}
+
+/// Whether the mouse pointer should go to viewers beside the video
+pub fn cursor_channel_enabled() -> bool {
+    let s = SETTINGS.read();
+    s.show_cursor && s.cursor_channel
+}
+
+/// Whether viewers show a ripple where the sharer clicks
+pub fn click_ripple_enabled() -> bool {
+    SETTINGS.read().click_ripple
+}
+
+/// mDNS query settings
+pub fn discovery_settings() -> discovery::DiscoverySettings {
+    let s = SETTINGS.read();
+    discovery::DiscoverySettings {
+        query_interval_secs: s.discovery_interval_secs,
+        passive: s.discovery_passive,
+    }
+}
+
+/// Upload budget shared by all viewers, if the user set one
+pub fn uplink_cap_bps() -> Option<u64> {
+    let mbps = SETTINGS.read().uplink_cap_mbps;
+    (mbps > 0).then(|| mbps as u64 * 1_000_000)
+}
+
+/// Most concurrent viewers of a share, if the user set a limit
+pub fn max_viewers() -> Option<u32> {
+    let max = SETTINGS.read().max_viewers;
+    (max > 0).then_some(max)
+}
+
+/// Who may watch or ask for control without being let in
+pub fn viewer_policy() -> crate::network::access::ViewerPolicy {
+    crate::network::access::ViewerPolicy::from_name(&SETTINGS.read().viewer_policy).unwrap_or_default()
+}
+
+/// The meeting password, empty when none is set
+pub fn meeting_password() -> String {
+    SETTINGS.read().meeting_password.clone()
+}
+
+/// Snapshot interval and retention, if archive mode is turned on
+pub fn archive_settings() -> Option<crate::capture::archive::ArchiveSettings> {
+    let s = SETTINGS.read();
+    s.archive_enabled.then(|| crate::capture::archive::ArchiveSettings {
+        interval: std::time::Duration::from_secs(s.archive_interval_secs.max(1) as u64),
+        max_snapshots: s.archive_max_snapshots as usize,
+        max_age: (s.archive_max_age_days > 0)
+            .then(|| std::time::Duration::from_secs(s.archive_max_age_days as u64 * 86_400)),
+        ocr: s.archive_ocr && crate::capture::ocr::is_available(),
+    })
+}
+
+/// Folder archive snapshots are saved in
+#[tauri::command]
+pub fn get_archive_directory() -> Result<String, String> {
+    crate::capture::archive::archive_root()
+        .map(|p| p.display().to_string())
+        .ok_or_else(|| "Cannot determine archive directory".to_string())
+}
+
+/// Search the recognized text of archive snapshots across past sessions
+#[tauri::command]
+pub async fn search_archive(query: String) -> Result<Vec<crate::capture::ocr::ArchiveMatch>, String> {
+    let root = crate::capture::archive::archive_root().ok_or("Cannot determine archive directory")?;
+    tokio::task::spawn_blocking(move || crate::capture::ocr::search(&root, &query))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Whether this build can index archive snapshots for search
+#[tauri::command]
+pub fn is_archive_ocr_available() -> bool {
+    crate::capture::ocr::is_available()
+}
+
+/// Watch folder and its receiving device, if both are set
+pub fn watch_folder_settings() -> Option<transfer::watch::WatchSettings> {
+    let s = SETTINGS.read();
+    (!s.watch_folder.is_empty() && !s.watch_folder_device.is_empty()).then(|| transfer::watch::WatchSettings {
+        folder: std::path::PathBuf::from(&s.watch_folder),
+        device_id: s.watch_folder_device.clone(),
+    })
+}
+
+/// Clipboard sync settings
+pub fn clipboard_settings() -> crate::clipboard::ClipboardSettings {
+    let s = SETTINGS.read();
+    crate::clipboard::ClipboardSettings {
+        sync: s.clipboard_sync,
+        images: s.clipboard_images,
+        files: s.clipboard_files,
+        send: s.clipboard_send,
+        receive: s.clipboard_receive,
+    }
+}
+
+/// Clipboard items shared with a peer, newest first
+#[tauri::command]
+pub fn get_clipboard_history(peer_ip: String) -> Vec<crate::clipboard::ClipboardItem> {
+    crate::clipboard::get_history(&peer_ip)
+}
+
+/// Put a past clipboard item back on the local clipboard
+#[tauri::command]
+pub fn copy_clipboard_item(peer_ip: String, item_id: String) -> Result<(), String> {
+    crate::clipboard::copy_item(&peer_ip, &item_id)
+}
+
+/// Send a link for `peer_ip` to open once its user confirms
+#[tauri::command]
+pub async fn send_url(peer_ip: String, url: String) -> Result<(), String> {
+    crate::links::send(&peer_ip, &url).await
+}
+
+/// Open a link a peer sent ("url-received"), or turn it down
+#[tauri::command]
+pub fn answer_url(id: String, open: bool) -> Result<(), String> {
+    crate::links::answer(&id, open)
+}
+
+/// Forget the clipboard history with one peer, or with all of them
+#[tauri::command]
+pub fn clear_clipboard_history(peer_ip: Option<String>) {
+    crate::clipboard::clear_history(peer_ip.as_deref());
+}
+
+/// Whether safe mode is turned on in settings
+pub fn safe_mode_setting_enabled() -> bool {
+    SETTINGS.read().safe_mode
+}
+
+/// Whether safe mode is on and which subsystems run on their safe path
+#[tauri::command]
+pub fn get_safe_mode_status() -> crate::safe_mode::SafeModeStatus {
+    crate::safe_mode::status()
+}
+
 // ===== Sharing status commands =====
 
 /// Sharing state
 static IS_SHARING: once_cell::sync::Lazy<parking_lot::RwLock<bool>> =
     once_cell::sync::Lazy::new(|| parking_lot::RwLock::new(false));
 
-/// Broadcast sharing status to all connected peers
+/// Whether the local user turned sharing on
+pub fn is_sharing() -> bool {
+    *IS_SHARING.read()
+}
+
+/// Everything the main window shows, for rebuilding the UI after a reload.
+/// Later changes arrive as "state-delta" events with a higher revision.
+#[tauri::command]
+pub fn get_app_state() -> crate::app_state::AppState {
+    crate::app_state::snapshot()
+}
+
+/// Broadcast sharing status to all connected peers.
+/// `display_ids` shares several displays at once; otherwise `display_id` alone is shared.
 #[tauri::command]
-pub async fn broadcast_sharing_status(is_sharing: bool, display_id: Option<u32>) -> Result<(), String> {
+pub async fn broadcast_sharing_status(
+    is_sharing: bool,
+    display_id: Option<u32>,
+    display_ids: Option<Vec<u32>>,
+) -> Result<(), String> {
     use crate::network::protocol;
     use crate::streaming::{get_streaming_manager, StreamingConfig, Quality, StreamingManager};
 
-    log::info!("Broadcasting sharing status: {} (display: {:?})", is_sharing, display_id);
+    log::info!(
+        "Broadcasting sharing status: {} (display: {:?}, displays: {:?})",
+        is_sharing,
+        display_id,
+        display_ids
+    );
 
     *IS_SHARING.write() = is_sharing;
+    crate::app_state::changed(Slice::Sharing);
 
     // Start or stop streaming
     if is_sharing {
@@ -802,7 +1856,9 @@ pub async fn broadcast_sharing_status(is_sharing: bool, display_id: Option<u32>)
                 "low" => Quality::Low,
                 _ => Quality::Auto,
             },
-            display_id: display_id.unwrap_or(0),
+            display_ids: display_ids.unwrap_or_else(|| display_id.into_iter().collect()),
+            tuning: encoder_tuning(),
+            color_space: preferred_color_space(),
         };
 
         // Initialize manager if needed (sync operation)
@@ -835,8 +1891,20 @@ pub async fn broadcast_sharing_status(is_sharing: bool, display_id: Option<u32>)
         if let Some(ref mut m) = *manager {
             m.stop_sync();
         }
+        crate::streaming::recording::stop();
+    }
+    if !is_sharing {
+        crate::network::trust::end_share().await;
+        crate::network::access::end_share();
     }
 
+    // Offer only the displays that actually started streaming
+    let shared_ids = get_streaming_manager()
+        .read()
+        .as_ref()
+        .map(|m| m.display_ids())
+        .unwrap_or_default();
+
     // Create sharing status message
     let msg = protocol::Message::ScreenOffer {
         displays: if is_sharing {
@@ -844,6 +1912,7 @@ pub async fn broadcast_sharing_status(is_sharing: bool, display_id: Option<u32>)
             match get_displays().await {
                 Ok(displays) => displays
                     .into_iter()
+                    .filter(|d| shared_ids.contains(&d.id))
                     .map(|d| protocol::DisplayInfo {
                         id: d.id,
                         name: d.name,
@@ -868,22 +1937,86 @@ pub async fn broadcast_sharing_status(is_sharing: bool, display_id: Option<u32>)
     Ok(())
 }
 
-/// Request screen stream from a peer (creates native render window)
+/// Move the running share to another display without stopping it. Viewers
+/// keep their connection and window; their decoder restarts at the new size.
+/// `from_display_id` picks which shared display to move (default: the first).
+#[tauri::command]
+pub async fn switch_display(display_id: u32, from_display_id: Option<u32>) -> Result<(), String> {
+    log::info!("Switching shared display {:?} to {}", from_display_id, display_id);
+
+    let manager_arc = crate::streaming::get_streaming_manager();
+    let manager = manager_arc.read();
+    let manager = manager
+        .as_ref()
+        .ok_or_else(|| "Not sharing".to_string())?;
+    manager
+        .switch_display(from_display_id, display_id)
+        .map_err(|e| format!("Failed to switch display: {}", e))
+}
+
+/// Pause sharing: viewers keep the session and see the last frame dimmed
+#[tauri::command]
+pub async fn pause_sharing() -> Result<(), String> {
+    set_sharing_paused(true).await
+}
+
+/// Resume a paused share
+#[tauri::command]
+pub async fn resume_sharing() -> Result<(), String> {
+    set_sharing_paused(false).await
+}
+
+async fn set_sharing_paused(paused: bool) -> Result<(), String> {
+    use crate::network::protocol::{self, Message};
+
+    {
+        let manager_arc = crate::streaming::get_streaming_manager();
+        let manager = manager_arc.read();
+        let manager = manager
+            .as_ref()
+            .ok_or_else(|| "Not sharing".to_string())?;
+        manager
+            .set_frozen(paused)
+            .map_err(|e| format!("Failed to pause sharing: {}", e))?;
+    }
+    crate::app_state::changed(Slice::Sharing);
+
+    let encoded = protocol::encode(&Message::ScreenPause { paused })
+        .map_err(|e| format!("Failed to encode message: {}", e))?;
+    for result in crate::network::quic::broadcast_message(&encoded).await {
+        if let Err(e) = result {
+            log::warn!("Failed to send ScreenPause: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Request screen stream from a peer (creates native render window).
+/// Requesting another display of the same peer opens a second window;
+/// without `display_id` the sharer's first shared display is sent.
 #[tauri::command]
-pub async fn request_screen_stream(peer_ip: String, peer_name: String) -> Result<(), String> {
+pub async fn request_screen_stream(
+    peer_ip: String,
+    peer_name: String,
+    display_id: Option<u32>,
+) -> Result<(), String> {
     use crate::streaming;
 
-    log::info!("Requesting screen stream from {} ({})", peer_name, peer_ip);
+    log::info!(
+        "Requesting screen stream from {} ({}), display {:?}",
+        peer_name,
+        peer_ip,
+        display_id
+    );
 
     // Ensure we have an active QUIC connection to this peer
     ensure_peer_connection(&peer_ip).await?;
 
     // Create viewer session (native window will be created on ScreenStart)
-    streaming::create_viewer_session(peer_ip.clone(), peer_name)
-        .map_err(|e| format!("Failed to create viewer session: {}", e))?;
+    streaming::create_viewer_session(peer_ip.clone(), peer_name);
 
     // Send request to peer
-    streaming::request_screen_stream(&peer_ip, 0)
+    streaming::request_screen_stream(&peer_ip, display_id.unwrap_or(0))
         .await
         .map_err(|e| format!("Failed to request stream: {}", e))?;
 
@@ -913,33 +2046,25 @@ async fn ensure_peer_connection(peer_ip: &str) -> Result<(), String> {
         .map(|d| d.port)
         .unwrap_or(quic::DEFAULT_PORT);
 
-    let addr: SocketAddr = format!("{}:{}", peer_ip, port)
-        .parse()
-        .map_err(|e| format!("Invalid address: {}", e))?;
+    let addr = crate::network::address::socket_addr(&peer_ip, port)
+        .ok_or_else(|| format!("Invalid address: {}", peer_ip))?;
 
     // Get QUIC endpoint
     let endpoint = crate::get_quic_endpoint()
         .ok_or_else(|| "QUIC endpoint not initialized - start service first".to_string())?;
 
-    // Connect with timeout
-    let conn = tokio::time::timeout(
-        std::time::Duration::from_secs(5),
-        endpoint.connect(addr),
-    )
-    .await
-    .map_err(|_| format!("Connection to {} timed out", peer_ip))?
-    .map_err(|e| format!("Failed to connect to {}: {}", peer_ip, e))?;
+    // Connect, backing off if the peer keeps failing
+    let conn = endpoint
+        .connect_with_retry(addr, std::time::Duration::from_secs(5))
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", peer_ip, ConnectFailure::from(e).message))?;
 
     log::info!("Connected to {} at {}", peer_ip, conn.remote_addr());
 
     // Send handshake
     let our_id = discovery::get_our_device_id();
-    let our_name = hostname::get()
-        .map(|h| h.to_string_lossy().to_string())
-        .unwrap_or_else(|_| "Unknown".to_string());
-
-    let handshake = crate::network::protocol::create_handshake(&our_id, &our_name);
-    let encoded = crate::network::protocol::encode(&handshake)
+    let handshake = crate::network::protocol::create_handshake(&our_id, &display_name());
+    let encoded = crate::network::protocol::encode_as(&handshake, crate::network::protocol::LEGACY_VERSION)
         .map_err(|e| format!("Failed to encode handshake: {}", e))?;
 
     let mut stream = conn
@@ -947,31 +2072,30 @@ async fn ensure_peer_connection(peer_ip: &str) -> Result<(), String> {
         .await
         .map_err(|e| format!("Failed to open handshake stream: {}", e))?;
 
+    crate::network::password::send_proof(&conn, &mut stream)
+        .await
+        .map_err(|e| format!("Failed to send meeting password: {}", e))?;
     stream
         .send_framed(&encoded)
         .await
         .map_err(|e| format!("Failed to send handshake: {}", e))?;
 
-    // Wait for handshake ack
-    let response = tokio::time::timeout(
-        std::time::Duration::from_secs(5),
-        stream.recv_framed(),
-    )
-    .await
-    .map_err(|_| "Handshake ack timed out".to_string())?
-    .map_err(|e| format!("Failed to receive handshake ack: {}", e))?;
-
-    let ack = crate::network::protocol::decode(&response)
-        .map_err(|e| format!("Failed to decode handshake ack: {}", e))?;
-
-    match ack {
-        crate::network::protocol::Message::HandshakeAck { accepted, reason, name, .. } => {
-            if !accepted {
-                return Err(format!("Connection rejected: {}", reason.unwrap_or_default()));
-            }
-            log::info!("Reconnected and handshake accepted by {}", name);
-        }
-        _ => return Err("Unexpected handshake response".to_string()),
+    // Wait for handshake ack, pairing first if we never met
+    let known = discovery::get_devices().into_iter().find(|d| d.ip == peer_ip);
+    let label = known.as_ref().map_or(peer_ip, |d| d.name.as_str());
+    let answer = crate::network::pairing::await_answer(&conn, &mut stream, label, known.as_ref().map(|d| d.id.as_str()))
+        .await
+        .map_err(|e| format!("Handshake with {} failed: {}", peer_ip, ConnectFailure::from(e).message))?;
+    log::info!("Reconnected and handshake accepted by {}", answer.name);
+    crate::network::pairing::complete(&conn, &answer.device_id);
+    crate::network::password::admit(&conn);
+    discovery::set_device_profile(
+        &answer.device_id,
+        &answer.name,
+        crate::network::profile::checked_avatar(answer.avatar.as_deref()),
+    );
+    if let Err(e) = crate::network::profile::send_avatar(&mut stream).await {
+        log::warn!("Failed to send our avatar to {}: {}", answer.name, e);
     }
 
     // Start listening for incoming messages on this connection
@@ -983,13 +2107,29 @@ async fn ensure_peer_connection(peer_ip: &str) -> Result<(), String> {
     Ok(())
 }
 
-/// Stop viewing a screen stream
+/// Frame and stall counts of the streams we are watching
+#[tauri::command]
+pub fn get_viewer_summaries() -> Vec<crate::streaming::ViewerSummary> {
+    crate::streaming::viewer_summaries()
+}
+
+/// Stop viewing a screen stream: one display, or every display when `display_id` is `None`
 #[tauri::command]
-pub fn stop_viewing_stream(peer_ip: String) -> Result<(), String> {
+pub async fn stop_viewing_stream(peer_ip: String, display_id: Option<u32>) -> Result<(), String> {
     use crate::streaming;
 
-    log::info!("Stopping stream viewer for {}", peer_ip);
-    streaming::remove_viewer_session(&peer_ip);
+    log::info!("Stopping stream viewer for {} (display {:?})", peer_ip, display_id);
+    match display_id {
+        Some(id) => {
+            streaming::close_viewer_display(&peer_ip, id);
+        }
+        None => streaming::remove_viewer_session(&peer_ip),
+    }
+
+    // Best effort: the sharer also drops us when the connection goes away
+    if let Err(e) = streaming::notify_viewer_left(&peer_ip, display_id).await {
+        log::warn!("Failed to notify {} that we stopped watching: {}", peer_ip, e);
+    }
     Ok(())
 }
 
@@ -1048,6 +2188,10 @@ pub async fn request_control(peer_id: String) -> Result<(), String> {
     use crate::network::protocol;
 
     log::info!("Requesting control of {}", peer_id);
+    let peer_ip = quic::find_connection(&peer_id).map(|c| c.peer_ip());
+    if peer_ip.is_some_and(|ip| !crate::network::capability::peer_supports(&ip, crate::network::capability::Capability::RemoteControl)) {
+        return Err(format!("{} does not support remote control", peer_id));
+    }
 
     let self_info = get_self_info()?;
     let msg = protocol::Message::ControlRequest {
@@ -1063,13 +2207,80 @@ pub async fn request_control(peer_id: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Let a peer that requested control type into this machine. With `trust`
+/// set, its later requests are granted without asking for that long.
+#[tauri::command]
+pub async fn grant_control(peer_id: String, trust: Option<crate::network::trust::TrustScope>) -> Result<(), String> {
+    use crate::network::protocol;
+
+    let self_info = get_self_info()?;
+    if let Some(scope) = trust {
+        crate::network::trust::trust(&peer_id, scope);
+    }
+    if let Some(previous) = crate::input::session::grant(&peer_id).filter(|p| *p != peer_id) {
+        // Only one controller at a time
+        if let Ok(encoded) = protocol::encode(&protocol::Message::ControlRevoke) {
+            let _ = quic::send_to_peer(&previous, &encoded).await;
+        }
+    }
+    log::info!("Granted control to {}", peer_id);
+
+    let msg = protocol::Message::ControlGrant { to_user: self_info.name };
+    let encoded = protocol::encode(&msg).map_err(|e| e.to_string())?;
+    quic::send_to_peer(&peer_id, &encoded)
+        .await
+        .map_err(|e| format!("Failed to send control grant: {}", e))
+}
+
+/// Take control back from the peer holding it
+#[tauri::command]
+pub async fn revoke_control() {
+    use crate::network::protocol;
+
+    let Some(peer_id) = crate::input::session::revoke() else {
+        return;
+    };
+    log::info!("Revoked control from {}", peer_id);
+
+    // Input from the peer is dropped from now on whether or not it hears this
+    if let Ok(encoded) = protocol::encode(&protocol::Message::ControlRevoke) {
+        if let Err(e) = quic::send_to_peer(&peer_id, &encoded).await {
+            log::debug!("Failed to send control revoke to {}: {}", peer_id, e);
+        }
+    }
+}
+
 // ===== Simple streaming commands (minimal pipeline for debugging) =====
 
 /// Start simple screen sharing (OpenH264 only, no optimizations)
 #[tauri::command]
 pub async fn simple_start_sharing(display_id: u32) -> Result<(), String> {
     log::info!("[SIMPLE] Command: simple_start_sharing(display_id={})", display_id);
-    crate::simple_streaming::start_sharing(display_id)
+    crate::simple_streaming::start_sharing(display_id, None)
+}
+
+/// Start simple screen sharing for a rectangle of a display
+#[tauri::command]
+pub async fn simple_start_region_sharing(
+    display_id: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
+    log::info!(
+        "[SIMPLE] Command: simple_start_region_sharing(display_id={}, {}x{} at ({}, {}))",
+        display_id, width, height, x, y
+    );
+    let region = crate::capture::CaptureRegion::new(x, y, width, height);
+    crate::simple_streaming::start_sharing(display_id, Some(region))
+}
+
+/// Start simple screen sharing for a single application window
+#[tauri::command]
+pub async fn simple_start_window_sharing(window_id: u64) -> Result<(), String> {
+    log::info!("[SIMPLE] Command: simple_start_window_sharing(window_id={:#x})", window_id);
+    crate::simple_streaming::start_window_sharing(window_id)
 }
 
 /// Request simple screen stream from a peer
@@ -1095,10 +2306,236 @@ pub async fn simple_request_stream(peer_ip: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Ask the sharer we are viewing to switch to another of its displays.
+/// The stream and render window stay open; the sharer resends ScreenStart.
+#[tauri::command]
+pub async fn simple_switch_display(peer_ip: String, display_id: u32) -> Result<(), String> {
+    log::info!("[SIMPLE] Command: simple_switch_display(peer_ip={}, display_id={})", peer_ip, display_id);
+    crate::simple_streaming::request_display(&peer_ip, display_id);
+    Ok(())
+}
+
 /// Stop simple screen sharing
 #[tauri::command]
 pub async fn simple_stop_sharing() -> Result<(), String> {
     log::info!("[SIMPLE] Command: simple_stop_sharing");
     crate::simple_streaming::stop_sharing();
+    crate::network::trust::end_share().await;
+    crate::network::access::end_share();
+    Ok(())
+}
+
+// ===== Meeting commands =====
+
+/// Send a protocol message to a device by ID, connecting first if needed
+async fn send_to_device(device_id: &str, msg: &crate::network::protocol::Message) -> Result<(), String> {
+    let ip = discovery::get_devices()
+        .into_iter()
+        .find(|d| d.id == device_id)
+        .map(|d| d.ip)
+        .ok_or_else(|| format!("Device not found: {}", device_id))?;
+
+    send_to_ip(&ip, msg).await
+}
+
+/// Send a protocol message to a peer IP, connecting first if needed
+async fn send_to_ip(ip: &str, msg: &crate::network::protocol::Message) -> Result<(), String> {
+    ensure_peer_connection(ip).await?;
+
+    let encoded = crate::network::protocol::encode(msg)
+        .map_err(|e| format!("Failed to encode message: {}", e))?;
+    quic::send_to_peer(ip, &encoded)
+        .await
+        .map_err(|e| format!("Failed to send to {}: {}", ip, e))
+}
+
+/// Schedule a meeting and invite devices (all online devices if none given)
+#[tauri::command]
+pub async fn create_meeting(
+    name: String,
+    start_time: u64,
+    invitees: Option<Vec<String>>,
+) -> Result<crate::meeting::ScheduledMeeting, String> {
+    use crate::network::protocol::Message;
+
+    if !*SERVICE_RUNNING.read() {
+        return Err("请先开启服务".to_string());
+    }
+
+    let invitees = invitees.unwrap_or_else(|| {
+        discovery::get_devices()
+            .into_iter()
+            .filter(|d| d.status == DeviceStatus::Online)
+            .map(|d| d.id)
+            .collect()
+    });
+
+    let self_info = get_self_info()?;
+    let meeting = crate::meeting::get_meeting_manager().create_meeting(
+        &name,
+        start_time,
+        &self_info.id,
+        &self_info.name,
+        &invitees,
+    );
+    log::info!("Created meeting '{}' with {} invitees", meeting.name, invitees.len());
+
+    let invite = Message::MeetingInvite {
+        meeting_id: meeting.id.clone(),
+        name: meeting.name.clone(),
+        host_id: meeting.host_id.clone(),
+        host_name: meeting.host_name.clone(),
+        start_time: meeting.start_time,
+    };
+
+    for device_id in &invitees {
+        if let Err(e) = send_to_device(device_id, &invite).await {
+            log::warn!("Failed to invite {}: {}", device_id, e);
+        }
+    }
+
+    Ok(meeting)
+}
+
+/// Accept or decline a meeting invitation
+#[tauri::command]
+pub async fn respond_to_meeting(meeting_id: String, accepted: bool) -> Result<(), String> {
+    use crate::meeting::InviteResponse;
+    use crate::network::protocol::Message;
+
+    let manager = crate::meeting::get_meeting_manager();
+    let meeting = manager
+        .get_meeting(&meeting_id)
+        .ok_or_else(|| format!("Meeting not found: {}", meeting_id))?;
+
+    let our_id = discovery::get_our_device_id().to_string();
+    let response = if accepted {
+        InviteResponse::Accepted
+    } else {
+        InviteResponse::Declined
+    };
+    manager.record_response(&meeting_id, &our_id, response);
+
+    let msg = Message::MeetingResponse {
+        meeting_id,
+        device_id: our_id,
+        accepted,
+    };
+
+    match meeting.host_ip {
+        Some(ip) => send_to_ip(&ip, &msg).await,
+        None => send_to_device(&meeting.host_id, &msg).await,
+    }
+}
+
+/// Cancel a meeting we host and notify invitees
+#[tauri::command]
+pub async fn cancel_meeting(meeting_id: String) -> Result<(), String> {
+    use crate::network::protocol::Message;
+
+    let meeting = crate::meeting::get_meeting_manager()
+        .remove_meeting(&meeting_id)
+        .ok_or_else(|| format!("Meeting not found: {}", meeting_id))?;
+
+    transfer::broadcast::meeting_cancelled(&meeting_id);
+    if !meeting.is_local {
+        return Ok(()); // Just drop the invitation locally
+    }
+
+    let msg = Message::MeetingCancel { meeting_id };
+    for device_id in meeting.responses.keys() {
+        if let Err(e) = send_to_device(device_id, &msg).await {
+            log::warn!("Failed to notify {} of cancellation: {}", device_id, e);
+        }
+    }
+
     Ok(())
 }
+
+/// Offer a file to every member of a meeting. Members who join later get
+/// the offer when they ask for it, for up to an hour.
+#[tauri::command]
+pub async fn offer_file_to_meeting(file_path: String, meeting_id: String) -> Result<Vec<FileTransfer>, String> {
+    let meeting = crate::meeting::get_meeting_manager()
+        .get_meeting(&meeting_id)
+        .ok_or_else(|| format!("Meeting not found: {}", meeting_id))?;
+    transfer::broadcast::offer_to_meeting(Path::new(&file_path), &meeting).await
+}
+
+/// Capture a display (or `region` of it) and send it to `peer_ip` as a
+/// PNG. With `annotate` the screenshot is first handed to the frontend in a
+/// "screenshot-annotate" event and nothing is sent until it calls
+/// `send_annotated_screenshot`; otherwise the offer is returned.
+#[tauri::command]
+pub async fn send_screenshot(
+    display_id: u32,
+    region: Option<crate::capture::CaptureRegion>,
+    peer_ip: String,
+    annotate: bool,
+) -> Result<Option<FileTransfer>, String> {
+    let path = tokio::task::spawn_blocking(move || crate::capture::screenshot::capture_png(display_id, region))
+        .await
+        .map_err(|e| format!("Screenshot failed: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    if !annotate {
+        return offer_file(path.display().to_string(), peer_ip).await.map(Some);
+    }
+
+    use base64::Engine;
+    let png = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        use tauri::Emitter;
+
+        #[derive(serde::Serialize, Clone)]
+        struct ScreenshotAnnotateEvent {
+            path: String,
+            peer_ip: String,
+            /// PNG as a data URL, for showing under the pen
+            image: String,
+        }
+
+        let _ = handle.emit(
+            "screenshot-annotate",
+            ScreenshotAnnotateEvent {
+                path: path.display().to_string(),
+                peer_ip,
+                image: format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(png)),
+            },
+        );
+    }
+    Ok(None)
+}
+
+/// Draw the user's pen strokes onto a screenshot from `send_screenshot`
+/// and send it to `peer_ip`
+#[tauri::command]
+pub async fn send_annotated_screenshot(
+    path: String,
+    peer_ip: String,
+    strokes: Vec<crate::capture::screenshot::Stroke>,
+) -> Result<FileTransfer, String> {
+    let root = crate::capture::screenshot::screenshot_root().ok_or("No picture folder")?;
+    if Path::new(&path).parent() != Some(root.as_path()) {
+        return Err(format!("Not a screenshot: {}", path));
+    }
+    let annotated = path.clone();
+    tokio::task::spawn_blocking(move || crate::capture::screenshot::annotate_png(Path::new(&annotated), &strokes))
+        .await
+        .map_err(|e| format!("Annotation failed: {}", e))??;
+    offer_file(path, peer_ip).await
+}
+
+/// Get all scheduled meetings
+#[tauri::command]
+pub fn get_meetings() -> Vec<crate::meeting::ScheduledMeeting> {
+    crate::meeting::get_meeting_manager().get_meetings()
+}
+
+/// Manually take over as host of a meeting
+#[tauri::command]
+pub async fn claim_meeting_host(meeting_id: String) -> Result<crate::meeting::ScheduledMeeting, String> {
+    crate::meeting::claim_host(&meeting_id)
+        .await
+        .ok_or_else(|| format!("Meeting not found: {}", meeting_id))
+}
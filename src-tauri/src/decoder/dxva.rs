@@ -44,4 +44,8 @@ impl VideoDecoder for DxvaDecoder {
     fn info(&self) -> &str {
         "DXVA2 (Hardware)"
     }
+
+    fn is_hardware(&self) -> bool {
+        true
+    }
 }
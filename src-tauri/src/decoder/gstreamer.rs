@@ -267,6 +267,10 @@ impl VideoDecoder for GStreamerDecoder {
     fn info(&self) -> &str {
         "GStreamer (auto hardware selection)"
     }
+
+    fn is_hardware(&self) -> bool {
+        true
+    }
 }
 
 impl Drop for GStreamerDecoder {
@@ -1,11 +1,15 @@
 // Video decoder module
 // Hardware decoding with software fallback
 //
-// Decoder priority:
+// Default decoder priority (reordered by the `decoder_preference` setting):
 // 1. GStreamer (cross-platform, auto-selects best hardware decoder)
 // 2. Platform-specific hardware (VideoToolbox/DXVA/VAAPI)
 // 3. OpenH264 software decoder
+//
+// A hardware decoder that keeps failing mid-stream is swapped for OpenH264
+// by the viewer (see `DecodeFailures`). AV1 streams are decoded by dav1d.
 
+pub mod av1;
 pub mod gstreamer;
 pub mod software;
 
@@ -18,6 +22,8 @@ pub mod dxva;
 #[cfg(target_os = "linux")]
 pub mod vaapi;
 
+use crate::encoder::color::ColorSpace;
+use crate::encoder::Codec;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -39,6 +45,8 @@ pub struct DecoderConfig {
     pub height: u32,
     /// Output format: BGRA for rendering, YUV420 for zero-copy
     pub output_format: OutputFormat,
+    /// Color space from the stream start, for the YUV → BGRA conversion
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -53,6 +61,7 @@ impl Default for DecoderConfig {
             width: 1920,
             height: 1080,
             output_format: OutputFormat::BGRA,
+            color_space: ColorSpace::default(),
         }
     }
 }
@@ -66,13 +75,11 @@ pub enum DecodedFrameData {
         /// For YUV420: strides for Y, U, V planes
         strides: Option<[usize; 3]>,
     },
-    /// Frame decoded directly to GPU texture (zero-copy path)
-    /// Used when GStreamer Vulkan pipeline shares memory with wgpu
-    #[allow(dead_code)]
-    Gpu {
-        /// Placeholder for future wgpu::Texture integration
-        texture_id: u64,
-    },
+    /// Frame decoded directly to a GPU texture (zero-copy path). The
+    /// texture must be created on `renderer::gpu::shared()`'s device, as
+    /// NV12 or sRGB BGRA/RGBA with `TEXTURE_BINDING` usage, so the renderer
+    /// can bind it without a readback.
+    Gpu { texture: wgpu::Texture },
 }
 
 /// Decoded frame ready for rendering
@@ -117,6 +124,23 @@ impl DecodedFrame {
         }
     }
 
+    /// Create a frame that stays on the GPU. `format` is YUV420 for NV12
+    /// textures.
+    pub fn gpu(width: u32, height: u32, timestamp: u64, texture: wgpu::Texture) -> Self {
+        let format = if texture.format() == wgpu::TextureFormat::NV12 {
+            OutputFormat::YUV420
+        } else {
+            OutputFormat::BGRA
+        };
+        Self {
+            width,
+            height,
+            timestamp,
+            format,
+            data: DecodedFrameData::Gpu { texture },
+        }
+    }
+
     /// Check if frame is in CPU memory
     pub fn is_cpu(&self) -> bool {
         matches!(self.data, DecodedFrameData::Cpu { .. })
@@ -144,7 +168,7 @@ pub trait VideoDecoder: Send + Sync {
     /// Initialize the decoder
     fn init(&mut self, config: DecoderConfig) -> Result<(), DecoderError>;
 
-    /// Decode H.264 NAL units
+    /// Decode one encoded frame (H.264 NAL units or AV1 OBUs)
     fn decode(&mut self, data: &[u8], timestamp: u64) -> Result<Option<DecodedFrame>, DecoderError>;
 
     /// Flush any buffered frames
@@ -152,54 +176,260 @@ pub trait VideoDecoder: Send + Sync {
 
     /// Get decoder info
     fn info(&self) -> &str;
+
+    /// Whether decoding runs on the GPU, i.e. whether falling back to a
+    /// software decoder is worth trying when it fails
+    fn is_hardware(&self) -> bool {
+        false
+    }
 }
 
-/// Create the best available decoder for this platform
-pub fn create_decoder() -> Result<Box<dyn VideoDecoder>, DecoderError> {
-    // Try GStreamer first (cross-platform, auto-selects best hardware decoder)
-    match gstreamer::GStreamerDecoder::new() {
-        Ok(dec) => {
-            log::info!("Using GStreamer decoder (auto hardware selection)");
-            return Ok(Box::new(dec));
+/// H.264 decoders this build knows of, in default priority order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderKind {
+    GStreamer,
+    #[cfg(target_os = "macos")]
+    VideoToolbox,
+    #[cfg(target_os = "windows")]
+    Dxva,
+    #[cfg(target_os = "linux")]
+    Vaapi,
+    OpenH264,
+}
+
+impl DecoderKind {
+    pub const ALL: &[DecoderKind] = &[
+        DecoderKind::GStreamer,
+        #[cfg(target_os = "macos")]
+        DecoderKind::VideoToolbox,
+        #[cfg(target_os = "windows")]
+        DecoderKind::Dxva,
+        #[cfg(target_os = "linux")]
+        DecoderKind::Vaapi,
+        DecoderKind::OpenH264,
+    ];
+
+    /// Settings name
+    pub fn name(self) -> &'static str {
+        match self {
+            DecoderKind::GStreamer => "gstreamer",
+            #[cfg(target_os = "macos")]
+            DecoderKind::VideoToolbox => "videotoolbox",
+            #[cfg(target_os = "windows")]
+            DecoderKind::Dxva => "dxva",
+            #[cfg(target_os = "linux")]
+            DecoderKind::Vaapi => "vaapi",
+            DecoderKind::OpenH264 => "openh264",
         }
-        Err(e) => log::warn!("GStreamer decoder not available: {}", e),
     }
 
-    // Try platform-specific hardware decoders as fallback
-    #[cfg(target_os = "macos")]
-    {
-        match videotoolbox::VideoToolboxDecoder::new() {
-            Ok(dec) => {
-                log::info!("Using VideoToolbox hardware decoder");
-                return Ok(Box::new(dec));
-            }
-            Err(e) => log::warn!("VideoToolbox decoder not available: {}", e),
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|k| k.name() == name)
+    }
+
+    pub fn is_hardware(self) -> bool {
+        self != DecoderKind::OpenH264
+    }
+
+    pub fn open(self) -> Result<Box<dyn VideoDecoder>, DecoderError> {
+        Ok(match self {
+            DecoderKind::GStreamer => Box::new(gstreamer::GStreamerDecoder::new()?),
+            #[cfg(target_os = "macos")]
+            DecoderKind::VideoToolbox => Box::new(videotoolbox::VideoToolboxDecoder::new()?),
+            #[cfg(target_os = "windows")]
+            DecoderKind::Dxva => Box::new(dxva::DxvaDecoder::new()?),
+            #[cfg(target_os = "linux")]
+            DecoderKind::Vaapi => Box::new(vaapi::VaapiDecoder::new()?),
+            DecoderKind::OpenH264 => Box::new(software::SoftwareDecoder::new()?),
+        })
+    }
+}
+
+/// Decoders to try: the preferred ones in the given order, then the rest in
+/// default order. Unknown names are skipped.
+pub fn decoder_order(preference: &[String]) -> Vec<DecoderKind> {
+    let mut order: Vec<DecoderKind> = Vec::new();
+    let preferred = preference.iter().filter_map(|name| DecoderKind::from_name(name));
+    for kind in preferred.chain(DecoderKind::ALL.iter().copied()) {
+        if !order.contains(&kind) {
+            order.push(kind);
         }
     }
+    order
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        match dxva::DxvaDecoder::new() {
-            Ok(dec) => {
-                log::info!("Using DXVA2 hardware decoder");
-                return Ok(Box::new(dec));
-            }
-            Err(e) => log::warn!("DXVA2 decoder not available: {}", e),
+/// Create the best available decoder for a stream in `codec`
+pub fn create_decoder_for(codec: Codec) -> Result<Box<dyn VideoDecoder>, DecoderError> {
+    match codec {
+        Codec::H264 => create_decoder(),
+        Codec::Av1 => {
+            let dec = av1::Av1Decoder::new()?;
+            log::info!("Using {} decoder", dec.info());
+            Ok(Box::new(dec))
         }
     }
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        match vaapi::VaapiDecoder::new() {
+/// Whether any decoder for `codec` opens here
+pub fn can_decode(codec: Codec) -> bool {
+    match codec {
+        Codec::H264 => DecoderKind::ALL.iter().any(|kind| kind.open().is_ok()),
+        Codec::Av1 => av1::Av1Decoder::new().is_ok(),
+    }
+}
+
+/// Software decoder for `codec`, used when a hardware decoder fails
+pub fn create_software_decoder_for(codec: Codec) -> Result<Box<dyn VideoDecoder>, DecoderError> {
+    match codec {
+        Codec::H264 => DecoderKind::OpenH264.open(),
+        Codec::Av1 => Ok(Box::new(av1::Av1Decoder::new()?)),
+    }
+}
+
+/// Create the best available decoder for this platform, in the order set
+/// in settings
+pub fn create_decoder() -> Result<Box<dyn VideoDecoder>, DecoderError> {
+    use crate::safe_mode::{self, Subsystem};
+
+    let safe = safe_mode::is_enabled();
+    safe_mode::record(Subsystem::Decoder, safe);
+    if safe {
+        log::info!("Safe mode: using OpenH264 software decoder");
+        return DecoderKind::OpenH264.open();
+    }
+
+    let mut last_error = DecoderError::HardwareNotAvailable;
+    for kind in decoder_order(&crate::commands::decoder_preference()) {
+        match kind.open() {
             Ok(dec) => {
-                log::info!("Using VAAPI hardware decoder");
-                return Ok(Box::new(dec));
+                log::info!("Using {} decoder", dec.info());
+                return Ok(dec);
             }
-            Err(e) => log::warn!("VAAPI decoder not available: {}", e),
+            Err(e) => {
+                log::warn!("{} decoder not available: {}", kind.name(), e);
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// A decoder as listed in settings
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DecoderInfo {
+    /// Settings name (`DecoderKind::name`)
+    pub name: &'static str,
+    pub codec: &'static str,
+    pub hardware: bool,
+    /// Opens on this machine
+    pub available: bool,
+}
+
+/// Every decoder this build knows of and whether it opens here
+pub fn list_decoders() -> Vec<DecoderInfo> {
+    let mut list: Vec<DecoderInfo> = DecoderKind::ALL
+        .iter()
+        .map(|&kind| DecoderInfo {
+            name: kind.name(),
+            codec: Codec::H264.name(),
+            hardware: kind.is_hardware(),
+            available: kind.open().is_ok(),
+        })
+        .collect();
+    list.push(DecoderInfo {
+        name: "dav1d",
+        codec: Codec::Av1.name(),
+        hardware: false,
+        available: av1::Av1Decoder::new().is_ok(),
+    });
+    list
+}
+
+/// Consecutive decode errors after which a hardware decoder is replaced
+/// with a software one. A few errors right after joining or after packet
+/// loss are normal; a hardware decoder that broke keeps failing.
+pub const FALLBACK_AFTER_FAILURES: u32 = 10;
+
+/// Counts consecutive decode failures of one stream
+#[derive(Debug, Default)]
+pub struct DecodeFailures {
+    consecutive: u32,
+}
+
+impl DecodeFailures {
+    /// Record the outcome of one decode. Returns true when the decoder has
+    /// failed often enough in a row that it should be replaced.
+    pub fn record(&mut self, ok: bool) -> bool {
+        if ok {
+            self.consecutive = 0;
+            return false;
+        }
+        self.consecutive += 1;
+        if self.consecutive >= FALLBACK_AFTER_FAILURES {
+            self.consecutive = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// Holds back frames until a keyframe arrives. A decoder fed delta frames
+/// without the keyframe they build on shows garbage or nothing, so a new
+/// decoder starts closed and opens on the first keyframe.
+#[derive(Debug, Default)]
+pub struct KeyframeGate {
+    open: bool,
+    asked: bool,
+}
+
+impl KeyframeGate {
+    /// Whether a frame should be decoded
+    pub fn admit(&mut self, keyframe: bool) -> bool {
+        self.open |= keyframe;
+        self.open
+    }
+
+    /// Whether to ask the sender for a keyframe: once while closed
+    pub fn should_ask(&mut self) -> bool {
+        !self.open && !std::mem::replace(&mut self.asked, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoder_order_puts_preferred_first() {
+        let order = decoder_order(&["openh264".to_string(), "bogus".to_string()]);
+        assert_eq!(order[0], DecoderKind::OpenH264);
+        assert_eq!(order[1], DecoderKind::GStreamer);
+        assert_eq!(order.len(), DecoderKind::ALL.len());
+        assert_eq!(decoder_order(&[]), DecoderKind::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_fallback_after_consecutive_failures() {
+        let mut failures = DecodeFailures::default();
+        for _ in 0..FALLBACK_AFTER_FAILURES - 1 {
+            assert!(!failures.record(false));
         }
+        // A good frame resets the count
+        assert!(!failures.record(true));
+        for _ in 0..FALLBACK_AFTER_FAILURES - 1 {
+            assert!(!failures.record(false));
+        }
+        assert!(failures.record(false));
     }
 
-    // Fall back to software decoder
-    log::info!("Using OpenH264 software decoder");
-    Ok(Box::new(software::SoftwareDecoder::new()?))
+    #[test]
+    fn test_gate_opens_on_keyframe() {
+        let mut gate = KeyframeGate::default();
+        assert!(!gate.admit(false));
+        assert!(gate.should_ask());
+        assert!(!gate.admit(false));
+        assert!(!gate.should_ask());
+        assert!(gate.admit(true));
+        assert!(gate.admit(false));
+    }
 }
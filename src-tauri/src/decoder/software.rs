@@ -2,6 +2,7 @@
 // Cross-platform H.264 software decoding
 
 use super::{DecodedFrame, DecoderConfig, DecoderError, OutputFormat, VideoDecoder};
+use crate::encoder::color::ColorSpace;
 use openh264::decoder::Decoder;
 use openh264::formats::YUVSource;
 use parking_lot::Mutex;
@@ -21,8 +22,9 @@ impl SoftwareDecoder {
         })
     }
 
-    /// Convert YUV420 to BGRA format
-    fn yuv420_to_bgra(
+    /// Convert YUV420 in `color` to BGRA format
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn yuv420_to_bgra(
         y_data: &[u8],
         u_data: &[u8],
         v_data: &[u8],
@@ -31,7 +33,9 @@ impl SoftwareDecoder {
         v_stride: usize,
         width: u32,
         height: u32,
+        color: ColorSpace,
     ) -> Vec<u8> {
+        let to_rgb = color.yuv_to_rgb();
         let w = width as usize;
         let h = height as usize;
         let mut bgra = vec![0u8; w * h * 4];
@@ -44,14 +48,7 @@ impl SoftwareDecoder {
                 let u_idx = uv_y * u_stride + uv_x;
                 let v_idx = uv_y * v_stride + uv_x;
 
-                let y_val = y_data[y_idx] as i32;
-                let u_val = u_data[u_idx] as i32 - 128;
-                let v_val = v_data[v_idx] as i32 - 128;
-
-                // YUV to RGB conversion (BT.601)
-                let r = (y_val + ((v_val * 359) >> 8)).clamp(0, 255) as u8;
-                let g = (y_val - ((u_val * 88 + v_val * 183) >> 8)).clamp(0, 255) as u8;
-                let b = (y_val + ((u_val * 454) >> 8)).clamp(0, 255) as u8;
+                let (r, g, b) = to_rgb.rgb(y_data[y_idx], u_data[u_idx], v_data[v_idx]);
 
                 let bgra_idx = (y * w + x) * 4;
                 bgra[bgra_idx] = b;
@@ -127,6 +124,7 @@ impl VideoDecoder for SoftwareDecoder {
                     v_stride,
                     width,
                     height,
+                    config.color_space,
                 );
 
                 Ok(Some(DecodedFrame::bgra(width, height, timestamp, bgra)))
@@ -45,4 +45,8 @@ impl VideoDecoder for VaapiDecoder {
     fn info(&self) -> &str {
         "VAAPI (Hardware)"
     }
+
+    fn is_hardware(&self) -> bool {
+        true
+    }
 }
@@ -44,4 +44,8 @@ impl VideoDecoder for VideoToolboxDecoder {
     fn info(&self) -> &str {
         "VideoToolbox (Hardware)"
     }
+
+    fn is_hardware(&self) -> bool {
+        true
+    }
 }
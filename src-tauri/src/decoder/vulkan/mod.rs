@@ -6,6 +6,9 @@
 //! Note: Not available on macOS (Apple uses Metal, not Vulkan).
 //! Note: vk-video uses wgpu 24 while our renderer uses wgpu 28.
 //! For now, this decoder outputs to CPU memory (NV12 -> BGRA conversion).
+//! Once the versions match, decode into NV12 textures on
+//! `renderer::gpu::shared()`'s device and return `DecodedFrame::gpu`; the
+//! renderer binds those without a copy.
 
 use crate::decoder::{DecodedFrame, DecoderConfig, DecoderError, VideoDecoder};
 
@@ -16,6 +19,7 @@ use crate::decoder::OutputFormat;
 #[cfg(not(target_os = "macos"))]
 mod inner {
     use super::*;
+    use crate::encoder::color::ColorSpace;
     use parking_lot::Mutex;
     use std::sync::Arc;
 
@@ -25,6 +29,7 @@ mod inner {
         width: u32,
         height: u32,
         output_format: OutputFormat,
+        color_space: ColorSpace,
     }
 
     /// Vulkan Video hardware decoder
@@ -47,8 +52,9 @@ mod inner {
             })
         }
 
-        /// Convert NV12 to BGRA
-        fn nv12_to_bgra(nv12: &[u8], width: u32, height: u32) -> Vec<u8> {
+        /// Convert NV12 in `color` to BGRA
+        fn nv12_to_bgra(nv12: &[u8], width: u32, height: u32, color: ColorSpace) -> Vec<u8> {
+            let to_rgb = color.yuv_to_rgb();
             let w = width as usize;
             let h = height as usize;
             let mut bgra = vec![0u8; w * h * 4];
@@ -61,14 +67,9 @@ mod inner {
                     let y_idx = row * w + col;
                     let uv_idx = (row / 2) * w + (col / 2) * 2;
 
-                    let y = y_plane[y_idx] as i32;
-                    let u = uv_plane.get(uv_idx).copied().unwrap_or(128) as i32 - 128;
-                    let v = uv_plane.get(uv_idx + 1).copied().unwrap_or(128) as i32 - 128;
-
-                    // YUV to RGB (BT.601)
-                    let r = ((298 * (y - 16) + 409 * v + 128) >> 8).clamp(0, 255) as u8;
-                    let g = ((298 * (y - 16) - 100 * u - 208 * v + 128) >> 8).clamp(0, 255) as u8;
-                    let b = ((298 * (y - 16) + 516 * u + 128) >> 8).clamp(0, 255) as u8;
+                    let u = uv_plane.get(uv_idx).copied().unwrap_or(128);
+                    let v = uv_plane.get(uv_idx + 1).copied().unwrap_or(128);
+                    let (r, g, b) = to_rgb.rgb(y_plane[y_idx], u, v);
 
                     let bgra_idx = (row * w + col) * 4;
                     bgra[bgra_idx] = b;
@@ -126,6 +127,7 @@ mod inner {
                 width: config.width,
                 height: config.height,
                 output_format: config.output_format,
+                color_space: config.color_space,
             };
 
             self.state = Some(Mutex::new(state));
@@ -172,7 +174,7 @@ mod inner {
                 // Convert based on output format
                 let decoded = match state.output_format {
                     OutputFormat::BGRA => {
-                        let bgra = Self::nv12_to_bgra(&nv12_data, width, height);
+                        let bgra = Self::nv12_to_bgra(&nv12_data, width, height, state.color_space);
                         DecodedFrame::bgra(width, height, pts, bgra)
                     }
                     OutputFormat::YUV420 => {
@@ -211,7 +213,7 @@ mod inner {
 
                     match state.output_format {
                         OutputFormat::BGRA => {
-                            let bgra = Self::nv12_to_bgra(&nv12_data, width, height);
+                            let bgra = Self::nv12_to_bgra(&nv12_data, width, height, state.color_space);
                             DecodedFrame::bgra(width, height, pts, bgra)
                         }
                         OutputFormat::YUV420 => {
@@ -0,0 +1,169 @@
+//! YUV color spaces of a stream
+//!
+//! The sharer picks the matrix (BT.601 or BT.709) and range (limited 16–235
+//! or full 0–255) its RGB frames are converted to YUV with, and names it in
+//! the stream start so viewers convert back with the same one. BT.601
+//! limited range was the only choice before, and is what an unknown name
+//! means. Coefficients are fixed point with 8 fractional bits.
+
+/// RGB ↔ YUV matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Matrix {
+    #[default]
+    Bt601,
+    /// HD matrix; what modern displays and players assume
+    Bt709,
+}
+
+/// Matrix and range of the YUV frames of a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ColorSpace {
+    pub matrix: Matrix,
+    /// Luma uses 0–255 instead of 16–235 (chroma 0–255 instead of 16–240)
+    pub full_range: bool,
+}
+
+/// RGB → YUV coefficients, rows for Y, Cb and Cr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RgbToYuv {
+    pub y: [i32; 3],
+    pub u: [i32; 3],
+    pub v: [i32; 3],
+    /// Added to luma: 16 for limited range
+    pub y_offset: i32,
+}
+
+impl RgbToYuv {
+    pub fn luma(&self, r: i32, g: i32, b: i32) -> u8 {
+        (((self.y[0] * r + self.y[1] * g + self.y[2] * b + 128) >> 8) + self.y_offset).clamp(0, 255) as u8
+    }
+
+    /// (Cb, Cr)
+    pub fn chroma(&self, r: i32, g: i32, b: i32) -> (u8, u8) {
+        let u = ((self.u[0] * r + self.u[1] * g + self.u[2] * b + 128) >> 8) + 128;
+        let v = ((self.v[0] * r + self.v[1] * g + self.v[2] * b + 128) >> 8) + 128;
+        (u.clamp(0, 255) as u8, v.clamp(0, 255) as u8)
+    }
+}
+
+/// YUV → RGB coefficients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YuvToRgb {
+    /// Subtracted from luma before scaling: 16 for limited range
+    pub y_offset: i32,
+    pub y_scale: i32,
+    /// Cr in red
+    pub rv: i32,
+    /// Cb and Cr subtracted from green
+    pub gu: i32,
+    pub gv: i32,
+    /// Cb in blue
+    pub bu: i32,
+}
+
+impl YuvToRgb {
+    /// (r, g, b) of a pixel, with chroma as stored (centered on 128)
+    pub fn rgb(&self, y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+        let c = self.y_scale * (y as i32 - self.y_offset);
+        let (d, e) = (u as i32 - 128, v as i32 - 128);
+        let r = (c + self.rv * e + 128) >> 8;
+        let g = (c - self.gu * d - self.gv * e + 128) >> 8;
+        let b = (c + self.bu * d + 128) >> 8;
+        (r.clamp(0, 255) as u8, g.clamp(0, 255) as u8, b.clamp(0, 255) as u8)
+    }
+}
+
+impl ColorSpace {
+    pub const BT601: Self = Self { matrix: Matrix::Bt601, full_range: false };
+    pub const BT709: Self = Self { matrix: Matrix::Bt709, full_range: false };
+
+    /// Every color space, in settings order
+    pub const ALL: [Self; 4] = [
+        Self::BT601,
+        Self { matrix: Matrix::Bt601, full_range: true },
+        Self::BT709,
+        Self { matrix: Matrix::Bt709, full_range: true },
+    ];
+
+    /// Name used in settings and `ScreenStart`
+    pub fn name(self) -> &'static str {
+        match (self.matrix, self.full_range) {
+            (Matrix::Bt601, false) => "bt601",
+            (Matrix::Bt601, true) => "bt601-full",
+            (Matrix::Bt709, false) => "bt709",
+            (Matrix::Bt709, true) => "bt709-full",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Id carried in the simple protocol's START message
+    pub fn id(self) -> u8 {
+        Self::ALL.iter().position(|&c| c == self).unwrap_or(0) as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn rgb_to_yuv(self) -> RgbToYuv {
+        let (y, u, v) = match (self.matrix, self.full_range) {
+            (Matrix::Bt601, false) => ([66, 129, 25], [-38, -74, 112], [112, -94, -18]),
+            (Matrix::Bt601, true) => ([77, 150, 29], [-43, -85, 128], [128, -107, -21]),
+            (Matrix::Bt709, false) => ([47, 157, 16], [-26, -86, 112], [112, -102, -10]),
+            (Matrix::Bt709, true) => ([54, 183, 19], [-29, -99, 128], [128, -116, -12]),
+        };
+        RgbToYuv { y, u, v, y_offset: if self.full_range { 0 } else { 16 } }
+    }
+
+    pub fn yuv_to_rgb(self) -> YuvToRgb {
+        let (y_scale, rv, gu, gv, bu) = match (self.matrix, self.full_range) {
+            (Matrix::Bt601, false) => (298, 409, 100, 208, 516),
+            (Matrix::Bt601, true) => (256, 359, 88, 183, 454),
+            (Matrix::Bt709, false) => (298, 459, 55, 136, 541),
+            (Matrix::Bt709, true) => (256, 403, 48, 120, 475),
+        };
+        YuvToRgb { y_offset: if self.full_range { 0 } else { 16 }, y_scale, rv, gu, gv, bu }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_and_ids_round_trip() {
+        for color in ColorSpace::ALL {
+            assert_eq!(ColorSpace::from_name(color.name()), Some(color));
+            assert_eq!(ColorSpace::from_id(color.id()), Some(color));
+        }
+        assert_eq!(ColorSpace::default(), ColorSpace::BT601);
+        assert_eq!(ColorSpace::from_name("rec2020"), None);
+    }
+
+    #[test]
+    fn test_rgb_round_trip() {
+        let colors = [(0, 0, 0), (255, 255, 255), (255, 0, 0), (0, 255, 0), (0, 0, 255), (200, 120, 40)];
+        for color in ColorSpace::ALL {
+            let (to_yuv, to_rgb) = (color.rgb_to_yuv(), color.yuv_to_rgb());
+            for (r, g, b) in colors {
+                let y = to_yuv.luma(r, g, b);
+                let (u, v) = to_yuv.chroma(r, g, b);
+                let back = to_rgb.rgb(y, u, v);
+                let error = [(back.0, r), (back.1, g), (back.2, b)]
+                    .iter()
+                    .map(|&(a, b)| (a as i32 - b).abs())
+                    .max()
+                    .unwrap();
+                assert!(error <= 3, "{} {:?} -> {:?}", color.name(), (r, g, b), back);
+            }
+        }
+
+        // Black and white hit the ends of the range
+        assert_eq!(ColorSpace::BT709.rgb_to_yuv().luma(255, 255, 255), 235);
+        assert_eq!(ColorSpace::ALL[3].rgb_to_yuv().luma(255, 255, 255), 255);
+        assert_eq!(ColorSpace::BT709.rgb_to_yuv().luma(0, 0, 0), 16);
+    }
+}
@@ -0,0 +1,245 @@
+//! GPU frame input for FFmpeg hardware encoders
+//!
+//! Wraps `GpuFrame` surfaces in FFmpeg hardware frames so the encoder reads
+//! the captured image directly:
+//! - D3D11 textures → NVENC (Windows)
+//! - IOSurface-backed CVPixelBuffers → VideoToolbox (macOS)
+//!
+//! The encoder converts BGRA to YUV on the GPU, so neither the readback nor
+//! `bgra_to_yuv420` runs on the CPU.
+
+use crate::capture::{FrameFormat, GpuFrame, GpuSurface};
+use crate::encoder::EncoderError;
+use ffmpeg_next::ffi;
+use ffmpeg_next::frame::Video as VideoFrame;
+use std::ffi::c_void;
+
+/// Hardware input state attached to an opened codec context
+pub struct HwInput {
+    /// AVHWFramesContext for D3D11 input (null for VideoToolbox)
+    frames_ctx: *mut ffi::AVBufferRef,
+    /// Identity of the GPU device the context was built for
+    device_key: usize,
+    format: FrameFormat,
+    width: u32,
+    height: u32,
+}
+
+impl HwInput {
+    /// Configure an unopened codec context to accept frames like `frame`
+    ///
+    /// # Safety
+    /// `ctx` must be a valid codec context that has not been opened yet.
+    pub unsafe fn configure(
+        ctx: *mut ffi::AVCodecContext,
+        frame: &GpuFrame,
+    ) -> Result<Self, EncoderError> {
+        match &frame.surface {
+            #[cfg(target_os = "windows")]
+            GpuSurface::D3D11Texture { device, .. } => unsafe {
+                use windows::core::Interface;
+
+                let frames_ctx = d3d11_frames_context(device.as_raw(), frame.width, frame.height)?;
+                (*ctx).pix_fmt = ffi::AVPixelFormat::AV_PIX_FMT_D3D11;
+                (*ctx).sw_pix_fmt = ffi::AVPixelFormat::AV_PIX_FMT_BGRA;
+                (*ctx).hw_frames_ctx = ffi::av_buffer_ref(frames_ctx);
+
+                Ok(Self {
+                    frames_ctx,
+                    device_key: device.as_raw() as usize,
+                    format: frame.format,
+                    width: frame.width,
+                    height: frame.height,
+                })
+            },
+            #[cfg(target_os = "macos")]
+            GpuSurface::IOSurface(_) => unsafe {
+                // VideoToolbox takes CVPixelBuffers directly, no frames context needed
+                (*ctx).pix_fmt = ffi::AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX;
+                (*ctx).sw_pix_fmt = match frame.format {
+                    FrameFormat::Nv12 => ffi::AVPixelFormat::AV_PIX_FMT_NV12,
+                    _ => ffi::AVPixelFormat::AV_PIX_FMT_BGRA,
+                };
+
+                Ok(Self {
+                    frames_ctx: std::ptr::null_mut(),
+                    device_key: 0,
+                    format: frame.format,
+                    width: frame.width,
+                    height: frame.height,
+                })
+            },
+        }
+    }
+
+    /// Whether frames like `frame` can be fed to the context built by `configure`
+    pub fn matches(&self, frame: &GpuFrame) -> bool {
+        frame.width == self.width
+            && frame.height == self.height
+            && frame.format == self.format
+            && surface_device_key(&frame.surface) == self.device_key
+    }
+
+    /// Wrap a GPU frame as an AVFrame that holds a reference to the surface
+    pub fn wrap(&self, frame: &GpuFrame) -> Result<VideoFrame, EncoderError> {
+        unsafe {
+            let av_frame = ffi::av_frame_alloc();
+            if av_frame.is_null() {
+                return Err(EncoderError::EncodeError("av_frame_alloc failed".to_string()));
+            }
+            (*av_frame).width = frame.width as i32;
+            (*av_frame).height = frame.height as i32;
+
+            // The buffer owns one reference to the surface so FFmpeg can keep
+            // the frame alive while the encoder is still reading it
+            let (surface, free): (*mut c_void, unsafe extern "C" fn(*mut c_void, *mut u8)) =
+                match &frame.surface {
+                    #[cfg(target_os = "windows")]
+                    GpuSurface::D3D11Texture { texture, .. } => {
+                        (*av_frame).format = ffi::AVPixelFormat::AV_PIX_FMT_D3D11 as i32;
+                        (*av_frame).hw_frames_ctx = ffi::av_buffer_ref(self.frames_ctx);
+                        (texture.clone().into_raw(), release_texture)
+                    }
+                    #[cfg(target_os = "macos")]
+                    GpuSurface::IOSurface(pixel_buffer) => {
+                        (*av_frame).format = ffi::AVPixelFormat::AV_PIX_FMT_VIDEOTOOLBOX as i32;
+                        CFRetain(pixel_buffer.as_ptr());
+                        (pixel_buffer.as_ptr(), release_pixel_buffer)
+                    }
+                };
+
+            let buf = ffi::av_buffer_create(surface as *mut u8, 1, Some(free), std::ptr::null_mut(), 0);
+            if buf.is_null() {
+                free(std::ptr::null_mut(), surface as *mut u8);
+                let mut av_frame = av_frame;
+                ffi::av_frame_free(&mut av_frame);
+                return Err(EncoderError::EncodeError("av_buffer_create failed".to_string()));
+            }
+            (*av_frame).buf[0] = buf;
+
+            // D3D11: data[0] = texture, data[1] = array index
+            // VideoToolbox: data[3] = CVPixelBufferRef
+            #[cfg(target_os = "windows")]
+            {
+                (*av_frame).data[0] = surface as *mut u8;
+                (*av_frame).data[1] = std::ptr::null_mut();
+            }
+            #[cfg(target_os = "macos")]
+            {
+                (*av_frame).data[3] = surface as *mut u8;
+            }
+
+            Ok(VideoFrame::wrap(av_frame))
+        }
+    }
+}
+
+impl Drop for HwInput {
+    fn drop(&mut self) {
+        if !self.frames_ctx.is_null() {
+            unsafe { ffi::av_buffer_unref(&mut self.frames_ctx) };
+        }
+    }
+}
+
+// The frames context is reference counted by FFmpeg and only used from the
+// thread that owns the encoder
+unsafe impl Send for HwInput {}
+unsafe impl Sync for HwInput {}
+
+fn surface_device_key(surface: &GpuSurface) -> usize {
+    match surface {
+        #[cfg(target_os = "windows")]
+        GpuSurface::D3D11Texture { device, .. } => {
+            use windows::core::Interface;
+            device.as_raw() as usize
+        }
+        #[cfg(target_os = "macos")]
+        GpuSurface::IOSurface(_) => 0,
+    }
+}
+
+/// Leading field of AVD3D11VADeviceContext (hwcontext_d3d11va.h).
+/// The remaining fields are filled in by av_hwdevice_ctx_init.
+#[cfg(target_os = "windows")]
+#[repr(C)]
+struct D3D11DeviceContextHead {
+    device: *mut c_void,
+}
+
+/// Create a D3D11 frames context on the capture device
+#[cfg(target_os = "windows")]
+unsafe fn d3d11_frames_context(
+    device: *mut c_void,
+    width: u32,
+    height: u32,
+) -> Result<*mut ffi::AVBufferRef, EncoderError> {
+    unsafe {
+        let mut device_ref = ffi::av_hwdevice_ctx_alloc(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_D3D11VA);
+        if device_ref.is_null() {
+            return Err(EncoderError::InitError("av_hwdevice_ctx_alloc failed".to_string()));
+        }
+
+        // FFmpeg releases the device when the context is freed, so hand it its own reference
+        let device_ctx = (*device_ref).data as *mut ffi::AVHWDeviceContext;
+        let d3d11_ctx = (*device_ctx).hwctx as *mut D3D11DeviceContextHead;
+        (*d3d11_ctx).device = device;
+        add_ref(device);
+
+        let ret = ffi::av_hwdevice_ctx_init(device_ref);
+        if ret < 0 {
+            ffi::av_buffer_unref(&mut device_ref);
+            return Err(EncoderError::InitError(format!("av_hwdevice_ctx_init failed: {}", ret)));
+        }
+
+        let mut frames_ref = ffi::av_hwframe_ctx_alloc(device_ref);
+        ffi::av_buffer_unref(&mut device_ref);
+        if frames_ref.is_null() {
+            return Err(EncoderError::InitError("av_hwframe_ctx_alloc failed".to_string()));
+        }
+
+        let frames_ctx = (*frames_ref).data as *mut ffi::AVHWFramesContext;
+        (*frames_ctx).format = ffi::AVPixelFormat::AV_PIX_FMT_D3D11;
+        (*frames_ctx).sw_format = ffi::AVPixelFormat::AV_PIX_FMT_BGRA;
+        (*frames_ctx).width = width as i32;
+        (*frames_ctx).height = height as i32;
+
+        let ret = ffi::av_hwframe_ctx_init(frames_ref);
+        if ret < 0 {
+            ffi::av_buffer_unref(&mut frames_ref);
+            return Err(EncoderError::InitError(format!("av_hwframe_ctx_init failed: {}", ret)));
+        }
+
+        Ok(frames_ref)
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn add_ref(device: *mut c_void) {
+    use windows::core::Interface;
+    use windows::Win32::Graphics::Direct3D11::ID3D11Device;
+
+    // Borrow without taking ownership, then leak a clone to bump the refcount
+    if let Some(device) = unsafe { ID3D11Device::from_raw_borrowed(&device) } {
+        std::mem::forget(device.clone());
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe extern "C" fn release_texture(_opaque: *mut c_void, data: *mut u8) {
+    use windows::core::Interface;
+    use windows::Win32::Graphics::Direct3D11::ID3D11Texture2D;
+
+    drop(unsafe { ID3D11Texture2D::from_raw(data as *mut c_void) });
+}
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn CFRetain(cf: *const c_void) -> *const c_void;
+    fn CFRelease(cf: *const c_void);
+}
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" fn release_pixel_buffer(_opaque: *mut c_void, data: *mut u8) {
+    unsafe { CFRelease(data as *const c_void) };
+}
@@ -6,21 +6,47 @@
 //! - VAAPI (Linux)
 //! - QSV (Intel)
 //! - libx264 software fallback
-
-use crate::encoder::{EncodedFrame, EncoderConfig, EncoderError, EncoderPreset, FrameType, VideoEncoder};
+//!
+//! and AV1 through NVENC, VAAPI and QSV on GPUs that have it, with SVT-AV1
+//! as the software fallback.
+//!
+//! NVENC (D3D11) and VideoToolbox can also take GPU-resident frames, see `hwframe`.
+//!
+//! An encoder is only picked after it has been opened once on this machine
+//! (`probe`): NVENC is compiled into most FFmpeg builds whether or not
+//! there is an NVIDIA GPU.
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+mod hwframe;
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::capture::GpuFrame;
+use crate::capture::{pool, FrameFormat};
+use crate::encoder::color::{ColorSpace, Matrix};
+use crate::encoder::threading::{self, BAND_ROWS};
+use crate::encoder::{
+    Codec, EncodedFrame, EncoderConfig, EncoderError, EncoderPreset, FrameType, RegionOfInterest, VideoEncoder,
+};
 use ffmpeg_next as ffmpeg;
 use ffmpeg_next::codec::Context;
 use ffmpeg_next::encoder::Video as VideoEncoder_;
 use ffmpeg_next::format::Pixel;
 use ffmpeg_next::frame::Video as VideoFrame;
 use ffmpeg_next::{Dictionary, Packet, Rational};
+use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use rayon::prelude::*;
+use std::collections::HashMap;
 use std::sync::Once;
 
 static FFMPEG_INIT: Once = Once::new();
 
+/// Whether each encoder opened on this machine. Finding a codec in FFmpeg
+/// only means it was compiled in, not that the GPU or driver is there.
+static PROBED: Lazy<Mutex<HashMap<(HwEncoderType, Codec), bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
 /// Initialize FFmpeg (call once)
-fn init_ffmpeg() {
+pub(crate) fn init_ffmpeg() {
     FFMPEG_INIT.call_once(|| {
         ffmpeg::init().expect("Failed to initialize FFmpeg");
         // Enable verbose logging in debug builds
@@ -31,27 +57,57 @@ fn init_ffmpeg() {
 }
 
 /// Hardware encoder types in order of preference
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum HwEncoderType {
     Nvenc,        // NVIDIA NVENC
     VideoToolbox, // Apple VideoToolbox
     Vaapi,        // Linux VAAPI
     Qsv,          // Intel QuickSync
     Libx264,      // Software fallback
+    SvtAv1,       // Software AV1
 }
 
 impl HwEncoderType {
-    /// Get the FFmpeg codec name for H.264
-    fn codec_name(&self) -> &'static str {
-        match self {
-            HwEncoderType::Nvenc => "h264_nvenc",
-            HwEncoderType::VideoToolbox => "h264_videotoolbox",
-            HwEncoderType::Vaapi => "h264_vaapi",
-            HwEncoderType::Qsv => "h264_qsv",
-            HwEncoderType::Libx264 => "libx264",
+    pub const ALL: [HwEncoderType; 6] = [
+        HwEncoderType::Nvenc,
+        HwEncoderType::VideoToolbox,
+        HwEncoderType::Vaapi,
+        HwEncoderType::Qsv,
+        HwEncoderType::Libx264,
+        HwEncoderType::SvtAv1,
+    ];
+
+    /// Get the FFmpeg codec name for `codec`, if this encoder does it
+    pub fn codec_name(&self, codec: Codec) -> Option<&'static str> {
+        match (codec, self) {
+            (Codec::H264, HwEncoderType::Nvenc) => Some("h264_nvenc"),
+            (Codec::H264, HwEncoderType::VideoToolbox) => Some("h264_videotoolbox"),
+            (Codec::H264, HwEncoderType::Vaapi) => Some("h264_vaapi"),
+            (Codec::H264, HwEncoderType::Qsv) => Some("h264_qsv"),
+            (Codec::H264, HwEncoderType::Libx264) => Some("libx264"),
+            (Codec::Av1, HwEncoderType::Nvenc) => Some("av1_nvenc"),
+            (Codec::Av1, HwEncoderType::Vaapi) => Some("av1_vaapi"),
+            (Codec::Av1, HwEncoderType::Qsv) => Some("av1_qsv"),
+            (Codec::Av1, HwEncoderType::SvtAv1) => Some("libsvtav1"),
+            _ => None,
         }
     }
 
+    /// The encoder type and codec behind an FFmpeg codec name
+    pub fn from_codec_name(name: &str) -> Option<(HwEncoderType, Codec)> {
+        Self::ALL.into_iter().find_map(|encoder_type| {
+            [Codec::H264, Codec::Av1]
+                .into_iter()
+                .find(|&codec| encoder_type.codec_name(codec) == Some(name))
+                .map(|codec| (encoder_type, codec))
+        })
+    }
+
+    /// Whether the encoder runs on the CPU
+    pub fn is_software(&self) -> bool {
+        matches!(self, HwEncoderType::Libx264 | HwEncoderType::SvtAv1)
+    }
+
     /// Get encoder-specific options
     fn options(&self, preset: EncoderPreset) -> Dictionary<'static> {
         let mut opts = Dictionary::new();
@@ -98,48 +154,106 @@ impl HwEncoderType {
                 opts.set("tune", "zerolatency");
                 opts.set("crf", "23");
             }
+            HwEncoderType::SvtAv1 => {
+                // Higher presets are faster; 8+ keep up in real time
+                opts.set("preset", match preset {
+                    EncoderPreset::UltraFast => "12",
+                    EncoderPreset::Fast => "10",
+                    EncoderPreset::Medium => "9",
+                    EncoderPreset::Quality => "8",
+                });
+                // Low-delay prediction (no reordering) and screen content tools
+                opts.set("svtav1-params", "pred-struct=1:scm=1");
+            }
         }
 
         opts
     }
 }
 
+/// Whether `encoder_type` can encode `codec` here: the codec is compiled
+/// into FFmpeg and opens on this machine's hardware. Opened once per process.
+pub fn probe(encoder_type: HwEncoderType, codec: Codec) -> bool {
+    if let Some(&ok) = PROBED.lock().get(&(encoder_type, codec)) {
+        return ok;
+    }
+
+    let result = FfmpegEncoder::with_codec_type(encoder_type, codec).and_then(|mut enc| {
+        enc.init(EncoderConfig {
+            width: 640,
+            height: 360,
+            fps: 30,
+            bitrate: 1_000_000,
+            max_bitrate: 2_000_000,
+            ..Default::default()
+        })
+    });
+    if let Err(ref e) = result {
+        log::debug!("Probing {:?} {} failed: {}", encoder_type, codec.name(), e);
+    }
+    let ok = result.is_ok();
+    PROBED.lock().insert((encoder_type, codec), ok);
+    ok
+}
+
 /// FFmpeg-based video encoder with hardware acceleration
 pub struct FfmpegEncoder {
     encoder: Option<Mutex<VideoEncoder_>>,
     config: Option<EncoderConfig>,
     encoder_type: HwEncoderType,
+    codec: Codec,
+    codec_name: &'static str,
     force_keyframe: bool,
+    /// Region attached to every frame as ROI side data
+    roi: Option<RegionOfInterest>,
     frame_count: u64,
     pts: i64,
+    /// Set while the codec is opened for GPU frame input
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    hw_input: Option<hwframe::HwInput>,
 }
 
 impl FfmpegEncoder {
-    /// Create a new FFmpeg encoder, trying hardware encoders in order
+    /// Create a new H.264 FFmpeg encoder, trying hardware encoders in order
     pub fn new() -> Result<Self, EncoderError> {
+        Self::new_for(Codec::H264)
+    }
+
+    /// Create a new FFmpeg encoder for `codec`, trying hardware encoders in order
+    pub fn new_for(codec: Codec) -> Result<Self, EncoderError> {
         init_ffmpeg();
 
         // Try hardware encoders in order of preference
-        let encoder_type = Self::detect_best_encoder()?;
+        let (encoder_type, codec_name) = Self::detect_best_encoder(codec)?;
 
-        log::info!("Selected FFmpeg encoder: {:?}", encoder_type);
+        log::info!("Selected FFmpeg encoder: {:?} ({})", encoder_type, codec_name);
 
         Ok(Self {
             encoder: None,
             config: None,
             encoder_type,
+            codec,
+            codec_name,
             force_keyframe: false,
+            roi: None,
             frame_count: 0,
             pts: 0,
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            hw_input: None,
         })
     }
 
-    /// Create with a specific encoder type
+    /// Create an H.264 encoder with a specific encoder type
     pub fn with_type(encoder_type: HwEncoderType) -> Result<Self, EncoderError> {
+        Self::with_codec_type(encoder_type, Codec::H264)
+    }
+
+    /// Create an encoder for `codec` with a specific encoder type
+    pub fn with_codec_type(encoder_type: HwEncoderType, codec: Codec) -> Result<Self, EncoderError> {
         init_ffmpeg();
 
         // Verify the encoder is available
-        let codec_name = encoder_type.codec_name();
+        let codec_name = encoder_type.codec_name(codec).ok_or(EncoderError::HardwareNotAvailable)?;
         ffmpeg::encoder::find_by_name(codec_name)
             .ok_or_else(|| EncoderError::InitError(format!("Codec {} not found", codec_name)))?;
 
@@ -147,19 +261,25 @@ impl FfmpegEncoder {
             encoder: None,
             config: None,
             encoder_type,
+            codec,
+            codec_name,
             force_keyframe: false,
+            roi: None,
             frame_count: 0,
             pts: 0,
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            hw_input: None,
         })
     }
 
-    /// Detect the best available hardware encoder
-    fn detect_best_encoder() -> Result<HwEncoderType, EncoderError> {
-        // Platform-specific priority
+    /// Detect the best available hardware encoder for `codec`
+    fn detect_best_encoder(codec: Codec) -> Result<(HwEncoderType, &'static str), EncoderError> {
+        // Platform-specific priority; entries without the codec are skipped
         #[cfg(target_os = "macos")]
         let priority = [
             HwEncoderType::VideoToolbox,
             HwEncoderType::Libx264,
+            HwEncoderType::SvtAv1,
         ];
 
         #[cfg(target_os = "windows")]
@@ -167,6 +287,7 @@ impl FfmpegEncoder {
             HwEncoderType::Nvenc,
             HwEncoderType::Qsv,
             HwEncoderType::Libx264,
+            HwEncoderType::SvtAv1,
         ];
 
         #[cfg(target_os = "linux")]
@@ -175,13 +296,16 @@ impl FfmpegEncoder {
             HwEncoderType::Vaapi,
             HwEncoderType::Qsv,
             HwEncoderType::Libx264,
+            HwEncoderType::SvtAv1,
         ];
 
         for encoder_type in priority {
-            let codec_name = encoder_type.codec_name();
-            if ffmpeg::encoder::find_by_name(codec_name).is_some() {
+            let Some(codec_name) = encoder_type.codec_name(codec) else {
+                continue;
+            };
+            if probe(encoder_type, codec) {
                 log::info!("Found encoder: {}", codec_name);
-                return Ok(encoder_type);
+                return Ok((encoder_type, codec_name));
             } else {
                 log::debug!("Encoder not available: {}", codec_name);
             }
@@ -191,7 +315,8 @@ impl FfmpegEncoder {
     }
 
     /// Convert BGRA to YUV420P for encoding (two-pass, no branching)
-    fn bgra_to_yuv420(bgra: &[u8], width: u32, height: u32) -> Vec<u8> {
+    fn bgra_to_yuv420(bgra: &[u8], width: u32, height: u32, color: ColorSpace) -> Vec<u8> {
+        let to_yuv = color.rgb_to_yuv();
         let w = width as usize;
         let h = height as usize;
         let bgra_stride = w * 4;
@@ -200,38 +325,45 @@ impl FfmpegEncoder {
         let uv_w = w / 2;
         let uv_h = h / 2;
         let uv_size = uv_w * uv_h;
-        let mut yuv = vec![0u8; y_size + 2 * uv_size];
+        let mut yuv = pool::take(y_size + 2 * uv_size);
 
         let (y_plane, uv_planes) = yuv.split_at_mut(y_size);
         let (u_plane, v_plane) = uv_planes.split_at_mut(uv_size);
 
-        // Pass 1: Y plane (sequential row access, no branching)
-        for y in 0..h {
-            let src_row = y * bgra_stride;
-            let dst_row = y * w;
-            for x in 0..w {
-                let si = src_row + x * 4;
-                let b = bgra[si] as i32;
-                let g = bgra[si + 1] as i32;
-                let r = bgra[si + 2] as i32;
-                y_plane[dst_row + x] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16).clamp(0, 255) as u8;
-            }
-        }
+        threading::in_frame_pool(|| {
+            // Pass 1: Y plane, in bands of rows (sequential access, no branching)
+            y_plane
+                .par_chunks_exact_mut(w.max(1))
+                .with_min_len(BAND_ROWS)
+                .enumerate()
+                .for_each(|(y, dst)| {
+                    let src_row = &bgra[y * bgra_stride..];
+                    for (x, out) in dst.iter_mut().enumerate() {
+                        let si = x * 4;
+                        let b = src_row[si] as i32;
+                        let g = src_row[si + 1] as i32;
+                        let r = src_row[si + 2] as i32;
+                        *out = to_yuv.luma(r, g, b);
+                    }
+                });
 
-        // Pass 2: UV planes in 2x2 blocks (top-left pixel, no per-pixel branch)
-        for by in 0..uv_h {
-            let src_row = (by * 2) * bgra_stride;
-            let uv_row = by * uv_w;
-            for bx in 0..uv_w {
-                let si = src_row + (bx * 2) * 4;
-                let b = bgra[si] as i32;
-                let g = bgra[si + 1] as i32;
-                let r = bgra[si + 2] as i32;
-                let ui = uv_row + bx;
-                u_plane[ui] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128).clamp(0, 255) as u8;
-                v_plane[ui] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128).clamp(0, 255) as u8;
-            }
-        }
+            // Pass 2: UV planes in 2x2 blocks (top-left pixel, no per-pixel branch)
+            u_plane
+                .par_chunks_exact_mut(uv_w.max(1))
+                .zip(v_plane.par_chunks_exact_mut(uv_w.max(1)))
+                .with_min_len(BAND_ROWS / 2)
+                .enumerate()
+                .for_each(|(by, (u_row, v_row))| {
+                    let src_row = &bgra[(by * 2) * bgra_stride..];
+                    for (bx, (u, v)) in u_row.iter_mut().zip(v_row.iter_mut()).enumerate() {
+                        let si = (bx * 2) * 4;
+                        let b = src_row[si] as i32;
+                        let g = src_row[si + 1] as i32;
+                        let r = src_row[si + 2] as i32;
+                        (*u, *v) = to_yuv.chroma(r, g, b);
+                    }
+                });
+        });
 
         yuv
     }
@@ -272,9 +404,23 @@ impl FfmpegEncoder {
     }
 }
 
+/// Tag the stream with its matrix and range, for decoders that convert to
+/// RGB themselves
+fn set_color_space(encoder: &mut ffmpeg::codec::encoder::video::Video, color: ColorSpace) {
+    encoder.set_colorspace(match color.matrix {
+        Matrix::Bt601 => ffmpeg::color::Space::SMPTE170M,
+        Matrix::Bt709 => ffmpeg::color::Space::BT709,
+    });
+    encoder.set_color_range(if color.full_range {
+        ffmpeg::color::Range::JPEG
+    } else {
+        ffmpeg::color::Range::MPEG
+    });
+}
+
 impl VideoEncoder for FfmpegEncoder {
     fn init(&mut self, config: EncoderConfig) -> Result<(), EncoderError> {
-        let codec_name = self.encoder_type.codec_name();
+        let codec_name = self.codec_name;
         let codec = ffmpeg::encoder::find_by_name(codec_name)
             .ok_or_else(|| EncoderError::InitError(format!("Codec {} not found", codec_name)))?;
 
@@ -285,23 +431,44 @@ impl VideoEncoder for FfmpegEncoder {
         // Configure encoder
         encoder.set_width(config.width);
         encoder.set_height(config.height);
-        encoder.set_format(Pixel::YUV420P);
+        // NV12 input goes to the codec as-is; BGRA is converted to YUV420P first
+        encoder.set_format(match config.input_format {
+            FrameFormat::Nv12 => Pixel::NV12,
+            _ => Pixel::YUV420P,
+        });
         encoder.set_time_base(Rational::new(1, config.fps as i32));
         encoder.set_frame_rate(Some(Rational::new(config.fps as i32, 1)));
         encoder.set_bit_rate(config.bitrate as usize);
         encoder.set_max_bit_rate(config.max_bitrate as usize);
         encoder.set_gop(config.keyframe_interval);
+        set_color_space(&mut encoder, config.color_space);
 
         // Set encoder-specific options
-        let opts = self.encoder_type.options(config.preset);
+        let mut opts = self.encoder_type.options(config.preset);
+        if let (Some(crf), HwEncoderType::Libx264) = (config.crf, self.encoder_type) {
+            opts.set("crf", &crf.to_string());
+        }
+        if self.encoder_type.is_software() {
+            let threads = threading::worker_threads();
+            opts.set("threads", &threads.to_string());
+            log::info!("{} using {} thread(s)", codec_name, threads);
+        }
 
-        let encoder = encoder.open_with(opts)
-            .map_err(|e| EncoderError::InitError(format!("Failed to open encoder: {}", e)))?;
+        // x264 spawns its workers on open; they inherit the pinning on Linux
+        let encoder = {
+            let _scope = self.encode_scope();
+            encoder.open_with(opts)
+                .map_err(|e| EncoderError::InitError(format!("Failed to open encoder: {}", e)))?
+        };
 
         self.encoder = Some(Mutex::new(encoder));
         self.config = Some(config.clone());
         self.frame_count = 0;
         self.pts = 0;
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        {
+            self.hw_input = None;
+        }
 
         log::info!(
             "FFmpeg {} encoder initialized: {}x{} @ {} fps, {} bps",
@@ -316,16 +483,24 @@ impl VideoEncoder for FfmpegEncoder {
     }
 
     fn encode(&mut self, frame_data: &[u8], timestamp: u64) -> Result<EncodedFrame, EncoderError> {
-        let config = self.config.as_ref()
-            .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
+        // Switching back from GPU input needs a codec opened for YUV420P
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        if self.hw_input.is_some() {
+            let config = self.config.clone()
+                .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
+            self.init(config)?;
+        }
 
-        let encoder_guard = self.encoder.as_ref()
+        let config = self.config.as_ref()
             .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
 
-        let mut encoder = encoder_guard.lock();
+        if config.input_format == FrameFormat::Nv12 {
+            let mut frame = self.nv12_frame(frame_data)?;
+            return self.send_and_receive(&mut frame, timestamp);
+        }
 
         // Convert BGRA to YUV420P
-        let yuv_data = Self::bgra_to_yuv420(frame_data, config.width, config.height);
+        let yuv_data = Self::bgra_to_yuv420(frame_data, config.width, config.height, config.color_space);
 
         // Create video frame
         let mut frame = VideoFrame::new(Pixel::YUV420P, config.width, config.height);
@@ -370,18 +545,198 @@ impl VideoEncoder for FfmpegEncoder {
                     .copy_from_slice(&yuv_data[src_offset..src_offset + (config.width / 2) as usize]);
             }
         }
+        pool::recycle(yuv_data);
+
+        self.send_and_receive(&mut frame, timestamp)
+    }
+
+    fn request_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<(), EncoderError> {
+        if let Some(ref mut config) = self.config {
+            config.bitrate = bitrate;
+            log::info!("Bitrate change requested to {} bps", bitrate);
+            // Note: Dynamic bitrate change would require recreating the encoder
+            // or using encoder-specific rate control APIs
+        }
+        Ok(())
+    }
+
+    fn info(&self) -> &str {
+        match (self.codec, self.encoder_type) {
+            (Codec::Av1, HwEncoderType::Nvenc) => "FFmpeg NVENC AV1 (Hardware)",
+            (Codec::Av1, HwEncoderType::Vaapi) => "FFmpeg VAAPI AV1 (Hardware)",
+            (Codec::Av1, HwEncoderType::Qsv) => "FFmpeg QuickSync AV1 (Hardware)",
+            (_, HwEncoderType::Nvenc) => "FFmpeg NVENC (Hardware)",
+            (_, HwEncoderType::VideoToolbox) => "FFmpeg VideoToolbox (Hardware)",
+            (_, HwEncoderType::Vaapi) => "FFmpeg VAAPI (Hardware)",
+            (_, HwEncoderType::Qsv) => "FFmpeg QuickSync (Hardware)",
+            (_, HwEncoderType::Libx264) => "FFmpeg libx264 (Software)",
+            (_, HwEncoderType::SvtAv1) => "FFmpeg SVT-AV1 (Software)",
+        }
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    fn get_dimensions(&self) -> Option<(u32, u32)> {
+        self.config.as_ref().map(|c| (c.width, c.height))
+    }
+
+    fn supports_nv12_input(&self) -> bool {
+        // VAAPI needs hardware frames for any input format, SVT-AV1 takes
+        // planar YUV only
+        !matches!(self.encoder_type, HwEncoderType::Vaapi | HwEncoderType::SvtAv1)
+    }
+
+    fn supports_gpu_input(&self) -> bool {
+        match self.encoder_type {
+            HwEncoderType::Nvenc => cfg!(target_os = "windows"),
+            HwEncoderType::VideoToolbox => cfg!(target_os = "macos"),
+            _ => false,
+        }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn encode_gpu(&mut self, frame: &GpuFrame) -> Result<EncodedFrame, EncoderError> {
+        if !self.supports_gpu_input() {
+            return Err(EncoderError::HardwareNotAvailable);
+        }
+
+        // (Re)open the codec for this surface type and device
+        if !self.hw_input.as_ref().is_some_and(|h| h.matches(frame)) {
+            self.open_for_gpu(frame)?;
+        }
+
+        let hw_input = self.hw_input.as_ref()
+            .ok_or_else(|| EncoderError::EncodeError("GPU input not configured".to_string()))?;
+        let mut av_frame = hw_input.wrap(frame)?;
+        av_frame.set_pts(Some(self.pts));
+
+        if self.force_keyframe {
+            av_frame.set_kind(ffmpeg::picture::Type::I);
+            self.force_keyframe = false;
+        }
+
+        self.send_and_receive(&mut av_frame, frame.timestamp)
+    }
+
+    fn supports_roi(&self) -> bool {
+        // FFmpeg turns ROI side data into x264's quantizer offsets and
+        // NVENC's QP delta map; the other wrappers ignore it
+        matches!(self.encoder_type, HwEncoderType::Libx264 | HwEncoderType::Nvenc)
+    }
+
+    fn set_roi(&mut self, roi: Option<RegionOfInterest>) {
+        if self.supports_roi() {
+            self.roi = roi;
+        }
+    }
+}
+
+impl FfmpegEncoder {
+    /// Thread pinning/priority for software encoders; hardware encoders
+    /// barely use the CPU
+    fn encode_scope(&self) -> Option<threading::EncodeScope> {
+        self.encoder_type.is_software().then(threading::enter)
+    }
+
+    /// Wrap packed NV12 data (Y plane, then interleaved CbCr) in a codec frame
+    fn nv12_frame(&mut self, frame_data: &[u8]) -> Result<VideoFrame, EncoderError> {
+        let config = self.config.as_ref()
+            .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
+
+        let width = config.width as usize;
+        let height = config.height as usize;
+        let y_size = width * height;
+        if frame_data.len() < y_size + y_size / 2 {
+            return Err(EncoderError::EncodeError(format!(
+                "NV12 frame too small: {} bytes for {}x{}",
+                frame_data.len(), width, height
+            )));
+        }
+
+        let mut frame = VideoFrame::new(Pixel::NV12, config.width, config.height);
+        frame.set_pts(Some(self.pts));
+
+        if self.force_keyframe {
+            frame.set_kind(ffmpeg::picture::Type::I);
+            self.force_keyframe = false;
+        }
+
+        // Both planes are `width` bytes per row; only the strides differ
+        for (plane, offset, rows) in [(0, 0, height), (1, y_size, height / 2)] {
+            let stride = frame.stride(plane);
+            for row in 0..rows {
+                let src = offset + row * width;
+                let dst = row * stride;
+                frame.data_mut(plane)[dst..dst + width]
+                    .copy_from_slice(&frame_data[src..src + width]);
+            }
+        }
+
+        Ok(frame)
+    }
+
+    /// Attach the region of interest to a frame as side data
+    fn attach_roi(frame: &mut VideoFrame, roi: &RegionOfInterest) {
+        use ffmpeg::ffi;
+
+        let size = std::mem::size_of::<ffi::AVRegionOfInterest>();
+        // SAFETY: the side data buffer is allocated with room for one
+        // AVRegionOfInterest and owned by the frame
+        unsafe {
+            let side_data = ffi::av_frame_new_side_data(
+                frame.as_mut_ptr(),
+                ffi::AVFrameSideDataType::AV_FRAME_DATA_REGIONS_OF_INTEREST,
+                size as _,
+            );
+            if side_data.is_null() {
+                return;
+            }
+            ((*side_data).data as *mut ffi::AVRegionOfInterest).write_unaligned(ffi::AVRegionOfInterest {
+                self_size: size as u32,
+                top: roi.y as i32,
+                bottom: (roi.y + roi.height) as i32,
+                left: roi.x as i32,
+                right: (roi.x + roi.width) as i32,
+                qoffset: ffi::AVRational {
+                    num: (roi.qoffset.clamp(-1.0, 1.0) * 100.0).round() as i32,
+                    den: 100,
+                },
+            });
+        }
+    }
+
+    /// Send a frame to the codec and collect the encoded output
+    fn send_and_receive(&mut self, frame: &mut VideoFrame, timestamp: u64) -> Result<EncodedFrame, EncoderError> {
+        if let Some(roi) = &self.roi {
+            Self::attach_roi(frame, roi);
+        }
+
+        let encoder_guard = self.encoder.as_ref()
+            .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
+
+        let mut encoder = encoder_guard.lock();
+        let _scope = self.encode_scope();
 
         // Send frame to encoder
-        encoder.send_frame(&frame)
+        encoder.send_frame(frame)
             .map_err(|e| EncoderError::EncodeError(format!("Failed to send frame: {}", e)))?;
 
         // Receive encoded packet
         let mut packet = Packet::empty();
         let mut encoded_data = Vec::new();
+        let mut key_packet = false;
 
         while encoder.receive_packet(&mut packet).is_ok() {
             encoded_data.extend_from_slice(packet.data().unwrap_or(&[]));
+            key_packet |= packet.is_key();
         }
+        drop(encoder);
 
         // If no data, the encoder is buffering
         if encoded_data.is_empty() {
@@ -394,7 +749,12 @@ impl VideoEncoder for FfmpegEncoder {
             });
         }
 
-        let frame_type = if Self::is_keyframe(&encoded_data) {
+        // AV1 has no NAL units to look into; trust the packet flags
+        let keyframe = match self.codec {
+            Codec::H264 => Self::is_keyframe(&encoded_data),
+            Codec::Av1 => key_packet,
+        };
+        let frame_type = if keyframe {
             FrameType::KeyFrame
         } else {
             FrameType::Delta
@@ -412,32 +772,55 @@ impl VideoEncoder for FfmpegEncoder {
         })
     }
 
-    fn request_keyframe(&mut self) {
-        self.force_keyframe = true;
-    }
-
-    fn set_bitrate(&mut self, bitrate: u32) -> Result<(), EncoderError> {
-        if let Some(ref mut config) = self.config {
-            config.bitrate = bitrate;
-            log::info!("Bitrate change requested to {} bps", bitrate);
-            // Note: Dynamic bitrate change would require recreating the encoder
-            // or using encoder-specific rate control APIs
+    /// Reopen the codec to take GPU frames like `frame` as input
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn open_for_gpu(&mut self, frame: &GpuFrame) -> Result<(), EncoderError> {
+        let config = self.config.clone()
+            .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
+        if (frame.width, frame.height) != (config.width, config.height) {
+            return Err(EncoderError::EncodeError(format!(
+                "GPU frame {}x{} does not match encoder {}x{}",
+                frame.width, frame.height, config.width, config.height
+            )));
         }
-        Ok(())
-    }
 
-    fn info(&self) -> &str {
-        match self.encoder_type {
-            HwEncoderType::Nvenc => "FFmpeg NVENC (Hardware)",
-            HwEncoderType::VideoToolbox => "FFmpeg VideoToolbox (Hardware)",
-            HwEncoderType::Vaapi => "FFmpeg VAAPI (Hardware)",
-            HwEncoderType::Qsv => "FFmpeg QuickSync (Hardware)",
-            HwEncoderType::Libx264 => "FFmpeg libx264 (Software)",
-        }
-    }
+        let codec_name = self.codec_name;
+        let codec = ffmpeg::encoder::find_by_name(codec_name)
+            .ok_or_else(|| EncoderError::InitError(format!("Codec {} not found", codec_name)))?;
 
-    fn get_dimensions(&self) -> Option<(u32, u32)> {
-        self.config.as_ref().map(|c| (c.width, c.height))
+        let context = Context::new_with_codec(codec);
+        let mut encoder = context.encoder().video()
+            .map_err(|e| EncoderError::InitError(format!("Failed to create encoder context: {}", e)))?;
+
+        encoder.set_width(config.width);
+        encoder.set_height(config.height);
+        encoder.set_time_base(Rational::new(1, config.fps as i32));
+        encoder.set_frame_rate(Some(Rational::new(config.fps as i32, 1)));
+        encoder.set_bit_rate(config.bitrate as usize);
+        encoder.set_max_bit_rate(config.max_bitrate as usize);
+        encoder.set_gop(config.keyframe_interval);
+        set_color_space(&mut encoder, config.color_space);
+
+        let hw_input = unsafe { hwframe::HwInput::configure(encoder.as_mut_ptr(), frame)? };
+
+        let opts = self.encoder_type.options(config.preset);
+        let encoder = encoder.open_with(opts)
+            .map_err(|e| EncoderError::InitError(format!("Failed to open encoder for GPU input: {}", e)))?;
+
+        self.encoder = Some(Mutex::new(encoder));
+        self.hw_input = Some(hw_input);
+        self.pts = 0;
+        // A fresh codec must start with a keyframe
+        self.force_keyframe = true;
+
+        log::info!(
+            "FFmpeg {} encoder reopened for GPU input: {}x{}",
+            codec_name,
+            config.width,
+            config.height
+        );
+
+        Ok(())
     }
 }
 
@@ -446,3 +829,16 @@ impl Default for FfmpegEncoder {
         Self::new().expect("Failed to create FfmpegEncoder")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_codec_name() {
+        assert_eq!(HwEncoderType::from_codec_name("h264_nvenc"), Some((HwEncoderType::Nvenc, Codec::H264)));
+        assert_eq!(HwEncoderType::from_codec_name("av1_qsv"), Some((HwEncoderType::Qsv, Codec::Av1)));
+        assert_eq!(HwEncoderType::from_codec_name("libsvtav1"), Some((HwEncoderType::SvtAv1, Codec::Av1)));
+        assert_eq!(HwEncoderType::from_codec_name("openh264"), None);
+    }
+}
@@ -0,0 +1,432 @@
+//! GPU frame scaling and color conversion
+//!
+//! A compute shader downsamples captured BGRA/RGBA frames to the encode size
+//! and converts them to the encoder's input format (NV12, or BGRA for
+//! encoders without NV12 input) in one pass. For high-resolution captures
+//! this replaces the CPU scaler and color conversion, the largest per-frame
+//! cost before encoding. The result is read back into a buffer so any
+//! encoder can take it; without a usable GPU sharing keeps the CPU path.
+//!
+//! Downscaling averages the source pixels under each output pixel (at most
+//! 4x4 of them), so text holds up better than with the CPU's nearest
+//! neighbor. YUV is in the stream's color space, with the CPU conversion's
+//! coefficients.
+
+use super::color::ColorSpace;
+use crate::capture::{pool, FrameFormat};
+use once_cell::sync::Lazy;
+
+/// Invocations per workgroup; each writes one 4-byte output word
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Most workgroups in one dispatch dimension
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// Size of the shader's `Params`
+const PARAMS_SIZE: u64 = 80;
+
+const SHADER: &str = r#"
+struct Params {
+    src_size: vec2<u32>,
+    dst_size: vec2<u32>,
+    // 1 when the input is RGBA rather than BGRA
+    rgba_input: u32,
+    // Output words to write
+    words: u32,
+    _pad: vec2<u32>,
+    // RGB coefficients of Y, Cb and Cr (8 fractional bits); the luma
+    // offset is in to_y.w
+    to_y: vec4<i32>,
+    to_u: vec4<i32>,
+    to_v: vec4<i32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> src: array<u32>;
+@group(0) @binding(2) var<storage, read_write> dst: array<u32>;
+
+// Average (r, g, b) of the source pixels under the output area d0..d1,
+// from at most 4x4 samples
+fn average(d0: vec2<u32>, d1: vec2<u32>) -> vec3<i32> {
+    let s0 = d0 * params.src_size / params.dst_size;
+    let s1 = max(d1 * params.src_size / params.dst_size, s0 + vec2<u32>(1u));
+    let step = max((s1 - s0) / 4u, vec2<u32>(1u));
+    var sum = vec3<u32>(0u);
+    var n = 0u;
+    for (var y = s0.y; y < s1.y; y += step.y) {
+        for (var x = s0.x; x < s1.x; x += step.x) {
+            let px = src[y * params.src_size.x + x];
+            sum += vec3<u32>(px & 0xFFu, (px >> 8u) & 0xFFu, (px >> 16u) & 0xFFu);
+            n += 1u;
+        }
+    }
+    let c = vec3<i32>((sum + vec3<u32>(n / 2u)) / n);
+    if params.rgba_input == 1u {
+        return c;
+    }
+    return c.zyx;
+}
+
+fn luma(c: vec3<i32>) -> u32 {
+    return u32(clamp(((dot(params.to_y.xyz, c) + 128) >> 8u) + params.to_y.w, 0, 255));
+}
+
+fn chroma_b(c: vec3<i32>) -> u32 {
+    return u32(clamp(((dot(params.to_u.xyz, c) + 128) >> 8u) + 128, 0, 255));
+}
+
+fn chroma_r(c: vec3<i32>) -> u32 {
+    return u32(clamp(((dot(params.to_v.xyz, c) + 128) >> 8u) + 128, 0, 255));
+}
+
+// Byte `i` of the NV12 output: the Y plane, then interleaved CbCr rows
+// (as wide in bytes as the frame is in pixels)
+fn nv12_byte(i: u32) -> u32 {
+    let w = params.dst_size.x;
+    let y_size = w * params.dst_size.y;
+    if i < y_size {
+        let p = vec2<u32>(i % w, i / w);
+        return luma(average(p, p + vec2<u32>(1u)));
+    }
+    let c = i - y_size;
+    let col = c % w;
+    let block = vec2<u32>(col / 2u, c / w) * 2u;
+    let rgb = average(block, block + vec2<u32>(2u));
+    if col % 2u == 0u {
+        return chroma_b(rgb);
+    }
+    return chroma_r(rgb);
+}
+
+@compute @workgroup_size(256)
+fn to_nv12(@builtin(global_invocation_id) id: vec3<u32>) {
+    let word = id.x;
+    if word >= params.words {
+        return;
+    }
+    let total = params.dst_size.x * params.dst_size.y * 3u / 2u;
+    var packed = 0u;
+    for (var b = 0u; b < 4u; b++) {
+        let i = word * 4u + b;
+        if i < total {
+            packed |= nv12_byte(i) << (8u * b);
+        }
+    }
+    dst[word] = packed;
+}
+
+@compute @workgroup_size(256)
+fn to_bgra(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= params.words {
+        return;
+    }
+    let p = vec2<u32>(i % params.dst_size.x, i / params.dst_size.x);
+    let c = average(p, p + vec2<u32>(1u));
+    dst[i] = u32(c.b) | (u32(c.g) << 8u) | (u32(c.r) << 16u) | 0xFF000000u;
+}
+"#;
+
+/// Device and pipelines, shared by every scaler
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    nv12: wgpu::ComputePipeline,
+    bgra: wgpu::ComputePipeline,
+}
+
+static GPU: Lazy<Option<Gpu>> = Lazy::new(|| match pollster::block_on(Gpu::new()) {
+    Ok(gpu) => Some(gpu),
+    Err(e) => {
+        log::warn!("GPU frame scaling not available: {}", e);
+        None
+    }
+});
+
+impl Gpu {
+    async fn new() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .map_err(|e| format!("Failed to request adapter: {}", e))?;
+        if adapter.get_info().device_type == wgpu::DeviceType::Cpu {
+            return Err("only a software adapter is available".to_string());
+        }
+
+        // High-resolution frames need the adapter's full buffer limits
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("Frame Scaler Device"),
+                required_limits: adapter.limits(),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("Failed to create device: {}", e))?;
+        log::info!("GPU frame scaling on {}", adapter.get_info().name);
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Frame Scaler Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Frame Scaler Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Frame Scaler Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            immediate_size: 0,
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let nv12 = pipeline("to_nv12");
+        let bgra = pipeline("to_bgra");
+
+        Ok(Self { device, queue, layout, nv12, bgra })
+    }
+}
+
+/// Bytes of a `width` x `height` frame in `format`
+fn frame_len(format: FrameFormat, width: u32, height: u32) -> usize {
+    let (w, h) = (width as usize, height as usize);
+    match format {
+        FrameFormat::Nv12 => w * h + (w / 2) * 2 * (h / 2),
+        FrameFormat::Bgra | FrameFormat::Rgba => w * h * 4,
+    }
+}
+
+/// Scales frames of one size and format to one output on the GPU
+pub struct GpuScaler {
+    pub src_width: u32,
+    pub src_height: u32,
+    pub dst_width: u32,
+    pub dst_height: u32,
+    pub input: FrameFormat,
+    pub output: FrameFormat,
+    pub color: ColorSpace,
+    src_buffer: wgpu::Buffer,
+    dst_buffer: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    words: u32,
+    out_len: usize,
+}
+
+impl GpuScaler {
+    /// Set up scaling of `input` frames (BGRA or RGBA) to `dst_width` x
+    /// `dst_height` in `output` (NV12 in `color`, or BGRA). Fails when no
+    /// GPU is usable or the frames are too large for it.
+    pub fn new(
+        src_width: u32,
+        src_height: u32,
+        dst_width: u32,
+        dst_height: u32,
+        input: FrameFormat,
+        output: FrameFormat,
+        color: ColorSpace,
+    ) -> Result<Self, String> {
+        if input == FrameFormat::Nv12 || output == FrameFormat::Rgba {
+            return Err(format!("Cannot scale {:?} to {:?}", input, output));
+        }
+        if dst_width == 0 || dst_height == 0 || dst_width % 2 != 0 || dst_height % 2 != 0 {
+            return Err(format!("Invalid output size {}x{}", dst_width, dst_height));
+        }
+        let gpu = GPU.as_ref().ok_or("No GPU available")?;
+
+        let in_len = frame_len(input, src_width, src_height) as u64;
+        let out_len = frame_len(output, dst_width, dst_height);
+        let out_size = (out_len as u64).div_ceil(4) * 4;
+        let words = (out_size / 4) as u32;
+        let limit = gpu.device.limits().max_storage_buffer_binding_size as u64;
+        if in_len > limit || out_size > limit || words.div_ceil(WORKGROUP_SIZE) > MAX_WORKGROUPS {
+            return Err(format!("{}x{} frames are too large for the GPU", src_width, src_height));
+        }
+
+        let buffer = |label, size, usage| {
+            gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let params = buffer("Frame Scaler Params", PARAMS_SIZE, wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST);
+        let src_buffer = buffer("Frame Scaler Input", in_len, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST);
+        let dst_buffer = buffer("Frame Scaler Output", out_size, wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC);
+        let readback = buffer("Frame Scaler Readback", out_size, wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST);
+
+        let values = [
+            src_width,
+            src_height,
+            dst_width,
+            dst_height,
+            (input == FrameFormat::Rgba) as u32,
+            words,
+            0,
+            0,
+        ];
+        let to_yuv = color.rgb_to_yuv();
+        let coefficients = [
+            to_yuv.y[0],
+            to_yuv.y[1],
+            to_yuv.y[2],
+            to_yuv.y_offset,
+            to_yuv.u[0],
+            to_yuv.u[1],
+            to_yuv.u[2],
+            0,
+            to_yuv.v[0],
+            to_yuv.v[1],
+            to_yuv.v[2],
+            0,
+        ];
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .chain(coefficients.iter().flat_map(|v| v.to_le_bytes()))
+            .collect();
+        gpu.queue.write_buffer(&params, 0, &bytes);
+
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Frame Scaler Bind Group"),
+            layout: &gpu.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: src_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: dst_buffer.as_entire_binding() },
+            ],
+        });
+
+        log::info!(
+            "GPU frame scaler: {}x{} {:?} -> {}x{} {:?}",
+            src_width, src_height, input, dst_width, dst_height, output
+        );
+        Ok(Self {
+            src_width,
+            src_height,
+            dst_width,
+            dst_height,
+            input,
+            output,
+            color,
+            src_buffer,
+            dst_buffer,
+            readback,
+            bind_group,
+            words,
+            out_len,
+        })
+    }
+
+    /// Whether this scaler handles the given frames and output
+    pub fn matches(
+        &self,
+        src: (u32, u32),
+        dst: (u32, u32),
+        input: FrameFormat,
+        output: FrameFormat,
+        color: ColorSpace,
+    ) -> bool {
+        (self.src_width, self.src_height) == src
+            && (self.dst_width, self.dst_height) == dst
+            && self.input == input
+            && self.output == output
+            && self.color == color
+    }
+
+    /// Scale and convert one frame; blocks until the GPU is done
+    pub fn process(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let gpu = GPU.as_ref().ok_or("No GPU available")?;
+        if data.len() as u64 != self.src_buffer.size() {
+            return Err(format!("Frame is {} bytes, expected {}", data.len(), self.src_buffer.size()));
+        }
+        gpu.queue.write_buffer(&self.src_buffer, 0, data);
+
+        let mut encoder = gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Frame Scaler Encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Frame Scaler Pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(match self.output {
+                FrameFormat::Nv12 => &gpu.nv12,
+                _ => &gpu.bgra,
+            });
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(self.words.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.dst_buffer, 0, &self.readback, 0, self.readback.size());
+        gpu.queue.submit([encoder.finish()]);
+
+        let slice = self.readback.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        gpu.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| format!("GPU poll failed: {}", e))?;
+        rx.recv()
+            .map_err(|_| "Readback was dropped".to_string())?
+            .map_err(|e| format!("Readback failed: {}", e))?;
+
+        let mut out = pool::take_empty(self.out_len);
+        out.extend_from_slice(&slice.get_mapped_range()[..self.out_len]);
+        self.readback.unmap();
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_len() {
+        assert_eq!(frame_len(FrameFormat::Nv12, 1920, 1080), 1920 * 1080 * 3 / 2);
+        assert_eq!(frame_len(FrameFormat::Bgra, 1280, 720), 1280 * 720 * 4);
+    }
+}
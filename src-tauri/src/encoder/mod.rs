@@ -1,9 +1,13 @@
 // Video encoder module
 // Hardware encoding with software fallback
 
+pub mod color;
 pub mod ffmpeg;
+pub mod gpu_scaler;
 pub mod scaler;
 pub mod software;
+pub mod threading;
+pub mod validate;
 
 // Legacy platform-specific stubs (kept for reference)
 #[cfg(target_os = "macos")]
@@ -15,6 +19,8 @@ pub mod nvenc;
 #[cfg(target_os = "linux")]
 pub mod vaapi;
 
+use crate::capture::{FrameFormat, GpuFrame};
+use color::ColorSpace;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -36,16 +42,85 @@ pub struct EncoderConfig {
     pub max_bitrate: u32,
     pub keyframe_interval: u32,
     pub preset: EncoderPreset,
+    /// Pixel format of the frames passed to `encode` (BGRA or NV12)
+    pub input_format: FrameFormat,
+    /// Constant quality (x264 CRF) instead of the bitrate, for encoders
+    /// that support it; the others keep to the bitrate
+    pub crf: Option<u8>,
+    /// Matrix and range BGRA input is converted to YUV with, and that the
+    /// stream is tagged with
+    pub color_space: ColorSpace,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// CRF of the near-lossless text mode. True lossless (CRF 0) needs the
+/// High 4:4:4 profile, which viewers' OpenH264 decoders cannot play; at 8
+/// small fonts are indistinguishable from the source
+pub const NEAR_LOSSLESS_CRF: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum EncoderPreset {
+    #[default]
     UltraFast, // Lowest latency
     Fast,
     Medium,
     Quality, // Best quality
 }
 
+impl EncoderPreset {
+    /// Name used in settings
+    pub fn name(self) -> &'static str {
+        match self {
+            EncoderPreset::UltraFast => "ultrafast",
+            EncoderPreset::Fast => "fast",
+            EncoderPreset::Medium => "medium",
+            EncoderPreset::Quality => "quality",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ultrafast" => Some(EncoderPreset::UltraFast),
+            "fast" => Some(EncoderPreset::Fast),
+            "medium" => Some(EncoderPreset::Medium),
+            "quality" => Some(EncoderPreset::Quality),
+            _ => None,
+        }
+    }
+}
+
+/// Encoder settings the sharer picks, applied on top of each pipeline's
+/// own resolution, frame rate and bitrate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderTuning {
+    pub preset: EncoderPreset,
+    /// Seconds between keyframes
+    pub keyframe_interval_secs: u32,
+    /// Peak bitrate in bps (`None` = twice the target bitrate)
+    pub max_bitrate: Option<u32>,
+}
+
+impl Default for EncoderTuning {
+    fn default() -> Self {
+        Self {
+            preset: EncoderPreset::UltraFast,
+            keyframe_interval_secs: 1,
+            max_bitrate: None,
+        }
+    }
+}
+
+impl EncoderTuning {
+    /// Keyframe interval in frames at `fps`
+    pub fn keyframe_interval(&self, fps: u32) -> u32 {
+        fps.max(1) * self.keyframe_interval_secs.max(1)
+    }
+
+    /// Peak bitrate for a target of `bitrate`; never below the target
+    pub fn max_bitrate(&self, bitrate: u32) -> u32 {
+        self.max_bitrate.map_or(bitrate.saturating_mul(2), |max| max.max(bitrate))
+    }
+}
+
 impl Default for EncoderConfig {
     fn default() -> Self {
         Self {
@@ -56,6 +131,52 @@ impl Default for EncoderConfig {
             max_bitrate: 15_000_000, // 15 Mbps peak
             keyframe_interval: 60,   // 1 second at 60fps
             preset: EncoderPreset::UltraFast,
+            input_format: FrameFormat::Bgra,
+            crf: None,
+            color_space: ColorSpace::default(),
+        }
+    }
+}
+
+/// Video codec of a stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Codec {
+    #[default]
+    H264,
+    /// Better quality at low bitrates, on hardware that can encode it
+    Av1,
+}
+
+impl Codec {
+    /// Name used in settings and `ScreenStart`
+    pub fn name(self) -> &'static str {
+        match self {
+            Codec::H264 => "h264",
+            Codec::Av1 => "av1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "h264" => Some(Codec::H264),
+            "av1" => Some(Codec::Av1),
+            _ => None,
+        }
+    }
+
+    /// Id carried in the simple protocol's START message
+    pub fn id(self) -> u8 {
+        match self {
+            Codec::H264 => 0,
+            Codec::Av1 => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Codec::H264),
+            1 => Some(Codec::Av1),
+            _ => None,
         }
     }
 }
@@ -74,6 +195,43 @@ pub struct EncodedFrame {
     pub size: usize,
 }
 
+/// Part of the frame encoded at higher quality than the rest, in encoded
+/// frame pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegionOfInterest {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Quantizer offset from -1.0 (best quality) to 1.0 (worst)
+    pub qoffset: f32,
+}
+
+/// Quantizer offset of the area around the cursor: a third of the way to
+/// the best quality, which keeps it sharp without starving the rest
+const CURSOR_ROI_QOFFSET: f32 = -1.0 / 3.0;
+
+impl RegionOfInterest {
+    /// Square around the pointer at (x, y) in a `width`x`height` frame,
+    /// about a quarter of the frame wide and kept inside it. `None` when
+    /// the pointer is off the frame.
+    pub fn around_cursor(x: i32, y: i32, width: u32, height: u32) -> Option<Self> {
+        if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+            return None;
+        }
+        let side = (width / 4).clamp(128, 512).min(width).min(height);
+        let left = (x as u32).saturating_sub(side / 2).min(width - side);
+        let top = (y as u32).saturating_sub(side / 2).min(height - side);
+        Some(Self {
+            x: left,
+            y: top,
+            width: side,
+            height: side,
+            qoffset: CURSOR_ROI_QOFFSET,
+        })
+    }
+}
+
 /// Video encoder trait
 pub trait VideoEncoder: Send + Sync {
     /// Initialize the encoder
@@ -91,12 +249,80 @@ pub trait VideoEncoder: Send + Sync {
     /// Get encoder info
     fn info(&self) -> &str;
 
+    /// Codec of the encoded frames
+    fn codec(&self) -> Codec {
+        Codec::H264
+    }
+
     /// Get actual encoding dimensions (may differ from input if scaling is applied)
     fn get_dimensions(&self) -> Option<(u32, u32)>;
+
+    /// Whether `encode` accepts NV12 input (`EncoderConfig::input_format`),
+    /// which skips the BGRA→YUV conversion
+    fn supports_nv12_input(&self) -> bool {
+        false
+    }
+
+    /// Whether `encode_gpu` can consume GPU-resident frames on this platform
+    fn supports_gpu_input(&self) -> bool {
+        false
+    }
+
+    /// Encode a frame that is still in GPU memory. The frame size must match
+    /// the configured encoding dimensions (no scaling is applied).
+    fn encode_gpu(&mut self, _frame: &GpuFrame) -> Result<EncodedFrame, EncoderError> {
+        Err(EncoderError::HardwareNotAvailable)
+    }
+
+    /// Whether the encoder can spend more bits on a region (`set_roi`)
+    fn supports_roi(&self) -> bool {
+        false
+    }
+
+    /// Region to encode at higher quality from the next frame on, or `None`
+    /// for uniform quality. Ignored by encoders without ROI support.
+    fn set_roi(&mut self, _roi: Option<RegionOfInterest>) {}
 }
 
-/// Create the best available encoder for this platform
+/// Create the best available encoder for this platform, in the codec
+/// chosen in settings when it can be encoded here and H.264 otherwise
 pub fn create_encoder() -> Result<Box<dyn VideoEncoder>, EncoderError> {
+    if crate::commands::preferred_codec() == Codec::Av1 {
+        match create_encoder_for(Codec::Av1) {
+            Ok(enc) => return Ok(enc),
+            Err(e) => log::warn!("AV1 encoder not available, using H.264: {}", e),
+        }
+    }
+    create_encoder_for(Codec::H264)
+}
+
+/// Create the best available encoder for `codec`, e.g. to replace an
+/// encoder mid-stream without changing what viewers decode
+pub fn create_encoder_for(codec: Codec) -> Result<Box<dyn VideoEncoder>, EncoderError> {
+    use crate::safe_mode::{self, Subsystem};
+
+    let safe = safe_mode::is_enabled();
+    safe_mode::record(Subsystem::Encoder, safe);
+    if codec == Codec::Av1 {
+        // Every AV1 encoder is a hardware or FFmpeg one
+        if safe {
+            return Err(EncoderError::HardwareNotAvailable);
+        }
+        if let Some(enc) = forced_encoder(codec) {
+            return Ok(enc);
+        }
+        let enc = ffmpeg::FfmpegEncoder::new_for(Codec::Av1)?;
+        log::info!("Using FFmpeg encoder: {}", enc.info());
+        return Ok(Box::new(enc));
+    }
+    if safe {
+        log::info!("Safe mode: using OpenH264 software encoder");
+        return Ok(Box::new(software::SoftwareEncoder::new()?));
+    }
+    if let Some(enc) = forced_encoder(codec) {
+        return Ok(enc);
+    }
+
     // Try FFmpeg hardware-accelerated encoder first
     match ffmpeg::FfmpegEncoder::new() {
         Ok(enc) => {
@@ -110,3 +336,114 @@ pub fn create_encoder() -> Result<Box<dyn VideoEncoder>, EncoderError> {
     log::info!("Using OpenH264 software encoder");
     Ok(Box::new(software::SoftwareEncoder::new()?))
 }
+
+/// Settings name of the OpenH264 software encoder; FFmpeg encoders go by
+/// their FFmpeg codec name
+pub const OPENH264: &str = "openh264";
+
+/// The encoder forced in settings, if it does `codec` and works here;
+/// otherwise the automatic choice is used
+fn forced_encoder(codec: Codec) -> Option<Box<dyn VideoEncoder>> {
+    let name = crate::commands::forced_encoder()?;
+    if name == OPENH264 {
+        if codec != Codec::H264 {
+            return None;
+        }
+        return match software::SoftwareEncoder::new() {
+            Ok(enc) => {
+                log::info!("Using forced encoder: OpenH264");
+                Some(Box::new(enc))
+            }
+            Err(e) => {
+                log::warn!("Forced encoder OpenH264 not available: {}", e);
+                None
+            }
+        };
+    }
+
+    let (encoder_type, forced_codec) = ffmpeg::HwEncoderType::from_codec_name(&name)?;
+    if forced_codec != codec {
+        log::debug!("Forced encoder {} does not encode {}", name, codec.name());
+        return None;
+    }
+    if !ffmpeg::probe(encoder_type, codec) {
+        log::warn!("Forced encoder {} does not work on this machine, choosing automatically", name);
+        return None;
+    }
+    match ffmpeg::FfmpegEncoder::with_codec_type(encoder_type, codec) {
+        Ok(enc) => {
+            log::info!("Using forced encoder: {}", enc.info());
+            Some(Box::new(enc))
+        }
+        Err(e) => {
+            log::warn!("Forced encoder {} not available: {}", name, e);
+            None
+        }
+    }
+}
+
+/// An encoder that can be forced in settings
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EncoderInfo {
+    /// Settings name: `OPENH264` or the FFmpeg codec name
+    pub name: &'static str,
+    pub codec: &'static str,
+    pub hardware: bool,
+    /// Opens on this machine
+    pub available: bool,
+}
+
+/// Every encoder this build knows of, probing the ones not tried yet
+/// (slow the first time: each is opened once)
+pub fn list_encoders() -> Vec<EncoderInfo> {
+    let mut list = vec![EncoderInfo {
+        name: OPENH264,
+        codec: Codec::H264.name(),
+        hardware: false,
+        available: software::SoftwareEncoder::new().is_ok(),
+    }];
+    for codec in [Codec::H264, Codec::Av1] {
+        for encoder_type in ffmpeg::HwEncoderType::ALL {
+            let Some(name) = encoder_type.codec_name(codec) else {
+                continue;
+            };
+            list.push(EncoderInfo {
+                name,
+                codec: codec.name(),
+                hardware: !encoder_type.is_software(),
+                available: ffmpeg::probe(encoder_type, codec),
+            });
+        }
+    }
+    list
+}
+
+/// Create an encoder for the near-lossless text mode. Only libx264 does
+/// constant quality this low; hardware encoders smear small fonts at any
+/// bitrate.
+pub fn create_near_lossless_encoder() -> Result<Box<dyn VideoEncoder>, EncoderError> {
+    if crate::safe_mode::is_enabled() {
+        return Err(EncoderError::HardwareNotAvailable);
+    }
+    let enc = ffmpeg::FfmpegEncoder::with_type(ffmpeg::HwEncoderType::Libx264)?;
+    log::info!("Using FFmpeg encoder for near-lossless mode: {}", enc.info());
+    Ok(Box::new(enc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_roi_stays_inside_frame() {
+        let roi = RegionOfInterest::around_cursor(960, 540, 1920, 1080).unwrap();
+        assert_eq!((roi.x, roi.y, roi.width, roi.height), (720, 300, 480, 480));
+
+        // Pushed back inside at the corner
+        let roi = RegionOfInterest::around_cursor(1915, 5, 1920, 1080).unwrap();
+        assert_eq!((roi.x, roi.y), (1920 - 480, 0));
+
+        assert_eq!(RegionOfInterest::around_cursor(-1, 5, 1920, 1080), None);
+        assert_eq!(RegionOfInterest::around_cursor(5, 1080, 1920, 1080), None);
+    }
+}
@@ -3,6 +3,13 @@
 //! Supports two modes:
 //! 1. Cropping: fast edge removal when dimensions slightly exceed OpenH264 limits
 //! 2. Downscaling: nearest-neighbor resize for significant resolution reduction
+//!
+//! Row copies run in bands on the frame pool, so a 4K source does not eat
+//! the frame budget on a single core.
+
+use super::threading::{self, BAND_ROWS};
+use crate::capture::pool;
+use rayon::prelude::*;
 
 /// Maximum dimensions supported by OpenH264
 pub const OPENH264_MAX_WIDTH: u32 = 3840;
@@ -147,15 +154,8 @@ impl FrameScaler {
     fn crop_width<'a>(&self, src: &[u8]) -> std::borrow::Cow<'a, [u8]> {
         let src_stride = self.src_width as usize * 4;
         let dst_stride = self.dst_width as usize * 4;
-        let mut dst = vec![0u8; dst_stride * self.src_height as usize];
-
-        for y in 0..self.src_height as usize {
-            let src_offset = y * src_stride;
-            let dst_offset = y * dst_stride;
-            dst[dst_offset..dst_offset + dst_stride]
-                .copy_from_slice(&src[src_offset..src_offset + dst_stride]);
-        }
-
+        let mut dst = pool::take(dst_stride * self.src_height as usize);
+        copy_rows(src, src_stride, &mut dst, dst_stride);
         std::borrow::Cow::Owned(dst)
     }
 
@@ -163,15 +163,8 @@ impl FrameScaler {
     fn crop_both<'a>(&self, src: &[u8]) -> std::borrow::Cow<'a, [u8]> {
         let src_stride = self.src_width as usize * 4;
         let dst_stride = self.dst_width as usize * 4;
-        let mut dst = vec![0u8; dst_stride * self.dst_height as usize];
-
-        for y in 0..self.dst_height as usize {
-            let src_offset = y * src_stride;
-            let dst_offset = y * dst_stride;
-            dst[dst_offset..dst_offset + dst_stride]
-                .copy_from_slice(&src[src_offset..src_offset + dst_stride]);
-        }
-
+        let mut dst = pool::take(dst_stride * self.dst_height as usize);
+        copy_rows(src, src_stride, &mut dst, dst_stride);
         std::borrow::Cow::Owned(dst)
     }
 
@@ -184,26 +177,42 @@ impl FrameScaler {
         let dh = self.dst_height as usize;
         let src_stride = sw * 4;
         let dst_stride = dw * 4;
-        let mut dst = vec![0u8; dst_stride * dh];
+        let mut dst = pool::take(dst_stride * dh);
 
         // Precompute source X byte offsets for each destination column
         let x_offsets: Vec<usize> = (0..dw).map(|dx| (dx * sw / dw) * 4).collect();
 
-        for dy in 0..dh {
-            let sy = dy * sh / dh;
-            let src_row = sy * src_stride;
-            let dst_row = dy * dst_stride;
-            for (dx, &sx_off) in x_offsets.iter().enumerate() {
-                let si = src_row + sx_off;
-                let di = dst_row + dx * 4;
-                dst[di..di + 4].copy_from_slice(&src[si..si + 4]);
-            }
-        }
+        threading::in_frame_pool(|| {
+            dst.par_chunks_mut(dst_stride)
+                .with_min_len(BAND_ROWS)
+                .enumerate()
+                .for_each(|(dy, dst_row)| {
+                    let sy = dy * sh / dh;
+                    let src_row = &src[sy * src_stride..(sy + 1) * src_stride];
+                    for (px, &sx_off) in dst_row.chunks_exact_mut(4).zip(&x_offsets) {
+                        px.copy_from_slice(&src_row[sx_off..sx_off + 4]);
+                    }
+                });
+        });
 
         dst
     }
 }
 
+/// Copy the first `dst_stride` bytes of each source row, as many rows as
+/// `dst` holds
+fn copy_rows(src: &[u8], src_stride: usize, dst: &mut [u8], dst_stride: usize) {
+    threading::in_frame_pool(|| {
+        dst.par_chunks_mut(dst_stride)
+            .with_min_len(BAND_ROWS)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let start = y * src_stride;
+                row.copy_from_slice(&src[start..start + dst_stride]);
+            });
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,4 +326,16 @@ mod tests {
         // (0,1) maps to src (0,2) = blue
         assert_eq!(&result[8..12], &[255, 0, 0, 255]);
     }
+
+    #[test]
+    fn test_crop_width_keeps_every_row() {
+        // More rows than one band, so the copy is split across tasks
+        let scaler = FrameScaler::new(3842, 100);
+        let src: Vec<u8> = (0..100u8).flat_map(|y| vec![y; 3842 * 4]).collect();
+        let result = scaler.scale(&src);
+        assert_eq!(result.len(), 3840 * 100 * 4);
+        for (y, row) in result.chunks_exact(3840 * 4).enumerate() {
+            assert!(row.iter().all(|&b| b == y as u8));
+        }
+    }
 }
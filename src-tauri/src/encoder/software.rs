@@ -2,11 +2,15 @@
 // Cross-platform H.264 software encoding
 
 use super::scaler::FrameScaler;
+use super::color::ColorSpace;
+use super::threading::{self, BAND_ROWS};
+use crate::capture::{pool, FrameFormat};
 use super::{EncodedFrame, EncoderConfig, EncoderError, FrameType, VideoEncoder};
 use openh264::encoder::{Encoder, EncoderConfig as H264Config};
-use openh264::formats::YUVBuffer;
+use openh264::formats::YUVSlices;
 use openh264::OpenH264API;
 use parking_lot::Mutex;
+use rayon::prelude::*;
 
 pub struct SoftwareEncoder {
     config: Option<EncoderConfig>,
@@ -32,7 +36,10 @@ impl SoftwareEncoder {
     /// Optimized with two-pass approach:
     /// - Pass 1: Y plane computed row-by-row (sequential memory access)
     /// - Pass 2: UV planes computed in 2x2 blocks using top-left pixel (no branching)
-    fn bgra_to_yuv420(bgra: &[u8], width: u32, height: u32) -> Vec<u8> {
+    ///
+    /// Both passes split the rows into bands on the frame pool.
+    fn bgra_to_yuv420(bgra: &[u8], width: u32, height: u32, color: ColorSpace) -> Vec<u8> {
+        let to_yuv = color.rgb_to_yuv();
         let w = width as usize;
         let h = height as usize;
         let bgra_stride = w * 4;
@@ -42,36 +49,75 @@ impl SoftwareEncoder {
         let uv_w = w / 2;
         let uv_h = h / 2;
         let uv_size = uv_w * uv_h;
-        let mut yuv = vec![0u8; y_size + 2 * uv_size];
+        let mut yuv = pool::take(y_size + 2 * uv_size);
 
         let (y_plane, uv_planes) = yuv.split_at_mut(y_size);
         let (u_plane, v_plane) = uv_planes.split_at_mut(uv_size);
 
-        // Pass 1: Compute Y plane (sequential row access, no branching)
-        for y in 0..h {
-            let src_row = y * bgra_stride;
-            let dst_row = y * w;
-            for x in 0..w {
-                let si = src_row + x * 4;
-                let b = bgra[si] as i32;
-                let g = bgra[si + 1] as i32;
-                let r = bgra[si + 2] as i32;
-                y_plane[dst_row + x] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16).clamp(0, 255) as u8;
-            }
+        threading::in_frame_pool(|| {
+            // Pass 1: Y plane, in bands of rows (sequential access, no branching)
+            y_plane
+                .par_chunks_exact_mut(w.max(1))
+                .with_min_len(BAND_ROWS)
+                .enumerate()
+                .for_each(|(y, dst)| {
+                    let src_row = &bgra[y * bgra_stride..];
+                    for (x, out) in dst.iter_mut().enumerate() {
+                        let si = x * 4;
+                        let b = src_row[si] as i32;
+                        let g = src_row[si + 1] as i32;
+                        let r = src_row[si + 2] as i32;
+                        *out = to_yuv.luma(r, g, b);
+                    }
+                });
+
+            // Pass 2: UV planes in 2x2 blocks (top-left pixel, no per-pixel branch)
+            u_plane
+                .par_chunks_exact_mut(uv_w.max(1))
+                .zip(v_plane.par_chunks_exact_mut(uv_w.max(1)))
+                .with_min_len(BAND_ROWS / 2)
+                .enumerate()
+                .for_each(|(by, (u_row, v_row))| {
+                    let src_row = &bgra[(by * 2) * bgra_stride..];
+                    for (bx, (u, v)) in u_row.iter_mut().zip(v_row.iter_mut()).enumerate() {
+                        let si = (bx * 2) * 4;
+                        let b = src_row[si] as i32;
+                        let g = src_row[si + 1] as i32;
+                        let r = src_row[si + 2] as i32;
+                        (*u, *v) = to_yuv.chroma(r, g, b);
+                    }
+                });
+        });
+
+        yuv
+    }
+
+    /// Convert NV12 to I420 by splitting the interleaved CbCr plane.
+    /// Much cheaper than a color conversion: one pass over half the pixels.
+    /// The source may be larger than the output (`src_width` x `src_height`),
+    /// in which case it is cropped from the top-left like `FrameScaler::new`.
+    fn nv12_to_yuv420(nv12: &[u8], src_width: u32, src_height: u32, width: u32, height: u32) -> Vec<u8> {
+        let stride = src_width as usize;
+        let w = width as usize;
+        let h = height as usize;
+        let y_size = w * h;
+        let uv_w = w / 2;
+        let uv_size = uv_w * (h / 2);
+        let mut yuv = pool::take(y_size + 2 * uv_size);
+
+        let (y_plane, uv_planes) = yuv.split_at_mut(y_size);
+        let (u_plane, v_plane) = uv_planes.split_at_mut(uv_size);
+
+        for row in 0..h {
+            y_plane[row * w..(row + 1) * w].copy_from_slice(&nv12[row * stride..row * stride + w]);
         }
 
-        // Pass 2: Compute UV planes in 2x2 blocks (top-left pixel, no per-pixel branch)
-        for by in 0..uv_h {
-            let src_row = (by * 2) * bgra_stride;
-            let uv_row = by * uv_w;
-            for bx in 0..uv_w {
-                let si = src_row + (bx * 2) * 4;
-                let b = bgra[si] as i32;
-                let g = bgra[si + 1] as i32;
-                let r = bgra[si + 2] as i32;
-                let ui = uv_row + bx;
-                u_plane[ui] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128).clamp(0, 255) as u8;
-                v_plane[ui] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128).clamp(0, 255) as u8;
+        let uv_offset = stride * src_height as usize;
+        for row in 0..h / 2 {
+            let src = &nv12[uv_offset + row * stride..uv_offset + row * stride + w];
+            for (i, pair) in src.chunks_exact(2).enumerate() {
+                u_plane[row * uv_w + i] = pair[0];
+                v_plane[row * uv_w + i] = pair[1];
             }
         }
 
@@ -119,14 +165,20 @@ impl VideoEncoder for SoftwareEncoder {
 
         // Configure OpenH264 encoder
         // Note: OpenH264 infers dimensions from the first YUVSource
+        let threads = threading::worker_threads();
         let h264_config = H264Config::new()
             .set_bitrate_bps(config.bitrate)
             .max_frame_rate(config.fps as f32)
+            .num_threads(threads as u16)
             .enable_skip_frame(false); // Disable skip for consistent latency
 
-        // Create encoder with config
-        let encoder = Encoder::with_api_config(api, h264_config)
-            .map_err(|e| EncoderError::InitError(format!("Failed to create OpenH264 encoder: {}", e)))?;
+        // Create encoder with config (worker threads inherit the pinning)
+        let encoder = {
+            let _scope = threading::enter();
+            Encoder::with_api_config(api, h264_config)
+                .map_err(|e| EncoderError::InitError(format!("Failed to create OpenH264 encoder: {}", e)))?
+        };
+        log::info!("OpenH264 using {} thread(s)", threads);
 
         // Store scaler and modified config with scaled dimensions
         let mut scaled_config = config.clone();
@@ -178,6 +230,7 @@ impl VideoEncoder for SoftwareEncoder {
             .ok_or_else(|| EncoderError::EncodeError("Encoder not initialized".to_string()))?;
 
         let mut encoder = encoder_guard.lock();
+        let _scope = threading::enter();
 
         // Force keyframe if requested
         if self.force_keyframe {
@@ -185,26 +238,34 @@ impl VideoEncoder for SoftwareEncoder {
             self.force_keyframe = false;
         }
 
-        // Scale frame if needed (when resolution exceeds OpenH264 limits)
-        let scaled_frame = scaler.scale(frame_data);
+        let yuv_data = if config.input_format == FrameFormat::Nv12 {
+            // Oversized NV12 frames are cropped during the chroma split
+            Self::nv12_to_yuv420(frame_data, scaler.src_width, scaler.src_height, config.width, config.height)
+        } else {
+            // Scale frame if needed (when resolution exceeds OpenH264 limits)
+            let scaled_frame = scaler.scale(frame_data);
 
-        // Convert BGRA to YUV420 using scaled dimensions
-        let yuv_data = Self::bgra_to_yuv420(&scaled_frame, config.width, config.height);
+            // Convert BGRA to YUV420 using scaled dimensions
+            let yuv = Self::bgra_to_yuv420(&scaled_frame, config.width, config.height, config.color_space);
+            pool::recycle_cow(scaled_frame);
+            yuv
+        };
 
-        // Create YUV buffer from the converted data
-        let yuv_buffer = YUVBuffer::from_vec(
-            yuv_data,
-            config.width as usize,
-            config.height as usize,
-        );
+        // Borrow the planes so the buffer can go back to the pool
+        let (w, h) = (config.width as usize, config.height as usize);
+        let (y_plane, uv_planes) = yuv_data.split_at(w * h);
+        let (u_plane, v_plane) = uv_planes.split_at((w / 2) * (h / 2));
+        let yuv_slices = YUVSlices::new((y_plane, u_plane, v_plane), (w, h), (w, w / 2, w / 2));
 
         // Encode the frame
-        let bitstream = encoder
-            .encode(&yuv_buffer)
-            .map_err(|e| EncoderError::EncodeError(format!("Encode failed: {}", e)))?;
+        let encoded = encoder
+            .encode(&yuv_slices)
+            .map(|bitstream| bitstream.to_vec())
+            .map_err(|e| EncoderError::EncodeError(format!("Encode failed: {}", e)));
+        pool::recycle(yuv_data);
 
         // Collect encoded data
-        let encoded_data = bitstream.to_vec();
+        let encoded_data = encoded?;
 
         // Determine frame type from NAL units
         let frame_type = if Self::is_keyframe(&encoded_data) {
@@ -242,6 +303,10 @@ impl VideoEncoder for SoftwareEncoder {
         "OpenH264 (Software)"
     }
 
+    fn supports_nv12_input(&self) -> bool {
+        true
+    }
+
     fn get_dimensions(&self) -> Option<(u32, u32)> {
         self.config.as_ref().map(|c| (c.width, c.height))
     }
@@ -252,3 +317,24 @@ impl Default for SoftwareEncoder {
         Self::new().expect("Failed to create SoftwareEncoder")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nv12_to_yuv420_splits_chroma() {
+        // 4x2 frame: 8 luma bytes, then 2 CbCr pairs
+        let nv12 = [1, 2, 3, 4, 5, 6, 7, 8, 10, 20, 11, 21];
+        let yuv = SoftwareEncoder::nv12_to_yuv420(&nv12, 4, 2, 4, 2);
+        assert_eq!(yuv, vec![1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 20, 21]);
+    }
+
+    #[test]
+    fn test_nv12_to_yuv420_crops_oversized_source() {
+        // 4x2 source cropped to 2x2: keep the left half of each row
+        let nv12 = [1, 2, 3, 4, 5, 6, 7, 8, 10, 20, 11, 21];
+        let yuv = SoftwareEncoder::nv12_to_yuv420(&nv12, 4, 2, 2, 2);
+        assert_eq!(yuv, vec![1, 2, 5, 6, 10, 20]);
+    }
+}
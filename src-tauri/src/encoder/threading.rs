@@ -0,0 +1,261 @@
+//! Encoder threading
+//!
+//! Software encoders (OpenH264, libx264) spread across every core by default,
+//! which on a big screen starves capture, networking and the UI. The thread
+//! count defaults to leaving a couple of cores free. Optionally the encoding
+//! thread is pinned to the remaining cores and runs at lower priority while
+//! it encodes.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+/// Encoder thread settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeThreading {
+    /// Encoder worker threads (0 = all cores minus `reserved_cores`)
+    pub threads: u32,
+    /// Cores left to the rest of the app
+    pub reserved_cores: u32,
+    /// Keep the encoding thread off the reserved cores (Windows and Linux)
+    pub pin_cores: bool,
+    /// Lower the encoding thread's priority while encoding (Windows)
+    pub low_priority: bool,
+}
+
+impl Default for EncodeThreading {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            reserved_cores: 2,
+            pin_cores: false,
+            low_priority: false,
+        }
+    }
+}
+
+impl EncodeThreading {
+    /// Worker threads for an encoder on a machine with `cores` cores
+    pub fn worker_threads(&self, cores: u32) -> u32 {
+        if self.threads > 0 {
+            self.threads.min(cores.max(1))
+        } else {
+            cores.saturating_sub(self.reserved_cores).max(1)
+        }
+    }
+
+    /// Cores the encoding thread may run on when pinned: all but the first
+    /// `reserved_cores`, always at least one
+    pub fn encode_cores(&self, cores: u32) -> std::ops::Range<u32> {
+        let cores = cores.max(1);
+        self.reserved_cores.min(cores - 1)..cores
+    }
+}
+
+static SETTINGS: once_cell::sync::Lazy<RwLock<EncodeThreading>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(EncodeThreading::default()));
+
+/// Current settings
+pub fn get() -> EncodeThreading {
+    *SETTINGS.read()
+}
+
+/// Replace the settings. Thread counts apply when the next encoder is
+/// created; pinning and priority apply from the next frame.
+pub fn set(settings: EncodeThreading) {
+    *SETTINGS.write() = settings;
+}
+
+/// Logical cores on this machine
+pub fn available_cores() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
+/// Worker threads a software encoder should use now
+pub fn worker_threads() -> u32 {
+    get().worker_threads(available_cores())
+}
+
+/// Rows handed to one task when a frame is scaled or converted in parallel
+pub const BAND_ROWS: usize = 32;
+
+/// Pool for pre-encode scaling and color conversion, sized like the
+/// encoder's own workers so the reserved cores stay free
+static FRAME_POOL: once_cell::sync::Lazy<Option<rayon::ThreadPool>> =
+    once_cell::sync::Lazy::new(|| {
+        let threads = worker_threads() as usize;
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("frame-worker-{}", i))
+            .build()
+            .inspect(|_| log::info!("Frame processing pool: {} threads", threads))
+            .map_err(|e| log::warn!("Frame processing pool unavailable, using the global pool: {}", e))
+            .ok()
+    });
+
+/// Run per-frame pixel work on the frame pool; parallel iterators inside
+/// `f` split across its threads (rayon's global pool if it could not be
+/// created).
+pub fn in_frame_pool<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    match FRAME_POOL.as_ref() {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// Pins and deprioritizes the current thread until dropped. Encoding runs
+/// on shared tokio workers, so the previous state is always restored.
+pub struct EncodeScope {
+    saved: Option<platform::Saved>,
+}
+
+impl Drop for EncodeScope {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            platform::restore(saved);
+        }
+    }
+}
+
+/// Apply the pinning/priority settings to the current thread for the
+/// lifetime of the returned scope. Threads an encoder spawns while the scope
+/// is active inherit the pinning on Linux.
+pub fn enter() -> EncodeScope {
+    let settings = get();
+    if !settings.pin_cores && !settings.low_priority {
+        return EncodeScope { saved: None };
+    }
+    let cores = settings.pin_cores.then(|| settings.encode_cores(available_cores()));
+    EncodeScope {
+        saved: platform::apply(cores, settings.low_priority),
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ops::Range;
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadAffinityMask, SetThreadPriority,
+        THREAD_PRIORITY, THREAD_PRIORITY_BELOW_NORMAL,
+    };
+
+    pub struct Saved {
+        mask: Option<usize>,
+        priority: Option<i32>,
+    }
+
+    pub fn apply(cores: Option<Range<u32>>, low_priority: bool) -> Option<Saved> {
+        unsafe {
+            let thread = GetCurrentThread();
+            let mask = cores.and_then(|cores| {
+                // One affinity mask covers at most usize::BITS cores
+                let mask = cores
+                    .filter(|&c| c < usize::BITS)
+                    .fold(0usize, |mask, c| mask | (1 << c));
+                match SetThreadAffinityMask(thread, mask) {
+                    0 => None,
+                    previous => Some(previous),
+                }
+            });
+            let priority = if low_priority {
+                let previous = GetThreadPriority(thread);
+                SetThreadPriority(thread, THREAD_PRIORITY_BELOW_NORMAL)
+                    .is_ok()
+                    .then_some(previous)
+            } else {
+                None
+            };
+            Some(Saved { mask, priority })
+        }
+    }
+
+    pub fn restore(saved: Saved) {
+        unsafe {
+            let thread = GetCurrentThread();
+            if let Some(mask) = saved.mask {
+                SetThreadAffinityMask(thread, mask);
+            }
+            if let Some(priority) = saved.priority {
+                let _ = SetThreadPriority(thread, THREAD_PRIORITY(priority));
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::ops::Range;
+
+    pub struct Saved {
+        mask: libc::cpu_set_t,
+    }
+
+    /// Only pinning is supported: an unprivileged thread cannot raise its
+    /// priority back after lowering it
+    pub fn apply(cores: Option<Range<u32>>, _low_priority: bool) -> Option<Saved> {
+        let cores = cores?;
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        unsafe {
+            let mut previous: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, size, &mut previous) != 0 {
+                return None;
+            }
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for core in cores {
+                libc::CPU_SET(core as usize, &mut set);
+            }
+            if libc::sched_setaffinity(0, size, &set) != 0 {
+                return None;
+            }
+            Some(Saved { mask: previous })
+        }
+    }
+
+    pub fn restore(saved: Saved) {
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        unsafe {
+            libc::sched_setaffinity(0, size, &saved.mask);
+        }
+    }
+}
+
+/// macOS has no thread affinity API and the scheduler already keeps QoS
+/// classes apart, so only the thread count applies there
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod platform {
+    use std::ops::Range;
+
+    pub struct Saved;
+
+    pub fn apply(_cores: Option<Range<u32>>, _low_priority: bool) -> Option<Saved> {
+        None
+    }
+
+    pub fn restore(_saved: Saved) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_threads_leave_cores_free() {
+        let settings = EncodeThreading::default();
+        assert_eq!(settings.worker_threads(8), 6);
+        assert_eq!(settings.worker_threads(2), 1);
+        assert_eq!(settings.worker_threads(1), 1);
+
+        let fixed = EncodeThreading { threads: 4, ..Default::default() };
+        assert_eq!(fixed.worker_threads(16), 4);
+        assert_eq!(fixed.worker_threads(2), 2);
+    }
+
+    #[test]
+    fn test_encode_cores_skip_reserved() {
+        let settings = EncodeThreading::default();
+        assert_eq!(settings.encode_cores(8), 2..8);
+        assert_eq!(settings.encode_cores(2), 1..2);
+        assert_eq!(settings.encode_cores(1), 0..1);
+    }
+}
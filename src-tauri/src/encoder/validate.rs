@@ -0,0 +1,187 @@
+// H.264 bitstream validation
+// Optional checks on every encoded frame before it is sent. A hardware
+// encoder with a driver bug can emit frames viewers cannot decode; without
+// these checks that only shows up as decode errors on the other machine,
+// far from the cause. Problems are logged on the sharer with the encoder
+// name, and each access unit gets an access unit delimiter, which some
+// decoders and stream analyzers rely on to split frames.
+
+use thiserror::Error;
+
+/// NAL unit types we look at
+const NAL_SLICE: u8 = 1;
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+/// Access unit delimiter allowing any slice type (primary_pic_type 7)
+const AUD: [u8; 6] = [0, 0, 0, 1, NAL_AUD, 0xF0];
+
+/// Frames logged in full before only every `LOG_EVERY`th one is
+const LOG_FIRST: u64 = 5;
+const LOG_EVERY: u64 = 100;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BitstreamError {
+    #[error("frame does not start with an Annex B start code")]
+    NoStartCode,
+    #[error("NAL unit {index} is empty")]
+    EmptyNal { index: usize },
+    #[error("NAL unit {index} (type {nal_type}) has the forbidden zero bit set")]
+    ForbiddenBit { index: usize, nal_type: u8 },
+    #[error("NAL unit {index} has reserved/unspecified type {nal_type}")]
+    ReservedType { index: usize, nal_type: u8 },
+    #[error("frame has no slice data")]
+    NoSlice,
+    #[error("IDR frame without {missing} in front of it; viewers that join on it cannot decode")]
+    MissingParameterSets { missing: &'static str },
+}
+
+/// Validates one encoder's output, keeping count of what it found
+#[derive(Debug, Default)]
+pub struct BitstreamValidator {
+    frames: u64,
+    bad_frames: u64,
+}
+
+impl BitstreamValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a frame and add an access unit delimiter if it has none. A
+    /// malformed frame is still returned, since dropping it would corrupt
+    /// the frames that reference it anyway; the problem is logged with
+    /// `encoder` (e.g. `VideoEncoder::info`) so it can be traced back.
+    pub fn process(&mut self, frame: Vec<u8>, encoder: &str) -> Vec<u8> {
+        self.frames += 1;
+        if let Err(e) = check(&frame) {
+            self.bad_frames += 1;
+            if self.bad_frames <= LOG_FIRST || self.bad_frames % LOG_EVERY == 0 {
+                let head: Vec<String> = frame.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+                log::error!(
+                    "Malformed H.264 from {} (frame {}, {} bad so far): {}; {} bytes starting {}",
+                    encoder,
+                    self.frames,
+                    self.bad_frames,
+                    e,
+                    frame.len(),
+                    head.join(" ")
+                );
+            }
+        }
+        with_aud(frame)
+    }
+}
+
+/// Split an Annex B frame into its NAL units (without start codes)
+pub fn nal_units(frame: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= frame.len() {
+        if frame[i] == 0 && frame[i + 1] == 0 && frame[i + 2] == 1 {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &(_, payload))| {
+            let mut end = starts.get(n + 1).map_or(frame.len(), |&(code, _)| code);
+            // A four-byte start code's leading zero belongs to the next one
+            while end > payload && frame[end - 1] == 0 && n + 1 < starts.len() {
+                end -= 1;
+            }
+            &frame[payload..end]
+        })
+        .collect()
+}
+
+/// Check the NAL structure of one encoded frame
+pub fn check(frame: &[u8]) -> Result<(), BitstreamError> {
+    if !frame.starts_with(&[0, 0, 1]) && !frame.starts_with(&[0, 0, 0, 1]) {
+        return Err(BitstreamError::NoStartCode);
+    }
+
+    let (mut sps, mut pps, mut slice) = (false, false, false);
+    for (index, nal) in nal_units(frame).into_iter().enumerate() {
+        let Some(&header) = nal.first() else {
+            return Err(BitstreamError::EmptyNal { index });
+        };
+        let nal_type = header & 0x1F;
+        if header & 0x80 != 0 {
+            return Err(BitstreamError::ForbiddenBit { index, nal_type });
+        }
+        match nal_type {
+            0 | 24..=31 => return Err(BitstreamError::ReservedType { index, nal_type }),
+            NAL_SPS => sps = true,
+            NAL_PPS => pps = true,
+            NAL_SLICE => slice = true,
+            NAL_IDR => {
+                let missing = match (sps, pps) {
+                    (true, true) => None,
+                    (false, true) => Some("an SPS"),
+                    (true, false) => Some("a PPS"),
+                    (false, false) => Some("an SPS and PPS"),
+                };
+                if let Some(missing) = missing {
+                    return Err(BitstreamError::MissingParameterSets { missing });
+                }
+                slice = true;
+            }
+            _ => {}
+        }
+    }
+
+    if slice { Ok(()) } else { Err(BitstreamError::NoSlice) }
+}
+
+/// Put an access unit delimiter in front of a frame that has none
+fn with_aud(frame: Vec<u8>) -> Vec<u8> {
+    if nal_units(&frame).first().and_then(|nal| nal.first()).is_some_and(|h| h & 0x1F == NAL_AUD) {
+        return frame;
+    }
+    let mut out = Vec::with_capacity(AUD.len() + frame.len());
+    out.extend_from_slice(&AUD);
+    out.extend_from_slice(&frame);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDR: [u8; 18] = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xCE, 0, 0, 1, 0x65, 0x88, 0x84];
+
+    #[test]
+    fn test_nal_units() {
+        let nals = nal_units(&IDR);
+        assert_eq!(nals, vec![&[0x67, 0x42][..], &[0x68, 0xCE][..], &[0x65, 0x88, 0x84][..]]);
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check(&IDR), Ok(()));
+        assert_eq!(check(&[0, 0, 1, 0x41, 0x9A]), Ok(()));
+        assert_eq!(check(&[0x65, 0x88]), Err(BitstreamError::NoStartCode));
+        assert_eq!(
+            check(&[0, 0, 1, 0x68, 0xCE, 0, 0, 1, 0x65, 0x88]),
+            Err(BitstreamError::MissingParameterSets { missing: "an SPS" })
+        );
+        assert_eq!(check(&[0, 0, 1, 0xE5, 0x88]), Err(BitstreamError::ForbiddenBit { index: 0, nal_type: 5 }));
+        assert_eq!(check(&[0, 0, 1, 0x67, 0x42]), Err(BitstreamError::NoSlice));
+    }
+
+    #[test]
+    fn test_aud_added_once() {
+        let framed = with_aud(IDR.to_vec());
+        assert_eq!(&framed[..6], &AUD);
+        assert_eq!(check(&framed), Ok(()));
+        assert_eq!(with_aud(framed.clone()), framed);
+    }
+}
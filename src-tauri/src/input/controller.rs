@@ -155,11 +155,22 @@ impl InputController {
     }
 
     /// Type text directly
+    ///
+    /// Goes through the platform's text API, so the sharer's keyboard layout
+    /// and IME state do not matter. Control characters in the text (Enter,
+    /// Backspace, ...) are pressed as keys instead.
     fn text_input(&self, text: &str) -> Result<(), InputError> {
         let mut enigo = self.enigo.lock();
-        enigo
-            .text(text)
-            .map_err(|e| InputError::SimulationError(format!("Text input failed: {}", e)))?;
+        for run in text_runs(text) {
+            match run {
+                TextRun::Text(text) => enigo
+                    .text(text)
+                    .map_err(|e| InputError::SimulationError(format!("Text input failed: {}", e)))?,
+                TextRun::Key(key) => enigo
+                    .key(key, Direction::Click)
+                    .map_err(|e| InputError::SimulationError(format!("Key press failed: {}", e)))?,
+            }
+        }
 
         Ok(())
     }
@@ -196,6 +207,47 @@ impl InputController {
     }
 }
 
+/// Piece of typed text: printable characters, or one editing key
+#[derive(Debug, PartialEq)]
+enum TextRun<'a> {
+    Text(&'a str),
+    Key(Key),
+}
+
+/// Key a control character in typed text stands for
+fn control_key(c: char) -> Option<Key> {
+    match c {
+        '\r' | '\n' => Some(Key::Return),
+        '\t' => Some(Key::Tab),
+        '\u{8}' => Some(Key::Backspace),
+        '\u{1b}' => Some(Key::Escape),
+        '\u{7f}' => Some(Key::Delete),
+        _ => None,
+    }
+}
+
+/// Split typed text at control characters; ones without a key are dropped
+fn text_runs(text: &str) -> Vec<TextRun<'_>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if !c.is_control() {
+            continue;
+        }
+        if start < i {
+            runs.push(TextRun::Text(&text[start..i]));
+        }
+        if let Some(key) = control_key(c) {
+            runs.push(TextRun::Key(key));
+        }
+        start = i + c.len_utf8();
+    }
+    if start < text.len() {
+        runs.push(TextRun::Text(&text[start..]));
+    }
+    runs
+}
+
 /// Convert scancode to enigo Key
 /// Scancodes follow USB HID usage tables for cross-platform compatibility
 fn scancode_to_key(scancode: u32) -> Option<Key> {
@@ -301,3 +353,25 @@ fn scancode_to_key(scancode: u32) -> Option<Key> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_runs() {
+        assert_eq!(text_runs("ls -la\r"), vec![TextRun::Text("ls -la"), TextRun::Key(Key::Return)]);
+        assert_eq!(
+            text_runs("你好\u{8}\u{8}ok"),
+            vec![
+                TextRun::Text("你好"),
+                TextRun::Key(Key::Backspace),
+                TextRun::Key(Key::Backspace),
+                TextRun::Text("ok"),
+            ]
+        );
+        // Control characters without a key are skipped
+        assert_eq!(text_runs("a\u{3}b"), vec![TextRun::Text("a"), TextRun::Text("b")]);
+        assert!(text_runs("").is_empty());
+    }
+}
@@ -3,6 +3,7 @@
 
 mod controller;
 mod events;
+pub mod session;
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -0,0 +1,98 @@
+// Remote control session state
+// The sharer grants control to one peer at a time and drops input from
+// everyone else; the viewer remembers which peers granted it control so
+// its render windows know whether to forward input.
+
+use super::{InputController, InputEvent};
+use crossbeam_channel::Sender;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+
+/// Peer allowed to control this machine
+static CONTROLLER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Peers whose screens this machine may control
+static CONTROLLING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Thread injecting the controller's input, started on first use (the
+/// platform input handles are not all safe to move between threads)
+static INJECTOR: Lazy<Sender<InputEvent>> = Lazy::new(|| {
+    let (tx, rx) = crossbeam_channel::unbounded::<InputEvent>();
+    std::thread::spawn(move || {
+        // Only text is injected so far, which needs no screen mapping
+        let controller = match InputController::new(0, 0) {
+            Ok(controller) => controller,
+            Err(e) => {
+                log::error!("Remote input unavailable: {}", e);
+                return;
+            }
+        };
+        for event in rx {
+            if let Err(e) = controller.execute(&event) {
+                log::warn!("Failed to inject remote input: {}", e);
+            }
+        }
+    });
+    tx
+});
+
+/// Let `peer_ip` control this machine, replacing any earlier controller
+pub fn grant(peer_ip: &str) -> Option<String> {
+    CONTROLLER.lock().replace(peer_ip.to_string())
+}
+
+/// Take control away; returns who had it
+pub fn revoke() -> Option<String> {
+    CONTROLLER.lock().take()
+}
+
+/// Whether `peer_ip` currently controls this machine
+pub fn is_controller(peer_ip: &str) -> bool {
+    CONTROLLER.lock().as_deref() == Some(peer_ip)
+}
+
+/// Record whether `peer_ip` granted us control of its screen
+pub fn set_controlling(peer_ip: &str, granted: bool) {
+    let mut controlling = CONTROLLING.lock();
+    if granted {
+        controlling.insert(peer_ip.to_string());
+    } else {
+        controlling.remove(peer_ip);
+    }
+}
+
+/// Whether we may send input to `peer_ip`
+pub fn is_controlling(peer_ip: &str) -> bool {
+    CONTROLLING.lock().contains(peer_ip)
+}
+
+/// Inject an event received from the controlling peer
+pub fn inject(event: InputEvent) {
+    let _ = INJECTOR.send(event);
+}
+
+/// Send text typed in a viewer window to the sharer at `peer_ip`, if it
+/// granted us control
+pub fn forward_text(peer_ip: &str, text: String) {
+    use crate::network::protocol::{self, InputData, InputEventType, Message};
+
+    if text.is_empty() || !is_controlling(peer_ip) {
+        return;
+    }
+    let msg = Message::InputEvent {
+        event_type: InputEventType::TextInput,
+        x: 0.0,
+        y: 0.0,
+        data: InputData::Text { text },
+    };
+    let Ok(encoded) = protocol::encode(&msg) else {
+        return;
+    };
+    let peer_ip = peer_ip.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = crate::network::quic::send_to_peer(&peer_ip, &encoded).await {
+            log::debug!("Failed to send text input to {}: {}", peer_ip, e);
+        }
+    });
+}
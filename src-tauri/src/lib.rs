@@ -1,32 +1,44 @@
 // LAN Meeting - High-performance screen sharing tool
 // Main library entry point
 
+pub mod app_state;
 pub mod capture;
 pub mod chat;
+pub mod clipboard;
 pub mod commands;
 pub mod decoder;
 pub mod encoder;
 pub mod input;
+pub mod links;
+pub mod meeting;
 pub mod network;
 pub mod renderer;
+pub mod safe_mode;
 pub mod simple_streaming;
 pub mod streaming;
 pub mod transfer;
 
 use network::quic::QuicEndpoint;
 use once_cell::sync::OnceCell;
+use parking_lot::RwLock;
 use std::sync::Arc;
 use tauri::Emitter;
 
-/// Global QUIC endpoint
-pub static QUIC_ENDPOINT: OnceCell<Arc<QuicEndpoint>> = OnceCell::new();
+/// Global QUIC endpoint while the service runs; replaced on every start
+static QUIC_ENDPOINT: RwLock<Option<Arc<QuicEndpoint>>> = RwLock::new(None);
 
 /// Global Tauri app handle for emitting events
 pub static APP_HANDLE: OnceCell<tauri::AppHandle> = OnceCell::new();
 
 /// Get the global QUIC endpoint
-pub fn get_quic_endpoint() -> Option<&'static Arc<QuicEndpoint>> {
-    QUIC_ENDPOINT.get()
+pub fn get_quic_endpoint() -> Option<Arc<QuicEndpoint>> {
+    QUIC_ENDPOINT.read().clone()
+}
+
+/// Install the endpoint of a started service, or take it out with `None`
+/// when the service stops; returns the one it replaces
+pub fn set_quic_endpoint(endpoint: Option<Arc<QuicEndpoint>>) -> Option<Arc<QuicEndpoint>> {
+    std::mem::replace(&mut *QUIC_ENDPOINT.write(), endpoint)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -34,6 +46,8 @@ pub fn run() {
     // Install aws-lc-rs as the default crypto provider for rustls
     let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
 
+    safe_mode::init_from_args(std::env::args());
+
     tauri::Builder::default()
         .setup(|app| {
             // Initialize logging
@@ -47,9 +61,15 @@ pub fn run() {
 
             // Initialize dialog plugin
             app.handle().plugin(tauri_plugin_dialog::init())?;
+            app.handle().plugin(tauri_plugin_opener::init())?;
 
             // Store app handle globally for emitting events
             let _ = APP_HANDLE.set(app.handle().clone());
+            app_state::start();
+
+            if safe_mode::from_flag() {
+                log::warn!("Safe mode enabled by {}: hardware acceleration disabled", safe_mode::FLAG);
+            }
 
             // Note: QUIC and mDNS are now started via start_service command
             log::info!("LAN Meeting started (service not yet enabled)");
@@ -57,12 +77,52 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_displays,
+            commands::get_capture_windows,
+            commands::set_capture_exclusions,
+            commands::get_encode_threading,
+            commands::set_encode_threading,
+            commands::get_timing_histograms,
+            commands::reset_timing_histograms,
+            commands::export_timing_csv,
+            commands::get_stream_stats,
+            commands::get_sharer_stats,
+            commands::get_viewer_stats,
+            commands::get_quic_stats,
+            commands::list_encoders,
+            commands::list_decoders,
+            commands::dump_frames,
+            commands::stop_frame_dump,
+            commands::start_recording,
+            commands::stop_recording,
+            commands::get_recording,
+            commands::start_viewer_recording,
+            commands::stop_viewer_recording,
+            commands::get_viewer_recordings,
+            commands::save_clip,
+            commands::update_stream_settings,
+            commands::offer_file_to_meeting,
+            commands::send_screenshot,
+            commands::send_annotated_screenshot,
             commands::start_capture,
             commands::stop_capture,
             commands::check_screen_permission,
             commands::request_screen_permission,
             commands::get_devices,
             commands::add_manual_device,
+            commands::scan_subnet,
+            commands::answer_pairing,
+            commands::answer_viewer_request,
+            commands::kick_viewer,
+            commands::get_current_viewers,
+            commands::get_connection_stats,
+            commands::refresh_devices_now,
+            commands::search_devices,
+            commands::set_device_favorite,
+            commands::get_known_devices,
+            commands::rename_device,
+            commands::forget_device,
+            commands::block_device,
+            commands::unblock_device,
             commands::connect_to_device,
             commands::disconnect,
             commands::get_self_info,
@@ -84,20 +144,62 @@ pub fn run() {
             commands::is_service_running,
             // Settings commands
             commands::get_settings,
+            commands::get_app_state,
             commands::save_settings,
+            commands::set_avatar,
+            commands::get_safe_mode_status,
+            commands::get_archive_directory,
+            commands::search_archive,
+            commands::is_archive_ocr_available,
+            // Clipboard commands
+            commands::get_clipboard_history,
+            commands::copy_clipboard_item,
+            commands::clear_clipboard_history,
+            commands::send_url,
+            commands::answer_url,
             // Sharing commands
             commands::broadcast_sharing_status,
+            commands::switch_display,
+            commands::pause_sharing,
+            commands::resume_sharing,
             commands::open_viewer_window,
             commands::request_control,
+            commands::grant_control,
+            commands::revoke_control,
+            commands::list_trusted_devices,
+            commands::untrust_device,
+            commands::capture_viewer_snapshot,
+            commands::set_viewer_pip,
+            commands::list_viewer_windows,
+            commands::tile_viewer_windows,
+            commands::annotate_shared_screen,
+            commands::annotate_viewer,
             commands::request_screen_stream,
             commands::stop_viewing_stream,
+            commands::get_viewer_summaries,
             // Simple streaming commands
             commands::simple_start_sharing,
+            commands::simple_start_region_sharing,
+            commands::simple_start_window_sharing,
             commands::simple_request_stream,
+            commands::simple_switch_display,
             commands::simple_stop_sharing,
+            // Meeting commands
+            commands::create_meeting,
+            commands::respond_to_meeting,
+            commands::cancel_meeting,
+            commands::get_meetings,
+            commands::claim_meeting_host,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Peers drop us at once instead of when our mDNS record expires
+                network::discovery::withdraw();
+                network::discovery::shutdown();
+            }
+        });
 }
 
 /// Handle incoming QUIC connection
@@ -123,7 +225,11 @@ pub async fn handle_incoming_connection(conn: Arc<network::quic::QuicConnection>
 
                     // Check if this is a simple streaming message
                     if simple_streaming::is_simple_message(&first_data) {
-                        let peer_ip = conn_clone.remote_addr().ip().to_string();
+                        let peer_ip = conn_clone.peer_ip();
+                        if !network::pairing::is_paired(&conn_clone) {
+                            log::warn!("Ignoring a simple stream from unpaired peer {}", peer_ip);
+                            return;
+                        }
                         log::info!("[SIMPLE] Detected simple stream from {}", peer_ip);
 
                         // Handle the first message manually, then pass to handler
@@ -137,25 +243,13 @@ pub async fn handle_incoming_connection(conn: Arc<network::quic::QuicConnection>
                     let mut codec = MessageCodec::new();
                     codec.feed(&first_data);
 
-                    // Process messages from the first read
-                    while let Ok(Some(msg)) = codec.decode() {
-                        if let Err(e) = handle_message(&msg, &mut stream, &conn_clone).await {
-                            log::error!("Failed to handle message: {}", e);
-                        }
-                    }
-
-                    // Handle subsequent stream messages
-                    loop {
+                    // Process messages from the first read, then subsequent ones
+                    while handle_buffered(&mut codec, &mut stream, &conn_clone).await {
                         match stream.recv_framed().await {
-                            Ok(data) => {
-                                codec.feed(&data);
-
-                                // Process all complete messages
-                                while let Ok(Some(msg)) = codec.decode() {
-                                    if let Err(e) = handle_message(&msg, &mut stream, &conn_clone).await {
-                                        log::error!("Failed to handle message: {}", e);
-                                    }
-                                }
+                            Ok(data) => codec.feed(&data),
+                            Err(network::NetworkError::Decode(e)) => {
+                                log::warn!("Dropping stream from {}: {}", conn_clone.peer_ip(), e);
+                                break;
                             }
                             Err(e) => {
                                 log::debug!("Stream closed: {}", e);
@@ -173,13 +267,15 @@ pub async fn handle_incoming_connection(conn: Arc<network::quic::QuicConnection>
     }
 
     // Connection ended - clean up the device associated with this peer
-    let peer_ip = conn.remote_addr().ip().to_string();
+    let peer_ip = conn.peer_ip();
     log::info!("Peer disconnected: {}, cleaning up device", peer_ip);
     let devices = network::discovery::get_devices();
+    let mut removed_ids = Vec::new();
     for device in &devices {
         if device.ip == peer_ip {
             log::info!("Removing disconnected device '{}' (ip={})", device.name, device.ip);
             network::discovery::remove_device(&device.id);
+            removed_ids.push(device.id.clone());
             if let Some(app) = APP_HANDLE.get() {
                 let _ = app.emit("device-removed", &device.id);
             }
@@ -187,6 +283,45 @@ pub async fn handle_incoming_connection(conn: Arc<network::quic::QuicConnection>
     }
     // Also clean up the QUIC connection entry
     network::quic::remove_connection_by_ip(&peer_ip);
+    network::clock::forget(&peer_ip);
+    streaming::remove_viewer(&peer_ip, None);
+
+    // Hand over meetings hosted by the departed peer
+    meeting::handle_peer_left(&peer_ip, &removed_ids).await;
+}
+
+/// Handle every complete message in `codec`. Malformed ones are skipped;
+/// once the peer has sent nothing but those for a while it is told why and
+/// disconnected, and false is returned.
+async fn handle_buffered(
+    codec: &mut network::protocol::MessageCodec,
+    stream: &mut network::quic::QuicStream,
+    conn: &Arc<network::quic::QuicConnection>,
+) -> bool {
+    use network::protocol::{self, Message};
+
+    loop {
+        match codec.decode() {
+            Ok(Some(msg)) => {
+                if let Err(e) = handle_message(&msg, stream, conn).await {
+                    log::error!("Failed to handle message: {}", e);
+                }
+            }
+            Ok(None) => return true,
+            Err(e) => {
+                log::warn!("Malformed message from {}: {}", conn.peer_ip(), e);
+                if codec.gave_up() {
+                    log::warn!("Disconnecting {}: too many malformed messages", conn.peer_ip());
+                    let bye = Message::Disconnect { reason: "Too many malformed messages".to_string() };
+                    if let Ok(encoded) = protocol::encode(&bye) {
+                        let _ = stream.send_framed(&encoded).await;
+                    }
+                    conn.close();
+                    return false;
+                }
+            }
+        }
+    }
 }
 
 /// Handle a protocol message
@@ -197,6 +332,21 @@ async fn handle_message(
 ) -> Result<(), network::NetworkError> {
     use network::protocol::{self, Message};
 
+    // Until the peer is paired, only its handshake (with the password proof
+    // ahead of it) and heartbeats are served
+    let greeting = matches!(
+        msg,
+        Message::Handshake { .. }
+            | Message::HandshakeAck { .. }
+            | Message::PasswordProof { .. }
+            | Message::Heartbeat { .. }
+            | Message::HeartbeatAck { .. }
+    );
+    if !greeting && !network::pairing::is_paired(_conn) {
+        log::warn!("Ignoring {:?} from unpaired peer {}", msg.message_type(), _conn.peer_ip());
+        return Ok(());
+    }
+
     match msg {
         Message::Handshake {
             device_id,
@@ -212,22 +362,55 @@ async fn handle_message(
                 capabilities
             );
 
-            // Add the remote device to our device list
-            let remote_addr = _conn.remote_addr();
-            let remote_device = network::discovery::DiscoveredDevice {
+            // A device on a protocol too old or without the meeting password
+            // is turned away; one seen for the first time has its user
+            // compare the pairing code first
+            let remote_ip = _conn.peer_ip();
+            let our_id = network::discovery::get_our_device_id();
+            let checked = network::capability::record(&remote_ip, capabilities)
+                .and_then(|_| network::password::check(_conn));
+            if let Err(reason) = checked {
+                log::warn!("Refusing {} ({}): {}", name, remote_ip, reason);
+                let ack = protocol::create_handshake_ack(&our_id, &commands::display_name(), false, Some(reason.to_string()));
+                stream.send_framed(&protocol::encode_as(&ack, protocol::LEGACY_VERSION)?).await?;
+                return Ok(());
+            }
+            if let Err(e) = network::pairing::confirm(_conn, name, Some(device_id)).await {
+                log::warn!("Not pairing with {} ({}): {}", name, remote_ip, e);
+                let reason = match e {
+                    network::NetworkError::Connect(_, reason) => reason,
+                    e => e.to_string(),
+                };
+                let ack = protocol::create_handshake_ack(&our_id, &commands::display_name(), false, Some(reason));
+                stream.send_framed(&protocol::encode_as(&ack, protocol::LEGACY_VERSION)?).await?;
+                return Ok(());
+            }
+
+            // Add the remote device to our device list, keeping what its
+            // mDNS record told us and its avatar until it sends it again
+            let known = network::discovery::get_devices().into_iter().find(|d| d.id == *device_id);
+            let mut remote_device = network::discovery::DiscoveredDevice {
                 id: device_id.clone(),
                 name: name.clone(),
-                ip: remote_addr.ip().to_string(),
+                ip: remote_ip.clone(),
                 port: network::quic::DEFAULT_PORT, // Use default port, not ephemeral source port
                 status: network::discovery::DeviceStatus::Online,
                 last_seen: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .map(|d| d.as_millis() as u64)
                     .unwrap_or(0),
-                is_sharing: false,
+                is_sharing: known.as_ref().is_some_and(|d| d.is_sharing),
+                avatar: known.as_ref().and_then(|d| d.avatar.clone()),
+                version: None,
+                platform: known.and_then(|d| d.platform),
+                capabilities: Vec::new(),
+                incompatible: false,
+                nickname: None,
             };
+            remote_device.set_version(version, capabilities.clone());
             network::discovery::add_device(remote_device.clone());
-            log::info!("Added {} ({}) to device list", name, remote_addr.ip());
+            network::pairing::complete(_conn, device_id);
+            log::info!("Added {} ({}) to device list", name, remote_ip);
 
             // Emit event to frontend to notify about the new connection
             if let Some(handle) = APP_HANDLE.get() {
@@ -240,20 +423,18 @@ async fn handle_message(
                 let _ = handle.emit("connection-received", ConnectionEvent {
                     device_id: device_id.clone(),
                     device_name: name.clone(),
-                    ip: remote_addr.ip().to_string(),
+                    ip: remote_ip,
                 });
 
                 // Also emit device-discovered so the device list updates
                 let _ = handle.emit("device-discovered", &remote_device);
             }
 
-            // Send handshake acknowledgment
-            let our_id = network::discovery::get_our_device_id();
-            let our_name = hostname::get()
-                .map(|h| h.to_string_lossy().to_string())
-                .unwrap_or_else(|_| "Unknown".to_string());
-
-            let ack = protocol::create_handshake_ack(&our_id, &our_name, true, None);
+            // Send our capabilities, avatar and the handshake acknowledgment
+            let tokens = Message::Capabilities { tokens: protocol::capabilities() };
+            stream.send_framed(&protocol::encode(&tokens)?).await?;
+            network::profile::send_avatar(stream).await?;
+            let ack = protocol::create_handshake_ack(&our_id, &commands::display_name(), true, None);
             let encoded = protocol::encode(&ack)?;
             stream.send_framed(&encoded).await?;
 
@@ -279,6 +460,11 @@ async fn handle_message(
             }
         }
 
+        Message::Capabilities { .. } => {
+            // Only read ahead of a handshake answer
+            log::debug!("Ignoring capabilities from {} outside a handshake", _conn.peer_ip());
+        }
+
         Message::Heartbeat { timestamp } => {
             // Respond with heartbeat ack
             let ack = protocol::create_heartbeat_ack(*timestamp);
@@ -290,6 +476,40 @@ async fn handle_message(
             log::debug!("Heartbeat latency: {}ms", latency_ms);
         }
 
+        Message::PasswordProof { proof } => {
+            network::password::offered(_conn, proof);
+        }
+
+        Message::Avatar { avatar } => {
+            let remote_ip = _conn.peer_ip();
+            if let Some(device) = network::discovery::get_devices().into_iter().find(|d| d.ip == remote_ip) {
+                network::discovery::set_device_profile(
+                    &device.id,
+                    &device.name,
+                    network::profile::checked_avatar(avatar.as_deref()),
+                );
+            }
+        }
+
+        Message::AddressUpdate { device_id, addresses, port } => {
+            let observed = _conn.peer_ip();
+            // A peer only reports where it moved itself, not another device
+            if !network::pairing::speaks_for(_conn, device_id) {
+                log::warn!("Ignoring address update for {} from {}", device_id, observed);
+                return Ok(());
+            }
+            log::info!("Address update from {}: {:?}", device_id, addresses);
+            network::quic::reindex_connection(_conn);
+            if let Some((old_ip, device)) =
+                network::discovery::update_device_address(device_id, addresses, *port, &observed)
+            {
+                log::info!("Device '{}' moved from {} to {}", device.name, old_ip, device.ip);
+                if let Some(handle) = APP_HANDLE.get() {
+                    let _ = handle.emit("device-discovered", &device);
+                }
+            }
+        }
+
         Message::Disconnect { reason } => {
             log::info!("Peer disconnected: {}", reason);
         }
@@ -300,8 +520,15 @@ async fn handle_message(
             timestamp,
         } => {
             log::info!("[{}] {}: {}", timestamp, from, content);
+            // Attribute the message to the device so the UI can show its avatar
+            let peer_ip = _conn.peer_ip();
+            let from_id = network::discovery::get_devices()
+                .into_iter()
+                .find(|d| d.ip == peer_ip)
+                .map(|d| d.id)
+                .unwrap_or_else(|| from.clone());
             // Store the message
-            chat::receive_message(from, from, content, *timestamp);
+            chat::receive_message(&from_id, from, content, *timestamp);
 
             // Emit event to frontend
             if let Some(handle) = APP_HANDLE.get() {
@@ -317,7 +544,7 @@ async fn handle_message(
 
         // Screen sharing messages
         Message::ScreenOffer { displays } => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
+            let remote_ip = _conn.peer_ip();
             let is_sharing = !displays.is_empty();
 
             log::info!(
@@ -335,17 +562,19 @@ async fn handle_message(
                     struct SharingStatusEvent {
                         device_id: String,
                         is_sharing: bool,
+                        displays: Vec<network::protocol::DisplayInfo>,
                     }
                     let _ = handle.emit("sharing-status-changed", SharingStatusEvent {
                         device_id,
                         is_sharing,
+                        displays,
                     });
                 }
             }
         }
 
         Message::ScreenRequest { display_id, preferred_fps, preferred_quality } => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
+            let remote_ip = _conn.peer_ip();
             log::info!(
                 "Received screen request from {}: display={}, fps={}, quality={}",
                 remote_ip,
@@ -359,24 +588,82 @@ async fn handle_message(
             let is_streaming = manager.read().as_ref().map(|m| m.is_streaming()).unwrap_or(false);
 
             if is_streaming {
+                // The encoder is shared, so a viewer that cannot decode its
+                // codec cannot watch
+                let codec = manager.read().as_ref().and_then(|m| m.codec(*display_id));
+                if let Some(codec) = codec.filter(|c| !network::capability::peer_supports(&remote_ip, network::capability::Capability::codec(*c))) {
+                    let reason = network::protocol::ScreenRejectReason::UnsupportedCodec { codec: codec.name().to_string() };
+                    if let Err(e) = streaming::reject_viewer(&remote_ip, *display_id, reason).await {
+                        log::warn!("Failed to reject screen request from {}: {}", remote_ip, e);
+                    }
+                    return Ok(());
+                }
+
+                if let Some(max_viewers) = commands::max_viewers() {
+                    let full = !manager.read().as_ref().is_some_and(|m| m.has_room_for(&remote_ip, max_viewers));
+                    if full {
+                        let reason = network::protocol::ScreenRejectReason::StreamFull { max_viewers };
+                        if let Err(e) = streaming::reject_viewer(&remote_ip, *display_id, reason).await {
+                            log::warn!("Failed to reject screen request from {}: {}", remote_ip, e);
+                        }
+                        return Ok(());
+                    }
+                }
+
+                if !network::access::admit_viewer(&remote_ip).await {
+                    let reason = network::protocol::ScreenRejectReason::Declined;
+                    if let Err(e) = streaming::reject_viewer(&remote_ip, *display_id, reason).await {
+                        log::warn!("Failed to reject screen request from {}: {}", remote_ip, e);
+                    }
+                    return Ok(());
+                }
+
+                // Checked again: others may have been let in while the user
+                // was asked
+                let added = manager.read().as_ref().map(|m| m.add_viewer(&remote_ip, *display_id, commands::max_viewers()));
+                let info = match added {
+                    Some(Ok(info)) => info,
+                    Some(Err(reason)) => {
+                        if let Err(e) = streaming::reject_viewer(&remote_ip, *display_id, reason).await {
+                            log::warn!("Failed to reject screen request from {}: {}", remote_ip, e);
+                        }
+                        return Ok(());
+                    }
+                    None => None,
+                };
+                let Some(info) = info else {
+                    log::warn!("ScreenRequest from {} for display {} which is not shared", remote_ip, display_id);
+                    return Ok(());
+                };
+
                 // Send ScreenStart response via a NEW stream (not the request stream)
                 // The request stream is already finished/dropped by the sender,
                 // so we must use send_to_peer to open a fresh stream
-                let (width, height) = manager.read().as_ref().map(|m| m.dimensions()).unwrap_or((1920, 1080));
-                let fps = manager.read().as_ref().map(|m| m.config().fps).unwrap_or(30);
-
                 let start_msg = network::protocol::Message::ScreenStart {
-                    width,
-                    height,
-                    fps: fps as u8,
-                    codec: "h264".to_string(),
+                    display_id: info.display_id,
+                    width: info.width,
+                    height: info.height,
+                    fps: info.fps as u8,
+                    codec: info.codec.name().to_string(),
+                    color_space: info.color_space.name().to_string(),
                 };
 
                 if let Ok(encoded) = network::protocol::encode(&start_msg) {
                     if let Err(e) = network::quic::send_to_peer(&remote_ip, &encoded).await {
                         log::error!("Failed to send ScreenStart to {}: {}", remote_ip, e);
                     } else {
-                        log::info!("Sent ScreenStart to {} ({}x{} @ {}fps)", remote_ip, width, height, fps);
+                        log::info!(
+                            "Sent ScreenStart to {} (display {} {}x{} @ {}fps)",
+                            remote_ip, info.display_id, info.width, info.height, info.fps
+                        );
+                    }
+                }
+
+                // Joined while the sharer has paused: the dimmed frame follows
+                let frozen = manager.read().as_ref().map(|m| m.is_frozen()).unwrap_or(false);
+                if frozen {
+                    if let Ok(encoded) = network::protocol::encode(&Message::ScreenPause { paused: true }) {
+                        let _ = network::quic::send_to_peer(&remote_ip, &encoded).await;
                     }
                 }
             } else {
@@ -384,23 +671,27 @@ async fn handle_message(
             }
         }
 
-        Message::ScreenStart { width, height, fps, codec } => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
+        Message::ScreenStart { display_id, width, height, fps, codec, color_space } => {
+            let remote_ip = _conn.peer_ip();
             log::info!(
-                "Received screen start from {}: {}x{} @ {} fps, codec={}",
+                "Received screen start from {}: display {} {}x{} @ {} fps, codec={}, color={}",
                 remote_ip,
+                display_id,
                 width,
                 height,
                 fps,
-                codec
+                codec,
+                color_space
             );
 
             // Initialize viewer session and create native render window
             let sessions = streaming::get_viewer_sessions();
+            let mut started = false;
             if let Some(session) = sessions.write().get_mut(&remote_ip) {
-                match session.handle_screen_start(*width, *height, *fps, codec) {
+                match session.handle_screen_start(*display_id, *width, *height, *fps, codec, color_space) {
                     Ok(_) => {
                         log::info!("Native viewer window created for {}", remote_ip);
+                        started = true;
                     }
                     Err(e) => {
                         log::error!("Failed to start viewer session: {}", e);
@@ -409,45 +700,160 @@ async fn handle_message(
             } else {
                 log::warn!("No viewer session found for {}", remote_ip);
             }
+
+            // Frames that arrived before the window was ready are gone; the
+            // sharer resends its last keyframe if nothing changed since
+            if started {
+                if let Err(e) = streaming::request_keyframe(&remote_ip, *display_id).await {
+                    log::warn!("Failed to request keyframe from {}: {}", remote_ip, e);
+                }
+            }
         }
 
-        Message::ScreenFrame { timestamp, frame_type: _, sequence, data } => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
+        Message::ScreenFrame { display_id, timestamp, frame_type, sequence, data } => {
+            let remote_ip = _conn.peer_ip();
+            streaming::timing::record_transit(*timestamp);
 
             // Decode and render frame in native window (no Tauri event overhead)
             let sessions = streaming::get_viewer_sessions();
             let mut sessions_guard = sessions.write();
+            let mut window_closed = false;
+            let mut wants_keyframe = false;
 
             if let Some(session) = sessions_guard.get_mut(&remote_ip) {
-                if session.is_active() {
+                if session.is_display_active(*display_id) {
                     // Decode and render directly to native wgpu window
-                    if let Err(e) = session.handle_screen_frame(*timestamp, data) {
+                    let keyframe = matches!(frame_type, protocol::FrameType::KeyFrame);
+                    if let Err(e) = session.handle_screen_frame(*display_id, *timestamp, keyframe, data) {
                         // Only log occasional errors to avoid spam
                         if *sequence % 100 == 0 {
                             log::warn!("Frame {} decode error: {}", sequence, e);
                         }
+                        if matches!(e, streaming::StreamingError::DecoderError(_)) {
+                            wants_keyframe = session.on_decode_error(*display_id);
+                        }
                     }
+                    window_closed = !session.is_display_active(*display_id);
                 }
             }
 
             // Drop lock before any other operations
             drop(sessions_guard);
+
+            // Broken references: recover now instead of at the next periodic keyframe
+            if wants_keyframe && !window_closed {
+                log::debug!("Decode error on display {} from {}, requesting keyframe", display_id, remote_ip);
+                if let Err(e) = streaming::request_keyframe(&remote_ip, *display_id).await {
+                    log::warn!("Failed to request keyframe from {}: {}", remote_ip, e);
+                }
+            }
+
+            // Window closed by the user: let the sharer know we left that display
+            if window_closed {
+                if let Err(e) = streaming::notify_viewer_left(&remote_ip, Some(*display_id)).await {
+                    log::warn!("Failed to notify {} that we stopped watching: {}", remote_ip, e);
+                }
+            }
         }
 
-        Message::ScreenStop => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
-            log::info!("Received screen stop from {}", remote_ip);
+        Message::ScreenStop { display_id } => {
+            let remote_ip = _conn.peer_ip();
+            log::info!("Received screen stop from {} (display {:?})", remote_ip, display_id);
 
-            // Stop viewer session (closes native window)
+            // Stop viewer session (closes the display's native window, or all of them)
             let sessions = streaming::get_viewer_sessions();
             if let Some(session) = sessions.write().get_mut(&remote_ip) {
-                session.handle_screen_stop();
+                session.handle_screen_stop(*display_id);
+            }
+        }
+
+        Message::ScreenReject { display_id, reason } => {
+            let remote_ip = _conn.peer_ip();
+            log::warn!("{} turned down our request for display {}: {:?}", remote_ip, display_id, reason);
+
+            if let Some(handle) = APP_HANDLE.get() {
+                #[derive(serde::Serialize, Clone)]
+                struct ScreenRejectedEvent {
+                    peer_ip: String,
+                    display_id: u32,
+                    reason: &'static str,
+                    max_viewers: Option<u32>,
+                }
+                let (reason, max_viewers) = match reason {
+                    network::protocol::ScreenRejectReason::StreamFull { max_viewers } => ("stream_full", Some(*max_viewers)),
+                    network::protocol::ScreenRejectReason::Declined => ("declined", None),
+                    network::protocol::ScreenRejectReason::Kicked => ("kicked", None),
+                    network::protocol::ScreenRejectReason::UnsupportedCodec { .. } => ("unsupported_codec", None),
+                };
+                let _ = handle.emit("screen-rejected", ScreenRejectedEvent {
+                    peer_ip: remote_ip,
+                    display_id: *display_id,
+                    reason,
+                    max_viewers,
+                });
+            }
+        }
+
+        Message::ScreenRecording { recording } => {
+            let remote_ip = _conn.peer_ip();
+            log::info!("{} {} recording our screen", remote_ip, if *recording { "started" } else { "stopped" });
+
+            if let Some(handle) = APP_HANDLE.get() {
+                #[derive(serde::Serialize, Clone)]
+                struct ViewerRecordingEvent {
+                    peer_ip: String,
+                    recording: bool,
+                }
+                let _ = handle.emit("viewer-recording", ViewerRecordingEvent {
+                    peer_ip: remote_ip,
+                    recording: *recording,
+                });
+            }
+        }
+
+        Message::ScreenLeave { display_id } => {
+            let remote_ip = _conn.peer_ip();
+            if !streaming::remove_viewer(&remote_ip, *display_id) {
+                log::debug!("ScreenLeave from {} who was not watching", remote_ip);
+            }
+        }
+
+        Message::ScreenPause { paused } => {
+            let remote_ip = _conn.peer_ip();
+            let sessions = streaming::get_viewer_sessions();
+            if let Some(session) = sessions.write().get_mut(&remote_ip) {
+                session.set_paused(*paused);
+            }
+
+            if let Some(handle) = APP_HANDLE.get() {
+                #[derive(serde::Serialize, Clone)]
+                struct StreamPausedEvent {
+                    peer_ip: String,
+                    paused: bool,
+                }
+                let _ = handle.emit("stream-paused", StreamPausedEvent {
+                    peer_ip: remote_ip,
+                    paused: *paused,
+                });
+            }
+        }
+
+        Message::KeyframeRequest { display_id } => {
+            let remote_ip = _conn.peer_ip();
+            let manager = streaming::get_streaming_manager();
+            let requested = manager
+                .read()
+                .as_ref()
+                .map(|m| m.request_keyframe(&remote_ip, *display_id))
+                .unwrap_or(false);
+            if !requested {
+                log::debug!("KeyframeRequest from {} for display {} which is not shared", remote_ip, display_id);
             }
         }
 
         // Simple streaming request (minimal pipeline)
         Message::SimpleScreenRequest { display_id } => {
-            let remote_ip = _conn.remote_addr().ip().to_string();
+            let remote_ip = _conn.peer_ip();
             log::info!("[SIMPLE] Received SimpleScreenRequest from {} (display={})", remote_ip, display_id);
 
             // Handle in a background task - this will open a persistent stream and stream frames
@@ -457,12 +863,57 @@ async fn handle_message(
             });
         }
 
-        // Remote control messages will be handled in Phase 6
-        Message::ControlRequest { .. }
-        | Message::ControlGrant { .. }
-        | Message::ControlRevoke
-        | Message::InputEvent { .. } => {
-            log::debug!("Remote control message received (not yet implemented)");
+        // Remote control
+        Message::ControlRequest { from_user } => {
+            let remote_ip = _conn.peer_ip();
+            log::info!("{} ({}) requests control", from_user, remote_ip);
+            let decision = network::access::control_decision(&remote_ip);
+            if decision == network::access::Decision::Deny {
+                log::info!("Turning down control request from untrusted {}", remote_ip);
+                return Ok(());
+            }
+            if let Some(handle) = APP_HANDLE.get() {
+                #[derive(serde::Serialize, Clone)]
+                struct ControlRequestEvent {
+                    peer_ip: String,
+                    from_user: String,
+                    /// Approved without asking
+                    trusted: bool,
+                }
+                let _ = handle.emit("control-requested", ControlRequestEvent {
+                    trusted: network::trust::is_trusted(&remote_ip),
+                    peer_ip: remote_ip,
+                    from_user: from_user.clone(),
+                });
+            }
+        }
+
+        Message::ControlGrant { .. } | Message::ControlRevoke => {
+            let remote_ip = _conn.peer_ip();
+            let granted = matches!(msg, Message::ControlGrant { .. });
+            log::info!("Control of {} {}", remote_ip, if granted { "granted" } else { "revoked" });
+            input::session::set_controlling(&remote_ip, granted);
+            // A controller giving control back
+            if !granted && input::session::is_controller(&remote_ip) {
+                input::session::revoke();
+            }
+            if let Some(handle) = APP_HANDLE.get() {
+                let _ = handle.emit(if granted { "control-granted" } else { "control-revoked" }, &remote_ip);
+            }
+        }
+
+        Message::InputEvent { data, .. } => {
+            let remote_ip = _conn.peer_ip();
+            if !input::session::is_controller(&remote_ip) {
+                log::debug!("Dropping input from {} without control", remote_ip);
+                return Ok(());
+            }
+            match data {
+                network::protocol::InputData::Text { text } => {
+                    input::session::inject(input::InputEvent::text_input(text.as_str()));
+                }
+                _ => log::debug!("Pointer and key injection not yet implemented"),
+            }
         }
 
         // File transfer messages
@@ -490,7 +941,20 @@ async fn handle_message(
 
             // Get peer ID from connection
             let peer_id = _conn.remote_addr().to_string();
-            let transfer_record = transfer::get_transfer_manager().receive_offer(info, &peer_id);
+            let transfer_record = match transfer::get_transfer_manager().receive_offer(info, &peer_id) {
+                Ok(record) => record,
+                Err(e) => {
+                    log::warn!("Refusing file offer from {}: {}", peer_id, e);
+                    let reject = Message::FileReject { file_id: file_id.clone() };
+                    stream.send_framed(&protocol::encode(&reject)?).await?;
+                    return Ok(());
+                }
+            };
+
+            // Files a trusted peer copied come in without asking
+            if clipboard::offer_arrived(&_conn.peer_ip(), file_id) {
+                return Ok(());
+            }
 
             // Emit event to frontend to show file offer UI
             if let Some(handle) = APP_HANDLE.get() {
@@ -511,12 +975,55 @@ async fn handle_message(
                         t.start();
                     }
 
-                    // TODO: Start sending chunks in a separate task
-                    log::info!("Starting to send file chunks for {}", file_id);
+                    // Small files go in one message
+                    match manager.small_file_data(file_id) {
+                        Ok(Some(data)) => {
+                            let remote_ip = _conn.peer_ip();
+                            log::info!("Sending {} ({} bytes) in one message", transfer.info.name, data.len());
+                            let msg = Message::FileData { file_id: file_id.clone(), data };
+                            let sent = match network::protocol::encode(&msg) {
+                                Ok(encoded) => network::quic::send_to_peer(&remote_ip, &encoded)
+                                    .await
+                                    .map_err(|e| e.to_string()),
+                                Err(e) => Err(e.to_string()),
+                            };
+                            match sent {
+                                Ok(()) => {
+                                    let _ = manager.complete_transfer(file_id);
+                                }
+                                Err(e) => log::error!("Failed to send file {}: {}", file_id, e),
+                            }
+                        }
+                        Ok(None) => {
+                            // TODO: Start sending chunks in a separate task
+                            log::info!("Starting to send file chunks for {}", file_id);
+                        }
+                        Err(e) => log::error!("Failed to read file {}: {}", file_id, e),
+                    }
                 }
             }
         }
 
+        Message::FileData { file_id, data } => {
+            log::info!("Received file {} in one message ({} bytes)", file_id, data.len());
+            let result = transfer::get_transfer_manager().receive_whole(file_id, data);
+            if let Err(e) = &result {
+                log::error!("Failed to save file {}: {}", file_id, e);
+            }
+
+            if let Some(handle) = APP_HANDLE.get() {
+                #[derive(serde::Serialize, Clone)]
+                struct CompleteEvent {
+                    file_id: String,
+                    success: bool,
+                }
+                let _ = handle.emit("file-complete", CompleteEvent {
+                    file_id: file_id.clone(),
+                    success: result.is_ok(),
+                });
+            }
+        }
+
         Message::FileReject { file_id } => {
             log::info!("File transfer rejected: {}", file_id);
             let _ = transfer::get_transfer_manager().cancel_transfer(file_id);
@@ -617,6 +1124,126 @@ async fn handle_message(
                 });
             }
         }
+
+        // Scheduled meetings
+        Message::MeetingInvite {
+            meeting_id,
+            name,
+            host_id,
+            host_name,
+            start_time,
+        } => {
+            log::info!("Received meeting invite '{}' from {} (start={})", name, host_name, start_time);
+
+            let our_id = network::discovery::get_our_device_id().to_string();
+            let invite = meeting::ScheduledMeeting {
+                id: meeting_id.clone(),
+                name: name.clone(),
+                host_id: host_id.clone(),
+                host_name: host_name.clone(),
+                start_time: *start_time,
+                responses: [(our_id, meeting::InviteResponse::Pending)].into_iter().collect(),
+                is_local: false,
+                host_ip: Some(_conn.peer_ip()),
+                reminded: false,
+            };
+            meeting::get_meeting_manager().receive_invitation(invite.clone());
+
+            if let Some(handle) = APP_HANDLE.get() {
+                let _ = handle.emit("meeting-invite", &invite);
+            }
+        }
+
+        Message::MeetingResponse {
+            meeting_id,
+            device_id,
+            accepted,
+        } => {
+            log::info!("Meeting {} response from {}: accepted={}", meeting_id, device_id, accepted);
+
+            let response = if *accepted {
+                meeting::InviteResponse::Accepted
+            } else {
+                meeting::InviteResponse::Declined
+            };
+            if let Some(updated) = meeting::get_meeting_manager().record_response(meeting_id, device_id, response) {
+                if let Some(handle) = APP_HANDLE.get() {
+                    let _ = handle.emit("meeting-updated", &updated);
+                }
+                // Keep members' rosters in sync so any of them can take over as host
+                meeting::broadcast_roster(&updated).await;
+            }
+        }
+
+        Message::MeetingRoster {
+            meeting_id,
+            members,
+            profiles,
+        } => {
+            network::profile::apply_member_profiles(profiles);
+            if let Some(updated) = meeting::get_meeting_manager().apply_roster(meeting_id, members) {
+                if let Some(handle) = APP_HANDLE.get() {
+                    let _ = handle.emit("meeting-updated", &updated);
+                }
+                // Catch up on files offered to the meeting before we joined
+                transfer::broadcast::request_missed_files(&updated).await;
+            }
+        }
+
+        Message::MeetingFilesRequest { meeting_id } => {
+            let peer_ip = _conn.peer_ip();
+            transfer::broadcast::handle_files_request(&peer_ip, meeting_id).await;
+        }
+
+        Message::MeetingHostChanged {
+            meeting_id,
+            host_id,
+            host_name,
+        } => {
+            let host_ip = _conn.peer_ip();
+            // Only a member may announce itself, and only when it is its turn
+            let manager = meeting::get_meeting_manager();
+            let our_id = network::discovery::get_our_device_id();
+            let allowed = network::pairing::speaks_for(_conn, host_id)
+                && manager
+                    .get_meeting(meeting_id)
+                    .is_some_and(|m| meeting::may_take_over(&m, host_id, &meeting::online_ids(), our_id));
+            if !allowed {
+                log::warn!("Ignoring claim by {} to host meeting {} from {}", host_id, meeting_id, host_ip);
+                return Ok(());
+            }
+            log::info!("Meeting {} host changed to {} ({})", meeting_id, host_name, host_id);
+            if let Some(updated) = manager.set_host(meeting_id, host_id, host_name, Some(host_ip), false)
+            {
+                if let Some(handle) = APP_HANDLE.get() {
+                    let _ = handle.emit("meeting-host-changed", &updated);
+                }
+            }
+        }
+
+        Message::MeetingCancel { meeting_id } => {
+            log::info!("Meeting cancelled by host: {}", meeting_id);
+            transfer::broadcast::meeting_cancelled(meeting_id);
+            if meeting::get_meeting_manager().remove_meeting(meeting_id).is_some() {
+                if let Some(handle) = APP_HANDLE.get() {
+                    let _ = handle.emit("meeting-cancelled", meeting_id);
+                }
+            }
+        }
+
+        Message::ClipboardItem { id, from, content, timestamp } => {
+            clipboard::receive_item(
+                &_conn.peer_ip(),
+                id.clone(),
+                from.clone(),
+                content.clone(),
+                *timestamp,
+            );
+        }
+
+        Message::OpenUrl { url, from } => {
+            links::receive(&_conn.peer_ip(), from, url);
+        }
     }
 
     Ok(())
@@ -649,7 +1276,7 @@ async fn handle_simple_stream_with_first(
             ) {
                 log::info!("[SIMPLE] Sending initial resolution request: {} + {}", res.label, br.label);
                 let req = crate::simple_streaming::encode_resolution_request_msg(
-                    res.target_width, res.target_height, br.bitrate,
+                    res.target_width, res.target_height, br.bitrate, br.near_lossless,
                 );
                 if let Err(e) = stream.send_framed(&req).await {
                     log::error!("[SIMPLE] Failed to send initial resolution request: {}", e);
@@ -664,12 +1291,41 @@ async fn handle_simple_stream_with_first(
         // Poll window events (resolution requests)
         if let Some(ref handle) = window_handle {
             while let Some(event) = handle.try_recv_event() {
-                if let crate::renderer::WindowEvent::ResolutionRequested(target_w, target_h, bitrate) = event {
-                    log::info!("[SIMPLE] Viewer requesting resolution {}x{} @ {} bps", target_w, target_h, bitrate);
-                    let req = crate::simple_streaming::encode_resolution_request_msg(target_w, target_h, bitrate);
-                    if let Err(e) = stream.send_framed(&req).await {
-                        log::error!("[SIMPLE] Failed to send resolution request: {}", e);
+                match event {
+                    crate::renderer::WindowEvent::ResolutionRequested(target_w, target_h, bitrate, near_lossless) => {
+                        log::info!("[SIMPLE] Viewer requesting resolution {}x{} @ {} bps", target_w, target_h, bitrate);
+                        let req = crate::simple_streaming::encode_resolution_request_msg(target_w, target_h, bitrate, near_lossless);
+                        if let Err(e) = stream.send_framed(&req).await {
+                            log::error!("[SIMPLE] Failed to send resolution request: {}", e);
+                        }
+                    }
+                    crate::renderer::WindowEvent::TextInput(text) => {
+                        crate::input::session::forward_text(peer_ip, text);
+                    }
+                    crate::renderer::WindowEvent::SnapshotRequested => {
+                        crate::renderer::snapshot::capture_in_background(peer_ip);
+                    }
+                    crate::renderer::WindowEvent::Annotated(annotation) => {
+                        let msg = crate::simple_streaming::encode_annotation_msg(&annotation);
+                        if let Err(e) = stream.send_framed(&msg).await {
+                            log::warn!("[SIMPLE] Failed to send annotation: {}", e);
+                        }
                     }
+                    _ => {}
+                }
+            }
+            if let Some(display_id) = crate::simple_streaming::take_display_request(peer_ip) {
+                log::info!("[SIMPLE] Viewer requesting display {}", display_id);
+                let req = crate::simple_streaming::encode_display_request_msg(display_id);
+                if let Err(e) = stream.send_framed(&req).await {
+                    log::error!("[SIMPLE] Failed to send display request: {}", e);
+                }
+            }
+            // A recording that just started wants to begin on a keyframe
+            if streaming::recording::viewer_keyframe_wanted(peer_ip) {
+                let req = crate::simple_streaming::encode_keyframe_request_msg();
+                if let Err(e) = stream.send_framed(&req).await {
+                    log::warn!("[SIMPLE] Failed to send keyframe request: {}", e);
                 }
             }
             if !handle.is_open() {
@@ -711,6 +1367,9 @@ async fn handle_simple_stream_with_first(
     }
 
     // Cleanup
+    let _ = crate::simple_streaming::take_display_request(peer_ip);
+    streaming::recording::viewer_ended(peer_ip);
+    crate::renderer::viewers::unregister(peer_ip);
     if let Some(handle) = window_handle.as_ref() {
         handle.close();
     }
@@ -745,8 +1404,20 @@ fn process_simple_message(
 
             let width = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
             let height = u32::from_be_bytes([data[5], data[6], data[7], data[8]]);
+            let color_space = data
+                .get(10)
+                .copied()
+                .and_then(encoder::color::ColorSpace::from_id)
+                .unwrap_or_default();
 
-            log::info!("[SIMPLE] Received ScreenStart: {}x{} from {}", width, height, peer_ip);
+            log::info!(
+                "[SIMPLE] Received ScreenStart: {}x{} {} from {}",
+                width,
+                height,
+                color_space.name(),
+                peer_ip
+            );
+            streaming::recording::stream_started(peer_ip, 0, width, height, true);
 
             // Init decoder
             let mut dec = match SoftwareDecoder::new() {
@@ -761,6 +1432,7 @@ fn process_simple_message(
                 width,
                 height,
                 output_format: OutputFormat::BGRA,
+                color_space,
             };
 
             if let Err(e) = dec.init(config) {
@@ -771,10 +1443,11 @@ fn process_simple_message(
 
             // Only create window if not already open (resolution changes keep existing window)
             if window_handle.is_none() {
-                let title = format!("[Simple] {} screen", peer_ip);
+                let title = crate::renderer::viewers::title_for(peer_ip);
                 match RenderWindow::create(&title, width, height) {
                     Ok(handle) => {
                         log::info!("[SIMPLE] Render window created: {}x{}", width, height);
+                        crate::renderer::viewers::register(peer_ip, &title, &handle);
                         *window_handle = Some(handle);
                     }
                     Err(e) => {
@@ -833,20 +1506,25 @@ fn process_simple_message(
                 return;
             };
 
-            match dec.decode(frame_data, timestamp) {
-                Ok(Some(decoded)) => {
-                    if let Some(cpu_data) = decoded.cpu_data() {
-                        let render_frame = RenderFrame::from_bgra(
-                            decoded.width,
-                            decoded.height,
-                            cpu_data.to_vec(),
-                        );
+            streaming::recording::record_received(peer_ip, 0, frame_data);
+            streaming::timing::record_transit(timestamp);
+            let decode_start = std::time::Instant::now();
+            let result = dec.decode(frame_data, timestamp);
+            streaming::timing::record(streaming::timing::Stage::Decode, decode_start.elapsed());
+            // The software decoder hands over BGRA, so the dump needs no color space
+            streaming::frame_dump::record(
+                peer_ip,
+                frame_data,
+                result.as_ref().ok().and_then(Option::as_ref),
+                Default::default(),
+            );
 
-                        if let Some(handle) = window_handle.as_ref() {
-                            if let Err(e) = handle.render_frame(render_frame) {
-                                if *frame_count % 100 == 0 {
-                                    log::warn!("[SIMPLE] Render error: {}", e);
-                                }
+            match result {
+                Ok(Some(decoded)) => {
+                    if let Some(handle) = window_handle.as_ref() {
+                        if let Err(e) = handle.render_frame(RenderFrame::from_decoded(decoded)) {
+                            if *frame_count % 100 == 0 {
+                                log::warn!("[SIMPLE] Render error: {}", e);
                             }
                         }
                     }
@@ -869,6 +1547,21 @@ fn process_simple_message(
             }
         }
 
+        0x09 => {
+            // MSG_TYPE_METADATA
+            crate::simple_streaming::handle_metadata_message(peer_ip, data);
+        }
+
+        0x0B => {
+            // MSG_TYPE_POINTER
+            crate::simple_streaming::handle_pointer_message(window_handle.as_ref(), data);
+        }
+
+        0x0C => {
+            // MSG_TYPE_ANNOTATION
+            crate::simple_streaming::handle_annotation_message(window_handle.as_ref(), data);
+        }
+
         _ => {
             log::warn!("[SIMPLE] Unknown message type: 0x{:02x}", msg_type);
         }
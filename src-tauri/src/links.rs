@@ -0,0 +1,111 @@
+// Links sent to peers
+// "Open on remote": a URL sent to a peer is offered to its user in a
+// "url-received" event and opened in the default browser only once they
+// confirm, so nobody can pop pages open on another machine. Only http and
+// https links are accepted.
+
+use crate::network::protocol::{self, Message};
+use crate::network::quic;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::Emitter;
+
+/// Longest URL sent or accepted
+pub const MAX_URL_LEN: usize = 2048;
+
+/// Links waiting for the user to confirm
+const MAX_PENDING: usize = 20;
+
+/// A link a peer sent, as the confirmation prompt shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedUrl {
+    pub id: String,
+    pub peer_ip: String,
+    /// Device name of the sender
+    pub from_name: String,
+    pub url: String,
+}
+
+/// Received links by id, until answered
+static PENDING: once_cell::sync::Lazy<Mutex<HashMap<String, ReceivedUrl>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `url` may be opened: http(s), not too long, nothing that a
+/// browser or shell would read differently
+pub fn is_openable(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    let rest = match lower.strip_prefix("https://").or_else(|| lower.strip_prefix("http://")) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    url.len() <= MAX_URL_LEN && !rest.is_empty() && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Send `url` to `peer_ip` to open
+pub async fn send(peer_ip: &str, url: &str) -> Result<(), String> {
+    let url = url.trim();
+    if !is_openable(url) {
+        return Err("Only http and https links can be sent".to_string());
+    }
+    let from = crate::commands::get_self_info()?.name;
+    let encoded = protocol::encode(&Message::OpenUrl { url: url.to_string(), from }).map_err(|e| e.to_string())?;
+    quic::send_to_peer(peer_ip, &encoded).await.map_err(|e| e.to_string())?;
+    log::info!("Sent {} to {} to open", url, peer_ip);
+    Ok(())
+}
+
+/// A peer sent a link: ask the user
+pub fn receive(peer_ip: &str, from_name: &str, url: &str) {
+    if !is_openable(url) {
+        log::warn!("Ignoring a link from {} that cannot be opened", peer_ip);
+        return;
+    }
+    let received = ReceivedUrl {
+        id: uuid::Uuid::new_v4().to_string(),
+        peer_ip: peer_ip.to_string(),
+        from_name: from_name.to_string(),
+        url: url.to_string(),
+    };
+    {
+        let mut pending = PENDING.lock();
+        if pending.len() >= MAX_PENDING {
+            log::warn!("Too many links waiting, ignoring one from {}", peer_ip);
+            return;
+        }
+        pending.insert(received.id.clone(), received.clone());
+    }
+    log::info!("{} sent a link to open", peer_ip);
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("url-received", received);
+    }
+}
+
+/// The user answered the prompt for link `id`: open it or drop it
+pub fn answer(id: &str, open: bool) -> Result<(), String> {
+    let received = PENDING.lock().remove(id).ok_or_else(|| "Link not found".to_string())?;
+    if !open {
+        return Ok(());
+    }
+    use tauri_plugin_opener::OpenerExt;
+    let handle = crate::APP_HANDLE.get().ok_or_else(|| "App not ready".to_string())?;
+    log::info!("Opening {} from {}", received.url, received.peer_ip);
+    handle.opener().open_url(&received.url, None::<&str>).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_web_links_open() {
+        assert!(is_openable("https://example.com/a?b=c"));
+        assert!(is_openable("HTTP://192.168.1.20:8080"));
+        assert!(!is_openable("https://"));
+        assert!(!is_openable("file:///etc/passwd"));
+        assert!(!is_openable("javascript:alert(1)"));
+        assert!(!is_openable("https://example.com/a b"));
+        assert!(!is_openable("https://example.com/\n"));
+        assert!(!is_openable(&format!("https://example.com/{}", "a".repeat(MAX_URL_LEN))));
+    }
+}
@@ -0,0 +1,527 @@
+// Meeting module
+// Scheduled meetings with LAN-wide invitations, stored locally
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::network::discovery;
+use crate::network::protocol::{self, Message};
+use crate::network::quic;
+
+/// How long before the start time a reminder is emitted (5 minutes)
+pub const REMINDER_LEAD_MS: u64 = 5 * 60 * 1000;
+
+/// How often the reminder task checks for due meetings
+const REMINDER_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Meetings that ended more than this long ago are dropped (1 day)
+const EXPIRY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Invitee response state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InviteResponse {
+    Pending,
+    Accepted,
+    Declined,
+}
+
+/// A scheduled meeting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledMeeting {
+    /// Meeting ID
+    pub id: String,
+    /// Meeting title
+    pub name: String,
+    /// Host device ID
+    pub host_id: String,
+    /// Host display name
+    pub host_name: String,
+    /// Start time (Unix milliseconds)
+    pub start_time: u64,
+    /// Invitee device ID -> response
+    pub responses: HashMap<String, InviteResponse>,
+    /// Whether we are the host
+    pub is_local: bool,
+    /// Host IP for sending responses (received invitations only)
+    #[serde(default)]
+    pub host_ip: Option<String>,
+    /// Whether the reminder was already emitted
+    #[serde(default)]
+    pub reminded: bool,
+}
+
+/// Meeting manager holding all known meetings
+pub struct MeetingManager {
+    meetings: RwLock<HashMap<String, ScheduledMeeting>>,
+}
+
+impl Default for MeetingManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeetingManager {
+    /// Create a new meeting manager, loading saved meetings from disk
+    pub fn new() -> Self {
+        Self {
+            meetings: RwLock::new(load_meetings_from_disk()),
+        }
+    }
+
+    /// Create a meeting that we host
+    pub fn create_meeting(
+        &self,
+        name: &str,
+        start_time: u64,
+        host_id: &str,
+        host_name: &str,
+        invitees: &[String],
+    ) -> ScheduledMeeting {
+        let meeting = ScheduledMeeting {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            host_id: host_id.to_string(),
+            host_name: host_name.to_string(),
+            start_time,
+            responses: invitees
+                .iter()
+                .map(|id| (id.clone(), InviteResponse::Pending))
+                .collect(),
+            is_local: true,
+            host_ip: None,
+            reminded: false,
+        };
+
+        self.meetings.write().insert(meeting.id.clone(), meeting.clone());
+        self.save();
+        meeting
+    }
+
+    /// Store an invitation received from a host
+    pub fn receive_invitation(&self, meeting: ScheduledMeeting) {
+        self.meetings.write().insert(meeting.id.clone(), meeting);
+        self.save();
+    }
+
+    /// Record an invitee's response. Returns the updated meeting.
+    pub fn record_response(
+        &self,
+        meeting_id: &str,
+        device_id: &str,
+        response: InviteResponse,
+    ) -> Option<ScheduledMeeting> {
+        let updated = {
+            let mut meetings = self.meetings.write();
+            let meeting = meetings.get_mut(meeting_id)?;
+            meeting.responses.insert(device_id.to_string(), response);
+            meeting.clone()
+        };
+        self.save();
+        Some(updated)
+    }
+
+    /// Remove a meeting (cancelled by the host)
+    pub fn remove_meeting(&self, meeting_id: &str) -> Option<ScheduledMeeting> {
+        let removed = self.meetings.write().remove(meeting_id);
+        if removed.is_some() {
+            self.save();
+        }
+        removed
+    }
+
+    /// Get a meeting by ID
+    pub fn get_meeting(&self, meeting_id: &str) -> Option<ScheduledMeeting> {
+        self.meetings.read().get(meeting_id).cloned()
+    }
+
+    /// Get all meetings sorted by start time
+    pub fn get_meetings(&self) -> Vec<ScheduledMeeting> {
+        let mut meetings: Vec<_> = self.meetings.read().values().cloned().collect();
+        meetings.sort_by_key(|m| m.start_time);
+        meetings
+    }
+
+    /// Replace the member list of a received meeting with the host's roster
+    pub fn apply_roster(&self, meeting_id: &str, members: &[String]) -> Option<ScheduledMeeting> {
+        let updated = {
+            let mut meetings = self.meetings.write();
+            let meeting = meetings.get_mut(meeting_id)?;
+            if meeting.is_local {
+                return None;
+            }
+            for id in members {
+                meeting.responses.insert(id.clone(), InviteResponse::Accepted);
+            }
+            meeting.clone()
+        };
+        self.save();
+        Some(updated)
+    }
+
+    /// Transfer the host role. The new host is removed from the member list.
+    pub fn set_host(
+        &self,
+        meeting_id: &str,
+        host_id: &str,
+        host_name: &str,
+        host_ip: Option<String>,
+        is_local: bool,
+    ) -> Option<ScheduledMeeting> {
+        let updated = {
+            let mut meetings = self.meetings.write();
+            let meeting = meetings.get_mut(meeting_id)?;
+            meeting.responses.remove(host_id);
+            meeting.host_id = host_id.to_string();
+            meeting.host_name = host_name.to_string();
+            meeting.host_ip = host_ip;
+            meeting.is_local = is_local;
+            meeting.clone()
+        };
+        self.save();
+        Some(updated)
+    }
+
+    /// Take meetings whose reminder is due and mark them reminded.
+    /// Also drops meetings that are long over.
+    pub fn take_due_reminders(&self, now: u64) -> Vec<ScheduledMeeting> {
+        let mut due = Vec::new();
+        let mut changed = false;
+        {
+            let mut meetings = self.meetings.write();
+
+            let before = meetings.len();
+            meetings.retain(|_, m| m.start_time + EXPIRY_MS > now);
+            changed |= meetings.len() != before;
+
+            for meeting in meetings.values_mut() {
+                // Received invitations only hold our own response
+                let declined = !meeting.is_local
+                    && meeting.responses.values().any(|r| *r == InviteResponse::Declined);
+                if !meeting.reminded
+                    && !declined
+                    && now + REMINDER_LEAD_MS >= meeting.start_time
+                {
+                    meeting.reminded = true;
+                    due.push(meeting.clone());
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.save();
+        }
+        due
+    }
+
+    fn save(&self) {
+        let meetings = self.get_meetings();
+        save_meetings_to_disk(&meetings);
+    }
+}
+
+/// Meetings file path
+fn meetings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("lan-meeting").join("meetings.json"))
+}
+
+fn load_meetings_from_disk() -> HashMap<String, ScheduledMeeting> {
+    let Some(path) = meetings_path() else {
+        return HashMap::new();
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(json) => match serde_json::from_str::<Vec<ScheduledMeeting>>(&json) {
+            Ok(meetings) => meetings.into_iter().map(|m| (m.id.clone(), m)).collect(),
+            Err(e) => {
+                log::warn!("Failed to parse meetings file: {}", e);
+                HashMap::new()
+            }
+        },
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_meetings_to_disk(meetings: &[ScheduledMeeting]) {
+    let Some(path) = meetings_path() else {
+        log::warn!("Cannot determine meetings path");
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_string_pretty(meetings) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                log::error!("Failed to write meetings to {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => log::error!("Failed to serialize meetings: {}", e),
+    }
+}
+
+/// Global meeting manager
+static MEETING_MANAGER: once_cell::sync::Lazy<Arc<MeetingManager>> =
+    once_cell::sync::Lazy::new(|| Arc::new(MeetingManager::new()));
+
+/// Get the global meeting manager
+pub fn get_meeting_manager() -> Arc<MeetingManager> {
+    MEETING_MANAGER.clone()
+}
+
+/// Current time in Unix milliseconds
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl ScheduledMeeting {
+    /// Device IDs of members who accepted the invitation
+    pub fn accepted_members(&self) -> Vec<String> {
+        self.responses
+            .iter()
+            .filter(|(_, r)| **r == InviteResponse::Accepted)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+/// Deterministically pick a new host after the current one left:
+/// the lowest device ID among accepted members that are still reachable.
+/// Every member runs the same election and reaches the same result.
+pub fn elect_host(meeting: &ScheduledMeeting, online_ids: &[String], our_id: &str) -> Option<String> {
+    meeting
+        .accepted_members()
+        .into_iter()
+        .filter(|id| id != &meeting.host_id)
+        .filter(|id| id == our_id || online_ids.contains(id))
+        .min()
+}
+
+/// Whether `claimant` may take over as host of `meeting` as it announced:
+/// it must be an accepted member, and the one the election picks unless
+/// the current host is gone
+pub fn may_take_over(meeting: &ScheduledMeeting, claimant: &str, online_ids: &[String], our_id: &str) -> bool {
+    if !meeting.accepted_members().iter().any(|id| id == claimant) {
+        return false;
+    }
+    let host_gone = !meeting.is_local && !online_ids.contains(&meeting.host_id);
+    host_gone || elect_host(meeting, online_ids, our_id).as_deref() == Some(claimant)
+}
+
+/// Ids of the devices not offline
+pub fn online_ids() -> Vec<String> {
+    discovery::get_devices()
+        .into_iter()
+        .filter(|d| d.status != discovery::DeviceStatus::Offline)
+        .map(|d| d.id)
+        .collect()
+}
+
+/// Send a message to meeting members by device ID (best effort)
+async fn send_to_members(members: &[String], msg: &Message) {
+    let Ok(encoded) = protocol::encode(msg) else {
+        return;
+    };
+    let devices = discovery::get_devices();
+
+    for id in members {
+        let Some(device) = devices.iter().find(|d| &d.id == id) else {
+            log::debug!("Meeting member {} not reachable", id);
+            continue;
+        };
+        if let Err(e) = quic::send_to_peer(&device.ip, &encoded).await {
+            log::warn!("Failed to send meeting update to {}: {}", device.ip, e);
+        }
+    }
+}
+
+/// Send the current member list to all members (host only)
+pub async fn broadcast_roster(meeting: &ScheduledMeeting) {
+    if !meeting.is_local {
+        return;
+    }
+    let members = meeting.accepted_members();
+    let msg = Message::MeetingRoster {
+        meeting_id: meeting.id.clone(),
+        members: members.clone(),
+        profiles: member_profiles(meeting),
+    };
+    send_to_members(&members, &msg).await;
+}
+
+/// Names and avatars of the host and accepted members that we know
+fn member_profiles(meeting: &ScheduledMeeting) -> Vec<protocol::MemberProfile> {
+    use base64::Engine;
+
+    let our_id = discovery::get_our_device_id();
+    let mut profiles = vec![protocol::MemberProfile {
+        device_id: our_id.to_string(),
+        name: crate::commands::display_name(),
+        avatar: crate::network::profile::our_avatar(),
+    }];
+    let devices = discovery::get_devices();
+    for id in meeting.accepted_members() {
+        let Some(device) = devices.iter().find(|d| d.id == id && d.id != our_id) else {
+            continue;
+        };
+        profiles.push(protocol::MemberProfile {
+            device_id: device.id.clone(),
+            name: device.name.clone(),
+            avatar: device
+                .avatar
+                .as_ref()
+                .and_then(|a| base64::engine::general_purpose::STANDARD.decode(a).ok()),
+        });
+    }
+    profiles
+}
+
+/// Take over as host of a meeting and notify the other members
+pub async fn claim_host(meeting_id: &str) -> Option<ScheduledMeeting> {
+    let our_id = discovery::get_our_device_id();
+    let our_name = crate::commands::display_name();
+
+    let meeting = get_meeting_manager().set_host(meeting_id, our_id, &our_name, None, true)?;
+    log::info!("Became host of meeting '{}'", meeting.name);
+    emit_host_changed(&meeting);
+
+    let msg = Message::MeetingHostChanged {
+        meeting_id: meeting.id.clone(),
+        host_id: our_id.to_string(),
+        host_name: our_name,
+    };
+    send_to_members(&meeting.accepted_members(), &msg).await;
+    broadcast_roster(&meeting).await;
+
+    Some(meeting)
+}
+
+/// Migrate hosting of meetings whose host just disconnected
+pub async fn handle_peer_left(peer_ip: &str, device_ids: &[String]) {
+    let manager = get_meeting_manager();
+    let our_id = discovery::get_our_device_id();
+    let devices = discovery::get_devices();
+    let online_ids = online_ids();
+
+    for meeting in manager.get_meetings() {
+        let host_left = !meeting.is_local
+            && (device_ids.contains(&meeting.host_id)
+                || meeting.host_ip.as_deref() == Some(peer_ip));
+        if !host_left {
+            continue;
+        }
+
+        let Some(new_host) = elect_host(&meeting, &online_ids, our_id) else {
+            log::info!("Host of meeting '{}' left and no member can take over", meeting.name);
+            continue;
+        };
+
+        if new_host == our_id {
+            claim_host(&meeting.id).await;
+        } else if let Some(device) = devices.iter().find(|d| d.id == new_host) {
+            log::info!("Host of meeting '{}' left, new host is {}", meeting.name, device.name);
+            if let Some(updated) =
+                manager.set_host(&meeting.id, &device.id, &device.name, Some(device.ip.clone()), false)
+            {
+                emit_host_changed(&updated);
+            }
+        }
+    }
+}
+
+fn emit_host_changed(meeting: &ScheduledMeeting) {
+    if let Some(app) = crate::APP_HANDLE.get() {
+        let _ = app.emit("meeting-host-changed", meeting);
+    }
+}
+
+static REMINDER_TASK_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Start the background task that emits `meeting-reminder` events
+pub fn start_reminder_task(app: AppHandle) {
+    if REMINDER_TASK_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        log::info!("Meeting reminder task started");
+        loop {
+            for meeting in get_meeting_manager().take_due_reminders(now_ms()) {
+                log::info!("Meeting reminder: '{}' at {}", meeting.name, meeting.start_time);
+                let _ = app.emit("meeting-reminder", &meeting);
+            }
+            tokio::time::sleep(REMINDER_CHECK_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meeting_with(host_id: &str, members: &[(&str, InviteResponse)]) -> ScheduledMeeting {
+        ScheduledMeeting {
+            id: "m1".to_string(),
+            name: "Standup".to_string(),
+            host_id: host_id.to_string(),
+            host_name: "Host".to_string(),
+            start_time: 0,
+            responses: members.iter().map(|(id, r)| (id.to_string(), *r)).collect(),
+            is_local: false,
+            host_ip: None,
+            reminded: false,
+        }
+    }
+
+    #[test]
+    fn test_elect_host_picks_lowest_online_member() {
+        let meeting = meeting_with(
+            "a",
+            &[
+                ("d", InviteResponse::Accepted),
+                ("b", InviteResponse::Declined),
+                ("c", InviteResponse::Accepted),
+                ("e", InviteResponse::Accepted),
+            ],
+        );
+        let online = vec!["c".to_string(), "d".to_string()];
+        // "b" declined, "e" is ours but "c" sorts first
+        assert_eq!(elect_host(&meeting, &online, "e"), Some("c".to_string()));
+    }
+
+    #[test]
+    fn test_elect_host_none_without_members() {
+        let meeting = meeting_with("a", &[("b", InviteResponse::Pending)]);
+        assert_eq!(elect_host(&meeting, &["b".to_string()], "z"), None);
+    }
+
+    #[test]
+    fn test_spoofed_host_claim_is_rejected() {
+        let meeting = meeting_with(
+            "a",
+            &[("b", InviteResponse::Accepted), ("c", InviteResponse::Accepted), ("d", InviteResponse::Declined)],
+        );
+        let online: Vec<String> = ["a", "b", "c", "d", "x"].iter().map(|id| id.to_string()).collect();
+        // Not a member, or one that declined
+        assert!(!may_take_over(&meeting, "x", &online, "z"));
+        assert!(!may_take_over(&meeting, "d", &online, "z"));
+        // A member the election does not pick while the host is still here
+        assert!(!may_take_over(&meeting, "c", &online, "z"));
+        assert!(may_take_over(&meeting, "b", &online, "z"));
+
+        // Once the host is gone, any accepted member may take over
+        let online: Vec<String> = ["c".to_string()].into();
+        assert!(may_take_over(&meeting, "c", &online, "z"));
+        assert!(!may_take_over(&meeting, "x", &online, "z"));
+    }
+}
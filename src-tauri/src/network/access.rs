@@ -0,0 +1,209 @@
+//! Viewer approval
+//!
+//! Who may watch our screen or ask to control it is up to the viewer
+//! policy in settings: everyone, trusted devices only, or (the default)
+//! trusted devices and whoever the user lets in when asked. A viewer let in
+//! stays in until our screen share ends or the user kicks them, so
+//! switching displays or reconnecting does not ask again.
+
+use super::protocol::{self, Message, ScreenRejectReason};
+use super::{discovery, quic, trust};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::oneshot;
+
+/// How long a request waits for the user
+const ASK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Who may watch without the user letting them in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ViewerPolicy {
+    /// Everyone
+    AllowAll,
+    /// Trusted devices; everyone else is turned down
+    TrustedOnly,
+    /// Trusted devices; the user is asked about everyone else
+    #[default]
+    Ask,
+}
+
+impl ViewerPolicy {
+    pub const ALL: [ViewerPolicy; 3] = [ViewerPolicy::AllowAll, ViewerPolicy::TrustedOnly, ViewerPolicy::Ask];
+
+    /// Settings name
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewerPolicy::AllowAll => "allow_all",
+            ViewerPolicy::TrustedOnly => "trusted_only",
+            ViewerPolicy::Ask => "ask",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// What to do with a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny,
+    Ask,
+}
+
+/// The decision on a request from a device that is `trusted` or was
+/// `admitted` earlier in this share
+pub fn decide(policy: ViewerPolicy, trusted: bool, admitted: bool) -> Decision {
+    match policy {
+        _ if trusted || admitted => Decision::Allow,
+        ViewerPolicy::AllowAll => Decision::Allow,
+        ViewerPolicy::TrustedOnly => Decision::Deny,
+        ViewerPolicy::Ask => Decision::Ask,
+    }
+}
+
+/// Payload of the "viewer-requested" event
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewerRequest {
+    pub id: String,
+    pub peer_ip: String,
+    pub name: String,
+}
+
+/// Peers let in during the current share
+static ADMITTED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Requests waiting for the user, by id
+static PENDING: Lazy<Mutex<HashMap<String, oneshot::Sender<bool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The decision on a control request from a device that is `trusted`.
+/// Only trusted devices get control without the user saying so: the policy
+/// decides who may ask, and admission to watch does not extend to control.
+pub fn decide_control(policy: ViewerPolicy, trusted: bool) -> Decision {
+    match policy {
+        _ if trusted => Decision::Allow,
+        ViewerPolicy::TrustedOnly => Decision::Deny,
+        ViewerPolicy::AllowAll | ViewerPolicy::Ask => Decision::Ask,
+    }
+}
+
+/// The decision on a control request from `peer_ip`
+pub fn control_decision(peer_ip: &str) -> Decision {
+    decide_control(crate::commands::viewer_policy(), trust::is_trusted(peer_ip))
+}
+
+/// Whether `peer_ip` may watch our screen, asking the user if the policy
+/// says so
+pub async fn admit_viewer(peer_ip: &str) -> bool {
+    let admitted = ADMITTED.lock().contains(peer_ip);
+    let allowed = match decide(crate::commands::viewer_policy(), trust::is_trusted(peer_ip), admitted) {
+        Decision::Allow => true,
+        Decision::Deny => false,
+        Decision::Ask => ask(peer_ip).await,
+    };
+    if allowed {
+        ADMITTED.lock().insert(peer_ip.to_string());
+    } else {
+        log::info!("Not letting {} watch", peer_ip);
+    }
+    allowed
+}
+
+async fn ask(peer_ip: &str) -> bool {
+    let request = ViewerRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        peer_ip: peer_ip.to_string(),
+        name: discovery::get_devices()
+            .into_iter()
+            .find(|d| d.ip == peer_ip)
+            .map(|d| d.nickname.unwrap_or(d.name))
+            .unwrap_or_else(|| peer_ip.to_string()),
+    };
+    let (tx, rx) = oneshot::channel();
+    PENDING.lock().insert(request.id.clone(), tx);
+    let id = request.id.clone();
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("viewer-requested", request);
+    }
+
+    let allowed = matches!(tokio::time::timeout(ASK_TIMEOUT, rx).await, Ok(Ok(true)));
+    if PENDING.lock().remove(&id).is_some() {
+        // Nobody answered
+        if let Some(handle) = crate::APP_HANDLE.get() {
+            let _ = handle.emit("viewer-request-closed", &id);
+        }
+    }
+    allowed
+}
+
+/// The user answered "viewer-requested" `id`
+pub fn answer(id: &str, allow: bool) -> Result<(), String> {
+    let tx = PENDING.lock().remove(id).ok_or_else(|| "Viewer request not found".to_string())?;
+    let _ = tx.send(allow);
+    Ok(())
+}
+
+/// Our screen share ended: the next one asks again
+pub fn end_share() {
+    ADMITTED.lock().clear();
+}
+
+/// Stop streaming to `peer_ip` and tell them why; they have to be let in
+/// again to come back. Returns whether they were watching.
+pub async fn kick(peer_ip: &str) -> bool {
+    ADMITTED.lock().remove(peer_ip);
+    let watching = crate::streaming::remove_viewer(peer_ip, None);
+    let watching_simple = crate::simple_streaming::kick_viewer(peer_ip);
+    if !watching && !watching_simple {
+        return false;
+    }
+    log::info!("Kicked viewer {}", peer_ip);
+
+    // The simple stream closes with its stop message; the full pipeline's
+    // windows need a ScreenStop
+    let mut messages = vec![Message::ScreenReject { display_id: 0, reason: ScreenRejectReason::Kicked }];
+    if watching {
+        messages.insert(0, Message::ScreenStop { display_id: None });
+    }
+    for msg in messages {
+        let sent = match protocol::encode(&msg) {
+            Ok(encoded) => quic::send_to_peer(peer_ip, &encoded).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            log::warn!("Failed to tell {} they were kicked: {}", peer_ip, e);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_decisions() {
+        use ViewerPolicy::*;
+        assert_eq!(decide(AllowAll, false, false), Decision::Allow);
+        assert_eq!(decide(TrustedOnly, false, false), Decision::Deny);
+        assert_eq!(decide(TrustedOnly, true, false), Decision::Allow);
+        assert_eq!(decide(Ask, false, false), Decision::Ask);
+        assert_eq!(decide(Ask, true, false), Decision::Allow);
+        // Let in once this share
+        assert_eq!(decide(Ask, false, true), Decision::Allow);
+
+        // Control is never granted by the policy alone
+        assert_eq!(decide_control(AllowAll, false), Decision::Ask);
+        assert_eq!(decide_control(Ask, false), Decision::Ask);
+        assert_eq!(decide_control(TrustedOnly, false), Decision::Deny);
+        assert_eq!(decide_control(AllowAll, true), Decision::Allow);
+
+        assert_eq!(ViewerPolicy::from_name("trusted_only"), Some(TrustedOnly));
+        assert_eq!(ViewerPolicy::from_name(""), None);
+    }
+}
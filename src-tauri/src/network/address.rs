@@ -0,0 +1,225 @@
+//! Local address changes
+//!
+//! A DHCP renewal, another Wi-Fi network or a VPN coming up can change our
+//! LAN address while the service runs. Peers that added us by IP would keep
+//! dialing the old one, and the mDNS record would go on advertising it. The
+//! interface list is polled, and when our addresses change:
+//!
+//! - mDNS starts over with a new daemon, bound to the interfaces we have now
+//! - every known peer is told the new addresses with an `AddressUpdate`,
+//!   reconnecting first if the old connection did not survive the change.
+//!   The QUIC socket is bound to the wildcard address, so it keeps working;
+//!   a live connection migrates to the new path with that first packet.
+//! - the frontend gets a `network-changed` event and fresh self info
+
+use super::discovery;
+use super::protocol::{self, Message};
+use super::quic;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::Emitter;
+
+/// How often the interface list is checked
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long reconnecting to a peer may take
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Bumped for every watch loop started, so one left over from before a
+/// quick stop and start knows to exit
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Whether an IPv6 address is link-local (fe80::/10)
+pub fn is_link_local_v6(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// A peer's address as we file it. IPv4 peers reaching our dual-stack
+/// socket show up as IPv4-mapped IPv6 and are turned back into IPv4.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(IpAddr::V4(v4), v6.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
+
+/// The string a peer is known by: its IP, plus the interface index of a
+/// link-local IPv6 address (`fe80::1%3`), which cannot be dialed without it
+pub fn peer_ip(addr: SocketAddr) -> String {
+    match canonical(addr) {
+        SocketAddr::V6(v6) if is_link_local_v6(v6.ip()) && v6.scope_id() != 0 => {
+            format!("{}%{}", v6.ip(), v6.scope_id())
+        }
+        addr => addr.ip().to_string(),
+    }
+}
+
+/// The address to dial for a peer IP as `peer_ip` writes it
+pub fn socket_addr(ip: &str, port: u16) -> Option<SocketAddr> {
+    match ip.split_once('%') {
+        Some((ip, scope)) => Some(SocketAddr::V6(SocketAddrV6::new(ip.parse().ok()?, port, 0, scope.parse().ok()?))),
+        None => Some(SocketAddr::new(ip.trim_matches(['[', ']']).parse().ok()?, port)),
+    }
+}
+
+/// Our addresses peers can reach: private IPv4 and routable IPv6
+pub fn local_addresses() -> Vec<IpAddr> {
+    let mut addresses: Vec<IpAddr> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| iface.ip())
+        .filter(|ip| match ip {
+            IpAddr::V4(_) => crate::commands::is_real_lan_ip(ip),
+            // Link-local IPv6 needs a scope id, useless to a peer
+            IpAddr::V6(v6) => !is_link_local_v6(v6),
+        })
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    addresses
+}
+
+/// Payload of the "network-changed" event
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkChanged {
+    pub addresses: Vec<String>,
+}
+
+/// Notices when the address list changes
+#[derive(Debug, Default)]
+struct AddressWatch {
+    current: Option<Vec<IpAddr>>,
+}
+
+impl AddressWatch {
+    /// Feed the current addresses; returns them if they changed since the
+    /// last call (the first call only records them)
+    fn update(&mut self, addresses: Vec<IpAddr>) -> Option<Vec<IpAddr>> {
+        let previous = self.current.replace(addresses.clone())?;
+        // Briefly having no address mid-switch is not worth announcing
+        (previous != addresses && !addresses.is_empty()).then_some(addresses)
+    }
+}
+
+/// Start watching for address changes
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::spawn(watch_loop(generation));
+    }
+}
+
+/// Stop watching, with the service
+pub fn stop() {
+    RUNNING.store(false, Ordering::SeqCst);
+}
+
+async fn watch_loop(generation: u64) {
+    let mut watch = AddressWatch::default();
+    while RUNNING.load(Ordering::SeqCst) && GENERATION.load(Ordering::SeqCst) == generation {
+        let addresses = tokio::task::spawn_blocking(local_addresses).await.unwrap_or_default();
+        if let Some(addresses) = watch.update(addresses) {
+            log::info!("Local addresses changed: {:?}", addresses);
+            if let Err(e) = discovery::restart().await {
+                log::warn!("Failed to restart mDNS discovery: {}", e);
+            }
+            announce(&addresses).await;
+            crate::app_state::changed(crate::app_state::Slice::SelfInfo);
+            if let Some(handle) = crate::APP_HANDLE.get() {
+                let addresses = addresses.iter().map(|ip| ip.to_string()).collect();
+                let _ = handle.emit("network-changed", NetworkChanged { addresses });
+            }
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Tell every known peer our new addresses
+async fn announce(addresses: &[IpAddr]) {
+    let Some(endpoint) = crate::get_quic_endpoint() else {
+        return;
+    };
+    let msg = Message::AddressUpdate {
+        device_id: discovery::get_our_device_id().to_string(),
+        addresses: addresses.iter().map(|ip| ip.to_string()).collect(),
+        port: endpoint.local_addr().port(),
+    };
+    let Ok(encoded) = protocol::encode(&msg) else {
+        return;
+    };
+
+    for device in discovery::get_devices() {
+        if quic::find_connection(&device.ip).is_none_or(|c| !c.is_alive()) {
+            // The old path died with our address; dial the peer again
+            let Some(addr) = socket_addr(&device.ip, device.port) else {
+                continue;
+            };
+            quic::remove_connection_by_ip(&device.ip);
+            match endpoint.connect_with_retry(addr, RECONNECT_TIMEOUT).await {
+                Ok(conn) => {
+                    tokio::spawn(crate::handle_incoming_connection(conn));
+                }
+                Err(e) => {
+                    log::debug!("Reconnecting to {} failed: {}", device.name, e);
+                    continue;
+                }
+            }
+        }
+        if let Err(e) = quic::send_to_peer(&device.ip, &encoded).await {
+            log::debug!("Failed to send address update to {}: {}", device.name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(list: &[&str]) -> Vec<IpAddr> {
+        list.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_reports_changes_only() {
+        let mut watch = AddressWatch::default();
+        assert_eq!(watch.update(ips(&["192.168.1.5"])), None);
+        assert_eq!(watch.update(ips(&["192.168.1.5"])), None);
+        assert_eq!(
+            watch.update(ips(&["10.0.0.7", "fd00::7"])),
+            Some(ips(&["10.0.0.7", "fd00::7"]))
+        );
+        // Between networks: nothing to announce until a new address shows up
+        assert_eq!(watch.update(Vec::new()), None);
+        assert_eq!(watch.update(ips(&["10.0.0.7", "fd00::7"])), Some(ips(&["10.0.0.7", "fd00::7"])));
+    }
+
+    #[test]
+    fn test_peer_ip_round_trip() {
+        // IPv4 through a dual-stack socket
+        let mapped: SocketAddr = "[::ffff:192.168.1.5]:19876".parse().unwrap();
+        assert_eq!(canonical(mapped), "192.168.1.5:19876".parse().unwrap());
+        assert_eq!(peer_ip(mapped), "192.168.1.5");
+
+        // Link-local keeps its interface, ULA needs none
+        let link_local = SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 19876, 0, 3));
+        assert_eq!(peer_ip(link_local), "fe80::1%3");
+        assert_eq!(socket_addr("fe80::1%3", 19876), Some(link_local));
+        assert_eq!(peer_ip("[fd12::7]:5000".parse().unwrap()), "fd12::7");
+        assert_eq!(socket_addr("fd12::7", 19876), Some("[fd12::7]:19876".parse().unwrap()));
+        assert_eq!(socket_addr("192.168.1.5", 19876), Some("192.168.1.5:19876".parse().unwrap()));
+        assert_eq!(socket_addr("fe80::1%eth0", 19876), None);
+        assert_eq!(socket_addr("not an ip", 19876), None);
+
+        // Connection keys parse back to the same peer
+        let key = canonical(link_local).to_string();
+        assert_eq!(key.parse::<SocketAddr>().map(peer_ip).ok().as_deref(), Some("fe80::1%3"));
+    }
+}
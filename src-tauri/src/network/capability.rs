@@ -0,0 +1,226 @@
+//! Protocol version and capabilities
+//!
+//! The handshake and the mDNS record carry a list of tokens: the protocol
+//! version as `protocol/N` and one name per feature; the answering side
+//! sends its own ahead of an accepting answer. Builds from before the
+//! version was announced count as protocol 1, and are turned away: their
+//! messages are laid out differently. Each side keeps, by peer IP, the
+//! intersection of its own set and the peer's, and does not offer a peer
+//! what it lacks. New features are added as tokens, which older builds
+//! ignore.
+
+use crate::encoder::Codec;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Protocol this build speaks
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest protocol this build still talks to
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// Handshake refusal reason for a peer on a protocol too old
+pub const UNSUPPORTED: &str = "Protocol version not supported, update LAN Meeting on both devices";
+
+const VERSION_PREFIX: &str = "protocol/";
+
+/// A feature a build may have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    ScreenShare,
+    RemoteControl,
+    Chat,
+    FileTransfer,
+    /// Decodes H.264 streams
+    H264,
+    /// Decodes AV1 streams
+    Av1,
+    Audio,
+    /// Picks up an interrupted file transfer where it stopped
+    TransferResume,
+    /// Takes delta frames as QUIC datagrams
+    Datagrams,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 9] = [
+        Capability::ScreenShare,
+        Capability::RemoteControl,
+        Capability::Chat,
+        Capability::FileTransfer,
+        Capability::H264,
+        Capability::Av1,
+        Capability::Audio,
+        Capability::TransferResume,
+        Capability::Datagrams,
+    ];
+
+    /// Token in handshakes and the mDNS record
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::ScreenShare => "screen-share",
+            Capability::RemoteControl => "remote-control",
+            Capability::Chat => "chat",
+            Capability::FileTransfer => "file-transfer",
+            Capability::H264 => "codec-h264",
+            Capability::Av1 => "codec-av1",
+            Capability::Audio => "audio",
+            Capability::TransferResume => "transfer-resume",
+            Capability::Datagrams => "datagrams",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Decoding streams of `codec`
+    pub fn codec(codec: Codec) -> Self {
+        match codec {
+            Codec::H264 => Capability::H264,
+            Codec::Av1 => Capability::Av1,
+        }
+    }
+}
+
+/// Codecs a decoder opens for here, probed once: a peer picking one we
+/// cannot decode would send frames nobody sees
+static DECODABLE: Lazy<BTreeSet<Capability>> = Lazy::new(|| {
+    [Codec::H264, Codec::Av1]
+        .into_iter()
+        .filter(|&codec| {
+            let ok = crate::decoder::can_decode(codec);
+            if !ok {
+                log::warn!("No {} decoder opens here, not announcing it", codec.name());
+            }
+            ok
+        })
+        .map(Capability::codec)
+        .collect()
+});
+
+/// A protocol version and feature set, ours, a peer's or the two combined
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub protocol: u32,
+    pub features: BTreeSet<Capability>,
+}
+
+impl Capabilities {
+    /// What this build does on this machine
+    pub fn ours() -> Self {
+        use Capability::*;
+        let mut features: BTreeSet<Capability> = [ScreenShare, RemoteControl, Chat, FileTransfer, Datagrams].into();
+        features.extend(DECODABLE.iter().copied());
+        Self { protocol: PROTOCOL_VERSION, features }
+    }
+
+    /// Tokens to announce
+    pub fn tokens(&self) -> Vec<String> {
+        std::iter::once(format!("{}{}", VERSION_PREFIX, self.protocol))
+            .chain(self.features.iter().map(|c| c.name().to_string()))
+            .collect()
+    }
+
+    /// Read announced tokens; unknown ones are skipped
+    pub fn parse(tokens: &[String]) -> Self {
+        let protocol = tokens
+            .iter()
+            .find_map(|t| t.strip_prefix(VERSION_PREFIX)?.parse().ok())
+            .unwrap_or(1);
+        let features = tokens.iter().filter_map(|t| Capability::from_name(t)).collect();
+        Self { protocol, features }
+    }
+
+    /// What both sides can do
+    pub fn negotiate(&self, theirs: &Capabilities) -> Self {
+        Self {
+            protocol: self.protocol.min(theirs.protocol),
+            features: self.features.intersection(&theirs.features).copied().collect(),
+        }
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        self.features.contains(&capability)
+    }
+}
+
+/// Negotiated with each peer, by peer IP
+static NEGOTIATED: Lazy<RwLock<HashMap<String, Capabilities>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Negotiate with a peer from the tokens of its handshake or answer; fails
+/// with the reason to refuse it with when its protocol is too old. A newer
+/// one is fine: it talks down to ours.
+pub fn record(peer_ip: &str, tokens: &[String]) -> Result<Capabilities, &'static str> {
+    let theirs = Capabilities::parse(tokens);
+    if theirs.protocol < MIN_PROTOCOL_VERSION {
+        log::warn!("{} speaks protocol {}, too old", peer_ip, theirs.protocol);
+        return Err(UNSUPPORTED);
+    }
+    let negotiated = Capabilities::ours().negotiate(&theirs);
+    log::info!("Negotiated protocol {} with {}: {:?}", negotiated.protocol, peer_ip, negotiated.features);
+    NEGOTIATED.write().insert(peer_ip.to_string(), negotiated.clone());
+    Ok(negotiated)
+}
+
+/// What we and `peer_ip` can both do: as negotiated at the handshake, else
+/// from its mDNS record, else everything we can (the peer will say no)
+pub fn with_peer(peer_ip: &str) -> Capabilities {
+    if let Some(negotiated) = NEGOTIATED.read().get(peer_ip) {
+        return negotiated.clone();
+    }
+    super::discovery::get_devices()
+        .into_iter()
+        .find(|d| d.ip == peer_ip && !d.capabilities.is_empty())
+        .map(|d| Capabilities::ours().negotiate(&Capabilities::parse(&d.capabilities)))
+        .unwrap_or_else(Capabilities::ours)
+}
+
+/// Whether `peer_ip` can take part in a feature
+pub fn peer_supports(peer_ip: &str, capability: Capability) -> bool {
+    with_peer(peer_ip).has(capability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tokens_round_trip() {
+        let ours = Capabilities::ours();
+        assert_eq!(Capabilities::parse(&ours.tokens()), ours);
+        assert!(ours.tokens().contains(&format!("protocol/{}", PROTOCOL_VERSION)));
+    }
+
+    #[test]
+    fn test_unversioned_peer_is_turned_away() {
+        let legacy = tokens(&["screen-share", "remote-control", "chat", "file-transfer"]);
+        assert_eq!(Capabilities::parse(&legacy).protocol, 1);
+        assert_eq!(record("192.0.2.1", &legacy), Err(UNSUPPORTED));
+        // An answer that came without any
+        assert_eq!(record("192.0.2.1", &[]), Err(UNSUPPORTED));
+        assert!(!NEGOTIATED.read().contains_key("192.0.2.1"));
+    }
+
+    #[test]
+    fn test_negotiation_keeps_common_features() {
+        let theirs = Capabilities::parse(&tokens(&["protocol/3", "codec-av1", "audio", "hologram"]));
+        assert_eq!(theirs.protocol, 3);
+        // Unknown tokens are skipped, nothing is implied past protocol 1
+        assert_eq!(theirs.features, [Capability::Av1, Capability::Audio].into());
+
+        let ours = Capabilities::ours();
+        let negotiated = ours.negotiate(&theirs);
+        assert_eq!(negotiated.protocol, PROTOCOL_VERSION);
+        // AV1 only where dav1d opens
+        assert_eq!(negotiated.features.contains(&Capability::Av1), ours.has(Capability::Av1));
+        assert!(!negotiated.has(Capability::Audio));
+    }
+}
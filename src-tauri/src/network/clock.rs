@@ -0,0 +1,155 @@
+//! Clock offset between peers
+//!
+//! Frames carry the sharer's wall-clock capture time, which only means
+//! something on the viewer once the two clocks are lined up. Each probe is
+//! a Heartbeat round trip: we stamp it with our time t0, the peer answers
+//! with its own time t1, and the answer arrives at t2. Assuming a symmetric
+//! path the peer's clock is ahead of ours by t1 - (t0 + t2) / 2, wrong by
+//! at most half the round trip, so the recent sample with the shortest
+//! round trip is the one trusted.
+
+use super::protocol::{self, Message};
+use super::quic;
+use super::NetworkError;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Probes remembered per peer
+const SAMPLES: usize = 8;
+
+/// How often a watched sharer is probed
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to wait for a probe's answer
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How far a peer's clock is ahead of ours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ClockEstimate {
+    /// Peer time minus local time
+    pub offset_ms: i64,
+    /// Round trip of the probe the estimate comes from
+    pub rtt_ms: u64,
+}
+
+/// Recent probe results for one peer
+#[derive(Debug, Default)]
+struct ClockSync {
+    samples: VecDeque<ClockEstimate>,
+}
+
+impl ClockSync {
+    /// Add a probe sent at `sent_ms` (local), answered at `peer_ms` (peer)
+    /// and received at `received_ms` (local)
+    fn add(&mut self, sent_ms: u64, peer_ms: u64, received_ms: u64) -> Option<ClockEstimate> {
+        let rtt_ms = received_ms.checked_sub(sent_ms)?;
+        let midpoint = sent_ms + rtt_ms / 2;
+        let sample = ClockEstimate { offset_ms: peer_ms as i64 - midpoint as i64, rtt_ms };
+        self.samples.push_back(sample);
+        if self.samples.len() > SAMPLES {
+            self.samples.pop_front();
+        }
+        self.estimate()
+    }
+
+    fn estimate(&self) -> Option<ClockEstimate> {
+        self.samples.iter().min_by_key(|s| s.rtt_ms).copied()
+    }
+}
+
+/// Estimates per peer IP
+static CLOCKS: once_cell::sync::Lazy<RwLock<HashMap<String, ClockSync>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Current offset estimate for a peer, once it has been probed
+pub fn estimate(peer_ip: &str) -> Option<ClockEstimate> {
+    CLOCKS.read().get(peer_ip).and_then(ClockSync::estimate)
+}
+
+/// Convert a peer timestamp (ms since the Unix epoch) to our clock
+pub fn to_local_ms(peer_ip: &str, peer_ms: u64) -> Option<u64> {
+    estimate(peer_ip).map(|e| (peer_ms as i64 - e.offset_ms).max(0) as u64)
+}
+
+/// Forget a peer's estimate, e.g. when it disconnects
+pub fn forget(peer_ip: &str) {
+    CLOCKS.write().remove(peer_ip);
+}
+
+/// Measure the clock offset to a peer with one Heartbeat round trip
+pub async fn probe(peer_ip: &str) -> Result<ClockEstimate, NetworkError> {
+    let conn = quic::find_connection(peer_ip)
+        .ok_or_else(|| NetworkError::ConnectionFailed(format!("Peer not found: {}", peer_ip)))?;
+    let mut stream = tokio::time::timeout(PROBE_TIMEOUT, conn.open_bi_stream())
+        .await
+        .map_err(|_| NetworkError::ConnectionFailed("Clock probe timed out".to_string()))??;
+
+    let sent_ms = now_ms();
+    stream.send_framed(&protocol::encode(&Message::Heartbeat { timestamp: sent_ms })?).await?;
+    let response = tokio::time::timeout(PROBE_TIMEOUT, stream.recv_framed())
+        .await
+        .map_err(|_| NetworkError::ConnectionFailed("Clock probe timed out".to_string()))??;
+    let received_ms = now_ms();
+
+    let Message::HeartbeatAck { timestamp: peer_ms, .. } = protocol::decode(&response)? else {
+        return Err(NetworkError::ProtocolError("Expected HeartbeatAck".to_string()));
+    };
+    CLOCKS
+        .write()
+        .entry(peer_ip.to_string())
+        .or_default()
+        .add(sent_ms, peer_ms, received_ms)
+        .ok_or_else(|| NetworkError::ProtocolError("Local clock went backwards".to_string()))
+}
+
+/// Keep probing a peer until the task is aborted
+pub async fn sync_loop(peer_ip: String) {
+    loop {
+        match probe(&peer_ip).await {
+            Ok(e) => log::trace!("Clock offset to {}: {}ms (rtt {}ms)", peer_ip, e.offset_ms, e.rtt_ms),
+            Err(e) => log::debug!("Clock probe to {} failed: {}", peer_ip, e),
+        }
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_from_round_trip() {
+        let mut sync = ClockSync::default();
+        // Peer is 500ms ahead, 10ms each way
+        let e = sync.add(1_000, 1_510, 1_020).unwrap();
+        assert_eq!(e, ClockEstimate { offset_ms: 500, rtt_ms: 20 });
+        // Peer behind us
+        let mut sync = ClockSync::default();
+        assert_eq!(sync.add(1_000, 700, 1_000).unwrap().offset_ms, -300);
+        // Local clock stepped backwards mid-probe
+        assert!(sync.add(1_000, 700, 900).is_none());
+    }
+
+    #[test]
+    fn test_shortest_round_trip_wins() {
+        let mut sync = ClockSync::default();
+        sync.add(0, 150, 200); // queued on the way there: looks 50ms off
+        sync.add(1_000, 1_005, 1_010);
+        sync.add(2_000, 2_080, 2_100);
+        assert_eq!(sync.estimate(), Some(ClockEstimate { offset_ms: 0, rtt_ms: 10 }));
+
+        for i in 0..SAMPLES as u64 {
+            sync.add(10_000 + i * 1_000, 10_000 + i * 1_000 + 40, 10_000 + i * 1_000 + 60);
+        }
+        assert_eq!(sync.estimate(), Some(ClockEstimate { offset_ms: 10, rtt_ms: 60 }));
+    }
+}
@@ -0,0 +1,151 @@
+//! Device list search and ordering
+//!
+//! The device list can grow to hundreds of entries on a busy network, so
+//! filtering and sorting happen here rather than in the UI. A query matches
+//! when its letters appear in order in the device name, its IP, or, for
+//! Chinese names, the pinyin spelling or initials ("zs" and "zhangsan" both
+//! find 张三). Results list favorites first, then reachable devices, then by
+//! name, with Chinese names sorted by their pinyin.
+
+use super::discovery::{DeviceStatus, DiscoveredDevice};
+use pinyin::ToPinyin;
+use serde::Serialize;
+
+/// A device in search results
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceEntry {
+    #[serde(flatten)]
+    pub device: DiscoveredDevice,
+    pub favorite: bool,
+}
+
+/// Devices matching `query`, in display order
+pub fn search(devices: Vec<DiscoveredDevice>, query: &str, favorites: &[String]) -> Vec<DeviceEntry> {
+    let query: String = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+    let mut entries: Vec<(SortKey, DeviceEntry)> = devices
+        .into_iter()
+        .filter(|d| query.is_empty() || matches(d, &query))
+        .map(|device| {
+            let favorite = favorites.contains(&device.id);
+            (SortKey::new(&device, favorite), DeviceEntry { device, favorite })
+        })
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn matches(device: &DiscoveredDevice, query: &str) -> bool {
+    let names = std::iter::once(&device.name).chain(device.nickname.as_ref());
+    names.into_iter().any(|name| {
+        let (spelled, initials) = transliterate(name);
+        [name.to_lowercase(), spelled, initials].iter().any(|key| is_subsequence(query, key))
+    }) || is_subsequence(query, &device.ip)
+}
+
+/// Whether the characters of `needle` appear in `haystack` in order
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut rest = haystack.chars();
+    needle.chars().all(|c| rest.any(|h| h == c))
+}
+
+/// `name` lowercased with Chinese characters spelled out in pinyin, and the
+/// same with each Chinese character reduced to its initial. Spaces are
+/// dropped so queries can skip them.
+fn transliterate(name: &str) -> (String, String) {
+    let mut spelled = String::new();
+    let mut initials = String::new();
+    for c in name.chars().filter(|c| !c.is_whitespace()) {
+        match c.to_pinyin() {
+            Some(p) => {
+                spelled.push_str(p.plain());
+                initials.push_str(p.first_letter());
+            }
+            None => {
+                spelled.extend(c.to_lowercase());
+                initials.extend(c.to_lowercase());
+            }
+        }
+    }
+    (spelled, initials)
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    not_favorite: bool,
+    availability: u8,
+    name: String,
+    /// Keeps devices with the same name in a stable order
+    id: String,
+}
+
+impl SortKey {
+    fn new(device: &DiscoveredDevice, favorite: bool) -> Self {
+        let availability = match device.status {
+            DeviceStatus::Online | DeviceStatus::Busy => 0,
+            DeviceStatus::Seen => 1,
+            DeviceStatus::Offline => 2,
+        };
+        Self {
+            not_favorite: !favorite,
+            availability,
+            // Listed under the nickname the user gave it, if any
+            name: transliterate(device.nickname.as_ref().unwrap_or(&device.name)).0,
+            id: device.id.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str, status: DeviceStatus) -> DiscoveredDevice {
+        DiscoveredDevice {
+            id: id.to_string(),
+            name: name.to_string(),
+            ip: format!("192.168.1.{}", id.len()),
+            port: 19876,
+            status,
+            last_seen: 0,
+            is_sharing: false,
+            avatar: None,
+            version: None,
+            platform: None,
+            capabilities: Vec::new(),
+            incompatible: false,
+            nickname: None,
+        }
+    }
+
+    fn names(entries: &[DeviceEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.device.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_pinyin_and_fuzzy_match() {
+        let devices = vec![
+            device("a", "张三的电脑", DeviceStatus::Online),
+            device("b", "Meeting Room Mac", DeviceStatus::Online),
+            device("c", "李四", DeviceStatus::Online),
+        ];
+        assert_eq!(names(&search(devices.clone(), "zhangsan", &[])), ["张三的电脑"]);
+        assert_eq!(names(&search(devices.clone(), "zs", &[])), ["张三的电脑"]);
+        assert_eq!(names(&search(devices.clone(), "张三", &[])), ["张三的电脑"]);
+        assert_eq!(names(&search(devices.clone(), "mrm", &[])), ["Meeting Room Mac"]);
+        assert_eq!(names(&search(devices, "room mac", &[])), ["Meeting Room Mac"]);
+    }
+
+    #[test]
+    fn test_order_favorites_online_name() {
+        let devices = vec![
+            device("1", "Zed", DeviceStatus::Online),
+            device("2", "李四", DeviceStatus::Online),
+            device("3", "alpha", DeviceStatus::Offline),
+            device("4", "Bob", DeviceStatus::Seen),
+            device("5", "Yan", DeviceStatus::Offline),
+        ];
+        let entries = search(devices, "", &["5".to_string()]);
+        assert_eq!(names(&entries), ["Yan", "李四", "Zed", "Bob", "alpha"]);
+        assert!(entries[0].favorite && !entries[1].favorite);
+    }
+}
@@ -1,18 +1,63 @@
 //! mDNS service discovery
 //! Automatically find other LAN Meeting instances on the network
+//!
+//! By default mdns-sd re-sends browse queries on its own backoff schedule.
+//! A fixed query interval replaces that schedule with a re-query every N
+//! seconds. Passive mode sends no queries at all: the service is still
+//! advertised and answers peers' queries, and devices appear when they
+//! connect to us or on a manual refresh.
+//!
+//! Once the network is steady (several re-queries in a row found no device
+//! come or go) the fixed interval is stretched, up to `MAX_STEADY_INTERVAL`,
+//! to cut multicast traffic on large LANs; any change snaps it back. When
+//! the service stops or the app exits, our record is withdrawn with goodbye
+//! packets so peers drop us right away instead of when the record expires.
 
 use super::NetworkError;
+use crate::app_state::Slice;
 use mdns_sd::{ResolvedService, ServiceDaemon, ServiceEvent, ServiceInfo};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
 
 const SERVICE_TYPE: &str = "_lan-meeting._udp.local.";
 const SERVICE_PORT: u16 = 19876;
 
+/// How long a manual refresh listens for answers in passive mode
+const PASSIVE_LISTEN: Duration = Duration::from_secs(5);
+
+/// How long a reachability probe waits for the QUIC handshake
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Re-queries without a device coming or going before the interval grows
+const STEADY_AFTER_QUERIES: u32 = 3;
+
+/// Longest the query interval is stretched to on a steady network
+const MAX_STEADY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How long withdrawing waits for the goodbye packets to go out
+const GOODBYE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Query settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiscoverySettings {
+    /// Re-query every this many seconds (0 = mdns-sd's own backoff)
+    pub query_interval_secs: u32,
+    /// Never query except on a manual refresh
+    pub passive: bool,
+}
+
+impl DiscoverySettings {
+    fn query_interval(&self) -> Option<Duration> {
+        (self.query_interval_secs > 0).then(|| Duration::from_secs(self.query_interval_secs as u64))
+    }
+}
+
 /// Discovered device information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveredDevice {
@@ -24,11 +69,39 @@ pub struct DiscoveredDevice {
     pub last_seen: u64,
     #[serde(default)]
     pub is_sharing: bool,
+    /// Avatar PNG, base64 encoded, as the device sent it in a handshake
+    #[serde(default)]
+    pub avatar: Option<String>,
+    /// App version it announced
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Operating system it runs on ("windows", "macos", "linux")
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Its version cannot talk to ours
+    #[serde(default)]
+    pub incompatible: bool,
+    /// Name the user gave it (see `known`)
+    #[serde(default)]
+    pub nickname: Option<String>,
+}
+
+impl DiscoveredDevice {
+    /// Record the version and capabilities a device announced
+    pub fn set_version(&mut self, version: &str, capabilities: Vec<String>) {
+        self.incompatible = !super::protocol::is_compatible_version(version);
+        self.version = Some(version.to_string());
+        self.capabilities = capabilities;
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DeviceStatus {
+    /// Announced over mDNS but its service has not answered a probe yet
+    Seen,
     Online,
     Busy,
     Offline,
@@ -39,27 +112,66 @@ pub static DEVICES: once_cell::sync::Lazy<Arc<RwLock<HashMap<String, DiscoveredD
     once_cell::sync::Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
 
 /// Our own device ID
-static OUR_DEVICE_ID: once_cell::sync::Lazy<String> =
-    once_cell::sync::Lazy::new(|| uuid::Uuid::new_v4().to_string());
-
-/// mDNS service daemon handle
-static MDNS_DAEMON: once_cell::sync::Lazy<Option<ServiceDaemon>> =
-    once_cell::sync::Lazy::new(|| match ServiceDaemon::new() {
-        Ok(daemon) => {
-            log::info!("mDNS daemon created successfully");
-            Some(daemon)
-        }
-        Err(e) => {
-            log::error!("Failed to create mDNS daemon: {}", e);
-            None
-        }
-    });
+static OUR_DEVICE_ID: once_cell::sync::Lazy<String> = once_cell::sync::Lazy::new(load_device_id);
+
+/// mDNS service daemon handle, from discovery start until `shutdown`
+static MDNS_DAEMON: parking_lot::Mutex<Option<ServiceDaemon>> = parking_lot::Mutex::new(None);
+
+/// Set once the query loop is running
+static DISCOVERY_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Bumped for every query loop started, so one left over from before a
+/// quick stop and start knows to exit
+static QUERY_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A manual refresh is waiting for the query loop
+static REFRESH_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the query loop after a refresh request or a settings change
+static WAKE: once_cell::sync::Lazy<Notify> = once_cell::sync::Lazy::new(Notify::new);
+
+/// A device appeared or went away since the query loop last looked
+static DEVICES_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Devices with a reachability probe in flight
+static PROBING: once_cell::sync::Lazy<parking_lot::Mutex<std::collections::HashSet<String>>> =
+    once_cell::sync::Lazy::new(|| parking_lot::Mutex::new(std::collections::HashSet::new()));
+
+/// Sharing state our mDNS record last announced
+static PUBLISHED_SHARING: AtomicBool = AtomicBool::new(false);
+
+/// Full name of our registered mDNS service
+static REGISTERED: once_cell::sync::Lazy<parking_lot::Mutex<Option<String>>> =
+    once_cell::sync::Lazy::new(|| parking_lot::Mutex::new(None));
 
 /// Get our device ID
 pub fn get_our_device_id() -> &'static str {
     &OUR_DEVICE_ID
 }
 
+/// Our device ID, kept on disk so peers know us again after a restart
+fn load_device_id() -> String {
+    let path = dirs::config_dir().map(|p| p.join("lan-meeting").join("device_id"));
+    let stored = path
+        .as_ref()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| uuid::Uuid::parse_str(id).is_ok());
+    if let Some(id) = stored {
+        return id;
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    if let Some(path) = path {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        if let Err(e) = std::fs::write(&path, &id) {
+            log::warn!("Failed to save device ID to {}: {}", path.display(), e);
+        }
+    }
+    id
+}
+
 /// Get current timestamp in milliseconds
 fn now_ms() -> u64 {
     SystemTime::now()
@@ -68,28 +180,187 @@ fn now_ms() -> u64 {
         .unwrap_or(0)
 }
 
+/// The running mDNS daemon, created on first use after a shutdown
+fn daemon() -> Result<ServiceDaemon, NetworkError> {
+    let mut slot = MDNS_DAEMON.lock();
+    if let Some(daemon) = slot.as_ref() {
+        return Ok(daemon.clone());
+    }
+    let daemon = ServiceDaemon::new()
+        .map_err(|e| NetworkError::DiscoveryError(format!("Failed to create mDNS daemon: {}", e)))?;
+    log::info!("mDNS daemon created successfully");
+    *slot = Some(daemon.clone());
+    Ok(daemon)
+}
+
 /// Start mDNS discovery and service advertisement
 pub async fn start_discovery(app: AppHandle) -> Result<(), NetworkError> {
     log::info!("Starting mDNS discovery on {}", SERVICE_TYPE);
 
-    let daemon = MDNS_DAEMON
-        .as_ref()
-        .ok_or_else(|| NetworkError::DiscoveryError("Failed to create mDNS daemon".to_string()))?;
+    let daemon = daemon()?;
 
     // Register our service
-    register_service(daemon)?;
+    register_service(&daemon)?;
 
-    // Start browsing for other services
-    browse_services(daemon, app)?;
+    // Browse for other services on the configured schedule
+    if !DISCOVERY_RUNNING.swap(true, Ordering::SeqCst) {
+        let generation = QUERY_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::spawn(query_loop(daemon, app, generation));
+    }
 
     Ok(())
 }
 
+/// Query immediately, even in passive mode
+pub fn refresh_now() -> Result<(), NetworkError> {
+    if !DISCOVERY_RUNNING.load(Ordering::SeqCst) {
+        return Err(NetworkError::DiscoveryError("Discovery not running".to_string()));
+    }
+    REFRESH_REQUESTED.store(true, Ordering::SeqCst);
+    WAKE.notify_one();
+    Ok(())
+}
+
+/// Apply changed discovery settings
+pub fn settings_changed() {
+    WAKE.notify_one();
+}
+
+/// What the query loop does with the browse next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BrowseAction {
+    Keep,
+    /// Start browsing, or restart it to send a fresh query
+    Query,
+    Stop,
+}
+
+/// Decides when to (re-)query
+#[derive(Debug, Default)]
+struct QuerySchedule {
+    browsing: bool,
+    interval: Option<Duration>,
+    next_query: Option<Instant>,
+    /// Passive mode: stop browsing after a manual refresh
+    listen_until: Option<Instant>,
+    /// Scheduled re-queries in a row that found no device come or go
+    quiet_queries: u32,
+}
+
+impl QuerySchedule {
+    fn step(&mut self, now: Instant, settings: DiscoverySettings, refresh: bool) -> BrowseAction {
+        let interval = settings.query_interval();
+        if interval != self.interval {
+            self.interval = interval;
+            self.next_query = None;
+        }
+
+        if settings.passive {
+            self.next_query = None;
+            if refresh {
+                self.browsing = true;
+                self.listen_until = Some(now + PASSIVE_LISTEN);
+                BrowseAction::Query
+            } else if self.browsing && self.listen_until.is_none_or(|t| now >= t) {
+                self.browsing = false;
+                self.listen_until = None;
+                BrowseAction::Stop
+            } else {
+                BrowseAction::Keep
+            }
+        } else {
+            self.listen_until = None;
+            let due = self.next_query.is_some_and(|t| now >= t);
+            if refresh {
+                self.quiet_queries = 0;
+            } else if due {
+                self.quiet_queries += 1;
+            }
+            let action = if !self.browsing || refresh || due {
+                self.browsing = true;
+                self.next_query = None;
+                BrowseAction::Query
+            } else {
+                BrowseAction::Keep
+            };
+            if self.next_query.is_none() {
+                self.next_query = interval.map(|i| now + self.stretched(i));
+            }
+            action
+        }
+    }
+
+    /// A device came or went: back to the configured interval
+    fn devices_changed(&mut self) {
+        if self.quiet_queries >= STEADY_AFTER_QUERIES {
+            self.next_query = None;
+        }
+        self.quiet_queries = 0;
+    }
+
+    /// `interval`, doubled for each quiet re-query past `STEADY_AFTER_QUERIES`
+    fn stretched(&self, interval: Duration) -> Duration {
+        let doublings = (self.quiet_queries + 1).saturating_sub(STEADY_AFTER_QUERIES).min(8);
+        (interval * 2u32.pow(doublings)).min(MAX_STEADY_INTERVAL.max(interval))
+    }
+
+    /// When the loop must wake up on its own
+    fn deadline(&self) -> Option<Instant> {
+        self.listen_until.or(self.next_query)
+    }
+}
+
+/// Start, restart and stop browsing as settings and refresh requests demand
+async fn query_loop(daemon: ServiceDaemon, app: AppHandle, generation: u64) {
+    let mut schedule = QuerySchedule::default();
+    loop {
+        if !DISCOVERY_RUNNING.load(Ordering::SeqCst) || QUERY_GENERATION.load(Ordering::SeqCst) != generation {
+            let _ = daemon.stop_browse(SERVICE_TYPE);
+            log::info!("mDNS discovery stopped");
+            break;
+        }
+        if DEVICES_CHANGED.swap(false, Ordering::SeqCst) {
+            schedule.devices_changed();
+        }
+        let refresh = REFRESH_REQUESTED.swap(false, Ordering::SeqCst);
+        let settings = crate::commands::discovery_settings();
+        let was_browsing = schedule.browsing;
+
+        match schedule.step(Instant::now(), settings, refresh) {
+            BrowseAction::Query => {
+                if was_browsing {
+                    // Restarting the browse sends a fresh query
+                    let _ = daemon.stop_browse(SERVICE_TYPE);
+                }
+                if let Err(e) = browse_services(&daemon, app.clone()) {
+                    log::error!("{}", e);
+                }
+            }
+            BrowseAction::Stop => {
+                log::info!("Passive discovery: no longer querying");
+                let _ = daemon.stop_browse(SERVICE_TYPE);
+            }
+            BrowseAction::Keep => {}
+        }
+
+        match schedule.deadline() {
+            Some(deadline) => {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(deadline.into()) => {}
+                    _ = WAKE.notified() => {}
+                }
+            }
+            None => WAKE.notified().await,
+        }
+    }
+}
+
 /// Register our service on the network
 fn register_service(daemon: &ServiceDaemon) -> Result<(), NetworkError> {
     let hostname = hostname::get()
         .map(|h| h.to_string_lossy().to_string())
         .unwrap_or_else(|_| "Unknown".to_string());
+    let display_name = crate::commands::display_name();
 
     let device_id = get_our_device_id();
     let instance_name = format!("{}-{}", hostname, &device_id[..8]);
@@ -103,15 +374,21 @@ fn register_service(daemon: &ServiceDaemon) -> Result<(), NetworkError> {
     // Create service info with properties
     let mut properties = HashMap::new();
     properties.insert("id".to_string(), device_id.to_string());
-    properties.insert("name".to_string(), hostname.clone());
+    properties.insert("name".to_string(), display_name);
     properties.insert("version".to_string(), env!("CARGO_PKG_VERSION").to_string());
+    properties.insert("os".to_string(), std::env::consts::OS.to_string());
+    properties.insert("caps".to_string(), super::protocol::capabilities().join(","));
+    let sharing = we_are_sharing();
+    properties.insert("sharing".to_string(), if sharing { "1" } else { "0" }.to_string());
+    PUBLISHED_SHARING.store(sharing, Ordering::SeqCst);
 
-    // Collect our real LAN IPs to register with mDNS
+    // Collect our real LAN IPs to register with mDNS: private IPv4 and
+    // unique local IPv6 (link-local needs an interface, mdns-sd adds those)
     let lan_ips: Vec<String> = if_addrs::get_if_addrs()
         .unwrap_or_default()
         .iter()
         .filter(|iface| !iface.is_loopback())
-        .filter(|iface| iface.ip().is_ipv4())
+        .filter(|iface| !matches!(iface.ip(), std::net::IpAddr::V6(v6) if super::address::is_link_local_v6(&v6)))
         .filter(|iface| crate::commands::is_real_lan_ip(&iface.ip()))
         .map(|iface| iface.ip().to_string())
         .collect();
@@ -140,14 +417,69 @@ fn register_service(daemon: &ServiceDaemon) -> Result<(), NetworkError> {
     // when network interfaces change (e.g., VPN connect/disconnect)
     .enable_addr_auto();
 
+    let fullname = service_info.get_fullname().to_string();
     daemon
         .register(service_info)
         .map_err(|e| NetworkError::DiscoveryError(format!("Failed to register service: {}", e)))?;
+    *REGISTERED.lock() = Some(fullname);
 
     log::info!("mDNS service registered successfully");
     Ok(())
 }
 
+/// Register our service again with the current name and addresses
+pub fn reregister() -> Result<(), NetworkError> {
+    let Some(daemon) = MDNS_DAEMON.lock().clone() else {
+        // Discovery not running
+        return Ok(());
+    };
+    let Some(fullname) = REGISTERED.lock().take() else {
+        // Discovery never started
+        return Ok(());
+    };
+    let _ = daemon.unregister(&fullname);
+    register_service(&daemon)
+}
+
+/// Whether we share a screen, in either streaming mode
+fn we_are_sharing() -> bool {
+    crate::commands::is_sharing() || crate::simple_streaming::is_simple_sharing()
+}
+
+/// Announce our sharing state again if it changed since it was published.
+/// Registering under the same name updates the record in place, without
+/// the goodbye that would make peers drop us for a moment.
+pub fn publish_sharing() {
+    if we_are_sharing() == PUBLISHED_SHARING.load(Ordering::SeqCst) || REGISTERED.lock().is_none() {
+        return;
+    }
+    let Some(daemon) = MDNS_DAEMON.lock().clone() else {
+        return;
+    };
+    if let Err(e) = register_service(&daemon) {
+        log::warn!("Failed to update mDNS record: {}", e);
+    }
+}
+
+/// Start over with a new daemon after we moved to another network: the
+/// old one's sockets belong to interfaces that may be gone. Does nothing
+/// while discovery is stopped.
+pub async fn restart() -> Result<(), NetworkError> {
+    let Some(app) = crate::APP_HANDLE.get().cloned() else {
+        return Ok(());
+    };
+    if MDNS_DAEMON.lock().is_none() {
+        return Ok(());
+    }
+    tokio::task::spawn_blocking(|| {
+        withdraw();
+        shutdown();
+    })
+    .await
+    .map_err(|e| NetworkError::DiscoveryError(e.to_string()))?;
+    start_discovery(app).await
+}
+
 /// Browse for other services on the network
 fn browse_services(daemon: &ServiceDaemon, app: AppHandle) -> Result<(), NetworkError> {
     log::info!("Browsing for LAN Meeting services...");
@@ -164,7 +496,8 @@ fn browse_services(daemon: &ServiceDaemon, app: AppHandle) -> Result<(), Network
                     handle_service_event(event, &app);
                 }
                 Err(e) => {
-                    log::error!("mDNS browse error: {}", e);
+                    // Also reached when the browse is restarted or stopped
+                    log::debug!("mDNS browse ended: {}", e);
                     break;
                 }
             }
@@ -190,12 +523,33 @@ fn handle_service_event(event: ServiceEvent, app: &AppHandle) {
             log::debug!("mDNS resolved addresses: {:?}", all_addrs);
 
             let device = extract_device_info(&info);
-            if let Some(device) = device {
+            if let Some(mut device) = device {
+                if super::known::is_blocked(Some(&device.id), None) {
+                    log::debug!("Ignoring blocked device {} ({})", device.name, device.ip);
+                    return;
+                }
                 log::info!("Discovered device: {} ({})", device.name, device.ip);
+
+                // A re-announcement keeps a verified status at the same
+                // address; the record's sharing state is the newer one
+                match DEVICES.read().get(&device.id) {
+                    Some(existing) => {
+                        if existing.ip == device.ip && existing.status != DeviceStatus::Offline {
+                            device.status = existing.status;
+                        }
+                        device.avatar = existing.avatar.clone();
+                    }
+                    None => DEVICES_CHANGED.store(true, Ordering::SeqCst),
+                }
                 add_device(device.clone());
 
                 // Notify frontend
                 let _ = app.emit("device-discovered", &device);
+
+                if device.status == DeviceStatus::Seen {
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(verify_device(device, app));
+                }
             }
         }
         ServiceEvent::ServiceRemoved(_type, fullname) => {
@@ -203,6 +557,7 @@ fn handle_service_event(event: ServiceEvent, app: &AppHandle) {
             if let Some(device) = find_device_by_fullname(&fullname) {
                 log::info!("Device removed: {} ({})", device.name, device.ip);
                 remove_device(&device.id);
+                DEVICES_CHANGED.store(true, Ordering::SeqCst);
 
                 // Notify frontend
                 let _ = app.emit("device-removed", &device.id);
@@ -218,6 +573,59 @@ fn handle_service_event(event: ServiceEvent, app: &AppHandle) {
     }
 }
 
+/// Probe a discovered device's QUIC service and list it as Online only if
+/// it answers. Unreachable devices stay Seen.
+async fn verify_device(device: DiscoveredDevice, app: AppHandle) {
+    if !PROBING.lock().insert(device.id.clone()) {
+        return;
+    }
+
+    let reachable = if super::quic::find_connection(&device.ip).is_some() {
+        // Already connected: the service is clearly alive
+        true
+    } else if let Some(endpoint) = crate::get_quic_endpoint() {
+        match super::address::socket_addr(&device.ip, device.port) {
+            Some(addr) => match endpoint.probe(addr, PROBE_TIMEOUT).await {
+                Ok(elapsed) => {
+                    log::debug!("Probe of {} ({}) answered in {:?}", device.name, device.ip, elapsed);
+                    true
+                }
+                Err(e) => {
+                    log::info!("Device {} ({}) not reachable: {}", device.name, device.ip, e);
+                    false
+                }
+            },
+            None => false,
+        }
+    } else {
+        log::debug!("QUIC endpoint not ready, cannot verify {}", device.ip);
+        false
+    };
+
+    PROBING.lock().remove(&device.id);
+    if !reachable {
+        return;
+    }
+
+    let updated = {
+        let mut devices = DEVICES.write();
+        match devices.get_mut(&device.id) {
+            // Moved or removed while probing
+            Some(current) if current.ip == device.ip && current.status == DeviceStatus::Seen => {
+                current.status = DeviceStatus::Online;
+                current.last_seen = now_ms();
+                Some(current.clone())
+            }
+            _ => None,
+        }
+    };
+    if let Some(updated) = updated {
+        super::known::remember(&updated);
+        crate::app_state::changed(Slice::Devices);
+        let _ = app.emit("device-discovered", &updated);
+    }
+}
+
 /// Extract device info from ResolvedService
 fn extract_device_info(info: &ResolvedService) -> Option<DiscoveredDevice> {
     let id = info.txt_properties.get("id")?.val_str().to_string();
@@ -227,34 +635,82 @@ fn extract_device_info(info: &ResolvedService) -> Option<DiscoveredDevice> {
         .map(|prop| prop.val_str().to_string())
         .unwrap_or_else(|| "Unknown".to_string());
 
-    // Collect all IPv4 addresses from the resolved service
-    let ipv4_addrs: Vec<std::net::IpAddr> = info
+    // Collect all addresses from the resolved service
+    let addrs: Vec<std::net::IpAddr> = info
         .addresses
         .iter()
         .map(|scoped_ip| scoped_ip.to_ip_addr())
-        .filter(|ip| ip.is_ipv4() && !ip.is_loopback())
+        .filter(|ip| !ip.is_loopback())
         .collect();
 
-    // Priority: 1) same subnet as us, 2) real LAN IP, 3) any IPv4
-    let our_subnets = crate::commands::get_local_subnets();
-    let ip = ipv4_addrs
-        .iter()
-        .find(|ip| crate::commands::is_same_subnet(ip, &our_subnets))
-        .or_else(|| ipv4_addrs.iter().find(|ip| crate::commands::is_real_lan_ip(ip)))
-        .or_else(|| ipv4_addrs.first())
-        .map(|ip| ip.to_string())?;
+    let ip = pick_resolved(&addrs, &crate::commands::get_local_subnets(), link_local_scope())?;
 
     let port = info.port;
+    let txt = |key: &str| info.txt_properties.get(key).map(|prop| prop.val_str().to_string());
 
-    Some(DiscoveredDevice {
+    let mut device = DiscoveredDevice {
         id,
         name,
         ip,
         port,
-        status: DeviceStatus::Online,
+        status: DeviceStatus::Seen,
         last_seen: now_ms(),
-        is_sharing: false,
-    })
+        is_sharing: txt("sharing").as_deref() == Some("1"),
+        avatar: None,
+        version: None,
+        platform: txt("os").filter(|os| !os.is_empty()),
+        capabilities: Vec::new(),
+        incompatible: false,
+        nickname: None,
+    };
+    // Versions before these records announced no capabilities
+    if let Some(version) = txt("version") {
+        let caps = txt("caps").unwrap_or_default();
+        device.set_version(&version, caps.split(',').filter(|c| !c.is_empty()).map(str::to_string).collect());
+    }
+    Some(device)
+}
+
+/// Which address of a resolved service to dial. Priority: 1) IPv4 on our
+/// subnet, 2) real LAN IPv4, 3) unique local IPv6, 4) any IPv4, 5) other
+/// routable IPv6, 6) link-local IPv6 on interface `scope`
+fn pick_resolved(addrs: &[std::net::IpAddr], subnets: &[(u32, u32)], scope: Option<u32>) -> Option<String> {
+    use crate::commands::{is_real_lan_ip, is_same_subnet};
+    use std::net::IpAddr;
+
+    let rank = |ip: &IpAddr| match ip {
+        IpAddr::V4(_) if is_same_subnet(ip, subnets) => Some(0),
+        IpAddr::V4(_) if is_real_lan_ip(ip) => Some(1),
+        IpAddr::V4(_) => Some(3),
+        IpAddr::V6(v6) if super::address::is_link_local_v6(v6) => scope.map(|_| 5),
+        IpAddr::V6(_) if is_real_lan_ip(ip) => Some(2),
+        IpAddr::V6(_) => Some(4),
+    };
+    let ip = addrs.iter().filter_map(|ip| Some((rank(ip)?, *ip))).min_by_key(|(rank, _)| *rank)?.1;
+    let addr = match (ip, scope) {
+        (IpAddr::V6(v6), Some(scope)) => std::net::SocketAddr::V6(std::net::SocketAddrV6::new(v6, 0, 0, scope)),
+        _ => std::net::SocketAddr::new(ip, 0),
+    };
+    Some(super::address::peer_ip(addr))
+}
+
+/// The interface a link-local peer is on. mDNS answers do not say which
+/// interface they came in on, so this is the one interface with a
+/// link-local IPv6 address; with several there is no telling.
+fn link_local_scope() -> Option<u32> {
+    let mut scopes: Vec<u32> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter(|iface| matches!(iface.ip(), std::net::IpAddr::V6(v6) if super::address::is_link_local_v6(&v6)))
+        .filter_map(|iface| iface.index)
+        .collect();
+    scopes.sort();
+    scopes.dedup();
+    match scopes[..] {
+        [scope] => Some(scope),
+        _ => None,
+    }
 }
 
 /// Find device by mDNS fullname
@@ -264,35 +720,74 @@ fn find_device_by_fullname(fullname: &str) -> Option<DiscoveredDevice> {
     devices.values().find(|d| fullname.contains(&d.id[..8])).cloned()
 }
 
-/// Get all discovered devices
+/// Get all discovered devices, with the nicknames the user gave them
 pub fn get_devices() -> Vec<DiscoveredDevice> {
-    DEVICES.read().values().cloned().collect()
+    let mut devices: Vec<DiscoveredDevice> = DEVICES.read().values().cloned().collect();
+    for device in &mut devices {
+        device.nickname = super::known::nickname(&device.id);
+    }
+    devices
 }
 
 /// Add or update a device
 pub fn add_device(device: DiscoveredDevice) {
+    super::known::remember(&device);
     let mut devices = DEVICES.write();
     devices.insert(device.id.clone(), device);
+    crate::app_state::changed(Slice::Devices);
 }
 
 /// Remove a device
 pub fn remove_device(id: &str) {
     let mut devices = DEVICES.write();
     devices.remove(id);
+    crate::app_state::changed(Slice::Devices);
 }
 
 /// Clear all devices
 pub fn clear_devices() {
     let mut devices = DEVICES.write();
     devices.clear();
+    crate::app_state::changed(Slice::Devices);
 }
 
 /// Update device status
 pub fn update_device_status(id: &str, status: DeviceStatus) {
-    let mut devices = DEVICES.write();
-    if let Some(device) = devices.get_mut(id) {
+    let updated = {
+        let mut devices = DEVICES.write();
+        let Some(device) = devices.get_mut(id) else {
+            return;
+        };
         device.status = status;
         device.last_seen = now_ms();
+        device.clone()
+    };
+    crate::app_state::changed(Slice::Devices);
+    super::known::remember(&updated);
+}
+
+/// Set a device's status as its heartbeats found it, leaving `last_seen`
+/// at the last time it answered. Returns false if it is gone.
+pub fn set_device_liveness(id: &str, status: DeviceStatus) -> bool {
+    let mut devices = DEVICES.write();
+    let Some(device) = devices.get_mut(id) else {
+        return false;
+    };
+    device.status = status;
+    if status != DeviceStatus::Offline {
+        device.last_seen = now_ms();
+    }
+    crate::app_state::changed(Slice::Devices);
+    true
+}
+
+/// Set the name and avatar a device introduced itself with
+pub fn set_device_profile(id: &str, name: &str, avatar: Option<String>) {
+    let mut devices = DEVICES.write();
+    if let Some(device) = devices.get_mut(id) {
+        device.name = name.to_string();
+        device.avatar = avatar;
+        crate::app_state::changed(Slice::Devices);
     }
 }
 
@@ -302,6 +797,7 @@ pub fn update_device_sharing(id: &str, is_sharing: bool) {
     if let Some(device) = devices.get_mut(id) {
         device.is_sharing = is_sharing;
         device.last_seen = now_ms();
+        crate::app_state::changed(Slice::Devices);
     }
 }
 
@@ -312,106 +808,114 @@ pub fn update_device_sharing_by_ip(ip: &str, is_sharing: bool) -> Option<String>
         if device.ip == ip {
             device.is_sharing = is_sharing;
             device.last_seen = now_ms();
+            crate::app_state::changed(Slice::Devices);
             return Some(device.id.clone());
         }
     }
     None
 }
 
+/// Which of a peer's announced addresses to dial: the one its message came
+/// from if listed, else one on our subnets, else IPv4 before IPv6
+fn pick_address(addresses: &[String], observed: &str, subnets: &[(u32, u32)]) -> Option<String> {
+    if addresses.iter().any(|a| a == observed) {
+        return Some(observed.to_string());
+    }
+    let parsed: Vec<std::net::IpAddr> = addresses.iter().filter_map(|a| a.parse().ok()).collect();
+    parsed
+        .iter()
+        .find(|ip| crate::commands::is_same_subnet(ip, subnets))
+        .or_else(|| parsed.iter().find(|ip| ip.is_ipv4()))
+        .or_else(|| parsed.first())
+        .map(|ip| ip.to_string())
+}
+
+/// A peer announced new addresses. Returns the device with its updated
+/// address and the address it had before, if it changed.
+pub fn update_device_address(
+    device_id: &str,
+    addresses: &[String],
+    port: u16,
+    observed: &str,
+) -> Option<(String, DiscoveredDevice)> {
+    let ip = pick_address(addresses, observed, &crate::commands::get_local_subnets())?;
+    let mut devices = DEVICES.write();
+    let device = devices.get_mut(device_id)?;
+    if device.ip == ip && device.port == port {
+        device.last_seen = now_ms();
+        return None;
+    }
+    let old_ip = std::mem::replace(&mut device.ip, ip);
+    device.port = port;
+    device.last_seen = now_ms();
+    let device = device.clone();
+    drop(devices);
+    crate::app_state::changed(Slice::Devices);
+    super::known::remember(&device);
+    Some((old_ip, device))
+}
+
 /// Manually add a device by IP address
 /// This will attempt to connect and exchange handshake to verify the device
 pub async fn add_manual_device(ip: String, port: u16) -> Result<DiscoveredDevice, NetworkError> {
     use super::protocol;
-    use std::net::SocketAddr;
+    use super::ConnectErrorKind;
     use std::time::Duration;
 
-    let addr: SocketAddr = format!("{}:{}", ip, port)
-        .parse()
-        .map_err(|e| NetworkError::ConnectionFailed(format!("Invalid address: {}", e)))?;
+    let addr = super::address::socket_addr(ip.trim(), port)
+        .ok_or_else(|| NetworkError::Connect(ConnectErrorKind::InvalidAddress, format!("Invalid address: {}", ip)))?;
 
     // Try to connect with a timeout to verify the device is reachable
     let endpoint = crate::get_quic_endpoint()
-        .ok_or_else(|| NetworkError::ConnectionFailed("请先开启服务".to_string()))?;
-
-    // Attempt connection with timeout
-    let connect_future = endpoint.connect(addr);
-    let timeout_duration = Duration::from_secs(5);
-
-    let conn = match tokio::time::timeout(timeout_duration, connect_future).await {
-        Ok(Ok(conn)) => conn,
-        Ok(Err(e)) => {
-            log::warn!("Failed to connect to manual device {}: {}", ip, e);
-            return Err(NetworkError::ConnectionFailed(format!(
-                "连接失败: 对方可能未开启服务"
-            )));
-        }
-        Err(_) => {
-            log::warn!("Connection timeout to manual device {}", ip);
-            return Err(NetworkError::ConnectionFailed(
-                "连接超时: 对方可能未开启服务".to_string(),
-            ));
-        }
-    };
+        .ok_or_else(|| NetworkError::Connect(ConnectErrorKind::ServiceStopped, "QUIC endpoint not initialized".to_string()))?;
+
+    let conn = endpoint
+        .connect_within(addr, Duration::from_secs(5))
+        .await
+        .inspect_err(|e| log::warn!("Failed to connect to manual device {}: {}", ip, e))?;
 
     // Send handshake to get device info
     let our_id = get_our_device_id();
-    let our_name = hostname::get()
-        .map(|h| h.to_string_lossy().to_string())
-        .unwrap_or_else(|_| "Unknown".to_string());
-
-    let handshake = protocol::create_handshake(&our_id, &our_name);
-    let encoded = protocol::encode(&handshake)?;
+    let handshake = protocol::create_handshake(&our_id, &crate::commands::display_name());
+    let encoded = protocol::encode_as(&handshake, protocol::LEGACY_VERSION)?;
 
     let mut stream = conn.open_bi_stream().await?;
+    super::password::send_proof(&conn, &mut stream).await?;
     stream.send_framed(&encoded).await?;
 
-    // Wait for handshake ack with timeout
-    let recv_future = stream.recv_framed();
-    let response = match tokio::time::timeout(Duration::from_secs(5), recv_future).await {
-        Ok(Ok(data)) => data,
-        Ok(Err(e)) => {
-            return Err(NetworkError::ConnectionFailed(format!(
-                "握手失败: {}", e
-            )));
-        }
-        Err(_) => {
-            return Err(NetworkError::ConnectionFailed(
-                "握手超时".to_string(),
-            ));
-        }
-    };
-
-    // Parse handshake ack to get device info
-    let ack = protocol::decode(&response)?;
-    let (device_id, device_name) = match ack {
-        protocol::Message::HandshakeAck { device_id, name, accepted, reason, .. } => {
-            if !accepted {
-                return Err(NetworkError::ConnectionFailed(format!(
-                    "对方拒绝连接: {}",
-                    reason.unwrap_or_else(|| "未知原因".to_string())
-                )));
-            }
-            (device_id, name)
-        }
-        _ => {
-            return Err(NetworkError::ConnectionFailed(
-                "无效的握手响应".to_string(),
-            ));
-        }
-    };
+    // Wait for handshake ack, pairing first if we never met
+    let answer = super::pairing::await_answer(&conn, &mut stream, ip.trim(), None).await?;
+    if super::known::is_blocked(Some(&answer.device_id), None) {
+        conn.close();
+        return Err(NetworkError::Connect(ConnectErrorKind::Rejected, "Device is blocked".to_string()));
+    }
+    if let Err(e) = super::profile::send_avatar(&mut stream).await {
+        log::warn!("Failed to send our avatar to {}: {}", answer.name, e);
+    }
 
-    // Connection and handshake successful, add device
-    let device = DiscoveredDevice {
-        id: device_id,
-        name: device_name,
-        ip,
+    // Connection and handshake successful, add device under the IP its
+    // connection is filed by
+    let known = DEVICES.read().get(&answer.device_id).cloned();
+    let mut device = DiscoveredDevice {
+        id: answer.device_id,
+        name: answer.name,
+        ip: conn.peer_ip(),
         port,
         status: DeviceStatus::Online,
         last_seen: now_ms(),
-        is_sharing: false,
+        is_sharing: known.as_ref().is_some_and(|d| d.is_sharing),
+        avatar: super::profile::checked_avatar(answer.avatar.as_deref()),
+        version: None,
+        platform: known.as_ref().and_then(|d| d.platform.clone()),
+        capabilities: Vec::new(),
+        incompatible: false,
+        nickname: None,
     };
+    device.set_version(&answer.version, answer.capabilities);
 
     add_device(device.clone());
+    super::pairing::complete(&conn, &device.id);
+    super::password::admit(&conn);
     log::info!("Manual device added and verified: {} ({})", device.name, device.ip);
 
     // Start listening for incoming messages on this connection
@@ -423,9 +927,125 @@ pub async fn add_manual_device(ip: String, port: u16) -> Result<DiscoveredDevice
     Ok(device)
 }
 
-/// Shutdown mDNS service
+/// Take our service off the network and stop querying. mdns-sd sends
+/// goodbye packets (records with TTL 0) for it; we wait briefly for them to
+/// go out, since the process may be about to exit.
+pub fn withdraw() {
+    let Some(daemon) = MDNS_DAEMON.lock().clone() else {
+        return;
+    };
+    if let Some(fullname) = REGISTERED.lock().take() {
+        match daemon.unregister(&fullname) {
+            Ok(status) => match status.recv_timeout(GOODBYE_TIMEOUT) {
+                Ok(status) => log::info!("mDNS service withdrawn: {:?}", status),
+                Err(e) => log::warn!("No confirmation of mDNS goodbye: {}", e),
+            },
+            Err(e) => log::warn!("Failed to unregister mDNS service: {}", e),
+        }
+    }
+    if DISCOVERY_RUNNING.swap(false, Ordering::SeqCst) {
+        WAKE.notify_one();
+    }
+}
+
+/// Shutdown mDNS service; the next `start_discovery` creates a new daemon
 pub fn shutdown() {
-    if let Some(daemon) = MDNS_DAEMON.as_ref() {
+    if let Some(daemon) = MDNS_DAEMON.lock().take() {
         let _ = daemon.shutdown();
+        log::info!("mDNS daemon shut down");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_requeries() {
+        let start = Instant::now();
+        let settings = DiscoverySettings { query_interval_secs: 30, passive: false };
+        let mut schedule = QuerySchedule::default();
+
+        assert_eq!(schedule.step(start, settings, false), BrowseAction::Query);
+        assert_eq!(schedule.deadline(), Some(start + Duration::from_secs(30)));
+        assert_eq!(schedule.step(start + Duration::from_secs(10), settings, false), BrowseAction::Keep);
+        assert_eq!(schedule.step(start + Duration::from_secs(30), settings, false), BrowseAction::Query);
+        assert_eq!(schedule.deadline(), Some(start + Duration::from_secs(60)));
+
+        // Default backoff: browse once, never wake up on our own
+        let default = DiscoverySettings::default();
+        assert_eq!(schedule.step(start + Duration::from_secs(40), default, false), BrowseAction::Keep);
+        assert_eq!(schedule.deadline(), None);
+    }
+
+    #[test]
+    fn test_steady_network_stretches_interval() {
+        let start = Instant::now();
+        let settings = DiscoverySettings { query_interval_secs: 30, passive: false };
+        let mut schedule = QuerySchedule::default();
+        let mut now = start;
+        assert_eq!(schedule.step(now, settings, false), BrowseAction::Query);
+
+        let mut gaps = Vec::new();
+        for _ in 0..5 {
+            let next = schedule.deadline().unwrap();
+            gaps.push((next - now).as_secs());
+            now = next;
+            assert_eq!(schedule.step(now, settings, false), BrowseAction::Query);
+        }
+        assert_eq!(gaps, [30, 30, 30, 60, 120]);
+
+        // A device came or went: the configured interval again
+        schedule.devices_changed();
+        assert_eq!(schedule.step(now, settings, false), BrowseAction::Keep);
+        assert_eq!(schedule.deadline(), Some(now + Duration::from_secs(30)));
+
+        // Never longer than the cap
+        schedule.quiet_queries = 50;
+        assert_eq!(schedule.stretched(Duration::from_secs(30)), MAX_STEADY_INTERVAL);
+    }
+
+    #[test]
+    fn test_pick_address() {
+        let addresses = vec!["fd00::7".to_string(), "10.0.0.7".to_string(), "192.168.1.7".to_string()];
+        // 192.168.1.0/24
+        let subnets = [(0xc0a8_0100, 0xffff_ff00)];
+        assert_eq!(pick_address(&addresses, "10.0.0.7", &subnets).as_deref(), Some("10.0.0.7"));
+        assert_eq!(pick_address(&addresses, "172.16.0.1", &subnets).as_deref(), Some("192.168.1.7"));
+        assert_eq!(pick_address(&addresses, "172.16.0.1", &[]).as_deref(), Some("10.0.0.7"));
+        assert_eq!(pick_address(&["fd00::7".to_string()], "172.16.0.1", &[]).as_deref(), Some("fd00::7"));
+        assert_eq!(pick_address(&[], "172.16.0.1", &[]), None);
+    }
+
+    #[test]
+    fn test_pick_resolved() {
+        let ips = |list: &[&str]| list.iter().map(|s| s.parse().unwrap()).collect::<Vec<std::net::IpAddr>>();
+        // 192.168.1.0/24
+        let subnets = [(0xc0a8_0100, 0xffff_ff00)];
+        let all = ips(&["fe80::7", "2001:db8::7", "198.18.0.7", "fd00::7", "10.0.0.7", "192.168.1.7"]);
+        assert_eq!(pick_resolved(&all, &subnets, Some(3)).as_deref(), Some("192.168.1.7"));
+        assert_eq!(pick_resolved(&all, &[], Some(3)).as_deref(), Some("10.0.0.7"));
+
+        // IPv6-only segment
+        assert_eq!(pick_resolved(&ips(&["fe80::7", "fd00::7"]), &[], Some(3)).as_deref(), Some("fd00::7"));
+        assert_eq!(pick_resolved(&ips(&["fe80::7", "2001:db8::7"]), &[], Some(3)).as_deref(), Some("2001:db8::7"));
+        assert_eq!(pick_resolved(&ips(&["fe80::7"]), &[], Some(3)).as_deref(), Some("fe80::7%3"));
+        // Unknown interface: link-local cannot be dialed
+        assert_eq!(pick_resolved(&ips(&["fe80::7"]), &[], None), None);
+    }
+
+    #[test]
+    fn test_passive_only_queries_on_refresh() {
+        let start = Instant::now();
+        let passive = DiscoverySettings { query_interval_secs: 30, passive: true };
+        let mut schedule = QuerySchedule::default();
+
+        assert_eq!(schedule.step(start, passive, false), BrowseAction::Keep);
+        assert_eq!(schedule.deadline(), None);
+
+        assert_eq!(schedule.step(start, passive, true), BrowseAction::Query);
+        assert_eq!(schedule.step(start + Duration::from_secs(1), passive, false), BrowseAction::Keep);
+        assert_eq!(schedule.step(start + PASSIVE_LISTEN, passive, false), BrowseAction::Stop);
+        assert_eq!(schedule.deadline(), None);
     }
 }
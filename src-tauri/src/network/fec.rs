@@ -0,0 +1,116 @@
+//! Forward error correction for unreliable datagrams
+//!
+//! XOR parity: the data shards of a frame are split into groups and each
+//! group gets one parity shard, which repairs any single lost shard of its
+//! group without waiting for a retransmission. Smaller groups repair more
+//! loss at the cost of more overhead, so the group size follows the loss
+//! rate the connection reports; a clean link sends no parity at all.
+
+/// Loss rate below which no parity is sent
+const CLEAN_LOSS: f64 = 0.005;
+
+/// Group size per loss rate, from light to heavy loss
+const GROUP_SIZES: [(f64, usize); 4] = [(0.02, 10), (0.05, 5), (0.10, 3), (f64::INFINITY, 2)];
+
+/// Weight of the newest loss sample in the smoothed loss rate
+const SMOOTHING: f64 = 0.3;
+
+/// Parity shard covering `shards`. Every shard is XORed as its 2-byte
+/// length followed by its bytes, padded to the longest shard, so a
+/// recovered shard knows its own length.
+pub fn parity(shards: &[&[u8]]) -> Vec<u8> {
+    let longest = shards.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut parity = vec![0u8; 2 + longest];
+    for shard in shards {
+        xor_into(&mut parity, shard);
+    }
+    parity
+}
+
+/// Rebuild the one missing shard of a group from the others and the
+/// group's parity. Returns `None` if the parity is malformed.
+pub fn recover(present: &[&[u8]], parity: &[u8]) -> Option<Vec<u8>> {
+    let mut missing = parity.to_vec();
+    for shard in present {
+        if 2 + shard.len() > missing.len() {
+            return None;
+        }
+        xor_into(&mut missing, shard);
+    }
+    if missing.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([missing[0], missing[1]]) as usize;
+    if 2 + len > missing.len() {
+        return None;
+    }
+    missing.truncate(2 + len);
+    missing.drain(..2);
+    Some(missing)
+}
+
+fn xor_into(acc: &mut [u8], shard: &[u8]) {
+    let len = (shard.len() as u16).to_be_bytes();
+    acc[0] ^= len[0];
+    acc[1] ^= len[1];
+    for (a, b) in acc[2..].iter_mut().zip(shard) {
+        *a ^= b;
+    }
+}
+
+/// Picks the parity group size from observed loss
+#[derive(Debug, Clone, Default)]
+pub struct FecTuner {
+    loss: f64,
+}
+
+impl FecTuner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the loss rate of the last interval (0.0 – 1.0)
+    pub fn update(&mut self, loss: f64) {
+        self.loss = SMOOTHING * loss.clamp(0.0, 1.0) + (1.0 - SMOOTHING) * self.loss;
+    }
+
+    /// Data shards per parity shard, or `None` on a clean link
+    pub fn group_size(&self) -> Option<usize> {
+        if self.loss < CLEAN_LOSS {
+            return None;
+        }
+        GROUP_SIZES
+            .iter()
+            .find(|(below, _)| self.loss < *below)
+            .map(|&(_, size)| size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recover_any_one_shard() {
+        let shards: [&[u8]; 3] = [b"first shard", b"second", b"third!"];
+        let parity = parity(&shards);
+        for missing in 0..shards.len() {
+            let present: Vec<&[u8]> = (0..shards.len()).filter(|&i| i != missing).map(|i| shards[i]).collect();
+            assert_eq!(recover(&present, &parity).as_deref(), Some(shards[missing]));
+        }
+    }
+
+    #[test]
+    fn test_group_size_follows_loss() {
+        let mut tuner = FecTuner::new();
+        assert_eq!(tuner.group_size(), None);
+        for _ in 0..20 {
+            tuner.update(0.04);
+        }
+        assert_eq!(tuner.group_size(), Some(5));
+        for _ in 0..20 {
+            tuner.update(0.0);
+        }
+        assert_eq!(tuner.group_size(), None);
+    }
+}
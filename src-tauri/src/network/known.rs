@@ -0,0 +1,313 @@
+//! Known devices
+//!
+//! Every device we verified or connected to is remembered on disk with the
+//! address it was last seen at, so it is listed (as Offline) even while
+//! discovery does not see it, and the user can give it a nickname. Trust
+//! and favorites stay in settings; this only adds what the device list
+//! needs to show a device that is not around. Pairing pins the device's
+//! certificate fingerprint here too, so forgetting a device unpairs it.
+//! A blocked device is kept here as well: its handshakes are turned away,
+//! by device id or by the certificate it presented, and discovery does not
+//! list it until it is unblocked.
+
+use super::discovery::{DeviceStatus, DiscoveredDevice};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Longest nickname kept
+const MAX_NICKNAME_CHARS: usize = 64;
+
+/// A device we saw before
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnownDevice {
+    pub id: String,
+    /// Name the device last announced
+    pub name: String,
+    pub last_ip: String,
+    pub port: u16,
+    /// Name the user gave it
+    #[serde(default)]
+    pub nickname: Option<String>,
+    /// Unix milliseconds
+    pub last_seen: u64,
+    /// Fingerprint of the certificate pinned when we paired
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Blocked by the user
+    #[serde(default)]
+    pub blocked: bool,
+    /// Certificate the device presented when it was blocked
+    #[serde(default)]
+    pub blocked_fingerprint: Option<String>,
+}
+
+static KNOWN: Lazy<RwLock<HashMap<String, KnownDevice>>> = Lazy::new(|| RwLock::new(load_from_disk()));
+
+/// Known devices file path
+fn known_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|p| p.join("lan-meeting").join("known_devices.json"))
+}
+
+fn load_from_disk() -> HashMap<String, KnownDevice> {
+    let Some(path) = known_path() else {
+        return HashMap::new();
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(json) => match serde_json::from_str::<Vec<KnownDevice>>(&json) {
+            Ok(devices) => devices.into_iter().map(|d| (d.id.clone(), d)).collect(),
+            Err(e) => {
+                log::warn!("Failed to parse known devices file: {}", e);
+                HashMap::new()
+            }
+        },
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_to_disk(known: &HashMap<String, KnownDevice>) {
+    let Some(path) = known_path() else {
+        log::warn!("Cannot determine known devices path");
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let mut devices: Vec<&KnownDevice> = known.values().collect();
+    devices.sort_by(|a, b| a.id.cmp(&b.id));
+    match serde_json::to_string_pretty(&devices) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                log::error!("Failed to write known devices to {}: {}", path.display(), e);
+            }
+        }
+        Err(e) => log::error!("Failed to serialize known devices: {}", e),
+    }
+}
+
+/// Fold a live device into its record; returns whether anything worth
+/// saving changed (not just the time it was seen)
+fn merge(known: &mut HashMap<String, KnownDevice>, device: &DiscoveredDevice) -> bool {
+    match known.get_mut(&device.id) {
+        Some(entry) => {
+            entry.last_seen = device.last_seen.max(entry.last_seen);
+            let changed = (entry.name.as_str(), entry.last_ip.as_str(), entry.port)
+                != (device.name.as_str(), device.ip.as_str(), device.port);
+            entry.name = device.name.clone();
+            entry.last_ip = device.ip.clone();
+            entry.port = device.port;
+            changed
+        }
+        None => {
+            known.insert(
+                device.id.clone(),
+                KnownDevice {
+                    id: device.id.clone(),
+                    name: device.name.clone(),
+                    last_ip: device.ip.clone(),
+                    port: device.port,
+                    nickname: None,
+                    last_seen: device.last_seen,
+                    fingerprint: None,
+                    blocked: false,
+                    blocked_fingerprint: None,
+                },
+            );
+            true
+        }
+    }
+}
+
+/// Remember a device once it is verified; unverified announcements are
+/// not worth keeping
+pub fn remember(device: &DiscoveredDevice) {
+    if matches!(device.status, DeviceStatus::Seen | DeviceStatus::Offline) {
+        return;
+    }
+    let mut known = KNOWN.write();
+    if merge(&mut known, device) {
+        save_to_disk(&known);
+    }
+}
+
+/// The nickname the user gave a device
+pub fn nickname(device_id: &str) -> Option<String> {
+    KNOWN.read().get(device_id).and_then(|d| d.nickname.clone())
+}
+
+/// Change the record of a device and save it; a device on the list that
+/// is not remembered yet is remembered now
+fn update(device_id: &str, change: impl FnOnce(&mut KnownDevice)) -> Result<(), String> {
+    let live = super::discovery::get_devices().into_iter().find(|d| d.id == device_id);
+    let mut known = KNOWN.write();
+    if let Some(device) = live {
+        merge(&mut known, &device);
+    }
+    let entry = known.get_mut(device_id).ok_or_else(|| format!("Unknown device: {}", device_id))?;
+    change(entry);
+    save_to_disk(&known);
+    Ok(())
+}
+
+/// Give a device a nickname, or clear it with an empty one
+pub fn rename(device_id: &str, nickname: &str) -> Result<(), String> {
+    let nickname: String = nickname.trim().chars().take(MAX_NICKNAME_CHARS).collect();
+    update(device_id, |entry| entry.nickname = Some(nickname).filter(|n| !n.is_empty()))
+}
+
+/// The device a certificate fingerprint is pinned to; a blocked device
+/// is not paired
+pub fn paired_device(fingerprint: &str) -> Option<String> {
+    KNOWN
+        .read()
+        .values()
+        .find(|d| !d.blocked && d.fingerprint.as_deref() == Some(fingerprint))
+        .map(|d| d.id.clone())
+}
+
+/// The certificate fingerprint pinned to a device
+pub fn pinned(device_id: &str) -> Option<String> {
+    KNOWN.read().get(device_id).and_then(|d| d.fingerprint.clone())
+}
+
+/// Pin a certificate fingerprint to a device, taking it from any device
+/// it was pinned to before (the same key under a new device id)
+pub fn pin(device_id: &str, fingerprint: &str) -> Result<(), String> {
+    for entry in KNOWN.write().values_mut() {
+        if entry.fingerprint.as_deref() == Some(fingerprint) {
+            entry.fingerprint = None;
+        }
+    }
+    update(device_id, |entry| entry.fingerprint = Some(fingerprint.to_string()))
+}
+
+/// Whether `entry` blocks a device with `device_id` or a certificate with
+/// `fingerprint`
+fn blocks(entry: &KnownDevice, device_id: Option<&str>, fingerprint: Option<&str>) -> bool {
+    entry.blocked
+        && (device_id == Some(entry.id.as_str())
+            || fingerprint.is_some_and(|fp| {
+                entry.fingerprint.as_deref() == Some(fp) || entry.blocked_fingerprint.as_deref() == Some(fp)
+            }))
+}
+
+/// Whether a device id or certificate fingerprint is blocked
+pub fn is_blocked(device_id: Option<&str>, fingerprint: Option<&str>) -> bool {
+    KNOWN.read().values().any(|d| blocks(d, device_id, fingerprint))
+}
+
+/// Block a device, along with the certificate it presents now if it is
+/// connected
+pub fn block(device_id: &str, fingerprint: Option<&str>) -> Result<(), String> {
+    update(device_id, |entry| {
+        entry.blocked = true;
+        entry.blocked_fingerprint = fingerprint.map(str::to_string);
+    })
+}
+
+/// Lift the block on a device
+pub fn unblock(device_id: &str) -> Result<(), String> {
+    update(device_id, |entry| {
+        entry.blocked = false;
+        entry.blocked_fingerprint = None;
+    })
+}
+
+/// Forget a device; returns whether it was known
+pub fn forget(device_id: &str) -> bool {
+    let mut known = KNOWN.write();
+    let removed = known.remove(device_id).is_some();
+    if removed {
+        save_to_disk(&known);
+    }
+    removed
+}
+
+/// Every known device
+pub fn list() -> Vec<KnownDevice> {
+    let mut devices: Vec<KnownDevice> = KNOWN.read().values().cloned().collect();
+    devices.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    devices
+}
+
+/// Live devices plus the known ones discovery does not see, as Offline;
+/// blocked devices are left out
+pub fn with_offline(mut live: Vec<DiscoveredDevice>) -> Vec<DiscoveredDevice> {
+    for known in KNOWN.read().values() {
+        if known.blocked || live.iter().any(|d| d.id == known.id) {
+            continue;
+        }
+        live.push(DiscoveredDevice {
+            id: known.id.clone(),
+            name: known.name.clone(),
+            ip: known.last_ip.clone(),
+            port: known.port,
+            status: DeviceStatus::Offline,
+            last_seen: known.last_seen,
+            is_sharing: false,
+            avatar: None,
+            version: None,
+            platform: None,
+            capabilities: Vec::new(),
+            incompatible: false,
+            nickname: known.nickname.clone(),
+        });
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(ip: &str, last_seen: u64) -> DiscoveredDevice {
+        DiscoveredDevice {
+            id: "a".to_string(),
+            name: "Alpha".to_string(),
+            ip: ip.to_string(),
+            port: 19876,
+            status: DeviceStatus::Online,
+            last_seen,
+            is_sharing: false,
+            avatar: None,
+            version: None,
+            platform: None,
+            capabilities: Vec::new(),
+            incompatible: false,
+            nickname: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_saves_only_real_changes() {
+        let mut known = HashMap::new();
+        assert!(merge(&mut known, &device("10.0.0.2", 1)));
+        assert!(!merge(&mut known, &device("10.0.0.2", 5)));
+        assert_eq!(known["a"].last_seen, 5);
+
+        known.get_mut("a").unwrap().nickname = Some("Meeting room".to_string());
+        assert!(merge(&mut known, &device("10.0.0.9", 9)));
+        assert_eq!(known["a"].last_ip, "10.0.0.9");
+        assert_eq!(known["a"].nickname.as_deref(), Some("Meeting room"));
+    }
+
+    #[test]
+    fn test_block_matches_id_or_certificate() {
+        let mut known = HashMap::new();
+        merge(&mut known, &device("10.0.0.2", 1));
+        let entry = known.get_mut("a").unwrap();
+        entry.fingerprint = Some("pinned".to_string());
+        assert!(!blocks(entry, Some("a"), None));
+
+        entry.blocked = true;
+        entry.blocked_fingerprint = Some("seen".to_string());
+        assert!(blocks(entry, Some("a"), None));
+        // The same certificate under another device id
+        assert!(blocks(entry, Some("b"), Some("seen")));
+        assert!(blocks(entry, None, Some("pinned")));
+        assert!(!blocks(entry, Some("b"), Some("other")));
+        assert!(!blocks(entry, None, None));
+    }
+}
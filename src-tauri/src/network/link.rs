@@ -0,0 +1,198 @@
+//! Link quality per peer
+//!
+//! quinn keeps path statistics for every connection: RTT, congestion
+//! window, packets lost and bytes on the wire. While the service runs they
+//! are sampled every `INTERVAL` and sent to the UI as a "network-stats"
+//! event, one entry per connected peer, with loss and throughput over the
+//! last interval; `get_connection_stats` gives one peer's on demand.
+
+use super::discovery;
+use super::quic::{self, LinkStats, QuicConnection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// Time between samples
+const INTERVAL: Duration = Duration::from_secs(2);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Bumped for every sampling loop started, so one left over from before a
+/// quick stop and start knows to exit
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Link quality of the connection to one peer
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStats {
+    pub peer_ip: String,
+    pub device_id: Option<String>,
+    pub rtt_ms: f64,
+    /// Congestion window in bytes
+    pub cwnd: u64,
+    /// Over the connection's lifetime
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+    /// Packets lost over the last interval, in percent (lifetime without one)
+    pub loss_percent: f64,
+    /// Throughput over the last interval, in bits per second
+    pub send_bps: u64,
+    pub receive_bps: u64,
+}
+
+/// Stats from `now`, with loss and throughput since `before` when known
+fn stats(
+    peer_ip: &str,
+    now: &LinkStats,
+    before: Option<(&LinkStats, Duration)>,
+) -> ConnectionStats {
+    let (sent, lost) = match before {
+        Some((b, _)) => (
+            now.sent_packets.saturating_sub(b.sent_packets),
+            now.lost_packets.saturating_sub(b.lost_packets),
+        ),
+        None => (now.sent_packets, now.lost_packets),
+    };
+    let rate = |bytes: u64, earlier: u64, elapsed: Duration| {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            (bytes.saturating_sub(earlier) as f64 * 8.0 / secs) as u64
+        } else {
+            0
+        }
+    };
+    ConnectionStats {
+        peer_ip: peer_ip.to_string(),
+        device_id: None,
+        rtt_ms: now.rtt.as_secs_f64() * 1000.0,
+        cwnd: now.cwnd,
+        sent_packets: now.sent_packets,
+        lost_packets: now.lost_packets,
+        sent_bytes: now.sent_bytes,
+        received_bytes: now.received_bytes,
+        loss_percent: if sent > 0 {
+            lost as f64 * 100.0 / sent as f64
+        } else {
+            0.0
+        },
+        send_bps: before.map_or(0, |(b, elapsed)| {
+            rate(now.sent_bytes, b.sent_bytes, elapsed)
+        }),
+        receive_bps: before.map_or(0, |(b, elapsed)| {
+            rate(now.received_bytes, b.received_bytes, elapsed)
+        }),
+    }
+}
+
+/// The live connection to each peer, by peer IP
+fn connections() -> HashMap<String, std::sync::Arc<QuicConnection>> {
+    let mut by_peer = HashMap::new();
+    for conn in quic::get_all_connections()
+        .into_iter()
+        .filter(|c| c.is_alive())
+    {
+        by_peer.entry(conn.peer_ip()).or_insert(conn);
+    }
+    by_peer
+}
+
+fn with_device(mut stats: ConnectionStats) -> ConnectionStats {
+    stats.device_id = discovery::get_devices()
+        .into_iter()
+        .find(|d| d.ip == stats.peer_ip)
+        .map(|d| d.id);
+    stats
+}
+
+/// Stats of the connection to `peer`, a device id or peer IP
+pub fn connection_stats(peer: &str) -> Option<ConnectionStats> {
+    let peer_ip = discovery::get_devices()
+        .into_iter()
+        .find(|d| d.id == peer)
+        .map_or_else(|| peer.to_string(), |d| d.ip);
+    let conn = connections().remove(&peer_ip)?;
+    Some(with_device(stats(&peer_ip, &conn.link_stats(), None)))
+}
+
+/// Start sampling, with the service
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::spawn(sample_loop(generation));
+    }
+}
+
+/// Stop sampling, with the service
+pub fn stop() {
+    RUNNING.store(false, Ordering::SeqCst);
+}
+
+async fn sample_loop(generation: u64) {
+    // Last sample of each peer
+    let mut last: HashMap<String, (Instant, LinkStats)> = HashMap::new();
+    while RUNNING.load(Ordering::SeqCst) && GENERATION.load(Ordering::SeqCst) == generation {
+        tokio::time::sleep(INTERVAL).await;
+
+        let now = Instant::now();
+        let connections = connections();
+        last.retain(|ip, _| connections.contains_key(ip));
+        if connections.is_empty() {
+            continue;
+        }
+        let mut report = Vec::with_capacity(connections.len());
+        for (peer_ip, conn) in connections {
+            let sample = conn.link_stats();
+            let before = last
+                .get(&peer_ip)
+                .map(|(at, s)| (s, now.duration_since(*at)));
+            report.push(with_device(stats(&peer_ip, &sample, before)));
+            last.insert(peer_ip, (now, sample));
+        }
+        report.sort_by(|a, b| a.peer_ip.cmp(&b.peer_ip));
+        if let Some(handle) = crate::APP_HANDLE.get() {
+            let _ = handle.emit("network-stats", report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_loss_and_throughput() {
+        let before = LinkStats {
+            sent_packets: 100,
+            lost_packets: 10,
+            sent_bytes: 1_000,
+            received_bytes: 500,
+            ..Default::default()
+        };
+        let now = LinkStats {
+            rtt: Duration::from_micros(2_500),
+            sent_packets: 200,
+            lost_packets: 12,
+            sent_bytes: 251_000,
+            received_bytes: 500,
+            ..Default::default()
+        };
+
+        let lifetime = stats("10.0.0.2", &now, None);
+        assert_eq!(lifetime.rtt_ms, 2.5);
+        assert_eq!(lifetime.loss_percent, 6.0);
+        assert_eq!(lifetime.send_bps, 0);
+
+        let interval = stats("10.0.0.2", &now, Some((&before, Duration::from_secs(2))));
+        assert_eq!(interval.loss_percent, 2.0);
+        assert_eq!(interval.send_bps, 1_000_000);
+        assert_eq!(interval.receive_bps, 0);
+        // Nothing sent
+        assert_eq!(
+            stats("10.0.0.2", &before, Some((&before, Duration::ZERO))).loss_percent,
+            0.0
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Heartbeats and stale device expiry
+//!
+//! QUIC keep-alives hold a connection open, but say nothing about whether
+//! the app on the other end still answers. Every `INTERVAL` each connected
+//! peer gets a `Heartbeat`; a device that misses `MAX_MISSED` in a row is
+//! marked Offline, and one that stays Offline for `EXPIRE_AFTER` since it
+//! was last seen is dropped from the list. An answer brings an Offline
+//! device back Online. Status changes go out as "device-status" events.
+
+use super::discovery::{self, DeviceStatus};
+use super::protocol::{self, Message};
+use super::quic;
+use super::NetworkError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+
+/// Time between heartbeats
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a heartbeat waits for its answer
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Heartbeats missed in a row before a device is Offline
+const MAX_MISSED: u32 = 3;
+
+/// How long an Offline device stays listed after it was last seen
+const EXPIRE_AFTER: Duration = Duration::from_secs(60);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Bumped for every heartbeat loop started, so one left over from before a
+/// quick stop and start knows to exit
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Payload of the "device-status" event
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStatusEvent {
+    pub id: String,
+    pub status: DeviceStatus,
+}
+
+/// The status a device moves to after a heartbeat round, if it changes:
+/// `missed` counts the heartbeats it failed to answer in a row
+fn next_status(status: DeviceStatus, missed: u32) -> Option<DeviceStatus> {
+    match status {
+        DeviceStatus::Offline if missed == 0 => Some(DeviceStatus::Online),
+        DeviceStatus::Offline => None,
+        _ if missed >= MAX_MISSED => Some(DeviceStatus::Offline),
+        _ => None,
+    }
+}
+
+/// Whether a device last seen at `last_seen_ms` has been gone long enough
+/// to drop
+fn expired(status: DeviceStatus, last_seen_ms: u64, now_ms: u64) -> bool {
+    status == DeviceStatus::Offline && now_ms.saturating_sub(last_seen_ms) >= EXPIRE_AFTER.as_millis() as u64
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Start sending heartbeats
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::spawn(heartbeat_loop(generation));
+    }
+}
+
+/// Stop sending heartbeats, with the service
+pub fn stop() {
+    RUNNING.store(false, Ordering::SeqCst);
+}
+
+/// One Heartbeat round trip to `peer_ip`
+async fn ping(peer_ip: &str) -> Result<(), NetworkError> {
+    let conn = quic::find_connection(peer_ip)
+        .ok_or_else(|| NetworkError::ConnectionFailed(format!("Peer not found: {}", peer_ip)))?;
+    let round_trip = async {
+        let mut stream = conn.open_bi_stream().await?;
+        stream.send_framed(&protocol::encode(&protocol::create_heartbeat())?).await?;
+        match protocol::decode(&stream.recv_framed().await?)? {
+            Message::HeartbeatAck { .. } => Ok(()),
+            _ => Err(NetworkError::ProtocolError("Expected HeartbeatAck".to_string())),
+        }
+    };
+    tokio::time::timeout(TIMEOUT, round_trip)
+        .await
+        .map_err(|_| NetworkError::ConnectionFailed("Heartbeat timed out".to_string()))?
+}
+
+async fn heartbeat_loop(generation: u64) {
+    // Heartbeats missed in a row, by peer IP
+    let mut missed: HashMap<String, u32> = HashMap::new();
+    while RUNNING.load(Ordering::SeqCst) && GENERATION.load(Ordering::SeqCst) == generation {
+        tokio::time::sleep(INTERVAL).await;
+
+        let mut peers: Vec<String> = quic::get_all_connections()
+            .iter()
+            .filter(|c| c.is_alive())
+            .map(|c| c.peer_ip())
+            .collect();
+        peers.sort();
+        peers.dedup();
+        missed.retain(|ip, _| peers.contains(ip));
+
+        let mut pings = tokio::task::JoinSet::new();
+        for ip in peers {
+            pings.spawn(async move {
+                let answer = ping(&ip).await;
+                (ip, answer)
+            });
+        }
+        while let Some(Ok((ip, answer))) = pings.join_next().await {
+            let count = missed.entry(ip.clone()).or_default();
+            match answer {
+                Ok(()) => *count = 0,
+                Err(e) => {
+                    *count += 1;
+                    log::debug!("Heartbeat to {} missed ({} in a row): {}", ip, count, e);
+                }
+            }
+        }
+
+        let now = now_ms();
+        for device in discovery::get_devices() {
+            let answered = missed.get(&device.ip).copied();
+            if let Some(status) = answered.and_then(|count| next_status(device.status, count)) {
+                log::info!("Device '{}' ({}) is now {:?}", device.name, device.ip, status);
+                if discovery::set_device_liveness(&device.id, status) {
+                    emit_status(&device.id, status);
+                }
+            } else if expired(device.status, device.last_seen, now) {
+                log::info!("Device '{}' ({}) expired", device.name, device.ip);
+                discovery::remove_device(&device.id);
+                if let Some(handle) = crate::APP_HANDLE.get() {
+                    let _ = handle.emit("device-removed", &device.id);
+                }
+            }
+        }
+    }
+}
+
+fn emit_status(id: &str, status: DeviceStatus) {
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("device-status", DeviceStatusEvent { id: id.to_string(), status });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_beats_and_expiry() {
+        assert_eq!(next_status(DeviceStatus::Online, 0), None);
+        assert_eq!(next_status(DeviceStatus::Online, MAX_MISSED - 1), None);
+        assert_eq!(next_status(DeviceStatus::Busy, MAX_MISSED), Some(DeviceStatus::Offline));
+        assert_eq!(next_status(DeviceStatus::Offline, MAX_MISSED + 1), None);
+        assert_eq!(next_status(DeviceStatus::Offline, 0), Some(DeviceStatus::Online));
+
+        let expire_ms = EXPIRE_AFTER.as_millis() as u64;
+        assert!(!expired(DeviceStatus::Offline, 1_000, 1_000 + expire_ms - 1));
+        assert!(expired(DeviceStatus::Offline, 1_000, 1_000 + expire_ms));
+        assert!(!expired(DeviceStatus::Online, 1_000, 1_000 + expire_ms * 10));
+        // Clock stepped back
+        assert!(!expired(DeviceStatus::Offline, 5_000, 1_000));
+    }
+}
@@ -1,20 +1,149 @@
 // Network module
 // QUIC-based P2P communication with mDNS discovery
 
+pub mod access;
+pub mod address;
+pub mod capability;
+pub mod clock;
+pub mod device_search;
 pub mod discovery;
+pub mod fec;
+pub mod known;
+pub mod link;
+pub mod liveness;
+pub mod pairing;
+pub mod password;
+pub mod profile;
 pub mod protocol;
 pub mod quic;
+pub mod retry;
+pub mod scan;
+pub mod trust;
 
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum NetworkError {
     #[error("Connection failed: {0}")]
     ConnectionFailed(String),
+    #[error("Connection failed ({0:?}): {1}")]
+    Connect(ConnectErrorKind, String),
     #[error("Discovery error: {0}")]
     DiscoveryError(String),
     #[error("Protocol error: {0}")]
     ProtocolError(String),
+    #[error("Decode error: {0}")]
+    Decode(#[from] protocol::DecodeError),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
+
+/// Why connecting to a peer failed, so the UI can tell the user what to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectErrorKind {
+    /// Our own service is not started
+    ServiceStopped,
+    /// Not an address we can dial
+    InvalidAddress,
+    /// No answer: the peer's service is not running or a firewall drops it
+    Timeout,
+    /// The peer's endpoint refused the connection
+    Refused,
+    /// The TLS handshake failed
+    Certificate,
+    /// The peer runs an incompatible QUIC or app version
+    VersionMismatch,
+    /// The peer answered but declined our handshake
+    Rejected,
+    /// The peer holds a meeting and wants its password
+    PasswordRequired,
+    /// The connection dropped while setting up
+    ConnectionLost,
+    /// The peer failed too often lately; not dialed again yet
+    RetryLater,
+    Other,
+}
+
+impl ConnectErrorKind {
+    /// Classify the transport error code a peer closed the connection with
+    pub fn from_transport_code(code: u64) -> Self {
+        match code {
+            // CONNECTION_REFUSED
+            0x2 => Self::Refused,
+            // TLS alerts are carried as CRYPTO_ERROR 0x100 + alert
+            0x100..=0x1ff => Self::Certificate,
+            _ => Self::ConnectionLost,
+        }
+    }
+
+    /// Whether trying again soon may help
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Timeout | Self::ConnectionLost | Self::Other)
+    }
+
+    /// What the user can do about it
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::ServiceStopped => "请先开启服务",
+            Self::InvalidAddress => "地址格式不正确",
+            Self::Timeout => "连接超时: 对方可能未开启服务，或被防火墙拦截",
+            Self::Refused => "对方拒绝了连接，请确认对方已开启服务",
+            Self::Certificate => "安全握手失败，请双方重启服务后再试",
+            Self::VersionMismatch => "对方版本不兼容，请将双方升级到相同版本",
+            Self::Rejected => "对方拒绝了连接请求",
+            Self::PasswordRequired => "对方设置了会议密码，请输入正确的密码",
+            Self::ConnectionLost => "连接中断，请检查网络后重试",
+            Self::RetryLater => "对方最近多次连接失败，请稍后再试",
+            Self::Other => "连接失败",
+        }
+    }
+}
+
+/// Connect failure returned by connect commands
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectFailure {
+    pub kind: ConnectErrorKind,
+    /// Hint for the user
+    pub message: String,
+    /// Underlying error, for logs and bug reports
+    pub detail: String,
+}
+
+impl From<NetworkError> for ConnectFailure {
+    fn from(e: NetworkError) -> Self {
+        match e {
+            NetworkError::Connect(kind, detail) => Self { kind, message: kind.hint().to_string(), detail },
+            e => Self::from(e.to_string()),
+        }
+    }
+}
+
+impl From<String> for ConnectFailure {
+    fn from(detail: String) -> Self {
+        Self { kind: ConnectErrorKind::Other, message: detail.clone(), detail }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_codes() {
+        assert_eq!(ConnectErrorKind::from_transport_code(0x2), ConnectErrorKind::Refused);
+        // bad_certificate alert
+        assert_eq!(ConnectErrorKind::from_transport_code(0x100 + 42), ConnectErrorKind::Certificate);
+        assert_eq!(ConnectErrorKind::from_transport_code(0x1), ConnectErrorKind::ConnectionLost);
+    }
+
+    #[test]
+    fn test_failure_keeps_kind() {
+        let failure = ConnectFailure::from(NetworkError::Connect(ConnectErrorKind::Timeout, "timed out".into()));
+        assert_eq!(failure.kind, ConnectErrorKind::Timeout);
+        assert_eq!(failure.detail, "timed out");
+        let failure = ConnectFailure::from(NetworkError::ProtocolError("bad".into()));
+        assert_eq!(failure.kind, ConnectErrorKind::Other);
+    }
+}
@@ -0,0 +1,249 @@
+//! Pairing
+//!
+//! Both ends of a connection present a self-signed certificate. The first
+//! time two devices connect, both users see a six-digit code derived from
+//! the TLS session and confirm that the two match; a machine relaying
+//! between them would hold two sessions and show two different codes. The
+//! peer's certificate fingerprint is then pinned in the known devices
+//! list, and later connections presenting it need no confirmation. Until a
+//! connection is paired only handshakes and heartbeats are served on it.
+//! A device that comes back with another certificate than the one pinned
+//! is paired again, with a warning. Blocked devices are turned away before
+//! any of this.
+
+use super::{capability, known};
+use super::protocol::{self, Message};
+use super::quic::{QuicConnection, QuicStream};
+use super::{ConnectErrorKind, NetworkError};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::oneshot;
+
+/// How long a user has to compare the code
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a handshake answer may take once the peer's user confirmed
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Label of the keying material the code is derived from
+const EXPORT_LABEL: &[u8] = b"EXPORTER-lan-meeting-pairing";
+
+/// Payload of the "pairing-request" event
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingRequest {
+    pub id: String,
+    pub peer_ip: String,
+    /// Device name, or the address while the peer has not said
+    pub name: String,
+    pub code: String,
+    /// The device was paired before, with another certificate
+    pub key_changed: bool,
+}
+
+/// Prompts waiting for the user, by id
+static PENDING: Lazy<Mutex<HashMap<String, oneshot::Sender<bool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A prompt on screen; dropping it takes it down
+struct Prompt {
+    id: String,
+    answer: oneshot::Receiver<bool>,
+}
+
+impl Prompt {
+    fn open(request: PairingRequest) -> Self {
+        let (tx, answer) = oneshot::channel();
+        PENDING.lock().insert(request.id.clone(), tx);
+        let id = request.id.clone();
+        if let Some(handle) = crate::APP_HANDLE.get() {
+            let _ = handle.emit("pairing-request", request);
+        }
+        Self { id, answer }
+    }
+}
+
+impl Drop for Prompt {
+    fn drop(&mut self) {
+        PENDING.lock().remove(&self.id);
+        if let Some(handle) = crate::APP_HANDLE.get() {
+            let _ = handle.emit("pairing-closed", &self.id);
+        }
+    }
+}
+
+/// Six decimal digits from exported keying material
+fn code(secret: [u8; 8]) -> String {
+    format!("{:06}", u64::from_be_bytes(secret) % 1_000_000)
+}
+
+fn rejected(reason: &str) -> NetworkError {
+    NetworkError::Connect(ConnectErrorKind::Rejected, reason.to_string())
+}
+
+/// Whether the peer's certificate is pinned to a device
+pub fn is_paired(conn: &QuicConnection) -> bool {
+    conn.peer_fingerprint().is_some_and(|fp| known::paired_device(fp).is_some())
+}
+
+/// Whether `conn` is device `device_id`: its certificate is pinned to it,
+/// or the device is recorded at the connection's IP
+pub fn speaks_for(conn: &QuicConnection, device_id: &str) -> bool {
+    if let Some(paired) = conn.peer_fingerprint().and_then(known::paired_device) {
+        return paired == device_id;
+    }
+    let peer_ip = conn.peer_ip();
+    super::discovery::get_devices().iter().any(|d| d.id == device_id && d.ip == peer_ip)
+}
+
+/// Have the user confirm the code of `conn`, unless its certificate is
+/// pinned to `device_id` (to any device while the id is not known yet).
+/// Fails when the device or its certificate is blocked, the user declines,
+/// does not answer within `PAIRING_TIMEOUT` or the connection closes
+/// meanwhile.
+pub async fn confirm(conn: &QuicConnection, name: &str, device_id: Option<&str>) -> Result<(), NetworkError> {
+    let fingerprint = conn
+        .peer_fingerprint()
+        .ok_or_else(|| rejected("The peer presented no certificate, it may be an older version"))?;
+    if known::is_blocked(device_id, Some(fingerprint)) {
+        log::info!("Turning away blocked device {} ({})", name, conn.peer_ip());
+        return Err(rejected("Device is blocked"));
+    }
+    let paired = known::paired_device(fingerprint);
+    if paired.is_some() && device_id.is_none_or(|id| paired.as_deref() == Some(id)) {
+        return Ok(());
+    }
+
+    let mut secret = [0u8; 8];
+    conn.export_keying_material(&mut secret, EXPORT_LABEL)?;
+    let key_changed = device_id.is_some_and(|id| known::pinned(id).is_some());
+    if key_changed {
+        log::warn!("{} ({}) presents another certificate than the one paired", name, conn.peer_ip());
+    }
+    let mut prompt = Prompt::open(PairingRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        peer_ip: conn.peer_ip(),
+        name: name.to_string(),
+        code: code(secret),
+        key_changed,
+    });
+
+    tokio::select! {
+        answer = tokio::time::timeout(PAIRING_TIMEOUT, &mut prompt.answer) => match answer {
+            Ok(Ok(true)) => Ok(()),
+            Ok(_) => Err(rejected("Pairing was declined")),
+            Err(_) => Err(rejected("Pairing was not confirmed in time")),
+        },
+        _ = conn.closed() => Err(NetworkError::Connect(
+            ConnectErrorKind::ConnectionLost,
+            "Connection closed during pairing".to_string(),
+        )),
+    }
+}
+
+/// Both sides confirmed: pin the peer's certificate to `device_id`
+pub fn complete(conn: &QuicConnection, device_id: &str) {
+    let Some(fingerprint) = conn.peer_fingerprint() else {
+        return;
+    };
+    if known::pinned(device_id).as_deref() == Some(fingerprint) {
+        return;
+    }
+    match known::pin(device_id, fingerprint) {
+        Ok(()) => log::info!("Paired with {} ({})", device_id, conn.peer_ip()),
+        Err(e) => log::warn!("Failed to pin the certificate of {}: {}", device_id, e),
+    }
+}
+
+/// The user answered prompt `id`
+pub fn answer(id: &str, accept: bool) -> Result<(), String> {
+    let tx = PENDING.lock().remove(id).ok_or_else(|| "Pairing request not found".to_string())?;
+    let _ = tx.send(accept);
+    Ok(())
+}
+
+/// A peer's accepting answer to our handshake
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub device_id: String,
+    pub name: String,
+    pub version: String,
+    /// As sent ahead of the answer, not checked yet
+    pub avatar: Option<Vec<u8>>,
+    /// Tokens sent ahead of the answer; none from a build on protocol 1
+    pub capabilities: Vec<String>,
+}
+
+/// Read the peer's answer off `stream`, with what it sends ahead of it
+async fn read_answer(conn: &QuicConnection, stream: &mut QuicStream) -> Result<Answer, NetworkError> {
+    let mut avatar = None;
+    let mut capabilities = Vec::new();
+    loop {
+        let data = stream
+            .recv_framed()
+            .await
+            .map_err(|e| NetworkError::Connect(ConnectErrorKind::ConnectionLost, format!("Handshake failed: {}", e)))?;
+        match protocol::decode(&data)? {
+            Message::Avatar { avatar: sent } => avatar = sent,
+            Message::Capabilities { tokens } => capabilities = tokens,
+            Message::HandshakeAck { accepted: false, reason: Some(reason), .. } if super::password::is_refusal(&reason) => {
+                return Err(NetworkError::Connect(ConnectErrorKind::PasswordRequired, reason));
+            }
+            Message::HandshakeAck { accepted: false, reason: Some(reason), .. } if reason == capability::UNSUPPORTED => {
+                return Err(NetworkError::Connect(ConnectErrorKind::VersionMismatch, reason));
+            }
+            Message::HandshakeAck { accepted: false, reason, .. } => {
+                return Err(rejected(reason.as_deref().unwrap_or("Unknown reason")));
+            }
+            Message::HandshakeAck { device_id, name, version, .. } => {
+                capability::record(&conn.peer_ip(), &capabilities)
+                    .map_err(|reason| NetworkError::Connect(ConnectErrorKind::VersionMismatch, reason.to_string()))?;
+                return Ok(Answer { device_id, name, version, avatar, capabilities });
+            }
+            msg => {
+                return Err(NetworkError::Connect(
+                    ConnectErrorKind::VersionMismatch,
+                    format!("Unexpected {:?} in answer to the handshake", msg.message_type()),
+                ));
+            }
+        }
+    }
+}
+
+/// Wait for the answer to the handshake we sent on `stream`. Our user
+/// compares the code meanwhile unless the peer is paired, and the peer's
+/// user may take until `PAIRING_TIMEOUT` to do the same. A refusal on
+/// either side is an error, and the connection is closed on any. An
+/// accepting answer settles the capabilities we share with the peer.
+pub async fn await_answer(
+    conn: &QuicConnection,
+    stream: &mut QuicStream,
+    name: &str,
+    device_id: Option<&str>,
+) -> Result<Answer, NetworkError> {
+    let answer = async {
+        tokio::time::timeout(PAIRING_TIMEOUT + ANSWER_TIMEOUT, read_answer(conn, stream))
+            .await
+            .map_err(|_| NetworkError::Connect(ConnectErrorKind::Timeout, "Handshake timed out".to_string()))?
+    };
+    let result = tokio::try_join!(confirm(conn, name, device_id), answer).map(|((), answer)| answer);
+    if result.is_err() {
+        conn.close();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_is_six_digits() {
+        assert_eq!(code([0; 8]), "000000");
+        assert_eq!(code(1_234_567u64.to_be_bytes()), "234567");
+        assert_eq!(code([0xff; 8]), format!("{:06}", u64::MAX % 1_000_000));
+        assert!(code([0xa5; 8]).chars().all(|c| c.is_ascii_digit()));
+    }
+}
@@ -0,0 +1,143 @@
+//! Meeting password
+//!
+//! With a meeting password set in settings, a handshake has to be preceded
+//! by proof of the password or it is turned away before pairing is even
+//! offered. The proof is a hash of the password and keying material
+//! exported from the TLS session, so the password never crosses the wire
+//! and a proof is worthless on any other connection. A certificate that
+//! proved it once is let in again until the password changes, and devices
+//! we connect to ourselves need no proof. Passwords the user typed to join
+//! someone else are kept in memory, by peer address, for reconnects.
+
+use super::protocol::{self, Message};
+use super::quic::{QuicConnection, QuicStream};
+use super::NetworkError;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Label of the keying material proofs are derived from
+const EXPORT_LABEL: &[u8] = b"EXPORTER-lan-meeting-password";
+
+/// Handshake refusal reasons; the connecting side asks for the password
+/// when it gets one of these
+pub const MISSING: &str = "Meeting password required";
+pub const WRONG: &str = "Wrong meeting password";
+
+/// Passwords typed to join peers, by peer IP
+static ENTERED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Certificate fingerprints let in, with the password they were let in under
+static ADMITTED: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Proofs received ahead of a handshake, by connection (remote address)
+static OFFERED: Lazy<Mutex<HashMap<String, Vec<u8>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn digest(secret: &[u8; 32], password: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(EXPORT_LABEL);
+    hasher.update(secret);
+    hasher.update(password.as_bytes());
+    hasher.finalize().into()
+}
+
+/// Compare without giving away how much matched
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn secret(conn: &QuicConnection) -> Option<[u8; 32]> {
+    let mut secret = [0u8; 32];
+    match conn.export_keying_material(&mut secret, EXPORT_LABEL) {
+        Ok(()) => Some(secret),
+        Err(e) => {
+            log::warn!("No keying material for the meeting password of {}: {}", conn.peer_ip(), e);
+            None
+        }
+    }
+}
+
+/// Whether a handshake refusal was about the meeting password
+pub fn is_refusal(reason: &str) -> bool {
+    reason == MISSING || reason == WRONG
+}
+
+/// Keep the password the user typed for `peer_ip`; an empty one forgets it
+pub fn remember(peer_ip: &str, password: &str) {
+    let mut entered = ENTERED.lock();
+    if password.is_empty() {
+        entered.remove(peer_ip);
+    } else {
+        entered.insert(peer_ip.to_string(), password.to_string());
+    }
+}
+
+/// Proof of the password typed for the peer of `conn`, for our handshake
+pub fn proof(conn: &QuicConnection) -> Option<Vec<u8>> {
+    let password = ENTERED.lock().get(&conn.peer_ip()).cloned()?;
+    Some(digest(&secret(conn)?, &password).to_vec())
+}
+
+/// Send the proof of the password typed for the peer, if any, on the
+/// stream our handshake is about to go on
+pub async fn send_proof(conn: &QuicConnection, stream: &mut QuicStream) -> Result<(), NetworkError> {
+    match proof(conn) {
+        Some(proof) => {
+            let msg = Message::PasswordProof { proof };
+            stream.send_framed(&protocol::encode_as(&msg, protocol::LEGACY_VERSION)?).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// A proof arrived on `conn`, for the handshake that follows it
+pub fn offered(conn: &QuicConnection, proof: &[u8]) {
+    OFFERED.lock().insert(conn.remote_addr().to_string(), proof.to_vec());
+}
+
+/// Let the peer of `conn` in without proof from now on: we connected to it
+pub fn admit(conn: &QuicConnection) {
+    let password = crate::commands::meeting_password();
+    if let (false, Some(fingerprint)) = (password.is_empty(), conn.peer_fingerprint()) {
+        ADMITTED.lock().insert(fingerprint.to_string(), password);
+    }
+}
+
+/// Check the proof that came ahead of a handshake received on `conn`; the
+/// error is the reason to refuse it with
+pub fn check(conn: &QuicConnection) -> Result<(), &'static str> {
+    let proof = OFFERED.lock().remove(&conn.remote_addr().to_string());
+    let password = crate::commands::meeting_password();
+    if password.is_empty() {
+        return Ok(());
+    }
+    let fingerprint = conn.peer_fingerprint().unwrap_or_default();
+    if ADMITTED.lock().get(fingerprint) == Some(&password) {
+        return Ok(());
+    }
+    let proof = proof.ok_or(MISSING)?;
+    let secret = secret(conn).ok_or(WRONG)?;
+    if !same(&digest(&secret, &password), &proof) {
+        log::warn!("Wrong meeting password from {}", conn.peer_ip());
+        return Err(WRONG);
+    }
+    if !fingerprint.is_empty() {
+        ADMITTED.lock().insert(fingerprint.to_string(), password);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proof_depends_on_session_and_password() {
+        let proof = digest(&[1; 32], "room 42");
+        assert!(same(&proof, &digest(&[1; 32], "room 42")));
+        assert!(!same(&proof, &digest(&[2; 32], "room 42")));
+        assert!(!same(&proof, &digest(&[1; 32], "room 43")));
+        assert!(!same(&proof, &proof[..16]));
+    }
+}
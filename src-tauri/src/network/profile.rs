@@ -0,0 +1,112 @@
+//! Display name and avatar
+//!
+//! Peers introduce themselves with the name set in settings (the hostname
+//! when none is set) in the handshake, and follow it with an optional small
+//! avatar. The avatar is cropped to a square and scaled down to a PNG of at
+//! most `MAX_AVATAR_BYTES` when it is picked, and anything a peer sends us
+//! that is larger or not a PNG is dropped, so an avatar or roster stays small.
+
+use super::protocol::{self, MemberProfile, Message, MAX_AVATAR_BYTES};
+use super::quic::QuicStream;
+use super::NetworkError;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Edge of the square avatar, in pixels; halved if the PNG is too large
+const AVATAR_SIZE: u32 = 96;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Turn an image file into an avatar PNG
+pub fn avatar_from_file(path: &Path) -> Result<Vec<u8>, String> {
+    let image = image::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    make_avatar(&image)
+}
+
+/// Center-crop `image` to a square and encode it small enough to send
+fn make_avatar(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let side = image.width().min(image.height());
+    if side == 0 {
+        return Err("Image is empty".to_string());
+    }
+    let square = image.crop_imm((image.width() - side) / 2, (image.height() - side) / 2, side, side);
+
+    let mut size = AVATAR_SIZE.min(side);
+    loop {
+        let mut png = Vec::new();
+        square
+            .resize_exact(size, size, FilterType::Triangle)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode avatar: {}", e))?;
+        if png.len() <= MAX_AVATAR_BYTES {
+            return Ok(png);
+        }
+        if size <= AVATAR_SIZE / 4 {
+            return Err("Avatar image is too detailed to fit".to_string());
+        }
+        size /= 2;
+    }
+}
+
+/// A peer's avatar as base64 for the UI, if it is a PNG within the limit
+pub fn checked_avatar(avatar: Option<&[u8]>) -> Option<String> {
+    let avatar = avatar?;
+    if avatar.len() > MAX_AVATAR_BYTES || !avatar.starts_with(PNG_SIGNATURE) {
+        log::warn!("Ignoring avatar of {} bytes that is not a small PNG", avatar.len());
+        return None;
+    }
+    Some(base64::engine::general_purpose::STANDARD.encode(avatar))
+}
+
+/// Our avatar as PNG bytes, as sent to peers
+pub fn our_avatar() -> Option<Vec<u8>> {
+    let encoded = crate::commands::avatar_setting();
+    if encoded.is_empty() {
+        return None;
+    }
+    base64::engine::general_purpose::STANDARD.decode(encoded).ok()
+}
+
+/// Send our avatar on the handshake stream `stream`
+pub async fn send_avatar(stream: &mut QuicStream) -> Result<(), NetworkError> {
+    stream.send_framed(&protocol::encode(&Message::Avatar { avatar: our_avatar() })?).await
+}
+
+/// Apply names and avatars a meeting host sent along with the roster to
+/// the devices we know
+pub fn apply_member_profiles(profiles: &[MemberProfile]) {
+    let known = super::discovery::get_devices();
+    for profile in profiles {
+        let Some(device) = known.iter().find(|d| d.id == profile.device_id) else {
+            continue;
+        };
+        // The host may not have the avatar of a member it never shook hands with
+        let avatar = checked_avatar(profile.avatar.as_deref()).or_else(|| device.avatar.clone());
+        super::discovery::set_device_profile(&device.id, &profile.name, avatar);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avatar_cropped_and_bounded() {
+        let image = DynamicImage::new_rgb8(400, 200);
+        let png = make_avatar(&image).unwrap();
+        assert!(png.len() <= MAX_AVATAR_BYTES);
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (AVATAR_SIZE, AVATAR_SIZE));
+
+        // Small images are not scaled up
+        let png = make_avatar(&DynamicImage::new_rgb8(40, 60)).unwrap();
+        assert_eq!(image::load_from_memory(&png).unwrap().width(), 40);
+
+        assert!(checked_avatar(Some(png.as_slice())).is_some());
+        assert!(checked_avatar(Some(&b"GIF89a"[..])).is_none());
+        assert!(checked_avatar(Some(&[0; MAX_AVATAR_BYTES + 1][..])).is_none());
+    }
+}
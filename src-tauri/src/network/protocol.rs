@@ -2,19 +2,58 @@
 // Binary message format for efficient transmission
 
 use super::NetworkError;
+use bincode::Options;
 use bytes::{Buf, BufMut, BytesMut};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 /// Magic bytes for protocol identification
 pub const MAGIC: [u8; 2] = [0x4C, 0x4D]; // "LM"
-pub const VERSION: u8 = 1;
+
+/// Frame version written: the payload has varint integers, lengths and
+/// variant tags, which makes most control messages a fraction of the size
+pub const VERSION: u8 = 2;
+
+/// Frame version of builds before the compact encoding, with fixed-width
+/// integers throughout. Still read, and what goes out before the peer's
+/// protocol is known (the handshake, the password proof, a refusal) is
+/// written in it, so a build that old can still read why it is turned away.
+pub const LEGACY_VERSION: u8 = 1;
 
 /// Maximum message size (16MB)
 pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
 
+/// Largest avatar PNG a peer may send in a handshake or roster
+pub const MAX_AVATAR_BYTES: usize = 16 * 1024;
+
 /// Header size: magic(2) + version(1) + type(1) + length(4)
 pub const HEADER_SIZE: usize = 8;
 
+/// Largest length-prefixed frame read off a stream
+pub const MAX_FRAME_SIZE: usize = HEADER_SIZE + MAX_MESSAGE_SIZE;
+
+/// Decode errors in a row after which a codec gives up on its peer
+pub const MAX_DECODE_ERRORS: u32 = 16;
+
+/// Why bytes did not decode to a message
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("Data too short: {have} bytes (need at least {need})")]
+    TooShort { have: usize, need: usize },
+    #[error("Invalid magic bytes: {:02X}{:02X}", .0[0], .0[1])]
+    BadMagic([u8; 2]),
+    #[error("Unsupported frame version: {0} (expected {} or {})", VERSION, LEGACY_VERSION)]
+    UnsupportedVersion(u8),
+    #[error("Unknown message type: 0x{0:02X}")]
+    UnknownType(u8),
+    #[error("Message too large: {len} bytes (max {max})")]
+    TooLarge { len: usize, max: usize },
+    #[error("Incomplete message: have {have} bytes, need {need}")]
+    Incomplete { have: usize, need: usize },
+    #[error("Deserialization error: {0}")]
+    Malformed(String),
+}
+
 /// Message type IDs for efficient encoding
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -25,6 +64,10 @@ pub enum MessageType {
     Disconnect = 0x02,
     Heartbeat = 0x03,
     HeartbeatAck = 0x04,
+    AddressUpdate = 0x05,
+    Avatar = 0x06,
+    PasswordProof = 0x07,
+    Capabilities = 0x08,
 
     // Screen sharing (0x10-0x1F)
     ScreenOffer = 0x10,
@@ -32,6 +75,11 @@ pub enum MessageType {
     ScreenStart = 0x12,
     ScreenFrame = 0x13,
     ScreenStop = 0x14,
+    ScreenLeave = 0x15,
+    KeyframeRequest = 0x16,
+    ScreenPause = 0x17,
+    ScreenReject = 0x18,
+    ScreenRecording = 0x19,
 
     // Remote control (0x20-0x2F)
     ControlRequest = 0x20,
@@ -49,13 +97,28 @@ pub enum MessageType {
     FileChunk = 0x43,
     FileComplete = 0x44,
     FileCancel = 0x45,
+    FileData = 0x46,
 
     // Simple streaming (0x50-0x5F)
     SimpleScreenRequest = 0x50,
+
+    // Scheduled meetings (0x60-0x6F)
+    MeetingInvite = 0x60,
+    MeetingResponse = 0x61,
+    MeetingCancel = 0x62,
+    MeetingRoster = 0x63,
+    MeetingHostChanged = 0x64,
+    MeetingFilesRequest = 0x65,
+
+    // Clipboard (0x70-0x7F)
+    ClipboardItem = 0x70,
+
+    // Links (0x80-0x8F)
+    OpenUrl = 0x80,
 }
 
 impl TryFrom<u8> for MessageType {
-    type Error = NetworkError;
+    type Error = DecodeError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -64,11 +127,20 @@ impl TryFrom<u8> for MessageType {
             0x02 => Ok(Self::Disconnect),
             0x03 => Ok(Self::Heartbeat),
             0x04 => Ok(Self::HeartbeatAck),
+            0x05 => Ok(Self::AddressUpdate),
+            0x06 => Ok(Self::Avatar),
+            0x07 => Ok(Self::PasswordProof),
+            0x08 => Ok(Self::Capabilities),
             0x10 => Ok(Self::ScreenOffer),
             0x11 => Ok(Self::ScreenRequest),
             0x12 => Ok(Self::ScreenStart),
             0x13 => Ok(Self::ScreenFrame),
             0x14 => Ok(Self::ScreenStop),
+            0x15 => Ok(Self::ScreenLeave),
+            0x16 => Ok(Self::KeyframeRequest),
+            0x17 => Ok(Self::ScreenPause),
+            0x18 => Ok(Self::ScreenReject),
+            0x19 => Ok(Self::ScreenRecording),
             0x20 => Ok(Self::ControlRequest),
             0x21 => Ok(Self::ControlGrant),
             0x22 => Ok(Self::ControlRevoke),
@@ -80,16 +152,25 @@ impl TryFrom<u8> for MessageType {
             0x43 => Ok(Self::FileChunk),
             0x44 => Ok(Self::FileComplete),
             0x45 => Ok(Self::FileCancel),
+            0x46 => Ok(Self::FileData),
             0x50 => Ok(Self::SimpleScreenRequest),
-            _ => Err(NetworkError::ProtocolError(format!(
-                "Unknown message type: 0x{:02X}",
-                value
-            ))),
+            0x60 => Ok(Self::MeetingInvite),
+            0x61 => Ok(Self::MeetingResponse),
+            0x62 => Ok(Self::MeetingCancel),
+            0x63 => Ok(Self::MeetingRoster),
+            0x64 => Ok(Self::MeetingHostChanged),
+            0x65 => Ok(Self::MeetingFilesRequest),
+            0x70 => Ok(Self::ClipboardItem),
+            0x80 => Ok(Self::OpenUrl),
+            _ => Err(DecodeError::UnknownType(value)),
         }
     }
 }
 
 /// Message types for the protocol
+///
+/// bincode numbers variants and reads fields by position, so new variants
+/// go at the end and new fields after the existing ones.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
     // Connection management
@@ -126,19 +207,27 @@ pub enum Message {
         preferred_fps: u8,
         preferred_quality: u8,
     },
+    /// Sharer → viewer: one shared display is about to stream
     ScreenStart {
         width: u32,
         height: u32,
         fps: u8,
         codec: String,
+        /// See `ColorSpace::name`
+        color_space: String,
+        display_id: u32,
     },
     ScreenFrame {
         timestamp: u64,
         frame_type: FrameType,
         sequence: u32,
         data: Vec<u8>,
+        display_id: u32,
+    },
+    /// Sharer → viewer: one display stopped streaming, or all of them when `None`
+    ScreenStop {
+        display_id: Option<u32>,
     },
-    ScreenStop,
 
     // Remote control
     ControlRequest {
@@ -191,6 +280,120 @@ pub enum Message {
     SimpleScreenRequest {
         display_id: u32,
     },
+
+    // Scheduled meetings
+    MeetingInvite {
+        meeting_id: String,
+        name: String,
+        host_id: String,
+        host_name: String,
+        start_time: u64,
+    },
+    MeetingResponse {
+        meeting_id: String,
+        device_id: String,
+        accepted: bool,
+    },
+    MeetingCancel {
+        meeting_id: String,
+    },
+    MeetingRoster {
+        meeting_id: String,
+        /// Device IDs of members who accepted
+        members: Vec<String>,
+        /// Names and avatars of the host and members, as the host knows them
+        profiles: Vec<MemberProfile>,
+    },
+    MeetingHostChanged {
+        meeting_id: String,
+        host_id: String,
+        host_name: String,
+    },
+    /// A member that joined late asks for files offered to the meeting
+    /// before it joined
+    MeetingFilesRequest {
+        meeting_id: String,
+    },
+
+    // Clipboard
+    /// The sender copied something; added to the history shared with it
+    ClipboardItem {
+        id: String,
+        from: String,
+        content: ClipboardContent,
+        timestamp: u64,
+    },
+
+    // Links
+    /// Open this link; the receiver's user confirms first
+    OpenUrl {
+        url: String,
+        from: String,
+    },
+
+    // Addresses
+    /// The sender's local addresses changed (DHCP renewal, VPN toggle)
+    AddressUpdate {
+        device_id: String,
+        addresses: Vec<String>,
+        port: u16,
+    },
+
+    // Screen sharing
+    /// Viewer → sharer: this viewer stopped watching one display, or all of them when `None`
+    ScreenLeave {
+        display_id: Option<u32>,
+    },
+    /// Viewer → sharer: playback of this display stalled or a frame failed
+    /// to decode, send a keyframe
+    KeyframeRequest {
+        display_id: u32,
+    },
+    /// Sharer → viewer: the sharer froze (or unfroze) every shared display
+    ScreenPause {
+        paused: bool,
+    },
+    /// Sharer → viewer: a ScreenRequest was turned down
+    ScreenReject {
+        display_id: u32,
+        reason: ScreenRejectReason,
+    },
+    /// Viewer → sharer: the viewer started (or stopped) recording the
+    /// sharer's screen
+    ScreenRecording {
+        recording: bool,
+    },
+
+    // File transfer
+    /// A whole small file (`transfer::SMALL_FILE_MAX`), sent instead of
+    /// chunks once the offer is accepted
+    FileData {
+        file_id: String,
+        data: Vec<u8>,
+    },
+
+    // Profiles
+    /// The sender's avatar, sent on the handshake stream once the handshake
+    /// is answered (ahead of an accepting answer, after it): PNG, at most
+    /// `MAX_AVATAR_BYTES`
+    Avatar {
+        avatar: Option<Vec<u8>>,
+    },
+
+    // Meeting password
+    /// Proof of the meeting password (see `password::proof`), sent on the
+    /// handshake stream right before the handshake
+    PasswordProof {
+        proof: Vec<u8>,
+    },
+
+    // Capabilities
+    /// Tokens of `capability::Capabilities`, as in the handshake: the
+    /// answering side's, sent on the handshake stream ahead of an accepting
+    /// answer
+    Capabilities {
+        tokens: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -202,6 +405,51 @@ pub struct DisplayInfo {
     pub primary: bool,
 }
 
+/// Name and avatar of a meeting member, sent with the roster
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberProfile {
+    pub device_id: String,
+    pub name: String,
+    /// PNG, at most `MAX_AVATAR_BYTES`
+    pub avatar: Option<Vec<u8>>,
+}
+
+/// Why a sharer turned down a ScreenRequest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScreenRejectReason {
+    /// The sharer's viewer limit is reached
+    StreamFull { max_viewers: u32 },
+    /// The sharer did not let the viewer in
+    Declined,
+    /// The sharer removed the viewer
+    Kicked,
+    /// The viewer does not decode the codec the stream is encoded with
+    UnsupportedCodec { codec: String },
+}
+
+/// Clipboard content shared between peers
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClipboardContent {
+    Text(String),
+    /// PNG image, base64 encoded
+    Image {
+        width: u32,
+        height: u32,
+        png_base64: String,
+    },
+    /// Copied files; each follows as a file transfer offer
+    Files(Vec<ClipboardFile>),
+}
+
+/// A copied file, sent with a file transfer
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClipboardFile {
+    /// Id of the transfer offer that follows
+    pub file_id: String,
+    pub name: String,
+    pub size: u64,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum FrameType {
     KeyFrame,
@@ -216,6 +464,8 @@ pub enum InputEventType {
     MouseScroll,
     KeyDown,
     KeyUp,
+    /// Composed text from the viewer's IME or keyboard layout
+    TextInput,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -231,6 +481,10 @@ pub enum InputData {
         key_code: u32,
         modifiers: Modifiers,
     },
+    /// UTF-8 text typed on the sharer through its text input API
+    Text {
+        text: String,
+    },
     None,
 }
 
@@ -258,11 +512,20 @@ impl Message {
             Message::Disconnect { .. } => MessageType::Disconnect,
             Message::Heartbeat { .. } => MessageType::Heartbeat,
             Message::HeartbeatAck { .. } => MessageType::HeartbeatAck,
+            Message::AddressUpdate { .. } => MessageType::AddressUpdate,
+            Message::Avatar { .. } => MessageType::Avatar,
+            Message::PasswordProof { .. } => MessageType::PasswordProof,
+            Message::Capabilities { .. } => MessageType::Capabilities,
             Message::ScreenOffer { .. } => MessageType::ScreenOffer,
             Message::ScreenRequest { .. } => MessageType::ScreenRequest,
             Message::ScreenStart { .. } => MessageType::ScreenStart,
             Message::ScreenFrame { .. } => MessageType::ScreenFrame,
-            Message::ScreenStop => MessageType::ScreenStop,
+            Message::ScreenStop { .. } => MessageType::ScreenStop,
+            Message::ScreenLeave { .. } => MessageType::ScreenLeave,
+            Message::KeyframeRequest { .. } => MessageType::KeyframeRequest,
+            Message::ScreenPause { .. } => MessageType::ScreenPause,
+            Message::ScreenReject { .. } => MessageType::ScreenReject,
+            Message::ScreenRecording { .. } => MessageType::ScreenRecording,
             Message::ControlRequest { .. } => MessageType::ControlRequest,
             Message::ControlGrant { .. } => MessageType::ControlGrant,
             Message::ControlRevoke => MessageType::ControlRevoke,
@@ -274,15 +537,45 @@ impl Message {
             Message::FileChunk { .. } => MessageType::FileChunk,
             Message::FileComplete { .. } => MessageType::FileComplete,
             Message::FileCancel { .. } => MessageType::FileCancel,
+            Message::FileData { .. } => MessageType::FileData,
             Message::SimpleScreenRequest { .. } => MessageType::SimpleScreenRequest,
+            Message::MeetingInvite { .. } => MessageType::MeetingInvite,
+            Message::MeetingResponse { .. } => MessageType::MeetingResponse,
+            Message::MeetingCancel { .. } => MessageType::MeetingCancel,
+            Message::MeetingRoster { .. } => MessageType::MeetingRoster,
+            Message::MeetingHostChanged { .. } => MessageType::MeetingHostChanged,
+            Message::MeetingFilesRequest { .. } => MessageType::MeetingFilesRequest,
+            Message::ClipboardItem { .. } => MessageType::ClipboardItem,
+            Message::OpenUrl { .. } => MessageType::OpenUrl,
         }
     }
 }
 
-/// Encode a message to bytes
-/// Format: MAGIC(2) + VERSION(1) + TYPE(1) + LENGTH(4) + PAYLOAD
-pub fn encode(msg: &Message) -> Result<Vec<u8>, NetworkError> {
-    let payload = bincode::serialize(msg)
+/// Payload of `msg` in the encoding of frame `version`
+fn serialize(msg: &Message, version: u8) -> bincode::Result<Vec<u8>> {
+    let options = bincode::DefaultOptions::new();
+    if version == LEGACY_VERSION {
+        // What bincode::serialize writes
+        options.with_fixint_encoding().allow_trailing_bytes().serialize(msg)
+    } else {
+        options.serialize(msg)
+    }
+}
+
+/// Read a payload in the encoding of frame `version`; no length inside it
+/// can make this read (or allocate) past its end
+fn deserialize(payload: &[u8], version: u8) -> bincode::Result<Message> {
+    let options = bincode::DefaultOptions::new().with_limit(payload.len() as u64);
+    if version == LEGACY_VERSION {
+        options.with_fixint_encoding().allow_trailing_bytes().deserialize(payload)
+    } else {
+        options.deserialize(payload)
+    }
+}
+
+/// Encode a message in the encoding of frame `version`
+pub fn encode_as(msg: &Message, version: u8) -> Result<Vec<u8>, NetworkError> {
+    let payload = serialize(msg, version)
         .map_err(|e| NetworkError::ProtocolError(format!("Serialization error: {}", e)))?;
 
     if payload.len() > MAX_MESSAGE_SIZE {
@@ -298,7 +591,7 @@ pub fn encode(msg: &Message) -> Result<Vec<u8>, NetworkError> {
 
     let mut buf = Vec::with_capacity(HEADER_SIZE + payload.len());
     buf.extend_from_slice(&MAGIC);
-    buf.push(VERSION);
+    buf.push(version);
     buf.push(msg_type);
     buf.extend_from_slice(&len.to_be_bytes());
     buf.extend_from_slice(&payload);
@@ -306,30 +599,33 @@ pub fn encode(msg: &Message) -> Result<Vec<u8>, NetworkError> {
     Ok(buf)
 }
 
+/// Encode a message to bytes
+/// Format: MAGIC(2) + VERSION(1) + TYPE(1) + LENGTH(4) + PAYLOAD
+pub fn encode(msg: &Message) -> Result<Vec<u8>, NetworkError> {
+    encode_as(msg, VERSION)
+}
+
 /// Decode bytes to a message
 pub fn decode(data: &[u8]) -> Result<Message, NetworkError> {
+    Ok(decode_frame(data)?)
+}
+
+/// Decode one frame. Any input is safe: nothing is trusted or allocated
+/// before it is checked against the frame and `MAX_MESSAGE_SIZE`.
+pub fn decode_frame(data: &[u8]) -> Result<Message, DecodeError> {
     if data.len() < HEADER_SIZE {
-        return Err(NetworkError::ProtocolError(format!(
-            "Data too short: {} bytes (need at least {})",
-            data.len(),
-            HEADER_SIZE
-        )));
+        return Err(DecodeError::TooShort { have: data.len(), need: HEADER_SIZE });
     }
 
     // Verify magic
     if data[0..2] != MAGIC {
-        return Err(NetworkError::ProtocolError(format!(
-            "Invalid magic bytes: {:02X}{:02X}",
-            data[0], data[1]
-        )));
+        return Err(DecodeError::BadMagic([data[0], data[1]]));
     }
 
     // Verify version
-    if data[2] != VERSION {
-        return Err(NetworkError::ProtocolError(format!(
-            "Unsupported protocol version: {} (expected {})",
-            data[2], VERSION
-        )));
+    let version = data[2];
+    if version != VERSION && version != LEGACY_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
     }
 
     // Get message type (for validation)
@@ -339,27 +635,29 @@ pub fn decode(data: &[u8]) -> Result<Message, NetworkError> {
     let len = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
 
     if len > MAX_MESSAGE_SIZE {
-        return Err(NetworkError::ProtocolError(format!(
-            "Message too large: {} bytes (max {})",
-            len, MAX_MESSAGE_SIZE
-        )));
+        return Err(DecodeError::TooLarge { len, max: MAX_MESSAGE_SIZE });
     }
 
     if data.len() < HEADER_SIZE + len {
-        return Err(NetworkError::ProtocolError(format!(
-            "Incomplete message: have {} bytes, need {}",
-            data.len(),
-            HEADER_SIZE + len
-        )));
+        return Err(DecodeError::Incomplete { have: data.len(), need: HEADER_SIZE + len });
     }
 
-    bincode::deserialize(&data[HEADER_SIZE..HEADER_SIZE + len])
-        .map_err(|e| NetworkError::ProtocolError(format!("Deserialization error: {}", e)))
+    deserialize(&data[HEADER_SIZE..HEADER_SIZE + len], version)
+        .map_err(|e| DecodeError::Malformed(e.to_string()))
 }
 
 /// Streaming message codec for handling partial reads
+///
+/// A bad frame costs only itself: one that fails to decode is dropped
+/// whole, one too large is dropped as it arrives without being buffered,
+/// and after bytes that are not a header at all the codec skips to the
+/// next magic. The caller gives up on the peer once `gave_up` says so.
 pub struct MessageCodec {
     buffer: BytesMut,
+    /// Bytes of a dropped frame that have not arrived yet
+    skip: usize,
+    /// Decode errors since the last message
+    errors: u32,
 }
 
 impl Default for MessageCodec {
@@ -372,29 +670,40 @@ impl MessageCodec {
     pub fn new() -> Self {
         Self {
             buffer: BytesMut::with_capacity(64 * 1024), // 64KB initial buffer
+            skip: 0,
+            errors: 0,
         }
     }
 
     /// Feed data into the codec
     pub fn feed(&mut self, data: &[u8]) {
-        self.buffer.put_slice(data);
+        let skipped = self.skip.min(data.len());
+        self.skip -= skipped;
+        self.buffer.put_slice(&data[skipped..]);
+    }
+
+    /// Try to decode a complete message from the buffer. After an error
+    /// the offending bytes are gone, so the caller can call again.
+    pub fn decode(&mut self) -> Result<Option<Message>, DecodeError> {
+        let decoded = self.next_message();
+        match decoded {
+            Ok(Some(_)) => self.errors = 0,
+            Err(_) => self.errors += 1,
+            Ok(None) => {}
+        }
+        decoded
     }
 
-    /// Try to decode a complete message from the buffer
-    pub fn decode(&mut self) -> Result<Option<Message>, NetworkError> {
+    fn next_message(&mut self) -> Result<Option<Message>, DecodeError> {
         if self.buffer.len() < HEADER_SIZE {
             return Ok(None); // Need more data
         }
 
         // Verify magic
         if self.buffer[0..2] != MAGIC {
-            // Invalid data, try to find next valid header
-            if let Some(pos) = self.find_magic() {
-                self.buffer.advance(pos);
-            } else {
-                self.buffer.clear();
-            }
-            return Err(NetworkError::ProtocolError("Invalid magic bytes".to_string()));
+            let magic = [self.buffer[0], self.buffer[1]];
+            self.resync();
+            return Err(DecodeError::BadMagic(magic));
         }
 
         // Get payload length
@@ -406,12 +715,12 @@ impl MessageCodec {
         ]) as usize;
 
         if len > MAX_MESSAGE_SIZE {
-            // Skip this message
+            // Drop the payload rather than look for a header inside it
             self.buffer.advance(HEADER_SIZE);
-            return Err(NetworkError::ProtocolError(format!(
-                "Message too large: {}",
-                len
-            )));
+            let buffered = len.min(self.buffer.len());
+            self.buffer.advance(buffered);
+            self.skip = len - buffered;
+            return Err(DecodeError::TooLarge { len, max: MAX_MESSAGE_SIZE });
         }
 
         let total_len = HEADER_SIZE + len;
@@ -419,18 +728,28 @@ impl MessageCodec {
             return Ok(None); // Need more data
         }
 
-        // Decode the message
+        // Decode the message; the frame goes either way
         let msg_data = self.buffer.split_to(total_len);
-        let msg = decode(&msg_data)?;
+        decode_frame(&msg_data).map(Some)
+    }
 
-        Ok(Some(msg))
+    /// Drop bytes up to the next magic, keeping a first magic byte at the
+    /// end in case the rest of it is still to come
+    fn resync(&mut self) {
+        match self.buffer[1..].windows(2).position(|w| w == MAGIC) {
+            Some(pos) => self.buffer.advance(pos + 1),
+            None => {
+                let keep = usize::from(self.buffer.last() == Some(&MAGIC[0]));
+                let len = self.buffer.len();
+                self.buffer.advance(len - keep);
+            }
+        }
     }
 
-    /// Find the next magic bytes in the buffer
-    fn find_magic(&self) -> Option<usize> {
-        self.buffer
-            .windows(2)
-            .position(|w| w == MAGIC)
+    /// Whether the peer sent so many bad frames in a row that it is not
+    /// worth listening to any more
+    pub fn gave_up(&self) -> bool {
+        self.errors >= MAX_DECODE_ERRORS
     }
 
     /// Encode a message and return the bytes
@@ -441,6 +760,8 @@ impl MessageCodec {
     /// Clear the buffer
     pub fn clear(&mut self) {
         self.buffer.clear();
+        self.skip = 0;
+        self.errors = 0;
     }
 
     /// Get buffer length
@@ -449,23 +770,51 @@ impl MessageCodec {
     }
 }
 
+/// What this build can do, as announced in handshakes and mDNS
+pub fn capabilities() -> Vec<String> {
+    super::capability::Capabilities::ours().tokens()
+}
+
+/// Whether a peer running app version `theirs` can talk to us: the same
+/// major version, and the same minor while still at 0.x
+pub fn is_compatible_version(theirs: &str) -> bool {
+    same_release(env!("CARGO_PKG_VERSION"), theirs)
+}
+
+fn same_release(ours: &str, theirs: &str) -> bool {
+    let release = |v: &str| -> Option<(u64, u64)> {
+        let mut parts = v.trim().trim_start_matches('v').split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().and_then(|m| m.parse().ok()).unwrap_or(0);
+        Some(if major == 0 { (0, minor) } else { (major, 0) })
+    };
+    match (release(ours), release(theirs)) {
+        (Some(ours), Some(theirs)) => ours == theirs,
+        // Unknown versions get the benefit of the doubt
+        _ => true,
+    }
+}
+
 /// Create a handshake message
-pub fn create_handshake(device_id: &str, name: &str) -> Message {
+pub fn create_handshake(
+    device_id: &str,
+    name: &str,
+) -> Message {
     Message::Handshake {
         device_id: device_id.to_string(),
         name: name.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        capabilities: vec![
-            "screen-share".to_string(),
-            "remote-control".to_string(),
-            "chat".to_string(),
-            "file-transfer".to_string(),
-        ],
+        capabilities: capabilities(),
     }
 }
 
 /// Create a handshake acknowledgment
-pub fn create_handshake_ack(device_id: &str, name: &str, accepted: bool, reason: Option<String>) -> Message {
+pub fn create_handshake_ack(
+    device_id: &str,
+    name: &str,
+    accepted: bool,
+    reason: Option<String>,
+) -> Message {
     Message::HandshakeAck {
         device_id: device_id.to_string(),
         name: name.to_string(),
@@ -501,3 +850,104 @@ pub fn create_heartbeat_ack(original_timestamp: u64) -> Message {
         latency_ms: (now.saturating_sub(original_timestamp)) as u32,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_release() {
+        assert!(same_release("0.1.0", "0.1.7"));
+        assert!(!same_release("0.1.0", "0.2.0"));
+        assert!(same_release("1.2.0", "1.9.3"));
+        assert!(!same_release("1.2.0", "2.0.0"));
+        assert!(same_release("0.1.0", "v0.1.2"));
+        assert!(same_release("0.1.0", "dev"));
+    }
+
+    fn frame(msg: &Message) -> Vec<u8> {
+        encode(msg).unwrap()
+    }
+
+    #[test]
+    fn test_decode_frame_rejects_bad_input() {
+        let msg = frame(&Message::Disconnect { reason: "see you next week".into() });
+        assert!(matches!(decode_frame(&msg), Ok(Message::Disconnect { .. })));
+
+        assert_eq!(decode_frame(&msg[..4]).unwrap_err(), DecodeError::TooShort { have: 4, need: HEADER_SIZE });
+        assert!(matches!(decode_frame(&msg[..msg.len() - 1]), Err(DecodeError::Incomplete { .. })));
+
+        let mut bad = msg.clone();
+        bad[0] = b'X';
+        assert_eq!(decode_frame(&bad).unwrap_err(), DecodeError::BadMagic([b'X', b'M']));
+        let mut bad = msg.clone();
+        bad[2] = 9;
+        assert_eq!(decode_frame(&bad).unwrap_err(), DecodeError::UnsupportedVersion(9));
+        let mut bad = msg.clone();
+        bad[3] = 0xFF;
+        assert_eq!(decode_frame(&bad).unwrap_err(), DecodeError::UnknownType(0xFF));
+        let mut bad = msg.clone();
+        bad[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(decode_frame(&bad), Err(DecodeError::TooLarge { .. })));
+
+        // A string claiming to be longer than the payload: its length
+        // becomes a u64 read from the text
+        let mut bad = msg.clone();
+        bad[HEADER_SIZE + 1] = 0xFD;
+        assert!(matches!(decode_frame(&bad), Err(DecodeError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_legacy_frames() {
+        let msg = Message::ChatMessage { from: "Alice".into(), content: "hi".into(), timestamp: 1_700_000_000_000 };
+        let compact = frame(&msg);
+        let legacy = encode_as(&msg, LEGACY_VERSION).unwrap();
+        assert_eq!(compact[2], VERSION);
+        assert_eq!(legacy[2], LEGACY_VERSION);
+        assert!(compact.len() < legacy.len());
+
+        // What builds before the compact encoding sent
+        let old = bincode::serialize(&msg).unwrap();
+        assert_eq!(&legacy[HEADER_SIZE..], &old[..]);
+        for data in [&compact, &legacy] {
+            let Ok(Message::ChatMessage { content, timestamp, .. }) = decode_frame(data) else {
+                panic!("not decoded");
+            };
+            assert_eq!((content.as_str(), timestamp), ("hi", 1_700_000_000_000));
+        }
+    }
+
+    #[test]
+    fn test_codec_recovers_from_garbage() {
+        let heartbeat = frame(&create_heartbeat());
+        let mut codec = MessageCodec::new();
+
+        // Garbage before a message, which arrives in two pieces
+        codec.feed(b"garbage!");
+        codec.feed(&heartbeat[..3]);
+        assert!(matches!(codec.decode(), Err(DecodeError::BadMagic(_))));
+        assert!(matches!(codec.decode(), Ok(None)));
+        codec.feed(&heartbeat[3..]);
+        assert!(matches!(codec.decode(), Ok(Some(Message::Heartbeat { .. }))));
+
+        // An oversized frame is dropped as it arrives, not buffered
+        let mut huge = heartbeat[..HEADER_SIZE].to_vec();
+        let len = MAX_MESSAGE_SIZE + 10;
+        huge[4..8].copy_from_slice(&(len as u32).to_be_bytes());
+        codec.feed(&huge);
+        assert!(matches!(codec.decode(), Err(DecodeError::TooLarge { .. })));
+        codec.feed(&vec![b'L'; len - 4]);
+        assert_eq!(codec.buffer_len(), 0);
+        codec.feed(&[b'M'; 4]);
+        codec.feed(&heartbeat);
+        assert!(matches!(codec.decode(), Ok(Some(Message::Heartbeat { .. }))));
+        assert!(matches!(codec.decode(), Ok(None)));
+
+        // A peer that only sends garbage is given up on
+        for _ in 0..MAX_DECODE_ERRORS {
+            codec.feed(&[0xAB; HEADER_SIZE]);
+            assert!(codec.decode().is_err());
+        }
+        assert!(codec.gave_up());
+    }
+}
@@ -1,12 +1,17 @@
 //! QUIC-based P2P transport
 //! Low-latency, encrypted communication using quinn
 
-use super::NetworkError;
-use parking_lot::RwLock;
+use super::protocol;
+use super::retry::{self, Gate, RetryPolicy};
+use super::{ConnectErrorKind, NetworkError};
+use parking_lot::{Mutex, RwLock};
 use quinn::{ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig};
-use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,7 +29,8 @@ pub struct QuicConfig {
 impl Default for QuicConfig {
     fn default() -> Self {
         Self {
-            bind_addr: format!("0.0.0.0:{}", DEFAULT_PORT).parse().unwrap(),
+            // Dual-stack where the system allows it, see `bind_socket`
+            bind_addr: SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), DEFAULT_PORT),
             max_idle_timeout: Duration::from_secs(30),
             keep_alive_interval: Duration::from_secs(5),
         }
@@ -43,40 +49,201 @@ pub enum ConnectionState {
 pub static CONNECTIONS: once_cell::sync::Lazy<Arc<RwLock<HashMap<String, Arc<QuicConnection>>>>> =
     once_cell::sync::Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
 
+/// Outgoing connect attempts and why they failed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EndpointStats {
+    pub local_addr: String,
+    pub open_connections: usize,
+    pub connect_attempts: u64,
+    pub connect_failures: HashMap<ConnectErrorKind, u64>,
+}
+
+/// The certificate we present, as server and as client. It is generated
+/// once (self-signed, valid for centuries) and kept on disk with its key,
+/// so devices that paired with us still know us after a restart.
+struct Identity {
+    cert: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+    fingerprint: String,
+}
+
+static IDENTITY: once_cell::sync::OnceCell<Identity> = once_cell::sync::OnceCell::new();
+
+impl Identity {
+    fn new(cert: Vec<u8>, key: Vec<u8>) -> Self {
+        Self {
+            fingerprint: fingerprint(&cert),
+            cert: CertificateDer::from(cert),
+            key: PrivatePkcs8KeyDer::from(key),
+        }
+    }
+
+    /// Ours, from disk or generated the first time
+    fn ours() -> Result<&'static Self, NetworkError> {
+        IDENTITY.get_or_try_init(|| {
+            if let Some(identity) = Self::load() {
+                return Ok(identity);
+            }
+            let identity = Self::generate()?;
+            identity.save();
+            log::info!("Generated device certificate {}", identity.fingerprint);
+            Ok(identity)
+        })
+    }
+
+    /// Certificate and private key files, DER encoded
+    fn paths() -> Option<(PathBuf, PathBuf)> {
+        let dir = dirs::config_dir()?.join("lan-meeting");
+        Some((dir.join("identity.crt"), dir.join("identity.key")))
+    }
+
+    fn load() -> Option<Self> {
+        let (cert_path, key_path) = Self::paths()?;
+        let cert = std::fs::read(cert_path).ok()?;
+        let key = std::fs::read(&key_path).ok()?;
+        // A key that does not parse is replaced along with its certificate
+        if let Err(e) = rcgen::KeyPair::try_from(key.as_slice()) {
+            log::warn!("Ignoring unreadable key {}: {}", key_path.display(), e);
+            return None;
+        }
+        Some(Self::new(cert, key))
+    }
+
+    fn save(&self) {
+        let Some((cert_path, key_path)) = Self::paths() else {
+            log::warn!("Cannot determine where to keep the device certificate");
+            return;
+        };
+        if let Some(parent) = cert_path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let result = write_private(&key_path, self.key.secret_pkcs8_der())
+            .and_then(|()| std::fs::write(&cert_path, self.cert.as_ref()));
+        if let Err(e) = result {
+            log::error!("Failed to save the device certificate: {}", e);
+        }
+    }
+
+    /// Generate a self-signed certificate
+    fn generate() -> Result<Self, NetworkError> {
+        let cert = rcgen::generate_simple_self_signed(vec!["lan-meeting".to_string()])
+            .map_err(|e| NetworkError::ConnectionFailed(format!("Failed to generate cert: {}", e)))?;
+        Ok(Self::new(cert.cert.der().to_vec(), cert.key_pair.serialize_der()))
+    }
+
+    fn key(&self) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::Pkcs8(self.key.clone_key())
+    }
+}
+
+/// SHA-256 fingerprint of a DER certificate, as lowercase hex
+pub fn fingerprint(cert: &[u8]) -> String {
+    Sha256::digest(cert).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Fingerprint of our certificate, the one peers pin when they pair
+pub fn our_fingerprint() -> Option<String> {
+    Identity::ours().ok().map(|identity| identity.fingerprint.clone())
+}
+
+/// Write a file only we can read
+fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
 /// QUIC endpoint for P2P connections
 pub struct QuicEndpoint {
     endpoint: Endpoint,
     config: QuicConfig,
+    identity: &'static Identity,
+    stats: Mutex<EndpointStats>,
+}
+
+/// Classify a connect that could not even start
+fn connect_error(e: quinn::ConnectError) -> NetworkError {
+    let kind = match e {
+        quinn::ConnectError::InvalidRemoteAddress(_) | quinn::ConnectError::InvalidServerName(_) => {
+            ConnectErrorKind::InvalidAddress
+        }
+        quinn::ConnectError::EndpointStopping => ConnectErrorKind::ServiceStopped,
+        quinn::ConnectError::UnsupportedVersion => ConnectErrorKind::VersionMismatch,
+        _ => ConnectErrorKind::Other,
+    };
+    NetworkError::Connect(kind, e.to_string())
+}
+
+/// Classify a handshake that failed
+fn connection_error(e: quinn::ConnectionError) -> NetworkError {
+    use quinn::ConnectionError as E;
+    let kind = match &e {
+        E::TimedOut => ConnectErrorKind::Timeout,
+        E::VersionMismatch => ConnectErrorKind::VersionMismatch,
+        E::TransportError(t) => ConnectErrorKind::from_transport_code(t.code.into()),
+        E::ConnectionClosed(close) => ConnectErrorKind::from_transport_code(close.error_code.into()),
+        E::ApplicationClosed(_) => ConnectErrorKind::Rejected,
+        // Stateless reset: the peer restarted and forgot us
+        E::Reset => ConnectErrorKind::ConnectionLost,
+        _ => ConnectErrorKind::Other,
+    };
+    NetworkError::Connect(kind, e.to_string())
 }
 
 impl QuicEndpoint {
     /// Create a new QUIC endpoint (both server and client)
     pub async fn new(config: QuicConfig) -> Result<Self, NetworkError> {
-        // Generate self-signed certificate
-        let (server_config, _cert) = Self::generate_server_config()?;
+        let identity = Identity::ours()?;
+        let server_config = Self::generate_server_config(identity)?;
 
         // Create endpoint with server config
-        let endpoint = Endpoint::server(server_config, config.bind_addr)
+        let socket = Self::bind_socket(config.bind_addr)
             .map_err(|e| NetworkError::ConnectionFailed(format!("Failed to create endpoint: {}", e)))?;
+        let endpoint = Endpoint::new(
+            quinn::EndpointConfig::default(),
+            Some(server_config),
+            socket,
+            Arc::new(quinn::TokioRuntime),
+        )
+        .map_err(|e| NetworkError::ConnectionFailed(format!("Failed to create endpoint: {}", e)))?;
 
-        log::info!("QUIC endpoint created on {}", config.bind_addr);
+        log::info!("QUIC endpoint created on {}", endpoint.local_addr().unwrap_or(config.bind_addr));
 
-        Ok(Self { endpoint, config })
+        Ok(Self { endpoint, config, identity, stats: Mutex::new(EndpointStats::default()) })
     }
 
-    /// Generate server configuration with self-signed certificate
-    fn generate_server_config() -> Result<(ServerConfig, CertificateDer<'static>), NetworkError> {
-        // Generate self-signed certificate
-        let cert = rcgen::generate_simple_self_signed(vec!["lan-meeting".to_string()])
-            .map_err(|e| NetworkError::ConnectionFailed(format!("Failed to generate cert: {}", e)))?;
-
-        let cert_der = CertificateDer::from(cert.cert);
-        let key_der = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+    /// Bind the UDP socket. The IPv6 wildcard address is bound dual-stack,
+    /// so IPv4 peers reach it too (Windows makes IPv6 sockets v6-only
+    /// unless told otherwise); without IPv6 on the host it falls back to
+    /// the IPv4 wildcard on the same port.
+    fn bind_socket(addr: SocketAddr) -> std::io::Result<UdpSocket> {
+        if addr.ip() != Ipv6Addr::UNSPECIFIED {
+            return UdpSocket::bind(addr);
+        }
+        let dual_stack = || -> std::io::Result<UdpSocket> {
+            let socket = socket2::Socket::new(socket2::Domain::IPV6, socket2::Type::DGRAM, Some(socket2::Protocol::UDP))?;
+            socket.set_only_v6(false)?;
+            socket.set_nonblocking(true)?;
+            socket.bind(&addr.into())?;
+            Ok(socket.into())
+        };
+        dual_stack().or_else(|e| {
+            log::warn!("IPv6 unavailable ({}), listening on IPv4 only", e);
+            UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), addr.port()))
+        })
+    }
 
+    /// Generate server configuration with our certificate. Clients are
+    /// asked for theirs, for pairing, but probes and older builds connect
+    /// without one.
+    fn generate_server_config(identity: &Identity) -> Result<ServerConfig, NetworkError> {
         // Create rustls server config
         let mut server_crypto = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(vec![cert_der.clone()], key_der.into())
+            .with_client_cert_verifier(Arc::new(AcceptAnyClientCert))
+            .with_single_cert(vec![identity.cert.clone()], identity.key())
             .map_err(|e| NetworkError::ConnectionFailed(format!("TLS config error: {}", e)))?;
 
         server_crypto.alpn_protocols = vec![b"lan-meeting".to_vec()];
@@ -91,7 +258,7 @@ impl QuicEndpoint {
         let transport = Self::create_transport_config();
         server_config.transport_config(Arc::new(transport));
 
-        Ok((server_config, cert_der))
+        Ok(server_config)
     }
 
     /// Create shared transport configuration for both server and client
@@ -116,19 +283,20 @@ impl QuicEndpoint {
         // Send window: 2MB to allow burst without waiting for ACKs on LAN
         transport.send_window(2 * 1024 * 1024);
 
-        // Enable datagrams for future low-latency frame delivery
-        transport.datagram_receive_buffer_size(Some(65536));
+        // Enable datagrams for low-latency delta frame delivery; room for a
+        // few frames' worth of fragments while the viewer is decoding
+        transport.datagram_receive_buffer_size(Some(1024 * 1024));
         transport
     }
 
-    /// Create client configuration (accepts any certificate for LAN use)
-    fn create_client_config() -> Result<ClientConfig, NetworkError> {
-        // For LAN use, we skip certificate verification
-        // In production, you'd want proper certificate validation
+    /// Create client configuration (accepts any certificate for LAN use,
+    /// pairing pins it instead) presenting our certificate
+    fn create_client_config(&self) -> Result<ClientConfig, NetworkError> {
         let mut crypto = rustls::ClientConfig::builder()
             .dangerous()
             .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
-            .with_no_client_auth();
+            .with_client_auth_cert(vec![self.identity.cert.clone()], self.identity.key())
+            .map_err(|e| NetworkError::ConnectionFailed(format!("TLS config error: {}", e)))?;
 
         // IMPORTANT: Must match server's ALPN protocols
         crypto.alpn_protocols = vec![b"lan-meeting".to_vec()];
@@ -148,20 +316,19 @@ impl QuicEndpoint {
     /// Connect to a remote peer
     pub async fn connect(&self, addr: SocketAddr) -> Result<Arc<QuicConnection>, NetworkError> {
         log::info!("Connecting to {}", addr);
+        self.stats.lock().connect_attempts += 1;
 
-        let client_config = Self::create_client_config()?;
+        let client_config = self.create_client_config()?;
 
-        let connection = self
-            .endpoint
-            .connect_with(client_config, addr, "lan-meeting")
-            .map_err(|e| NetworkError::ConnectionFailed(format!("Connect error: {}", e)))?
-            .await
-            .map_err(|e| NetworkError::ConnectionFailed(format!("Connection failed: {}", e)))?;
-
-        let remote_addr = connection.remote_address();
-        log::info!("Connected to {}", remote_addr);
+        let connection = match self.endpoint.connect_with(client_config, addr, "lan-meeting") {
+            Ok(connecting) => connecting.await.map_err(connection_error),
+            Err(e) => Err(connect_error(e)),
+        }
+        .inspect_err(|e| self.record_failure(e))?;
 
         let conn = Arc::new(QuicConnection::new(connection));
+        let remote_addr = conn.remote_addr();
+        log::info!("Connected to {}", remote_addr);
 
         // Store connection
         let conn_id = remote_addr.to_string();
@@ -170,6 +337,97 @@ impl QuicEndpoint {
         Ok(conn)
     }
 
+    /// Connect, giving up after `timeout`
+    pub async fn connect_within(
+        &self,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> Result<Arc<QuicConnection>, NetworkError> {
+        tokio::time::timeout(timeout, self.connect(addr)).await.unwrap_or_else(|_| {
+            let e = NetworkError::Connect(ConnectErrorKind::Timeout, format!("No answer within {:?}", timeout));
+            self.record_failure(&e);
+            Err(e)
+        })
+    }
+
+    /// Reconnect under the shared retry policy: attempts back off with
+    /// jitter, and a peer whose circuit is open fails fast
+    pub async fn connect_with_retry(
+        &self,
+        addr: SocketAddr,
+        timeout: Duration,
+    ) -> Result<Arc<QuicConnection>, NetworkError> {
+        let peer = addr.ip().to_string();
+        let mut attempts = 0;
+        loop {
+            match retry::check(&peer) {
+                Ok(()) => {}
+                // Not worth holding up the caller for
+                Err(Gate::Open(wait)) | Err(Gate::Backoff(wait)) if wait > timeout => {
+                    return Err(NetworkError::Connect(
+                        ConnectErrorKind::RetryLater,
+                        format!("{} failed recently, next attempt in {:?}", peer, wait),
+                    ));
+                }
+                Err(Gate::Open(wait)) | Err(Gate::Backoff(wait)) => tokio::time::sleep(wait).await,
+            }
+
+            attempts += 1;
+            match self.connect_within(addr, timeout).await {
+                Ok(conn) => {
+                    retry::on_success(&peer);
+                    return Ok(conn);
+                }
+                Err(e) => {
+                    retry::on_failure(&peer);
+                    let transient = matches!(&e, NetworkError::Connect(kind, _) if kind.is_transient());
+                    if !transient || attempts >= RetryPolicy::default().max_attempts {
+                        return Err(e);
+                    }
+                    log::debug!("Connect attempt {} to {} failed: {}", attempts, peer, e);
+                }
+            }
+        }
+    }
+
+    fn record_failure(&self, e: &NetworkError) {
+        let kind = match e {
+            NetworkError::Connect(kind, _) => *kind,
+            _ => ConnectErrorKind::Other,
+        };
+        *self.stats.lock().connect_failures.entry(kind).or_default() += 1;
+    }
+
+    /// Connect attempts, failures by kind and open connections
+    pub fn stats(&self) -> EndpointStats {
+        EndpointStats {
+            local_addr: self.local_addr().to_string(),
+            open_connections: CONNECTIONS.read().values().filter(|c| c.is_alive()).count(),
+            ..self.stats.lock().clone()
+        }
+    }
+
+    /// Check that a LAN Meeting service answers at `addr` by completing a
+    /// handshake. The connection is closed right away and never registered.
+    /// Returns how long the handshake took.
+    pub async fn probe(&self, addr: SocketAddr, timeout: Duration) -> Result<Duration, NetworkError> {
+        let client_config = self.create_client_config()?;
+        let start = std::time::Instant::now();
+
+        let connecting = self
+            .endpoint
+            .connect_with(client_config, addr, "lan-meeting")
+            .map_err(|e| NetworkError::ConnectionFailed(format!("Connect error: {}", e)))?;
+        let connection = tokio::time::timeout(timeout, connecting)
+            .await
+            .map_err(|_| NetworkError::ConnectionFailed("Probe timed out".to_string()))?
+            .map_err(|e| NetworkError::ConnectionFailed(format!("Probe failed: {}", e)))?;
+
+        let elapsed = start.elapsed();
+        connection.close(0u32.into(), b"probe");
+        Ok(elapsed)
+    }
+
     /// Accept incoming connections
     pub async fn accept(&self) -> Result<Arc<QuicConnection>, NetworkError> {
         let incoming = self
@@ -182,10 +440,16 @@ impl QuicEndpoint {
             .await
             .map_err(|e| NetworkError::ConnectionFailed(format!("Accept failed: {}", e)))?;
 
-        let remote_addr = connection.remote_address();
-        log::info!("Accepted connection from {}", remote_addr);
-
         let conn = Arc::new(QuicConnection::new(connection));
+        let remote_addr = conn.remote_addr();
+        if super::known::is_blocked(None, conn.peer_fingerprint()) {
+            conn.close();
+            return Err(NetworkError::Connect(
+                ConnectErrorKind::Rejected,
+                format!("Certificate of {} is blocked", remote_addr),
+            ));
+        }
+        log::info!("Accepted connection from {}", remote_addr);
 
         // Store connection
         let conn_id = remote_addr.to_string();
@@ -215,10 +479,15 @@ impl QuicEndpoint {
                 // Complete the connection handshake (may fail for individual connections)
                 match incoming.await {
                     Ok(connection) => {
-                        let remote_addr = connection.remote_address();
+                        let conn = Arc::new(QuicConnection::new(connection));
+                        let remote_addr = conn.remote_addr();
+                        if super::known::is_blocked(None, conn.peer_fingerprint()) {
+                            log::info!("Refusing connection from {}: certificate is blocked", remote_addr);
+                            conn.close();
+                            continue;
+                        }
                         log::info!("Accepted connection from {}", remote_addr);
 
-                        let conn = Arc::new(QuicConnection::new(connection));
                         let conn_id = remote_addr.to_string();
                         CONNECTIONS.write().insert(conn_id, conn.clone());
 
@@ -246,30 +515,95 @@ impl QuicEndpoint {
     pub fn close(&self) {
         self.endpoint.close(0u32.into(), b"shutdown");
     }
+
+    /// Wait, at most `timeout`, for closed connections to tell their peers
+    pub async fn wait_idle(&self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, self.endpoint.wait_idle()).await;
+    }
+}
+
+/// Path statistics reported by quinn. Packet counters are cumulative over
+/// the connection's lifetime.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkStats {
+    pub rtt: Duration,
+    /// Congestion window in bytes
+    pub cwnd: u64,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub congestion_events: u64,
+    /// UDP payload bytes, both ways
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
 }
 
 /// Active QUIC connection to a peer
 pub struct QuicConnection {
     connection: Connection,
     state: RwLock<ConnectionState>,
+    /// Fingerprint of the certificate the peer presented
+    fingerprint: Option<String>,
 }
 
 impl QuicConnection {
     fn new(connection: Connection) -> Self {
+        let fingerprint = connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+            .and_then(|certs| certs.first().map(|cert| fingerprint(cert)));
         Self {
             connection,
             state: RwLock::new(ConnectionState::Connected),
+            fingerprint,
         }
     }
 
+    /// Fingerprint of the peer's certificate; `None` for a client that
+    /// presented none
+    pub fn peer_fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    /// Fill `output` with keying material exported from the TLS session,
+    /// the same on both ends of this connection only
+    pub fn export_keying_material(&self, output: &mut [u8], label: &[u8]) -> Result<(), NetworkError> {
+        self.connection
+            .export_keying_material(output, label, b"")
+            .map_err(|_| NetworkError::ConnectionFailed("Failed to export keying material".to_string()))
+    }
+
+    /// Resolves once the connection is closed, by either side
+    pub async fn closed(&self) {
+        self.connection.closed().await;
+    }
+
     /// Get connection state
     pub fn state(&self) -> ConnectionState {
         *self.state.read()
     }
 
-    /// Get remote address
+    /// Get remote address, IPv4 peers as IPv4 (see `address::canonical`)
     pub fn remote_addr(&self) -> SocketAddr {
-        self.connection.remote_address()
+        super::address::canonical(self.connection.remote_address())
+    }
+
+    /// The IP the peer is known by elsewhere (see `address::peer_ip`)
+    pub fn peer_ip(&self) -> String {
+        super::address::peer_ip(self.connection.remote_address())
+    }
+
+    /// Current path statistics (RTT, congestion window, loss, traffic)
+    pub fn link_stats(&self) -> LinkStats {
+        let stats = self.connection.stats();
+        LinkStats {
+            rtt: stats.path.rtt,
+            cwnd: stats.path.cwnd,
+            sent_packets: stats.path.sent_packets,
+            lost_packets: stats.path.lost_packets,
+            congestion_events: stats.path.congestion_events,
+            sent_bytes: stats.udp_tx.bytes,
+            received_bytes: stats.udp_rx.bytes,
+        }
     }
 
     /// Open a new bidirectional stream
@@ -317,6 +651,12 @@ impl QuicConnection {
             .map_err(|e| NetworkError::ConnectionFailed(format!("Failed to send datagram: {}", e)))
     }
 
+    /// Largest datagram the peer accepts right now, or `None` if it does
+    /// not support datagrams
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+
     /// Receive datagram
     pub async fn recv_datagram(&self) -> Result<bytes::Bytes, NetworkError> {
         self.connection
@@ -350,6 +690,16 @@ pub struct QuicStream {
     recv: RecvStream,
 }
 
+/// Length of a frame from its prefix. Nothing after a prefix too large to
+/// take can be read as a frame any more, so the stream should be dropped.
+fn frame_len(prefix: [u8; 4]) -> Result<usize, NetworkError> {
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > protocol::MAX_FRAME_SIZE {
+        return Err(protocol::DecodeError::TooLarge { len, max: protocol::MAX_FRAME_SIZE }.into());
+    }
+    Ok(len)
+}
+
 impl QuicStream {
     fn new(send: SendStream, recv: RecvStream) -> Self {
         Self { send, recv }
@@ -393,7 +743,7 @@ impl QuicStream {
             .await
             .map_err(|e| NetworkError::ConnectionFailed(format!("Recv length error: {}", e)))?;
 
-        let len = u32::from_be_bytes(len_buf) as usize;
+        let len = frame_len(len_buf)?;
         let mut data = vec![0u8; len];
         self.recv
             .read_exact(&mut data)
@@ -413,7 +763,7 @@ impl QuicStream {
             Err(_) => return Ok(None), // no data ready
         }
 
-        let len = u32::from_be_bytes(len_buf) as usize;
+        let len = frame_len(len_buf)?;
         let mut data = vec![0u8; len];
         self.recv
             .read_exact(&mut data)
@@ -516,14 +866,19 @@ pub fn find_connection(peer_id: &str) -> Option<Arc<QuicConnection>> {
     if let Some(conn) = get_connection(peer_id) {
         return Some(conn);
     }
-    // If no exact match, try to find by IP prefix (when only IP is provided without port)
+    // If no exact match, try to find by IP (when only IP is provided without port)
     let connections = CONNECTIONS.read();
     connections
         .iter()
-        .find(|(key, _)| key.starts_with(&format!("{}:", peer_id)))
+        .find(|(key, _)| key_has_ip(key, peer_id))
         .map(|(_, conn)| conn.clone())
 }
 
+/// Whether a registry key (`ip:port`, `[ipv6]:port`) is for peer IP `ip`
+fn key_has_ip(key: &str, ip: &str) -> bool {
+    key.parse::<SocketAddr>().is_ok_and(|addr| super::address::peer_ip(addr) == ip)
+}
+
 /// Remove dead connections from the registry and clean up corresponding devices.
 /// Returns the IPs of removed connections so callers can act on them.
 pub fn cleanup_dead_connections() -> Vec<String> {
@@ -533,7 +888,7 @@ pub fn cleanup_dead_connections() -> Vec<String> {
             .iter()
             .filter(|(_, conn)| !conn.is_alive())
             .map(|(key, conn)| {
-                let ip = conn.remote_addr().ip().to_string();
+                let ip = conn.peer_ip();
                 (key.clone(), ip)
             })
             .collect()
@@ -576,13 +931,27 @@ pub fn cleanup_dead_connections() -> Vec<String> {
     dead_ips
 }
 
+/// File a connection under its current remote address, after the peer's
+/// address changed and the connection migrated with it
+pub fn reindex_connection(conn: &Arc<QuicConnection>) {
+    let mut connections = CONNECTIONS.write();
+    connections.retain(|_, c| !Arc::ptr_eq(c, conn));
+    connections.insert(conn.remote_addr().to_string(), conn.clone());
+}
+
 /// Remove connection by IP address (matches ip:port keys)
 pub fn remove_connection_by_ip(ip: &str) {
     let mut connections = CONNECTIONS.write();
-    connections.retain(|key, _| !key.starts_with(&format!("{}:", ip)) && key != ip);
+    connections.retain(|key, _| !key_has_ip(key, ip) && key != ip);
 }
 
-/// Skip server certificate verification for LAN use
+/// Signature algorithms handshake signatures are checked with
+static SIGNATURE_ALGORITHMS: once_cell::sync::Lazy<rustls::crypto::WebPkiSupportedAlgorithms> =
+    once_cell::sync::Lazy::new(|| rustls::crypto::aws_lc_rs::default_provider().signature_verification_algorithms);
+
+/// Skip server certificate validation for LAN use: certificates are
+/// self-signed and pinned by pairing instead. The handshake signature is
+/// still checked, so a peer cannot present a certificate it has no key for.
 #[derive(Debug)]
 struct SkipServerVerification;
 
@@ -600,34 +969,69 @@ impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
 
     fn verify_tls12_signature(
         &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &SIGNATURE_ALGORITHMS)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &SIGNATURE_ALGORITHMS)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        SIGNATURE_ALGORITHMS.supported_schemes()
+    }
+}
+
+/// Take any client certificate, for pairing to pin, or none at all; like
+/// `SkipServerVerification`, the handshake signature is checked
+#[derive(Debug)]
+struct AcceptAnyClientCert;
+
+impl rustls::server::danger::ClientCertVerifier for AcceptAnyClientCert {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[rustls::DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::server::danger::ClientCertVerified, rustls::Error> {
+        Ok(rustls::server::danger::ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
     ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &SIGNATURE_ALGORITHMS)
     }
 
     fn verify_tls13_signature(
         &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
     ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &SIGNATURE_ALGORITHMS)
     }
 
     fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        vec![
-            rustls::SignatureScheme::RSA_PKCS1_SHA256,
-            rustls::SignatureScheme::RSA_PKCS1_SHA384,
-            rustls::SignatureScheme::RSA_PKCS1_SHA512,
-            rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
-            rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
-            rustls::SignatureScheme::ECDSA_NISTP521_SHA512,
-            rustls::SignatureScheme::RSA_PSS_SHA256,
-            rustls::SignatureScheme::RSA_PSS_SHA384,
-            rustls::SignatureScheme::RSA_PSS_SHA512,
-            rustls::SignatureScheme::ED25519,
-        ]
+        SIGNATURE_ALGORITHMS.supported_schemes()
     }
 }
@@ -0,0 +1,173 @@
+//! Reconnect policy
+//!
+//! Every reconnect goes through here so a peer that keeps dropping off the
+//! network is not dialed in a tight loop from several places at once.
+//! Attempts back off exponentially with jitter, and after enough
+//! consecutive failures the peer's circuit opens: dialing it fails fast
+//! until a cool-down has passed. A successful connection resets the peer.
+
+use parking_lot::Mutex;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+/// Retry settings shared by all reconnect paths
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Delay after the first failure
+    pub base_delay: Duration,
+    /// Longest delay between attempts
+    pub max_delay: Duration,
+    /// Fraction of each delay that is randomized
+    pub jitter: f64,
+    /// Attempts one reconnect call makes before giving up
+    pub max_attempts: u32,
+    /// Consecutive failures that open the circuit
+    pub breaker_threshold: u32,
+    /// How long an open circuit refuses attempts
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            max_attempts: 3,
+            breaker_threshold: 6,
+            breaker_cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after `failures` consecutive failures, `random` in 0..=1
+    /// picking where inside the jitter range it lands
+    pub fn delay(&self, failures: u32, random: f64) -> Duration {
+        let exp = self.base_delay.saturating_mul(1u32 << failures.saturating_sub(1).min(16));
+        let delay = exp.min(self.max_delay);
+        delay.mul_f64(1.0 - self.jitter * random.clamp(0.0, 1.0))
+    }
+}
+
+/// Why an attempt may not be made yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gate {
+    /// Backing off after a failure
+    Backoff(Duration),
+    /// Too many failures in a row; cooling down
+    Open(Duration),
+}
+
+#[derive(Debug, Default)]
+struct PeerState {
+    failures: u32,
+    not_before: Option<Instant>,
+}
+
+/// Failure history of every peer
+#[derive(Debug, Default)]
+pub struct Backoff {
+    peers: HashMap<String, PeerState>,
+}
+
+impl Backoff {
+    /// Whether `peer` may be dialed at `now`
+    pub fn check(&self, policy: &RetryPolicy, peer: &str, now: Instant) -> Result<(), Gate> {
+        let Some(state) = self.peers.get(peer) else {
+            return Ok(());
+        };
+        match state.not_before.and_then(|t| t.checked_duration_since(now)) {
+            Some(wait) if !wait.is_zero() && state.failures >= policy.breaker_threshold => Err(Gate::Open(wait)),
+            Some(wait) if !wait.is_zero() => Err(Gate::Backoff(wait)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failed attempt; returns how long to wait before the next
+    pub fn on_failure(&mut self, policy: &RetryPolicy, peer: &str, now: Instant, random: f64) -> Duration {
+        let state = self.peers.entry(peer.to_string()).or_default();
+        state.failures += 1;
+        let wait = if state.failures >= policy.breaker_threshold {
+            policy.breaker_cooldown
+        } else {
+            policy.delay(state.failures, random)
+        };
+        state.not_before = Some(now + wait);
+        wait
+    }
+
+    /// The peer answered; forget its failures
+    pub fn on_success(&mut self, peer: &str) {
+        self.peers.remove(peer);
+    }
+}
+
+static BACKOFF: once_cell::sync::Lazy<Mutex<Backoff>> = once_cell::sync::Lazy::new(|| Mutex::new(Backoff::default()));
+
+/// A number in 0..=1 that differs between calls
+fn random() -> f64 {
+    (RandomState::new().hash_one(Instant::now()) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Whether `peer` may be dialed now
+pub fn check(peer: &str) -> Result<(), Gate> {
+    BACKOFF.lock().check(&RetryPolicy::default(), peer, Instant::now())
+}
+
+/// Record a failed attempt to reach `peer`; returns the wait before the next
+pub fn on_failure(peer: &str) -> Duration {
+    let wait = BACKOFF.lock().on_failure(&RetryPolicy::default(), peer, Instant::now(), random());
+    log::debug!("Backing off {:?} before dialing {} again", wait, peer);
+    wait
+}
+
+/// Record that `peer` was reached
+pub fn on_success(peer: &str) {
+    BACKOFF.lock().on_success(peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_doubles_and_caps() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(4, 0.0), Duration::from_secs(4));
+        assert_eq!(policy.delay(40, 0.0), Duration::from_secs(30));
+        // Full jitter takes off up to half
+        assert_eq!(policy.delay(2, 1.0), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_circuit_opens_and_resets() {
+        let policy = RetryPolicy::default();
+        let mut backoff = Backoff::default();
+        let now = Instant::now();
+        assert_eq!(backoff.check(&policy, "10.0.0.2", now), Ok(()));
+
+        let wait = backoff.on_failure(&policy, "10.0.0.2", now, 0.0);
+        assert_eq!(backoff.check(&policy, "10.0.0.2", now), Err(Gate::Backoff(wait)));
+        assert_eq!(backoff.check(&policy, "10.0.0.2", now + wait), Ok(()));
+        // Other peers are not affected
+        assert_eq!(backoff.check(&policy, "10.0.0.3", now), Ok(()));
+
+        for _ in 1..policy.breaker_threshold {
+            backoff.on_failure(&policy, "10.0.0.2", now, 0.0);
+        }
+        assert_eq!(
+            backoff.check(&policy, "10.0.0.2", now),
+            Err(Gate::Open(policy.breaker_cooldown))
+        );
+        assert_eq!(backoff.check(&policy, "10.0.0.2", now + policy.breaker_cooldown), Ok(()));
+
+        backoff.on_success("10.0.0.2");
+        backoff.on_failure(&policy, "10.0.0.2", now, 0.0);
+        assert_eq!(backoff.check(&policy, "10.0.0.2", now), Err(Gate::Backoff(policy.base_delay)));
+    }
+}
@@ -0,0 +1,152 @@
+//! Subnet scan
+//!
+//! When neither mDNS nor broadcast gets through (multicast filtered by the
+//! access point, a guest network), the user can scan a subnet instead:
+//! every address in it gets a QUIC handshake probe, a few at a time with a
+//! short timeout, and those that answer are listed for the user to pick
+//! from. Only the one picked is paired, like a manually entered IP, so a
+//! scan never starts a handshake (and a pairing prompt) on every machine of
+//! the subnet. Scans are IPv4 only and capped at `MAX_HOSTS`.
+
+use super::discovery::{self, DeviceStatus};
+use super::quic::DEFAULT_PORT;
+use super::{ConnectErrorKind, NetworkError};
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+
+/// Most addresses one scan probes (a /22)
+const MAX_HOSTS: usize = 1024;
+
+/// Probes in flight at once
+const CONCURRENCY: usize = 64;
+
+/// How long a probe waits for the handshake; a LAN answers in a few ms
+const PROBE_TIMEOUT: Duration = Duration::from_millis(400);
+
+/// A scan is running
+static SCANNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears `SCANNING` however the scan ends, early return or cancelled
+struct ScanGuard;
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        SCANNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Payload of the "subnet-scan-progress" event
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanProgress {
+    pub scanned: usize,
+    pub total: usize,
+    pub found: usize,
+}
+
+/// The host addresses of an IPv4 subnet in CIDR notation, without the
+/// network and broadcast addresses where the subnet has them
+pub fn hosts(cidr: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let (ip, prefix) = cidr.trim().split_once('/').ok_or_else(|| format!("Not a subnet: {}", cidr))?;
+    let ip: Ipv4Addr = ip.parse().map_err(|_| format!("Not an IPv4 address: {}", ip))?;
+    let prefix: u32 = prefix.parse().ok().filter(|p| *p <= 32).ok_or_else(|| format!("Bad prefix: /{}", prefix))?;
+
+    let size = 1u64 << (32 - prefix);
+    if size > MAX_HOSTS as u64 {
+        return Err(format!("Subnet too large: /{} (at most /22)", prefix));
+    }
+    let network = u32::from(ip) & u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    let (first, last) = match size {
+        1 | 2 => (network as u64, network as u64 + size - 1),
+        _ => (network as u64 + 1, network as u64 + size - 2),
+    };
+    Ok((first..=last).map(|n| Ipv4Addr::from(n as u32)).collect())
+}
+
+/// Probe every host of `cidr`; returns the addresses of those running the
+/// service, for the user to pick the one to pair with
+pub async fn scan(cidr: &str) -> Result<Vec<String>, NetworkError> {
+    let hosts = hosts(cidr).map_err(|e| NetworkError::Connect(ConnectErrorKind::InvalidAddress, e))?;
+    let endpoint = crate::get_quic_endpoint().ok_or_else(|| {
+        NetworkError::Connect(ConnectErrorKind::ServiceStopped, "QUIC endpoint not initialized".to_string())
+    })?;
+    if SCANNING.swap(true, Ordering::SeqCst) {
+        return Err(NetworkError::DiscoveryError("A scan is already running".to_string()));
+    }
+    let _scanning = ScanGuard;
+
+    // Ourselves and devices already verified need no probe
+    let ours = super::address::local_addresses();
+    let known: Vec<String> = discovery::get_devices()
+        .into_iter()
+        .filter(|d| d.status != DeviceStatus::Seen && d.status != DeviceStatus::Offline)
+        .map(|d| d.ip)
+        .collect();
+    let targets: Vec<Ipv4Addr> = hosts
+        .into_iter()
+        .filter(|ip| !ours.contains(&IpAddr::V4(*ip)) && !known.contains(&ip.to_string()))
+        .collect();
+    log::info!("Scanning {} ({} hosts)", cidr, targets.len());
+
+    let total = targets.len();
+    let limit = Arc::new(tokio::sync::Semaphore::new(CONCURRENCY));
+    let mut probes = tokio::task::JoinSet::new();
+    for ip in targets {
+        let endpoint = endpoint.clone();
+        let limit = limit.clone();
+        probes.spawn(async move {
+            let _permit = limit.acquire_owned().await.ok()?;
+            let addr = SocketAddr::new(IpAddr::V4(ip), DEFAULT_PORT);
+            endpoint.probe(addr, PROBE_TIMEOUT).await.ok().map(|_| ip)
+        });
+    }
+
+    let mut responders = Vec::new();
+    let mut scanned = 0;
+    while let Some(result) = probes.join_next().await {
+        scanned += 1;
+        if let Ok(Some(ip)) = result {
+            responders.push(ip);
+        }
+        if scanned % CONCURRENCY == 0 || scanned == total {
+            emit_progress(ScanProgress { scanned, total, found: responders.len() });
+        }
+    }
+
+    responders.sort();
+    log::info!("Scan of {} found {} device(s)", cidr, responders.len());
+    Ok(responders.iter().map(|ip| ip.to_string()).collect())
+}
+
+fn emit_progress(progress: ScanProgress) {
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("subnet-scan-progress", progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_hosts() {
+        let hosts_of = |cidr| hosts(cidr).map(|h| h.iter().map(|ip| ip.to_string()).collect::<Vec<_>>());
+        let slash24 = hosts("192.168.1.77/24").unwrap();
+        assert_eq!(slash24.len(), 254);
+        assert_eq!(slash24.first(), Some(&Ipv4Addr::new(192, 168, 1, 1)));
+        assert_eq!(slash24.last(), Some(&Ipv4Addr::new(192, 168, 1, 254)));
+
+        assert_eq!(hosts_of("10.0.0.5/30").unwrap(), ["10.0.0.5", "10.0.0.6"]);
+        assert_eq!(hosts_of("10.0.0.5/31").unwrap(), ["10.0.0.4", "10.0.0.5"]);
+        assert_eq!(hosts_of("10.0.0.5/32").unwrap(), ["10.0.0.5"]);
+        assert_eq!(hosts("10.0.0.0/22").unwrap().len(), 1022);
+
+        assert!(hosts("10.0.0.0/21").is_err());
+        assert!(hosts("10.0.0.0/33").is_err());
+        assert!(hosts("10.0.0.0").is_err());
+        assert!(hosts("fd00::/120").is_err());
+    }
+}
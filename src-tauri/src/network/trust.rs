@@ -0,0 +1,163 @@
+//! Device trust
+//!
+//! Approving a device's request (remote control, for now) can also trust
+//! the device, so its later requests are approved without asking. Trust is
+//! permanent (kept in settings with the device ID), until the app restarts,
+//! or only while our current screen share lasts: when it stops, share trust
+//! ends. Temporary trust lives here in memory, and
+//! whatever it let a device do is taken back when it ends.
+//!
+//! Devices are keyed by their discovery ID, or by their IP while discovery
+//! does not know them.
+
+use super::discovery;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How long trust lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustScope {
+    /// Until our current screen share ends (not a scheduled meeting)
+    Share,
+    /// Until the app restarts
+    App,
+    /// Until removed
+    Permanent,
+}
+
+/// A trusted device, for the UI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrustedDevice {
+    pub device_id: String,
+    /// Last known address; empty for permanent trust of a device not seen
+    pub peer_ip: String,
+    pub scope: TrustScope,
+}
+
+/// Temporary trust by device
+#[derive(Debug, Default)]
+struct TrustStore {
+    temporary: HashMap<String, TrustedDevice>,
+}
+
+impl TrustStore {
+    fn grant(&mut self, device_id: &str, peer_ip: &str, scope: TrustScope) {
+        self.temporary.insert(
+            device_id.to_string(),
+            TrustedDevice { device_id: device_id.to_string(), peer_ip: peer_ip.to_string(), scope },
+        );
+    }
+
+    fn is_trusted(&self, device_id: &str) -> bool {
+        self.temporary.contains_key(device_id)
+    }
+
+    /// End share trust; returns the devices that lost it
+    fn end_share(&mut self) -> Vec<TrustedDevice> {
+        let (ended, kept): (Vec<TrustedDevice>, Vec<TrustedDevice>) =
+            std::mem::take(&mut self.temporary).into_values().partition(|t| t.scope == TrustScope::Share);
+        self.temporary = kept.into_iter().map(|t| (t.device_id.clone(), t)).collect();
+        ended
+    }
+
+    fn remove(&mut self, device_id: &str) -> Option<TrustedDevice> {
+        self.temporary.remove(device_id)
+    }
+}
+
+static TRUST: once_cell::sync::Lazy<Mutex<TrustStore>> = once_cell::sync::Lazy::new(Default::default);
+
+/// Key of the device at `peer_ip`
+fn device_id_for(peer_ip: &str) -> String {
+    discovery::get_devices()
+        .into_iter()
+        .find(|d| d.ip == peer_ip)
+        .map(|d| d.id)
+        .unwrap_or_else(|| peer_ip.to_string())
+}
+
+/// Trust the device at `peer_ip` for `scope`
+pub fn trust(peer_ip: &str, scope: TrustScope) {
+    let device_id = device_id_for(peer_ip);
+    log::info!("Trusting {} ({}) for {:?}", device_id, peer_ip, scope);
+    match scope {
+        TrustScope::Permanent => {
+            TRUST.lock().remove(&device_id);
+            crate::commands::set_trusted_permanently(&device_id, true);
+        }
+        scope => TRUST.lock().grant(&device_id, peer_ip, scope),
+    }
+}
+
+/// Whether requests from `peer_ip` are approved without asking
+pub fn is_trusted(peer_ip: &str) -> bool {
+    let device_id = device_id_for(peer_ip);
+    TRUST.lock().is_trusted(&device_id) || crate::commands::trusted_devices().contains(&device_id)
+}
+
+/// Every trusted device
+pub fn list() -> Vec<TrustedDevice> {
+    let devices = discovery::get_devices();
+    let mut trusted: Vec<TrustedDevice> = crate::commands::trusted_devices()
+        .into_iter()
+        .map(|device_id| TrustedDevice {
+            peer_ip: devices.iter().find(|d| d.id == device_id).map(|d| d.ip.clone()).unwrap_or_default(),
+            device_id,
+            scope: TrustScope::Permanent,
+        })
+        .collect();
+    trusted.extend(TRUST.lock().temporary.values().cloned());
+    trusted
+}
+
+/// Stop trusting a device and take back what its trust allowed
+pub async fn untrust(device_id: &str) {
+    crate::commands::set_trusted_permanently(device_id, false);
+    let removed = TRUST.lock().remove(device_id);
+    let peer_ip = removed.map(|t| t.peer_ip).or_else(|| {
+        discovery::get_devices().into_iter().find(|d| d.id == device_id).map(|d| d.ip)
+    });
+    if let Some(peer_ip) = peer_ip {
+        take_back(&peer_ip).await;
+    }
+}
+
+/// Our screen share ended: share trust ends with it
+pub async fn end_share() {
+    let ended = TRUST.lock().end_share();
+    for trusted in ended {
+        log::info!("Share over, no longer trusting {}", trusted.device_id);
+        take_back(&trusted.peer_ip).await;
+    }
+}
+
+/// Take back what trust let the device at `peer_ip` do
+async fn take_back(peer_ip: &str) {
+    if crate::input::session::is_controller(peer_ip) {
+        crate::commands::revoke_control().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_trust_ends_with_share() {
+        let mut store = TrustStore::default();
+        store.grant("a", "10.0.0.2", TrustScope::Share);
+        store.grant("b", "10.0.0.3", TrustScope::App);
+        assert!(store.is_trusted("a") && store.is_trusted("b"));
+
+        let ended = store.end_share();
+        assert_eq!(ended.len(), 1);
+        assert_eq!((ended[0].device_id.as_str(), ended[0].peer_ip.as_str()), ("a", "10.0.0.2"));
+        assert!(!store.is_trusted("a"));
+        assert!(store.is_trusted("b"));
+
+        assert!(store.remove("b").is_some());
+        assert!(!store.is_trusted("b"));
+    }
+}
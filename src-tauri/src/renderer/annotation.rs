@@ -0,0 +1,326 @@
+// Annotations
+// Strokes, arrows and text drawn over the shared screen. Viewers draw in the
+// render window (F5 switches the mouse between controlling the sharer and
+// drawing, Shift draws an arrow, F6 clears everything); the sharer and the
+// UI add them with commands. Shapes are kept in fractions of the frame, go
+// to everyone watching (see `simple_streaming::annotation`) and are
+// rasterized into one overlay covering the video. Text uses the HUD font.
+
+use super::hud::{glyph, GLYPH_H, GLYPH_W};
+use super::overlay::OverlayImage;
+use serde::{Deserialize, Serialize};
+
+/// Most shapes kept; the oldest go first
+const MAX_SHAPES: usize = 256;
+
+/// Most points in a stroke
+pub const MAX_POINTS: usize = 1024;
+
+/// Smallest move, in fractions of the frame, that adds a point to a stroke
+const MIN_STEP: f32 = 0.002;
+
+/// Pen of shapes drawn in the render window
+const PEN_COLOR: [u8; 4] = [235, 50, 50, 255];
+const PEN_WIDTH: u8 = 4;
+
+/// Arrow head length in line widths, and its half-angle
+const HEAD_LENGTH: f32 = 4.0;
+const HEAD_ANGLE: f32 = 0.45;
+
+/// Box behind text (RGBA)
+const TEXT_BOX: [u8; 4] = [0, 0, 0, 150];
+
+/// A shape over the video; points are fractions of the frame's width and
+/// height, widths in screen points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Annotation {
+    Stroke { color: [u8; 4], width: u8, points: Vec<[f32; 2]> },
+    Arrow { color: [u8; 4], width: u8, from: [f32; 2], to: [f32; 2] },
+    /// Text with its top-left corner at `at`
+    Text { color: [u8; 4], at: [f32; 2], text: String },
+    /// Remove every shape
+    Clear,
+}
+
+/// Position on the frame of surface point (x, y) over the video at `video`
+/// (x, y, width, height); `None` off the video
+pub(super) fn frame_point(video: Option<[f32; 4]>, x: f64, y: f64) -> Option<[f32; 2]> {
+    let [vx, vy, vw, vh] = video?;
+    let point = [(x as f32 - vx) / vw, (y as f32 - vy) / vh];
+    point.iter().all(|v| (0.0..=1.0).contains(v)).then_some(point)
+}
+
+/// The shapes on the video and the one being drawn
+#[derive(Debug, Default)]
+pub(super) struct AnnotationLayer {
+    shapes: Vec<Annotation>,
+    drawing: Option<Annotation>,
+    /// The mouse draws instead of controlling the sharer
+    active: bool,
+}
+
+impl AnnotationLayer {
+    /// Switch the mouse between drawing and controlling the sharer; returns
+    /// whether it draws now
+    pub fn toggle(&mut self) -> bool {
+        self.active = !self.active;
+        self.drawing = None;
+        self.active
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Show a shape, or remove them all with `Annotation::Clear`
+    pub fn apply(&mut self, annotation: Annotation) {
+        if annotation == Annotation::Clear {
+            self.shapes.clear();
+            self.drawing = None;
+            return;
+        }
+        if self.shapes.len() >= MAX_SHAPES {
+            self.shapes.remove(0);
+        }
+        self.shapes.push(annotation);
+    }
+
+    /// Start a stroke, or with `arrow` an arrow, at `at`
+    pub fn begin(&mut self, at: [f32; 2], arrow: bool) {
+        self.drawing = Some(if arrow {
+            Annotation::Arrow { color: PEN_COLOR, width: PEN_WIDTH, from: at, to: at }
+        } else {
+            Annotation::Stroke { color: PEN_COLOR, width: PEN_WIDTH, points: vec![at] }
+        });
+    }
+
+    /// The mouse moved to `at` while drawing; returns whether the shape changed
+    pub fn extend(&mut self, at: [f32; 2]) -> bool {
+        match self.drawing.as_mut() {
+            Some(Annotation::Stroke { points, .. }) => {
+                let last = points.last().copied().unwrap_or(at);
+                if points.len() >= MAX_POINTS || ((at[0] - last[0]).abs().max((at[1] - last[1]).abs())) < MIN_STEP {
+                    return false;
+                }
+                points.push(at);
+                true
+            }
+            Some(Annotation::Arrow { to, .. }) => {
+                *to = at;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The mouse was released: the shape is done. Returns it, to be sent to
+    /// the others.
+    pub fn finish(&mut self) -> Option<Annotation> {
+        let shape = self.drawing.take()?;
+        self.apply(shape.clone());
+        Some(shape)
+    }
+
+    /// The shapes over the video at `video` on a surface of `surface` size,
+    /// `scale` surface pixels per point; `None` if there are none
+    pub fn image(&self, video: Option<[f32; 4]>, surface: (u32, u32), scale: usize) -> Option<OverlayImage> {
+        if self.shapes.is_empty() && self.drawing.is_none() {
+            return None;
+        }
+        let [vx, vy, vw, vh] = video?;
+        let (left, top) = (vx.max(0.0) as i32, vy.max(0.0) as i32);
+        let (right, bottom) = ((vx + vw).min(surface.0 as f32) as i32, (vy + vh).min(surface.1 as f32) as i32);
+        if right <= left || bottom <= top {
+            return None;
+        }
+        let mut raster = Raster::new(left, top, right - left, bottom - top);
+        let to_surface = |p: [f32; 2]| (vx + p[0] * vw, vy + p[1] * vh);
+
+        for shape in self.shapes.iter().chain(self.drawing.as_ref()) {
+            match shape {
+                Annotation::Stroke { color, width, points } => {
+                    let points: Vec<_> = points.iter().map(|&p| to_surface(p)).collect();
+                    raster.line(&points, *width as f32 * scale as f32, *color);
+                }
+                Annotation::Arrow { color, width, from, to } => {
+                    let (from, to) = (to_surface(*from), to_surface(*to));
+                    let width = *width as f32 * scale as f32;
+                    raster.line(&[from, to], width, *color);
+                    let angle = (from.1 - to.1).atan2(from.0 - to.0);
+                    let length = width * HEAD_LENGTH;
+                    for side in [-HEAD_ANGLE, HEAD_ANGLE] {
+                        let end = (to.0 + length * (angle + side).cos(), to.1 + length * (angle + side).sin());
+                        raster.line(&[to, end], width, *color);
+                    }
+                }
+                Annotation::Text { color, at, text } => {
+                    let (x, y) = to_surface(*at);
+                    raster.text(x as i32, y as i32, text, *color, scale * 2);
+                }
+                Annotation::Clear => {}
+            }
+        }
+        Some(raster.into_image())
+    }
+}
+
+/// RGBA pixels (straight alpha) covering part of the surface
+struct Raster {
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Raster {
+    fn new(left: i32, top: i32, width: i32, height: i32) -> Self {
+        Self { left, top, width, height, pixels: vec![[0; 4]; (width * height) as usize] }
+    }
+
+    /// Blend `color` over surface pixel (x, y), `cover` of it
+    fn blend(&mut self, x: i32, y: i32, color: [u8; 4], cover: f32) {
+        let (col, row) = (x - self.left, y - self.top);
+        if col < 0 || row < 0 || col >= self.width || row >= self.height {
+            return;
+        }
+        let dst = &mut self.pixels[(row * self.width + col) as usize];
+        let src_a = color[3] as f32 / 255.0 * cover;
+        let dst_a = dst[3] as f32 / 255.0;
+        let out_a = src_a + dst_a * (1.0 - src_a);
+        if out_a <= 0.0 {
+            return;
+        }
+        for i in 0..3 {
+            let c = (color[i] as f32 * src_a + dst[i] as f32 * dst_a * (1.0 - src_a)) / out_a;
+            dst[i] = c.round() as u8;
+        }
+        dst[3] = (out_a * 255.0).round() as u8;
+    }
+
+    /// Antialiased polyline through `points` (surface pixels) with round
+    /// joints; a single point is a dot
+    fn line(&mut self, points: &[(f32, f32)], width: f32, color: [u8; 4]) {
+        let Some(&first) = points.first() else {
+            return;
+        };
+        let half = width / 2.0;
+        let (mut x0, mut y0, mut x1, mut y1) = (first.0, first.1, first.0, first.1);
+        for &(x, y) in points {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x), y1.max(y));
+        }
+        let left = ((x0 - half - 1.0).floor() as i32).max(self.left);
+        let top = ((y0 - half - 1.0).floor() as i32).max(self.top);
+        let right = ((x1 + half + 1.0).ceil() as i32).min(self.left + self.width);
+        let bottom = ((y1 + half + 1.0).ceil() as i32).min(self.top + self.height);
+        if right <= left || bottom <= top {
+            return;
+        }
+
+        // Coverage is the most any segment gives a pixel, so joints do not
+        // come out darker
+        let w = (right - left) as usize;
+        let mut cover = vec![0f32; w * (bottom - top) as usize];
+        let segments = points.windows(2).map(|s| (s[0], s[1])).chain((points.len() == 1).then_some((first, first)));
+        for (a, b) in segments {
+            let sx0 = ((a.0.min(b.0) - half - 1.0).floor() as i32).max(left);
+            let sy0 = ((a.1.min(b.1) - half - 1.0).floor() as i32).max(top);
+            let sx1 = ((a.0.max(b.0) + half + 1.0).ceil() as i32).min(right);
+            let sy1 = ((a.1.max(b.1) + half + 1.0).ceil() as i32).min(bottom);
+            for y in sy0..sy1 {
+                for x in sx0..sx1 {
+                    let d = distance_to_segment((x as f32 + 0.5, y as f32 + 0.5), a, b);
+                    let c = (half + 0.5 - d).clamp(0.0, 1.0);
+                    let slot = &mut cover[(y - top) as usize * w + (x - left) as usize];
+                    *slot = slot.max(c);
+                }
+            }
+        }
+        for (i, &c) in cover.iter().enumerate() {
+            if c > 0.0 {
+                self.blend(left + (i % w) as i32, top + (i / w) as i32, color, c);
+            }
+        }
+    }
+
+    /// Text with its top-left corner at (x, y) on a box, each font pixel
+    /// `size` surface pixels; lower case is drawn in upper case
+    fn text(&mut self, x: i32, y: i32, text: &str, color: [u8; 4], size: usize) {
+        let size = size as i32;
+        let chars = text.chars().count() as i32;
+        let box_w = (chars * (GLYPH_W as i32 + 1) + 1) * size;
+        let box_h = (GLYPH_H as i32 + 2) * size;
+        for row in y..y + box_h {
+            for col in x..x + box_w {
+                self.blend(col, row, TEXT_BOX, 1.0);
+            }
+        }
+        for (n, c) in text.to_uppercase().chars().enumerate() {
+            let cell_x = x + (1 + n as i32 * (GLYPH_W as i32 + 1)) * size;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_W {
+                    if bits & (0x10 >> col) == 0 {
+                        continue;
+                    }
+                    let (px, py) = (cell_x + col as i32 * size, y + (1 + row as i32) * size);
+                    for dy in 0..size {
+                        for dx in 0..size {
+                            self.blend(px + dx, py + dy, color, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn into_image(self) -> OverlayImage {
+        OverlayImage {
+            x: self.left as u32,
+            y: self.top as u32,
+            width: self.width as u32,
+            height: self.height as u32,
+            rgba: self.pixels.into_iter().flatten().collect(),
+        }
+    }
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0) } else { 0.0 };
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_stroke_and_clear() {
+        let mut layer = AnnotationLayer::default();
+        let video = Some([0.0, 50.0, 400.0, 300.0]);
+        assert!(layer.image(video, (400, 400), 1).is_none());
+        assert_eq!(frame_point(video, 200.0, 20.0), None);
+
+        assert!(layer.toggle());
+        layer.begin(frame_point(video, 100.0, 200.0).unwrap(), false);
+        // Tiny moves add no points
+        assert!(!layer.extend(frame_point(video, 100.2, 200.0).unwrap()));
+        assert!(layer.extend(frame_point(video, 300.0, 200.0).unwrap()));
+        let stroke = layer.finish().unwrap();
+        assert!(matches!(stroke, Annotation::Stroke { ref points, .. } if points.len() == 2));
+
+        // The image covers the video; the line is on it, the rest is clear
+        let image = layer.image(video, (400, 400), 1).unwrap();
+        assert_eq!((image.x, image.y, image.width, image.height), (0, 50, 400, 300));
+        let pixel = |x: u32, y: u32| &image.rgba[((y - 50) * 400 + x) as usize * 4..][..4];
+        assert_eq!(pixel(200, 200), &PEN_COLOR);
+        assert_eq!(pixel(200, 100)[3], 0);
+
+        layer.apply(Annotation::Clear);
+        assert!(layer.image(video, (400, 400), 1).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+// Viewer color filters
+// Applied in the fragment stage after the frame is converted to RGB, so they
+// cost nothing extra per frame and never touch the stream itself. Useful for
+// low-vision and colorblind viewers, and for spotting subtle UI differences.
+
+/// Post-processing applied to the shared screen in a viewer window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Invert,
+    Grayscale,
+    HighContrast,
+    /// Daltonization for red-blind viewers
+    Protanopia,
+    /// Daltonization for green-blind viewers
+    Deuteranopia,
+    /// Daltonization for blue-blind viewers
+    Tritanopia,
+}
+
+impl ColorFilter {
+    /// All filters, in toolbar order
+    pub const ALL: [ColorFilter; 7] = [
+        ColorFilter::None,
+        ColorFilter::Invert,
+        ColorFilter::Grayscale,
+        ColorFilter::HighContrast,
+        ColorFilter::Protanopia,
+        ColorFilter::Deuteranopia,
+        ColorFilter::Tritanopia,
+    ];
+
+    /// Settings name
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorFilter::None => "none",
+            ColorFilter::Invert => "invert",
+            ColorFilter::Grayscale => "grayscale",
+            ColorFilter::HighContrast => "high_contrast",
+            ColorFilter::Protanopia => "protanopia",
+            ColorFilter::Deuteranopia => "deuteranopia",
+            ColorFilter::Tritanopia => "tritanopia",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    /// Toolbar label
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorFilter::None => "Original",
+            ColorFilter::Invert => "Invert",
+            ColorFilter::Grayscale => "Grayscale",
+            ColorFilter::HighContrast => "High Contrast",
+            ColorFilter::Protanopia => "Protanopia",
+            ColorFilter::Deuteranopia => "Deuteranopia",
+            ColorFilter::Tritanopia => "Tritanopia",
+        }
+    }
+
+    /// Position in `ALL`
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&f| f == self).unwrap_or(0)
+    }
+
+    /// The shader's `view_params.color_filter` (a `vec4<u32>`, mode in x)
+    pub(super) fn uniform(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..4].copy_from_slice(&(self.index() as u32).to_le_bytes());
+        bytes
+    }
+}
+
+/// WGSL appended to both frame shaders; mode numbers follow `ColorFilter::ALL`
+pub(super) const FILTER_SHADER: &str = r#"
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Move the colors a dichromat cannot tell apart into channels they can see
+// (simulation matrices from Machado et al. 2009, full severity)
+fn daltonize(c: vec3<f32>, sim: mat3x3<f32>, red_green: bool) -> vec3<f32> {
+    let err = c - sim * c;
+    if red_green {
+        return c + vec3<f32>(0.0, 0.7 * err.r + err.g, 0.7 * err.r + err.b);
+    }
+    return c + vec3<f32>(err.r + 0.7 * err.b, err.g + 0.7 * err.b, 0.0);
+}
+
+fn apply_filter(c: vec3<f32>) -> vec3<f32> {
+    switch view_params.color_filter.x {
+        case 1u: {
+            return vec3<f32>(1.0) - c;
+        }
+        case 2u: {
+            return vec3<f32>(luma(c));
+        }
+        case 3u: {
+            // Stretch contrast and drop the color of near-gray pixels
+            let l = luma(c);
+            let boosted = mix(vec3<f32>(l), c, 1.5);
+            return clamp((boosted - 0.5) * 2.0 + 0.5, vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        case 4u: {
+            // WGSL matrices are built column by column
+            return clamp(daltonize(c, mat3x3<f32>(
+                0.152286, 0.114503, -0.003882,
+                1.052583, 0.786281, -0.048116,
+                -0.204868, 0.099216, 1.051998,
+            ), true), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        case 5u: {
+            return clamp(daltonize(c, mat3x3<f32>(
+                0.367322, 0.280085, -0.011820,
+                0.860646, 0.672501, 0.042940,
+                -0.227968, 0.047413, 0.968881,
+            ), true), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        case 6u: {
+            return clamp(daltonize(c, mat3x3<f32>(
+                1.255528, -0.078411, 0.004733,
+                -0.076749, 0.930809, 0.691367,
+                -0.178779, 0.147602, 0.303900,
+            ), false), vec3<f32>(0.0), vec3<f32>(1.0));
+        }
+        default: {
+            return c;
+        }
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for filter in ColorFilter::ALL {
+            assert_eq!(ColorFilter::from_name(filter.name()), Some(filter));
+        }
+        assert_eq!(ColorFilter::from_name("sepia"), None);
+        assert_eq!(ColorFilter::HighContrast.uniform()[..4], 3u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,118 @@
+// Video fit modes
+// How the frame is placed in a viewer window whose shape differs from the
+// shared screen's, e.g. a 16:10 screen in a 16:9 window. The frame's rect
+// may reach past the window (fill, 1:1); only the part on the surface is
+// drawn, by cropping the texture coordinates rather than the viewport.
+
+/// How a viewer window fits the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// Whole frame, aspect kept, with bars (letterbox/pillarbox)
+    #[default]
+    Fit,
+    /// Window covered, aspect kept, edges cropped
+    Fill,
+    /// One frame pixel per surface pixel, centered and cropped if larger
+    Original,
+    /// Whole window, aspect ignored
+    Stretch,
+}
+
+impl FitMode {
+    /// All modes, in toolbar order
+    pub const ALL: [FitMode; 4] = [FitMode::Fit, FitMode::Fill, FitMode::Original, FitMode::Stretch];
+
+    /// Settings name
+    pub fn name(self) -> &'static str {
+        match self {
+            FitMode::Fit => "fit",
+            FitMode::Fill => "fill",
+            FitMode::Original => "original",
+            FitMode::Stretch => "stretch",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    /// Toolbar label
+    pub fn label(self) -> &'static str {
+        match self {
+            FitMode::Fit => "Fit",
+            FitMode::Fill => "Fill",
+            FitMode::Original => "1:1",
+            FitMode::Stretch => "Stretch",
+        }
+    }
+
+    /// Position in `ALL`
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|&m| m == self).unwrap_or(0)
+    }
+
+    /// Rect of the whole frame on a surface of `surface` size (x, y, width,
+    /// height in surface pixels); may reach past the surface
+    pub(super) fn video_rect(self, surface: (f32, f32), frame: (f32, f32)) -> [f32; 4] {
+        let (sw, sh) = surface;
+        let (fw, fh) = frame;
+        let (w, h) = match self {
+            FitMode::Stretch => (sw, sh),
+            FitMode::Original => (fw, fh),
+            FitMode::Fit | FitMode::Fill => {
+                let scale = if self == FitMode::Fit { (sw / fw).min(sh / fh) } else { (sw / fw).max(sh / fh) };
+                (fw * scale, fh * scale)
+            }
+        };
+        [(sw - w) / 2.0, (sh - h) / 2.0, w, h]
+    }
+}
+
+/// The part of `video` on the surface: its viewport and the texture
+/// coordinates it shows (u, v offset, then u, v span, the shader's
+/// `view_params.frame_uv`). `None` if nothing of it is on the surface.
+pub(super) fn visible_part(video: [f32; 4], surface: (f32, f32)) -> Option<([f32; 4], [f32; 4])> {
+    let [vx, vy, vw, vh] = video;
+    let (x0, y0) = (vx.max(0.0), vy.max(0.0));
+    let (x1, y1) = ((vx + vw).min(surface.0), (vy + vh).min(surface.1));
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some((
+        [x0, y0, x1 - x0, y1 - y0],
+        [(x0 - vx) / vw, (y0 - vy) / vh, (x1 - x0) / vw, (y1 - y0) / vh],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_video_rect_per_mode() {
+        // 16:10 screen in a 16:9 window
+        let (surface, frame) = ((1600.0, 900.0), (1920.0, 1200.0));
+        assert_eq!(FitMode::Fit.video_rect(surface, frame), [80.0, 0.0, 1440.0, 900.0]);
+        assert_eq!(FitMode::Fill.video_rect(surface, frame), [0.0, -50.0, 1600.0, 1000.0]);
+        assert_eq!(FitMode::Original.video_rect(surface, frame), [-160.0, -150.0, 1920.0, 1200.0]);
+        assert_eq!(FitMode::Stretch.video_rect(surface, frame), [0.0, 0.0, 1600.0, 900.0]);
+
+        for mode in FitMode::ALL {
+            assert_eq!(FitMode::from_name(mode.name()), Some(mode));
+        }
+    }
+
+    #[test]
+    fn test_visible_part_crops_texture() {
+        let (viewport, uv) = visible_part([0.0, -50.0, 1600.0, 1000.0], (1600.0, 900.0)).unwrap();
+        assert_eq!(viewport, [0.0, 0.0, 1600.0, 900.0]);
+        assert_eq!(uv, [0.0, 0.05, 1.0, 0.9]);
+
+        // Letterboxed: all of the texture, in part of the surface
+        let (viewport, uv) = visible_part([80.0, 0.0, 1440.0, 900.0], (1600.0, 900.0)).unwrap();
+        assert_eq!(viewport, [80.0, 0.0, 1440.0, 900.0]);
+        assert_eq!(uv, [0.0, 0.0, 1.0, 1.0]);
+
+        assert_eq!(visible_part([1700.0, 0.0, 100.0, 100.0], (1600.0, 900.0)), None);
+    }
+}
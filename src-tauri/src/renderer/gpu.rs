@@ -0,0 +1,80 @@
+// Shared GPU device
+// Render windows and decoders that output GPU textures use one wgpu device,
+// so a decoded texture is bound by the renderer as it is instead of being
+// read back into memory, converted and uploaded again. Windows whose surface
+// the shared adapter cannot present to get a device of their own and only
+// take CPU frames.
+
+use std::sync::{Arc, OnceLock};
+
+/// Instance, adapter and device shared by renderers and GPU decoders
+pub struct SharedGpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl SharedGpu {
+    /// Whether decoders may hand over NV12 textures
+    pub fn supports_nv12(&self) -> bool {
+        self.device.features().contains(wgpu::Features::TEXTURE_FORMAT_NV12)
+    }
+}
+
+static SHARED: OnceLock<Option<Arc<SharedGpu>>> = OnceLock::new();
+
+/// The shared device, created on first use; `None` if there is no usable GPU
+pub fn shared() -> Option<Arc<SharedGpu>> {
+    SHARED.get_or_init(|| pollster::block_on(create()).map(Arc::new)).clone()
+}
+
+async fn create() -> Option<SharedGpu> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = match instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        })
+        .await
+    {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            log::warn!("No shared GPU adapter: {}", e);
+            return None;
+        }
+    };
+
+    // NV12 textures are what hardware decoders produce
+    let features = adapter.features() & wgpu::Features::TEXTURE_FORMAT_NV12;
+    let (device, queue) = match adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: Some("Shared Device"),
+            required_features: features,
+            ..Default::default()
+        })
+        .await
+    {
+        Ok(device) => device,
+        Err(e) => {
+            log::warn!("Failed to create shared GPU device: {}", e);
+            return None;
+        }
+    };
+
+    log::info!(
+        "Shared GPU device on {:?} (NV12 textures: {})",
+        adapter.get_info().name,
+        !features.is_empty()
+    );
+    Some(SharedGpu {
+        instance,
+        adapter,
+        device,
+        queue,
+    })
+}
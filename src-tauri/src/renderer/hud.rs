@@ -0,0 +1,133 @@
+//! Latency HUD drawn into decoded frames
+//!
+//! The viewer writes a small text box into the top-left corner of the luma
+//! plane before the frame goes to the render window, so the HUD looks the
+//! same on every renderer backend and needs no font or text pipeline. The
+//! viewer toolbar on Windows and Linux draws its labels with the same font.
+
+/// Glyph cell size in font pixels
+pub(super) const GLYPH_W: usize = 5;
+pub(super) const GLYPH_H: usize = 7;
+
+/// Font pixels between glyphs and around the text
+const SPACING: usize = 1;
+const PADDING: usize = 2;
+
+/// Luma of the background box and the text (video range)
+const BOX_LUMA: u8 = 16;
+const TEXT_LUMA: u8 = 235;
+
+/// Rows of a glyph, top to bottom, 5 bits each (MSB is the left column);
+/// digits, upper case letters and a few symbols
+pub(super) fn glyph(c: char) -> [u8; GLYPH_H] {
+    match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        'm' => [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11],
+        's' => [0x00, 0x00, 0x0f, 0x10, 0x0e, 0x01, 0x1e],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        _ => [0; GLYPH_H],
+    }
+}
+
+/// Text shown by the HUD
+pub fn latency_text(glass_to_glass_ms: Option<u64>, rtt_ms: Option<u64>) -> String {
+    let show = |ms: Option<u64>| ms.map_or("--".to_string(), |ms| format!("{}ms", ms));
+    format!("E2E {} RTT {}", show(glass_to_glass_ms), show(rtt_ms))
+}
+
+/// Draw `text` on a dark box in the top-left corner of a luma plane,
+/// scaled up on large frames so it stays readable
+pub fn draw_text(luma: &mut [u8], stride: usize, width: usize, height: usize, text: &str) {
+    let scale = (height / 360).max(1);
+    let chars = text.chars().count();
+    let box_w = ((GLYPH_W + SPACING) * chars - SPACING + PADDING * 2) * scale;
+    let box_h = (GLYPH_H + PADDING * 2) * scale;
+
+    let mut set = |x: usize, y: usize, value: u8| {
+        if x < width && y < height {
+            if let Some(p) = luma.get_mut(y * stride + x) {
+                *p = value;
+            }
+        }
+    };
+    for y in 0..box_h {
+        for x in 0..box_w {
+            set(x, y, BOX_LUMA);
+        }
+    }
+    for (i, c) in text.chars().enumerate() {
+        let left = PADDING + i * (GLYPH_W + SPACING);
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        set((left + col) * scale + dx, (PADDING + row) * scale + dy, TEXT_LUMA);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_text() {
+        assert_eq!(latency_text(Some(42), Some(3)), "E2E 42ms RTT 3ms");
+        assert_eq!(latency_text(None, None), "E2E -- RTT --");
+    }
+
+    #[test]
+    fn test_draw_text_stays_in_bounds() {
+        let (width, height, stride) = (20, 8, 24);
+        let mut luma = vec![128u8; stride * height];
+        draw_text(&mut luma, stride, width, height, "E2E 42ms");
+        assert_eq!(luma[0], BOX_LUMA);
+        assert!(luma.contains(&TEXT_LUMA));
+        // Stride padding past the frame width is left alone
+        assert!(luma.chunks(stride).all(|row| row[width..].iter().all(|&p| p == 128)));
+    }
+}
@@ -0,0 +1,111 @@
+// Magnifier lens
+// While the magnifier key is held, a round lens follows the cursor showing
+// the frame under it zoomed in. It is drawn in a second render pass over the
+// finished frame, sampling the same frame texture, so it works at whatever
+// size the window shows the video.
+
+/// Diameter of the lens in surface pixels (smaller on tiny windows)
+const LENS_SIZE: f32 = 280.0;
+
+/// Lowest and highest zoom of the lens
+const MIN_ZOOM: f32 = 2.0;
+const MAX_ZOOM: f32 = 4.0;
+
+/// Zoom of the lens for the state of the magnifier key (Alt/Option) and
+/// Shift: the key alone gives 2x, with Shift 4x; released, no lens
+pub(super) fn zoom_for_keys(magnifier_key: bool, shift: bool) -> Option<f32> {
+    magnifier_key.then_some(if shift { MAX_ZOOM } else { MIN_ZOOM })
+}
+
+/// Lens position and zoom, in surface pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Magnifier {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+}
+
+/// Where the lens goes and what it shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct LensGeometry {
+    /// Viewport of the lens pass: x, y, width, height in surface pixels
+    pub viewport: [f32; 4],
+    /// Frame texture coordinates at the lens center, and the texture span
+    /// the lens covers (the shader's `view_params.lens`)
+    pub params: [f32; 4],
+}
+
+impl Magnifier {
+    /// Lens for a surface of `surface` size showing the video in `video`
+    /// (x, y, width, height); `None` while the cursor is off the video
+    pub(super) fn geometry(&self, surface: (f32, f32), video: [f32; 4]) -> Option<LensGeometry> {
+        let [vx, vy, vw, vh] = video;
+        let u = (self.x - vx) / vw;
+        let v = (self.y - vy) / vh;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        // Keep the whole lens on the surface; near the edges it stops
+        // centering on the cursor but still shows what is under it
+        let size = LENS_SIZE.min(surface.0).min(surface.1);
+        let x = (self.x - size / 2.0).clamp(0.0, surface.0 - size);
+        let y = (self.y - size / 2.0).clamp(0.0, surface.1 - size);
+
+        let zoom = self.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        Some(LensGeometry {
+            viewport: [x, y, size, size],
+            params: [u, v, size / (vw * zoom), size / (vh * zoom)],
+        })
+    }
+}
+
+/// WGSL of the lens pass; expects the frame shader's `sample_rgb`
+pub(super) const LENS_SHADER: &str = r#"
+@fragment
+fn fs_lens(input: VertexOutput) -> @location(0) vec4<f32> {
+    let d = input.tex_coord - vec2<f32>(0.5);
+    let uv = view_params.lens.xy + d * view_params.lens.zw;
+    // Sampled before branching: texture sampling needs uniform control flow
+    var color = apply_filter(sample_rgb(uv));
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        color = vec3<f32>(0.0);
+    }
+
+    let r = length(d);
+    if r > 0.5 {
+        discard;
+    }
+    if r > 0.485 {
+        // Rim, so the lens stands out from the frame around it
+        return vec4<f32>(0.9, 0.9, 0.9, 1.0);
+    }
+    return vec4<f32>(color, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lens_geometry() {
+        let lens = Magnifier { x: 960.0, y: 540.0, zoom: 2.0 };
+        let g = lens.geometry((1920.0, 1080.0), [0.0, 0.0, 1920.0, 1080.0]).unwrap();
+        assert_eq!(g.viewport, [820.0, 400.0, 280.0, 280.0]);
+        assert_eq!(g.params, [0.5, 0.5, 280.0 / 3840.0, 280.0 / 2160.0]);
+
+        // Pushed back inside at the corner, still sampling under the cursor
+        let corner = Magnifier { x: 10.0, y: 1070.0, zoom: 9.0 };
+        let g = corner.geometry((1920.0, 1080.0), [0.0, 0.0, 1920.0, 1080.0]).unwrap();
+        assert_eq!(g.viewport, [0.0, 800.0, 280.0, 280.0]);
+        assert_eq!(g.params[2], 280.0 / (1920.0 * MAX_ZOOM));
+
+        // Over the letterbox bars
+        assert_eq!(lens.geometry((1920.0, 1080.0), [0.0, 600.0, 1920.0, 400.0]), None);
+
+        assert_eq!(zoom_for_keys(false, true), None);
+        assert_eq!(zoom_for_keys(true, false), Some(2.0));
+        assert_eq!(zoom_for_keys(true, true), Some(4.0));
+    }
+}
@@ -1,12 +1,35 @@
 // GPU renderer module
 // wgpu-based rendering for decoded frames
 
+pub mod annotation;
+mod filter;
+mod fit;
+pub mod gpu;
+pub mod hud;
+mod magnifier;
+mod overlay;
+mod pip;
+mod pointer;
+pub mod snapshot;
+mod stats_hud;
+mod tiling;
+// The macOS viewer uses a native panel for its toolbar
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+mod toolbar;
+pub mod viewers;
 mod wgpu_renderer;
 mod window;
 
+pub use annotation::Annotation;
+pub use filter::ColorFilter;
+pub use fit::FitMode;
+pub use magnifier::Magnifier;
+pub use pointer::RemotePointer;
 pub use wgpu_renderer::WgpuRenderer;
 pub use window::{RenderWindow, RenderWindowHandle, WindowEvent};
 
+use crate::decoder::{DecodedFrame, DecodedFrameData, OutputFormat};
+use crate::encoder::color::ColorSpace;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -26,6 +49,8 @@ pub enum RendererError {
 pub enum FrameFormat {
     BGRA,
     YUV420,
+    /// NV12 texture decoded on the GPU (only with `RenderFrame::texture`)
+    NV12,
 }
 
 /// Frame to be rendered
@@ -37,6 +62,13 @@ pub struct RenderFrame {
     pub data: Vec<u8>,
     /// For YUV420: strides for Y, U, V planes
     pub strides: Option<[usize; 3]>,
+    /// When the frame was captured, on our clock, for glass-to-glass latency
+    pub captured_at_ms: Option<u64>,
+    /// Texture on the shared device (see `gpu`), bound as is instead of
+    /// uploading `data`
+    pub texture: Option<wgpu::Texture>,
+    /// Matrix and range of YUV and NV12 frames
+    pub color_space: ColorSpace,
 }
 
 impl RenderFrame {
@@ -47,6 +79,9 @@ impl RenderFrame {
             format: FrameFormat::BGRA,
             data,
             strides: None,
+            captured_at_ms: None,
+            texture: None,
+            color_space: ColorSpace::default(),
         }
     }
 
@@ -57,6 +92,56 @@ impl RenderFrame {
             format: FrameFormat::YUV420,
             data,
             strides: Some(strides),
+            captured_at_ms: None,
+            texture: None,
+            color_space: ColorSpace::default(),
         }
     }
+
+    /// Frame already on the GPU: an NV12 or sRGB BGRA/RGBA texture created
+    /// on the shared device
+    pub fn from_texture(width: u32, height: u32, texture: wgpu::Texture) -> Self {
+        let format = if texture.format() == wgpu::TextureFormat::NV12 {
+            FrameFormat::NV12
+        } else {
+            FrameFormat::BGRA
+        };
+        Self {
+            width,
+            height,
+            format,
+            data: Vec::new(),
+            strides: None,
+            captured_at_ms: None,
+            texture: Some(texture),
+            color_space: ColorSpace::default(),
+        }
+    }
+
+    /// Take over a decoded frame's pixels (or texture) without copying them
+    pub fn from_decoded(frame: DecodedFrame) -> Self {
+        let (width, height) = (frame.width, frame.height);
+        match frame.data {
+            DecodedFrameData::Gpu { texture } => Self::from_texture(width, height, texture),
+            DecodedFrameData::Cpu { data, strides } => match frame.format {
+                OutputFormat::BGRA => Self::from_bgra(width, height, data),
+                OutputFormat::YUV420 => {
+                    let w = width as usize;
+                    Self::from_yuv420(width, height, data, strides.unwrap_or([w, w / 2, w / 2]))
+                }
+            },
+        }
+    }
+
+    /// Record when the frame was captured (local clock, ms since the Unix epoch)
+    pub fn with_capture_time(mut self, captured_at_ms: Option<u64>) -> Self {
+        self.captured_at_ms = captured_at_ms;
+        self
+    }
+
+    /// Set the color space the stream start named
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
 }
@@ -0,0 +1,166 @@
+// Overlays
+// Small RGBA images blended over the frame once it is drawn: annotations,
+// the sharer's pointer, the REC badge, the viewer toolbar on Windows/Linux
+// and the stats HUD. Text is rasterized on the CPU with the HUD font in font pixels and
+// scaled up to surface pixels, and each image is drawn in its own viewport,
+// so they need no text pipeline.
+
+use super::hud::{glyph, GLYPH_H, GLYPH_W};
+
+/// WGSL shader drawing an overlay image over the frame
+pub(super) const OVERLAY_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var tex_coords = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 0.0),
+    );
+
+    var output: VertexOutput;
+    output.position = vec4<f32>(positions[vertex_index], 0.0, 1.0);
+    output.tex_coord = tex_coords[vertex_index];
+    return output;
+}
+
+@group(0) @binding(0) var overlay_texture: texture_2d<f32>;
+@group(0) @binding(1) var overlay_sampler: sampler;
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(overlay_texture, overlay_sampler, input.tex_coord);
+}
+"#;
+
+/// Overlay slots, drawn in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayLayer {
+    Annotations,
+    Pointer,
+    Recording,
+    Toolbar,
+    Stats,
+}
+
+impl OverlayLayer {
+    pub(super) const COUNT: usize = 5;
+}
+
+/// RGBA image drawn over the frame at (x, y) in surface pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayImage {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Surface pixels per font pixel on a display with `scale_factor`
+pub(super) fn scale_for(scale_factor: f64) -> usize {
+    (scale_factor * 2.0).round().max(1.0) as usize
+}
+
+/// Height in font pixels of a line of text
+pub(super) const LINE_HEIGHT: usize = GLYPH_H;
+
+/// Width in font pixels of `chars` characters of text
+pub(super) fn text_width(chars: usize) -> usize {
+    ((GLYPH_W + 1) * chars).saturating_sub(1)
+}
+
+/// RGBA pixels (straight alpha) in font pixels, to draw an overlay on
+pub(super) struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: [u8; 4]) -> Self {
+        Self { width, height, pixels: vec![background; width * height] }
+    }
+
+    pub fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: [u8; 4]) {
+        for row in y..(y + h).min(self.height) {
+            for col in x..(x + w).min(self.width) {
+                self.pixels[row * self.width + col] = color;
+            }
+        }
+    }
+
+    /// Text with its top-left corner at (left, top); lower case is drawn
+    /// in upper case
+    pub fn text(&mut self, left: usize, top: usize, text: &str, color: [u8; 4]) {
+        for (n, c) in text.to_uppercase().chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_W {
+                    if bits & (0x10 >> col) != 0 {
+                        self.fill(left + n * (GLYPH_W + 1) + col, top + row, 1, 1, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The canvas at (x, y) on the surface, each font pixel `scale` surface
+    /// pixels wide
+    pub fn into_image(self, x: u32, y: u32, scale: usize) -> OverlayImage {
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for row in 0..height {
+            for col in 0..width {
+                rgba.extend_from_slice(&self.pixels[(row / scale) * self.width + col / scale]);
+            }
+        }
+        OverlayImage { x, y, width: width as u32, height: height as u32, rgba }
+    }
+}
+
+/// Surface pixels between the REC badge and the window's top-left corner
+const BADGE_MARGIN: u32 = 12;
+
+/// Badge shown in the top-left corner while the stream is recorded: a red
+/// dot and "REC"
+pub(super) fn recording_badge(scale: usize) -> OverlayImage {
+    const PADDING: usize = 3;
+    let width = PADDING + LINE_HEIGHT + PADDING + text_width(3) + PADDING;
+    let mut canvas = Canvas::new(width, LINE_HEIGHT + PADDING * 2, [0, 0, 0, 170]);
+    canvas.fill(PADDING + 1, PADDING + 1, LINE_HEIGHT - 2, LINE_HEIGHT - 2, [235, 40, 40, 255]);
+    canvas.text(PADDING * 2 + LINE_HEIGHT, PADDING, "REC", [255, 255, 255, 255]);
+    canvas.into_image(BADGE_MARGIN, BADGE_MARGIN, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_scales_to_surface_pixels() {
+        let mut canvas = Canvas::new(text_width(2) + 2, LINE_HEIGHT + 2, [0, 0, 0, 128]);
+        canvas.text(1, 1, "1a", [255; 4]);
+        let image = canvas.into_image(10, 20, 3);
+        assert_eq!((image.x, image.y), (10, 20));
+        assert_eq!((image.width, image.height), (13 * 3, 9 * 3));
+        assert_eq!(image.rgba.len(), (image.width * image.height * 4) as usize);
+        // Corner is background, and the text made it through
+        assert_eq!(&image.rgba[..4], &[0, 0, 0, 128]);
+        assert!(image.rgba.chunks(4).any(|p| p == [255; 4]));
+    }
+}
@@ -0,0 +1,49 @@
+// Picture-in-picture
+// A viewer window can shrink into a small borderless frame that stays on top
+// of other apps in the bottom-right corner of its screen, so the shared
+// screen can be watched while working elsewhere. It has no toolbar; it is
+// dragged with the mouse and F4 (or the `set_viewer_pip` command) toggles
+// it, restoring the window's size and place.
+
+/// Width of the mini viewer relative to the screen, and its bounds in
+/// screen pixels
+const WIDTH_FRACTION: u32 = 5;
+const MIN_WIDTH: u32 = 240;
+const MAX_WIDTH: u32 = 640;
+
+/// Screen pixels between the mini viewer and the screen edges
+const MARGIN: i32 = 24;
+
+/// Size of the mini viewer for a frame of `frame` size on a screen of
+/// `screen` size, in screen pixels; keeps the frame's aspect
+pub(super) fn pip_size(frame: (u32, u32), screen: (u32, u32)) -> (u32, u32) {
+    let width = (screen.0 / WIDTH_FRACTION).clamp(MIN_WIDTH, MAX_WIDTH);
+    let (fw, fh) = if frame.0 == 0 || frame.1 == 0 { (16, 9) } else { frame };
+    (width, (width as u64 * fh as u64 / fw as u64).max(1) as u32)
+}
+
+/// Top-left corner of the mini viewer of `size` in the bottom-right corner
+/// of a screen at `screen_origin` of `screen` size (y grows downwards)
+pub(super) fn pip_position(screen_origin: (i32, i32), screen: (u32, u32), size: (u32, u32)) -> (i32, i32) {
+    (
+        screen_origin.0 + screen.0 as i32 - size.0 as i32 - MARGIN,
+        screen_origin.1 + screen.1 as i32 - size.1 as i32 - MARGIN,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pip_geometry() {
+        assert_eq!(pip_size((1920, 1080), (2560, 1440)), (512, 288));
+        // Small screens get the smallest mini viewer, huge ones the largest
+        assert_eq!(pip_size((1920, 1200), (1024, 768)), (240, 150));
+        assert_eq!(pip_size((1920, 1080), (7680, 4320)), (640, 360));
+        assert_eq!(pip_size((0, 0), (2560, 1440)), (512, 288));
+
+        assert_eq!(pip_position((0, 0), (2560, 1440), (512, 288)), (2024, 1128));
+        assert_eq!(pip_position((-1920, 0), (1920, 1080), (384, 216)), (-408, 840));
+    }
+}
@@ -0,0 +1,176 @@
+// Remote pointer
+// The sharer's pointer, drawn by the viewer over the video from the position
+// the sharer sends beside it, so it stays sharp and moves smoothly whatever
+// the encoder does to the frames. When the sharer clicks, a ripple grows and
+// fades around it.
+
+use super::overlay::OverlayImage;
+use crate::capture::cursor::ARROW;
+use std::time::{Duration, Instant};
+
+/// How long a click ripple lasts
+const RIPPLE_DURATION: Duration = Duration::from_millis(400);
+
+/// Ripple radius when it ends and ring width, in sprite pixels
+const RIPPLE_RADIUS: f32 = 20.0;
+const RIPPLE_WIDTH: f32 = 2.5;
+
+/// Ripple color (RGB) and its opacity when it starts
+const RIPPLE_COLOR: [u8; 3] = [255, 196, 0];
+const RIPPLE_ALPHA: f32 = 230.0;
+
+/// The sharer's pointer, as a fraction of the frame's width and height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RemotePointer {
+    pub x: f32,
+    pub y: f32,
+    /// Primary button held
+    pub pressed: bool,
+}
+
+/// The pointer last reported and its click ripple
+#[derive(Debug)]
+pub(super) struct PointerLayer {
+    pointer: Option<RemotePointer>,
+    ripple_from: Option<Instant>,
+    ripples: bool,
+}
+
+impl PointerLayer {
+    /// Layer with or without click ripples
+    pub fn new(ripples: bool) -> Self {
+        Self { pointer: None, ripple_from: None, ripples }
+    }
+
+    /// The sharer's pointer moved, was pressed or released, or left the
+    /// shared area (`None`)
+    pub fn set(&mut self, pointer: Option<RemotePointer>, now: Instant) {
+        let was_pressed = self.pointer.is_some_and(|p| p.pressed);
+        if self.ripples && pointer.is_some_and(|p| p.pressed) && !was_pressed {
+            self.ripple_from = Some(now);
+        }
+        self.pointer = pointer;
+    }
+
+    /// Whether a ripple is running, so the layer has to be redrawn (once
+    /// more after it ended, to take it off)
+    pub fn animating(&self) -> bool {
+        self.ripple_from.is_some()
+    }
+
+    /// The pointer over the video at `video` (x, y, width, height on the
+    /// surface) on a surface of `surface` size, each sprite pixel `scale`
+    /// surface pixels; `None` while there is nothing to draw
+    pub fn image(&mut self, now: Instant, video: Option<[f32; 4]>, surface: (u32, u32), scale: usize) -> Option<OverlayImage> {
+        let progress = self
+            .ripple_from
+            .map(|from| now.saturating_duration_since(from).as_secs_f32() / RIPPLE_DURATION.as_secs_f32())
+            .filter(|&progress| progress < 1.0);
+        if progress.is_none() {
+            self.ripple_from = None;
+        }
+        let pointer = self.pointer?;
+        let [vx, vy, vw, vh] = video?;
+        let hotspot = ((vx + pointer.x * vw).round() as i32, (vy + pointer.y * vh).round() as i32);
+
+        // The image spans the arrow, and the ripple around the hotspot while
+        // there is one
+        let s = scale as i32;
+        let (arrow_w, arrow_h) = (ARROW[0].len() as i32 * s, ARROW.len() as i32 * s);
+        let pad = match progress {
+            Some(_) => ((RIPPLE_RADIUS + RIPPLE_WIDTH) * scale as f32).ceil() as i32,
+            None => 0,
+        };
+        let (left, top) = (hotspot.0 - pad, hotspot.1 - pad);
+        let (width, height) = (pad + arrow_w.max(pad), pad + arrow_h.max(pad));
+        let mut pixels = vec![[0u8; 4]; (width * height) as usize];
+
+        if let Some(progress) = progress {
+            let radius = RIPPLE_RADIUS * scale as f32 * (0.2 + 0.8 * progress);
+            let half_width = RIPPLE_WIDTH * scale as f32 / 2.0;
+            let alpha = RIPPLE_ALPHA * (1.0 - progress);
+            for row in 0..height {
+                for col in 0..width {
+                    let dx = (col - pad) as f32 + 0.5;
+                    let dy = (row - pad) as f32 + 0.5;
+                    // Antialiased ring: full inside its width, fading over a pixel
+                    let cover = (half_width + 0.5 - ((dx * dx + dy * dy).sqrt() - radius).abs()).clamp(0.0, 1.0);
+                    if cover > 0.0 {
+                        let [r, g, b] = RIPPLE_COLOR;
+                        pixels[(row * width + col) as usize] = [r, g, b, (alpha * cover) as u8];
+                    }
+                }
+            }
+        }
+
+        for (row, line) in ARROW.iter().enumerate() {
+            for (col, ch) in line.bytes().enumerate() {
+                let color = match ch {
+                    b'X' => [0, 0, 0, 255],
+                    b'.' => [255, 255, 255, 255],
+                    _ => continue,
+                };
+                for dy in 0..s {
+                    for dx in 0..s {
+                        let (x, y) = (pad + col as i32 * s + dx, pad + row as i32 * s + dy);
+                        pixels[(y * width + x) as usize] = color;
+                    }
+                }
+            }
+        }
+
+        clip(left, top, width, height, &pixels, surface)
+    }
+}
+
+/// The part of a `width`×`height` image at (left, top) that is on a surface
+/// of `surface` size; overlays have to stay on the surface
+fn clip(left: i32, top: i32, width: i32, height: i32, pixels: &[[u8; 4]], surface: (u32, u32)) -> Option<OverlayImage> {
+    let (x0, y0) = (left.max(0), top.max(0));
+    let (x1, y1) = ((left + width).min(surface.0 as i32), (top + height).min(surface.1 as i32));
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    let mut rgba = Vec::with_capacity(((x1 - x0) * (y1 - y0) * 4) as usize);
+    for row in y0..y1 {
+        let start = ((row - top) * width + (x0 - left)) as usize;
+        for pixel in &pixels[start..start + (x1 - x0) as usize] {
+            rgba.extend_from_slice(pixel);
+        }
+    }
+    Some(OverlayImage { x: x0 as u32, y: y0 as u32, width: (x1 - x0) as u32, height: (y1 - y0) as u32, rgba })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_on_video_and_ripple() {
+        let now = Instant::now();
+        let mut layer = PointerLayer::new(true);
+        let video = Some([100.0, 0.0, 800.0, 600.0]);
+        assert!(layer.image(now, video, (1000, 600), 1).is_none());
+
+        // Hotspot at the middle of the video, arrow-sized image
+        layer.set(Some(RemotePointer { x: 0.5, y: 0.5, pressed: false }), now);
+        let image = layer.image(now, video, (1000, 600), 2).unwrap();
+        assert_eq!((image.x, image.y, image.width, image.height), (500, 300, 24, 38));
+        assert!(!layer.animating());
+
+        // A press starts a ripple around the hotspot; holding does not restart it
+        layer.set(Some(RemotePointer { x: 0.5, y: 0.5, pressed: true }), now);
+        assert!(layer.animating());
+        let later = now + RIPPLE_DURATION / 2;
+        layer.set(Some(RemotePointer { x: 0.5, y: 0.5, pressed: true }), later);
+        assert!(layer.image(later, video, (1000, 600), 1).is_some_and(|i| i.x < 500 && i.y < 300));
+        assert!(layer.animating());
+
+        // Near the surface's corner the image is cut to fit
+        layer.set(Some(RemotePointer { x: 1.0, y: 1.0, pressed: false }), now);
+        let image = layer.image(now + RIPPLE_DURATION, video, (1000, 610), 1).unwrap();
+        assert_eq!((image.x, image.y, image.width, image.height), (900, 600, 12, 10));
+        assert_eq!(image.rgba.len(), 12 * 10 * 4);
+        assert!(!layer.animating());
+    }
+}
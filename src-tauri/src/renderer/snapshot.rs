@@ -0,0 +1,73 @@
+// Viewer snapshots
+// Saves the frame a viewer window last drew as a PNG in the download folder,
+// e.g. to keep a slide or an error message from the shared screen. The
+// render window reads the frame back from the GPU at the stream's size, so
+// it works for every frame format and decoder; the color filter is kept,
+// the toolbar, HUD and lens are not. Taken with the `capture_viewer_snapshot`
+// command or F2 in the window.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+
+/// Payload of the "viewer-snapshot" event
+#[derive(Debug, Clone, Serialize)]
+struct SnapshotEvent {
+    peer_ip: String,
+    path: String,
+}
+
+/// File name of a snapshot of `peer_ip`'s screen (IPv6 colons are not
+/// allowed in Windows file names)
+fn file_name(peer_ip: &str, stamp: &str) -> String {
+    let peer: String = peer_ip.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' }).collect();
+    format!("snapshot-{}-{}.png", peer, stamp)
+}
+
+/// Save the frame `peer_ip`'s viewer window last drew under `dir`; returns
+/// the file. Blocks until the window has read the frame back.
+pub fn capture(peer_ip: &str, dir: &Path) -> Result<PathBuf, String> {
+    let handle = super::viewers::get(peer_ip).ok_or_else(|| format!("Not viewing {}", peer_ip))?;
+    let (width, height, rgba) = handle.snapshot().map_err(|e| e.to_string())?;
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stamp = crate::capture::archive::utc_stamp(crate::capture::archive::now_secs());
+    let path = dir.join(file_name(peer_ip, &stamp));
+    image::save_buffer(&path, &rgba, width, height, image::ExtendedColorType::Rgba8)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    log::info!("Saved {}x{} snapshot of {}'s screen to {}", width, height, peer_ip, path.display());
+
+    if let Some(app) = crate::APP_HANDLE.get() {
+        let _ = app.emit(
+            "viewer-snapshot",
+            SnapshotEvent { peer_ip: peer_ip.to_string(), path: path.to_string_lossy().to_string() },
+        );
+    }
+    Ok(path)
+}
+
+/// Where snapshots go: the download folder of file transfers
+pub fn default_dir() -> PathBuf {
+    crate::transfer::get_transfer_manager().download_dir().to_path_buf()
+}
+
+/// Take a snapshot on its own thread, for the snapshot key in the window
+pub fn capture_in_background(peer_ip: &str) {
+    let peer_ip = peer_ip.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = capture(&peer_ip, &default_dir()) {
+            log::warn!("Snapshot of {}'s screen failed: {}", peer_ip, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_is_portable() {
+        assert_eq!(file_name("192.168.1.5", "20260101-120000"), "snapshot-192.168.1.5-20260101-120000.png");
+        assert_eq!(file_name("fe80::1%en0", "x"), "snapshot-fe80--1-en0-x.png");
+    }
+}
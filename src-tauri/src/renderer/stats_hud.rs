@@ -0,0 +1,119 @@
+// Stats HUD
+// Toggled in the render window with F3: a box in the top-right corner with
+// the viewer's frame rate, bitrate, decode time, frames waiting for the
+// render window and glass-to-glass latency, so a choppy stream can be looked
+// into without the logs. It is an overlay, so unlike the latency HUD it does
+// not touch the frame.
+
+use super::overlay::{text_width, Canvas, OverlayImage, LINE_HEIGHT};
+use crate::streaming::stats::ViewerStats;
+use std::time::{Duration, Instant};
+
+/// How often the numbers are redrawn
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// Font pixels around the text and between lines
+const PADDING: usize = 3;
+const LINE_GAP: usize = 2;
+
+/// Surface pixels between the box and the window's top-right corner
+const MARGIN: u32 = 8;
+
+const BOX_COLOR: [u8; 4] = [0, 0, 0, 180];
+const TEXT_COLOR: [u8; 4] = [120, 255, 120, 255];
+
+/// Lines shown by the HUD
+pub(super) fn stats_lines(stats: &ViewerStats, latency_ms: Option<u64>, queued: usize) -> Vec<String> {
+    vec![
+        format!("FPS {:.1} / {:.1}", stats.fps, stats.receive_fps),
+        format!("BITRATE {:.1} MBPS", stats.bitrate_bps as f64 / 1_000_000.0),
+        format!("DECODE {:.1} MS", stats.avg_decode_ms),
+        format!("QUEUE {}", queued),
+        format!("LATENCY {}", latency_ms.map_or("--".to_string(), |ms| format!("{} MS", ms))),
+        format!("DROPPED {} ERRORS {}", stats.dropped_frames, stats.decode_errors),
+    ]
+}
+
+/// Whether the HUD shows and when it was last drawn
+#[derive(Debug, Default)]
+pub(super) struct StatsHud {
+    visible: bool,
+    drawn_at: Option<Instant>,
+}
+
+impl StatsHud {
+    /// Show or hide the HUD; returns whether it shows now
+    pub fn toggle(&mut self) -> bool {
+        self.visible = !self.visible;
+        self.drawn_at = None;
+        self.visible
+    }
+
+    /// Whether the HUD shows and its numbers are due for a redraw
+    pub fn due(&self, now: Instant) -> bool {
+        self.visible && self.drawn_at.is_none_or(|at| now.duration_since(at) >= REFRESH)
+    }
+
+    /// The HUD on a surface `surface_width` wide with the current numbers;
+    /// `None` while it is hidden. `queued` is the most frames found waiting
+    /// for the render window since the last time.
+    pub fn image(&mut self, now: Instant, surface_width: u32, scale: usize, queued: usize) -> Option<OverlayImage> {
+        if !self.visible {
+            return None;
+        }
+        self.drawn_at = Some(now);
+        let lines = stats_lines(
+            &crate::streaming::stats::viewer_stats(),
+            crate::streaming::latency::last_ms(),
+            queued,
+        );
+
+        let chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let width = text_width(chars) + PADDING * 2;
+        let height = lines.len() * (LINE_HEIGHT + LINE_GAP) - LINE_GAP + PADDING * 2;
+        let mut canvas = Canvas::new(width, height, BOX_COLOR);
+        for (i, line) in lines.iter().enumerate() {
+            canvas.text(PADDING, PADDING + i * (LINE_HEIGHT + LINE_GAP), line, TEXT_COLOR);
+        }
+
+        let x = surface_width.saturating_sub((width * scale) as u32 + MARGIN);
+        Some(canvas.into_image(x, MARGIN, scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_lines() {
+        let stats = ViewerStats {
+            fps: 59.94,
+            receive_fps: 60.0,
+            bitrate_bps: 8_240_000,
+            avg_decode_ms: 3.14,
+            ..Default::default()
+        };
+        let lines = stats_lines(&stats, None, 2);
+        assert_eq!(lines[0], "FPS 59.9 / 60.0");
+        assert_eq!(lines[1], "BITRATE 8.2 MBPS");
+        assert_eq!(lines[2], "DECODE 3.1 MS");
+        assert_eq!(lines[3], "QUEUE 2");
+        assert_eq!(lines[4], "LATENCY --");
+        assert_eq!(stats_lines(&stats, Some(42), 0)[4], "LATENCY 42 MS");
+    }
+
+    #[test]
+    fn test_toggle_and_refresh() {
+        let mut hud = StatsHud::default();
+        let now = Instant::now();
+        assert!(!hud.due(now));
+        assert!(hud.toggle());
+        assert!(hud.due(now));
+        hud.drawn_at = Some(now);
+        assert!(!hud.due(now + REFRESH / 2));
+        assert!(hud.due(now + REFRESH));
+        assert!(!hud.toggle());
+        assert!(!hud.due(now + REFRESH));
+    }
+}
@@ -0,0 +1,50 @@
+// Tiled viewer windows
+// Several screens can be watched at once, each in its own window. F7 in any
+// of them (or the `tile_viewer_windows` command) lays every open viewer
+// window out in a grid on the primary screen, in the order they opened; the
+// title bars name whose screen each one shows.
+
+/// Screen pixels between tiles and around the grid
+const GAP: u32 = 8;
+
+/// Columns and rows of a grid of `count` tiles, as square as it gets with
+/// the extra column first (screens are wider than tall)
+pub(super) fn grid(count: usize) -> (u32, u32) {
+    let count = count.max(1) as u32;
+    let cols = (count as f64).sqrt().ceil() as u32;
+    (cols, count.div_ceil(cols))
+}
+
+/// Position and size (x, y, width, height; y grows downwards) of tile
+/// `index` of `count` on a screen at `origin` of `screen` size
+pub(super) fn tile_rect(index: usize, count: usize, origin: (i32, i32), screen: (u32, u32)) -> (i32, i32, u32, u32) {
+    let (cols, rows) = grid(count);
+    let (col, row) = (index as u32 % cols, (index as u32 / cols).min(rows - 1));
+    let width = (screen.0.saturating_sub(GAP * (cols + 1)) / cols).max(1);
+    let height = (screen.1.saturating_sub(GAP * (rows + 1)) / rows).max(1);
+    (
+        origin.0 + (GAP + col * (width + GAP)) as i32,
+        origin.1 + (GAP + row * (height + GAP)) as i32,
+        width,
+        height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_grid() {
+        assert_eq!(grid(1), (1, 1));
+        assert_eq!(grid(2), (2, 1));
+        assert_eq!(grid(3), (2, 2));
+        assert_eq!(grid(5), (3, 2));
+        assert_eq!(grid(9), (3, 3));
+
+        // Two windows side by side; a third goes under the first
+        assert_eq!(tile_rect(0, 2, (0, 0), (1928, 1096)), (8, 8, 952, 1080));
+        assert_eq!(tile_rect(1, 2, (0, 0), (1928, 1096)), (968, 8, 952, 1080));
+        assert_eq!(tile_rect(2, 3, (-1920, 0), (1928, 1096)), (-1912, 552, 952, 536));
+    }
+}
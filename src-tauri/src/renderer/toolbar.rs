@@ -0,0 +1,214 @@
+// Viewer toolbar for the winit window (Windows/Linux)
+// macOS puts NSPopUpButtons in a floating NSPanel; winit has no native
+// controls, so here the renderer draws the toolbar itself as an overlay: a
+// row of buttons, one per setting. It shows while the cursor is near the top
+// of the window. A left click on a button selects the next option, a right
+// click the previous one.
+
+use super::overlay::{scale_for, text_width, Canvas, OverlayImage, LINE_HEIGHT};
+use super::{ColorFilter, FitMode};
+use crate::simple_streaming::{BITRATE_OPTIONS, RESOLUTION_OPTIONS};
+
+/// Font pixels around labels and between buttons
+const PADDING: usize = 3;
+const GAP: usize = 2;
+
+/// Surface pixels between the toolbar and the top of the window
+const MARGIN: u32 = 8;
+
+/// How far below the toolbar the cursor still shows it, in font pixels
+const REVEAL: f64 = 24.0;
+
+/// Colors (RGBA, straight alpha)
+const BAR_COLOR: [u8; 4] = [20, 20, 20, 200];
+const BUTTON_COLOR: [u8; 4] = [60, 60, 60, 230];
+const HOVER_COLOR: [u8; 4] = [90, 90, 90, 240];
+const TEXT_COLOR: [u8; 4] = [240, 240, 240, 255];
+
+/// A setting on the toolbar, in button order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Setting {
+    Resolution,
+    Bitrate,
+    Filter,
+    Fit,
+}
+
+impl Setting {
+    const ALL: [Setting; 4] = [Setting::Resolution, Setting::Bitrate, Setting::Filter, Setting::Fit];
+
+    /// Labels of the setting's options
+    fn labels(self) -> Vec<&'static str> {
+        match self {
+            Setting::Resolution => RESOLUTION_OPTIONS.iter().map(|o| o.label).collect(),
+            Setting::Bitrate => BITRATE_OPTIONS.iter().map(|o| o.label).collect(),
+            Setting::Filter => ColorFilter::ALL.iter().map(|f| f.label()).collect(),
+            Setting::Fit => FitMode::ALL.iter().map(|m| m.label()).collect(),
+        }
+    }
+}
+
+/// The toolbar's selections, placement and visibility
+#[derive(Debug)]
+pub(super) struct Toolbar {
+    labels: [Vec<&'static str>; 4],
+    selected: [usize; 4],
+    /// Surface pixels per font pixel
+    scale: usize,
+    visible: bool,
+    hovered: Option<usize>,
+}
+
+impl Toolbar {
+    /// Toolbar starting on the given option of each setting (resolution,
+    /// bitrate, filter, fit)
+    pub fn new(selected: [usize; 4]) -> Self {
+        Self {
+            labels: Setting::ALL.map(Setting::labels),
+            selected,
+            scale: 2,
+            visible: false,
+            hovered: None,
+        }
+    }
+
+    /// Follow the window's scale factor
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale = scale_for(scale_factor);
+    }
+
+    /// Selected option of `setting`
+    pub fn selected(&self, setting: Setting) -> usize {
+        self.selected[setting as usize]
+    }
+
+    /// Width of each button in font pixels, fitting its longest label so
+    /// the toolbar does not jump around
+    fn button_widths(&self) -> [usize; 4] {
+        self.labels.each_ref().map(|labels| {
+            let chars = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            text_width(chars.max(1)) + PADDING * 2
+        })
+    }
+
+    /// Size in font pixels
+    fn font_size(&self) -> (usize, usize) {
+        let width = self.button_widths().iter().sum::<usize>() + GAP * (Setting::ALL.len() + 1);
+        (width, LINE_HEIGHT + PADDING * 2 + GAP * 2)
+    }
+
+    /// Rect on a surface `surface_width` wide: x, y, width, height
+    fn rect(&self, surface_width: u32) -> (u32, u32, u32, u32) {
+        let (w, h) = self.font_size();
+        let (w, h) = ((w * self.scale) as u32, (h * self.scale) as u32);
+        (surface_width.saturating_sub(w) / 2, MARGIN, w, h)
+    }
+
+    /// Button under (x, y), if the toolbar shows
+    fn button_at(&self, x: f64, y: f64, surface_width: u32) -> Option<usize> {
+        let (left, top, width, height) = self.rect(surface_width);
+        if !self.visible || x < left as f64 || y < top as f64 || x >= (left + width) as f64 || y >= (top + height) as f64 {
+            return None;
+        }
+        let fx = ((x - left as f64) / self.scale as f64) as usize;
+        let fy = ((y - top as f64) / self.scale as f64) as usize;
+        if fy < GAP || fy >= GAP + LINE_HEIGHT + PADDING * 2 {
+            return None;
+        }
+        let mut start = GAP;
+        for (i, w) in self.button_widths().into_iter().enumerate() {
+            if (start..start + w).contains(&fx) {
+                return Some(i);
+            }
+            start += w + GAP;
+        }
+        None
+    }
+
+    /// Whether the toolbar shows and covers (x, y); clicks there are the
+    /// toolbar's, not the sharer's
+    pub fn contains(&self, x: f64, y: f64, surface_width: u32) -> bool {
+        let (left, top, width, height) = self.rect(surface_width);
+        self.visible && x >= left as f64 && y >= top as f64 && x < (left + width) as f64 && y < (top + height) as f64
+    }
+
+    /// The cursor moved to `cursor` (None: it left the window). Returns
+    /// whether the toolbar has to be redrawn.
+    pub fn hover(&mut self, cursor: Option<(f64, f64)>, surface_width: u32) -> bool {
+        let (_, top, _, height) = self.rect(surface_width);
+        let reveal = (top + height) as f64 + REVEAL * self.scale as f64;
+        let visible = cursor.is_some_and(|(_, y)| y < reveal);
+        let changed = visible != self.visible;
+        self.visible = visible;
+
+        let hovered = cursor.and_then(|(x, y)| self.button_at(x, y, surface_width));
+        let changed = changed || hovered != self.hovered;
+        self.hovered = hovered;
+        changed
+    }
+
+    /// Click at (x, y): selects the next option of the button there, or the
+    /// previous one with `forward` unset. Returns the setting that changed.
+    pub fn click(&mut self, x: f64, y: f64, surface_width: u32, forward: bool) -> Option<Setting> {
+        let i = self.button_at(x, y, surface_width)?;
+        let count = self.labels[i].len();
+        self.selected[i] = if forward { (self.selected[i] + 1) % count } else { (self.selected[i] + count - 1) % count };
+        Some(Setting::ALL[i])
+    }
+
+    /// The toolbar as drawn on a surface `surface_width` wide; `None` while
+    /// it is hidden
+    pub fn image(&self, surface_width: u32) -> Option<OverlayImage> {
+        if !self.visible {
+            return None;
+        }
+        let (width, height) = self.font_size();
+        let mut canvas = Canvas::new(width, height, BAR_COLOR);
+        let mut left = GAP;
+        for (i, width) in self.button_widths().into_iter().enumerate() {
+            let color = if self.hovered == Some(i) { HOVER_COLOR } else { BUTTON_COLOR };
+            canvas.fill(left, GAP, width, LINE_HEIGHT + PADDING * 2, color);
+
+            let label = self.labels[i].get(self.selected[i]).copied().unwrap_or("");
+            let text_left = left + (width - text_width(label.chars().count())) / 2;
+            canvas.text(text_left, GAP + PADDING, label, TEXT_COLOR);
+            left += width + GAP;
+        }
+
+        let (x, y, _, _) = self.rect(surface_width);
+        Some(canvas.into_image(x, y, self.scale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hover_reveals_and_click_cycles() {
+        let mut toolbar = Toolbar::new([1, 2, 0, 0]);
+        let surface_width = 1600;
+        assert!(toolbar.image(surface_width).is_none());
+
+        // Far from the top: stays hidden and clicks go to the sharer
+        assert!(!toolbar.hover(Some((800.0, 600.0)), surface_width));
+        assert_eq!(toolbar.click(800.0, 20.0, surface_width, true), None);
+
+        // First button, in the middle of its height
+        let (left, top, _, height) = toolbar.rect(surface_width);
+        let (x, y) = ((left + 10) as f64, (top + height / 2) as f64);
+        assert!(toolbar.hover(Some((x, y)), surface_width));
+        assert!(toolbar.contains(x, y, surface_width));
+        assert_eq!(toolbar.click(x, y, surface_width, true), Some(Setting::Resolution));
+        assert_eq!(toolbar.selected(Setting::Resolution), 2);
+        toolbar.click(x, y, surface_width, false);
+        toolbar.click(x, y, surface_width, false);
+        toolbar.click(x, y, surface_width, false);
+        assert_eq!(toolbar.selected(Setting::Resolution), RESOLUTION_OPTIONS.len() - 1);
+        assert_eq!(toolbar.selected(Setting::Bitrate), 2);
+
+        let image = toolbar.image(surface_width).unwrap();
+        assert_eq!(image.rgba.len(), (image.width * image.height * 4) as usize);
+        assert_eq!(image.x + image.width / 2, surface_width / 2);
+    }
+}
@@ -0,0 +1,77 @@
+// Open viewer windows by sharer
+// The viewer loops register the windows they open for a sharer's screen, so
+// commands can reach them by the sharer's IP (snapshots, picture-in-picture)
+// and they can be listed and tiled together.
+
+use super::RenderWindowHandle;
+use parking_lot::RwLock;
+use serde::Serialize;
+
+/// An open viewer window
+struct Entry {
+    peer_ip: String,
+    label: String,
+    handle: RenderWindowHandle,
+}
+
+/// Open windows in the order they opened
+static WINDOWS: once_cell::sync::Lazy<RwLock<Vec<Entry>>> = once_cell::sync::Lazy::new(Default::default);
+
+/// A viewer window as the UI lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewerWindow {
+    pub peer_ip: String,
+    /// The window's title: whose screen it shows
+    pub label: String,
+}
+
+/// Title of a viewer window for `peer_ip`'s screen, naming the peer if it
+/// was discovered
+pub fn title_for(peer_ip: &str) -> String {
+    let name = crate::network::discovery::get_devices()
+        .into_iter()
+        .find(|d| d.ip == peer_ip)
+        .map_or_else(|| peer_ip.to_string(), |d| d.name);
+    format!("{} 的屏幕 ({})", name, peer_ip)
+}
+
+/// A viewer window showing `peer_ip`'s screen opened with `label` as title
+pub fn register(peer_ip: &str, label: &str, handle: &RenderWindowHandle) {
+    let mut windows = WINDOWS.write();
+    windows.retain(|e| e.handle.is_open());
+    windows.push(Entry { peer_ip: peer_ip.to_string(), label: label.to_string(), handle: handle.clone() });
+}
+
+/// The viewer windows showing `peer_ip`'s screen closed
+pub fn unregister(peer_ip: &str) {
+    WINDOWS.write().retain(|e| e.peer_ip != peer_ip);
+}
+
+/// The first open window showing `peer_ip`'s screen
+pub fn get(peer_ip: &str) -> Option<RenderWindowHandle> {
+    WINDOWS.read().iter().find(|e| e.peer_ip == peer_ip && e.handle.is_open()).map(|e| e.handle.clone())
+}
+
+/// Every open viewer window, oldest first
+pub fn list() -> Vec<ViewerWindow> {
+    WINDOWS
+        .read()
+        .iter()
+        .filter(|e| e.handle.is_open())
+        .map(|e| ViewerWindow { peer_ip: e.peer_ip.clone(), label: e.label.clone() })
+        .collect()
+}
+
+/// Lay every open viewer window out in a grid on the primary screen (see
+/// `tiling`); returns how many there are
+pub fn tile() -> usize {
+    let windows = WINDOWS.read();
+    let open: Vec<_> = windows.iter().filter(|e| e.handle.is_open()).collect();
+    for (index, entry) in open.iter().enumerate() {
+        if let Err(e) = entry.handle.tile(index, open.len()) {
+            log::warn!("Failed to tile the window of {}: {}", entry.peer_ip, e);
+        }
+    }
+    log::info!("Tiled {} viewer windows", open.len());
+    open.len()
+}
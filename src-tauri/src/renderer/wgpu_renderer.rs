@@ -1,7 +1,12 @@
 // wgpu-based GPU renderer
 // Efficient texture upload and rendering for video frames
 
-use super::{FrameFormat, RenderFrame, RendererError};
+use super::filter::FILTER_SHADER;
+use super::fit::visible_part;
+use super::magnifier::LENS_SHADER;
+use super::overlay::{OverlayImage, OverlayLayer, OVERLAY_SHADER};
+use super::{ColorFilter, FitMode, FrameFormat, Magnifier, RenderFrame, RendererError};
+use crate::encoder::color::ColorSpace;
 use std::sync::Arc;
 
 /// WGSL shader for rendering BGRA textures
@@ -40,9 +45,14 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 @group(0) @binding(0) var frame_texture: texture_2d<f32>;
 @group(0) @binding(1) var frame_sampler: sampler;
 
+fn sample_rgb(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(frame_texture, frame_sampler, uv).rgb;
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(frame_texture, frame_sampler, input.tex_coord);
+    let color = textureSample(frame_texture, frame_sampler, frame_uv(input.tex_coord));
+    return vec4<f32>(apply_filter(color.rgb), color.a);
 }
 "#;
 
@@ -83,21 +93,137 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 @group(0) @binding(2) var v_texture: texture_2d<f32>;
 @group(0) @binding(3) var yuv_sampler: sampler;
 
+fn sample_rgb(uv: vec2<f32>) -> vec3<f32> {
+    let y = textureSample(y_texture, yuv_sampler, uv).r;
+    let u = textureSample(u_texture, yuv_sampler, uv).r - 0.5;
+    let v = textureSample(v_texture, yuv_sampler, uv).r - 0.5;
+    return yuv_to_rgb(y, u, v);
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let y = textureSample(y_texture, yuv_sampler, input.tex_coord).r;
-    let u = textureSample(u_texture, yuv_sampler, input.tex_coord).r - 0.5;
-    let v = textureSample(v_texture, yuv_sampler, input.tex_coord).r - 0.5;
+    return vec4<f32>(apply_filter(sample_rgb(frame_uv(input.tex_coord))), 1.0);
+}
+"#;
 
-    // BT.601 YUV to RGB conversion
-    let r = y + 1.402 * v;
-    let g = y - 0.344 * u - 0.714 * v;
-    let b = y + 1.772 * u;
+/// WGSL shader for NV12 textures decoded on the GPU. The planes are bound
+/// as separate views: luma as R8, interleaved chroma as RG8.
+const NV12_SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    var tex_coords = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 0.0),
+    );
 
-    return vec4<f32>(r, g, b, 1.0);
+    var output: VertexOutput;
+    output.position = vec4<f32>(positions[vertex_index], 0.0, 1.0);
+    output.tex_coord = tex_coords[vertex_index];
+    return output;
+}
+
+@group(0) @binding(0) var y_texture: texture_2d<f32>;
+@group(0) @binding(1) var uv_texture: texture_2d<f32>;
+@group(0) @binding(2) var nv12_sampler: sampler;
+
+fn sample_rgb(uv: vec2<f32>) -> vec3<f32> {
+    let y = textureSample(y_texture, nv12_sampler, uv).r;
+    let chroma = textureSample(uv_texture, nv12_sampler, uv).rg - vec2<f32>(0.5);
+    return yuv_to_rgb(y, chroma.x, chroma.y);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(apply_filter(sample_rgb(frame_uv(input.tex_coord))), 1.0);
+}
+"#;
+
+/// WGSL of the per-window view settings (group 1), shared by both pipelines
+const VIEW_SHADER: &str = r#"
+struct ViewParams {
+    color_filter: vec4<u32>,
+    lens: vec4<f32>,
+    // Luma offset and scale of the stream's range
+    yuv_range: vec4<f32>,
+    // Cr in red, Cb and Cr in green, Cb in blue, for the stream's matrix
+    yuv_matrix: vec4<f32>,
+    // Offset and span of the frame's texture coordinates on screen (fit mode crop)
+    frame_uv: vec4<f32>,
+}
+
+@group(1) @binding(0) var<uniform> view_params: ViewParams;
+
+// YUV with chroma centered on 0 to RGB, in the stream's color space
+fn yuv_to_rgb(y: f32, u: f32, v: f32) -> vec3<f32> {
+    let l = (y - view_params.yuv_range.x) * view_params.yuv_range.y;
+    let m = view_params.yuv_matrix;
+    return vec3<f32>(l + m.x * v, l - m.y * u - m.z * v, l + m.w * u);
+}
+
+fn frame_uv(tex_coord: vec2<f32>) -> vec2<f32> {
+    return view_params.frame_uv.xy + tex_coord * view_params.frame_uv.zw;
 }
 "#;
 
+/// Size of `ViewParams`
+const VIEW_PARAMS_SIZE: u64 = 80;
+
+/// Offset of `yuv_range` in `ViewParams`
+const VIEW_PARAMS_YUV_OFFSET: u64 = 32;
+
+/// Offset of `frame_uv` in `ViewParams`
+const VIEW_PARAMS_FRAME_UV_OFFSET: u64 = 64;
+
+/// `frame_uv` showing the whole texture
+const FULL_FRAME_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+fn uv_uniform(uv: [f32; 4]) -> Vec<u8> {
+    uv.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// `yuv_range` and `yuv_matrix` of `ViewParams` for `color`
+fn yuv_uniform(color: ColorSpace) -> [u8; 32] {
+    let m = color.yuv_to_rgb();
+    let values = [
+        m.y_offset as f32 / 255.0,
+        m.y_scale as f32 / 256.0,
+        0.0,
+        0.0,
+        m.rv as f32 / 256.0,
+        m.gu as f32 / 256.0,
+        m.gv as f32 / 256.0,
+        m.bu as f32 / 256.0,
+    ];
+    let mut bytes = [0u8; 32];
+    for (chunk, value) in bytes.chunks_exact_mut(4).zip(values) {
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Full source of a frame shader: its own sampling plus the shared filter
+/// and lens code
+fn frame_shader_source(frame_shader: &str) -> String {
+    [frame_shader, VIEW_SHADER, FILTER_SHADER, LENS_SHADER].concat()
+}
+
 /// wgpu-based GPU renderer
 pub struct WgpuRenderer {
     device: wgpu::Device,
@@ -117,12 +243,54 @@ pub struct WgpuRenderer {
     yuv_textures: Option<(wgpu::Texture, wgpu::Texture, wgpu::Texture)>,
     yuv_bind_group: Option<wgpu::BindGroup>,
 
+    // NV12 pipeline, for textures a decoder made on the shared device
+    nv12_pipeline: wgpu::RenderPipeline,
+    nv12_bind_group_layout: wgpu::BindGroupLayout,
+    nv12_bind_group: Option<wgpu::BindGroup>,
+
+    // Running on `gpu::shared()`'s device, so decoded textures can be bound
+    shared_device: bool,
+
     // Samplers
     sampler: wgpu::Sampler,
 
+    // View settings uniform (color filter and lens), shared by both pipelines
+    view_buffer: wgpu::Buffer,
+    view_bind_group: wgpu::BindGroup,
+    color_filter: ColorFilter,
+    /// Color space `yuv_range` and `yuv_matrix` are set for
+    color_space: ColorSpace,
+    fit_mode: FitMode,
+    /// Texture coordinates `frame_uv` is set to
+    frame_uv: [f32; 4],
+
+    // Magnifier lens pass
+    bgra_lens_pipeline: wgpu::RenderPipeline,
+    yuv_lens_pipeline: wgpu::RenderPipeline,
+    nv12_lens_pipeline: wgpu::RenderPipeline,
+    magnifier: Option<Magnifier>,
+
+    // Overlay pass (viewer toolbar, stats HUD)
+    overlay_pipeline: wgpu::RenderPipeline,
+    overlays: [Option<Overlay>; OverlayLayer::COUNT],
+
     // Current frame dimensions
     frame_width: u32,
     frame_height: u32,
+
+    // Capture time of the uploaded frame, until it is presented
+    captured_at_ms: Option<u64>,
+
+    // A frame was uploaded and not presented yet
+    frame_pending: bool,
+}
+
+/// Overlay image on the GPU
+struct Overlay {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// x, y, width, height in surface pixels
+    viewport: [f32; 4],
 }
 
 impl WgpuRenderer {
@@ -155,22 +323,35 @@ impl WgpuRenderer {
         width: u32,
         height: u32,
     ) -> Result<Self, RendererError> {
+        // The window created the surface from the shared instance if there
+        // is one; use the shared device when it can present to it
+        let shared = super::gpu::shared().filter(|gpu| gpu.adapter.is_surface_supported(&surface));
+        let shared_device = shared.is_some();
+        let (adapter, device, queue) = match shared {
+            Some(gpu) => (gpu.adapter.clone(), gpu.device.clone(), gpu.queue.clone()),
+            None => {
+                let adapter = instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::HighPerformance,
+                        compatible_surface: Some(&surface),
+                        force_fallback_adapter: false,
+                    })
+                    .await
+                    .map_err(|e| RendererError::GpuNotAvailable(format!("Failed to request adapter: {}", e)))?;
+
+                let (device, queue) = adapter
+                    .request_device(&wgpu::DeviceDescriptor::default())
+                    .await
+                    .map_err(|e| RendererError::InitError(format!("Failed to create device: {}", e)))?;
+                (adapter, device, queue)
+            }
+        };
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .map_err(|e| RendererError::GpuNotAvailable(format!("Failed to request adapter: {}", e)))?;
-
-        log::info!("Using GPU adapter: {:?}", adapter.get_info().name);
-
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .map_err(|e| RendererError::InitError(format!("Failed to create device: {}", e)))?;
+        log::info!(
+            "Using GPU adapter: {:?} (shared device: {})",
+            adapter.get_info().name,
+            shared_device
+        );
 
         // Configure surface
         let capabilities = surface.get_capabilities(&adapter);
@@ -181,8 +362,13 @@ impl WgpuRenderer {
             .copied()
             .unwrap_or(capabilities.formats[0]);
 
-        // Pick the best present mode from what's supported
-        let present_mode = if capabilities.present_modes.contains(&wgpu::PresentMode::Mailbox) {
+        // Pick the best present mode from what's supported; safe mode sticks
+        // to Fifo, which every driver implements
+        let safe = crate::safe_mode::is_enabled();
+        crate::safe_mode::record(crate::safe_mode::Subsystem::Presentation, safe);
+        let present_mode = if safe {
+            wgpu::PresentMode::Fifo
+        } else if capabilities.present_modes.contains(&wgpu::PresentMode::Mailbox) {
             wgpu::PresentMode::Mailbox
         } else if capabilities.present_modes.contains(&wgpu::PresentMode::Immediate) {
             wgpu::PresentMode::Immediate
@@ -215,9 +401,11 @@ impl WgpuRenderer {
             ..Default::default()
         });
 
+        let (view_bind_group_layout, view_buffer, view_bind_group) = create_view_binding(&device, &queue);
+
         let bgra_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("BGRA Shader"),
-            source: wgpu::ShaderSource::Wgsl(BGRA_SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(frame_shader_source(BGRA_SHADER).into()),
         });
 
         let bgra_bind_group_layout =
@@ -246,7 +434,7 @@ impl WgpuRenderer {
         let bgra_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("BGRA Pipeline Layout"),
-                bind_group_layouts: &[&bgra_bind_group_layout],
+                bind_group_layouts: &[&bgra_bind_group_layout, &view_bind_group_layout],
                 immediate_size: 0,
             });
 
@@ -281,7 +469,7 @@ impl WgpuRenderer {
 
         let yuv_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("YUV Shader"),
-            source: wgpu::ShaderSource::Wgsl(YUV_SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(frame_shader_source(YUV_SHADER).into()),
         });
 
         let yuv_bind_group_layout =
@@ -330,7 +518,7 @@ impl WgpuRenderer {
         let yuv_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("YUV Pipeline Layout"),
-                bind_group_layouts: &[&yuv_bind_group_layout],
+                bind_group_layouts: &[&yuv_bind_group_layout, &view_bind_group_layout],
                 immediate_size: 0,
             });
 
@@ -363,6 +551,12 @@ impl WgpuRenderer {
             cache: None,
         });
 
+        let bgra_lens_pipeline = create_lens_pipeline(&device, "BGRA", &bgra_pipeline_layout, &bgra_shader, format);
+        let yuv_lens_pipeline = create_lens_pipeline(&device, "YUV", &yuv_pipeline_layout, &yuv_shader, format);
+        let (nv12_bind_group_layout, nv12_pipeline, nv12_lens_pipeline) =
+            create_nv12_pipelines(&device, &view_bind_group_layout, format);
+        let overlay_pipeline = create_overlay_pipeline(&device, &bgra_bind_group_layout, format);
+
         log::info!("wgpu renderer initialized (raw surface)");
 
         Ok(Self {
@@ -378,20 +572,42 @@ impl WgpuRenderer {
             yuv_bind_group_layout,
             yuv_textures: None,
             yuv_bind_group: None,
+            nv12_pipeline,
+            nv12_bind_group_layout,
+            nv12_bind_group: None,
+            shared_device,
             sampler,
+            view_buffer,
+            view_bind_group,
+            color_filter: ColorFilter::None,
+            color_space: ColorSpace::default(),
+            fit_mode: FitMode::default(),
+            frame_uv: FULL_FRAME_UV,
+            bgra_lens_pipeline,
+            yuv_lens_pipeline,
+            nv12_lens_pipeline,
+            magnifier: None,
+            overlay_pipeline,
+            overlays: Default::default(),
             frame_width: 0,
             frame_height: 0,
+            captured_at_ms: None,
+            frame_pending: false,
         })
     }
 
     async fn new_internal(
         window: Option<Arc<winit::window::Window>>,
     ) -> Result<Self, RendererError> {
-        // Create wgpu instance
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            ..Default::default()
-        });
+        // Create wgpu instance, the shared one if there is a shared device
+        let shared = super::gpu::shared();
+        let instance = match &shared {
+            Some(gpu) => gpu.instance.clone(),
+            None => wgpu::Instance::new(&wgpu::InstanceDescriptor {
+                backends: wgpu::Backends::all(),
+                ..Default::default()
+            }),
+        };
 
         // Create surface if window provided
         let surface = if let Some(ref window) = window {
@@ -404,23 +620,34 @@ impl WgpuRenderer {
             None
         };
 
-        // Request adapter
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: surface.as_ref(),
-                force_fallback_adapter: false,
-            })
-            .await
-            .map_err(|e| RendererError::GpuNotAvailable(format!("Failed to request adapter: {}", e)))?;
-
-        log::info!("Using GPU adapter: {:?}", adapter.get_info().name);
+        // Use the shared device when it can present to the surface
+        let shared = shared.filter(|gpu| surface.as_ref().is_none_or(|s| gpu.adapter.is_surface_supported(s)));
+        let shared_device = shared.is_some();
+        let (adapter, device, queue) = match shared {
+            Some(gpu) => (gpu.adapter.clone(), gpu.device.clone(), gpu.queue.clone()),
+            None => {
+                let adapter = instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::HighPerformance,
+                        compatible_surface: surface.as_ref(),
+                        force_fallback_adapter: false,
+                    })
+                    .await
+                    .map_err(|e| RendererError::GpuNotAvailable(format!("Failed to request adapter: {}", e)))?;
+
+                let (device, queue) = adapter
+                    .request_device(&wgpu::DeviceDescriptor::default())
+                    .await
+                    .map_err(|e| RendererError::InitError(format!("Failed to create device: {}", e)))?;
+                (adapter, device, queue)
+            }
+        };
 
-        // Request device
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .map_err(|e| RendererError::InitError(format!("Failed to create device: {}", e)))?;
+        log::info!(
+            "Using GPU adapter: {:?} (shared device: {})",
+            adapter.get_info().name,
+            shared_device
+        );
 
         // Configure surface if available
         let surface_config = if let (Some(surface), Some(window)) = (&surface, &window) {
@@ -438,7 +665,11 @@ impl WgpuRenderer {
                 format,
                 width: size.width.max(1),
                 height: size.height.max(1),
-                present_mode: wgpu::PresentMode::Mailbox, // Low latency
+                present_mode: if crate::safe_mode::is_enabled() {
+                    wgpu::PresentMode::Fifo
+                } else {
+                    wgpu::PresentMode::Mailbox // Low latency
+                },
                 alpha_mode: wgpu::CompositeAlphaMode::Auto,
                 view_formats: vec![],
                 desired_maximum_frame_latency: 2,
@@ -462,9 +693,11 @@ impl WgpuRenderer {
         });
 
         // Create BGRA pipeline
+        let (view_bind_group_layout, view_buffer, view_bind_group) = create_view_binding(&device, &queue);
+
         let bgra_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("BGRA Shader"),
-            source: wgpu::ShaderSource::Wgsl(BGRA_SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(frame_shader_source(BGRA_SHADER).into()),
         });
 
         let bgra_bind_group_layout =
@@ -493,7 +726,7 @@ impl WgpuRenderer {
         let bgra_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("BGRA Pipeline Layout"),
-                bind_group_layouts: &[&bgra_bind_group_layout],
+                bind_group_layouts: &[&bgra_bind_group_layout, &view_bind_group_layout],
                 immediate_size: 0,
             });
 
@@ -534,7 +767,7 @@ impl WgpuRenderer {
         // Create YUV pipeline
         let yuv_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("YUV Shader"),
-            source: wgpu::ShaderSource::Wgsl(YUV_SHADER.into()),
+            source: wgpu::ShaderSource::Wgsl(frame_shader_source(YUV_SHADER).into()),
         });
 
         let yuv_bind_group_layout =
@@ -583,7 +816,7 @@ impl WgpuRenderer {
         let yuv_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("YUV Pipeline Layout"),
-                bind_group_layouts: &[&yuv_bind_group_layout],
+                bind_group_layouts: &[&yuv_bind_group_layout, &view_bind_group_layout],
                 immediate_size: 0,
             });
 
@@ -616,6 +849,13 @@ impl WgpuRenderer {
             cache: None,
         });
 
+        let bgra_lens_pipeline =
+            create_lens_pipeline(&device, "BGRA", &bgra_pipeline_layout, &bgra_shader, surface_format);
+        let yuv_lens_pipeline = create_lens_pipeline(&device, "YUV", &yuv_pipeline_layout, &yuv_shader, surface_format);
+        let (nv12_bind_group_layout, nv12_pipeline, nv12_lens_pipeline) =
+            create_nv12_pipelines(&device, &view_bind_group_layout, surface_format);
+        let overlay_pipeline = create_overlay_pipeline(&device, &bgra_bind_group_layout, surface_format);
+
         log::info!("wgpu renderer initialized");
 
         Ok(Self {
@@ -631,9 +871,27 @@ impl WgpuRenderer {
             yuv_bind_group_layout,
             yuv_textures: None,
             yuv_bind_group: None,
+            nv12_pipeline,
+            nv12_bind_group_layout,
+            nv12_bind_group: None,
+            shared_device,
             sampler,
+            view_buffer,
+            view_bind_group,
+            color_filter: ColorFilter::None,
+            color_space: ColorSpace::default(),
+            fit_mode: FitMode::default(),
+            frame_uv: FULL_FRAME_UV,
+            bgra_lens_pipeline,
+            yuv_lens_pipeline,
+            nv12_lens_pipeline,
+            magnifier: None,
+            overlay_pipeline,
+            overlays: Default::default(),
             frame_width: 0,
             frame_height: 0,
+            captured_at_ms: None,
+            frame_pending: false,
         })
     }
 
@@ -649,15 +907,102 @@ impl WgpuRenderer {
 
     /// Upload a frame to GPU textures
     pub fn upload_frame(&mut self, frame: &RenderFrame) -> Result<(), RendererError> {
-        match frame.format {
-            FrameFormat::BGRA => self.upload_bgra_frame(frame),
-            FrameFormat::YUV420 => self.upload_yuv_frame(frame),
+        let start = std::time::Instant::now();
+        self.captured_at_ms = frame.captured_at_ms;
+        self.frame_pending = true;
+        if frame.format != FrameFormat::BGRA && frame.color_space != self.color_space {
+            self.queue.write_buffer(&self.view_buffer, VIEW_PARAMS_YUV_OFFSET, &yuv_uniform(frame.color_space));
+            self.color_space = frame.color_space;
+            log::info!("Viewer color space: {}", frame.color_space.name());
+        }
+        let result = match (&frame.texture, frame.format) {
+            (Some(texture), _) => self.bind_texture(frame, texture),
+            (None, FrameFormat::BGRA) => self.upload_bgra_frame(frame),
+            (None, FrameFormat::YUV420) => self.upload_yuv_frame(frame),
+            (None, FrameFormat::NV12) => Err(RendererError::RenderError("NV12 frame without a texture".to_string())),
+        };
+        crate::streaming::timing::record(crate::streaming::timing::Stage::Upload, start.elapsed());
+        result
+    }
+
+    /// Bind a texture a decoder produced on the shared device, without
+    /// copying it
+    fn bind_texture(&mut self, frame: &RenderFrame, texture: &wgpu::Texture) -> Result<(), RendererError> {
+        if !self.shared_device {
+            return Err(RendererError::RenderError(
+                "GPU frame for a window that is not on the shared device".to_string(),
+            ));
         }
+
+        let bind_group = match texture.format() {
+            wgpu::TextureFormat::NV12 => {
+                let plane = |aspect, format| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        format: Some(format),
+                        aspect,
+                        ..Default::default()
+                    })
+                };
+                let y_view = plane(wgpu::TextureAspect::Plane0, wgpu::TextureFormat::R8Unorm);
+                let uv_view = plane(wgpu::TextureAspect::Plane1, wgpu::TextureFormat::Rg8Unorm);
+                self.nv12_bind_group = Some(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("NV12 Bind Group"),
+                    layout: &self.nv12_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&y_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&uv_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                }));
+                return self.set_frame_size(frame);
+            }
+            wgpu::TextureFormat::Bgra8UnormSrgb | wgpu::TextureFormat::Rgba8UnormSrgb => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("BGRA Bind Group"),
+                    layout: &self.bgra_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                    ],
+                })
+            }
+            other => {
+                return Err(RendererError::RenderError(format!("Unsupported frame texture format {:?}", other)));
+            }
+        };
+
+        // The bind group now points at the decoder's texture; CPU frames
+        // after this need a texture of our own again
+        self.bgra_texture = None;
+        self.bgra_bind_group = Some(bind_group);
+        self.set_frame_size(frame)
+    }
+
+    fn set_frame_size(&mut self, frame: &RenderFrame) -> Result<(), RendererError> {
+        self.frame_width = frame.width;
+        self.frame_height = frame.height;
+        Ok(())
     }
 
     fn upload_bgra_frame(&mut self, frame: &RenderFrame) -> Result<(), RendererError> {
         // Recreate texture if dimensions changed
-        if self.frame_width != frame.width || self.frame_height != frame.height {
+        if self.bgra_texture.is_none() || self.frame_width != frame.width || self.frame_height != frame.height {
             let texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("BGRA Frame Texture"),
                 size: wgpu::Extent3d {
@@ -731,7 +1076,7 @@ impl WgpuRenderer {
         let uv_height = (frame.height + 1) / 2;
 
         // Recreate textures if dimensions changed
-        if self.frame_width != frame.width || self.frame_height != frame.height {
+        if self.yuv_textures.is_none() || self.frame_width != frame.width || self.frame_height != frame.height {
             let y_texture = self.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("Y Texture"),
                 size: wgpu::Extent3d {
@@ -884,6 +1229,7 @@ impl WgpuRenderer {
 
     /// Render the current frame to the surface
     pub fn render(&mut self, format: FrameFormat) -> Result<(), RendererError> {
+        let start = std::time::Instant::now();
         let surface = self
             .surface
             .as_ref()
@@ -903,6 +1249,18 @@ impl WgpuRenderer {
                 label: Some("Render Encoder"),
             });
 
+        // Draw the part of the frame the fit mode puts on the surface; the
+        // viewport has to stay inside the surface, so overflow is cropped
+        // through the texture coordinates
+        let surface_size = self.surface_config.as_ref().map(|c| (c.width as f32, c.height as f32));
+        let visible = self.video_viewport().zip(surface_size).and_then(|(video, size)| visible_part(video, size));
+        let uv = visible.map_or(FULL_FRAME_UV, |(_, uv)| uv);
+        if uv != self.frame_uv {
+            // Lands before this frame's commands, which are submitted after it
+            self.queue.write_buffer(&self.view_buffer, VIEW_PARAMS_FRAME_UV_OFFSET, &uv_uniform(uv));
+            self.frame_uv = uv;
+        }
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -921,52 +1279,338 @@ impl WgpuRenderer {
                 multiview_mask: None,
             });
 
-            // Set viewport to maintain video aspect ratio (letterbox/pillarbox)
-            if let Some(ref config) = self.surface_config {
-                if self.frame_width > 0 && self.frame_height > 0 {
-                    let surface_w = config.width as f32;
-                    let surface_h = config.height as f32;
-                    let frame_aspect = self.frame_width as f32 / self.frame_height as f32;
-                    let surface_aspect = surface_w / surface_h;
-
-                    let (vp_x, vp_y, vp_w, vp_h) = if frame_aspect > surface_aspect {
-                        // Video wider than window - fit width, letterbox top/bottom
-                        let h = surface_w / frame_aspect;
-                        (0.0, (surface_h - h) / 2.0, surface_w, h)
-                    } else {
-                        // Video taller than window - fit height, pillarbox left/right
-                        let w = surface_h * frame_aspect;
-                        ((surface_w - w) / 2.0, 0.0, w, surface_h)
-                    };
-
-                    render_pass.set_viewport(vp_x, vp_y, vp_w, vp_h, 0.0, 1.0);
-                }
-            }
-
-            match format {
-                FrameFormat::BGRA => {
-                    if let Some(ref bind_group) = self.bgra_bind_group {
-                        render_pass.set_pipeline(&self.bgra_pipeline);
-                        render_pass.set_bind_group(0, bind_group, &[]);
-                        render_pass.draw(0..6, 0..1);
-                    }
-                }
-                FrameFormat::YUV420 => {
-                    if let Some(ref bind_group) = self.yuv_bind_group {
-                        render_pass.set_pipeline(&self.yuv_pipeline);
-                        render_pass.set_bind_group(0, bind_group, &[]);
-                        render_pass.draw(0..6, 0..1);
-                    }
-                }
+            if let Some(([vp_x, vp_y, vp_w, vp_h], _)) = visible {
+                render_pass.set_viewport(vp_x, vp_y, vp_w, vp_h, 0.0, 1.0);
             }
+            self.draw_frame(&mut render_pass, format);
         }
 
+        self.render_lens(&mut encoder, &view, format);
+        self.render_overlays(&mut encoder, &view);
+
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
+        crate::streaming::timing::record(crate::streaming::timing::Stage::Render, start.elapsed());
+        if let Some(captured_at_ms) = self.captured_at_ms.take() {
+            crate::streaming::latency::record_presented(captured_at_ms);
+        }
+        if std::mem::take(&mut self.frame_pending) {
+            crate::streaming::stats::frame_rendered();
+        }
 
         Ok(())
     }
 
+    /// Draw the uploaded frame of `format` with its pipeline
+    fn draw_frame(&self, render_pass: &mut wgpu::RenderPass, format: FrameFormat) {
+        let (pipeline, bind_group) = match format {
+            FrameFormat::BGRA => (&self.bgra_pipeline, &self.bgra_bind_group),
+            FrameFormat::YUV420 => (&self.yuv_pipeline, &self.yuv_bind_group),
+            FrameFormat::NV12 => (&self.nv12_pipeline, &self.nv12_bind_group),
+        };
+        if let Some(bind_group) = bind_group {
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_bind_group(1, &self.view_bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+
+    /// Read the uploaded frame back at its own size as it is drawn (color
+    /// filter included; no overlays or lens): width, height and tightly
+    /// packed RGBA. Blocks until the GPU is done.
+    pub fn snapshot(&mut self, format: FrameFormat) -> Result<(u32, u32, Vec<u8>), RendererError> {
+        let target = self
+            .surface_config
+            .as_ref()
+            .map(|c| c.format)
+            .ok_or_else(|| RendererError::RenderError("No surface configured".to_string()))?;
+        let (width, height) = (self.frame_width, self.frame_height);
+        if width == 0 || height == 0 {
+            return Err(RendererError::RenderError("No frame to snapshot".to_string()));
+        }
+
+        // Drawn into a texture of the surface's format, so the frame
+        // pipelines can draw it
+        let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Snapshot Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: target,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Snapshot Readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        // All of the frame, whatever part the fit mode shows; the next
+        // render puts back its own texture coordinates
+        if self.frame_uv != FULL_FRAME_UV {
+            self.queue.write_buffer(&self.view_buffer, VIEW_PARAMS_FRAME_UV_OFFSET, &uv_uniform(FULL_FRAME_UV));
+            self.frame_uv = FULL_FRAME_UV;
+        }
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Snapshot Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Snapshot Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+            self.draw_frame(&mut render_pass, format);
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = readback.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| RendererError::RenderError(format!("GPU poll failed: {}", e)))?;
+        rx.recv()
+            .map_err(|_| RendererError::RenderError("Readback was dropped".to_string()))?
+            .map_err(|e| RendererError::RenderError(format!("Readback failed: {}", e)))?;
+
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in slice.get_mapped_range().chunks(padded_row as usize).take(height as usize) {
+            rgba.extend_from_slice(&row[..width as usize * 4]);
+        }
+        readback.unmap();
+        let bgra = matches!(target, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        for px in rgba.chunks_exact_mut(4) {
+            if bgra {
+                px.swap(0, 2);
+            }
+            px[3] = 255;
+        }
+        Ok((width, height, rgba))
+    }
+
+    /// Change the color filter; takes effect with the next render
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        if filter != self.color_filter {
+            self.queue.write_buffer(&self.view_buffer, 0, &filter.uniform());
+            self.color_filter = filter;
+            log::info!("Viewer color filter: {:?}", filter);
+        }
+    }
+
+    pub fn color_filter(&self) -> ColorFilter {
+        self.color_filter
+    }
+
+    /// Show the magnifier lens, or hide it with `None`; takes effect with
+    /// the next render
+    pub fn set_magnifier(&mut self, magnifier: Option<Magnifier>) {
+        self.magnifier = magnifier;
+    }
+
+    /// Change how the frame fits the window; takes effect with the next render
+    pub fn set_fit_mode(&mut self, mode: FitMode) {
+        if mode != self.fit_mode {
+            self.fit_mode = mode;
+            log::info!("Viewer fit mode: {:?}", mode);
+        }
+    }
+
+    pub fn fit_mode(&self) -> FitMode {
+        self.fit_mode
+    }
+
+    /// Size of the uploaded frame; zero before the first
+    pub fn frame_size(&self) -> (u32, u32) {
+        (self.frame_width, self.frame_height)
+    }
+
+    /// Where the whole video goes on the surface (x, y, width, height) in
+    /// the fit mode; may reach past the surface
+    pub(super) fn video_viewport(&self) -> Option<[f32; 4]> {
+        let config = self.surface_config.as_ref()?;
+        if self.frame_width == 0 || self.frame_height == 0 {
+            return None;
+        }
+        let surface = (config.width as f32, config.height as f32);
+        Some(self.fit_mode.video_rect(surface, (self.frame_width as f32, self.frame_height as f32)))
+    }
+
+    /// Draw the magnifier lens over the rendered frame, in its own pass
+    fn render_lens(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, format: FrameFormat) {
+        let (Some(magnifier), Some(config), Some(video)) = (self.magnifier, &self.surface_config, self.video_viewport())
+        else {
+            return;
+        };
+        let Some(lens) = magnifier.geometry((config.width as f32, config.height as f32), video) else {
+            return;
+        };
+        let (pipeline, bind_group) = match format {
+            FrameFormat::BGRA => (&self.bgra_lens_pipeline, &self.bgra_bind_group),
+            FrameFormat::YUV420 => (&self.yuv_lens_pipeline, &self.yuv_bind_group),
+            FrameFormat::NV12 => (&self.nv12_lens_pipeline, &self.nv12_bind_group),
+        };
+        let Some(bind_group) = bind_group else {
+            return;
+        };
+
+        // Lands before this frame's commands, which are submitted after it
+        let params: Vec<u8> = lens.params.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.queue.write_buffer(&self.view_buffer, 16, &params);
+
+        let mut lens_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lens Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        let [x, y, w, h] = lens.viewport;
+        lens_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+        lens_pass.set_pipeline(pipeline);
+        lens_pass.set_bind_group(0, bind_group, &[]);
+        lens_pass.set_bind_group(1, &self.view_bind_group, &[]);
+        lens_pass.draw(0..6, 0..1);
+    }
+
+    /// Show an image over the frame in `layer`, or hide the layer with
+    /// `None`; takes effect with the next render
+    pub(super) fn set_overlay(&mut self, layer: OverlayLayer, image: Option<&OverlayImage>) {
+        let slot = &mut self.overlays[layer as usize];
+        let Some(image) = image else {
+            *slot = None;
+            return;
+        };
+        let size = wgpu::Extent3d { width: image.width, height: image.height, depth_or_array_layers: 1 };
+        if slot.as_ref().is_none_or(|o| o.texture.size() != size) {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Overlay Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Overlay Bind Group"),
+                layout: &self.bgra_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                ],
+            });
+            *slot = Some(Overlay { texture, bind_group, viewport: [0.0; 4] });
+        }
+        let Some(overlay) = slot.as_mut() else {
+            return;
+        };
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &overlay.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &image.rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(image.width * 4),
+                rows_per_image: Some(image.height),
+            },
+            size,
+        );
+        overlay.viewport = [image.x as f32, image.y as f32, image.width as f32, image.height as f32];
+    }
+
+    /// Blend the overlays over the rendered frame, in their own pass
+    fn render_overlays(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let Some(config) = &self.surface_config else {
+            return;
+        };
+        if self.overlays.iter().all(Option::is_none) {
+            return;
+        }
+
+        let mut overlay_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        overlay_pass.set_pipeline(&self.overlay_pipeline);
+        for overlay in self.overlays.iter().flatten() {
+            // The viewport has to stay on the surface; a window smaller than
+            // an overlay goes without it
+            let [x, y, w, h] = overlay.viewport;
+            if x + w > config.width as f32 || y + h > config.height as f32 {
+                continue;
+            }
+            overlay_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+            overlay_pass.set_bind_group(0, &overlay.bind_group, &[]);
+            overlay_pass.draw(0..6, 0..1);
+        }
+    }
+
     /// Get device and queue for external use
     pub fn device(&self) -> &wgpu::Device {
         &self.device
@@ -976,3 +1620,203 @@ impl WgpuRenderer {
         &self.queue
     }
 }
+
+/// Layout, uniform buffer and bind group of the view settings (group 1)
+fn create_view_binding(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> (wgpu::BindGroupLayout, wgpu::Buffer, wgpu::BindGroup) {
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("View Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    });
+
+    // Zeroed at creation, which is `ColorFilter::None`; the YUV conversion
+    // starts out as the default color space and the whole frame is shown
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("View Uniform"),
+        size: VIEW_PARAMS_SIZE,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    queue.write_buffer(&buffer, VIEW_PARAMS_YUV_OFFSET, &yuv_uniform(ColorSpace::default()));
+    queue.write_buffer(&buffer, VIEW_PARAMS_FRAME_UV_OFFSET, &uv_uniform(FULL_FRAME_UV));
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("View Bind Group"),
+        layout: &layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    });
+
+    (layout, buffer, bind_group)
+}
+
+/// Bind group layout, frame pipeline and lens pipeline for NV12 textures
+fn create_nv12_pipelines(
+    device: &wgpu::Device,
+    view_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline, wgpu::RenderPipeline) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("NV12 Shader"),
+        source: wgpu::ShaderSource::Wgsl(frame_shader_source(NV12_SHADER).into()),
+    });
+
+    let plane_entry = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("NV12 Bind Group Layout"),
+        entries: &[
+            plane_entry(0),
+            plane_entry(1),
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("NV12 Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout, view_layout],
+        immediate_size: 0,
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("NV12 Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    });
+    let lens_pipeline = create_lens_pipeline(device, "NV12", &layout, &shader, format);
+
+    (bind_group_layout, pipeline, lens_pipeline)
+}
+
+/// Pipeline drawing the magnifier lens from a frame shader's `fs_lens`
+fn create_lens_pipeline(
+    device: &wgpu::Device,
+    name: &str,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{} Lens Pipeline", name)),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_lens"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    })
+}
+
+/// Pipeline blending an RGBA overlay over the frame; its texture binds like
+/// a BGRA frame's
+fn create_overlay_pipeline(
+    device: &wgpu::Device,
+    texture_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Overlay Shader"),
+        source: wgpu::ShaderSource::Wgsl(OVERLAY_SHADER.into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Overlay Pipeline Layout"),
+        bind_group_layouts: &[texture_layout],
+        immediate_size: 0,
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Overlay Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview_mask: None,
+        cache: None,
+    })
+}
@@ -2,7 +2,11 @@
 // Uses winit for window management on Windows/Linux,
 // and native AppKit window on macOS (winit requires main thread on macOS)
 
-use super::{wgpu_renderer::WgpuRenderer, FrameFormat, RenderFrame, RendererError};
+use super::annotation::{frame_point, AnnotationLayer};
+use super::overlay::{recording_badge, scale_for, OverlayLayer};
+use super::pointer::PointerLayer;
+use super::stats_hud::StatsHud;
+use super::{wgpu_renderer::WgpuRenderer, Annotation, FrameFormat, RemotePointer, RenderFrame, RendererError};
 use crossbeam_channel::{Receiver, Sender};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -10,11 +14,11 @@ use std::sync::Arc;
 #[cfg(not(target_os = "macos"))]
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::WindowEvent as WinitWindowEvent,
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     platform::scancode::PhysicalKeyExtScancode,
-    window::{Window, WindowAttributes, WindowId},
+    window::{Window, WindowAttributes, WindowId, WindowLevel},
 };
 
 /// Events from the render window
@@ -24,16 +28,39 @@ pub enum WindowEvent {
     CloseRequested,
     Focused(bool),
     KeyPressed(u32),
+    /// Text typed into the window, composed by the IME if one is active
+    TextInput(String),
     MouseMoved(f64, f64),
     MouseButton(u32, bool), // button, pressed
     MouseWheel(f64, f64),
-    ResolutionRequested(u32, u32, u32), // (target_width, target_height, bitrate) from toolbar
+    ResolutionRequested(u32, u32, u32, bool), // (target_width, target_height, bitrate, near_lossless) from toolbar
+    /// The snapshot key was pressed
+    SnapshotRequested,
+    /// A shape was drawn in the window, or the clear key pressed; for the
+    /// sharer and the other viewers
+    Annotated(Annotation),
 }
 
+/// Width, height and RGBA pixels of a frame read back from the window
+type Snapshot = Result<(u32, u32, Vec<u8>), String>;
+
 /// Command to the render window
 enum WindowCommand {
     RenderFrame(RenderFrame),
     SetTitle(String),
+    /// Read the last frame back (see `WgpuRenderer::snapshot`)
+    Snapshot(Sender<Snapshot>),
+    /// Enter or leave picture-in-picture
+    SetPip(bool),
+    /// Move into tile `.0` of a grid of `.1` on the primary screen
+    Tile(usize, usize),
+    /// The sharer's pointer moved, or left the shared area
+    Pointer(Option<RemotePointer>),
+    /// Show a shape; with the flag set it is ours and goes out as
+    /// `WindowEvent::Annotated` too
+    Annotate(Annotation, bool),
+    /// Show or take off the REC badge
+    SetRecording(bool),
     Close,
 }
 
@@ -63,6 +90,62 @@ impl RenderWindowHandle {
             .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
     }
 
+    /// The frame the window last drew, read back at the stream's size:
+    /// width, height and RGBA pixels. Blocks until the window answers.
+    pub fn snapshot(&self) -> Result<(u32, u32, Vec<u8>), RendererError> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.command_tx
+            .send(WindowCommand::Snapshot(tx))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))?;
+        rx.recv_timeout(std::time::Duration::from_secs(5))
+            .map_err(|_| RendererError::WindowError("Window did not answer".to_string()))?
+            .map_err(RendererError::RenderError)
+    }
+
+    /// Shrink the window into the picture-in-picture mini viewer, or
+    /// restore it
+    pub fn set_pip(&self, enabled: bool) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::SetPip(enabled))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
+    /// Move the window into tile `index` of a grid of `count` windows on
+    /// the primary screen, leaving picture-in-picture
+    pub fn tile(&self, index: usize, count: usize) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::Tile(index, count))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
+    /// Draw the sharer's pointer at `pointer`, or take it off with `None`
+    pub fn set_pointer(&self, pointer: Option<RemotePointer>) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::Pointer(pointer))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
+    /// Show the REC badge while the stream is recorded, or take it off
+    pub fn set_recording(&self, recording: bool) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::SetRecording(recording))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
+    /// Show a shape the sharer or another viewer drew
+    pub fn show_annotation(&self, annotation: Annotation) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::Annotate(annotation, false))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
+    /// Draw a shape as if drawn in the window, so it goes to the others too
+    pub fn annotate(&self, annotation: Annotation) -> Result<(), RendererError> {
+        self.command_tx
+            .send(WindowCommand::Annotate(annotation, true))
+            .map_err(|_| RendererError::WindowError("Failed to send command".to_string()))
+    }
+
     /// Close the window
     pub fn close(&self) {
         let _ = self.command_tx.send(WindowCommand::Close);
@@ -96,6 +179,25 @@ pub struct RenderWindow {
     window: Option<Arc<Window>>,
     renderer: Option<WgpuRenderer>,
     current_format: FrameFormat,
+    /// IME is composing; key presses belong to the composition
+    ime_composing: bool,
+    /// Modifier keys held, for the magnifier
+    modifiers: winit::keyboard::ModifiersState,
+    /// Cursor position over the window, in physical pixels
+    cursor: Option<(f64, f64)>,
+    magnifier: Option<super::Magnifier>,
+    /// Drawn by the renderer; macOS uses a native panel instead
+    toolbar: super::toolbar::Toolbar,
+    stats_hud: StatsHud,
+    /// Most frames found waiting since the stats HUD was last drawn
+    queued: usize,
+    /// Size and place to restore while in picture-in-picture
+    pip: Option<(PhysicalSize<u32>, Option<PhysicalPosition<i32>>)>,
+    /// The sharer's pointer, drawn over the video
+    pointer: PointerLayer,
+    annotations: AnnotationLayer,
+    /// The stream is recorded: the REC badge is shown
+    recording: bool,
 }
 
 /// Render window (macOS uses native AppKit window)
@@ -139,6 +241,14 @@ impl RenderWindow {
         is_open: Arc<AtomicBool>,
     ) {
         let title_clone = title.clone();
+        // Start the toolbar on the options in settings, like the macOS panel
+        let (default_res_idx, default_br_idx) = crate::commands::get_default_streaming_indices();
+        let toolbar = super::toolbar::Toolbar::new([
+            default_res_idx,
+            default_br_idx,
+            crate::commands::viewer_color_filter().index(),
+            crate::commands::viewer_fit_mode().index(),
+        ]);
         std::thread::spawn(move || {
             log::debug!("Render window thread started for '{}'", title_clone);
 
@@ -156,6 +266,17 @@ impl RenderWindow {
                 window: None,
                 renderer: None,
                 current_format: FrameFormat::BGRA,
+                ime_composing: false,
+                modifiers: Default::default(),
+                cursor: None,
+                magnifier: None,
+                toolbar,
+                stats_hud: StatsHud::default(),
+                queued: 0,
+                pip: None,
+                pointer: PointerLayer::new(crate::commands::click_ripple_enabled()),
+                annotations: AnnotationLayer::default(),
+                recording: false,
             };
 
             event_loop.run_app(&mut app).ok();
@@ -217,22 +338,31 @@ impl RenderWindow {
 
         // Read default resolution/bitrate indices from settings
         let (default_res_idx, default_br_idx) = crate::commands::get_default_streaming_indices();
+        let default_filter = crate::commands::viewer_color_filter();
+        let default_fit = crate::commands::viewer_fit_mode();
 
         // Create floating toolbar on main thread (using child NSPanel for reliable rendering over Metal)
         let (toolbar_tx, toolbar_rx) =
-            std::sync::mpsc::channel::<Result<(usize, usize, usize), String>>();
+            std::sync::mpsc::channel::<Result<(usize, usize, usize, usize, usize), String>>();
 
         let window_addr_for_toolbar = ns_window_addr;
         app_handle
             .run_on_main_thread(move || {
-                let result = create_toolbar_panel(window_addr_for_toolbar, width, default_res_idx, default_br_idx);
+                let result = create_toolbar_panel(
+                    window_addr_for_toolbar,
+                    width,
+                    default_res_idx,
+                    default_br_idx,
+                    default_filter.index(),
+                    default_fit.index(),
+                );
                 let _ = toolbar_tx.send(result);
             })
             .map_err(|e| {
                 RendererError::WindowError(format!("Failed to dispatch toolbar creation: {}", e))
             })?;
 
-        let (toolbar_panel_addr, res_popup_addr, br_popup_addr) = toolbar_rx
+        let (toolbar_panel_addr, res_popup_addr, br_popup_addr, filter_popup_addr, fit_popup_addr) = toolbar_rx
             .recv()
             .map_err(|e| {
                 RendererError::WindowError(format!("Toolbar channel closed: {}", e))
@@ -246,13 +376,18 @@ impl RenderWindow {
         let (surface_tx, surface_rx) =
             std::sync::mpsc::channel::<Result<(wgpu::Instance, wgpu::Surface<'static>), String>>();
 
+        // The surface comes from the shared instance when there is one, so
+        // the renderer can use the device decoders share
+        let shared_instance = super::gpu::shared().map(|gpu| gpu.instance.clone());
         app_handle
             .run_on_main_thread(move || {
                 let result =
                     (|| -> Result<(wgpu::Instance, wgpu::Surface<'static>), String> {
-                        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-                            backends: wgpu::Backends::METAL,
-                            ..Default::default()
+                        let instance = shared_instance.unwrap_or_else(|| {
+                            wgpu::Instance::new(&wgpu::InstanceDescriptor {
+                                backends: wgpu::Backends::METAL,
+                                ..Default::default()
+                            })
                         });
 
                         let ns_view_ptr =
@@ -309,6 +444,9 @@ impl RenderWindow {
             let mut renderer = match renderer {
                 Ok(r) => {
                     log::info!("macOS render thread: renderer READY ({}x{})", width, height);
+                    let mut r = r;
+                    r.set_color_filter(default_filter);
+                    r.set_fit_mode(default_fit);
                     r
                 }
                 Err(e) => {
@@ -331,6 +469,31 @@ impl RenderWindow {
             let mut last_mouse_move_time = std::time::Instant::now();
             let mut last_selected_resolution: isize = default_res_idx as isize;
             let mut last_selected_bitrate: isize = default_br_idx as isize;
+            let mut last_selected_filter: isize = default_filter.index() as isize;
+            let mut last_selected_fit: isize = default_fit.index() as isize;
+            let mut last_magnifier: Option<super::Magnifier> = None;
+            let mut stats_hud = StatsHud::default();
+            let mut stats_key_down = false;
+            let mut snapshot_key_down = false;
+            let mut pip = false;
+            let mut pip_key_down = false;
+            let pip_restore = Arc::new(parking_lot::Mutex::new(None));
+            // Asked for by `set_pip`; applied with the next input poll
+            let mut pip_request: Option<bool> = None;
+            // Asked for by `tile`; applied like `pip_request`
+            let mut tile_request: Option<(usize, usize)> = None;
+            let mut tile_key_down = false;
+            let mut pointer = PointerLayer::new(crate::commands::click_ripple_enabled());
+            let mut annotations = AnnotationLayer::default();
+            // Overlays to redraw with the next render
+            let mut pointer_moved = false;
+            let mut annotations_changed = false;
+            let mut recording: Option<bool> = None;
+            let mut pen_down = false;
+            let mut draw_key_down = false;
+            let mut clear_key_down = false;
+            let mut backing_scale: f64 = 1.0;
+            let mut queued: usize = 0;
             let toolbar_hide_delay = std::time::Duration::from_secs(3);
 
             // Simple render loop (no winit event loop needed)
@@ -343,7 +506,9 @@ impl RenderWindow {
 
                 // Process all pending commands - only keep the latest frame
                 let mut latest_frame: Option<RenderFrame> = None;
+                let mut snapshot_requests: Vec<Sender<Snapshot>> = Vec::new();
                 let mut stale_count: u32 = 0;
+                queued = queued.max(command_rx.len());
                 while let Ok(cmd) = command_rx.try_recv() {
                     match cmd {
                         WindowCommand::RenderFrame(frame) => {
@@ -355,6 +520,22 @@ impl RenderWindow {
                         WindowCommand::SetTitle(_title) => {
                             // TODO: dispatch to main thread to update NSWindow title
                         }
+                        // Answered once the latest frame is uploaded
+                        WindowCommand::Snapshot(reply) => snapshot_requests.push(reply),
+                        WindowCommand::SetPip(enabled) => pip_request = Some(enabled),
+                        WindowCommand::Tile(index, count) => tile_request = Some((index, count)),
+                        WindowCommand::Pointer(p) => {
+                            pointer.set(p, std::time::Instant::now());
+                            pointer_moved = true;
+                        }
+                        WindowCommand::Annotate(annotation, ours) => {
+                            if ours {
+                                let _ = event_tx.send(WindowEvent::Annotated(annotation.clone()));
+                            }
+                            annotations.apply(annotation);
+                            annotations_changed = true;
+                        }
+                        WindowCommand::SetRecording(on) => recording = Some(on),
                         WindowCommand::Close => {
                             is_open.store(false, Ordering::Relaxed);
                             break;
@@ -380,6 +561,10 @@ impl RenderWindow {
                     }
                 }
 
+                for reply in snapshot_requests {
+                    let _ = reply.send(renderer.snapshot(current_format).map_err(|e| e.to_string()));
+                }
+
                 // Detect window resize by querying NSView backing size
                 if has_new_frame {
                     let (pixel_w, pixel_h) = unsafe {
@@ -405,9 +590,40 @@ impl RenderWindow {
                         renderer.resize(pixel_w, pixel_h);
                         last_surface_w = pixel_w;
                         last_surface_h = pixel_h;
+                        pointer_moved = true;
+                        annotations_changed = true;
                     }
                 }
 
+                // Annotations and the sharer's pointer follow the video; a
+                // static screen is redrawn for them
+                if annotations_changed {
+                    annotations_changed = false;
+                    let image = annotations.image(
+                        renderer.video_viewport(),
+                        (last_surface_w, last_surface_h),
+                        backing_scale.round().max(1.0) as usize,
+                    );
+                    renderer.set_overlay(OverlayLayer::Annotations, image.as_ref());
+                    has_new_frame = true;
+                }
+                if pointer_moved || pointer.animating() {
+                    pointer_moved = false;
+                    let image = pointer.image(
+                        std::time::Instant::now(),
+                        renderer.video_viewport(),
+                        (last_surface_w, last_surface_h),
+                        backing_scale.round().max(1.0) as usize,
+                    );
+                    renderer.set_overlay(OverlayLayer::Pointer, image.as_ref());
+                    has_new_frame = true;
+                }
+                if let Some(on) = recording.take() {
+                    let image = on.then(|| recording_badge(scale_for(backing_scale)));
+                    renderer.set_overlay(OverlayLayer::Recording, image.as_ref());
+                    has_new_frame = true;
+                }
+
                 // Render if we have new frame data
                 if has_new_frame {
                     if let Err(e) = renderer.render(current_format) {
@@ -435,23 +651,156 @@ impl RenderWindow {
 
                 // Toolbar: mouse tracking + auto-hide + resolution polling
                 if check_counter % 10 == 0 { // every ~10ms
-                    let (mouse_in_window, mouse_x, mouse_y) = unsafe {
+                    let (mouse_in_window, mouse_x, mouse_y, view_h, scale, modifier_flags, is_key) = unsafe {
                         use objc2::msg_send;
-                        use objc2::runtime::AnyObject;
+                        use objc2::runtime::{AnyClass, AnyObject};
                         let window_ptr = ns_window_addr as *mut AnyObject;
                         let view = ns_view_addr as *mut AnyObject;
 
                         let mouse_loc: objc2_foundation::NSPoint =
                             msg_send![window_ptr, mouseLocationOutsideOfEventStream];
                         let bounds: objc2_foundation::NSRect = msg_send![view, bounds];
+                        let scale: f64 = msg_send![window_ptr, backingScaleFactor];
+                        let modifier_flags: usize = match AnyClass::get(c"NSEvent") {
+                            Some(cls) => msg_send![cls, modifierFlags],
+                            None => 0,
+                        };
 
                         let inside = mouse_loc.x >= 0.0
                             && mouse_loc.y >= 0.0
                             && mouse_loc.x <= bounds.size.width
                             && mouse_loc.y <= bounds.size.height;
 
-                        (inside, mouse_loc.x, mouse_loc.y)
+                        let is_key: bool = msg_send![window_ptr, isKeyWindow];
+
+                        (inside, mouse_loc.x, mouse_loc.y, bounds.size.height, scale, modifier_flags, is_key)
                     };
+                    backing_scale = scale;
+
+                    // While the window has focus F2 asks for a snapshot and F3
+                    // toggles the stats HUD; key events go to AppKit, so the
+                    // key state is polled
+                    let f2_down = is_key && unsafe { CGEventSourceKeyState(0, KVK_F2) };
+                    if f2_down && !snapshot_key_down {
+                        let _ = event_tx.send(WindowEvent::SnapshotRequested);
+                    }
+                    snapshot_key_down = f2_down;
+
+                    // F4 toggles picture-in-picture; the borderless mini
+                    // viewer never has focus, so there it only needs the cursor
+                    let f4_down = (is_key || (pip && mouse_in_window)) && unsafe { CGEventSourceKeyState(0, KVK_F4) };
+                    if f4_down && !pip_key_down {
+                        pip_request = Some(!pip);
+                    }
+                    pip_key_down = f4_down;
+                    if let Some(enabled) = pip_request.take().filter(|&enabled| enabled != pip) {
+                        pip = enabled;
+                        log::info!("Picture-in-picture {}", if enabled { "on" } else { "off" });
+                        if let Some(handle) = crate::APP_HANDLE.get() {
+                            let restore = pip_restore.clone();
+                            let frame_size = renderer.frame_size();
+                            let win_addr = ns_window_addr;
+                            let _ = handle.run_on_main_thread(move || {
+                                set_ns_window_pip(win_addr, frame_size, &restore, enabled)
+                            });
+                        }
+                    }
+
+                    // F7 tiles every viewer window, this one included
+                    let f7_down = is_key && unsafe { CGEventSourceKeyState(0, KVK_F7) };
+                    if f7_down && !tile_key_down {
+                        super::viewers::tile();
+                    }
+                    tile_key_down = f7_down;
+                    if let Some((index, count)) = tile_request.take() {
+                        let leave_pip = std::mem::take(&mut pip);
+                        if let Some(handle) = crate::APP_HANDLE.get() {
+                            let restore = pip_restore.clone();
+                            let frame_size = renderer.frame_size();
+                            let win_addr = ns_window_addr;
+                            let _ = handle.run_on_main_thread(move || {
+                                if leave_pip {
+                                    set_ns_window_pip(win_addr, frame_size, &restore, false);
+                                }
+                                set_ns_window_tile(win_addr, index, count);
+                            });
+                        }
+                    }
+
+                    // F5 switches the mouse to drawing and F6 clears the
+                    // drawings; the button is polled like the keys
+                    let f5_down = is_key && unsafe { CGEventSourceKeyState(0, KVK_F5) };
+                    if f5_down && !draw_key_down {
+                        let drawing = annotations.toggle();
+                        log::info!("Annotation mode {}", if drawing { "on" } else { "off" });
+                        pen_down = false;
+                    }
+                    draw_key_down = f5_down;
+                    let f6_down = is_key && unsafe { CGEventSourceKeyState(0, KVK_F6) };
+                    if f6_down && !clear_key_down {
+                        let _ = event_tx.send(WindowEvent::Annotated(Annotation::Clear));
+                        annotations.apply(Annotation::Clear);
+                        annotations_changed = true;
+                    }
+                    clear_key_down = f6_down;
+                    if annotations.active() {
+                        let button = mouse_in_window && crate::capture::cursor::primary_button_down();
+                        let at = frame_point(renderer.video_viewport(), mouse_x * scale, (view_h - mouse_y) * scale);
+                        let changed = match (button, pen_down, at) {
+                            (true, false, Some(at)) => {
+                                annotations.begin(at, modifier_flags & (1 << 17) != 0);
+                                true
+                            }
+                            (true, true, Some(at)) => annotations.extend(at),
+                            (false, true, _) => match annotations.finish() {
+                                Some(shape) => {
+                                    let _ = event_tx.send(WindowEvent::Annotated(shape));
+                                    true
+                                }
+                                None => false,
+                            },
+                            _ => false,
+                        };
+                        pen_down = button && (pen_down || at.is_some());
+                        annotations_changed |= changed;
+                    }
+
+                    let f3_down = is_key && unsafe { CGEventSourceKeyState(0, KVK_F3) };
+                    let toggled = f3_down && !stats_key_down;
+                    stats_key_down = f3_down;
+                    if toggled {
+                        stats_hud.toggle();
+                    }
+                    let now = std::time::Instant::now();
+                    if toggled || stats_hud.due(now) {
+                        let image = stats_hud.image(now, last_surface_w, scale_for(scale), queued);
+                        queued = 0;
+                        renderer.set_overlay(OverlayLayer::Stats, image.as_ref());
+                        if let Err(e) = renderer.render(current_format) {
+                            log::error!("Render failed: {}", e);
+                        }
+                    }
+
+                    // Magnifier lens while Option is held (NSEventModifierFlagOption,
+                    // with NSEventModifierFlagShift for more zoom)
+                    let magnifier = super::magnifier::zoom_for_keys(
+                        modifier_flags & (1 << 19) != 0,
+                        modifier_flags & (1 << 17) != 0,
+                    )
+                    .filter(|_| mouse_in_window)
+                    .map(|zoom| super::Magnifier {
+                        // AppKit's origin is bottom-left; the surface's is top-left
+                        x: (mouse_x * scale) as f32,
+                        y: ((view_h - mouse_y) * scale) as f32,
+                        zoom,
+                    });
+                    if magnifier != last_magnifier {
+                        last_magnifier = magnifier;
+                        renderer.set_magnifier(magnifier);
+                        if let Err(e) = renderer.render(current_format) {
+                            log::error!("Render failed: {}", e);
+                        }
+                    }
 
                     // Detect mouse movement
                     let mouse_moved = (mouse_x - last_mouse_x).abs() > 1.0
@@ -462,7 +811,9 @@ impl RenderWindow {
                     last_mouse_x = mouse_x;
                     last_mouse_y = mouse_y;
 
+                    // No toolbar in the mini viewer
                     let should_show = mouse_in_window
+                        && !pip
                         && last_mouse_move_time.elapsed() < toolbar_hide_delay;
 
                     // Update toolbar panel visibility on state change
@@ -485,7 +836,7 @@ impl RenderWindow {
                                         main_win,
                                         contentRectForFrameRect: main_frame
                                     ];
-                                    let toolbar_w: f64 = 320.0;
+                                    let toolbar_w: f64 = 620.0;
                                     let toolbar_h: f64 = 36.0;
                                     let px = content_rect.origin.x
                                         + (content_rect.size.width - toolbar_w) / 2.0;
@@ -504,7 +855,7 @@ impl RenderWindow {
                         }
                     }
 
-                    // Poll both NSPopUpButtons (~every 100ms)
+                    // Poll the NSPopUpButtons (~every 100ms)
                     if check_counter % 100 == 0 {
                         let res_selected: isize = unsafe {
                             use objc2::msg_send;
@@ -535,10 +886,43 @@ impl RenderWindow {
                                 log::info!("Toolbar: {} + {}",
                                     res.label, br.label);
                                 let _ = event_tx.send(WindowEvent::ResolutionRequested(
-                                    res.target_width, res.target_height, br.bitrate,
+                                    res.target_width, res.target_height, br.bitrate, br.near_lossless,
                                 ));
                             }
                         }
+
+                        // The color filter is applied locally; the sharer never hears of it
+                        let filter_selected: isize = unsafe {
+                            use objc2::msg_send;
+                            use objc2::runtime::AnyObject;
+                            let popup = filter_popup_addr as *mut AnyObject;
+                            msg_send![popup, indexOfSelectedItem]
+                        };
+                        if filter_selected != last_selected_filter && filter_selected >= 0 {
+                            last_selected_filter = filter_selected;
+                            if let Some(&filter) = super::ColorFilter::ALL.get(filter_selected as usize) {
+                                renderer.set_color_filter(filter);
+                            }
+                        }
+
+                        // So is the fit mode; redraw right away so a static screen follows it
+                        let fit_selected: isize = unsafe {
+                            use objc2::msg_send;
+                            use objc2::runtime::AnyObject;
+                            let popup = fit_popup_addr as *mut AnyObject;
+                            msg_send![popup, indexOfSelectedItem]
+                        };
+                        if fit_selected != last_selected_fit && fit_selected >= 0 {
+                            last_selected_fit = fit_selected;
+                            if let Some(&mode) = super::FitMode::ALL.get(fit_selected as usize) {
+                                renderer.set_fit_mode(mode);
+                                pointer_moved = true;
+                                annotations_changed = true;
+                                if let Err(e) = renderer.render(current_format) {
+                                    log::error!("Render failed: {}", e);
+                                }
+                            }
+                        }
                     }
                 }
 
@@ -596,6 +980,114 @@ unsafe impl Send for SendPtr {}
 /// Create an NSWindow + NSView on the main thread using objc2.
 /// Returns (NSView pointer, NSWindow pointer).
 /// The NSWindow is retained (caller must release when done).
+/// NSWindowStyleMask of viewer windows: Titled(1) | Closable(2) |
+/// Miniaturizable(4) | Resizable(8)
+#[cfg(target_os = "macos")]
+const WINDOW_STYLE_MASK: usize = 1 | 2 | 4 | 8;
+
+/// Virtual key codes of F2 to F7
+#[cfg(target_os = "macos")]
+const KVK_F2: u16 = 0x78;
+#[cfg(target_os = "macos")]
+const KVK_F3: u16 = 0x63;
+#[cfg(target_os = "macos")]
+const KVK_F4: u16 = 0x76;
+#[cfg(target_os = "macos")]
+const KVK_F5: u16 = 0x60;
+#[cfg(target_os = "macos")]
+const KVK_F6: u16 = 0x61;
+#[cfg(target_os = "macos")]
+const KVK_F7: u16 = 0x62;
+
+/// Enter picture-in-picture (borderless, floating, in the bottom-right
+/// corner of the screen, dragged by its content) or restore the frame
+/// saved in `restore`. Runs on the main thread.
+#[cfg(target_os = "macos")]
+fn set_ns_window_pip(
+    window_addr: usize,
+    frame_size: (u32, u32),
+    restore: &parking_lot::Mutex<Option<objc2_foundation::NSRect>>,
+    enabled: bool,
+) {
+    use objc2::msg_send;
+    use objc2::runtime::AnyObject;
+    use objc2_foundation::{NSPoint, NSRect, NSSize};
+
+    unsafe {
+        let window = window_addr as *mut AnyObject;
+        if enabled {
+            let screen: *mut AnyObject = msg_send![window, screen];
+            if screen.is_null() {
+                return;
+            }
+            let frame: NSRect = msg_send![window, frame];
+            *restore.lock() = Some(frame);
+
+            // In points; AppKit's y grows upwards, so the top-left corner
+            // from `pip_position` is turned around
+            let visible: NSRect = msg_send![screen, visibleFrame];
+            let screen_size = (visible.size.width as u32, visible.size.height as u32);
+            let (w, h) = super::pip::pip_size(frame_size, screen_size);
+            let (x, top) = super::pip::pip_position((visible.origin.x as i32, 0), screen_size, (w, h));
+            let y = visible.origin.y + visible.size.height - top as f64 - h as f64;
+
+            // NSWindowStyleMaskBorderless, NSFloatingWindowLevel
+            let _: () = msg_send![window, setStyleMask: 0usize];
+            let _: () = msg_send![window, setLevel: 3isize];
+            let _: () = msg_send![window, setMovableByWindowBackground: true];
+            let pip_frame = NSRect::new(NSPoint::new(x as f64, y), NSSize::new(w as f64, h as f64));
+            let _: () = msg_send![window, setFrame: pip_frame, display: true];
+        } else if let Some(frame) = restore.lock().take() {
+            let _: () = msg_send![window, setStyleMask: WINDOW_STYLE_MASK];
+            let _: () = msg_send![window, setLevel: 0isize];
+            let _: () = msg_send![window, setMovableByWindowBackground: false];
+            let _: () = msg_send![window, setFrame: frame, display: true];
+        }
+    }
+}
+
+/// Move the window into tile `index` of a grid of `count` on the primary
+/// screen. Runs on the main thread.
+#[cfg(target_os = "macos")]
+fn set_ns_window_tile(window_addr: usize, index: usize, count: usize) {
+    use objc2::msg_send;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2_foundation::{NSPoint, NSRect, NSSize};
+
+    unsafe {
+        let window = window_addr as *mut AnyObject;
+        let Some(cls) = AnyClass::get(c"NSScreen") else {
+            return;
+        };
+        // The first screen is the one with the menu bar
+        let screens: *mut AnyObject = msg_send![cls, screens];
+        if screens.is_null() {
+            return;
+        }
+        let count_screens: usize = msg_send![screens, count];
+        if count_screens == 0 {
+            return;
+        }
+        let screen: *mut AnyObject = msg_send![screens, objectAtIndex: 0usize];
+
+        // In points; AppKit's y grows upwards, so the top-left corner from
+        // `tile_rect` is turned around
+        let visible: NSRect = msg_send![screen, visibleFrame];
+        let screen_size = (visible.size.width as u32, visible.size.height as u32);
+        let (x, top, w, h) = super::tiling::tile_rect(index, count, (visible.origin.x as i32, 0), screen_size);
+        let y = visible.origin.y + visible.size.height - top as f64 - h as f64;
+        let frame = NSRect::new(NSPoint::new(x as f64, y), NSSize::new(w as f64, h as f64));
+        let _: () = msg_send![window, setFrame: frame, display: true];
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "ApplicationServices", kind = "framework")]
+unsafe extern "C" {
+    /// Whether a key is down, by CGEventSourceStateID (0: combined session)
+    fn CGEventSourceKeyState(state_id: i32, key: u16) -> bool;
+}
+
 #[cfg(target_os = "macos")]
 fn create_ns_window(
     title: &str,
@@ -616,8 +1108,7 @@ fn create_ns_window(
     );
 
     unsafe {
-        // NSWindowStyleMask: Titled(1) | Closable(2) | Miniaturizable(4) | Resizable(8)
-        let style_mask: usize = 1 | 2 | 4 | 8;
+        let style_mask = WINDOW_STYLE_MASK;
 
         let frame = NSRect::new(
             NSPoint::new(100.0, 100.0),
@@ -679,10 +1170,18 @@ fn create_ns_window(
 /// Create a floating toolbar as a child NSPanel window.
 /// Using a child window ensures reliable rendering over Metal/wgpu content,
 /// since subviews of the Metal content view may be hidden by the CAMetalLayer.
-/// Returns (panel_addr, resolution_popup_addr, bitrate_popup_addr) as usize.
+/// Returns (panel_addr, resolution_popup_addr, bitrate_popup_addr, filter_popup_addr,
+/// fit_popup_addr) as usize.
 /// Must be called on the main thread.
 #[cfg(target_os = "macos")]
-fn create_toolbar_panel(window_addr: usize, _window_width: u32, default_res_idx: usize, default_br_idx: usize) -> Result<(usize, usize, usize), String> {
+fn create_toolbar_panel(
+    window_addr: usize,
+    _window_width: u32,
+    default_res_idx: usize,
+    default_br_idx: usize,
+    default_filter_idx: usize,
+    default_fit_idx: usize,
+) -> Result<(usize, usize, usize, usize, usize), String> {
     use objc2::msg_send;
     use objc2::runtime::{AnyClass, AnyObject};
     use objc2_foundation::{NSPoint, NSRect, NSSize, NSString};
@@ -694,7 +1193,7 @@ fn create_toolbar_panel(window_addr: usize, _window_width: u32, default_res_idx:
         let main_frame: NSRect = msg_send![main_window, frame];
         let content_rect: NSRect = msg_send![main_window, contentRectForFrameRect: main_frame];
 
-        let toolbar_w: f64 = 320.0;
+        let toolbar_w: f64 = 620.0;
         let toolbar_h: f64 = 36.0;
         let panel_x = content_rect.origin.x + (content_rect.size.width - toolbar_w) / 2.0;
         let panel_y = content_rect.origin.y + content_rect.size.height - toolbar_h - 8.0;
@@ -778,7 +1277,7 @@ fn create_toolbar_panel(window_addr: usize, _window_width: u32, default_res_idx:
         let res_idx = (default_res_idx as isize).min(crate::simple_streaming::RESOLUTION_OPTIONS.len() as isize - 1);
         let _: () = msg_send![res_popup, selectItemAtIndex: res_idx];
 
-        // --- Bitrate dropdown (right side) ---
+        // --- Bitrate dropdown (middle) ---
         let br_frame = NSRect::new(
             NSPoint::new(10.0 + popup_w + 10.0, 4.0),
             NSSize::new(popup_w, 28.0),
@@ -801,16 +1300,62 @@ fn create_toolbar_panel(window_addr: usize, _window_width: u32, default_res_idx:
         let br_idx = (default_br_idx as isize).min(crate::simple_streaming::BITRATE_OPTIONS.len() as isize - 1);
         let _: () = msg_send![br_popup, selectItemAtIndex: br_idx];
 
-        // Add both popups to panel's content view
+        // --- Color filter dropdown ---
+        let filter_frame = NSRect::new(
+            NSPoint::new(10.0 + (popup_w + 10.0) * 2.0, 4.0),
+            NSSize::new(popup_w, 28.0),
+        );
+        let filter_alloc: *mut AnyObject = msg_send![popup_cls, alloc];
+        let filter_popup: *mut AnyObject = msg_send![
+            filter_alloc,
+            initWithFrame: filter_frame,
+            pullsDown: false
+        ];
+        if filter_popup.is_null() {
+            return Err("Color filter NSPopUpButton alloc failed".to_string());
+        }
+        let _: () = msg_send![filter_popup, setFont: font];
+
+        for filter in super::ColorFilter::ALL {
+            let ns_title = NSString::from_str(filter.label());
+            let _: () = msg_send![filter_popup, addItemWithTitle: &*ns_title];
+        }
+        let _: () = msg_send![filter_popup, selectItemAtIndex: default_filter_idx as isize];
+
+        // --- Fit mode dropdown (right side) ---
+        let fit_frame = NSRect::new(
+            NSPoint::new(10.0 + (popup_w + 10.0) * 3.0, 4.0),
+            NSSize::new(popup_w, 28.0),
+        );
+        let fit_alloc: *mut AnyObject = msg_send![popup_cls, alloc];
+        let fit_popup: *mut AnyObject = msg_send![
+            fit_alloc,
+            initWithFrame: fit_frame,
+            pullsDown: false
+        ];
+        if fit_popup.is_null() {
+            return Err("Fit mode NSPopUpButton alloc failed".to_string());
+        }
+        let _: () = msg_send![fit_popup, setFont: font];
+
+        for mode in super::FitMode::ALL {
+            let ns_title = NSString::from_str(mode.label());
+            let _: () = msg_send![fit_popup, addItemWithTitle: &*ns_title];
+        }
+        let _: () = msg_send![fit_popup, selectItemAtIndex: default_fit_idx as isize];
+
+        // Add the popups to panel's content view
         let _: () = msg_send![panel_content, addSubview: res_popup];
         let _: () = msg_send![panel_content, addSubview: br_popup];
+        let _: () = msg_send![panel_content, addSubview: filter_popup];
+        let _: () = msg_send![panel_content, addSubview: fit_popup];
 
         // Initially hidden (orderOut removes from screen)
         let _: () = msg_send![panel, orderOut: std::ptr::null::<AnyObject>()];
 
-        log::debug!("Floating toolbar panel created with resolution + bitrate dropdowns");
+        log::debug!("Floating toolbar panel created with resolution, bitrate, filter and fit dropdowns");
 
-        Ok((panel as usize, res_popup as usize, br_popup as usize))
+        Ok((panel as usize, res_popup as usize, br_popup as usize, filter_popup as usize, fit_popup as usize))
     }
 }
 
@@ -819,6 +1364,7 @@ fn create_toolbar_panel(window_addr: usize, _window_width: u32, default_res_idx:
 #[cfg(not(target_os = "macos"))]
 impl RenderWindow {
     fn process_commands(&mut self) {
+        self.queued = self.queued.max(self.command_rx.len());
         while let Ok(cmd) = self.command_rx.try_recv() {
             match cmd {
                 WindowCommand::RenderFrame(frame) => {
@@ -837,12 +1383,231 @@ impl RenderWindow {
                         window.set_title(&title);
                     }
                 }
+                WindowCommand::Snapshot(reply) => {
+                    let snapshot = match self.renderer {
+                        Some(ref mut renderer) => renderer.snapshot(self.current_format).map_err(|e| e.to_string()),
+                        None => Err("No renderer".to_string()),
+                    };
+                    let _ = reply.send(snapshot);
+                }
+                WindowCommand::SetPip(enabled) => self.set_pip(enabled),
+                WindowCommand::Tile(index, count) => self.tile(index, count),
+                WindowCommand::Pointer(pointer) => {
+                    self.pointer.set(pointer, std::time::Instant::now());
+                    self.update_pointer();
+                }
+                WindowCommand::Annotate(annotation, ours) => {
+                    if ours {
+                        let _ = self.event_tx.send(WindowEvent::Annotated(annotation.clone()));
+                    }
+                    self.annotations.apply(annotation);
+                    self.update_annotations();
+                }
+                WindowCommand::SetRecording(recording) => {
+                    self.recording = recording;
+                    self.update_recording_badge();
+                }
                 WindowCommand::Close => {
                     self.is_open.store(false, Ordering::Relaxed);
                 }
             }
         }
     }
+
+    /// Enter picture-in-picture (small, borderless, on top, in the screen's
+    /// bottom-right corner) or go back to the window as it was
+    fn set_pip(&mut self, enabled: bool) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        if enabled == self.pip.is_some() {
+            return;
+        }
+        if enabled {
+            self.pip = Some((window.inner_size(), window.outer_position().ok()));
+            window.set_decorations(false);
+            window.set_window_level(WindowLevel::AlwaysOnTop);
+            if let Some(monitor) = window.current_monitor() {
+                let screen = (monitor.size().width, monitor.size().height);
+                let frame = self.renderer.as_ref().map_or((0, 0), |r| r.frame_size());
+                let size = super::pip::pip_size(frame, screen);
+                let origin = (monitor.position().x, monitor.position().y);
+                let (x, y) = super::pip::pip_position(origin, screen, size);
+                let _ = window.request_inner_size(PhysicalSize::new(size.0, size.1));
+                window.set_outer_position(PhysicalPosition::new(x, y));
+            }
+            // No toolbar in the mini viewer
+            self.toolbar.hover(None, self.width);
+            self.update_toolbar();
+        } else if let Some((size, position)) = self.pip.take() {
+            window.set_decorations(true);
+            window.set_window_level(WindowLevel::Normal);
+            let _ = window.request_inner_size(size);
+            if let Some(position) = position {
+                window.set_outer_position(position);
+            }
+        }
+        log::info!("Picture-in-picture {}", if enabled { "on" } else { "off" });
+    }
+
+    /// Move into tile `index` of a grid of `count` on the primary screen;
+    /// the tile is the outer size, title bar included
+    fn tile(&mut self, index: usize, count: usize) {
+        self.set_pip(false);
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        let Some(monitor) = window.primary_monitor().or_else(|| window.current_monitor()) else {
+            return;
+        };
+        let screen = (monitor.size().width, monitor.size().height);
+        let origin = (monitor.position().x, monitor.position().y);
+        let (x, y, width, height) = super::tiling::tile_rect(index, count, origin, screen);
+        let (outer, inner) = (window.outer_size(), window.inner_size());
+        let frame = (outer.width.saturating_sub(inner.width), outer.height.saturating_sub(inner.height));
+        let _ = window.request_inner_size(PhysicalSize::new(
+            width.saturating_sub(frame.0).max(1),
+            height.saturating_sub(frame.1).max(1),
+        ));
+        window.set_outer_position(PhysicalPosition::new(x, y));
+    }
+
+    /// Show, move or hide the magnifier lens after the cursor or the
+    /// modifier keys changed
+    fn update_magnifier(&mut self) {
+        let zoom = super::magnifier::zoom_for_keys(self.modifiers.alt_key(), self.modifiers.shift_key());
+        let magnifier = zoom.zip(self.cursor).map(|(zoom, (x, y))| super::Magnifier {
+            x: x as f32,
+            y: y as f32,
+            zoom,
+        });
+        if magnifier == self.magnifier {
+            return;
+        }
+        self.magnifier = magnifier;
+        if let Some(ref mut renderer) = self.renderer {
+            renderer.set_magnifier(magnifier);
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Redraw the toolbar after it was shown, hidden, hovered or clicked
+    fn update_toolbar(&mut self) {
+        if let Some(ref mut renderer) = self.renderer {
+            renderer.set_overlay(OverlayLayer::Toolbar, self.toolbar.image(self.width).as_ref());
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Redraw the stats HUD with fresh numbers, or take it off after it
+    /// was hidden
+    fn update_stats_hud(&mut self) {
+        let scale = scale_for(self.window.as_ref().map_or(1.0, |w| w.scale_factor()));
+        let image = self.stats_hud.image(std::time::Instant::now(), self.width, scale, self.queued);
+        self.queued = 0;
+        if let Some(ref mut renderer) = self.renderer {
+            renderer.set_overlay(OverlayLayer::Stats, image.as_ref());
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Show or take off the REC badge, or redraw it at a new scale
+    fn update_recording_badge(&mut self) {
+        let scale = scale_for(self.window.as_ref().map_or(1.0, |w| w.scale_factor()));
+        let image = self.recording.then(|| recording_badge(scale));
+        if let Some(ref mut renderer) = self.renderer {
+            renderer.set_overlay(OverlayLayer::Recording, image.as_ref());
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// Redraw the sharer's pointer after it moved, the video moved under it
+    /// or for the next step of its click ripple
+    fn update_pointer(&mut self) {
+        let scale = self.window.as_ref().map_or(1.0, |w| w.scale_factor()).round().max(1.0) as usize;
+        if let Some(ref mut renderer) = self.renderer {
+            let video = renderer.video_viewport();
+            let image = self.pointer.image(std::time::Instant::now(), video, (self.width, self.height), scale);
+            renderer.set_overlay(OverlayLayer::Pointer, image.as_ref());
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// The left button went down or up while drawing: start a stroke (an
+    /// arrow with Shift) or finish it and send it
+    fn draw_with_mouse(&mut self, pressed: bool) {
+        if pressed {
+            let video = self.renderer.as_ref().and_then(|r| r.video_viewport());
+            if let Some(at) = self.cursor.and_then(|(x, y)| frame_point(video, x, y)) {
+                self.annotations.begin(at, self.modifiers.shift_key());
+            }
+        } else if let Some(shape) = self.annotations.finish() {
+            let _ = self.event_tx.send(WindowEvent::Annotated(shape));
+        }
+        self.update_annotations();
+    }
+
+    /// Redraw the annotations after a shape was added or changed, or the
+    /// video moved under them
+    fn update_annotations(&mut self) {
+        let scale = self.window.as_ref().map_or(1.0, |w| w.scale_factor()).round().max(1.0) as usize;
+        if let Some(ref mut renderer) = self.renderer {
+            let image = self.annotations.image(renderer.video_viewport(), (self.width, self.height), scale);
+            renderer.set_overlay(OverlayLayer::Annotations, image.as_ref());
+        }
+        if let Some(ref window) = self.window {
+            window.request_redraw();
+        }
+    }
+
+    /// A toolbar button changed `setting`: apply it
+    fn apply_toolbar(&mut self, setting: super::toolbar::Setting) {
+        use super::toolbar::Setting;
+
+        match setting {
+            Setting::Resolution | Setting::Bitrate => {
+                let res_opts = &crate::simple_streaming::RESOLUTION_OPTIONS;
+                let br_opts = &crate::simple_streaming::BITRATE_OPTIONS;
+                if let (Some(res), Some(br)) = (
+                    res_opts.get(self.toolbar.selected(Setting::Resolution)),
+                    br_opts.get(self.toolbar.selected(Setting::Bitrate)),
+                ) {
+                    log::info!("Toolbar: {} + {}", res.label, br.label);
+                    let _ = self.event_tx.send(WindowEvent::ResolutionRequested(
+                        res.target_width, res.target_height, br.bitrate, br.near_lossless,
+                    ));
+                }
+            }
+            // The color filter and fit mode are applied locally; the sharer never hears of them
+            Setting::Filter => {
+                if let (Some(&filter), Some(renderer)) =
+                    (super::ColorFilter::ALL.get(self.toolbar.selected(Setting::Filter)), self.renderer.as_mut())
+                {
+                    renderer.set_color_filter(filter);
+                }
+            }
+            Setting::Fit => {
+                if let (Some(&mode), Some(renderer)) =
+                    (super::FitMode::ALL.get(self.toolbar.selected(Setting::Fit)), self.renderer.as_mut())
+                {
+                    renderer.set_fit_mode(mode);
+                }
+                self.update_pointer();
+                self.update_annotations();
+            }
+        }
+        self.update_toolbar();
+    }
 }
 
 #[cfg(not(target_os = "macos"))]
@@ -882,7 +1647,9 @@ impl ApplicationHandler for RenderWindow {
         });
 
         match renderer {
-            Ok(r) => {
+            Ok(mut r) => {
+                r.set_color_filter(crate::commands::viewer_color_filter());
+                r.set_fit_mode(crate::commands::viewer_fit_mode());
                 self.renderer = Some(r);
                 log::info!("Render window created: {}x{}", self.width, self.height);
             }
@@ -894,6 +1661,9 @@ impl ApplicationHandler for RenderWindow {
             }
         }
 
+        // Composed text is forwarded when controlling the sharer
+        window.set_ime_allowed(true);
+        self.toolbar.set_scale_factor(window.scale_factor());
         self.window = Some(window);
     }
 
@@ -915,22 +1685,135 @@ impl ApplicationHandler for RenderWindow {
                 if let Some(ref mut renderer) = self.renderer {
                     renderer.resize(size.width, size.height);
                 }
+                // The toolbar stays centered, the stats HUD in its corner
+                // and the pointer on the video
+                self.update_toolbar();
+                self.update_stats_hud();
+                self.update_pointer();
+                self.update_annotations();
                 let _ = self.event_tx.send(WindowEvent::Resized(size.width, size.height));
             }
+            WinitWindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.toolbar.set_scale_factor(scale_factor);
+                self.update_toolbar();
+                self.update_pointer();
+                self.update_annotations();
+                self.update_recording_badge();
+            }
             WinitWindowEvent::Focused(focused) => {
                 let _ = self.event_tx.send(WindowEvent::Focused(focused));
             }
             WinitWindowEvent::KeyboardInput { event, .. } => {
-                if event.state.is_pressed() {
+                use winit::keyboard::{KeyCode, PhysicalKey};
+
+                // F2 (snapshot), F3 (stats HUD), F4 (picture-in-picture),
+                // F5 (draw), F6 (clear the drawings) and F7 (tile the viewer
+                // windows) are the window's own keys
+                if event.physical_key == PhysicalKey::Code(KeyCode::F7) {
+                    if event.state.is_pressed() && !event.repeat {
+                        super::viewers::tile();
+                    }
+                } else if event.physical_key == PhysicalKey::Code(KeyCode::F5) {
+                    if event.state.is_pressed() && !event.repeat {
+                        let drawing = self.annotations.toggle();
+                        log::info!("Annotation mode {}", if drawing { "on" } else { "off" });
+                        self.update_annotations();
+                    }
+                } else if event.physical_key == PhysicalKey::Code(KeyCode::F6) {
+                    if event.state.is_pressed() && !event.repeat {
+                        let _ = self.event_tx.send(WindowEvent::Annotated(Annotation::Clear));
+                        self.annotations.apply(Annotation::Clear);
+                        self.update_annotations();
+                    }
+                } else if event.physical_key == PhysicalKey::Code(KeyCode::F4) {
+                    if event.state.is_pressed() && !event.repeat {
+                        self.set_pip(self.pip.is_none());
+                    }
+                } else if event.physical_key == PhysicalKey::Code(KeyCode::F2) {
+                    if event.state.is_pressed() && !event.repeat {
+                        let _ = self.event_tx.send(WindowEvent::SnapshotRequested);
+                    }
+                } else if event.physical_key == PhysicalKey::Code(KeyCode::F3) {
+                    if event.state.is_pressed() && !event.repeat {
+                        self.stats_hud.toggle();
+                        self.update_stats_hud();
+                    }
+                } else if event.state.is_pressed() {
                     let _ = self.event_tx.send(WindowEvent::KeyPressed(
                         event.physical_key.to_scancode().unwrap_or(0),
                     ));
+                    if let Some(text) = event.text.filter(|_| !self.ime_composing) {
+                        let _ = self.event_tx.send(WindowEvent::TextInput(text.to_string()));
+                    }
                 }
             }
+            WinitWindowEvent::Ime(ime) => match ime {
+                winit::event::Ime::Preedit(text, _) => self.ime_composing = !text.is_empty(),
+                winit::event::Ime::Commit(text) => {
+                    self.ime_composing = false;
+                    let _ = self.event_tx.send(WindowEvent::TextInput(text));
+                }
+                winit::event::Ime::Enabled | winit::event::Ime::Disabled => self.ime_composing = false,
+            },
             WinitWindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some((position.x, position.y));
+                self.update_magnifier();
+                if self.pip.is_none() && self.toolbar.hover(self.cursor, self.width) {
+                    self.update_toolbar();
+                }
+                // While drawing the mouse is the pen, not the sharer's mouse
+                if self.annotations.active() {
+                    let video = self.renderer.as_ref().and_then(|r| r.video_viewport());
+                    if let Some(at) = frame_point(video, position.x, position.y) {
+                        if self.annotations.extend(at) {
+                            self.update_annotations();
+                        }
+                    }
+                    return;
+                }
                 let _ = self.event_tx.send(WindowEvent::MouseMoved(position.x, position.y));
             }
+            WinitWindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.update_magnifier();
+                if self.toolbar.hover(None, self.width) {
+                    self.update_toolbar();
+                }
+            }
+            WinitWindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                self.update_magnifier();
+            }
             WinitWindowEvent::MouseInput { state, button, .. } => {
+                // The mini viewer is dragged around, not clicked through
+                if self.pip.is_some() {
+                    if button == winit::event::MouseButton::Left && state.is_pressed() {
+                        if let Some(ref window) = self.window {
+                            let _ = window.drag_window();
+                        }
+                    }
+                    return;
+                }
+                // Clicks on the toolbar are the toolbar's
+                if let Some((x, y)) = self.cursor.filter(|&(x, y)| self.toolbar.contains(x, y, self.width)) {
+                    let forward = match button {
+                        winit::event::MouseButton::Left => Some(true),
+                        winit::event::MouseButton::Right => Some(false),
+                        _ => None,
+                    };
+                    if let Some(forward) = forward.filter(|_| state.is_pressed()) {
+                        if let Some(setting) = self.toolbar.click(x, y, self.width, forward) {
+                            self.apply_toolbar(setting);
+                        }
+                    }
+                    return;
+                }
+                if self.annotations.active() {
+                    if button == winit::event::MouseButton::Left {
+                        self.draw_with_mouse(state.is_pressed());
+                    }
+                    return;
+                }
                 let button_id = match button {
                     winit::event::MouseButton::Left => 0,
                     winit::event::MouseButton::Right => 1,
@@ -974,5 +1857,11 @@ impl ApplicationHandler for RenderWindow {
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
         // Process commands even when idle
         self.process_commands();
+        if self.stats_hud.due(std::time::Instant::now()) {
+            self.update_stats_hud();
+        }
+        if self.pointer.animating() {
+            self.update_pointer();
+        }
     }
 }
@@ -0,0 +1,87 @@
+//! Safe mode
+//!
+//! When GPU drivers misbehave (black frames, crashes inside a hardware
+//! encoder, a compositor that stutters in Mailbox mode), safe mode keeps to
+//! the plain software paths: CPU capture backends, OpenH264 encode/decode and
+//! Fifo presentation. It is turned on by the `--safe-mode` command-line flag
+//! or the settings toggle. Toggling the setting affects capture sessions,
+//! codecs and windows created afterwards.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Command-line flag that forces safe mode for this run
+pub const FLAG: &str = "--safe-mode";
+
+/// Set when the app was started with `--safe-mode`
+static FROM_FLAG: AtomicBool = AtomicBool::new(false);
+
+/// Part of the pipeline with a hardware-accelerated path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Capture,
+    Encoder,
+    Decoder,
+    Presentation,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Capture,
+        Subsystem::Encoder,
+        Subsystem::Decoder,
+        Subsystem::Presentation,
+    ];
+}
+
+/// Whether the most recently created instance of each subsystem was forced
+/// onto its safe path
+static FORCED: [AtomicBool; 4] = [const { AtomicBool::new(false) }; 4];
+
+/// Safe mode state reported to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct SafeModeStatus {
+    pub enabled: bool,
+    /// Started with `--safe-mode` (cannot be turned off until restart)
+    pub from_flag: bool,
+    /// Enabled in settings
+    pub from_settings: bool,
+    /// Subsystems currently running on their safe path
+    pub subsystems: Vec<Subsystem>,
+}
+
+/// Enable safe mode if `args` contain `--safe-mode`
+pub fn init_from_args(args: impl IntoIterator<Item = String>) {
+    if args.into_iter().any(|arg| arg == FLAG) {
+        FROM_FLAG.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Whether safe mode was forced from the command line
+pub fn from_flag() -> bool {
+    FROM_FLAG.load(Ordering::SeqCst)
+}
+
+/// Whether new subsystems should use their safe path
+pub fn is_enabled() -> bool {
+    from_flag() || crate::commands::safe_mode_setting_enabled()
+}
+
+/// Record whether a newly created subsystem took its safe path
+pub fn record(subsystem: Subsystem, forced: bool) {
+    FORCED[subsystem as usize].store(forced, Ordering::SeqCst);
+}
+
+/// Current safe mode state
+pub fn status() -> SafeModeStatus {
+    SafeModeStatus {
+        enabled: is_enabled(),
+        from_flag: from_flag(),
+        from_settings: crate::commands::safe_mode_setting_enabled(),
+        subsystems: Subsystem::ALL
+            .into_iter()
+            .filter(|&s| FORCED[s as usize].load(Ordering::SeqCst))
+            .collect(),
+    }
+}
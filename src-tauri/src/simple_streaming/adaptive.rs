@@ -0,0 +1,258 @@
+//! Per-viewer adaptation for the simple stream fanout
+//!
+//! Every viewer has its own bounded send queue, so a slow link only delays
+//! that viewer. When the queue overflows, its backlog is dropped and the
+//! viewer resyncs at the next keyframe (delta frames after a gap would not
+//! decode anyway). Each viewer also tracks how busy its link is; viewers that
+//! keep falling behind move to a low-bitrate encoder ladder and move back
+//! once their link has had headroom for a while.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames queued per viewer before the backlog is dropped
+pub const QUEUE_FRAMES: usize = 8;
+
+/// Link load is measured over windows of this length
+const WINDOW: Duration = Duration::from_secs(2);
+
+/// Share of the window spent sending above which the link is congested
+const CONGESTED_LOAD: f64 = 0.8;
+
+/// Share of the window spent sending below which the link has headroom
+const HEADROOM_LOAD: f64 = 0.3;
+
+/// Windows with headroom needed before going back to the high ladder
+const RECOVER_WINDOWS: u32 = 5;
+
+/// Encoder a viewer receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ladder {
+    /// The resolution and bitrate the viewers asked for
+    High,
+    /// Reduced resolution and bitrate for congested links
+    Low,
+}
+
+/// How a queued item relates to decoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// Decoder reinit (new dimensions); a keyframe must follow
+    Reset,
+    Key,
+    Delta,
+}
+
+/// Bounded per-viewer queue that drops its backlog instead of blocking
+pub struct SendQueue<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+    waiting_for_keyframe: bool,
+    dropped: u64,
+}
+
+impl<T> SendQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            waiting_for_keyframe: true,
+            dropped: 0,
+        }
+    }
+
+    /// Queue an item. Returns true if the backlog was dropped and the viewer
+    /// needs a keyframe.
+    pub fn push(&mut self, item: T, kind: FrameKind) -> bool {
+        match kind {
+            FrameKind::Reset => {
+                self.dropped += self.items.len() as u64;
+                self.items.clear();
+                self.items.push_back(item);
+                self.waiting_for_keyframe = true;
+                false
+            }
+            FrameKind::Key => {
+                self.waiting_for_keyframe = false;
+                self.items.push_back(item);
+                self.trim()
+            }
+            FrameKind::Delta if self.waiting_for_keyframe => {
+                self.dropped += 1;
+                false
+            }
+            FrameKind::Delta => {
+                self.items.push_back(item);
+                self.trim()
+            }
+        }
+    }
+
+    fn trim(&mut self) -> bool {
+        if self.items.len() <= self.capacity {
+            return false;
+        }
+        self.dropped += self.items.len() as u64;
+        self.items.clear();
+        self.waiting_for_keyframe = true;
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.items.pop_front()
+    }
+
+    /// Forget everything queued and wait for a keyframe
+    pub fn clear(&mut self) {
+        self.dropped += self.items.len() as u64;
+        self.items.clear();
+        self.waiting_for_keyframe = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Frames this viewer never received
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Tracks one viewer's link and picks its ladder
+#[derive(Debug)]
+pub struct ViewerRate {
+    ladder: Ladder,
+    window_start: Instant,
+    bytes: u64,
+    busy: Duration,
+    overflows: u32,
+    healthy_windows: u32,
+    link_bps: Option<u32>,
+}
+
+impl ViewerRate {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            ladder: Ladder::High,
+            window_start: now,
+            bytes: 0,
+            busy: Duration::ZERO,
+            overflows: 0,
+            healthy_windows: 0,
+            link_bps: None,
+        }
+    }
+
+    /// A frame of `bytes` took `took` to write to the stream
+    pub fn on_sent(&mut self, bytes: usize, took: Duration) {
+        self.bytes += bytes as u64;
+        self.busy += took;
+    }
+
+    /// The send queue overflowed
+    pub fn on_overflow(&mut self) {
+        self.overflows += 1;
+    }
+
+    /// Close the measurement window once it is over. Returns the new ladder
+    /// if the viewer should switch.
+    pub fn update(&mut self, now: Instant) -> Option<Ladder> {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed < WINDOW {
+            return None;
+        }
+
+        let load = self.busy.as_secs_f64() / elapsed.as_secs_f64();
+        if self.busy > Duration::ZERO {
+            self.link_bps = Some((self.bytes as f64 * 8.0 / self.busy.as_secs_f64()).min(u32::MAX as f64) as u32);
+        }
+        let congested = self.overflows > 0 || load > CONGESTED_LOAD;
+
+        let next = match self.ladder {
+            Ladder::High if congested => Some(Ladder::Low),
+            Ladder::High => None,
+            Ladder::Low if !congested && load < HEADROOM_LOAD => {
+                self.healthy_windows += 1;
+                (self.healthy_windows >= RECOVER_WINDOWS).then_some(Ladder::High)
+            }
+            Ladder::Low => {
+                self.healthy_windows = 0;
+                None
+            }
+        };
+
+        self.window_start = now;
+        self.bytes = 0;
+        self.busy = Duration::ZERO;
+        self.overflows = 0;
+        if let Some(ladder) = next {
+            self.ladder = ladder;
+            self.healthy_windows = 0;
+        }
+        next
+    }
+
+    pub fn ladder(&self) -> Ladder {
+        self.ladder
+    }
+
+    /// The encode loop put the viewer on `ladder` (possibly not the one asked for)
+    pub fn set_ladder(&mut self, ladder: Ladder) {
+        self.ladder = ladder;
+        self.healthy_windows = 0;
+    }
+
+    /// Rate the link delivered while sending in the last closed window,
+    /// used as the viewer's bitrate hint. Reported once per window.
+    pub fn take_link_bps(&mut self) -> Option<u32> {
+        self.link_bps.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_drops_backlog_and_resyncs() {
+        let mut queue = SendQueue::new(2);
+        assert!(!queue.push(0, FrameKind::Delta)); // no keyframe yet
+        assert!(queue.is_empty());
+
+        assert!(!queue.push(1, FrameKind::Key));
+        assert!(!queue.push(2, FrameKind::Delta));
+        assert!(queue.push(3, FrameKind::Delta)); // overflow
+        assert!(queue.is_empty());
+        assert_eq!(queue.dropped(), 4);
+
+        assert!(!queue.push(4, FrameKind::Delta));
+        assert!(!queue.push(5, FrameKind::Key));
+        assert_eq!(queue.pop(), Some(5));
+    }
+
+    #[test]
+    fn test_congested_viewer_moves_down_and_back() {
+        let start = Instant::now();
+        let mut rate = ViewerRate::new(start);
+
+        rate.on_sent(500_000, Duration::from_millis(1800));
+        assert_eq!(rate.update(start + Duration::from_secs(1)), None);
+        assert_eq!(rate.update(start + WINDOW), Some(Ladder::Low));
+        assert_eq!(rate.take_link_bps(), Some(2_222_222));
+        assert_eq!(rate.take_link_bps(), None);
+
+        let mut now = start + WINDOW;
+        for window in 1..=RECOVER_WINDOWS {
+            rate.on_sent(50_000, Duration::from_millis(100));
+            now += WINDOW;
+            let expected = (window == RECOVER_WINDOWS).then_some(Ladder::High);
+            assert_eq!(rate.update(now), expected);
+        }
+        assert_eq!(rate.ladder(), Ladder::High);
+    }
+}
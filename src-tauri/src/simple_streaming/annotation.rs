@@ -0,0 +1,125 @@
+//! Annotations on the shared screen
+//!
+//! Viewers send the shapes they draw to the sharer, which passes them on to
+//! every other viewer and to its UI; the sharer's own shapes go to all
+//! viewers. The sharer keeps the shapes since the last clear so viewers who
+//! join later see them too. Points travel as 1/65535ths of the frame.
+
+use super::MSG_TYPE_ANNOTATION;
+use crate::renderer::annotation::{Annotation, MAX_POINTS};
+
+/// Shape kinds on the wire
+const KIND_CLEAR: u8 = 0;
+const KIND_STROKE: u8 = 1;
+const KIND_ARROW: u8 = 2;
+const KIND_TEXT: u8 = 3;
+
+/// Longest text sent, in bytes
+const MAX_TEXT: usize = 200;
+
+/// Most shapes a late viewer is sent
+pub const MAX_HISTORY: usize = 256;
+
+fn push_point(data: &mut Vec<u8>, point: [f32; 2]) {
+    for v in point {
+        data.extend_from_slice(&((v.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16).to_be_bytes());
+    }
+}
+
+/// Message carrying `annotation`
+pub fn encode_annotation_message(annotation: &Annotation) -> Vec<u8> {
+    let mut data = vec![MSG_TYPE_ANNOTATION];
+    match annotation {
+        Annotation::Clear => data.push(KIND_CLEAR),
+        Annotation::Stroke { color, width, points } => {
+            let points = &points[..points.len().min(MAX_POINTS)];
+            data.push(KIND_STROKE);
+            data.extend_from_slice(color);
+            data.push(*width);
+            data.extend_from_slice(&(points.len() as u16).to_be_bytes());
+            for &point in points {
+                push_point(&mut data, point);
+            }
+        }
+        Annotation::Arrow { color, width, from, to } => {
+            data.push(KIND_ARROW);
+            data.extend_from_slice(color);
+            data.push(*width);
+            push_point(&mut data, *from);
+            push_point(&mut data, *to);
+        }
+        Annotation::Text { color, at, text } => {
+            let mut end = text.len().min(MAX_TEXT);
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            data.push(KIND_TEXT);
+            data.extend_from_slice(color);
+            push_point(&mut data, *at);
+            data.extend_from_slice(&text.as_bytes()[..end]);
+        }
+    }
+    data
+}
+
+/// Inverse of `encode_annotation_message`; `None` if the message is malformed
+pub fn parse_annotation_message(data: &[u8]) -> Option<Annotation> {
+    let be_u16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let point = |at: usize| Some([be_u16(at)? as f32 / u16::MAX as f32, be_u16(at + 2)? as f32 / u16::MAX as f32]);
+    let color = |at: usize| data.get(at..at + 4).map(|c| [c[0], c[1], c[2], c[3]]);
+
+    match *data.get(1)? {
+        KIND_CLEAR => Some(Annotation::Clear),
+        KIND_STROKE => {
+            let count = be_u16(7)? as usize;
+            if count == 0 || count > MAX_POINTS {
+                return None;
+            }
+            let points = (0..count).map(|i| point(9 + i * 4)).collect::<Option<Vec<_>>>()?;
+            Some(Annotation::Stroke { color: color(2)?, width: *data.get(6)?, points })
+        }
+        KIND_ARROW => Some(Annotation::Arrow { color: color(2)?, width: *data.get(6)?, from: point(7)?, to: point(11)? }),
+        KIND_TEXT => Some(Annotation::Text {
+            color: color(2)?,
+            at: point(6)?,
+            text: String::from_utf8_lossy(data.get(10..)?).into_owned(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotation_round_trip() {
+        let shapes = [
+            Annotation::Clear,
+            Annotation::Stroke { color: [255, 0, 0, 255], width: 4, points: vec![[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]] },
+            Annotation::Arrow { color: [0, 0, 255, 200], width: 2, from: [1.0, 0.0], to: [0.0, 1.0] },
+            Annotation::Text { color: [255; 4], at: [0.0, 1.0], text: "HELLO".to_string() },
+        ];
+        for shape in shapes {
+            let message = encode_annotation_message(&shape);
+            assert_eq!(message[0], MSG_TYPE_ANNOTATION);
+            assert_eq!(parse_annotation_message(&message), Some(shape));
+        }
+    }
+
+    #[test]
+    fn test_malformed_annotations() {
+        let stroke = Annotation::Stroke { color: [255; 4], width: 4, points: vec![[0.25, 0.75]] };
+        let message = encode_annotation_message(&stroke);
+        assert_eq!(parse_annotation_message(&message[..message.len() - 1]), None);
+        assert_eq!(parse_annotation_message(&[MSG_TYPE_ANNOTATION, 9]), None);
+        assert_eq!(parse_annotation_message(&[MSG_TYPE_ANNOTATION]), None);
+
+        // Text is cut at a character boundary
+        let text = Annotation::Text { color: [255; 4], at: [0.0, 0.0], text: "é".repeat(150) };
+        match parse_annotation_message(&encode_annotation_message(&text)) {
+            Some(Annotation::Text { text, .. }) => assert_eq!(text, "é".repeat(MAX_TEXT / 2)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Viewer decode capability
+//!
+//! A link can be fast enough for 4K60 while the machine at the other end
+//! decodes only 1080p30. Viewers time their decoder and report, every
+//! `REPORT_INTERVAL`, how many frames per second it sustains at the size it
+//! is decoding and how many frames failed. The sharer turns that into a
+//! pixel rate and keeps viewers whose decoder cannot keep up with the high
+//! ladder on the low one, whatever their link measures.
+
+use std::time::{Duration, Instant};
+
+/// How often viewers report their decoder
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The decoder must handle the high ladder with this much to spare
+const DECODE_HEADROOM: f64 = 1.2;
+
+/// Decode errors per thousand frames above which the decoder counts as
+/// struggling; its reported rate is halved then
+const ERROR_LIMIT_PERMILLE: u16 = 50;
+
+/// What a viewer's decoder sustained over the last report interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeReport {
+    /// Frame size being decoded
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second the decoder keeps up with at that size, from its
+    /// average decode time
+    pub fps: u16,
+    /// Failed decodes per thousand frames
+    pub error_permille: u16,
+}
+
+impl DecodeReport {
+    /// Pixels per second the viewer can decode
+    pub fn pixel_rate(&self) -> u64 {
+        let rate = self.width as u64 * self.height as u64 * self.fps as u64;
+        if self.error_permille > ERROR_LIMIT_PERMILLE { rate / 2 } else { rate }
+    }
+
+    /// Whether the viewer can decode `width`×`height` at `fps`
+    pub fn fits(&self, width: u32, height: u32, fps: u32) -> bool {
+        let needed = width as u64 * height as u64 * fps as u64;
+        needed as f64 * DECODE_HEADROOM <= self.pixel_rate() as f64
+    }
+}
+
+/// Times the viewer's decoder for the next report
+#[derive(Debug)]
+pub struct DecodeMeter {
+    width: u32,
+    height: u32,
+    window_start: Instant,
+    calls: u32,
+    errors: u32,
+    busy: Duration,
+}
+
+impl DecodeMeter {
+    pub fn new(now: Instant) -> Self {
+        Self { width: 0, height: 0, window_start: now, calls: 0, errors: 0, busy: Duration::ZERO }
+    }
+
+    /// The stream (re)started at a new size: measurements so far are for
+    /// the old one
+    pub fn reset(&mut self, width: u32, height: u32, now: Instant) {
+        *self = Self { width, height, ..Self::new(now) };
+    }
+
+    /// One frame went through the decoder in `took`
+    pub fn on_decode(&mut self, took: Duration, failed: bool) {
+        self.calls += 1;
+        self.errors += failed as u32;
+        self.busy += took;
+    }
+
+    /// The report for the interval once it is over. Intervals without
+    /// frames (static desktop) say nothing about the decoder and are skipped.
+    pub fn take_report(&mut self, now: Instant) -> Option<DecodeReport> {
+        if now.saturating_duration_since(self.window_start) < REPORT_INTERVAL {
+            return None;
+        }
+        let (calls, errors, busy) = (self.calls, self.errors, self.busy);
+        self.window_start = now;
+        self.calls = 0;
+        self.errors = 0;
+        self.busy = Duration::ZERO;
+
+        if calls == 0 || self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let fps = calls as f64 / busy.as_secs_f64().max(1e-6);
+        Some(DecodeReport {
+            width: self.width,
+            height: self.height,
+            fps: fps.min(u16::MAX as f64) as u16,
+            error_permille: (errors as u64 * 1000 / calls as u64) as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meter_reports_sustained_fps() {
+        let start = Instant::now();
+        let mut meter = DecodeMeter::new(start);
+        meter.reset(1920, 1080, start);
+
+        // 40 frames at 25 ms each: the decoder sustains 40 fps
+        for i in 0..40 {
+            meter.on_decode(Duration::from_millis(25), i % 10 == 0);
+        }
+        assert_eq!(meter.take_report(start + Duration::from_secs(1)), None);
+        let report = meter.take_report(start + REPORT_INTERVAL).unwrap();
+        assert_eq!(report, DecodeReport { width: 1920, height: 1080, fps: 40, error_permille: 100 });
+
+        // Nothing decoded: nothing to report
+        assert_eq!(meter.take_report(start + REPORT_INTERVAL * 2), None);
+    }
+
+    #[test]
+    fn test_fits_decoder_capacity() {
+        let report = DecodeReport { width: 1920, height: 1080, fps: 40, error_permille: 0 };
+        assert!(report.fits(1920, 1080, 30));
+        assert!(!report.fits(3840, 2160, 30));
+        assert!(!report.fits(1920, 1080, 60));
+
+        // A decoder that keeps failing is taken at half its speed
+        let failing = DecodeReport { error_permille: 200, ..report };
+        assert!(!failing.fits(1920, 1080, 30));
+        assert!(failing.fits(1280, 720, 30));
+    }
+}
@@ -0,0 +1,337 @@
+//! Delta frames over QUIC datagrams
+//!
+//! On a reliable stream one lost packet holds back every frame behind it
+//! until it is retransmitted, which shows up as latency spikes on lossy
+//! Wi-Fi. Viewers that support datagrams get delta frames as datagrams
+//! instead: each frame is split into numbered fragments and reassembled in
+//! sequence order on arrival. Keyframes stay on the reliable stream, tagged
+//! with the same sequence numbers. A delta frame that never completes
+//! breaks the decode chain, so the viewer skips ahead to the next keyframe
+//! and asks for one. On lossy links parity datagrams (see `network::fec`)
+//! repair single lost fragments before it comes to that.
+
+use crate::network::fec;
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// First byte of every media datagram
+pub const DATAGRAM_TAG: u8 = 0x4d;
+
+/// First byte of parity datagrams
+pub const PARITY_TAG: u8 = 0x50;
+
+/// Tag, sequence number, fragment index and fragment count
+const HEADER_LEN: usize = 1 + 4 + 2 + 2;
+
+/// Parity datagrams add their group length to the header and a length
+/// prefix to the payload, so data fragments leave room for both
+const PARITY_OVERHEAD: usize = 1 + 2;
+
+/// Frames needing more fragments than this are sent on the stream; losing
+/// any one of them would lose the whole frame
+pub const MAX_FRAGMENTS: usize = 64;
+
+/// How long a missing frame may lag behind later ones before it is lost
+pub const REORDER_WAIT: Duration = Duration::from_millis(50);
+
+/// Incomplete frames kept before the oldest are dropped
+const MAX_PENDING: usize = 64;
+
+/// Split a frame message into datagrams of at most `max_size` bytes, with
+/// one parity datagram per `fec_group` fragments if set. Returns `None` if
+/// it would need more than `MAX_FRAGMENTS` data fragments.
+pub fn fragment(seq: u32, message: &[u8], max_size: usize, fec_group: Option<usize>) -> Option<Vec<Bytes>> {
+    let chunk = max_size.checked_sub(HEADER_LEN + PARITY_OVERHEAD).filter(|&c| c > 0)?;
+    let count = message.len().div_ceil(chunk).max(1);
+    if count > MAX_FRAGMENTS {
+        return None;
+    }
+    let payloads: Vec<&[u8]> = (0..count)
+        .map(|index| &message[(index * chunk).min(message.len())..((index + 1) * chunk).min(message.len())])
+        .collect();
+
+    let header = |tag: u8, index: usize| {
+        let mut datagram = Vec::with_capacity(max_size);
+        datagram.push(tag);
+        datagram.extend_from_slice(&seq.to_be_bytes());
+        datagram.extend_from_slice(&(index as u16).to_be_bytes());
+        datagram.extend_from_slice(&(count as u16).to_be_bytes());
+        datagram
+    };
+    let mut datagrams: Vec<Bytes> = payloads
+        .iter()
+        .enumerate()
+        .map(|(index, payload)| {
+            let mut datagram = header(DATAGRAM_TAG, index);
+            datagram.extend_from_slice(payload);
+            Bytes::from(datagram)
+        })
+        .collect();
+    if let Some(group) = fec_group.filter(|&g| g > 0) {
+        for (i, shards) in payloads.chunks(group).enumerate() {
+            let mut datagram = header(PARITY_TAG, i * group);
+            datagram.push(shards.len() as u8);
+            datagram.extend_from_slice(&fec::parity(shards));
+            datagrams.push(Bytes::from(datagram));
+        }
+    }
+    Some(datagrams)
+}
+
+/// A frame being reassembled
+struct Pending {
+    keyframe: bool,
+    fragments: Vec<Option<Bytes>>,
+    received: usize,
+    /// Parity shards not used yet: first fragment, group length, parity
+    parity: Vec<(usize, usize, Bytes)>,
+    first_seen: Instant,
+}
+
+impl Pending {
+    fn is_complete(&self) -> bool {
+        self.received == self.fragments.len()
+    }
+
+    /// Rebuild fragments that are the only one missing from a parity group
+    fn repair(&mut self) {
+        let mut i = 0;
+        while i < self.parity.len() {
+            let (first, len, _) = self.parity[i];
+            let group = first..first + len;
+            let missing: Vec<usize> = group.clone().filter(|&j| self.fragments[j].is_none()).collect();
+            if missing.len() > 1 {
+                i += 1;
+                continue;
+            }
+            let (_, _, parity) = self.parity.swap_remove(i);
+            if let [index] = missing[..] {
+                let present: Vec<&[u8]> = group.filter_map(|j| self.fragments[j].as_deref()).collect();
+                if let Some(shard) = fec::recover(&present, &parity) {
+                    self.fragments[index] = Some(Bytes::from(shard));
+                    self.received += 1;
+                }
+            }
+        }
+    }
+
+    fn into_message(self) -> Vec<u8> {
+        self.fragments.into_iter().flatten().flat_map(|f| f.to_vec()).collect()
+    }
+}
+
+/// Frames ready to decode, in order
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Ready {
+    pub frames: Vec<Vec<u8>>,
+    /// A frame was lost and the decoder needs a keyframe
+    pub lost: bool,
+}
+
+/// Puts datagram fragments and sequenced stream frames back in order.
+/// Sequence numbers are per viewer stream and never wrap in practice (u32
+/// at 60 fps lasts over two years).
+pub struct Reassembler {
+    /// Next frame to deliver; `None` while waiting for a keyframe
+    next_seq: Option<u32>,
+    pending: BTreeMap<u32, Pending>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self { next_seq: None, pending: BTreeMap::new() }
+    }
+
+    /// Decoder was reinitialized: frames from before it are useless
+    pub fn reset(&mut self) {
+        self.next_seq = None;
+        self.pending.clear();
+    }
+
+    /// Add a received datagram. Datagrams that are not media fragments or
+    /// parity, or belong to frames already delivered, are ignored.
+    pub fn push_fragment(&mut self, datagram: &[u8], now: Instant) {
+        if datagram.len() < HEADER_LEN || !matches!(datagram[0], DATAGRAM_TAG | PARITY_TAG) {
+            return;
+        }
+        let seq = u32::from_be_bytes([datagram[1], datagram[2], datagram[3], datagram[4]]);
+        let index = u16::from_be_bytes([datagram[5], datagram[6]]) as usize;
+        let count = u16::from_be_bytes([datagram[7], datagram[8]]) as usize;
+        if index >= count || count > MAX_FRAGMENTS || self.next_seq.is_some_and(|next| seq < next) {
+            return;
+        }
+
+        let pending = self.pending.entry(seq).or_insert_with(|| Pending {
+            keyframe: false,
+            fragments: vec![None; count],
+            received: 0,
+            parity: Vec::new(),
+            first_seen: now,
+        });
+        if pending.fragments.len() != count {
+            return;
+        }
+        if datagram[0] == PARITY_TAG {
+            let Some(&len) = datagram.get(HEADER_LEN) else {
+                return;
+            };
+            let len = len as usize;
+            if len == 0 || index + len > count {
+                return;
+            }
+            pending.parity.push((index, len, Bytes::copy_from_slice(&datagram[HEADER_LEN + 1..])));
+        } else {
+            if pending.fragments[index].is_some() {
+                return;
+            }
+            pending.fragments[index] = Some(Bytes::copy_from_slice(&datagram[HEADER_LEN..]));
+            pending.received += 1;
+        }
+        if !pending.is_complete() {
+            pending.repair();
+        }
+        self.trim();
+    }
+
+    /// Add a whole frame received on the reliable stream
+    pub fn push_frame(&mut self, seq: u32, keyframe: bool, message: Vec<u8>, now: Instant) {
+        if self.next_seq.is_some_and(|next| seq < next) {
+            return;
+        }
+        self.pending.insert(seq, Pending {
+            keyframe,
+            fragments: vec![Some(Bytes::from(message))],
+            received: 1,
+            parity: Vec::new(),
+            first_seen: now,
+        });
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.pending.len() > MAX_PENDING {
+            self.pending.pop_first();
+        }
+    }
+
+    /// Take every frame that can be decoded now
+    pub fn pop_ready(&mut self, now: Instant) -> Ready {
+        let mut ready = Ready::default();
+        loop {
+            let Some(next) = self.next_seq else {
+                // Start over at the first complete keyframe
+                let Some(seq) = self.first_keyframe(0) else {
+                    break;
+                };
+                self.skip_to(seq);
+                continue;
+            };
+
+            if self.pending.get(&next).is_some_and(Pending::is_complete) {
+                let frame = self.pending.remove(&next).expect("checked above");
+                ready.frames.push(frame.into_message());
+                self.next_seq = Some(next + 1);
+                continue;
+            }
+
+            // A later keyframe makes the missing frames irrelevant
+            if let Some(seq) = self.first_keyframe(next + 1) {
+                self.skip_to(seq);
+                continue;
+            }
+
+            let overdue = self
+                .pending
+                .range(next + 1..)
+                .any(|(_, p)| now.saturating_duration_since(p.first_seen) >= REORDER_WAIT);
+            if overdue {
+                ready.lost = true;
+                self.next_seq = None;
+                continue;
+            }
+            break;
+        }
+        ready
+    }
+
+    fn first_keyframe(&self, from: u32) -> Option<u32> {
+        self.pending
+            .range(from..)
+            .find(|(_, p)| p.keyframe && p.is_complete())
+            .map(|(&seq, _)| seq)
+    }
+
+    fn skip_to(&mut self, seq: u32) {
+        self.pending = self.pending.split_off(&seq);
+        self.next_seq = Some(seq);
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragments_reassemble_in_order() {
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+        reassembler.push_frame(1, true, b"key".to_vec(), now);
+
+        let message: Vec<u8> = (0..100).collect();
+        let mut fragments = fragment(2, &message, HEADER_LEN + PARITY_OVERHEAD + 30, None).unwrap();
+        assert_eq!(fragments.len(), 4);
+        fragments.reverse();
+        for f in &fragments[..3] {
+            reassembler.push_fragment(f, now);
+        }
+        assert_eq!(reassembler.pop_ready(now).frames, vec![b"key".to_vec()]);
+
+        reassembler.push_fragment(&fragments[3], now);
+        assert_eq!(reassembler.pop_ready(now), Ready { frames: vec![message], lost: false });
+        assert!(fragment(3, &[0; 1000], HEADER_LEN + PARITY_OVERHEAD + 10, None).is_none());
+    }
+
+    #[test]
+    fn test_parity_repairs_lost_fragment() {
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+        reassembler.push_frame(1, true, b"key".to_vec(), now);
+
+        let message: Vec<u8> = (0..100).collect();
+        let datagrams = fragment(2, &message, HEADER_LEN + PARITY_OVERHEAD + 30, Some(2)).unwrap();
+        assert_eq!(datagrams.len(), 4 + 2);
+        // Lose one data fragment from each group
+        for (i, d) in datagrams.iter().enumerate() {
+            if i != 1 && i != 2 {
+                reassembler.push_fragment(d, now);
+            }
+        }
+        assert_eq!(reassembler.pop_ready(now).frames, vec![b"key".to_vec(), message]);
+    }
+
+    #[test]
+    fn test_lost_frame_waits_for_keyframe() {
+        let now = Instant::now();
+        let mut reassembler = Reassembler::new();
+        reassembler.push_frame(1, true, b"key".to_vec(), now);
+        reassembler.push_fragment(&fragment(3, b"late", 100, None).unwrap()[0], now);
+        assert_eq!(reassembler.pop_ready(now).frames.len(), 1);
+
+        // Frame 2 never arrives
+        let later = now + REORDER_WAIT;
+        assert_eq!(reassembler.pop_ready(later), Ready { frames: vec![], lost: true });
+        reassembler.push_fragment(&fragment(4, b"delta", 100, None).unwrap()[0], later);
+        assert!(reassembler.pop_ready(later).frames.is_empty());
+
+        reassembler.push_frame(5, true, b"key2".to_vec(), later);
+        reassembler.push_fragment(&fragment(6, b"next", 100, None).unwrap()[0], later);
+        assert_eq!(reassembler.pop_ready(later).frames, vec![b"key2".to_vec(), b"next".to_vec()]);
+    }
+}
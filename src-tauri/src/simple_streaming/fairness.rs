@@ -0,0 +1,128 @@
+//! Uplink fairness across viewers
+//!
+//! Each viewer picks its ladder on its own from how its link keeps up (see
+//! `adaptive`), so a fast viewer is never held back by a slow one. The
+//! scheduler checks every choice against the rate the viewer's link
+//! measured and, when the sharer set an uplink cap, against the cap: while
+//! the chosen ladders cost more than the cap, the viewer with the slowest
+//! link still on the high ladder moves to the low one. If the cap is too
+//! small even with everyone on the low ladder, the low ladder's bitrate is
+//! limited to an equal share of what is left.
+//!
+//! A viewer whose decoder cannot keep up with the high ladder (see
+//! `capability`) stays on the low one however fast its link is.
+
+use super::adaptive::Ladder;
+
+/// A viewer's link must carry the high ladder with this much to spare
+const LINK_HEADROOM: f64 = 1.2;
+
+/// One viewer's own ladder choice and measured link
+#[derive(Debug, Clone, Copy)]
+pub struct Demand {
+    pub viewer: u64,
+    pub requested: Ladder,
+    pub link_bps: Option<u32>,
+    /// The viewer's decoder keeps up with the high ladder (or has not
+    /// reported yet)
+    pub decodes_high: bool,
+}
+
+/// Ladder per viewer and an optional low ladder bitrate limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub ladders: Vec<(u64, Ladder)>,
+    pub low_bitrate_cap: Option<u32>,
+}
+
+/// Assign ladders given the current high and low ladder bitrates
+pub fn schedule(demands: &[Demand], high_bps: u32, low_bps: u32, uplink_cap: Option<u64>) -> Schedule {
+    let fits_high =
+        |d: &Demand| d.decodes_high && d.link_bps.is_none_or(|bps| bps as f64 >= high_bps as f64 * LINK_HEADROOM);
+    let mut ladders: Vec<(u64, Ladder)> = demands
+        .iter()
+        .map(|d| {
+            let ladder = if d.requested == Ladder::High && fits_high(d) { Ladder::High } else { Ladder::Low };
+            (d.viewer, ladder)
+        })
+        .collect();
+
+    let Some(cap) = uplink_cap else {
+        return Schedule { ladders, low_bitrate_cap: None };
+    };
+    let cost = |ladders: &[(u64, Ladder)]| -> u64 {
+        ladders
+            .iter()
+            .map(|(_, l)| match l {
+                Ladder::High => high_bps as u64,
+                Ladder::Low => low_bps as u64,
+            })
+            .sum()
+    };
+
+    // Slowest links leave the high ladder first; unmeasured links last
+    let mut high: Vec<usize> = (0..ladders.len()).filter(|&i| ladders[i].1 == Ladder::High).collect();
+    high.sort_by_key(|&i| (demands[i].link_bps.map_or(u64::MAX, u64::from), demands[i].viewer));
+    for i in high {
+        if cost(&ladders) <= cap {
+            break;
+        }
+        ladders[i].1 = Ladder::Low;
+    }
+
+    let low_count = ladders.iter().filter(|(_, l)| *l == Ladder::Low).count() as u64;
+    let low_bitrate_cap = (cost(&ladders) > cap && low_count > 0).then(|| {
+        let high_count = ladders.len() as u64 - low_count;
+        let left = cap.saturating_sub(high_count * high_bps as u64);
+        (left / low_count).min(u32::MAX as u64) as u32
+    });
+    Schedule { ladders, low_bitrate_cap }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demand(viewer: u64, requested: Ladder, link_bps: Option<u32>) -> Demand {
+        Demand { viewer, requested, link_bps, decodes_high: true }
+    }
+
+    #[test]
+    fn test_fast_viewers_keep_high_without_cap() {
+        let demands = [
+            demand(1, Ladder::High, Some(20_000_000)),
+            demand(2, Ladder::High, Some(2_000_000)),
+            demand(3, Ladder::Low, None),
+            demand(4, Ladder::High, None),
+        ];
+        let plan = schedule(&demands, 4_000_000, 800_000, None);
+        assert_eq!(
+            plan.ladders,
+            vec![(1, Ladder::High), (2, Ladder::Low), (3, Ladder::Low), (4, Ladder::High)]
+        );
+        assert_eq!(plan.low_bitrate_cap, None);
+
+        // A fast link does not help a decoder that cannot keep up
+        let slow_decoder = Demand { decodes_high: false, ..demands[0] };
+        let plan = schedule(&[slow_decoder], 4_000_000, 800_000, None);
+        assert_eq!(plan.ladders, vec![(1, Ladder::Low)]);
+    }
+
+    #[test]
+    fn test_cap_demotes_slowest_first() {
+        let demands = [
+            demand(1, Ladder::High, Some(50_000_000)),
+            demand(2, Ladder::High, Some(10_000_000)),
+            demand(3, Ladder::High, None),
+        ];
+        // Room for two high viewers and one low
+        let plan = schedule(&demands, 4_000_000, 1_000_000, Some(9_000_000));
+        assert_eq!(plan.ladders, vec![(1, Ladder::High), (2, Ladder::Low), (3, Ladder::High)]);
+        assert_eq!(plan.low_bitrate_cap, None);
+
+        // Not even the low ladder fits for everyone
+        let plan = schedule(&demands, 4_000_000, 1_000_000, Some(1_500_000));
+        assert!(plan.ladders.iter().all(|(_, l)| *l == Ladder::Low));
+        assert_eq!(plan.low_bitrate_cap, Some(500_000));
+    }
+}
@@ -2,34 +2,103 @@
 //!
 //! Bypasses all complex encoder/decoder selection and optimization.
 //! Uses OpenH264 only, single QUIC stream for all messages.
+//! One capture+encode loop feeds every viewer through a broadcast channel;
+//! each viewer connection has its own sender task and send queue. Viewers on
+//! congested links are moved to a second, low-bitrate encoder. Viewers that
+//! support QUIC datagrams receive delta frames as datagrams. Viewers report
+//! how fast they decode, so a slow machine on a fast link still gets the
+//! low ladder. The pointer can travel beside the video instead of in it, so
+//! viewers draw it sharp. Everyone watching can annotate the screen.
 //! Designed to verify basic capture→encode→transmit→decode→render works.
 
-use crate::capture::{self, ScreenCapture};
+mod adaptive;
+mod annotation;
+mod capability;
+mod datagram;
+mod fairness;
+mod pointer;
+
+use annotation::{encode_annotation_message, parse_annotation_message, MAX_HISTORY};
+use adaptive::{FrameKind, Ladder, SendQueue, ViewerRate, QUEUE_FRAMES};
+use capability::{DecodeMeter, DecodeReport};
+use datagram::Reassembler;
+use pointer::{encode_pointer_message, parse_pointer_message, PointerState, POINTER_INTERVAL};
+use crate::app_state::Slice;
+use crate::capture::archive::SnapshotArchive;
+use crate::capture::convert::FormatPlan;
+use crate::capture::cursor::CursorOverlay;
+use crate::capture::damage::{Damage, DamageTracker, FrameAction};
+use crate::capture::focus;
+use crate::capture::pool;
+use crate::capture::recovery::{CaptureRecovery, RecoveryAction};
+use crate::capture::redaction::NotificationRedactor;
+use crate::capture::{self, CaptureError, CaptureRegion, ScreenCapture};
+use crate::decoder::av1::Av1Decoder;
 use crate::decoder::software::SoftwareDecoder;
 use crate::decoder::{DecoderConfig, OutputFormat, VideoDecoder};
+use crate::encoder::color::ColorSpace;
+use crate::encoder::gpu_scaler::GpuScaler;
 use crate::encoder::scaler::FrameScaler;
-use crate::encoder::{self, EncoderConfig, EncoderPreset, VideoEncoder};
-use crate::network::quic::{self, QuicStream};
-use crate::renderer::{RenderFrame, RenderWindow, RenderWindowHandle};
-use parking_lot::RwLock;
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::encoder::validate::BitstreamValidator;
+use crate::encoder::{self, Codec, EncoderConfig, EncoderTuning, FrameType, RegionOfInterest, VideoEncoder};
+use crate::network::clock;
+use crate::network::fec::FecTuner;
+use crate::network::quic::{self, QuicConnection, QuicStream};
+use crate::network::NetworkError;
+use crate::renderer::{hud, RenderFrame, RenderWindow, RenderWindowHandle};
+use crate::streaming::congestion::{self, LinkSample, RateController};
+use crate::streaming::frame_dump;
+use crate::streaming::health::DECODE_ERROR_RETRY;
+use crate::streaming::latency;
+use crate::streaming::recording;
+use crate::streaming::stats;
+use crate::streaming::timing::{self, Stage};
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::{broadcast, mpsc, watch};
 
 /// Simple message types sent on the persistent stream
 const MSG_TYPE_START: u8 = 0x01;
 const MSG_TYPE_FRAME: u8 = 0x02;
 const MSG_TYPE_STOP: u8 = 0x03;
 const MSG_TYPE_RESOLUTION_REQUEST: u8 = 0x04; // viewer → sharer
+const MSG_TYPE_DISPLAY_REQUEST: u8 = 0x05; // viewer → sharer
+const MSG_TYPE_KEYFRAME_REQUEST: u8 = 0x06; // viewer → sharer
+const MSG_TYPE_DATAGRAM_READY: u8 = 0x07; // viewer → sharer
+const MSG_TYPE_SEQ_FRAME: u8 = 0x08; // frame with its datagram sequence number
+const MSG_TYPE_METADATA: u8 = 0x09; // sharer → viewer: foreground window title
+const MSG_TYPE_DECODE_REPORT: u8 = 0x0A; // viewer → sharer
+const MSG_TYPE_POINTER: u8 = 0x0B; // sharer → viewer: pointer position and button
+const MSG_TYPE_ANNOTATION: u8 = 0x0C; // both ways: a shape drawn over the screen
+
+/// Flag in the resolution request's optional last byte
+const RESOLUTION_FLAG_NEAR_LOSSLESS: u8 = 0x01;
 
 /// Hardcoded FPS for simplicity
 const SIMPLE_FPS: u32 = 30;
 
+/// How often the foreground window title is checked for viewers
+const TITLE_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Target encode resolution (downscale from capture resolution)
 const SIMPLE_TARGET_WIDTH: u32 = 1280;
 const SIMPLE_TARGET_HEIGHT: u32 = 720;
 
+/// Default encode bitrate until the viewer picks one
+const SIMPLE_BITRATE: u32 = 2_000_000;
+
+/// Low ladder resolution cap (never above the high ladder's)
+const LOW_TARGET_WIDTH: u32 = 960;
+const LOW_TARGET_HEIGHT: u32 = 540;
+
+/// Low ladder bitrate bounds
+const LOW_BITRATE_MIN: u32 = 300_000;
+const LOW_BITRATE_MAX: u32 = 1_000_000;
+
 /// Resolution option for toolbar
 #[derive(Debug, Clone, Copy)]
 pub struct ResolutionOption {
@@ -43,6 +112,8 @@ pub struct ResolutionOption {
 pub struct BitrateOption {
     pub label: &'static str,
     pub bitrate: u32,
+    /// Encode at near-lossless constant quality, `bitrate` only capping it
+    pub near_lossless: bool,
 }
 
 /// Available resolution options (independent of bitrate)
@@ -54,11 +125,13 @@ pub const RESOLUTION_OPTIONS: [ResolutionOption; 4] = [
 ];
 
 /// Available bitrate options (independent of resolution)
-pub const BITRATE_OPTIONS: [BitrateOption; 4] = [
-    BitrateOption { label: "2 Mbps",  bitrate: 2_000_000 },
-    BitrateOption { label: "4 Mbps",  bitrate: 4_000_000 },
-    BitrateOption { label: "8 Mbps",  bitrate: 8_000_000 },
-    BitrateOption { label: "12 Mbps", bitrate: 12_000_000 },
+pub const BITRATE_OPTIONS: [BitrateOption; 5] = [
+    BitrateOption { label: "2 Mbps",  bitrate: 2_000_000,  near_lossless: false },
+    BitrateOption { label: "4 Mbps",  bitrate: 4_000_000,  near_lossless: false },
+    BitrateOption { label: "8 Mbps",  bitrate: 8_000_000,  near_lossless: false },
+    BitrateOption { label: "12 Mbps", bitrate: 12_000_000, near_lossless: false },
+    // Sharp text for code review; needs a fast LAN
+    BitrateOption { label: "Text",    bitrate: 40_000_000, near_lossless: true },
 ];
 
 // ===== Global state =====
@@ -69,6 +142,10 @@ static SIMPLE_SHARER_ACTIVE: once_cell::sync::Lazy<Arc<AtomicBool>> =
 static SIMPLE_STOP_TX: once_cell::sync::Lazy<RwLock<Option<mpsc::Sender<()>>>> =
     once_cell::sync::Lazy::new(|| RwLock::new(None));
 
+/// Display switches requested by the local user, keyed by sharer IP (viewer side)
+static DISPLAY_REQUESTS: once_cell::sync::Lazy<RwLock<HashMap<String, u32>>> =
+    once_cell::sync::Lazy::new(|| RwLock::new(HashMap::new()));
+
 /// Check if simple sharer is active
 pub fn is_simple_sharing() -> bool {
     SIMPLE_SHARER_ACTIVE.load(Ordering::SeqCst)
@@ -76,8 +153,9 @@ pub fn is_simple_sharing() -> bool {
 
 // ===== Sender side =====
 
-/// Start simple sharing - begins capture and waits for viewer requests
-pub fn start_sharing(display_id: u32) -> Result<(), String> {
+/// Start simple sharing - begins capture and waits for viewer requests.
+/// If `region` is set, only that rectangle of the display is shared.
+pub fn start_sharing(display_id: u32, region: Option<CaptureRegion>) -> Result<(), String> {
     if SIMPLE_SHARER_ACTIVE.load(Ordering::SeqCst) {
         log::info!("[SIMPLE] Already sharing, ignoring start request");
         return Ok(());
@@ -100,15 +178,82 @@ pub fn start_sharing(display_id: u32) -> Result<(), String> {
         .or_else(|| displays.first())
         .ok_or_else(|| "[SIMPLE] No display found".to_string())?;
 
-    let width = display.width;
-    let height = display.height;
-    log::info!("[SIMPLE] Display: {} ({}x{})", display.name, width, height);
+    log::info!("[SIMPLE] Display: {} ({}x{})", display.name, display.width, display.height);
+    let (display_width, display_height) = (display.width, display.height);
+
+    // Notification redaction works in display coordinates, so it runs before the region crop
+    let redactor = crate::commands::redact_notifications_enabled().then(|| {
+        log::info!("[SIMPLE] Notification redaction enabled");
+        NotificationRedactor::for_display(display.width, display.height, display.scale_factor)
+    });
+    let cursor = crate::commands::cursor_in_frame()
+        .then(|| CursorOverlay::new(display.scale_factor));
+
+    // Start capture (full display or a region of it)
+    let region = match region {
+        Some(r) => {
+            let clamped = capture.start_region(display_id, r.x, r.y, r.width, r.height)
+                .map_err(|e| format!("[SIMPLE] Failed to start region capture: {}", e))?;
+            log::info!("[SIMPLE] Region capture started: {}x{} at ({}, {})",
+                clamped.width, clamped.height, clamped.x, clamped.y);
+            Some(clamped)
+        }
+        None => {
+            capture.start(display_id)
+                .map_err(|e| format!("[SIMPLE] Failed to start capture: {}", e))?;
+            log::info!("[SIMPLE] Capture started");
+            None
+        }
+    };
+
+    // Source size for the scaler is the region when sharing part of the display
+    let (width, height) = region
+        .map(|r| (r.width, r.height))
+        .unwrap_or((display_width, display_height));
 
-    // Start capture
-    capture.start(display_id)
-        .map_err(|e| format!("[SIMPLE] Failed to start capture: {}", e))?;
-    log::info!("[SIMPLE] Capture started");
+    begin_sharing(capture, display_id, None, region, redactor, cursor, width, height)
+}
+
+/// Start simple sharing of a single application window (backends with
+/// per-window capture only, see `ScreenCapture::get_windows`)
+pub fn start_window_sharing(window_id: u64) -> Result<(), String> {
+    if SIMPLE_SHARER_ACTIVE.load(Ordering::SeqCst) {
+        log::info!("[SIMPLE] Already sharing, ignoring start request");
+        return Ok(());
+    }
+
+    log::info!("[SIMPLE] === Starting simple sharing for window {:#x} ===", window_id);
+
+    let mut capture = capture::create_capture()
+        .map_err(|e| format!("[SIMPLE] Failed to create capture: {}", e))?;
+    let (width, height) = capture.start_window(window_id)
+        .map_err(|e| format!("[SIMPLE] Failed to start window capture: {}", e))?;
+    log::info!("[SIMPLE] Window capture started: {}x{}", width, height);
+
+    // Notification banners are not part of a window, so no redaction
+    let cursor = crate::commands::cursor_in_frame().then(|| {
+        let scale_factor = capture.get_displays().ok()
+            .and_then(|d| d.first().map(|d| d.scale_factor))
+            .unwrap_or(1.0);
+        CursorOverlay::new(scale_factor)
+    });
+
+    begin_sharing(capture, 0, Some(window_id), None, None, cursor, width, height)
+}
 
+/// Set up scaler and encoder for a started capture and start the encode
+/// loop, which idles until a viewer joins
+#[allow(clippy::too_many_arguments)]
+fn begin_sharing(
+    mut capture: Box<dyn ScreenCapture>,
+    display_id: u32,
+    window_id: Option<u64>,
+    region: Option<CaptureRegion>,
+    redactor: Option<NotificationRedactor>,
+    cursor: Option<CursorOverlay>,
+    width: u32,
+    height: u32,
+) -> Result<(), String> {
     // Create pre-encoder downscaler: capture resolution → target resolution
     let pre_scaler = FrameScaler::new_with_target(width, height, SIMPLE_TARGET_WIDTH, SIMPLE_TARGET_HEIGHT);
     let encode_width = pre_scaler.dst_width;
@@ -119,15 +264,26 @@ pub fn start_sharing(display_id: u32) -> Result<(), String> {
     let mut encoder = encoder::create_encoder()
         .map_err(|e| format!("[SIMPLE] Failed to create encoder: {}", e))?;
     log::info!("[SIMPLE] Using encoder: {}", encoder.info());
+    // Every encoder of this session must produce what viewers were told
+    let codec = encoder.codec();
+    let color = crate::commands::preferred_color_space();
+
+    // Capture NV12 when frames go straight from capture to the encoder
+    let needs_bgra = redactor.is_some() || cursor.is_some() || region.is_some() || pre_scaler.needs_scaling;
+    let formats = FormatPlan::negotiate(capture.as_mut(), encoder.as_ref(), needs_bgra, color);
 
+    let tuning = crate::commands::encoder_tuning();
     let encoder_config = EncoderConfig {
         width: encode_width,
         height: encode_height,
         fps: SIMPLE_FPS,
-        bitrate: 2_000_000, // 2 Mbps
-        max_bitrate: 4_000_000,
-        keyframe_interval: SIMPLE_FPS, // 1 keyframe per second
-        preset: EncoderPreset::UltraFast,
+        bitrate: SIMPLE_BITRATE,
+        max_bitrate: tuning.max_bitrate(SIMPLE_BITRATE),
+        keyframe_interval: tuning.keyframe_interval(SIMPLE_FPS),
+        preset: tuning.preset,
+        input_format: formats.encoder,
+        crf: None,
+        color_space: color,
     };
 
     encoder.init(encoder_config)
@@ -141,63 +297,1204 @@ pub fn start_sharing(display_id: u32) -> Result<(), String> {
     *SIMPLE_STOP_TX.write() = Some(stop_tx);
     SIMPLE_SHARER_ACTIVE.store(true, Ordering::SeqCst);
 
-    log::info!("[SIMPLE] Sharer is now active, waiting for viewer requests");
-
-    // Broadcast that we're sharing (using existing protocol)
-    let active = SIMPLE_SHARER_ACTIVE.clone();
-    let _ = std::thread::Builder::new()
-        .name("simple-sharer-state".to_string())
-        .spawn(move || {
-            // Store capture/encoder for use when viewer requests come in
-            // We put them in a global so handle_simple_request can access them
-            let mut state = SHARER_STATE.write();
-            *state = Some(SharerState {
-                capture,
-                pre_scaler,
-                encoder,
-                encode_width,
-                encode_height,
-                stop_rx,
-                active,
-            });
-            log::info!("[SIMPLE] Sharer state stored, ready for viewer requests");
-        });
+    // One encode loop feeds every viewer through the fanout
+    let (frames, _) = broadcast::channel(FANOUT_CAPACITY);
+    let (control, control_rx) = mpsc::unbounded_channel();
+    let fanout = Arc::new(Fanout {
+        frames,
+        control,
+        encode_size: Mutex::new((encode_width, encode_height)),
+        codec,
+        color,
+        window_title: watch::channel(String::new()).0,
+        pointer: watch::channel(None).0,
+        annotations: broadcast::channel(FANOUT_CAPACITY).0,
+        annotation_log: Mutex::new(Vec::new()),
+        viewers: Mutex::new(HashMap::new()),
+        kicked: Mutex::new(HashSet::new()),
+    });
+    *FANOUT.write() = Some(fanout.clone());
+
+    let state = SharerState {
+        capture,
+        display_id,
+        window_id,
+        region,
+        redactor,
+        cursor,
+        pointer_channel: crate::commands::cursor_channel_enabled(),
+        archive: SnapshotArchive::start(&match window_id {
+            Some(id) => format!("window-{}", id),
+            None => format!("display-{}", display_id),
+        }),
+        damage: DamageTracker::new(),
+        pre_scaler,
+        encoder,
+        codec,
+        color,
+        encode_width,
+        encode_height,
+        target_width: SIMPLE_TARGET_WIDTH,
+        target_height: SIMPLE_TARGET_HEIGHT,
+        bitrate: SIMPLE_BITRATE,
+        near_lossless: false,
+        tuning,
+        formats,
+        // Safe mode keeps frames on the CPU
+        gpu_disabled: crate::safe_mode::is_enabled(),
+        gpu_scaler: None,
+        gpu_scaler_failed: crate::safe_mode::is_enabled(),
+        recovery: CaptureRecovery::new(),
+        low: None,
+        congestion: RateController::new(SIMPLE_BITRATE, SIMPLE_FPS),
+        low_bitrate_cap: None,
+        stop_rx,
+        active: SIMPLE_SHARER_ACTIVE.clone(),
+    };
+    tokio::spawn(encode_loop(state, control_rx, fanout));
+    crate::app_state::changed(Slice::Sharing);
 
+    log::info!("[SIMPLE] Sharer is now active, waiting for viewer requests");
     Ok(())
 }
 
-/// Internal sharer state
+/// Internal sharer state, owned by the encode loop
 struct SharerState {
     capture: Box<dyn ScreenCapture>,
+    /// Display being captured (may change mid-session at the viewer's request)
+    display_id: u32,
+    /// Window being captured instead of a display, if any
+    window_id: Option<u64>,
+    /// Optional crop applied to each frame before the scaler
+    region: Option<CaptureRegion>,
+    /// Optional notification redaction applied before the crop
+    redactor: Option<NotificationRedactor>,
+    /// Optional pointer compositing applied before the crop
+    cursor: Option<CursorOverlay>,
+    /// Send the pointer to viewers beside the video instead
+    pointer_channel: bool,
+    /// Periodic snapshots of what viewers see, if archive mode is on
+    archive: Option<SnapshotArchive>,
+    /// Detects unchanged frames so they are not re-encoded
+    damage: DamageTracker,
     pre_scaler: FrameScaler,
     encoder: Box<dyn VideoEncoder>,
+    /// Codec viewers decode; encoders rebuilt mid-session keep it
+    codec: Codec,
+    /// Color space viewers convert back with; kept like the codec
+    color: ColorSpace,
     encode_width: u32,
     encode_height: u32,
+    /// Resolution and bitrate last requested by a viewer
+    target_width: u32,
+    target_height: u32,
+    bitrate: u32,
+    /// Constant near-lossless quality for text, with the bitrate as a cap
+    near_lossless: bool,
+    /// Preset, keyframe interval and peak bitrate from settings
+    tuning: EncoderTuning,
+    /// Pixel formats between capture and encoder
+    formats: FormatPlan,
+    /// Set after a GPU encode failure to stay on the CPU pipeline
+    gpu_disabled: bool,
+    /// Compute-shader scaling and conversion for the main encoder
+    gpu_scaler: Option<GpuScaler>,
+    /// Set once the GPU scaler failed, to stay on the CPU scaler
+    gpu_scaler_failed: bool,
+    /// Decides whether capture errors are skipped, retried or fatal
+    recovery: CaptureRecovery,
+    /// Second encoder, running while any viewer is on the low ladder
+    low: Option<LowLadder>,
+    /// Steers the main encoder's bitrate and frame rate below the requested
+    /// bitrate from what the high ladder viewers' links report
+    congestion: RateController,
+    /// Low ladder bitrate limit from the fairness scheduler (uplink cap)
+    low_bitrate_cap: Option<u32>,
     stop_rx: mpsc::Receiver<()>,
     active: Arc<AtomicBool>,
 }
 
+/// Reduced resolution and bitrate encoder for congested viewers, fed from
+/// the same captured frames as the main one
+struct LowLadder {
+    pre_scaler: FrameScaler,
+    encoder: Box<dyn VideoEncoder>,
+    formats: FormatPlan,
+    encode_width: u32,
+    encode_height: u32,
+    bitrate: u32,
+}
+
+impl SharerState {
+    /// Whether any stage between capture and encoder works on BGRA pixels
+    fn needs_bgra(&self) -> bool {
+        self.redactor.is_some()
+            || self.cursor.is_some()
+            || self.region.is_some()
+            || self.pre_scaler.needs_scaling
+            || self.low.is_some()
+    }
+
+    /// GPU frames bypass redaction, cursor, crop and scaling, so they are only
+    /// used when none of those are needed, the encoder runs at capture size
+    /// and neither a low ladder nor the archive needs CPU pixels
+    fn use_gpu_path(&self) -> bool {
+        !self.gpu_disabled
+            && self.low.is_none()
+            && self.archive.is_none()
+            && self.region.is_none()
+            && self.redactor.is_none()
+            && self.cursor.is_none()
+            && self.pre_scaler.src_width == self.encode_width
+            && self.pre_scaler.src_height == self.encode_height
+            && self.capture.supports_gpu_frames()
+            && self.encoder.supports_gpu_input()
+    }
+
+    /// Scale a frame and convert it to the main encoder's input format on
+    /// the GPU. `None` when the frame needs no scaling, the GPU scaler is
+    /// turned off or failed; the CPU scaler handles the frame then.
+    fn gpu_preprocess(&mut self, frame: &capture::CapturedFrame) -> Option<Vec<u8>> {
+        if self.gpu_scaler_failed || !self.pre_scaler.needs_scaling || !crate::commands::gpu_preprocess_enabled() {
+            return None;
+        }
+        let src = (frame.width, frame.height);
+        let dst = (self.encode_width, self.encode_height);
+        let output = self.formats.encoder;
+        if !self.gpu_scaler.as_ref().is_some_and(|g| g.matches(src, dst, frame.format, output, self.color)) {
+            match GpuScaler::new(src.0, src.1, dst.0, dst.1, frame.format, output, self.color) {
+                Ok(scaler) => self.gpu_scaler = Some(scaler),
+                Err(e) => {
+                    log::warn!("[SIMPLE] GPU scaling not available, scaling on the CPU: {}", e);
+                    self.gpu_scaler = None;
+                    self.gpu_scaler_failed = true;
+                    return None;
+                }
+            }
+        }
+
+        let scaler = self.gpu_scaler.as_ref()?;
+        match scaler.process(&frame.data) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                log::warn!("[SIMPLE] GPU scaling failed, scaling on the CPU: {}", e);
+                self.gpu_scaler = None;
+                self.gpu_scaler_failed = true;
+                None
+            }
+        }
+    }
+
+    /// The pointer on the shared area, for the side channel
+    fn pointer(&self) -> Option<PointerState> {
+        let (x, y) = self.capture.cursor_position()?;
+        let (x, y) = match self.region {
+            Some(region) => (x - region.x as i32, y - region.y as i32),
+            None => (x, y),
+        };
+        PointerState::in_area(
+            x,
+            y,
+            self.pre_scaler.src_width,
+            self.pre_scaler.src_height,
+            crate::capture::cursor::primary_button_down(),
+        )
+    }
+
+    /// Point the encoders' region of interest at the pointer, mapped from
+    /// the captured display into each encoder's frame
+    fn update_roi(&mut self) {
+        let low_roi = self.low.as_ref().is_some_and(|low| low.encoder.supports_roi());
+        if !self.encoder.supports_roi() && !low_roi {
+            return;
+        }
+        let pointer = crate::commands::cursor_roi_enabled()
+            .then(|| self.capture.cursor_position())
+            .flatten()
+            .map(|(x, y)| match self.region {
+                Some(region) => (x - region.x as i32, y - region.y as i32),
+                None => (x, y),
+            });
+        let (src_w, src_h) = (self.pre_scaler.src_width.max(1) as i64, self.pre_scaler.src_height.max(1) as i64);
+        let roi_for = |width: u32, height: u32| {
+            pointer.and_then(|(x, y)| {
+                let x = (x as i64 * width as i64 / src_w) as i32;
+                let y = (y as i64 * height as i64 / src_h) as i32;
+                RegionOfInterest::around_cursor(x, y, width, height)
+            })
+        };
+
+        self.encoder.set_roi(roi_for(self.encode_width, self.encode_height));
+        if let Some(low) = self.low.as_mut() {
+            low.encoder.set_roi(roi_for(low.encode_width, low.encode_height));
+        }
+    }
+
+    /// Recreate the encoder for the current scaler output and bitrate.
+    /// On failure the old encoder is kept.
+    fn rebuild_encoder(&mut self) -> Result<(), String> {
+        // The near-lossless encoder is H.264 only; AV1 sessions keep to the bitrate
+        let near_lossless = self.near_lossless && self.codec == Codec::H264;
+        let mut new_encoder = if near_lossless {
+            encoder::create_near_lossless_encoder().or_else(|e| {
+                log::warn!("[SIMPLE] Near-lossless encoder not available, using the bitrate: {}", e);
+                encoder::create_encoder_for(self.codec)
+            })
+        } else {
+            encoder::create_encoder_for(self.codec)
+        }
+        .map_err(|e| format!("Failed to create encoder: {}", e))?;
+
+        let needs_bgra = self.needs_bgra();
+        let formats = FormatPlan::negotiate(self.capture.as_mut(), new_encoder.as_ref(), needs_bgra, self.color);
+        let encode_width = self.pre_scaler.dst_width;
+        let encode_height = self.pre_scaler.dst_height;
+        let enc_config = EncoderConfig {
+            width: encode_width,
+            height: encode_height,
+            fps: SIMPLE_FPS,
+            bitrate: self.bitrate,
+            max_bitrate: self.tuning.max_bitrate(self.bitrate),
+            keyframe_interval: self.tuning.keyframe_interval(SIMPLE_FPS),
+            preset: self.tuning.preset,
+            input_format: formats.encoder,
+            crf: near_lossless.then_some(encoder::NEAR_LOSSLESS_CRF),
+            color_space: self.color,
+        };
+        if let Err(e) = new_encoder.init(enc_config) {
+            // Keep the old encoder, with formats matching the new scaler
+            self.formats = FormatPlan::negotiate(self.capture.as_mut(), self.encoder.as_ref(), needs_bgra, self.color);
+            return Err(format!("Failed to init encoder: {}", e));
+        }
+
+        self.encoder = new_encoder;
+        self.formats = formats;
+        self.damage.reset();
+        self.encode_width = encode_width;
+        self.encode_height = encode_height;
+        self.congestion.reset(self.bitrate);
+        Ok(())
+    }
+
+    /// Feed the worst high ladder link to the rate controller and apply
+    /// its decision to the main encoder
+    fn adapt_to_link(&mut self, sample: LinkSample) {
+        let Some(decision) = self.congestion.update(sample) else {
+            return;
+        };
+        log::info!("[SIMPLE] Congestion control: {} bps @ {} fps (rtt {:?}, loss {:.1}%, queued {})",
+            decision.bitrate, decision.fps, sample.rtt, sample.loss * 100.0, sample.queued_frames);
+        if let Err(e) = self.encoder.set_bitrate(decision.bitrate) {
+            log::warn!("[SIMPLE] Failed to set bitrate: {}", e);
+        }
+    }
+
+    /// Time between frames at the controller's current frame rate
+    fn frame_interval(&self) -> Duration {
+        Duration::from_micros(1_000_000 / self.congestion.current().fps.max(1) as u64)
+    }
+
+    /// Start capturing the current source (window, region or display)
+    fn start_source(&mut self) -> Result<(), CaptureError> {
+        match (self.window_id, self.region) {
+            (Some(window_id), _) => self.capture.start_window(window_id).map(|_| ()),
+            (None, Some(r)) => self.capture.start_region(self.display_id, r.x, r.y, r.width, r.height).map(|_| ()),
+            (None, None) => self.capture.start(self.display_id),
+        }
+    }
+
+    /// Apply the recovery policy to a capture error. `Ok` means the frame is
+    /// simply skipped; a fatal error ends sharing through the `active` flag.
+    fn recover(&mut self, err: CaptureError) -> Result<(), String> {
+        match self.recovery.on_error(&err) {
+            RecoveryAction::Skip => Ok(()),
+            RecoveryAction::Restart => {
+                let _ = self.capture.stop();
+                if let Err(e) = self.start_source() {
+                    log::warn!("[SIMPLE] Failed to restart capture: {}", e);
+                    if self.recovery.on_error(&e) == RecoveryAction::Stop {
+                        self.active.store(false, Ordering::SeqCst);
+                    }
+                }
+                self.damage.reset();
+                self.encoder.request_keyframe();
+                Err(format!("Capture: {}", err))
+            }
+            RecoveryAction::Stop => {
+                self.active.store(false, Ordering::SeqCst);
+                Err(format!("Capture: {}", err))
+            }
+        }
+    }
+
+    /// Move capture to another display, keeping the viewer's resolution and
+    /// bitrate. A region share becomes a full-display share, since the region
+    /// belongs to the old display.
+    fn switch_display(&mut self, display_id: u32) -> Result<(), String> {
+        let displays = self.capture.get_displays()
+            .map_err(|e| format!("Failed to get displays: {}", e))?;
+        let display = displays.iter()
+            .find(|d| d.id == display_id)
+            .ok_or_else(|| format!("Display {} not found", display_id))?;
+
+        let _ = self.capture.stop();
+        if let Err(e) = self.capture.start(display_id) {
+            // Go back to the old source so the session keeps running
+            if let Err(restore_err) = self.start_source() {
+                log::error!("[SIMPLE] Failed to restore display {}: {}", self.display_id, restore_err);
+            }
+            return Err(format!("Failed to start capture on display {}: {}", display_id, e));
+        }
+
+        self.display_id = display_id;
+        self.window_id = None;
+        self.region = None;
+        if self.redactor.is_some() {
+            self.redactor = Some(NotificationRedactor::for_display(
+                display.width, display.height, display.scale_factor));
+        }
+        if self.cursor.is_some() {
+            self.cursor = Some(CursorOverlay::new(display.scale_factor));
+        }
+        self.pre_scaler = FrameScaler::new_with_target(
+            display.width, display.height, self.target_width, self.target_height);
+        self.rebuild_encoder()
+    }
+
+    /// Rescale to a new target resolution and bitrate
+    fn set_target(&mut self, target_width: u32, target_height: u32, bitrate: u32, near_lossless: bool) -> Result<(), String> {
+        let src_w = self.pre_scaler.src_width;
+        let src_h = self.pre_scaler.src_height;
+        self.pre_scaler = FrameScaler::new_with_target(src_w, src_h, target_width, target_height);
+        self.target_width = target_width;
+        self.target_height = target_height;
+        self.bitrate = bitrate;
+        self.near_lossless = near_lossless;
+        self.rebuild_encoder()
+    }
+
+    /// Start (or restart) the low ladder from the current source
+    fn open_low(&mut self, bitrate: u32) -> Result<(), String> {
+        let pre_scaler = FrameScaler::new_with_target(
+            self.pre_scaler.src_width,
+            self.pre_scaler.src_height,
+            LOW_TARGET_WIDTH.min(self.encode_width),
+            LOW_TARGET_HEIGHT.min(self.encode_height),
+        );
+        let mut encoder = encoder::create_encoder_for(self.codec)
+            .map_err(|e| format!("Failed to create low ladder encoder: {}", e))?;
+        let formats = FormatPlan::negotiate(self.capture.as_mut(), encoder.as_ref(), true, self.color);
+        let enc_config = EncoderConfig {
+            width: pre_scaler.dst_width,
+            height: pre_scaler.dst_height,
+            fps: SIMPLE_FPS,
+            bitrate,
+            max_bitrate: self.tuning.max_bitrate(bitrate),
+            keyframe_interval: self.tuning.keyframe_interval(SIMPLE_FPS),
+            preset: self.tuning.preset,
+            input_format: formats.encoder,
+            crf: None,
+            color_space: self.color,
+        };
+        encoder.init(enc_config)
+            .map_err(|e| format!("Failed to init low ladder encoder: {}", e))?;
+
+        log::info!("[SIMPLE] Low ladder: {}x{} @ {} bps", pre_scaler.dst_width, pre_scaler.dst_height, bitrate);
+        self.low = Some(LowLadder {
+            encode_width: pre_scaler.dst_width,
+            encode_height: pre_scaler.dst_height,
+            pre_scaler,
+            encoder,
+            formats,
+            bitrate,
+        });
+        // Both encoders now scale the same BGRA frame
+        self.formats = FormatPlan::negotiate(self.capture.as_mut(), self.encoder.as_ref(), true, self.color);
+        Ok(())
+    }
+
+    /// Stop the low ladder once no viewer uses it
+    fn close_low(&mut self) {
+        if self.low.take().is_some() {
+            log::info!("[SIMPLE] Low ladder closed");
+            let needs_bgra = self.needs_bgra();
+            self.formats = FormatPlan::negotiate(self.capture.as_mut(), self.encoder.as_ref(), needs_bgra, self.color);
+        }
+    }
+
+    fn request_keyframe(&mut self, ladder: Ladder) {
+        match (ladder, self.low.as_mut()) {
+            (Ladder::Low, Some(low)) => low.encoder.request_keyframe(),
+            _ => self.encoder.request_keyframe(),
+        }
+    }
+}
+
 // Safety: SharerState is only accessed from one thread at a time
 unsafe impl Send for SharerState {}
 unsafe impl Sync for SharerState {}
 
-static SHARER_STATE: once_cell::sync::Lazy<RwLock<Option<SharerState>>> =
+/// Frames buffered per viewer (one second); a viewer further behind skips
+/// ahead to the next keyframe
+const FANOUT_CAPACITY: usize = SIMPLE_FPS as usize;
+
+/// Output of the encode loop, shared by every viewer task
+#[derive(Clone)]
+enum Outgoing {
+    /// Encoder reconfigured: viewers on `ladder` reinit their decoder. With
+    /// `viewer` set, only that viewer (which just switched ladders).
+    Start { ladder: Ladder, width: u32, height: u32, codec: Codec, color: ColorSpace, viewer: Option<u64> },
+    /// Encoded frame message, serialized once for all viewers of the ladder
+    Frame { ladder: Ladder, message: Arc<Vec<u8>>, keyframe: bool },
+    Stop,
+}
+
+/// Viewer task → encode loop. Viewers are identified by a per-session id.
+enum ViewerCommand {
+    Joined(u64),
+    Left(u64),
+    /// A viewer's decoder lost frames and needs a keyframe
+    Keyframe(Ladder),
+    /// A viewer's own ladder choice; the fairness scheduler has the last word
+    Ladder { viewer: u64, ladder: Ladder },
+    /// What a viewer's link delivered recently
+    BitrateHint { viewer: u64, bps: u32 },
+    /// A viewer's connection stats over the last sample interval
+    Link { viewer: u64, sample: LinkSample },
+    /// How fast a viewer's decoder keeps up
+    Decode { viewer: u64, report: DecodeReport },
+    Resolution { target_width: u32, target_height: u32, bitrate: u32, near_lossless: bool },
+    Display(u32),
+    /// Encoder settings changed on the sharer
+    Tuning(EncoderTuning),
+}
+
+/// What the encode loop knows about a viewer
+struct ViewerInfo {
+    /// Ladder the viewer receives
+    ladder: Ladder,
+    /// Ladder the viewer asked for
+    requested: Ladder,
+    bitrate_hint: Option<u32>,
+    /// Latest link sample, consumed by the rate controller
+    link: Option<LinkSample>,
+    /// Latest decoder report, if the viewer sends them
+    decode: Option<DecodeReport>,
+}
+
+/// Ids handed to viewer tasks
+static NEXT_VIEWER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Connects the encode loop with the viewer tasks
+struct Fanout {
+    frames: broadcast::Sender<Outgoing>,
+    control: mpsc::UnboundedSender<ViewerCommand>,
+    /// Current high ladder encode size, sent to viewers as they join
+    encode_size: Mutex<(u32, u32)>,
+    codec: Codec,
+    color: ColorSpace,
+    /// Foreground window title while a whole display is shared, empty if unknown
+    window_title: watch::Sender<String>,
+    /// Pointer on the shared area, if it goes beside the video
+    pointer: watch::Sender<Option<PointerState>>,
+    /// Annotation messages with the viewer they came from (0: the sharer)
+    annotations: broadcast::Sender<(u64, Arc<Vec<u8>>)>,
+    /// Annotation messages since the last clear, for viewers who join later
+    annotation_log: Mutex<Vec<Arc<Vec<u8>>>>,
+    /// Peer of each running viewer task, by viewer id
+    viewers: Mutex<HashMap<u64, String>>,
+    /// Viewer tasks the sharer kicked, to end on their next pass
+    kicked: Mutex<HashSet<u64>>,
+}
+
+impl Fanout {
+    /// Pass a shape from `origin` on to the other viewers
+    fn annotate(&self, origin: u64, annotation: &crate::renderer::Annotation) {
+        let message = Arc::new(encode_annotation_message(annotation));
+        let mut log = self.annotation_log.lock();
+        if *annotation == crate::renderer::Annotation::Clear {
+            log.clear();
+        } else {
+            if log.len() >= MAX_HISTORY {
+                log.remove(0);
+            }
+            log.push(message.clone());
+        }
+        let _ = self.annotations.send((origin, message));
+    }
+}
+
+/// Whether `peer_ip` may start watching under a limit of `max_viewers`.
+/// A peer that is already watching (e.g. reconnecting) is let in again.
+fn has_room_for(viewers: &HashMap<u64, String>, peer_ip: &str, max_viewers: u32) -> bool {
+    let peers: std::collections::HashSet<&str> = viewers.values().map(String::as_str).collect();
+    peers.contains(peer_ip) || peers.len() < max_viewers as usize
+}
+
+static FANOUT: once_cell::sync::Lazy<RwLock<Option<Arc<Fanout>>>> =
     once_cell::sync::Lazy::new(|| RwLock::new(None));
 
-/// Handle a SimpleScreenRequest from a viewer - starts streaming to them
+/// Decode a viewer → sharer request read from the viewer stream
+fn parse_viewer_request(data: &[u8]) -> Option<ViewerCommand> {
+    let be_u32 = |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+    match *data.first()? {
+        MSG_TYPE_RESOLUTION_REQUEST if data.len() >= 13 => Some(ViewerCommand::Resolution {
+            target_width: be_u32(1),
+            target_height: be_u32(5),
+            bitrate: be_u32(9),
+            // Viewers that predate the text mode send no flags byte
+            near_lossless: data.get(13).is_some_and(|flags| flags & RESOLUTION_FLAG_NEAR_LOSSLESS != 0),
+        }),
+        MSG_TYPE_DISPLAY_REQUEST if data.len() >= 5 => Some(ViewerCommand::Display(be_u32(1))),
+        // The viewer task fills in the viewer's ladder
+        MSG_TYPE_KEYFRAME_REQUEST => Some(ViewerCommand::Keyframe(Ladder::High)),
+        // ... and its id
+        MSG_TYPE_DECODE_REPORT if data.len() >= 13 => Some(ViewerCommand::Decode {
+            viewer: 0,
+            report: DecodeReport {
+                width: be_u32(1),
+                height: be_u32(5),
+                fps: u16::from_be_bytes([data[9], data[10]]),
+                error_permille: u16::from_be_bytes([data[11], data[12]]),
+            },
+        }),
+        _ => None,
+    }
+}
+
+/// Tell the viewers of a ladder (or just `viewer`) its encode dimensions
+fn announce_start(state: &SharerState, fanout: &Fanout, ladder: Ladder, viewer: Option<u64>) {
+    let (width, height) = match (ladder, state.low.as_ref()) {
+        (Ladder::Low, Some(low)) => (low.encode_width, low.encode_height),
+        _ => {
+            *fanout.encode_size.lock() = (state.encode_width, state.encode_height);
+            (state.encode_width, state.encode_height)
+        }
+    };
+    let ladder = if state.low.is_some() { ladder } else { Ladder::High };
+    let _ = fanout.frames.send(Outgoing::Start { ladder, width, height, codec: state.codec, color: state.color, viewer });
+}
+
+/// Low ladder bitrate for the slowest low viewer's link, with some margin,
+/// within the scheduler's share of the uplink cap
+fn low_bitrate(state: &SharerState, viewers: &HashMap<u64, ViewerInfo>) -> u32 {
+    let slowest = viewers
+        .values()
+        .filter(|v| v.ladder == Ladder::Low)
+        .filter_map(|v| v.bitrate_hint)
+        .min();
+    let max = LOW_BITRATE_MAX
+        .min(state.bitrate / 2)
+        .min(state.low_bitrate_cap.unwrap_or(u32::MAX))
+        .max(LOW_BITRATE_MIN);
+    slowest
+        .map(|bps| (bps as u64 * 7 / 10) as u32)
+        .unwrap_or(max)
+        .clamp(LOW_BITRATE_MIN, max)
+}
+
+/// Move every low viewer back to the high ladder (the low encoder failed)
+fn demote_low_viewers(state: &SharerState, viewers: &mut HashMap<u64, ViewerInfo>, fanout: &Fanout) {
+    for (&id, info) in viewers.iter_mut().filter(|(_, v)| v.ladder == Ladder::Low) {
+        info.ladder = Ladder::High;
+        announce_start(state, fanout, Ladder::High, Some(id));
+    }
+}
+
+/// After the high ladder changed source or size, rebuild the low ladder to
+/// match and tell its viewers
+fn reopen_low(state: &mut SharerState, viewers: &mut HashMap<u64, ViewerInfo>, fanout: &Fanout) {
+    if state.low.is_none() {
+        return;
+    }
+    let bitrate = low_bitrate(state, viewers);
+    state.low = None;
+    match state.open_low(bitrate) {
+        Ok(()) => announce_start(state, fanout, Ladder::Low, None),
+        Err(e) => {
+            log::error!("[SIMPLE] {}", e);
+            state.close_low();
+            demote_low_viewers(state, viewers, fanout);
+        }
+    }
+}
+
+/// Move one viewer to `ladder`, starting or stopping the low encoder as
+/// needed, and send that viewer its new start message
+fn assign_ladder(
+    state: &mut SharerState,
+    viewers: &mut HashMap<u64, ViewerInfo>,
+    fanout: &Fanout,
+    viewer: u64,
+    ladder: Ladder,
+) {
+    let Some(info) = viewers.get_mut(&viewer) else {
+        return;
+    };
+    info.ladder = ladder;
+    log::info!("[SIMPLE] Viewer {} moved to the {:?} ladder", viewer, ladder);
+
+    if ladder == Ladder::Low && state.low.is_none() {
+        let bitrate = low_bitrate(state, viewers);
+        if let Err(e) = state.open_low(bitrate) {
+            log::error!("[SIMPLE] {}", e);
+            demote_low_viewers(state, viewers, fanout);
+            return;
+        }
+    } else {
+        state.damage.reset();
+        state.request_keyframe(ladder);
+    }
+    if !viewers.values().any(|v| v.ladder == Ladder::Low) {
+        state.close_low();
+    }
+    announce_start(state, fanout, ladder, Some(viewer));
+}
+
+/// Follow the viewers' link hints and the uplink cap with the low ladder's bitrate
+fn retune_low(state: &mut SharerState, viewers: &HashMap<u64, ViewerInfo>) {
+    let target = low_bitrate(state, viewers);
+    if let Some(low) = state.low.as_mut() {
+        // Only follow changes of more than 10%
+        if target.abs_diff(low.bitrate) > low.bitrate / 10 {
+            match low.encoder.set_bitrate(target) {
+                Ok(()) => {
+                    log::info!("[SIMPLE] Low ladder bitrate {} -> {} bps", low.bitrate, target);
+                    low.bitrate = target;
+                }
+                Err(e) => log::warn!("[SIMPLE] Failed to set low ladder bitrate: {}", e),
+            }
+        }
+    }
+}
+
+/// Run the fairness scheduler over every viewer's own ladder choice and
+/// apply the ladders it assigns
+fn reschedule(state: &mut SharerState, viewers: &mut HashMap<u64, ViewerInfo>, fanout: &Fanout) {
+    let fps = state.congestion.current().fps;
+    let demands: Vec<fairness::Demand> = viewers
+        .iter()
+        .map(|(&viewer, v)| fairness::Demand {
+            viewer,
+            requested: v.requested,
+            link_bps: v.bitrate_hint,
+            decodes_high: v.decode.is_none_or(|d| d.fits(state.encode_width, state.encode_height, fps)),
+        })
+        .collect();
+    let high_bps = state.congestion.current().bitrate;
+    let low_bps = state.low.as_ref().map_or_else(|| low_bitrate(state, viewers), |low| low.bitrate);
+    let plan = fairness::schedule(&demands, high_bps, low_bps, crate::commands::uplink_cap_bps());
+
+    if plan.low_bitrate_cap != state.low_bitrate_cap {
+        state.low_bitrate_cap = plan.low_bitrate_cap;
+        retune_low(state, viewers);
+    }
+    for (viewer, ladder) in plan.ladders {
+        if viewers.get(&viewer).is_some_and(|v| v.ladder != ladder) {
+            assign_ladder(state, viewers, fanout, viewer, ladder);
+        }
+    }
+}
+
+/// Apply a viewer command to the shared encoders
+fn apply_command(
+    state: &mut SharerState,
+    cmd: ViewerCommand,
+    viewers: &mut HashMap<u64, ViewerInfo>,
+    fanout: &Fanout,
+) {
+    match cmd {
+        ViewerCommand::Joined(viewer) => {
+            viewers.insert(viewer, ViewerInfo {
+                ladder: Ladder::High,
+                requested: Ladder::High,
+                bitrate_hint: None,
+                link: None,
+                decode: None,
+            });
+            // The new viewer's decoder starts from a keyframe, sent right away
+            state.damage.reset();
+            state.encoder.request_keyframe();
+            log::info!("[SIMPLE] Viewer {} joined, {} watching", viewer, viewers.len());
+            reschedule(state, viewers, fanout);
+        }
+        ViewerCommand::Left(viewer) => {
+            viewers.remove(&viewer);
+            log::info!("[SIMPLE] Viewer {} left, {} watching", viewer, viewers.len());
+            // Freed uplink may let others back onto the high ladder
+            reschedule(state, viewers, fanout);
+            if !viewers.values().any(|v| v.ladder == Ladder::Low) {
+                state.close_low();
+            }
+            // Go back to the default 720p for the next viewer
+            let is_default = state.target_width == SIMPLE_TARGET_WIDTH
+                && state.target_height == SIMPLE_TARGET_HEIGHT
+                && state.bitrate == SIMPLE_BITRATE
+                && !state.near_lossless;
+            if viewers.is_empty() && !is_default {
+                match state.set_target(SIMPLE_TARGET_WIDTH, SIMPLE_TARGET_HEIGHT, SIMPLE_BITRATE, false) {
+                    Ok(()) => announce_start(state, fanout, Ladder::High, None),
+                    Err(e) => log::error!("[SIMPLE] Failed to reset encoder for next viewer: {}", e),
+                }
+            }
+        }
+        ViewerCommand::Keyframe(ladder) => state.request_keyframe(ladder),
+        ViewerCommand::Ladder { viewer, ladder } => {
+            let Some(info) = viewers.get_mut(&viewer) else {
+                return;
+            };
+            info.requested = ladder;
+            let before = info.ladder;
+            reschedule(state, viewers, fanout);
+
+            // Kept where it was: the viewer still waits for its start message
+            if viewers.get(&viewer).is_some_and(|v| v.ladder == before) {
+                log::info!("[SIMPLE] Viewer {} asked for the {:?} ladder, kept on {:?}", viewer, ladder, before);
+                state.request_keyframe(before);
+                announce_start(state, fanout, before, Some(viewer));
+            }
+        }
+        ViewerCommand::BitrateHint { viewer, bps } => {
+            if let Some(info) = viewers.get_mut(&viewer) {
+                info.bitrate_hint = Some(bps);
+            }
+            retune_low(state, viewers);
+        }
+        ViewerCommand::Link { viewer, sample } => {
+            if let Some(info) = viewers.get_mut(&viewer) {
+                info.link = Some(sample);
+            }
+        }
+        ViewerCommand::Decode { viewer, report } => {
+            let Some(info) = viewers.get_mut(&viewer) else {
+                return;
+            };
+            let fits = |d: &DecodeReport| {
+                d.fits(state.encode_width, state.encode_height, state.congestion.current().fps)
+            };
+            if info.decode.as_ref().map(fits) != Some(fits(&report)) {
+                log::info!("[SIMPLE] Viewer {} decodes {}x{} at {} fps ({}‰ errors), {} the high ladder",
+                    viewer, report.width, report.height, report.fps, report.error_permille,
+                    if fits(&report) { "keeps up with" } else { "cannot keep up with" });
+            }
+            info.decode = Some(report);
+            // Applied on the next reschedule, together with the link samples
+        }
+        ViewerCommand::Resolution { target_width, target_height, bitrate, near_lossless } => {
+            log::info!("[SIMPLE] Resolution change requested: {}x{} @ {} bps{}", target_width, target_height, bitrate,
+                if near_lossless { " (near-lossless)" } else { "" });
+            match state.set_target(target_width, target_height, bitrate, near_lossless) {
+                Ok(()) => {
+                    log::info!("[SIMPLE] Encoder reconfigured: {}x{} @ {} bps",
+                        state.encode_width, state.encode_height, bitrate);
+                    announce_start(state, fanout, Ladder::High, None);
+                    reopen_low(state, viewers, fanout);
+                }
+                Err(e) => log::error!("[SIMPLE] Failed to reconfigure encoder: {}", e),
+            }
+        }
+        ViewerCommand::Display(display_id) => {
+            log::info!("[SIMPLE] Display switch requested: {} -> {}", state.display_id, display_id);
+            if display_id == state.display_id && state.region.is_none() && state.window_id.is_none() {
+                log::info!("[SIMPLE] Already capturing display {}", display_id);
+            } else if let Err(e) = state.switch_display(display_id) {
+                log::error!("[SIMPLE] Display switch failed: {}", e);
+            } else {
+                // Same streams and windows: viewers just reinit their decoder
+                log::info!("[SIMPLE] Switched to display {} ({}x{})",
+                    display_id, state.encode_width, state.encode_height);
+                announce_start(state, fanout, Ladder::High, None);
+                reopen_low(state, viewers, fanout);
+            }
+        }
+        ViewerCommand::Tuning(tuning) => {
+            if tuning == state.tuning {
+                return;
+            }
+            log::info!("[SIMPLE] Encoder settings changed: {:?}", tuning);
+            state.tuning = tuning;
+            match state.rebuild_encoder() {
+                Ok(()) => {
+                    announce_start(state, fanout, Ladder::High, None);
+                    reopen_low(state, viewers, fanout);
+                }
+                Err(e) => log::error!("[SIMPLE] Failed to apply encoder settings: {}", e),
+            }
+        }
+    }
+}
+
+/// Capture and encode once for all viewers until sharing stops. While
+/// nobody watches, the loop only waits for viewer commands.
+async fn encode_loop(
+    mut state: SharerState,
+    mut control: mpsc::UnboundedReceiver<ViewerCommand>,
+    fanout: Arc<Fanout>,
+) {
+    let mut viewers: HashMap<u64, ViewerInfo> = HashMap::new();
+    let mut sequence: u32 = 0;
+    // One per ladder; each encoder's output is its own stream
+    let mut validators = (BitstreamValidator::new(), BitstreamValidator::new());
+    let mut last_frame_time = std::time::Instant::now();
+    let mut last_rate_control = std::time::Instant::now();
+    let mut last_title_check = std::time::Instant::now();
+
+    log::info!("[SIMPLE] Encode loop started at {} fps", SIMPLE_FPS);
+
+    loop {
+        if viewers.is_empty() {
+            let cmd = tokio::select! {
+                cmd = control.recv() => cmd,
+                _ = state.stop_rx.recv() => None,
+            };
+            match cmd {
+                Some(cmd) => tokio::task::block_in_place(|| apply_command(&mut state, cmd, &mut viewers, &fanout)),
+                None => break,
+            }
+            continue;
+        }
+
+        // Check stop signal
+        if state.stop_rx.try_recv().is_ok() || !state.active.load(Ordering::SeqCst) {
+            log::info!("[SIMPLE] Stop signal received, ending stream");
+            break;
+        }
+
+        // Apply viewer commands (joins, resolution and display changes)
+        while let Ok(cmd) = control.try_recv() {
+            tokio::task::block_in_place(|| apply_command(&mut state, cmd, &mut viewers, &fanout));
+        }
+        if viewers.is_empty() {
+            continue;
+        }
+
+        // A recording that just started begins with a keyframe
+        if recording::keyframe_wanted() {
+            state.encoder.request_keyframe();
+        }
+
+        // Ladders are assigned first so a slow link moves to the low ladder
+        // instead of holding the main encoder back for everyone else
+        if last_rate_control.elapsed() >= congestion::SAMPLE_INTERVAL {
+            last_rate_control = std::time::Instant::now();
+            tokio::task::block_in_place(|| reschedule(&mut state, &mut viewers, &fanout));
+            let worst = viewers
+                .values_mut()
+                .filter(|v| v.ladder == Ladder::High)
+                .filter_map(|v| v.link.take())
+                .reduce(LinkSample::worst);
+            if let Some(sample) = worst {
+                state.adapt_to_link(sample);
+            }
+        }
+
+        // Window titles outside a shared window or region are not the
+        // viewers' business
+        if last_title_check.elapsed() >= TITLE_INTERVAL && state.window_id.is_none() && state.region.is_none() {
+            last_title_check = std::time::Instant::now();
+            let title = tokio::task::block_in_place(focus::foreground_window_title).unwrap_or_default();
+            fanout.window_title.send_if_modified(|current| {
+                let changed = *current != title;
+                *current = title;
+                changed
+            });
+        }
+
+        // Frame rate limiting, slower while the screen is static. The pointer
+        // goes out in between, so it moves smoothly either way.
+        let frame_interval = state.damage.capture_interval(state.frame_interval());
+        loop {
+            if state.pointer_channel {
+                let pointer = state.pointer();
+                fanout.pointer.send_if_modified(|current| {
+                    let changed = *current != pointer;
+                    *current = pointer;
+                    changed
+                });
+            }
+            let elapsed = last_frame_time.elapsed();
+            if elapsed >= frame_interval {
+                break;
+            }
+            tokio::time::sleep((frame_interval - elapsed).min(POINTER_INTERVAL)).await;
+        }
+        last_frame_time = std::time::Instant::now();
+
+        // Capture + scale + encode in block_in_place to avoid blocking tokio worker
+        let capture_result = tokio::task::block_in_place(|| {
+            let t0 = std::time::Instant::now();
+            state.update_roi();
+
+            // Zero-copy path: the frame stays on the GPU from capture to encoder
+            if state.use_gpu_path() {
+                let frame = match state.capture.capture_gpu_frame() {
+                    Ok(f) => f,
+                    Err(e) => return state.recover(e).map(|_| None),
+                };
+                state.recovery.on_success();
+                let t_capture = t0.elapsed();
+                timing::record(Stage::Capture, t_capture);
+
+                let changed = state.capture.last_frame_changed() != Some(false);
+                let damage = Damage { changed_tiles: changed as usize, total_tiles: 1 };
+                match state.damage.decide(&damage) {
+                    FrameAction::Skip => return Ok(None),
+                    FrameAction::Refresh => state.encoder.request_keyframe(),
+                    FrameAction::Encode => {}
+                }
+
+                return match state.encoder.encode_gpu(&frame) {
+                    Ok(encoded) => {
+                        let t_encode = t0.elapsed() - t_capture;
+                        timing::record(Stage::Encode, t_encode);
+                        stats::frame_encoded(encoded.data.len(), t_encode);
+                        if sequence < 10 || sequence % 50 == 0 {
+                            log::info!("[SIMPLE] Frame {} GPU capture+encode={:.1}ms",
+                                sequence, t0.elapsed().as_secs_f64() * 1000.0);
+                        }
+                        Ok(Some((frame.timestamp, encoded, None)))
+                    }
+                    Err(e) => {
+                        log::warn!("[SIMPLE] GPU encode failed, falling back to CPU path: {}", e);
+                        state.gpu_disabled = true;
+                        state.encoder.request_keyframe();
+                        Err(format!("Encode: {}", e))
+                    }
+                };
+            }
+
+            let mut frame = match state.capture.capture_frame() {
+                Ok(f) => {
+                    state.recovery.on_success();
+                    state.formats.prepare(f)
+                }
+                Err(e) => return state.recover(e).map(|_| None),
+            };
+
+            if let Some(ref redactor) = state.redactor {
+                redactor.apply(&mut frame);
+            }
+            if let Some(ref cursor) = state.cursor {
+                if let Some((x, y)) = state.capture.cursor_position() {
+                    cursor.draw(&mut frame, x, y);
+                }
+            }
+
+            // Crop to the shared region before scaling
+            let frame = match state.region {
+                Some(region) => region.crop(frame),
+                None => frame,
+            };
+            if let Some(ref mut archive) = state.archive {
+                archive.offer(&frame);
+            }
+
+            // Skip unchanged frames on a static desktop (the backend hint is
+            // ignored when we draw the cursor ourselves, since it may have moved)
+            let damage = if state.cursor.is_none() && state.capture.last_frame_changed() == Some(false) {
+                Damage { changed_tiles: 0, total_tiles: 0 }
+            } else {
+                state.damage.update(&frame)
+            };
+            match state.damage.decide(&damage) {
+                FrameAction::Skip => {
+                    frame.recycle();
+                    return Ok(None);
+                }
+                FrameAction::Refresh => {
+                    state.encoder.request_keyframe();
+                    if let Some(low) = state.low.as_mut() {
+                        low.encoder.request_keyframe();
+                    }
+                }
+                FrameAction::Encode => {}
+            }
+            let t_capture = t0.elapsed();
+
+            // Capture time, so viewers can measure glass-to-glass latency
+            let timestamp = frame.timestamp;
+
+            // Downscale before encoding (e.g. 3456x2160 → 1280x720), on the
+            // GPU when it is available
+            let (result, t_scale) = match state.gpu_preprocess(&frame) {
+                Some(input) => {
+                    let t_scale = t0.elapsed();
+                    let result = state.encoder.encode(&input, timestamp);
+                    pool::recycle(input);
+                    (result, t_scale)
+                }
+                None => {
+                    let scaled_data = state.pre_scaler.scale(&frame.data);
+                    let t_scale = t0.elapsed();
+                    let input = state.formats.for_encoder(
+                        &scaled_data, state.encode_width, state.encode_height, frame.format);
+                    let result = state.encoder.encode(&input, timestamp);
+                    pool::recycle_cow(input);
+                    pool::recycle_cow(scaled_data);
+                    (result, t_scale)
+                }
+            };
+
+            // Low ladder from the same captured frame
+            let low_encoded = state.low.as_mut().and_then(|low| {
+                let scaled = low.pre_scaler.scale(&frame.data);
+                let input = low.formats.for_encoder(&scaled, low.encode_width, low.encode_height, frame.format);
+                let result = low.encoder.encode(&input, timestamp);
+                pool::recycle_cow(input);
+                pool::recycle_cow(scaled);
+                result
+                    .map_err(|e| log::warn!("[SIMPLE] Low ladder encode failed: {}", e))
+                    .ok()
+            });
+
+            // Hand the frame's buffers back for the next capture
+            frame.recycle();
+
+            let encoded = match result {
+                Ok(e) => e,
+                Err(e) => {
+                    return Err(format!("Encode: {}", e));
+                }
+            };
+            let t_encode = t0.elapsed();
+            timing::record(Stage::Capture, t_capture);
+            timing::record(Stage::Scale, t_scale - t_capture);
+            timing::record(Stage::Encode, t_encode - t_scale);
+            stats::frame_encoded(encoded.data.len(), t_encode - t_scale);
+
+            if sequence < 10 || sequence % 50 == 0 {
+                log::info!("[SIMPLE] Frame {} timing: capture={:.1}ms scale={:.1}ms encode={:.1}ms total={:.1}ms",
+                    sequence,
+                    t_capture.as_secs_f64() * 1000.0,
+                    (t_scale - t_capture).as_secs_f64() * 1000.0,
+                    (t_encode - t_scale).as_secs_f64() * 1000.0,
+                    t_encode.as_secs_f64() * 1000.0,
+                );
+            }
+
+            Ok(Some((timestamp, encoded, low_encoded)))
+        });
+
+        let (timestamp, encoded, low_encoded) = match capture_result {
+            Ok(Some(r)) => r,
+            Ok(None) => continue, // nothing changed since the last frame
+            Err(e) => {
+                if sequence < 10 || sequence % 50 == 0 {
+                    log::warn!("[SIMPLE] Frame {} error: {}", sequence, e);
+                }
+                continue;
+            }
+        };
+
+        if sequence < 10 || sequence % 50 == 0 {
+            log::info!("[SIMPLE] Frame {} encoded: {} bytes, type={:?}",
+                sequence, encoded.data.len(), encoded.frame_type);
+        }
+
+        // Serialize once per ladder and hand the frames to every viewer task.
+        // Empty frames (encoder buffering, e.g. B-frame reordering) are skipped.
+        let ladders = std::iter::once((Ladder::High, encoded)).chain(low_encoded.map(|e| (Ladder::Low, e)));
+        let validate = state.codec == Codec::H264 && crate::commands::bitstream_validation_enabled();
+        for (ladder, mut encoded) in ladders.filter(|(_, e)| !e.data.is_empty()) {
+            if validate {
+                let (validator, encoder_name) = match ladder {
+                    Ladder::High => (&mut validators.0, state.encoder.info()),
+                    Ladder::Low => (&mut validators.1, state.low.as_ref().map_or("low ladder", |low| low.encoder.info())),
+                };
+                encoded.data = validator.process(std::mem::take(&mut encoded.data), encoder_name);
+            }
+            let keyframe = matches!(encoded.frame_type, FrameType::KeyFrame);
+            if ladder == Ladder::High && state.codec == Codec::H264 {
+                recording::record_shared(state.display_id, &encoded.data, keyframe, state.encode_width, state.encode_height);
+            }
+            let message = Arc::new(encode_frame_message(timestamp, &encoded.data));
+            let _ = fanout.frames.send(Outgoing::Frame { ladder, message, keyframe });
+        }
+
+        sequence += 1;
+    }
+
+    // Viewer tasks send the stop message on their streams
+    let _ = fanout.frames.send(Outgoing::Stop);
+    let _ = state.capture.stop();
+    {
+        // Sharing may already have been restarted with a new loop
+        let mut current = FANOUT.write();
+        if current.as_ref().is_some_and(|f| Arc::ptr_eq(f, &fanout)) {
+            *current = None;
+            SIMPLE_SHARER_ACTIVE.store(false, Ordering::SeqCst);
+            crate::app_state::changed(Slice::Sharing);
+        }
+    }
+    log::info!("[SIMPLE] Streaming ended after {} frames", sequence);
+}
+
+/// Handle a SimpleScreenRequest from a viewer: open a stream to them and
+/// forward the shared encoder's output until they leave or sharing stops
 pub async fn handle_viewer_request(peer_ip: &str) {
     log::info!("[SIMPLE] === Received viewer request from {} ===", peer_ip);
 
-    if !SIMPLE_SHARER_ACTIVE.load(Ordering::SeqCst) {
+    let fanout = FANOUT.read().clone();
+    let Some(fanout) = fanout.filter(|_| SIMPLE_SHARER_ACTIVE.load(Ordering::SeqCst)) else {
         log::warn!("[SIMPLE] Not sharing, ignoring viewer request from {}", peer_ip);
         return;
+    };
+
+    let codec = crate::network::capability::Capability::codec(fanout.codec);
+    if !crate::network::capability::peer_supports(peer_ip, codec) {
+        let reason = crate::network::protocol::ScreenRejectReason::UnsupportedCodec { codec: fanout.codec.name().to_string() };
+        if let Err(e) = crate::streaming::reject_viewer(peer_ip, 0, reason).await {
+            log::warn!("[SIMPLE] Failed to reject viewer {}: {}", peer_ip, e);
+        }
+        return;
+    }
+
+    if let Some(max_viewers) = crate::commands::max_viewers() {
+        if !has_room_for(&fanout.viewers.lock(), peer_ip, max_viewers) {
+            let reason = crate::network::protocol::ScreenRejectReason::StreamFull { max_viewers };
+            if let Err(e) = crate::streaming::reject_viewer(peer_ip, 0, reason).await {
+                log::warn!("[SIMPLE] Failed to reject viewer {}: {}", peer_ip, e);
+            }
+            return;
+        }
+    }
+
+    if !crate::network::access::admit_viewer(peer_ip).await {
+        let reason = crate::network::protocol::ScreenRejectReason::Declined;
+        if let Err(e) = crate::streaming::reject_viewer(peer_ip, 0, reason).await {
+            log::warn!("[SIMPLE] Failed to reject viewer {}: {}", peer_ip, e);
+        }
+        return;
+    }
+
+    // Take the place now, checked again: others may have been let in while
+    // the user was asked
+    let viewer_id = NEXT_VIEWER_ID.fetch_add(1, Ordering::Relaxed);
+    let full = {
+        let mut viewers = fanout.viewers.lock();
+        let full = crate::commands::max_viewers().filter(|&max| !has_room_for(&viewers, peer_ip, max));
+        if full.is_none() {
+            viewers.insert(viewer_id, peer_ip.to_string());
+        }
+        full
+    };
+    if let Some(max_viewers) = full {
+        let reason = crate::network::protocol::ScreenRejectReason::StreamFull { max_viewers };
+        if let Err(e) = crate::streaming::reject_viewer(peer_ip, 0, reason).await {
+            log::warn!("[SIMPLE] Failed to reject viewer {}: {}", peer_ip, e);
+        }
+        return;
     }
+    let give_up = || {
+        fanout.viewers.lock().remove(&viewer_id);
+    };
 
     // Find connection to the viewer
     let conn = match quic::find_connection(peer_ip) {
         Some(c) => c,
         None => {
             log::error!("[SIMPLE] No connection found for viewer {}", peer_ip);
+            give_up();
             return;
         }
     };
@@ -207,243 +1504,312 @@ pub async fn handle_viewer_request(peer_ip: &str) {
         Ok(s) => s,
         Err(e) => {
             log::error!("[SIMPLE] Failed to open stream to {}: {}", peer_ip, e);
+            give_up();
             return;
         }
     };
     log::info!("[SIMPLE] Opened persistent stream to viewer {}", peer_ip);
 
-    // Take the sharer state
-    let state_opt = SHARER_STATE.write().take();
-    let Some(mut state) = state_opt else {
-        log::error!("[SIMPLE] Sharer state not available");
-        return;
-    };
+    // Subscribe before joining so no frame after the join is missed
+    let mut frames = fanout.frames.subscribe();
 
     // Send ScreenStart as the FIRST message on this stream
-    let start_data = encode_start_message(state.encode_width, state.encode_height);
-    if let Err(e) = stream.send_framed(&start_data).await {
+    let (width, height) = *fanout.encode_size.lock();
+    if let Err(e) = stream.send_framed(&encode_start_message(width, height, fanout.codec, fanout.color)).await {
         log::error!("[SIMPLE] Failed to send ScreenStart: {}", e);
+        give_up();
         return;
     }
-    log::info!("[SIMPLE] Sent ScreenStart ({}x{}) to {}", state.encode_width, state.encode_height, peer_ip);
-
-    // Now stream frames on the SAME stream
-    let frame_interval = Duration::from_micros(1_000_000 / SIMPLE_FPS as u64);
-    let mut sequence: u32 = 0;
-    let mut last_frame_time = std::time::Instant::now();
-
-    log::info!("[SIMPLE] Starting frame streaming loop at {} fps", SIMPLE_FPS);
+    log::info!("[SIMPLE] Sent ScreenStart ({}x{}) to {} (viewer {})", width, height, peer_ip, viewer_id);
+    crate::streaming::audience::started(peer_ip, crate::streaming::audience::Stream::Simple(viewer_id));
+    let _ = fanout.control.send(ViewerCommand::Joined(viewer_id));
+
+    // Wake up at least once per frame interval to read viewer requests
+    let poll_interval = Duration::from_micros(1_000_000 / SIMPLE_FPS as u64);
+    let mut viewer = ViewerQueue {
+        id: viewer_id,
+        awaiting_start: false,
+        queue: SendQueue::new(QUEUE_FRAMES),
+        rate: ViewerRate::new(std::time::Instant::now()),
+        datagrams: false,
+        next_seq: 0,
+        fec: FecTuner::new(),
+    };
+    let mut sent: u32 = 0;
+    let mut dropped_reported: u64 = 0;
+    let mut link_stats = conn.link_stats();
+    let mut last_link_sample = std::time::Instant::now();
+    // The current title goes out with the first pass
+    let mut window_title = fanout.window_title.subscribe();
+    window_title.mark_changed();
+    let mut pointer = fanout.pointer.subscribe();
+    let mut pointer_sent: Option<PointerState> = None;
+    // Shapes drawn before this viewer joined go out first
+    let (mut annotations, mut pending_annotations) = {
+        let log = fanout.annotation_log.lock();
+        (fanout.annotations.subscribe(), log.clone())
+    };
 
-    loop {
-        // Check stop signal
-        if state.stop_rx.try_recv().is_ok() || !state.active.load(Ordering::SeqCst) {
-            log::info!("[SIMPLE] Stop signal received, ending stream");
+    'stream: loop {
+        if fanout.kicked.lock().remove(&viewer_id) {
+            log::info!("[SIMPLE] Viewer {} was kicked", peer_ip);
             break;
         }
 
-        // Check for resolution change request from viewer (non-blocking)
+        // Forward resolution and display requests to the encode loop
         match stream.try_recv_framed().await {
-            Ok(Some(req_data)) if req_data.len() >= 13 && req_data[0] == MSG_TYPE_RESOLUTION_REQUEST => {
-                let new_target_w = u32::from_be_bytes([req_data[1], req_data[2], req_data[3], req_data[4]]);
-                let new_target_h = u32::from_be_bytes([req_data[5], req_data[6], req_data[7], req_data[8]]);
-                let bitrate = u32::from_be_bytes([req_data[9], req_data[10], req_data[11], req_data[12]]);
-                log::info!("[SIMPLE] Resolution change requested: {}x{} @ {} bps", new_target_w, new_target_h, bitrate);
-
-                // Reconfigure scaler
-                let src_w = state.pre_scaler.src_width;
-                let src_h = state.pre_scaler.src_height;
-                state.pre_scaler = FrameScaler::new_with_target(src_w, src_h, new_target_w, new_target_h);
-                let new_encode_w = state.pre_scaler.dst_width;
-                let new_encode_h = state.pre_scaler.dst_height;
-
-                // Recreate encoder with new dimensions
-                match encoder::create_encoder() {
-                    Ok(mut new_encoder) => {
-                        let enc_config = EncoderConfig {
-                            width: new_encode_w,
-                            height: new_encode_h,
-                            fps: SIMPLE_FPS,
-                            bitrate,
-                            max_bitrate: bitrate * 2,
-                            keyframe_interval: SIMPLE_FPS,
-                            preset: EncoderPreset::UltraFast,
-                        };
-                        if let Err(e) = new_encoder.init(enc_config) {
-                            log::error!("[SIMPLE] Failed to reinit encoder: {}", e);
-                        } else {
-                            state.encoder = new_encoder;
-                            state.encode_width = new_encode_w;
-                            state.encode_height = new_encode_h;
-                            log::info!("[SIMPLE] Encoder reconfigured: {}x{} @ {} bps", new_encode_w, new_encode_h, bitrate);
-
-                            // Send new START message so viewer reinits decoder
-                            let start_data = encode_start_message(new_encode_w, new_encode_h);
-                            if let Err(e) = stream.send_framed(&start_data).await {
-                                log::error!("[SIMPLE] Failed to send new ScreenStart: {}", e);
-                                break;
-                            }
-                            log::info!("[SIMPLE] Sent new ScreenStart ({}x{}) after resolution change", new_encode_w, new_encode_h);
-                            sequence = 0;
+            Ok(Some(req_data)) if req_data.first() == Some(&MSG_TYPE_DATAGRAM_READY) => {
+                viewer.datagrams = conn.max_datagram_size().is_some();
+                log::info!("[SIMPLE] Viewer {} accepts datagrams: {}", peer_ip, viewer.datagrams);
+            }
+            Ok(Some(req_data)) if req_data.first() == Some(&MSG_TYPE_ANNOTATION) => {
+                if let Some(annotation) = parse_annotation_message(&req_data) {
+                    fanout.annotate(viewer_id, &annotation);
+                    emit_annotation(peer_ip, annotation);
+                }
+            }
+            Ok(Some(req_data)) => {
+                if let Some(cmd) = parse_viewer_request(&req_data) {
+                    let cmd = match cmd {
+                        ViewerCommand::Keyframe(_) => {
+                            log::debug!("[SIMPLE] Viewer {} asked for a keyframe", peer_ip);
+                            ViewerCommand::Keyframe(viewer.rate.ladder())
                         }
-                    }
-                    Err(e) => {
-                        log::error!("[SIMPLE] Failed to create new encoder: {}", e);
-                    }
+                        ViewerCommand::Decode { report, .. } => ViewerCommand::Decode { viewer: viewer_id, report },
+                        cmd => cmd,
+                    };
+                    let _ = fanout.control.send(cmd);
                 }
             }
-            Ok(Some(_)) => {} // unknown message from viewer, ignore
             Ok(None) => {} // no message ready
             Err(e) => {
                 log::debug!("[SIMPLE] Error reading from viewer: {}", e);
             }
         }
 
-        // Frame rate limiting
-        let elapsed = last_frame_time.elapsed();
-        if elapsed < frame_interval {
-            tokio::time::sleep(frame_interval - elapsed).await;
+        if window_title.has_changed().unwrap_or(false) {
+            let title = window_title.borrow_and_update().clone();
+            if !title.is_empty() {
+                if let Err(e) = stream.send_framed(&encode_metadata_message(&title)).await {
+                    log::debug!("[SIMPLE] Failed to send window title to {}: {}", peer_ip, e);
+                }
+            }
         }
-        last_frame_time = std::time::Instant::now();
-
-        // Capture + scale + encode in block_in_place to avoid blocking tokio worker
-        let capture_result = tokio::task::block_in_place(|| {
-            let t0 = std::time::Instant::now();
 
-            let frame = match state.capture.capture_frame() {
-                Ok(f) => f,
-                Err(e) => {
-                    return Err(format!("Capture: {}", e));
+        loop {
+            match annotations.try_recv() {
+                Ok((origin, message)) if origin != viewer_id => pending_annotations.push(message),
+                Ok(_) => {}
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    log::warn!("[SIMPLE] Viewer {} missed {} annotations", peer_ip, skipped);
                 }
-            };
-            let t_capture = t0.elapsed();
-
-            // Downscale before encoding (e.g. 3456x2160 → 1280x720)
-            let scaled_data = state.pre_scaler.scale(&frame.data);
-            let t_scale = t0.elapsed();
+                Err(_) => break,
+            }
+        }
+        for message in pending_annotations.drain(..) {
+            if let Err(e) = stream.send_framed(&message).await {
+                log::debug!("[SIMPLE] Failed to send annotation to {}: {}", peer_ip, e);
+            }
+        }
 
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_millis() as u64)
-                .unwrap_or(0);
+        let current = *pointer.borrow_and_update();
+        if current != pointer_sent {
+            pointer_sent = current;
+            if let Err(e) = stream.send_framed(&encode_pointer_message(current)).await {
+                log::debug!("[SIMPLE] Failed to send pointer to {}: {}", peer_ip, e);
+            }
+        }
 
-            let encoded = match state.encoder.encode(&scaled_data, timestamp) {
-                Ok(e) => e,
-                Err(e) => {
-                    return Err(format!("Encode: {}", e));
+        // Take everything already encoded, then wait only if there is
+        // nothing to send
+        loop {
+            let received = match frames.try_recv() {
+                Err(broadcast::error::TryRecvError::Empty) if viewer.queue.is_empty() => {
+                    tokio::select! {
+                        received = frames.recv() => received,
+                        // The pointer moved; it goes out with the next pass
+                        _ = pointer.changed() => break,
+                        _ = tokio::time::sleep(poll_interval) => break, // nothing encoded (static desktop)
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Empty) => break,
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    Err(broadcast::error::RecvError::Lagged(skipped))
                 }
+                Err(broadcast::error::TryRecvError::Closed) => Err(broadcast::error::RecvError::Closed),
+                Ok(outgoing) => Ok(outgoing),
             };
-            let t_encode = t0.elapsed();
-
-            if sequence < 10 || sequence % 50 == 0 {
-                log::info!("[SIMPLE] Frame {} timing: capture={:.1}ms scale={:.1}ms encode={:.1}ms total={:.1}ms",
-                    sequence,
-                    t_capture.as_secs_f64() * 1000.0,
-                    (t_scale - t_capture).as_secs_f64() * 1000.0,
-                    (t_encode - t_scale).as_secs_f64() * 1000.0,
-                    t_encode.as_secs_f64() * 1000.0,
-                );
+            let overflowed = match received {
+                Ok(Outgoing::Stop) | Err(broadcast::error::RecvError::Closed) => break 'stream,
+                Ok(outgoing) => viewer.enqueue(outgoing),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("[SIMPLE] Viewer {} fell behind by {} frames, resyncing", peer_ip, skipped);
+                    stats::frames_dropped(skipped);
+                    viewer.queue.clear();
+                    true
+                }
+            };
+            if overflowed {
+                viewer.rate.on_overflow();
+                let _ = fanout.control.send(ViewerCommand::Keyframe(viewer.rate.ladder()));
             }
+        }
 
-            Ok((timestamp, encoded))
-        });
-
-        let (timestamp, encoded) = match capture_result {
-            Ok(r) => r,
-            Err(e) => {
-                if sequence < 10 || sequence % 50 == 0 {
-                    log::warn!("[SIMPLE] Frame {} error: {}", sequence, e);
+        if let Some((message, kind)) = viewer.queue.pop() {
+            let send_start = std::time::Instant::now();
+            let on_stream = match viewer.send(&conn, &mut stream, &message, kind).await {
+                Ok(on_stream) => on_stream,
+                Err(e) => {
+                    log::info!("[SIMPLE] Viewer {} disconnected (send failed after {} frames): {}", peer_ip, sent, e);
+                    break;
                 }
-                continue;
+            };
+            let took = send_start.elapsed();
+            timing::record(Stage::Send, took);
+            // Datagrams never wait for the link, so only stream writes
+            // tell how busy it is
+            if on_stream {
+                viewer.rate.on_sent(message.len(), took);
             }
-        };
-
-        // Skip empty frames (encoder buffering, e.g. B-frame reordering)
-        if encoded.data.is_empty() {
-            sequence += 1;
-            continue;
+            stats::frame_sent(message.len(), 1);
+            sent += 1;
         }
-
-        if sequence < 10 || sequence % 50 == 0 {
-            log::info!("[SIMPLE] Frame {} encoded: {} bytes, type={:?}",
-                sequence, encoded.data.len(), encoded.frame_type);
+        if viewer.queue.dropped() > dropped_reported {
+            stats::frames_dropped(viewer.queue.dropped() - dropped_reported);
+            dropped_reported = viewer.queue.dropped();
         }
 
-        // Send frame on the same persistent stream
-        let frame_data = encode_frame_message(timestamp, &encoded.data);
-        if let Err(e) = stream.send_framed(&frame_data).await {
-            log::info!("[SIMPLE] Viewer disconnected (send failed at frame {}): {}", sequence, e);
-            break;
+        if let Some(ladder) = viewer.rate.update(std::time::Instant::now()) {
+            log::info!("[SIMPLE] Viewer {} switching to the {:?} ladder", peer_ip, ladder);
+            viewer.awaiting_start = true;
+            viewer.queue.clear();
+            let _ = fanout.control.send(ViewerCommand::Ladder { viewer: viewer_id, ladder });
         }
-
-        if sequence < 10 {
-            log::info!("[SIMPLE] Frame {} sent ({} bytes on wire)", sequence, frame_data.len());
+        if let Some(bps) = viewer.rate.take_link_bps() {
+            let _ = fanout.control.send(ViewerCommand::BitrateHint { viewer: viewer_id, bps });
+        }
+        if last_link_sample.elapsed() >= congestion::SAMPLE_INTERVAL {
+            last_link_sample = std::time::Instant::now();
+            let stats = conn.link_stats();
+            let sample = LinkSample::between(&link_stats, &stats, viewer.queue.len());
+            viewer.fec.update(sample.loss);
+            link_stats = stats;
+            let _ = fanout.control.send(ViewerCommand::Link { viewer: viewer_id, sample });
         }
-
-        sequence += 1;
     }
 
     // Send stop message
-    let stop_data = encode_stop_message();
-    let _ = stream.send_framed(&stop_data).await;
+    let _ = stream.send_framed(&encode_stop_message()).await;
     let _ = stream.finish().await;
 
-    // Check if sharing was stopped by user vs viewer just disconnected
-    let user_stopped = !state.active.load(Ordering::SeqCst);
+    fanout.viewers.lock().remove(&viewer_id);
+    fanout.kicked.lock().remove(&viewer_id);
+    crate::streaming::audience::ended(peer_ip, crate::streaming::audience::Stream::Simple(viewer_id));
+    let _ = fanout.control.send(ViewerCommand::Left(viewer_id));
+    log::info!("[SIMPLE] Viewer {} done after {} frames ({} dropped)", peer_ip, sent, viewer.queue.dropped());
+}
 
-    if user_stopped {
-        let _ = state.capture.stop();
-        SIMPLE_SHARER_ACTIVE.store(false, Ordering::SeqCst);
-        log::info!("[SIMPLE] Streaming ended (user stopped) after {} frames", sequence);
-    } else {
-        // Viewer disconnected but sharer is still active - restore state for next viewer
-        log::info!("[SIMPLE] Viewer disconnected after {} frames, ready for next viewer", sequence);
-
-        // Reset encoder/scaler to default 720p for next viewer
-        let src_w = state.pre_scaler.src_width;
-        let src_h = state.pre_scaler.src_height;
-        let pre_scaler = FrameScaler::new_with_target(src_w, src_h, SIMPLE_TARGET_WIDTH, SIMPLE_TARGET_HEIGHT);
-        let encode_width = pre_scaler.dst_width;
-        let encode_height = pre_scaler.dst_height;
-
-        match encoder::create_encoder() {
-            Ok(mut new_encoder) => {
-                let enc_config = EncoderConfig {
-                    width: encode_width,
-                    height: encode_height,
-                    fps: SIMPLE_FPS,
-                    bitrate: 2_000_000,
-                    max_bitrate: 4_000_000,
-                    keyframe_interval: SIMPLE_FPS,
-                    preset: EncoderPreset::UltraFast,
+/// Send side of one viewer task
+struct ViewerQueue {
+    id: u64,
+    /// Switched ladders and waiting for the encode loop to confirm
+    awaiting_start: bool,
+    queue: SendQueue<(Arc<Vec<u8>>, FrameKind)>,
+    rate: ViewerRate,
+    /// Viewer asked for delta frames as datagrams and the link allows them
+    datagrams: bool,
+    /// Sequence number of the next frame, once datagrams are in use
+    next_seq: u32,
+    /// Parity added to datagram frames, from the link's loss rate
+    fec: FecTuner,
+}
+
+impl ViewerQueue {
+    /// Queue what the encode loop produced if it is meant for this viewer.
+    /// Returns true if the queue overflowed and a keyframe is needed.
+    fn enqueue(&mut self, outgoing: Outgoing) -> bool {
+        match outgoing {
+            Outgoing::Start { ladder, width, height, codec, color, viewer } => {
+                let for_us = match viewer {
+                    Some(id) => id == self.id,
+                    None => ladder == self.rate.ladder() && !self.awaiting_start,
                 };
-                if let Err(e) = new_encoder.init(enc_config) {
-                    log::error!("[SIMPLE] Failed to reinit encoder for next viewer: {}", e);
-                    let _ = state.capture.stop();
-                    SIMPLE_SHARER_ACTIVE.store(false, Ordering::SeqCst);
-                    return;
-                }
-
-                // Create new stop channel
-                let (stop_tx, stop_rx) = mpsc::channel::<()>(1);
-                *SIMPLE_STOP_TX.write() = Some(stop_tx);
-
-                *SHARER_STATE.write() = Some(SharerState {
-                    capture: state.capture,
-                    pre_scaler,
-                    encoder: new_encoder,
-                    encode_width,
-                    encode_height,
-                    stop_rx,
-                    active: state.active,
-                });
-                log::info!("[SIMPLE] Sharer state restored, waiting for next viewer");
+                if !for_us {
+                    return false;
+                }
+                if viewer.is_some() {
+                    // The encode loop may have kept us on another ladder
+                    self.awaiting_start = false;
+                    self.rate.set_ladder(ladder);
+                }
+                log::info!("[SIMPLE] Queued ScreenStart ({}x{}) for viewer {}", width, height, self.id);
+                let start = encode_start_message(width, height, codec, color);
+                self.queue.push((Arc::new(start), FrameKind::Reset), FrameKind::Reset)
             }
-            Err(e) => {
-                log::error!("[SIMPLE] Failed to create encoder for next viewer: {}", e);
-                let _ = state.capture.stop();
-                SIMPLE_SHARER_ACTIVE.store(false, Ordering::SeqCst);
+            Outgoing::Frame { ladder, message, keyframe } => {
+                if self.awaiting_start || ladder != self.rate.ladder() {
+                    return false;
+                }
+                let kind = if keyframe { FrameKind::Key } else { FrameKind::Delta };
+                self.queue.push((message, kind), kind)
+            }
+            Outgoing::Stop => false,
+        }
+    }
+
+    /// Send one queued message. With datagrams on, delta frames go out as
+    /// datagram fragments and other frames on the stream with their
+    /// sequence number. Returns whether the message went on the stream.
+    async fn send(
+        &mut self,
+        conn: &QuicConnection,
+        stream: &mut QuicStream,
+        message: &[u8],
+        kind: FrameKind,
+    ) -> Result<bool, NetworkError> {
+        if !self.datagrams || kind == FrameKind::Reset {
+            stream.send_framed(message).await?;
+            return Ok(true);
+        }
+
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        if kind == FrameKind::Delta {
+            let fragments = conn
+                .max_datagram_size()
+                .and_then(|max_size| datagram::fragment(seq, message, max_size, self.fec.group_size()));
+            // Too large for datagrams, or the path stopped allowing them:
+            // the stream copy supersedes any fragments already sent
+            if let Some(fragments) = fragments {
+                if fragments.into_iter().try_for_each(|f| conn.send_datagram(f)).is_ok() {
+                    return Ok(false);
+                }
             }
         }
+        stream
+            .send_framed(&encode_sequenced_frame(seq, kind == FrameKind::Key, message))
+            .await?;
+        Ok(true)
+    }
+}
+
+/// End every stream to `peer_ip`; returns whether it was watching
+pub fn kick_viewer(peer_ip: &str) -> bool {
+    let Some(fanout) = FANOUT.read().clone() else {
+        return false;
+    };
+    let ids: Vec<u64> = fanout.viewers.lock().iter().filter(|(_, ip)| *ip == peer_ip).map(|(id, _)| *id).collect();
+    fanout.kicked.lock().extend(&ids);
+    !ids.is_empty()
+}
+
+/// Apply new encoder settings to the running share, if any
+pub fn set_encoder_tuning(tuning: EncoderTuning) {
+    if !is_simple_sharing() {
+        return;
+    }
+    if let Some(fanout) = FANOUT.read().as_ref() {
+        let _ = fanout.control.send(ViewerCommand::Tuning(tuning));
     }
 }
 
@@ -454,8 +1820,8 @@ pub fn stop_sharing() {
     if let Some(tx) = SIMPLE_STOP_TX.write().take() {
         let _ = tx.try_send(());
     }
-    // Clean up sharer state
-    let _ = SHARER_STATE.write().take();
+    recording::stop();
+    crate::app_state::changed(Slice::Sharing);
 }
 
 // ===== Receiver side =====
@@ -467,22 +1833,40 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
 
     log::info!("[SIMPLE] === Handling simple stream from {} ===", peer_ip);
 
-    let mut decoder: Option<SoftwareDecoder> = None;
+    let mut decoder: Option<Box<dyn VideoDecoder>> = None;
     let mut window_handle: Option<RenderWindowHandle> = None;
+    let mut color = ColorSpace::default();
     let mut frame_count: u32 = 0;
+    let mut last_keyframe_request: Option<std::time::Instant> = None;
+    // Delta frames arrive as datagrams once the sharer agrees
+    let conn = quic::find_connection(peer_ip);
+    let mut datagram_rx: Option<mpsc::UnboundedReceiver<bytes::Bytes>> = None;
+    let mut reassembler = Reassembler::new();
+    // Tells the sharer how fast we decode
+    let mut decode_meter = DecodeMeter::new(std::time::Instant::now());
+    // Line our clock up with the sharer's for glass-to-glass latency
+    latency::begin(peer_ip);
+    let clock_sync = tokio::spawn(clock::sync_loop(peer_ip.to_string()));
 
     loop {
         // Poll window events (resolution requests, close) between frame receives
         if let Some(ref handle) = window_handle {
             while let Some(event) = handle.try_recv_event() {
                 match event {
-                    WindowEvent::ResolutionRequested(target_w, target_h, bitrate) => {
+                    WindowEvent::ResolutionRequested(target_w, target_h, bitrate, near_lossless) => {
                         log::info!("[SIMPLE] Viewer requesting resolution {}x{} @ {} bps", target_w, target_h, bitrate);
-                        let req = encode_resolution_request(target_w, target_h, bitrate);
+                        let req = encode_resolution_request(target_w, target_h, bitrate, near_lossless);
                         if let Err(e) = stream.send_framed(&req).await {
                             log::error!("[SIMPLE] Failed to send resolution request: {}", e);
                         }
                     }
+                    WindowEvent::TextInput(text) => crate::input::session::forward_text(peer_ip, text),
+                    WindowEvent::SnapshotRequested => crate::renderer::snapshot::capture_in_background(peer_ip),
+                    WindowEvent::Annotated(annotation) => {
+                        if let Err(e) = stream.send_framed(&encode_annotation_message(&annotation)).await {
+                            log::warn!("[SIMPLE] Failed to send annotation: {}", e);
+                        }
+                    }
                     WindowEvent::CloseRequested => {
                         log::info!("[SIMPLE] Window close requested by user");
                         break;
@@ -490,23 +1874,69 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                     _ => {}
                 }
             }
+            if let Some(display_id) = take_display_request(peer_ip) {
+                log::info!("[SIMPLE] Viewer requesting display {}", display_id);
+                let req = encode_display_request(display_id);
+                if let Err(e) = stream.send_framed(&req).await {
+                    log::error!("[SIMPLE] Failed to send display request: {}", e);
+                }
+            }
             if !handle.is_open() {
                 log::info!("[SIMPLE] Render window closed by user");
                 break;
             }
         }
 
-        // Receive next framed message with timeout to allow event polling
-        let data = match tokio::time::timeout(
-            Duration::from_millis(100),
-            stream.recv_framed(),
-        ).await {
-            Ok(Ok(d)) => d,
-            Ok(Err(e)) => {
-                log::info!("[SIMPLE] Stream closed from {}: {}", peer_ip, e);
-                break;
+        // Frames from the datagram path, once they are complete and in order
+        let ready = reassembler.pop_ready(std::time::Instant::now());
+        if !ready.frames.is_empty() || ready.lost {
+            let decode_failed = match (decoder.as_mut(), window_handle.as_ref()) {
+                (Some(dec), Some(handle)) => decode_frames(dec, &ready.frames, handle, color, peer_ip, &mut decode_meter, &mut frame_count),
+                _ => false,
+            };
+            if ready.lost {
+                stats::frame_lost();
+                log::debug!("[SIMPLE] Lost a delta frame from {}, waiting for a keyframe", peer_ip);
+            }
+            if (decode_failed || ready.lost) && keyframe_request_due(&mut last_keyframe_request) {
+                if let Err(e) = stream.send_framed(&[MSG_TYPE_KEYFRAME_REQUEST]).await {
+                    log::warn!("[SIMPLE] Failed to send keyframe request: {}", e);
+                }
+            }
+        }
+
+        // A recording that just started wants to begin on a keyframe
+        if recording::viewer_keyframe_wanted(peer_ip) {
+            if let Err(e) = stream.send_framed(&[MSG_TYPE_KEYFRAME_REQUEST]).await {
+                log::warn!("[SIMPLE] Failed to send keyframe request: {}", e);
+            }
+        }
+
+        if let Some(report) = decode_meter.take_report(std::time::Instant::now()) {
+            if let Err(e) = stream.send_framed(&encode_decode_report(&report)).await {
+                log::debug!("[SIMPLE] Failed to send decode report: {}", e);
+            }
+        }
+
+        // Receive next framed message or datagram, with a timeout to allow
+        // event polling
+        let data = tokio::select! {
+            Some(datagram) = next_datagram(&mut datagram_rx) => {
+                let now = std::time::Instant::now();
+                reassembler.push_fragment(&datagram, now);
+                while let Some(datagram) = datagram_rx.as_mut().and_then(|rx| rx.try_recv().ok()) {
+                    reassembler.push_fragment(&datagram, now);
+                }
+                continue;
             }
-            Err(_) => continue, // timeout, loop back to poll events
+            received = tokio::time::timeout(Duration::from_millis(100), stream.recv_framed()) => match received {
+                Ok(Ok(d)) => d,
+                Ok(Err(e)) => {
+                    log::info!("[SIMPLE] Stream closed from {}: {}", peer_ip, e);
+                    break;
+                }
+                Err(_) => continue, // timeout, loop back to poll events
+            },
         };
 
         if data.is_empty() {
@@ -525,11 +1955,23 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
 
                 let width = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
                 let height = u32::from_be_bytes([data[5], data[6], data[7], data[8]]);
-
-                log::info!("[SIMPLE] Received ScreenStart: {}x{} from {}", width, height, peer_ip);
+                // Sharers that predate AV1 send no codec byte
+                let codec = data.get(9).copied().and_then(Codec::from_id).unwrap_or_default();
+                // ... and those that predate color spaces no color byte
+                color = data.get(10).copied().and_then(ColorSpace::from_id).unwrap_or_default();
+                recording::stream_started(peer_ip, 0, width, height, codec == Codec::H264);
+
+                log::info!(
+                    "[SIMPLE] Received ScreenStart: {}x{} {} {} from {}",
+                    width,
+                    height,
+                    codec.name(),
+                    color.name(),
+                    peer_ip
+                );
 
                 // Init decoder (always reinit on START - handles resolution changes)
-                let mut dec = match SoftwareDecoder::new() {
+                let mut dec = match stream_decoder(codec) {
                     Ok(d) => d,
                     Err(e) => {
                         log::error!("[SIMPLE] Failed to create decoder: {}", e);
@@ -541,6 +1983,7 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                     width,
                     height,
                     output_format: OutputFormat::YUV420,
+                    color_space: color,
                 };
 
                 if let Err(e) = dec.init(config) {
@@ -552,10 +1995,11 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                 // Only create window if not already open (resolution changes keep existing window)
                 let is_first_start = window_handle.is_none();
                 if is_first_start {
-                    let title = format!("[Simple] {} screen", peer_ip);
+                    let title = crate::renderer::viewers::title_for(peer_ip);
                     match RenderWindow::create(&title, width, height) {
                         Ok(handle) => {
                             log::info!("[SIMPLE] Render window created: {}x{}", width, height);
+                            crate::renderer::viewers::register(peer_ip, &title, &handle);
                             window_handle = Some(handle);
                         }
                         Err(e) => {
@@ -570,7 +2014,7 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                         let res = &RESOLUTION_OPTIONS[res_idx.min(RESOLUTION_OPTIONS.len() - 1)];
                         let br = &BITRATE_OPTIONS[br_idx.min(BITRATE_OPTIONS.len() - 1)];
                         log::info!("[SIMPLE] Sending initial resolution request: {} + {}", res.label, br.label);
-                        let req = encode_resolution_request(res.target_width, res.target_height, br.bitrate);
+                        let req = encode_resolution_request(res.target_width, res.target_height, br.bitrate, br.near_lossless);
                         if let Err(e) = stream.send_framed(&req).await {
                             log::error!("[SIMPLE] Failed to send initial resolution request: {}", e);
                         }
@@ -579,6 +2023,32 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
 
                 decoder = Some(dec);
                 frame_count = 0;
+                reassembler.reset();
+                decode_meter.reset(width, height, std::time::Instant::now());
+
+                // Offer to take delta frames as datagrams
+                if datagram_rx.is_none() {
+                    let datagrams = |c: &Arc<QuicConnection>| {
+                        c.max_datagram_size().is_some()
+                            && crate::network::capability::peer_supports(&c.peer_ip(), crate::network::capability::Capability::Datagrams)
+                    };
+                    if let Some(conn) = conn.clone().filter(datagrams) {
+                        datagram_rx = Some(spawn_datagram_reader(conn));
+                        if let Err(e) = stream.send_framed(&[MSG_TYPE_DATAGRAM_READY]).await {
+                            log::warn!("[SIMPLE] Failed to offer datagrams: {}", e);
+                        }
+                    }
+                }
+            }
+
+            MSG_TYPE_SEQ_FRAME => {
+                if data.len() < 6 {
+                    log::warn!("[SIMPLE] Sequenced frame too short: {} bytes", data.len());
+                    continue;
+                }
+                let seq = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+                let keyframe = data[5] != 0;
+                reassembler.push_frame(seq, keyframe, data[6..].to_vec(), std::time::Instant::now());
             }
 
             MSG_TYPE_FRAME => {
@@ -605,14 +2075,13 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                 }
 
                 // Check window is still open
-                if let Some(ref handle) = window_handle {
-                    if !handle.is_open() {
-                        log::info!("[SIMPLE] Render window closed by user");
-                        break;
-                    }
-                } else {
+                let Some(ref handle) = window_handle else {
                     log::warn!("[SIMPLE] Frame received but no window (missing ScreenStart?)");
                     continue;
+                };
+                if !handle.is_open() {
+                    log::info!("[SIMPLE] Render window closed by user");
+                    break;
                 }
 
                 let Some(ref mut dec) = decoder else {
@@ -620,74 +2089,22 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                     continue;
                 };
 
-                // Decode ALL frames (H.264 P-frames need sequential decode), render only the last
-                for (i, fdata) in pending_frames.iter().enumerate() {
-                    if fdata[0] == MSG_TYPE_STOP {
-                        break;
-                    }
-                    if fdata.len() < 13 {
-                        continue;
-                    }
-
-                    let timestamp = u64::from_be_bytes([
-                        fdata[1], fdata[2], fdata[3], fdata[4],
-                        fdata[5], fdata[6], fdata[7], fdata[8],
-                    ]);
-                    let frame_len = u32::from_be_bytes([fdata[9], fdata[10], fdata[11], fdata[12]]) as usize;
-
-                    if fdata.len() < 13 + frame_len {
-                        continue;
-                    }
+                let stopped = pending_frames.last().map(|f| f[0]) == Some(MSG_TYPE_STOP);
+                if stopped {
+                    pending_frames.pop();
+                }
+                let decode_failed = decode_frames(dec, &pending_frames, handle, color, peer_ip, &mut decode_meter, &mut frame_count);
 
-                    let encoded_data = &fdata[13..13 + frame_len];
-                    let is_last = i == pending_frames.len() - 1
-                        || (i + 1 < pending_frames.len() && pending_frames[i + 1][0] == MSG_TYPE_STOP);
-
-                    match dec.decode(encoded_data, timestamp) {
-                        Ok(Some(decoded)) => {
-                            frame_count += 1;
-                            // Only render the latest frame
-                            if is_last {
-                                if let Some(cpu_data) = decoded.cpu_data() {
-                                    let strides = decoded.strides().unwrap_or([
-                                        decoded.width as usize,
-                                        decoded.width as usize / 2,
-                                        decoded.width as usize / 2,
-                                    ]);
-                                    let render_frame = RenderFrame::from_yuv420(
-                                        decoded.width,
-                                        decoded.height,
-                                        cpu_data.to_vec(),
-                                        strides,
-                                    );
-                                    if let Some(ref handle) = window_handle {
-                                        if let Err(e) = handle.render_frame(render_frame) {
-                                            if frame_count % 100 == 0 {
-                                                log::warn!("[SIMPLE] Render error: {}", e);
-                                            }
-                                        }
-                                    }
-                                }
-                                if frame_count == 1 || frame_count % 50 == 0 {
-                                    log::info!("[SIMPLE] Frame {} decoded and rendered", frame_count);
-                                }
-                            }
-                        }
-                        Ok(None) => {
-                            if frame_count == 0 {
-                                log::debug!("[SIMPLE] Decoder buffering (no output yet)");
-                            }
-                        }
-                        Err(e) => {
-                            if frame_count % 100 == 0 {
-                                log::warn!("[SIMPLE] Decode error at frame {}: {}", frame_count, e);
-                            }
-                        }
+                // Broken references: ask for a keyframe instead of waiting
+                // for the periodic one
+                if decode_failed && keyframe_request_due(&mut last_keyframe_request) {
+                    if let Err(e) = stream.send_framed(&[MSG_TYPE_KEYFRAME_REQUEST]).await {
+                        log::warn!("[SIMPLE] Failed to send keyframe request: {}", e);
                     }
                 }
 
                 // If we drained a STOP message, exit
-                if pending_frames.last().map(|f| f[0]) == Some(MSG_TYPE_STOP) {
+                if stopped {
                     break;
                 }
             }
@@ -697,6 +2114,12 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
                 break;
             }
 
+            MSG_TYPE_METADATA => handle_metadata_message(peer_ip, &data),
+
+            MSG_TYPE_POINTER => handle_pointer_message(window_handle.as_ref(), &data),
+
+            MSG_TYPE_ANNOTATION => handle_annotation_message(window_handle.as_ref(), &data),
+
             _ => {
                 log::warn!("[SIMPLE] Unknown message type: 0x{:02x}", msg_type);
             }
@@ -705,6 +2128,11 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
 
     // Cleanup: stop receiving so sharer's send_framed fails immediately
     stream.stop_receiving();
+    clock_sync.abort();
+    latency::end(peer_ip);
+    let _ = take_display_request(peer_ip);
+    recording::viewer_ended(peer_ip);
+    crate::renderer::viewers::unregister(peer_ip);
     log::info!("[SIMPLE] Stream stopped, notifying sharer");
 
     if let Some(ref handle) = window_handle {
@@ -713,13 +2141,156 @@ pub async fn handle_simple_stream(stream: &mut QuicStream, peer_ip: &str) {
     log::info!("[SIMPLE] Simple stream handler ended, {} frames rendered", frame_count);
 }
 
+/// Decode frame messages in order (H.264 P-frames need sequential decode)
+/// and render only the last. Returns true if any frame failed to decode.
+/// Decoder for the simple viewer: OpenH264 for H.264, dav1d for AV1
+fn stream_decoder(codec: Codec) -> Result<Box<dyn VideoDecoder>, crate::decoder::DecoderError> {
+    Ok(match codec {
+        Codec::H264 => Box::new(SoftwareDecoder::new()?),
+        Codec::Av1 => Box::new(Av1Decoder::new()?),
+    })
+}
+
+fn decode_frames(
+    dec: &mut dyn VideoDecoder,
+    frames: &[Vec<u8>],
+    handle: &RenderWindowHandle,
+    color: ColorSpace,
+    peer_ip: &str,
+    meter: &mut DecodeMeter,
+    frame_count: &mut u32,
+) -> bool {
+    let mut decode_failed = false;
+    for (i, fdata) in frames.iter().enumerate() {
+        if fdata.len() < 13 {
+            continue;
+        }
+
+        let timestamp = u64::from_be_bytes([
+            fdata[1], fdata[2], fdata[3], fdata[4],
+            fdata[5], fdata[6], fdata[7], fdata[8],
+        ]);
+        let frame_len = u32::from_be_bytes([fdata[9], fdata[10], fdata[11], fdata[12]]) as usize;
+
+        if fdata.len() < 13 + frame_len {
+            continue;
+        }
+
+        let encoded_data = &fdata[13..13 + frame_len];
+        let is_last = i == frames.len() - 1;
+
+        stats::frame_received(frame_len);
+        recording::record_received(peer_ip, 0, encoded_data);
+        timing::record_transit(clock::to_local_ms(peer_ip, timestamp).unwrap_or(timestamp));
+        let decode_start = std::time::Instant::now();
+        let result = dec.decode(encoded_data, timestamp);
+        let decode_time = decode_start.elapsed();
+        timing::record(Stage::Decode, decode_time);
+        meter.on_decode(decode_time, result.is_err());
+        frame_dump::record(peer_ip, encoded_data, result.as_ref().ok().and_then(Option::as_ref), color);
+        match &result {
+            Ok(picture) => stats::frame_decoded(decode_time, picture.is_some()),
+            Err(_) => stats::decode_failed(decode_time),
+        }
+
+        match result {
+            Ok(Some(decoded)) => {
+                *frame_count += 1;
+                // Only render the latest frame
+                if is_last {
+                    let captured_at_ms = clock::to_local_ms(peer_ip, decoded.timestamp);
+                    let mut render_frame = RenderFrame::from_decoded(decoded)
+                        .with_capture_time(captured_at_ms)
+                        .with_color_space(color);
+                    // The HUD is drawn into the luma plane; frames on the GPU go without
+                    if let (Some(strides), None) = (render_frame.strides, &render_frame.texture) {
+                        if crate::commands::latency_hud_enabled() {
+                            let text = hud::latency_text(
+                                latency::last_ms(),
+                                clock::estimate(peer_ip).map(|e| e.rtt_ms),
+                            );
+                            let (w, h) = (render_frame.width as usize, render_frame.height as usize);
+                            hud::draw_text(&mut render_frame.data, strides[0], w, h, &text);
+                        }
+                    }
+                    if let Err(e) = handle.render_frame(render_frame) {
+                        if *frame_count % 100 == 0 {
+                            log::warn!("[SIMPLE] Render error: {}", e);
+                        }
+                    }
+                    if *frame_count == 1 || *frame_count % 50 == 0 {
+                        log::info!("[SIMPLE] Frame {} decoded and rendered", *frame_count);
+                    }
+                }
+            }
+            Ok(None) => {
+                if *frame_count == 0 {
+                    log::debug!("[SIMPLE] Decoder buffering (no output yet)");
+                }
+            }
+            Err(e) => {
+                decode_failed = true;
+                if *frame_count % 100 == 0 {
+                    log::warn!("[SIMPLE] Decode error at frame {}: {}", *frame_count, e);
+                }
+            }
+        }
+    }
+    decode_failed
+}
+
+/// Rate-limit keyframe requests after decode errors or lost frames
+fn keyframe_request_due(last_request: &mut Option<std::time::Instant>) -> bool {
+    let now = std::time::Instant::now();
+    let due = last_request.is_none_or(|t| now - t >= DECODE_ERROR_RETRY);
+    if due {
+        *last_request = Some(now);
+    }
+    due
+}
+
+/// Next datagram from the reader task, or never if datagrams are not in use
+async fn next_datagram(rx: &mut Option<mpsc::UnboundedReceiver<bytes::Bytes>>) -> Option<bytes::Bytes> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Forward the connection's datagrams to the stream handler until it stops
+/// listening
+fn spawn_datagram_reader(conn: Arc<QuicConnection>) -> mpsc::UnboundedReceiver<bytes::Bytes> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tx.closed() => break,
+                received = conn.recv_datagram() => match received {
+                    Ok(datagram) => {
+                        if tx.send(datagram).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::debug!("[SIMPLE] Datagram reader stopped: {}", e);
+                        break;
+                    }
+                },
+            }
+        }
+    });
+    rx
+}
+
 // ===== Message encoding =====
 
-fn encode_start_message(width: u32, height: u32) -> Vec<u8> {
-    let mut data = Vec::with_capacity(9);
+fn encode_start_message(width: u32, height: u32, codec: Codec, color: ColorSpace) -> Vec<u8> {
+    let mut data = Vec::with_capacity(11);
     data.push(MSG_TYPE_START);
     data.extend_from_slice(&width.to_be_bytes());
     data.extend_from_slice(&height.to_be_bytes());
+    data.push(codec.id());
+    data.push(color.id());
     data
 }
 
@@ -732,22 +2303,138 @@ fn encode_frame_message(timestamp: u64, frame_data: &[u8]) -> Vec<u8> {
     data
 }
 
+fn encode_sequenced_frame(seq: u32, keyframe: bool, frame_message: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(6 + frame_message.len());
+    data.push(MSG_TYPE_SEQ_FRAME);
+    data.extend_from_slice(&seq.to_be_bytes());
+    data.push(keyframe as u8);
+    data.extend_from_slice(frame_message);
+    data
+}
+
 fn encode_stop_message() -> Vec<u8> {
     vec![MSG_TYPE_STOP]
 }
 
-fn encode_resolution_request(target_width: u32, target_height: u32, bitrate: u32) -> Vec<u8> {
+fn encode_metadata_message(window_title: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(1 + window_title.len());
+    data.push(MSG_TYPE_METADATA);
+    data.extend_from_slice(window_title.as_bytes());
+    data
+}
+
+/// Tell the UI (and through it the screen reader) which window the sharer
+/// at `peer_ip` switched to. Public for the viewer in lib.rs.
+pub fn handle_metadata_message(peer_ip: &str, data: &[u8]) {
+    let window_title = String::from_utf8_lossy(data.get(1..).unwrap_or_default()).into_owned();
+    log::debug!("[SIMPLE] {} is now in window {:?}", peer_ip, window_title);
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        #[derive(serde::Serialize, Clone)]
+        struct ScreenMetadataEvent {
+            peer_ip: String,
+            window_title: String,
+        }
+        let _ = handle.emit("screen-metadata", ScreenMetadataEvent {
+            peer_ip: peer_ip.to_string(),
+            window_title,
+        });
+    }
+}
+
+/// Hand the sharer's pointer to the render window, which draws it over the
+/// video. Public for the viewer in lib.rs.
+pub fn handle_pointer_message(window: Option<&crate::renderer::RenderWindowHandle>, data: &[u8]) {
+    let (Some(window), Some(pointer)) = (window, parse_pointer_message(data)) else {
+        return;
+    };
+    let _ = window.set_pointer(pointer.map(PointerState::to_remote));
+}
+
+/// Show a shape the sharer or another viewer drew. Public for the viewer in
+/// lib.rs.
+pub fn handle_annotation_message(window: Option<&crate::renderer::RenderWindowHandle>, data: &[u8]) {
+    let (Some(window), Some(annotation)) = (window, parse_annotation_message(data)) else {
+        return;
+    };
+    let _ = window.show_annotation(annotation);
+}
+
+/// Encode a shape the viewer drew, to send to the sharer (used by lib.rs)
+pub fn encode_annotation_msg(annotation: &crate::renderer::Annotation) -> Vec<u8> {
+    encode_annotation_message(annotation)
+}
+
+/// Tell the UI about a shape a viewer drew on our screen
+fn emit_annotation(peer_ip: &str, annotation: crate::renderer::Annotation) {
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        #[derive(serde::Serialize, Clone)]
+        struct AnnotationEvent {
+            peer_ip: String,
+            annotation: crate::renderer::Annotation,
+        }
+        let _ = handle.emit("annotation", AnnotationEvent { peer_ip: peer_ip.to_string(), annotation });
+    }
+}
+
+/// Draw a shape over the screen we share, for every viewer; clearing takes
+/// everyone's shapes off
+pub fn annotate(annotation: &crate::renderer::Annotation) -> Result<(), String> {
+    let fanout = FANOUT.read().clone().ok_or_else(|| "Not sharing".to_string())?;
+    fanout.annotate(0, annotation);
+    Ok(())
+}
+
+fn encode_decode_report(report: &DecodeReport) -> Vec<u8> {
     let mut data = Vec::with_capacity(13);
+    data.push(MSG_TYPE_DECODE_REPORT);
+    data.extend_from_slice(&report.width.to_be_bytes());
+    data.extend_from_slice(&report.height.to_be_bytes());
+    data.extend_from_slice(&report.fps.to_be_bytes());
+    data.extend_from_slice(&report.error_permille.to_be_bytes());
+    data
+}
+
+fn encode_resolution_request(target_width: u32, target_height: u32, bitrate: u32, near_lossless: bool) -> Vec<u8> {
+    let mut data = Vec::with_capacity(14);
     data.push(MSG_TYPE_RESOLUTION_REQUEST);
     data.extend_from_slice(&target_width.to_be_bytes());
     data.extend_from_slice(&target_height.to_be_bytes());
     data.extend_from_slice(&bitrate.to_be_bytes());
+    data.push(if near_lossless { RESOLUTION_FLAG_NEAR_LOSSLESS } else { 0 });
     data
 }
 
 /// Public wrapper for encoding resolution request (used by lib.rs)
-pub fn encode_resolution_request_msg(target_width: u32, target_height: u32, bitrate: u32) -> Vec<u8> {
-    encode_resolution_request(target_width, target_height, bitrate)
+pub fn encode_resolution_request_msg(target_width: u32, target_height: u32, bitrate: u32, near_lossless: bool) -> Vec<u8> {
+    encode_resolution_request(target_width, target_height, bitrate, near_lossless)
+}
+
+fn encode_display_request(display_id: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5);
+    data.push(MSG_TYPE_DISPLAY_REQUEST);
+    data.extend_from_slice(&display_id.to_be_bytes());
+    data
+}
+
+/// Public wrapper for encoding display switch request (used by lib.rs)
+pub fn encode_display_request_msg(display_id: u32) -> Vec<u8> {
+    encode_display_request(display_id)
+}
+
+/// Public wrapper for encoding a keyframe request (used by lib.rs)
+pub fn encode_keyframe_request_msg() -> Vec<u8> {
+    vec![MSG_TYPE_KEYFRAME_REQUEST]
+}
+
+/// Ask the sharer at `peer_ip` to switch to another display. The request is
+/// sent on the open viewer stream the next time its loop polls for input.
+pub fn request_display(peer_ip: &str, display_id: u32) {
+    DISPLAY_REQUESTS.write().insert(peer_ip.to_string(), display_id);
+}
+
+/// Take the pending display switch request for a sharer, if any
+pub fn take_display_request(peer_ip: &str) -> Option<u32> {
+    DISPLAY_REQUESTS.write().remove(peer_ip)
 }
 
 /// Check if a framed message is a simple streaming message
@@ -756,5 +2443,15 @@ pub fn is_simple_message(data: &[u8]) -> bool {
     if data.is_empty() {
         return false;
     }
-    matches!(data[0], MSG_TYPE_START | MSG_TYPE_FRAME | MSG_TYPE_STOP | MSG_TYPE_RESOLUTION_REQUEST)
+    matches!(
+        data[0],
+        MSG_TYPE_START
+            | MSG_TYPE_FRAME
+            | MSG_TYPE_STOP
+            | MSG_TYPE_RESOLUTION_REQUEST
+            | MSG_TYPE_DISPLAY_REQUEST
+            | MSG_TYPE_KEYFRAME_REQUEST
+            | MSG_TYPE_DATAGRAM_READY
+            | MSG_TYPE_SEQ_FRAME
+    )
 }
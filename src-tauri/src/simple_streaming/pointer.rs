@@ -0,0 +1,102 @@
+//! Pointer side channel
+//!
+//! Drawn into the video, the pointer gets smeared by compression and only
+//! moves as often as frames go out, which is seldom on a static screen.
+//! Instead the sharer sends where the pointer is, as a fraction of the shared
+//! area, and whether the primary button is held, up to every
+//! `POINTER_INTERVAL`; viewers draw it over the video themselves.
+
+use super::MSG_TYPE_POINTER;
+use crate::renderer::RemotePointer;
+use std::time::Duration;
+
+/// How often the sharer looks at the pointer between frames
+pub const POINTER_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Flags of a pointer message; none set means the pointer is not on the
+/// shared area
+const FLAG_VISIBLE: u8 = 0x01;
+const FLAG_PRESSED: u8 = 0x02;
+
+/// Pointer on the shared area, in 1/65535ths of its width and height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerState {
+    pub x: u16,
+    pub y: u16,
+    pub pressed: bool,
+}
+
+impl PointerState {
+    /// Pointer at (x, y) in a shared area of `width`×`height` pixels; `None`
+    /// if that is outside of it
+    pub fn in_area(x: i32, y: i32, width: u32, height: u32, pressed: bool) -> Option<Self> {
+        if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+            return None;
+        }
+        Some(Self {
+            x: (x as u64 * u16::MAX as u64 / width as u64) as u16,
+            y: (y as u64 * u16::MAX as u64 / height as u64) as u16,
+            pressed,
+        })
+    }
+
+    /// The pointer as the render window takes it
+    pub fn to_remote(self) -> RemotePointer {
+        RemotePointer {
+            x: self.x as f32 / u16::MAX as f32,
+            y: self.y as f32 / u16::MAX as f32,
+            pressed: self.pressed,
+        }
+    }
+}
+
+/// Message telling viewers where the pointer is, or that it left
+pub fn encode_pointer_message(pointer: Option<PointerState>) -> Vec<u8> {
+    let mut data = Vec::with_capacity(6);
+    data.push(MSG_TYPE_POINTER);
+    match pointer {
+        Some(p) => {
+            data.push(FLAG_VISIBLE | if p.pressed { FLAG_PRESSED } else { 0 });
+            data.extend_from_slice(&p.x.to_be_bytes());
+            data.extend_from_slice(&p.y.to_be_bytes());
+        }
+        None => data.push(0),
+    }
+    data
+}
+
+/// Inverse of `encode_pointer_message`; `None` if the message is malformed,
+/// `Some(None)` if the pointer left the shared area
+pub fn parse_pointer_message(data: &[u8]) -> Option<Option<PointerState>> {
+    let flags = *data.get(1)?;
+    if flags & FLAG_VISIBLE == 0 {
+        return Some(None);
+    }
+    let be_u16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    Some(Some(PointerState { x: be_u16(2)?, y: be_u16(4)?, pressed: flags & FLAG_PRESSED != 0 }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_message_round_trip() {
+        let pointer = PointerState::in_area(960, 270, 1920, 1080, true).unwrap();
+        assert_eq!(pointer, PointerState { x: 32767, y: 16383, pressed: true });
+        let message = encode_pointer_message(Some(pointer));
+        assert_eq!(message[0], MSG_TYPE_POINTER);
+        assert_eq!(parse_pointer_message(&message), Some(Some(pointer)));
+
+        assert_eq!(parse_pointer_message(&encode_pointer_message(None)), Some(None));
+        assert_eq!(parse_pointer_message(&message[..4]), None);
+        assert_eq!(parse_pointer_message(&[MSG_TYPE_POINTER]), None);
+    }
+
+    #[test]
+    fn test_pointer_outside_area() {
+        assert_eq!(PointerState::in_area(-1, 10, 1920, 1080, false), None);
+        assert_eq!(PointerState::in_area(1920, 10, 1920, 1080, false), None);
+        assert_eq!(PointerState::in_area(0, 1079, 1920, 1080, false).map(|p| p.x), Some(0));
+    }
+}
@@ -0,0 +1,168 @@
+//! Who is watching our screen
+//!
+//! Both pipelines report each stream they start and end for a peer: the
+//! full pipeline one per display watched, simple sharing one per viewer
+//! task. A peer is one viewer however many streams it has open;
+//! "viewer-joined" is emitted when its first stream starts and
+//! "viewer-left" when its last one ends.
+
+use crate::network::discovery;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tauri::Emitter;
+
+/// One outbound media stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// A display of the full pipeline
+    Display(u32),
+    /// A simple sharing viewer task
+    Simple(u64),
+}
+
+impl Stream {
+    fn is_display(self) -> bool {
+        matches!(self, Stream::Display(_))
+    }
+}
+
+/// A peer watching our screen, as the UI lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct Viewer {
+    pub peer_ip: String,
+    pub device_id: Option<String>,
+    /// Nickname, device name or the address
+    pub name: String,
+    /// Unix milliseconds of its first stream
+    pub since: u64,
+    pub streams: usize,
+}
+
+struct Watching {
+    since: u64,
+    streams: HashSet<Stream>,
+}
+
+/// Open streams by peer
+#[derive(Default)]
+struct Audience {
+    peers: HashMap<String, Watching>,
+}
+
+impl Audience {
+    /// Returns whether the peer started watching with this stream
+    fn start(&mut self, peer_ip: &str, stream: Stream, now: u64) -> bool {
+        let first = !self.peers.contains_key(peer_ip);
+        self.peers
+            .entry(peer_ip.to_string())
+            .or_insert_with(|| Watching { since: now, streams: HashSet::new() })
+            .streams
+            .insert(stream);
+        first
+    }
+
+    /// End the streams of `peer_ip` that `ends` picks; returns whether it
+    /// stopped watching
+    fn end(&mut self, peer_ip: &str, ends: impl Fn(Stream) -> bool) -> bool {
+        let Some(watching) = self.peers.get_mut(peer_ip) else {
+            return false;
+        };
+        watching.streams.retain(|s| !ends(*s));
+        if watching.streams.is_empty() {
+            self.peers.remove(peer_ip);
+            return true;
+        }
+        false
+    }
+}
+
+static AUDIENCE: Lazy<Mutex<Audience>> = Lazy::new(|| Mutex::new(Audience::default()));
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn viewer(peer_ip: &str, since: u64, streams: usize) -> Viewer {
+    let device = discovery::get_devices().into_iter().find(|d| d.ip == peer_ip);
+    Viewer {
+        peer_ip: peer_ip.to_string(),
+        device_id: device.as_ref().map(|d| d.id.clone()),
+        name: device
+            .map(|d| d.nickname.unwrap_or(d.name))
+            .unwrap_or_else(|| peer_ip.to_string()),
+        since,
+        streams,
+    }
+}
+
+fn emit(event: &str, payload: impl Serialize + Clone) {
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit(event, payload);
+    }
+}
+
+/// A stream to `peer_ip` started
+pub fn started(peer_ip: &str, stream: Stream) {
+    let now = now_ms();
+    if AUDIENCE.lock().start(peer_ip, stream, now) {
+        emit("viewer-joined", viewer(peer_ip, now, 1));
+    }
+}
+
+/// A stream to `peer_ip` ended
+pub fn ended(peer_ip: &str, stream: Stream) {
+    if AUDIENCE.lock().end(peer_ip, |s| s == stream) {
+        emit("viewer-left", peer_ip);
+    }
+}
+
+/// The full pipeline stopped: every display stream ended
+pub fn displays_ended() {
+    let left: Vec<String> = {
+        let mut audience = AUDIENCE.lock();
+        let peers: Vec<String> = audience.peers.keys().cloned().collect();
+        peers.into_iter().filter(|ip| audience.end(ip, Stream::is_display)).collect()
+    };
+    for peer_ip in left {
+        emit("viewer-left", peer_ip);
+    }
+}
+
+/// Everyone watching, longest watching first
+pub fn current() -> Vec<Viewer> {
+    let watching: Vec<(String, u64, usize)> = AUDIENCE
+        .lock()
+        .peers
+        .iter()
+        .map(|(ip, w)| (ip.clone(), w.since, w.streams.len()))
+        .collect();
+    let mut viewers: Vec<Viewer> = watching.into_iter().map(|(ip, since, streams)| viewer(&ip, since, streams)).collect();
+    viewers.sort_by_key(|v| v.since);
+    viewers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_watches_until_last_stream_ends() {
+        let mut audience = Audience::default();
+        assert!(audience.start("10.0.0.2", Stream::Display(1), 5));
+        assert!(!audience.start("10.0.0.2", Stream::Display(2), 6));
+        assert!(!audience.start("10.0.0.2", Stream::Simple(7), 7));
+        assert_eq!(audience.peers["10.0.0.2"].since, 5);
+
+        assert!(!audience.end("10.0.0.2", |s| s == Stream::Display(1)));
+        assert!(!audience.end("10.0.0.2", Stream::is_display));
+        assert!(audience.end("10.0.0.2", |s| s == Stream::Simple(7)));
+        assert!(audience.peers.is_empty());
+        // Not watching
+        assert!(!audience.end("10.0.0.2", |_| true));
+    }
+}
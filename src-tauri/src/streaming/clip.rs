@@ -0,0 +1,189 @@
+//! Clips of what was just watched
+//!
+//! Every viewer keeps the last `KEPT` of the H.264 frames it received (see
+//! `recording::record_received`), from a keyframe on, so `save_clip` can
+//! write out the last seconds as an MP4 after something happened, without
+//! a recording having been started. The buffer starts over when the stream
+//! changes (another display or resolution) and is dropped with the viewer.
+
+use super::mp4::{FragmentedMp4, Pushed};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How much of the stream is kept
+pub const KEPT: Duration = Duration::from_secs(30);
+
+/// Most bytes kept per stream, for high bitrates
+const MAX_BYTES: usize = 96 * 1024 * 1024;
+
+struct Frame {
+    data: Vec<u8>,
+    keyframe: bool,
+    at: Instant,
+}
+
+/// The last frames of one stream, starting with a keyframe
+#[derive(Default)]
+pub struct ClipBuffer {
+    /// Display, width and height the frames are of
+    stream: Option<(u32, u32, u32)>,
+    frames: VecDeque<Frame>,
+    bytes: usize,
+}
+
+impl ClipBuffer {
+    /// Keep a frame of `stream` (display, width, height) received `at`
+    pub fn push(&mut self, stream: (u32, u32, u32), data: &[u8], keyframe: bool, at: Instant) {
+        if self.stream != Some(stream) {
+            self.stream = Some(stream);
+            self.frames.clear();
+            self.bytes = 0;
+        }
+        // Frames before the first keyframe cannot be decoded
+        if self.frames.is_empty() && !keyframe {
+            return;
+        }
+        self.bytes += data.len();
+        self.frames.push_back(Frame { data: data.to_vec(), keyframe, at });
+        self.trim(at);
+    }
+
+    /// Drop the oldest group of pictures while the next one still covers
+    /// `KEPT`, or while over `MAX_BYTES`
+    fn trim(&mut self, now: Instant) {
+        let cutoff = now.checked_sub(KEPT);
+        loop {
+            let Some(next) = self.frames.iter().skip(1).position(|f| f.keyframe).map(|i| i + 1) else {
+                return;
+            };
+            let covered = cutoff.is_some_and(|cutoff| self.frames[next].at <= cutoff);
+            if !covered && self.bytes <= MAX_BYTES {
+                return;
+            }
+            for frame in self.frames.drain(..next) {
+                self.bytes -= frame.data.len();
+            }
+        }
+    }
+
+    /// The frames of the last `span` before `now`, from the keyframe
+    /// before it: data, keyframe and microseconds from the first
+    fn last(&self, span: Duration, now: Instant) -> Vec<(&[u8], bool, u64)> {
+        let cutoff = now.checked_sub(span);
+        let start = self
+            .frames
+            .iter()
+            .rposition(|f| f.keyframe && cutoff.is_none_or(|cutoff| f.at <= cutoff))
+            .unwrap_or(0);
+        let Some(first) = self.frames.get(start) else {
+            return Vec::new();
+        };
+        self.frames
+            .iter()
+            .skip(start)
+            .map(|f| (f.data.as_slice(), f.keyframe, f.at.duration_since(first.at).as_micros() as u64))
+            .collect()
+    }
+
+    /// Seconds of stream kept
+    pub fn seconds(&self) -> f64 {
+        match (self.frames.front(), self.frames.back()) {
+            (Some(first), Some(last)) => last.at.duration_since(first.at).as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Buffers of the screens we watch, by sharer
+static BUFFERS: Lazy<Mutex<HashMap<String, ClipBuffer>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Keep a frame of `peer_ip`'s display `display_id` as it arrived
+pub fn push(peer_ip: &str, display_id: u32, data: &[u8], keyframe: bool, width: u32, height: u32) {
+    let mut buffers = BUFFERS.lock();
+    if !buffers.contains_key(peer_ip) {
+        buffers.insert(peer_ip.to_string(), ClipBuffer::default());
+    }
+    if let Some(buffer) = buffers.get_mut(peer_ip) {
+        buffer.push((display_id, width, height), data, keyframe, Instant::now());
+    }
+}
+
+/// We stopped watching `peer_ip`'s screen
+pub fn clear(peer_ip: &str) {
+    BUFFERS.lock().remove(peer_ip);
+}
+
+/// Write the last `span` (at most `KEPT`) of `peer_ip`'s screen into a new
+/// file under `dir`; returns the file
+pub fn save(peer_ip: &str, span: Duration, dir: &Path) -> Result<PathBuf, String> {
+    // Copied out so the viewer is not held up while the file is written
+    let (frames, width, height): (Vec<(Vec<u8>, bool, u64)>, u32, u32) = {
+        let buffers = BUFFERS.lock();
+        let buffer = buffers.get(peer_ip).ok_or_else(|| format!("Nothing received from {}", peer_ip))?;
+        let (_, width, height) = buffer.stream.unwrap_or_default();
+        let frames = buffer.last(span.min(KEPT), Instant::now());
+        (frames.into_iter().map(|(data, keyframe, micros)| (data.to_vec(), keyframe, micros)).collect(), width, height)
+    };
+    if frames.is_empty() {
+        return Err(format!("Nothing received from {} yet", peer_ip));
+    }
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stamp = crate::capture::archive::utc_stamp(crate::capture::archive::now_secs());
+    let path = dir.join(super::recording::peer_file_name("clip", peer_ip, &stamp));
+    let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let mut mp4 = FragmentedMp4::new(BufWriter::new(file));
+    for (data, keyframe, micros) in &frames {
+        let pushed = mp4
+            .push(data, *keyframe, *micros, width, height)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        // The encoder was reconfigured at the same size; the clip ends there
+        if pushed == Pushed::NewStream {
+            break;
+        }
+    }
+    let seconds = frames.last().map_or(0, |(_, _, micros)| micros / 1_000_000);
+    mp4.finish().map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    log::info!("Saved the last {}s of {}'s screen to {}", seconds, peer_ip, path.display());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_buffer_keeps_whole_gops() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut buffer = ClipBuffer::default();
+
+        // A delta frame before any keyframe is useless
+        buffer.push((0, 1920, 1080), &[1], false, at(0));
+        assert_eq!(buffer.frames.len(), 0);
+
+        // A keyframe every 10 s and a delta frame every second
+        for secs in 1..=45 {
+            buffer.push((0, 1920, 1080), &[secs as u8], secs % 10 == 1, at(secs));
+        }
+        // 45 - 30 = 15 falls in the group starting at 11
+        assert_eq!(buffer.frames.front().map(|f| f.data[0]), Some(11));
+        assert_eq!(buffer.seconds(), 34.0);
+
+        // The last 5 s start at the keyframe before them, at 31
+        let last = buffer.last(Duration::from_secs(5), at(45));
+        assert_eq!(last.first(), Some(&(&[31u8][..], true, 0)));
+        assert_eq!(last.last(), Some(&(&[45u8][..], false, 14_000_000)));
+        assert_eq!(last.len(), 15);
+
+        // Another resolution starts over
+        buffer.push((0, 1280, 720), &[99], true, at(46));
+        assert_eq!(buffer.frames.len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+//! Congestion-based rate control
+//!
+//! The viewer's toolbar picks a resolution and bitrate; the controller takes
+//! that bitrate as a ceiling and steers the encoder below it from what the
+//! QUIC connections report. RTT rising over the connection's baseline,
+//! packet loss or frames piling up in a viewer's send queue cut the bitrate
+//! multiplicatively, and once the bitrate is at its floor the frame rate goes
+//! down too. A clean link wins back frame rate first, then bitrate in small
+//! steps.
+
+use crate::network::quic::LinkStats;
+use std::time::Duration;
+
+/// How often viewers report their link
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lowest bitrate the controller goes down to
+pub const MIN_BITRATE: u32 = 250_000;
+
+/// Lowest frame rate the controller goes down to
+pub const MIN_FPS: u32 = 10;
+
+/// Queueing delay (RTT over the baseline) that counts as congestion
+const CONGESTED_DELAY: Duration = Duration::from_millis(60);
+
+/// Queueing delay below which the link is clean
+const CLEAR_DELAY: Duration = Duration::from_millis(20);
+
+/// Packet loss that counts as congestion
+const CONGESTED_LOSS: f64 = 0.02;
+
+/// Packet loss below which the link is clean
+const CLEAR_LOSS: f64 = 0.005;
+
+/// Frames waiting in a viewer's send queue that count as congestion
+const CONGESTED_QUEUE: usize = 3;
+
+/// Bitrate multiplier on congestion
+const DECREASE: f64 = 0.7;
+
+/// Bitrate added per step on a clean link, as a share of the ceiling
+const INCREASE_STEP: f64 = 0.1;
+
+/// Frame rate added per step on a clean link
+const FPS_STEP: u32 = 5;
+
+/// Clean samples in a row needed for one step up
+const CLEAR_SAMPLES: u32 = 2;
+
+/// What one viewer's connection reported over the last interval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkSample {
+    pub rtt: Duration,
+    /// Share of the packets sent in the interval that were lost
+    pub loss: f64,
+    /// Frames waiting in the viewer's send queue
+    pub queued_frames: usize,
+}
+
+impl LinkSample {
+    /// Sample from two readings of a connection's cumulative stats
+    pub fn between(before: &LinkStats, after: &LinkStats, queued_frames: usize) -> Self {
+        let sent = after.sent_packets.saturating_sub(before.sent_packets);
+        let lost = after.lost_packets.saturating_sub(before.lost_packets);
+        Self {
+            rtt: after.rtt,
+            loss: if sent == 0 { 0.0 } else { lost as f64 / sent as f64 },
+            queued_frames,
+        }
+    }
+
+    /// The worse of two viewers' samples, field by field
+    pub fn worst(self, other: Self) -> Self {
+        Self {
+            rtt: self.rtt.max(other.rtt),
+            loss: self.loss.max(other.loss),
+            queued_frames: self.queued_frames.max(other.queued_frames),
+        }
+    }
+}
+
+/// Encoder settings chosen by the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateDecision {
+    pub bitrate: u32,
+    pub fps: u32,
+}
+
+/// AIMD controller for one encoder
+#[derive(Debug)]
+pub struct RateController {
+    ceiling: u32,
+    max_fps: u32,
+    bitrate: u32,
+    fps: u32,
+    /// Lowest RTT seen, taken as the RTT of an empty queue
+    base_rtt: Option<Duration>,
+    clear_samples: u32,
+}
+
+impl RateController {
+    pub fn new(ceiling: u32, max_fps: u32) -> Self {
+        Self {
+            ceiling,
+            max_fps,
+            bitrate: ceiling,
+            fps: max_fps,
+            base_rtt: None,
+            clear_samples: 0,
+        }
+    }
+
+    /// The encoder was rebuilt at `ceiling`: start again from full rate
+    pub fn reset(&mut self, ceiling: u32) {
+        self.ceiling = ceiling;
+        self.bitrate = ceiling;
+        self.fps = self.max_fps;
+        self.clear_samples = 0;
+    }
+
+    pub fn current(&self) -> RateDecision {
+        RateDecision { bitrate: self.bitrate, fps: self.fps }
+    }
+
+    /// Feed the worst viewer's sample. Returns the new settings if they changed.
+    pub fn update(&mut self, sample: LinkSample) -> Option<RateDecision> {
+        let base = self.base_rtt.map_or(sample.rtt, |base| base.min(sample.rtt));
+        self.base_rtt = Some(base);
+        let delay = sample.rtt.saturating_sub(base);
+        let before = self.current();
+
+        let congested = delay > CONGESTED_DELAY
+            || sample.loss > CONGESTED_LOSS
+            || sample.queued_frames >= CONGESTED_QUEUE;
+        let clear = delay < CLEAR_DELAY && sample.loss < CLEAR_LOSS && sample.queued_frames == 0;
+
+        if congested {
+            self.clear_samples = 0;
+            let floor = MIN_BITRATE.min(self.ceiling);
+            if self.bitrate > floor {
+                self.bitrate = ((self.bitrate as f64 * DECREASE) as u32).max(floor);
+            } else {
+                self.fps = (self.fps * 2 / 3).max(MIN_FPS.min(self.max_fps));
+            }
+        } else if clear {
+            self.clear_samples += 1;
+            if self.clear_samples >= CLEAR_SAMPLES {
+                self.clear_samples = 0;
+                if self.fps < self.max_fps {
+                    self.fps = (self.fps + FPS_STEP).min(self.max_fps);
+                } else {
+                    let step = ((self.ceiling as f64 * INCREASE_STEP) as u32).max(1);
+                    self.bitrate = (self.bitrate + step).min(self.ceiling);
+                }
+            }
+        } else {
+            self.clear_samples = 0;
+        }
+
+        let after = self.current();
+        (after != before).then_some(after)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(rtt_ms: u64, loss: f64, queued_frames: usize) -> LinkSample {
+        LinkSample { rtt: Duration::from_millis(rtt_ms), loss, queued_frames }
+    }
+
+    #[test]
+    fn test_congestion_cuts_bitrate_then_fps() {
+        let mut rate = RateController::new(1_000_000, 30);
+        assert_eq!(rate.update(sample(5, 0.0, 0)), None);
+
+        // Queueing delay builds up
+        let d = rate.update(sample(100, 0.0, 0)).unwrap();
+        assert_eq!(d, RateDecision { bitrate: 700_000, fps: 30 });
+
+        // Loss keeps cutting until the floor, then the frame rate drops
+        while rate.current().bitrate > MIN_BITRATE {
+            rate.update(sample(5, 0.05, 0));
+        }
+        let d = rate.update(sample(5, 0.0, 5)).unwrap();
+        assert_eq!(d, RateDecision { bitrate: MIN_BITRATE, fps: 20 });
+    }
+
+    #[test]
+    fn test_clean_link_recovers_fps_first() {
+        let mut rate = RateController::new(1_000_000, 30);
+        rate.update(sample(5, 0.0, 0));
+        while rate.current().fps == 30 {
+            rate.update(sample(5, 0.1, 0));
+        }
+        let low = rate.current();
+
+        let mut decisions = Vec::new();
+        for _ in 0..8 {
+            if let Some(d) = rate.update(sample(6, 0.0, 0)) {
+                decisions.push(d);
+            }
+        }
+        assert_eq!(decisions[0], RateDecision { bitrate: low.bitrate, fps: 25 });
+        assert_eq!(decisions[1], RateDecision { bitrate: low.bitrate, fps: 30 });
+        assert_eq!(decisions[2], RateDecision { bitrate: low.bitrate + 100_000, fps: 30 });
+
+        rate.reset(2_000_000);
+        assert_eq!(rate.current(), RateDecision { bitrate: 2_000_000, fps: 30 });
+    }
+
+    #[test]
+    fn test_sample_between_readings() {
+        let before = LinkStats { sent_packets: 100, lost_packets: 1, ..Default::default() };
+        let after = LinkStats {
+            rtt: Duration::from_millis(8),
+            sent_packets: 300,
+            lost_packets: 5,
+            ..Default::default()
+        };
+        let s = LinkSample::between(&before, &after, 2);
+        assert_eq!(s.rtt, Duration::from_millis(8));
+        assert!((s.loss - 0.02).abs() < 1e-9);
+        assert_eq!(s.worst(sample(20, 0.0, 0)).rtt, Duration::from_millis(20));
+    }
+}
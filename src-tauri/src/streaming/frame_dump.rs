@@ -0,0 +1,289 @@
+//! Frame dumps for codec debugging
+//!
+//! `dump_frames` saves the next decoded frames of a watched stream as PNG or
+//! raw I420, optionally with the encoded bitstream they were decoded from,
+//! so artifacts can be looked at offline (`ffplay stream.h264`) and attached
+//! to bug reports. Files are written on their own thread so a dump does not
+//! stall decoding.
+
+use crate::decoder::software::SoftwareDecoder;
+use crate::decoder::{DecodedFrame, OutputFormat};
+use crate::encoder::color::ColorSpace;
+use crossbeam_channel::Sender;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Most frames one dump may take (1080p PNGs are a few MB each)
+pub const MAX_FRAMES: u32 = 600;
+
+/// How decoded frames are saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Png,
+    /// Tightly packed I420 (BGRA frames are saved as raw BGRA)
+    Yuv,
+}
+
+impl DumpFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "png" => Some(DumpFormat::Png),
+            "yuv" => Some(DumpFormat::Yuv),
+            _ => None,
+        }
+    }
+}
+
+enum Job {
+    Encoded(Vec<u8>),
+    Frame {
+        index: u32,
+        width: u32,
+        height: u32,
+        format: OutputFormat,
+        data: Vec<u8>,
+        strides: [usize; 3],
+        color: ColorSpace,
+    },
+}
+
+struct Dump {
+    /// Only frames from this sharer, or from any stream
+    peer_ip: Option<String>,
+    remaining: u32,
+    written: u32,
+    bitstream: bool,
+    tx: Sender<Job>,
+}
+
+static DUMP: Mutex<Option<Dump>> = Mutex::new(None);
+
+/// Set while a dump runs, so the decode loop skips the lock otherwise
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Where dumps go unless a folder is given
+pub fn default_root() -> Option<PathBuf> {
+    dirs::download_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|p| p.join("LAN Meeting").join("Frame Dumps"))
+}
+
+/// Start dumping the next `count` decoded frames into a new folder under
+/// `root`; returns the folder. Replaces a dump still running.
+pub fn start(
+    root: &Path,
+    count: u32,
+    format: DumpFormat,
+    bitstream: bool,
+    peer_ip: Option<String>,
+) -> Result<PathBuf, String> {
+    if count == 0 || count > MAX_FRAMES {
+        return Err(format!("Frame count must be 1 to {}", MAX_FRAMES));
+    }
+    let stamp = crate::capture::archive::utc_stamp(crate::capture::archive::now_secs());
+    let dir = root.join(stamp);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let writer_dir = dir.clone();
+    std::thread::spawn(move || write_jobs(&writer_dir, format, rx));
+
+    log::info!("Dumping the next {} frames to {}", count, dir.display());
+    *DUMP.lock() = Some(Dump {
+        peer_ip,
+        remaining: count,
+        written: 0,
+        bitstream,
+        tx,
+    });
+    ACTIVE.store(true, Ordering::Relaxed);
+    Ok(dir)
+}
+
+/// Stop a running dump early; the frames written so far are kept
+pub fn stop() {
+    ACTIVE.store(false, Ordering::Relaxed);
+    DUMP.lock().take();
+}
+
+/// Offer a frame from `peer_ip`'s stream: its encoded bytes and, if the
+/// decoder produced a picture for it, the decoded frame
+pub fn record(peer_ip: &str, encoded: &[u8], decoded: Option<&DecodedFrame>, color: ColorSpace) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut guard = DUMP.lock();
+    let Some(dump) = guard.as_mut() else {
+        return;
+    };
+    if dump.peer_ip.as_deref().is_some_and(|ip| ip != peer_ip) {
+        return;
+    }
+
+    // Every packet goes into the bitstream, including ones the decoder held
+    // back, or the saved stream would not decode
+    if dump.bitstream {
+        let _ = dump.tx.send(Job::Encoded(encoded.to_vec()));
+    }
+    let Some(frame) = decoded else {
+        return;
+    };
+    let Some(data) = frame.cpu_data() else {
+        return;
+    };
+    dump.written += 1;
+    dump.remaining -= 1;
+    let _ = dump.tx.send(Job::Frame {
+        index: dump.written,
+        width: frame.width,
+        height: frame.height,
+        format: frame.format,
+        data: data.to_vec(),
+        strides: frame.strides().unwrap_or([frame.width as usize, frame.width as usize / 2, frame.width as usize / 2]),
+        color,
+    });
+    if dump.remaining == 0 {
+        // Dropping the sender lets the writer finish
+        guard.take();
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
+
+fn write_jobs(dir: &Path, format: DumpFormat, rx: crossbeam_channel::Receiver<Job>) {
+    let mut bitstream: Option<File> = None;
+    let mut frames = 0u32;
+    for job in rx {
+        let result = match job {
+            Job::Encoded(data) => {
+                if bitstream.is_none() {
+                    let path = dir.join(format!("stream.{}", bitstream_extension(&data)));
+                    match File::create(&path) {
+                        Ok(file) => bitstream = Some(file),
+                        Err(e) => log::warn!("Failed to create {}: {}", path.display(), e),
+                    }
+                }
+                match bitstream.as_mut() {
+                    Some(file) => file.write_all(&data).map_err(|e| e.to_string()),
+                    None => Ok(()),
+                }
+            }
+            Job::Frame { index, width, height, format: frame_format, data, strides, color } => {
+                frames += 1;
+                write_frame(dir, format, index, width, height, frame_format, &data, strides, color)
+            }
+        };
+        if let Err(e) = result {
+            log::warn!("Frame dump write failed: {}", e);
+        }
+    }
+
+    log::info!("Frame dump finished: {} frames in {}", frames, dir.display());
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        use tauri::Emitter;
+
+        #[derive(serde::Serialize, Clone)]
+        struct FrameDumpFinishedEvent {
+            path: String,
+            frames: u32,
+        }
+
+        let _ = handle.emit(
+            "frame-dump-finished",
+            FrameDumpFinishedEvent {
+                path: dir.to_string_lossy().to_string(),
+                frames,
+            },
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_frame(
+    dir: &Path,
+    format: DumpFormat,
+    index: u32,
+    width: u32,
+    height: u32,
+    frame_format: OutputFormat,
+    data: &[u8],
+    strides: [usize; 3],
+    color: ColorSpace,
+) -> Result<(), String> {
+    let (w, h) = (width as usize, height as usize);
+    match (format, frame_format) {
+        (DumpFormat::Yuv, OutputFormat::YUV420) => {
+            let path = dir.join(format!("frame_{:05}_{}x{}.yuv", index, width, height));
+            std::fs::write(path, pack_i420(data, strides, w, h)).map_err(|e| e.to_string())
+        }
+        (DumpFormat::Yuv, OutputFormat::BGRA) => {
+            let path = dir.join(format!("frame_{:05}_{}x{}.bgra", index, width, height));
+            std::fs::write(path, data).map_err(|e| e.to_string())
+        }
+        (DumpFormat::Png, _) => {
+            let mut rgba = match frame_format {
+                OutputFormat::BGRA => data.to_vec(),
+                OutputFormat::YUV420 => {
+                    let packed = pack_i420(data, strides, w, h);
+                    let (y, uv) = packed.split_at(w * h);
+                    let (u, v) = uv.split_at(uv.len() / 2);
+                    SoftwareDecoder::yuv420_to_bgra(y, u, v, w, w.div_ceil(2), w.div_ceil(2), width, height, color)
+                }
+            };
+            for px in rgba.chunks_exact_mut(4) {
+                px.swap(0, 2);
+                px[3] = 255;
+            }
+            let path = dir.join(format!("frame_{:05}.png", index));
+            image::save_buffer(&path, &rgba, width, height, image::ExtendedColorType::Rgba8)
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        }
+    }
+}
+
+/// Copy the planes of a decoded I420 frame without their row padding
+fn pack_i420(data: &[u8], strides: [usize; 3], width: usize, height: usize) -> Vec<u8> {
+    let (uv_w, uv_h) = (width.div_ceil(2), height.div_ceil(2));
+    let planes = [(strides[0], width, height), (strides[1], uv_w, uv_h), (strides[2], uv_w, uv_h)];
+
+    let mut out = Vec::with_capacity(width * height + 2 * uv_w * uv_h);
+    let mut offset = 0;
+    for (stride, w, h) in planes {
+        for row in 0..h {
+            let start = offset + row * stride;
+            out.extend_from_slice(data.get(start..start + w).unwrap_or(&[]));
+        }
+        offset += stride * h;
+    }
+    out
+}
+
+/// File extension of the stream a packet came from: H.264 in Annex B
+/// starts with a start code, AV1 is a low-overhead OBU stream
+fn bitstream_extension(packet: &[u8]) -> &'static str {
+    if packet.starts_with(&[0, 0, 0, 1]) || packet.starts_with(&[0, 0, 1]) {
+        "h264"
+    } else {
+        "obu"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_i420_drops_padding() {
+        // 2x2 frame with Y rows padded to 4 bytes and chroma rows to 2
+        let data = [1, 2, 0, 0, 3, 4, 0, 0, 5, 0, 6, 0];
+        assert_eq!(pack_i420(&data, [4, 2, 2], 2, 2), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_bitstream_extension() {
+        assert_eq!(bitstream_extension(&[0, 0, 0, 1, 0x67]), "h264");
+        assert_eq!(bitstream_extension(&[0x12, 0x00, 0x0a]), "obu");
+    }
+}
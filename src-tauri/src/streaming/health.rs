@@ -0,0 +1,193 @@
+//! Viewer stream health
+//!
+//! Detects playback stalls on the viewer: the stream is active but nothing
+//! has been rendered for a while. A static desktop legitimately sends only
+//! one refresh keyframe per `IDLE_REFRESH_INTERVAL`, so silence counts as a stall
+//! only once that refresh is overdue; frames that arrive but never render
+//! (decoder waiting for a keyframe) count after `STALL_AFTER`.
+
+use crate::capture::damage::IDLE_REFRESH_INTERVAL;
+use std::time::{Duration, Instant};
+
+/// Nothing rendered for this long means playback stalled
+pub const STALL_AFTER: Duration = Duration::from_millis(500);
+
+/// Ask again for a keyframe this often while still stalled
+pub const KEYFRAME_RETRY: Duration = Duration::from_secs(2);
+
+/// After a decode error, ask for a keyframe at most this often; a lost
+/// reference usually breaks every frame until the keyframe arrives
+pub const DECODE_ERROR_RETRY: Duration = Duration::from_millis(250);
+
+/// What the caller should do after a health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// Playback just stalled: notify the UI and request a keyframe
+    Stalled,
+    /// Still stalled: request another keyframe
+    KeyframeRetry,
+}
+
+/// Render cadence and stall history of one viewed stream
+#[derive(Debug, Clone)]
+pub struct StreamHealth {
+    last_rendered: Instant,
+    last_received: Instant,
+    /// Frames arrived since the last render
+    pending: bool,
+    stalled_since: Option<Instant>,
+    last_keyframe_request: Option<Instant>,
+    stall_count: u32,
+    stalled_total: Duration,
+    decode_errors: u32,
+}
+
+impl StreamHealth {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_rendered: now,
+            last_received: now,
+            pending: false,
+            stalled_since: None,
+            last_keyframe_request: None,
+            stall_count: 0,
+            stalled_total: Duration::ZERO,
+            decode_errors: 0,
+        }
+    }
+
+    /// A frame arrived from the sharer
+    pub fn on_received(&mut self, now: Instant) {
+        self.last_received = now;
+        self.pending = true;
+    }
+
+    /// A frame was decoded and handed to the window. Returns how long
+    /// playback was stalled if this ends a stall.
+    pub fn on_rendered(&mut self, now: Instant) -> Option<Duration> {
+        self.last_rendered = now;
+        self.pending = false;
+        let since = self.stalled_since.take()?;
+        self.last_keyframe_request = None;
+        let stalled = now.saturating_duration_since(since);
+        self.stalled_total += stalled;
+        Some(stalled)
+    }
+
+    /// Periodic check while the stream is active
+    pub fn check(&mut self, now: Instant) -> Option<HealthEvent> {
+        if self.stalled_since.is_some() {
+            let retry = self
+                .last_keyframe_request
+                .is_none_or(|t| now.saturating_duration_since(t) >= KEYFRAME_RETRY);
+            if retry {
+                self.last_keyframe_request = Some(now);
+                return Some(HealthEvent::KeyframeRetry);
+            }
+            return None;
+        }
+
+        let idle = now.saturating_duration_since(self.last_rendered);
+        let silent = now.saturating_duration_since(self.last_received);
+        let stalled = idle > STALL_AFTER && (self.pending || silent > IDLE_REFRESH_INTERVAL + STALL_AFTER);
+        if !stalled {
+            return None;
+        }
+
+        self.stalled_since = Some(self.last_rendered);
+        self.last_keyframe_request = Some(now);
+        self.stall_count += 1;
+        Some(HealthEvent::Stalled)
+    }
+
+    /// A frame failed to decode. Returns true if a keyframe should be
+    /// requested now rather than waiting for the stall check.
+    pub fn on_decode_error(&mut self, now: Instant) -> bool {
+        self.decode_errors += 1;
+        let request = self
+            .last_keyframe_request
+            .is_none_or(|t| now.saturating_duration_since(t) >= DECODE_ERROR_RETRY);
+        if request {
+            self.last_keyframe_request = Some(now);
+        }
+        request
+    }
+
+    /// Frames that failed to decode since the stream started
+    pub fn decode_errors(&self) -> u32 {
+        self.decode_errors
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stalled_since.is_some()
+    }
+
+    /// Stalls since the stream started
+    pub fn stall_count(&self) -> u32 {
+        self.stall_count
+    }
+
+    /// Time spent stalled, including an ongoing stall
+    pub fn stalled_total(&self, now: Instant) -> Duration {
+        self.stalled_total
+            + self
+                .stalled_since
+                .map(|since| now.saturating_duration_since(since))
+                .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undecodable_frames_stall_and_recover() {
+        let start = Instant::now();
+        let mut health = StreamHealth::new(start);
+        health.on_rendered(start);
+
+        // Frames keep arriving but none render
+        health.on_received(start + Duration::from_millis(300));
+        assert_eq!(health.check(start + Duration::from_millis(400)), None);
+        health.on_received(start + Duration::from_millis(550));
+        assert_eq!(health.check(start + Duration::from_millis(600)), Some(HealthEvent::Stalled));
+        assert_eq!(health.check(start + Duration::from_millis(700)), None);
+        assert_eq!(
+            health.check(start + Duration::from_millis(2700)),
+            Some(HealthEvent::KeyframeRetry)
+        );
+
+        let stalled = health.on_rendered(start + Duration::from_millis(3000));
+        assert_eq!(stalled, Some(Duration::from_millis(3000)));
+        assert!(!health.is_stalled());
+        assert_eq!(health.stall_count(), 1);
+    }
+
+    #[test]
+    fn test_decode_errors_request_keyframes_rate_limited() {
+        let start = Instant::now();
+        let mut health = StreamHealth::new(start);
+
+        assert!(health.on_decode_error(start));
+        assert!(!health.on_decode_error(start + Duration::from_millis(100)));
+        assert!(health.on_decode_error(start + DECODE_ERROR_RETRY));
+        assert_eq!(health.decode_errors(), 3);
+    }
+
+    #[test]
+    fn test_static_desktop_is_not_a_stall() {
+        let start = Instant::now();
+        let mut health = StreamHealth::new(start);
+
+        // One refresh keyframe per interval on an unchanged screen
+        assert_eq!(health.check(start + IDLE_REFRESH_INTERVAL - Duration::from_millis(100)), None);
+        health.on_received(start + IDLE_REFRESH_INTERVAL);
+        health.on_rendered(start + IDLE_REFRESH_INTERVAL);
+        assert_eq!(health.check(start + IDLE_REFRESH_INTERVAL * 2 - Duration::from_millis(100)), None);
+
+        // The refresh is overdue: the stream is gone quiet
+        let late = start + IDLE_REFRESH_INTERVAL * 2 + STALL_AFTER + Duration::from_millis(100);
+        assert_eq!(health.check(late), Some(HealthEvent::Stalled));
+    }
+}
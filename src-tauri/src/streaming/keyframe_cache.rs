@@ -0,0 +1,158 @@
+//! Last keyframe of a shared display, for viewers joining mid-stream
+//!
+//! A viewer that starts watching between keyframes has nothing to decode
+//! the delta frames against until the next IDR. The streaming task keeps the
+//! last keyframe it sent and hands it to new viewers (and to viewers asking
+//! for a keyframe) on their frame stream before anything else. As long as no
+//! delta frame followed it, that keyframe is the current picture and no new
+//! one has to be encoded for everybody.
+//!
+//! H.264 keyframes are also made self-contained here: encoders that send
+//! SPS/PPS only at the start of the stream get the last ones seen put back
+//! in front of each IDR, so a viewer can start on any keyframe.
+
+use crate::encoder::validate::nal_units;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+#[derive(Debug, Default)]
+pub struct KeyframeCache {
+    /// Last SPS and PPS seen, each with a start code
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+    /// Encoded ScreenFrame message of the last keyframe
+    keyframe: Option<Arc<Vec<u8>>>,
+    /// Delta frames sent since `keyframe`
+    deltas_since: u32,
+    /// Viewers the keyframe still has to be sent to
+    pending: HashSet<String>,
+}
+
+impl KeyframeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the parameter sets of an H.264 frame, and put the
+    /// remembered ones in front of an IDR frame that lacks them
+    pub fn complete_h264(&mut self, frame: Vec<u8>) -> Vec<u8> {
+        let mut has_idr = false;
+        let (mut has_sps, mut has_pps) = (false, false);
+        for nal in nal_units(&frame) {
+            let Some(&header) = nal.first() else {
+                continue;
+            };
+            match header & 0x1F {
+                NAL_IDR => has_idr = true,
+                NAL_SPS => {
+                    has_sps = true;
+                    self.sps = Some([&START_CODE[..], nal].concat());
+                }
+                NAL_PPS => {
+                    has_pps = true;
+                    self.pps = Some([&START_CODE[..], nal].concat());
+                }
+                _ => {}
+            }
+        }
+        if !has_idr || (has_sps && has_pps) {
+            return frame;
+        }
+
+        let mut completed = Vec::with_capacity(frame.len() + 64);
+        if !has_sps {
+            completed.extend_from_slice(self.sps.as_deref().unwrap_or_default());
+        }
+        if !has_pps {
+            completed.extend_from_slice(self.pps.as_deref().unwrap_or_default());
+        }
+        completed.extend_from_slice(&frame);
+        completed
+    }
+
+    /// Record a ScreenFrame message that went out to the viewers
+    pub fn sent(&mut self, message: &[u8], keyframe: bool) {
+        if keyframe {
+            self.keyframe = Some(Arc::new(message.to_vec()));
+            self.deltas_since = 0;
+            // Everyone watching has just got it
+            self.pending.clear();
+        } else {
+            self.deltas_since += 1;
+        }
+    }
+
+    /// Forget the keyframe, e.g. when the stream changes size or encoder
+    pub fn clear(&mut self) {
+        self.keyframe = None;
+        self.deltas_since = 0;
+        self.pending.clear();
+    }
+
+    /// Whether the cached keyframe still shows the current picture
+    pub fn is_current(&self) -> bool {
+        self.keyframe.is_some() && self.deltas_since == 0
+    }
+
+    /// Send the cached keyframe to `peer_ip` on the next loop. Returns false
+    /// if there is none.
+    pub fn queue(&mut self, peer_ip: &str) -> bool {
+        if self.keyframe.is_none() {
+            return false;
+        }
+        self.pending.insert(peer_ip.to_string());
+        true
+    }
+
+    /// The keyframe and the viewers waiting for it
+    pub fn take_pending(&mut self) -> Option<(Arc<Vec<u8>>, Vec<String>)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let keyframe = self.keyframe.clone()?;
+        Some((keyframe, self.pending.drain().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPS: &[u8] = &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1F];
+    const PPS: &[u8] = &[0, 0, 0, 1, 0x68, 0xCE, 0x3C];
+    const IDR: &[u8] = &[0, 0, 0, 1, 0x65, 0x88, 0x84];
+    const SLICE: &[u8] = &[0, 0, 0, 1, 0x41, 0x9A, 0x02];
+
+    #[test]
+    fn test_parameter_sets_put_back_on_idr() {
+        let mut cache = KeyframeCache::new();
+        let first = [SPS, PPS, IDR].concat();
+        assert_eq!(cache.complete_h264(first.clone()), first);
+        assert_eq!(cache.complete_h264(SLICE.to_vec()), SLICE);
+        assert_eq!(cache.complete_h264(IDR.to_vec()), [SPS, PPS, IDR].concat());
+    }
+
+    #[test]
+    fn test_keyframe_current_until_delta() {
+        let mut cache = KeyframeCache::new();
+        assert!(!cache.queue("10.0.0.2"));
+
+        cache.sent(b"key", true);
+        assert!(cache.is_current());
+        assert!(cache.queue("10.0.0.2"));
+        let (frame, peers) = cache.take_pending().unwrap();
+        assert_eq!((frame.as_slice(), peers), (&b"key"[..], vec!["10.0.0.2".to_string()]));
+        assert!(cache.take_pending().is_none());
+
+        cache.sent(b"delta", false);
+        assert!(!cache.is_current());
+        cache.clear();
+        assert!(!cache.queue("10.0.0.3"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Glass-to-glass latency on the viewer
+//!
+//! Frames are stamped with the sharer's capture time. The viewer moves that
+//! stamp onto its own clock with the offset from `network::clock` and, once
+//! the frame is presented, records how long ago it was captured. Unlike the
+//! per-stage histograms this covers everything in between, including queues
+//! and the time a frame waits for the next redraw.
+
+use crate::network::clock::{self, ClockEstimate};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Frames the summary covers (about two seconds at 60 fps)
+const WINDOW: usize = 120;
+
+/// Latency over the most recent frames
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub last_ms: u64,
+    pub avg_ms: f64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+    pub samples: usize,
+}
+
+/// Latency of the stream being watched, for `get_stream_stats`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamStats {
+    /// Sharer being watched
+    pub peer_ip: Option<String>,
+    /// Clock offset to the sharer; latency is only measured once known
+    pub clock: Option<ClockEstimate>,
+    pub glass_to_glass: Option<LatencySummary>,
+}
+
+struct Recent {
+    peer_ip: Option<String>,
+    samples: VecDeque<u64>,
+}
+
+static RECENT: Mutex<Recent> = Mutex::new(Recent { peer_ip: None, samples: VecDeque::new() });
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Start measuring a stream from `peer_ip`
+pub fn begin(peer_ip: &str) {
+    let mut recent = RECENT.lock();
+    recent.peer_ip = Some(peer_ip.to_string());
+    recent.samples.clear();
+}
+
+/// The stream from `peer_ip` ended
+pub fn end(peer_ip: &str) {
+    let mut recent = RECENT.lock();
+    if recent.peer_ip.as_deref() == Some(peer_ip) {
+        recent.peer_ip = None;
+        recent.samples.clear();
+    }
+}
+
+/// A frame captured at `captured_at_ms` (local clock) was just presented
+pub fn record_presented(captured_at_ms: u64) {
+    if let Some(latency) = now_ms().checked_sub(captured_at_ms) {
+        let mut recent = RECENT.lock();
+        recent.samples.push_back(latency);
+        if recent.samples.len() > WINDOW {
+            recent.samples.pop_front();
+        }
+    }
+}
+
+/// Latency of the stream being watched
+pub fn stream_stats() -> StreamStats {
+    let recent = RECENT.lock();
+    StreamStats {
+        clock: recent.peer_ip.as_deref().and_then(clock::estimate),
+        peer_ip: recent.peer_ip.clone(),
+        glass_to_glass: summarize(&recent.samples),
+    }
+}
+
+/// Most recent latency, for the on-screen HUD
+pub fn last_ms() -> Option<u64> {
+    RECENT.lock().samples.back().copied()
+}
+
+fn summarize(samples: &VecDeque<u64>) -> Option<LatencySummary> {
+    let &last_ms = samples.back()?;
+    let mut sorted: Vec<u64> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    let p95 = ((sorted.len() * 95).div_ceil(100)).max(1) - 1;
+    Some(LatencySummary {
+        last_ms,
+        avg_ms: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+        p95_ms: sorted[p95],
+        max_ms: sorted[sorted.len() - 1],
+        samples: sorted.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        assert!(summarize(&VecDeque::new()).is_none());
+
+        let samples: VecDeque<u64> = (1..=100).rev().collect();
+        let summary = summarize(&samples).unwrap();
+        assert_eq!(summary.last_ms, 1);
+        assert_eq!(summary.p95_ms, 95);
+        assert_eq!(summary.max_ms, 100);
+        assert!((summary.avg_ms - 50.5).abs() < f64::EPSILON);
+
+        let one: VecDeque<u64> = [42].into();
+        assert_eq!(summarize(&one).unwrap().p95_ms, 42);
+    }
+}
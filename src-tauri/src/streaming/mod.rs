@@ -1,18 +1,48 @@
 //! Video streaming module
 //! Handles capture → encode → send and receive → decode pipelines
 
+pub mod audience;
+pub mod clip;
+pub mod congestion;
+pub mod frame_dump;
+pub mod health;
+pub mod keyframe_cache;
+pub mod latency;
+pub mod mp4;
+pub mod recording;
+pub mod stats;
+pub mod timing;
+
+use crate::capture::convert::FormatPlan;
+use crate::capture::damage::FrameAction;
+use crate::capture::pool;
+use crate::capture::recovery::{CaptureRecovery, RecoveryAction};
 use crate::capture::ScreenCapture;
-use crate::decoder::{DecoderConfig, OutputFormat, VideoDecoder};
-use crate::encoder::{EncoderConfig, EncoderPreset, FrameType};
+use crate::decoder::{DecodeFailures, DecoderConfig, KeyframeGate, OutputFormat, VideoDecoder};
+use crate::encoder::color::ColorSpace;
+use crate::encoder::validate::BitstreamValidator;
+use crate::encoder::{Codec, EncoderConfig, EncoderTuning, FrameType, RegionOfInterest};
 use crate::network::protocol::{self, Message};
 use crate::network::quic::{self, QuicStream};
 use crate::renderer::{RenderFrame, RenderWindow, RenderWindowHandle};
+use health::{HealthEvent, StreamHealth};
+use keyframe_cache::KeyframeCache;
+use timing::Stage;
 use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+
+/// Capture and encoding pause this long after the last viewer leaves
+const IDLE_PAUSE_AFTER: Duration = Duration::from_secs(10);
+
+/// How often a paused pipeline checks for new viewers
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How often the viewer checks its streams for stalls
+const STALL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Streaming errors
 #[derive(Debug, thiserror::Error)]
@@ -34,7 +64,14 @@ pub enum StreamingError {
 pub struct StreamingConfig {
     pub fps: u32,
     pub quality: Quality,
-    pub display_id: u32,
+    /// Displays to share, each with its own capture and encoder
+    /// (empty = the first display)
+    pub display_ids: Vec<u32>,
+    /// Preset, keyframe interval and peak bitrate
+    pub tuning: EncoderTuning,
+    /// Matrix and range frames are converted to YUV with, for the whole
+    /// session
+    pub color_space: ColorSpace,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -60,7 +97,9 @@ impl Default for StreamingConfig {
         Self {
             fps: 30,
             quality: Quality::Auto,
-            display_id: 0,
+            display_ids: Vec::new(),
+            tuning: EncoderTuning::default(),
+            color_space: ColorSpace::default(),
         }
     }
 }
@@ -74,35 +113,91 @@ pub fn get_streaming_manager() -> Arc<RwLock<Option<StreamingManager>>> {
     STREAMING_MANAGER.clone()
 }
 
+/// One shared display and the peers watching it
+struct DisplayStream {
+    /// Id viewers know this stream by: the display it started on
+    display_id: u32,
+    /// Display currently captured; differs from `display_id` after a switch
+    source: AtomicU32,
+    /// Encoded dimensions (may be scaled for OpenH264)
+    width: AtomicU32,
+    height: AtomicU32,
+    /// Codec of the current encoder
+    codec: parking_lot::Mutex<Codec>,
+    /// IPs of peers that requested this display and have not left
+    viewers: RwLock<HashSet<String>>,
+    /// Set while capture is paused because nobody is watching
+    is_paused: AtomicBool,
+    /// Display to move capture to, picked up by the streaming task
+    switch_to: parking_lot::Mutex<Option<u32>>,
+    /// A viewer asked for a keyframe to recover from a stall
+    keyframe_requested: AtomicBool,
+    /// Last keyframe sent, for viewers that join or ask for one
+    keyframes: parking_lot::Mutex<KeyframeCache>,
+    /// New encoder settings, picked up by the streaming task
+    retune: parking_lot::Mutex<Option<EncoderTuning>>,
+}
+
+/// What a viewer needs to start decoding one shared display
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayStreamInfo {
+    pub display_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub codec: Codec,
+    pub color_space: ColorSpace,
+}
+
+/// State shared by the streaming tasks of one sharing session
+struct SessionState {
+    /// Cleared to stop every display; a new session gets a new flag so
+    /// tasks of a stopped session never see it set again
+    is_streaming: Arc<AtomicBool>,
+    frame_count: Arc<AtomicU32>,
+    /// Displays still streaming; the last one to end sends ScreenStop
+    running: AtomicUsize,
+    /// Displays paused for lack of viewers
+    paused: AtomicUsize,
+    total: usize,
+    /// Frozen by the sharer; viewers keep a dimmed last frame
+    frozen: Arc<AtomicBool>,
+}
+
 /// Streaming manager for the sending side
 pub struct StreamingManager {
     is_streaming: Arc<AtomicBool>,
+    /// Set while the sharer has paused the stream
+    is_frozen: Arc<AtomicBool>,
+    displays: Vec<Arc<DisplayStream>>,
     frame_count: Arc<AtomicU32>,
     config: StreamingConfig,
-    width: u32,
-    height: u32,
-    stop_tx: Option<mpsc::Sender<()>>,
+    /// Held while a viewer is checked against the limit and added, so
+    /// viewers admitted at the same time cannot all take the last place
+    joining: parking_lot::Mutex<()>,
 }
 
 impl StreamingManager {
     pub fn new() -> Self {
         Self {
             is_streaming: Arc::new(AtomicBool::new(false)),
+            is_frozen: Arc::new(AtomicBool::new(false)),
+            displays: Vec::new(),
             frame_count: Arc::new(AtomicU32::new(0)),
             config: StreamingConfig::default(),
-            width: 0,
-            height: 0,
-            stop_tx: None,
+            joining: parking_lot::Mutex::new(()),
         }
     }
 
-    /// Start streaming (sync version - spawns background task)
+    /// Start streaming every configured display (sync version - spawns one
+    /// background task per display). `capture` serves the first display;
+    /// the others get captures of their own.
     pub fn start_sync(
         &mut self,
         config: StreamingConfig,
-        mut capture: Box<dyn ScreenCapture>,
+        capture: Box<dyn ScreenCapture>,
     ) -> Result<(), StreamingError> {
-        if self.is_streaming.load(Ordering::SeqCst) {
+        if self.is_streaming() {
             return Ok(()); // Already streaming
         }
 
@@ -113,262 +208,983 @@ impl StreamingManager {
             .get_displays()
             .map_err(|e| StreamingError::CaptureError(e.to_string()))?;
 
-        let display = displays
-            .iter()
-            .find(|d| d.id == config.display_id)
-            .or_else(|| displays.first())
-            .ok_or_else(|| StreamingError::CaptureError("No display found".to_string()))?;
+        let mut selected: Vec<&crate::capture::Display> = Vec::new();
+        for id in &config.display_ids {
+            match displays.iter().find(|d| d.id == *id) {
+                Some(d) if !selected.iter().any(|s| s.id == d.id) => selected.push(d),
+                Some(_) => {}
+                None => log::warn!("Display {} not found, not sharing it", id),
+            }
+        }
+        if selected.is_empty() {
+            selected.push(
+                displays
+                    .first()
+                    .ok_or_else(|| StreamingError::CaptureError("No display found".to_string()))?,
+            );
+        }
+
+        // Prepare every display before spawning anything so a failure on the
+        // first one leaves nothing running
+        let mut capture = Some(capture);
+        let mut pipelines = Vec::new();
+        for display in selected {
+            let capture = match capture.take() {
+                Some(c) => c,
+                None => match crate::capture::create_capture() {
+                    Ok(c) => c,
+                    Err(e) => {
+                        log::warn!("Failed to create capture for display {}: {}", display.id, e);
+                        continue;
+                    }
+                },
+            };
+            match DisplayPipeline::prepare(&config, display, capture) {
+                Ok(p) => pipelines.push(p),
+                // Extra displays are best effort
+                Err(e) if !pipelines.is_empty() => {
+                    log::warn!("Not sharing display {}: {}", display.id, e)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.config = config;
+        self.is_streaming = Arc::new(AtomicBool::new(true));
+        self.is_frozen = Arc::new(AtomicBool::new(false));
+        self.displays = pipelines.iter().map(|p| p.stream.clone()).collect();
+
+        let session = Arc::new(SessionState {
+            is_streaming: self.is_streaming.clone(),
+            frame_count: self.frame_count.clone(),
+            running: AtomicUsize::new(pipelines.len()),
+            paused: AtomicUsize::new(0),
+            total: pipelines.len(),
+            frozen: self.is_frozen.clone(),
+        });
+
+        for pipeline in pipelines {
+            tokio::spawn(pipeline.run(session.clone(), self.config.clone()));
+        }
 
-        self.width = display.width;
-        self.height = display.height;
-        self.config = config.clone();
+        Ok(())
+    }
+
+    /// Stop streaming (sync version)
+    pub fn stop_sync(&mut self) {
+        log::info!("Stopping streaming");
+
+        // Every display task checks the flag before each frame
+        self.is_streaming.store(false, Ordering::SeqCst);
+        audience::displays_ended();
+    }
+
+    /// Check if streaming
+    pub fn is_streaming(&self) -> bool {
+        self.is_streaming.load(Ordering::SeqCst)
+    }
+
+    /// Whether capture is paused on every display because nobody is watching
+    pub fn is_paused(&self) -> bool {
+        !self.displays.is_empty()
+            && self.displays.iter().all(|d| d.is_paused.load(Ordering::SeqCst))
+    }
+
+    /// Freeze or unfreeze every shared display. While frozen viewers see a
+    /// dimmed copy of the last frame and capture stops.
+    pub fn set_frozen(&self, frozen: bool) -> Result<(), StreamingError> {
+        if !self.is_streaming() {
+            return Err(StreamingError::NotStreaming);
+        }
+        if self.is_frozen.swap(frozen, Ordering::SeqCst) != frozen {
+            log::info!("Sharing {}", if frozen { "paused" } else { "resumed" });
+        }
+        Ok(())
+    }
+
+    /// Apply new encoder settings to every shared display. Each display
+    /// reopens its encoder and restarts its viewers' decoders.
+    pub fn set_tuning(&mut self, tuning: EncoderTuning) -> Result<(), StreamingError> {
+        if !self.is_streaming() {
+            return Err(StreamingError::NotStreaming);
+        }
+        if self.config.tuning != tuning {
+            log::info!("Encoder settings changed: {:?}", tuning);
+            self.config.tuning = tuning;
+            for display in &self.displays {
+                *display.retune.lock() = Some(tuning);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the sharer paused the stream
+    pub fn is_frozen(&self) -> bool {
+        self.is_frozen.load(Ordering::SeqCst)
+    }
+
+    /// Ids of the displays being shared
+    pub fn display_ids(&self) -> Vec<u32> {
+        self.displays.iter().map(|d| d.display_id).collect()
+    }
+
+    /// Record a peer that requested a display (resumes it if paused).
+    /// Display 0 means the first shared display. Returns `None` if the
+    /// display is not shared, and the reason to refuse the peer with when
+    /// `max_viewers` are already watching.
+    pub fn add_viewer(
+        &self,
+        peer_ip: &str,
+        display_id: u32,
+        max_viewers: Option<u32>,
+    ) -> Result<Option<DisplayStreamInfo>, protocol::ScreenRejectReason> {
+        let Some(display) = (if display_id == 0 {
+            self.displays.first()
+        } else {
+            self.displays.iter().find(|d| d.display_id == display_id)
+        }) else {
+            return Ok(None);
+        };
+
+        let _joining = self.joining.lock();
+        if let Some(max_viewers) = max_viewers.filter(|&max| !self.has_room_for(peer_ip, max)) {
+            return Err(protocol::ScreenRejectReason::StreamFull { max_viewers });
+        }
+
+        if display.viewers.write().insert(peer_ip.to_string()) {
+            audience::started(peer_ip, audience::Stream::Display(display.display_id));
+            log::info!(
+                "Viewer {} joined display {} ({} watching)",
+                peer_ip,
+                display.display_id,
+                self.viewer_count()
+            );
+            // Start the newcomer on the last keyframe, and encode a new one
+            // if frames were sent on top of it since
+            let mut keyframes = display.keyframes.lock();
+            if !keyframes.queue(peer_ip) || !keyframes.is_current() {
+                display.keyframe_requested.store(true, Ordering::SeqCst);
+            }
+        }
+
+        Ok(Some(DisplayStreamInfo {
+            display_id: display.display_id,
+            width: display.width.load(Ordering::SeqCst),
+            height: display.height.load(Ordering::SeqCst),
+            fps: self.config.fps,
+            codec: *display.codec.lock(),
+            color_space: self.config.color_space,
+        }))
+    }
+
+    /// Codec a display (0 = the first shared display) is encoded with
+    pub fn codec(&self, display_id: u32) -> Option<Codec> {
+        let display = if display_id == 0 {
+            self.displays.first()
+        } else {
+            self.displays.iter().find(|d| d.display_id == display_id)
+        }?;
+        Some(*display.codec.lock())
+    }
+
+    /// Get `peer_ip` a keyframe of a display (0 = the first shared display):
+    /// the last one sent if nothing changed since, else a new one for all
+    pub fn request_keyframe(&self, peer_ip: &str, display_id: u32) -> bool {
+        let display = if display_id == 0 {
+            self.displays.first()
+        } else {
+            self.displays.iter().find(|d| d.display_id == display_id)
+        };
+        match display {
+            Some(display) => {
+                let mut keyframes = display.keyframes.lock();
+                if !(keyframes.is_current() && keyframes.queue(peer_ip)) {
+                    display.keyframe_requested.store(true, Ordering::SeqCst);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move a running stream to another display without tearing it down:
+    /// viewers keep their stream and window and get a new ScreenStart.
+    /// `from` picks the stream by the id viewers know it by (default: the first).
+    pub fn switch_display(&self, from: Option<u32>, display_id: u32) -> Result<(), StreamingError> {
+        if !self.is_streaming() {
+            return Err(StreamingError::NotStreaming);
+        }
+        let stream = match from {
+            Some(id) => self.displays.iter().find(|d| d.display_id == id),
+            None => self.displays.first(),
+        }
+        .ok_or_else(|| StreamingError::CaptureError(format!("Display {:?} is not shared", from)))?;
+
+        let busy = self.displays.iter().any(|d| {
+            !Arc::ptr_eq(d, stream) && d.source.load(Ordering::SeqCst) == display_id
+        });
+        if busy {
+            return Err(StreamingError::CaptureError(format!(
+                "Display {} is already shared",
+                display_id
+            )));
+        }
+
+        log::info!("Switching stream {} to display {}", stream.display_id, display_id);
+        *stream.switch_to.lock() = Some(display_id);
+        Ok(())
+    }
+
+    /// Forget a peer that stopped watching one display, or every display
+    /// when `display_id` is `None`
+    pub fn remove_viewer(&self, peer_ip: &str, display_id: Option<u32>) -> bool {
+        let mut removed = false;
+        for display in &self.displays {
+            if display_id.is_none_or(|id| id == display.display_id) && display.viewers.write().remove(peer_ip) {
+                audience::ended(peer_ip, audience::Stream::Display(display.display_id));
+                removed = true;
+            }
+        }
+        if removed {
+            log::info!("Viewer {} left ({} watching)", peer_ip, self.viewer_count());
+        }
+        removed
+    }
+
+    /// Whether `peer_ip` may start watching under a limit of `max_viewers`:
+    /// peers already watching a display may always add another
+    pub fn has_room_for(&self, peer_ip: &str, max_viewers: u32) -> bool {
+        let watching = self.displays.iter().any(|d| d.viewers.read().contains(peer_ip));
+        watching || self.viewer_count() < max_viewers as usize
+    }
+
+    /// Number of peers currently watching at least one display
+    pub fn viewer_count(&self) -> usize {
+        let mut viewers = HashSet::new();
+        for display in &self.displays {
+            viewers.extend(display.viewers.read().iter().cloned());
+        }
+        viewers.len()
+    }
+
+    /// Get frame count (all displays)
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count.load(Ordering::Relaxed)
+    }
+
+    /// Get current config
+    pub fn config(&self) -> &StreamingConfig {
+        &self.config
+    }
+}
+
+/// Encoder and per-display frame processing for one captured display
+struct Encoding {
+    encoder: Box<dyn crate::encoder::VideoEncoder>,
+    formats: FormatPlan,
+    redactor: Option<crate::capture::redaction::NotificationRedactor>,
+    cursor: Option<crate::capture::cursor::CursorOverlay>,
+    /// Encoded dimensions (may be scaled for OpenH264)
+    width: u32,
+    height: u32,
+}
+
+impl Encoding {
+    /// Start `capture` on `display` and set up an encoder for it
+    fn open(
+        config: &StreamingConfig,
+        display: &crate::capture::Display,
+        capture: &mut dyn ScreenCapture,
+    ) -> Result<Self, StreamingError> {
+        let redactor = crate::commands::redact_notifications_enabled().then(|| {
+            crate::capture::redaction::NotificationRedactor::for_display(
+                display.width,
+                display.height,
+                display.scale_factor,
+            )
+        });
+        let cursor = crate::commands::show_cursor_enabled()
+            .then(|| crate::capture::cursor::CursorOverlay::new(display.scale_factor));
 
         // Start capture
         capture
-            .start(config.display_id)
+            .start(display.id)
             .map_err(|e| StreamingError::CaptureError(e.to_string()))?;
 
         // Create encoder
-        let mut encoder = crate::encoder::create_encoder()
-            .map_err(|e| StreamingError::EncoderError(e.to_string()))?;
+        let mut encoder = match crate::encoder::create_encoder() {
+            Ok(e) => e,
+            Err(e) => {
+                let _ = capture.stop();
+                return Err(StreamingError::EncoderError(e.to_string()));
+            }
+        };
+
+        // Redaction and cursor drawing work on BGRA pixels
+        let formats = FormatPlan::negotiate(
+            capture,
+            encoder.as_ref(),
+            redactor.is_some() || cursor.is_some(),
+            config.color_space,
+        );
 
         let encoder_config = EncoderConfig {
-            width: self.width,
-            height: self.height,
+            width: display.width,
+            height: display.height,
             fps: config.fps,
             bitrate: config.quality.bitrate(),
-            max_bitrate: config.quality.bitrate() * 2,
-            keyframe_interval: config.fps, // 1 keyframe per second
-            preset: EncoderPreset::UltraFast,
+            max_bitrate: config.tuning.max_bitrate(config.quality.bitrate()),
+            keyframe_interval: config.tuning.keyframe_interval(config.fps),
+            preset: config.tuning.preset,
+            input_format: formats.encoder,
+            crf: None,
+            color_space: config.color_space,
         };
 
-        encoder
-            .init(encoder_config)
-            .map_err(|e| StreamingError::EncoderError(e.to_string()))?;
+        if let Err(e) = encoder.init(encoder_config) {
+            let _ = capture.stop();
+            return Err(StreamingError::EncoderError(e.to_string()));
+        }
 
         // Get actual encoding dimensions (may be scaled for OpenH264)
-        let (encode_width, encode_height) = encoder
+        let (width, height) = encoder
             .get_dimensions()
-            .unwrap_or((self.width, self.height));
+            .unwrap_or((display.width, display.height));
 
         log::info!(
-            "Encoder initialized: {} ({}x{} @ {} fps)",
+            "Encoder initialized for display {}: {} ({}x{} @ {} fps)",
+            display.id,
             encoder.info(),
-            encode_width,
-            encode_height,
+            width,
+            height,
             config.fps
         );
 
-        // Create stop channel
-        let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
-        self.stop_tx = Some(stop_tx);
+        Ok(Self { encoder, formats, redactor, cursor, width, height })
+    }
 
-        // Set streaming flag
-        self.is_streaming.store(true, Ordering::SeqCst);
+    /// Move `capture` from display `from` to display `to` and open a new
+    /// encoder for it. On failure capture goes back to `from`.
+    fn switch(
+        config: &StreamingConfig,
+        capture: &mut dyn ScreenCapture,
+        from: u32,
+        to: u32,
+    ) -> Result<Self, StreamingError> {
+        let display = capture
+            .get_displays()
+            .map_err(|e| StreamingError::CaptureError(e.to_string()))?
+            .into_iter()
+            .find(|d| d.id == to)
+            .ok_or_else(|| StreamingError::CaptureError(format!("Display {} not found", to)))?;
+
+        let _ = capture.stop();
+        match Self::open(config, &display, capture) {
+            Ok(encoding) => Ok(encoding),
+            Err(e) => {
+                let _ = capture.start(from);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Capture and encoder for one shared display, ready to run
+struct DisplayPipeline {
+    stream: Arc<DisplayStream>,
+    capture: Box<dyn ScreenCapture>,
+    encoding: Encoding,
+}
 
-        let is_streaming = self.is_streaming.clone();
-        let frame_count = self.frame_count.clone();
+impl DisplayPipeline {
+    /// Start capture on `display` and set up its encoder
+    fn prepare(
+        config: &StreamingConfig,
+        display: &crate::capture::Display,
+        mut capture: Box<dyn ScreenCapture>,
+    ) -> Result<Self, StreamingError> {
+        let encoding = Encoding::open(config, display, capture.as_mut())?;
+
+        Ok(Self {
+            stream: Arc::new(DisplayStream {
+                display_id: display.id,
+                source: AtomicU32::new(display.id),
+                width: AtomicU32::new(encoding.width),
+                height: AtomicU32::new(encoding.height),
+                codec: parking_lot::Mutex::new(encoding.encoder.codec()),
+                viewers: RwLock::new(HashSet::new()),
+                is_paused: AtomicBool::new(false),
+                switch_to: parking_lot::Mutex::new(None),
+                keyframe_requested: AtomicBool::new(false),
+                keyframes: parking_lot::Mutex::new(KeyframeCache::new()),
+                retune: parking_lot::Mutex::new(None),
+            }),
+            capture,
+            encoding,
+        })
+    }
+
+    /// Capture, encode and send this display until the session stops
+    async fn run(self, session: Arc<SessionState>, mut config: StreamingConfig) {
+        let Self { stream: display, mut capture, encoding } = self;
+        let Encoding { mut encoder, mut formats, mut redactor, mut cursor, .. } = encoding;
+        let display_id = display.display_id;
+        let mut source = display_id;
         let fps = config.fps;
-        // Use encoded dimensions (may be scaled for OpenH264)
-        let width = encode_width;
-        let height = encode_height;
-
-        // Spawn streaming task
-        tokio::spawn(async move {
-            // Send ScreenStart to all connected peers via control streams
-            let start_msg = Message::ScreenStart {
-                width,
-                height,
-                fps: fps as u8,
-                codec: "h264".to_string(),
-            };
 
-            if let Ok(encoded) = protocol::encode(&start_msg) {
-                let _ = quic::broadcast_message(&encoded).await;
+        let frame_interval = Duration::from_micros(1_000_000 / fps as u64);
+        let mut last_frame_time = std::time::Instant::now();
+        let mut sequence: u32 = 0;
+        let mut validator = BitstreamValidator::new();
+        let mut damage_tracker = crate::capture::damage::DamageTracker::new();
+        let mut idle_since: Option<Instant> = None;
+        let mut recovery = CaptureRecovery::new();
+        let mut archive =
+            crate::capture::archive::SnapshotArchive::start(&format!("display-{}", display_id));
+
+        // While frozen: the dimmed keyframe, who has it, and whether capture
+        // was stopped for the pause
+        let mut frozen = false;
+        let mut freeze_msg: Option<Vec<u8>> = None;
+        let mut freeze_sent_to: HashSet<String> = HashSet::new();
+        let mut freeze_stopped_capture = false;
+
+        // Maintain persistent streams per peer for efficient frame delivery
+        // Instead of opening a new stream for every frame (30fps = 30 streams/sec),
+        // reuse persistent streams that stay open for the duration of streaming
+        let mut peer_streams: HashMap<String, crate::network::quic::QuicStream> = HashMap::new();
+
+        loop {
+            if !session.is_streaming.load(Ordering::SeqCst) {
+                log::info!("Streaming of display {} stopped by request", display_id);
+                break;
             }
 
-            let frame_interval = Duration::from_micros(1_000_000 / fps as u64);
-            let mut last_frame_time = std::time::Instant::now();
-            let mut sequence: u32 = 0;
-
-            // Maintain persistent streams per peer for efficient frame delivery
-            // Instead of opening a new stream for every frame (30fps = 30 streams/sec),
-            // reuse persistent streams that stay open for the duration of streaming
-            let mut peer_streams: HashMap<String, crate::network::quic::QuicStream> = HashMap::new();
+            // Pause capture and encoding while nobody is watching
+            if display.viewers.read().is_empty() {
+                let since = *idle_since.get_or_insert_with(Instant::now);
+                if !display.is_paused.load(Ordering::SeqCst) && since.elapsed() >= IDLE_PAUSE_AFTER {
+                    log::info!(
+                        "No viewers on display {} for {:?}, pausing capture",
+                        display_id,
+                        IDLE_PAUSE_AFTER
+                    );
+                    let _ = capture.stop();
+                    for (_, mut stream) in peer_streams.drain() {
+                        let _ = stream.finish().await;
+                    }
+                    // The screen may look different by the time someone watches again
+                    display.keyframes.lock().clear();
+                    display.is_paused.store(true, Ordering::SeqCst);
+                    if session.paused.fetch_add(1, Ordering::SeqCst) + 1 == session.total {
+                        emit_paused(true);
+                    }
+                }
+                if display.is_paused.load(Ordering::SeqCst) {
+                    tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                    continue;
+                }
+            } else {
+                idle_since = None;
+                if display.is_paused.load(Ordering::SeqCst) {
+                    if let Err(e) = capture.start(source) {
+                        log::warn!("Failed to resume capture of display {}: {}", source, e);
+                        tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                        continue;
+                    }
+                    log::info!("Viewer joined, resuming capture of display {}", display_id);
+                    freeze_stopped_capture = false;
+                    // The new viewer needs a keyframe to start decoding
+                    damage_tracker = crate::capture::damage::DamageTracker::new();
+                    encoder.request_keyframe();
+                    display.is_paused.store(false, Ordering::SeqCst);
+                    if session.paused.fetch_sub(1, Ordering::SeqCst) == session.total {
+                        emit_paused(false);
+                    }
+                }
+            }
 
-            loop {
-                // Check for stop signal
-                if stop_rx.try_recv().is_ok() {
-                    log::info!("Streaming stopped by request");
-                    break;
+            // Paused by the sharer: show viewers (including ones joining
+            // later) a dimmed last frame and stop capturing
+            if session.frozen.load(Ordering::SeqCst) {
+                if !frozen {
+                    frozen = true;
+                    freeze_msg = None;
+                    freeze_sent_to.clear();
+                    // Joiners get the dimmed frame instead
+                    display.keyframes.lock().clear();
+                }
+                let viewers = display.viewers.read().clone();
+                if freeze_msg.is_none() && !viewers.is_empty() {
+                    if freeze_stopped_capture && capture.start(source).is_ok() {
+                        freeze_stopped_capture = false;
+                    }
+                    if !freeze_stopped_capture {
+                        freeze_msg = freeze_frame(
+                            capture.as_mut(),
+                            encoder.as_mut(),
+                            &formats,
+                            redactor.as_ref(),
+                            display_id,
+                            sequence,
+                        );
+                        sequence = sequence.wrapping_add(1);
+                    }
+                }
+                if let Some(ref msg) = freeze_msg {
+                    if !viewers.is_subset(&freeze_sent_to) {
+                        broadcast_frame(msg, &mut peer_streams, &viewers).await;
+                        freeze_sent_to = viewers;
+                    }
+                    if !freeze_stopped_capture {
+                        let _ = capture.stop();
+                        freeze_stopped_capture = true;
+                    }
                 }
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                continue;
+            }
+            if frozen {
+                if freeze_stopped_capture {
+                    if let Err(e) = capture.start(source) {
+                        log::warn!("Failed to resume capture of display {}: {}", source, e);
+                        tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+                        continue;
+                    }
+                    freeze_stopped_capture = false;
+                }
+                frozen = false;
+                freeze_msg = None;
+                // Replace the dimmed frame right away
+                encoder.request_keyframe();
+                damage_tracker = crate::capture::damage::DamageTracker::new();
+            }
 
-                if !is_streaming.load(Ordering::SeqCst) {
-                    break;
+            // Move capture to another display if the sharer asked for it, or
+            // reopen the current one with new encoder settings
+            let retune = display.retune.lock().take();
+            if let Some(tuning) = retune {
+                config.tuning = tuning;
+            }
+            let target = display.switch_to.lock().take().filter(|&t| t != source);
+            if let Some(target) = target.or(retune.map(|_| source)) {
+                match Encoding::switch(&config, capture.as_mut(), source, target) {
+                    Ok(next) => {
+                        log::info!(
+                            "Stream {} now shows display {} ({}x{}, {:?})",
+                            display_id,
+                            target,
+                            next.width,
+                            next.height,
+                            config.tuning
+                        );
+                        source = target;
+                        display.source.store(target, Ordering::SeqCst);
+                        display.width.store(next.width, Ordering::SeqCst);
+                        display.height.store(next.height, Ordering::SeqCst);
+                        *display.codec.lock() = next.encoder.codec();
+                        encoder = next.encoder;
+                        formats = next.formats;
+                        redactor = next.redactor;
+                        cursor = next.cursor;
+                        damage_tracker = crate::capture::damage::DamageTracker::new();
+                        recovery = CaptureRecovery::new();
+                        display.keyframes.lock().clear();
+
+                        // Sent on the frame streams so viewers reinitialize
+                        // their decoder before the first frame of the new display
+                        let start_msg = Message::ScreenStart {
+                            display_id,
+                            width: next.width,
+                            height: next.height,
+                            fps: fps as u8,
+                            codec: encoder.codec().name().to_string(),
+                            color_space: config.color_space.name().to_string(),
+                        };
+                        if let Ok(encoded) = protocol::encode(&start_msg) {
+                            let viewers = display.viewers.read().clone();
+                            broadcast_frame(&encoded, &mut peer_streams, &viewers).await;
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to reopen display {}: {}", target, e),
                 }
+            }
 
-                // Frame rate limiting
-                let elapsed = last_frame_time.elapsed();
-                if elapsed < frame_interval {
-                    tokio::time::sleep(frame_interval - elapsed).await;
+            // Viewers that joined or asked for a keyframe get the last one first
+            let pending = display.keyframes.lock().take_pending();
+            if let Some((keyframe, peers)) = pending {
+                for peer in peers {
+                    send_to_viewer(&keyframe, &peer, &mut peer_streams).await;
                 }
-                last_frame_time = std::time::Instant::now();
+            }
 
-                // Capture frame
-                let frame = match capture.capture_frame() {
-                    Ok(f) => f,
-                    Err(e) => {
-                        log::warn!("Capture error: {}", e);
+            // A stalled viewer needs a keyframe, sent even if nothing
+            // changed, and so does a recording that just started
+            if display.keyframe_requested.swap(false, Ordering::SeqCst) | recording::keyframe_wanted() {
+                log::debug!("Keyframe requested for display {}", display_id);
+                encoder.request_keyframe();
+                damage_tracker = crate::capture::damage::DamageTracker::new();
+            }
+
+            // Frame rate limiting, slower while the screen is static
+            let elapsed = last_frame_time.elapsed();
+            let frame_interval = damage_tracker.capture_interval(frame_interval);
+            if elapsed < frame_interval {
+                tokio::time::sleep(frame_interval - elapsed).await;
+            }
+            last_frame_time = std::time::Instant::now();
+
+            // Capture frame
+            let capture_start = Instant::now();
+            let mut frame = match capture.capture_frame() {
+                Ok(f) => {
+                    timing::record(Stage::Capture, capture_start.elapsed());
+                    recovery.on_success();
+                    formats.prepare(f)
+                }
+                Err(e) => match recovery.on_error(&e) {
+                    RecoveryAction::Skip => {
+                        log::debug!("Capture error: {}", e);
                         continue;
                     }
-                };
-
-                // Get timestamp
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map(|d| d.as_millis() as u64)
-                    .unwrap_or(0);
-
-                // Encode frame
-                let encoded = match encoder.encode(&frame.data, timestamp) {
-                    Ok(e) => e,
-                    Err(e) => {
-                        log::warn!("Encode error: {}", e);
+                    RecoveryAction::Restart => {
+                        let _ = capture.stop();
+                        if let Err(e) = capture.start(source) {
+                            log::warn!("Failed to restart capture: {}", e);
+                            if recovery.on_error(&e) == RecoveryAction::Stop {
+                                break;
+                            }
+                        }
                         continue;
                     }
-                };
+                    RecoveryAction::Stop => break,
+                },
+            };
 
-                // Create ScreenFrame message
-                let frame_msg = Message::ScreenFrame {
-                    timestamp,
-                    frame_type: match encoded.frame_type {
-                        FrameType::KeyFrame => protocol::FrameType::KeyFrame,
-                        FrameType::Delta => protocol::FrameType::DeltaFrame,
-                    },
-                    sequence,
-                    data: encoded.data,
-                };
+            if let Some(ref redactor) = redactor {
+                redactor.apply(&mut frame);
+            }
+            if let Some(ref cursor) = cursor {
+                if let Some((x, y)) = capture.cursor_position() {
+                    cursor.draw(&mut frame, x, y);
+                }
+            }
+            if let Some(ref mut archive) = archive {
+                archive.offer(&frame);
+            }
 
-                // Send to all connected peers using persistent streams
-                if let Ok(encoded_msg) = protocol::encode(&frame_msg) {
-                    broadcast_frame(&encoded_msg, &mut peer_streams).await;
+            // Skip unchanged frames on a static desktop (NV12 frames are
+            // not diffed, so rely on the backend's hint when it has one)
+            let damage = if cursor.is_none() && capture.last_frame_changed() == Some(false) {
+                crate::capture::damage::Damage { changed_tiles: 0, total_tiles: 0 }
+            } else {
+                damage_tracker.update(&frame)
+            };
+            match damage_tracker.decide(&damage) {
+                FrameAction::Skip => {
+                    frame.recycle();
+                    continue;
                 }
+                FrameAction::Refresh => encoder.request_keyframe(),
+                FrameAction::Encode => {}
+            }
+
+            // Get timestamp
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+
+            // Keep the area the presenter points at sharp
+            if encoder.supports_roi() {
+                let roi = crate::commands::cursor_roi_enabled()
+                    .then(|| capture.cursor_position())
+                    .flatten()
+                    .and_then(|(x, y)| RegionOfInterest::around_cursor(x, y, frame.width, frame.height));
+                encoder.set_roi(roi);
+            }
 
-                sequence = sequence.wrapping_add(1);
-                frame_count.fetch_add(1, Ordering::Relaxed);
+            // Encode frame
+            let (width, height) = (frame.width, frame.height);
+            let input = formats.for_encoder(&frame.data, frame.width, frame.height, frame.format);
+            let encode_start = Instant::now();
+            let result = encoder.encode(&input, timestamp);
+            let encode_time = encode_start.elapsed();
+            timing::record(Stage::Encode, encode_time);
+            pool::recycle_cow(input);
+            frame.recycle();
+            let mut encoded = match result {
+                Ok(e) => {
+                    stats::frame_encoded(e.data.len(), encode_time);
+                    e
+                }
+                Err(e) => {
+                    log::warn!("Encode error: {}", e);
+                    continue;
+                }
+            };
+            if encoder.codec() == Codec::H264 {
+                if encoded.frame_type == FrameType::KeyFrame {
+                    encoded.data = display.keyframes.lock().complete_h264(std::mem::take(&mut encoded.data));
+                }
+                if crate::commands::bitstream_validation_enabled() {
+                    encoded.data = validator.process(std::mem::take(&mut encoded.data), encoder.info());
+                }
+            }
+            let is_keyframe = encoded.frame_type == FrameType::KeyFrame;
+            if encoder.codec() == Codec::H264 {
+                recording::record_shared(display_id, &encoded.data, is_keyframe, width, height);
             }
 
-            // Clean up: finish all persistent streams
-            for (peer, mut stream) in peer_streams.drain() {
-                log::debug!("Closing persistent stream to {}", peer);
-                let _ = stream.finish().await;
+            // Create ScreenFrame message
+            let frame_msg = Message::ScreenFrame {
+                display_id,
+                timestamp,
+                frame_type: match encoded.frame_type {
+                    FrameType::KeyFrame => protocol::FrameType::KeyFrame,
+                    FrameType::Delta => protocol::FrameType::DeltaFrame,
+                },
+                sequence,
+                data: encoded.data,
+            };
+
+            // Send to this display's viewers using persistent streams
+            if let Ok(encoded_msg) = protocol::encode(&frame_msg) {
+                let viewers = display.viewers.read().clone();
+                let send_start = Instant::now();
+                let sent = broadcast_frame(&encoded_msg, &mut peer_streams, &viewers).await;
+                timing::record(Stage::Send, send_start.elapsed());
+                stats::frame_sent(encoded_msg.len(), sent);
+                display.keyframes.lock().sent(&encoded_msg, is_keyframe);
             }
 
+            sequence = sequence.wrapping_add(1);
+            session.frame_count.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Clean up: finish all persistent streams
+        for (peer, mut stream) in peer_streams.drain() {
+            log::debug!("Closing persistent stream to {}", peer);
+            let _ = stream.finish().await;
+        }
+
+        if !display.is_paused.swap(false, Ordering::SeqCst) && !freeze_stopped_capture {
             let _ = capture.stop();
-            is_streaming.store(false, Ordering::SeqCst);
+        }
+        let viewers: Vec<String> = display.viewers.write().drain().collect();
+
+        if session.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // Last display: the whole session is over
+            session.is_streaming.store(false, Ordering::SeqCst);
+            crate::app_state::changed(crate::app_state::Slice::Sharing);
 
             // Send ScreenStop to all peers via control streams
-            let stop_msg = Message::ScreenStop;
+            let stop_msg = Message::ScreenStop { display_id: None };
             if let Ok(encoded) = protocol::encode(&stop_msg) {
                 let _ = quic::broadcast_message(&encoded).await;
             }
-
             log::info!("Streaming task ended");
-        });
-
-        Ok(())
+        } else {
+            // Only this display failed; its viewers close just its window
+            let stop_msg = Message::ScreenStop { display_id: Some(display_id) };
+            if let Ok(encoded) = protocol::encode(&stop_msg) {
+                for peer in viewers {
+                    let _ = quic::send_to_peer(&peer, &encoded).await;
+                }
+            }
+            log::info!("Streaming task for display {} ended", display_id);
+        }
     }
+}
 
-    /// Stop streaming (sync version)
-    pub fn stop_sync(&mut self) {
-        log::info!("Stopping streaming");
-
-        self.is_streaming.store(false, Ordering::SeqCst);
-
-        // Send stop signal (non-blocking)
-        if let Some(tx) = self.stop_tx.take() {
-            let _ = tx.try_send(());
+/// Capture one frame, dim it and encode it as a keyframe for viewers to show
+/// while the sharer has paused. Returns the encoded ScreenFrame message.
+fn freeze_frame(
+    capture: &mut dyn ScreenCapture,
+    encoder: &mut dyn crate::encoder::VideoEncoder,
+    formats: &FormatPlan,
+    redactor: Option<&crate::capture::redaction::NotificationRedactor>,
+    display_id: u32,
+    sequence: u32,
+) -> Option<Vec<u8>> {
+    let mut frame = match capture.capture_frame() {
+        Ok(f) => formats.prepare(f),
+        Err(e) => {
+            log::debug!("No frame to freeze yet: {}", e);
+            return None;
         }
+    };
+    if let Some(redactor) = redactor {
+        redactor.apply(&mut frame);
     }
+    frame.dim();
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+
+    encoder.request_keyframe();
+    let input = formats.for_encoder(&frame.data, frame.width, frame.height, frame.format);
+    let result = encoder.encode(&input, timestamp);
+    pool::recycle_cow(input);
+    frame.recycle();
+    let encoded = match result {
+        Ok(e) => e,
+        Err(e) => {
+            log::warn!("Failed to encode paused frame: {}", e);
+            return None;
+        }
+    };
 
-    /// Check if streaming
-    pub fn is_streaming(&self) -> bool {
-        self.is_streaming.load(Ordering::SeqCst)
-    }
+    protocol::encode(&Message::ScreenFrame {
+        display_id,
+        timestamp,
+        frame_type: match encoded.frame_type {
+            FrameType::KeyFrame => protocol::FrameType::KeyFrame,
+            FrameType::Delta => protocol::FrameType::DeltaFrame,
+        },
+        sequence,
+        data: encoded.data,
+    })
+    .ok()
+}
 
-    /// Get frame count
-    pub fn frame_count(&self) -> u32 {
-        self.frame_count.load(Ordering::Relaxed)
+/// Tell the sharer's UI that capture was paused or resumed for lack of viewers
+fn emit_paused(paused: bool) {
+    #[derive(serde::Serialize, Clone)]
+    struct StreamingPausedEvent {
+        paused: bool,
     }
-
-    /// Get current config
-    pub fn config(&self) -> &StreamingConfig {
-        &self.config
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit("streaming-paused", StreamingPausedEvent { paused });
     }
+}
 
-    /// Get dimensions
-    pub fn dimensions(&self) -> (u32, u32) {
-        (self.width, self.height)
-    }
+/// Remove a departed viewer from the local sharing session, if any.
+/// `None` removes the viewer from every shared display.
+pub fn remove_viewer(peer_ip: &str, display_id: Option<u32>) -> bool {
+    STREAMING_MANAGER
+        .read()
+        .as_ref()
+        .map(|m| m.remove_viewer(peer_ip, display_id))
+        .unwrap_or(false)
 }
 
-/// Viewer session for the receiving side
-/// Uses native wgpu window for efficient GPU rendering
-pub struct ViewerSession {
-    peer_ip: String,
-    peer_name: String,
+/// Decoder and native render window for one display of a peer's screen
+struct DisplayView {
     decoder: Box<dyn VideoDecoder>,
+    codec: Codec,
+    color_space: ColorSpace,
+    /// Consecutive decode errors, for falling back to software decoding
+    failures: DecodeFailures,
+    /// Holds back delta frames until the decoder has a keyframe
+    gate: KeyframeGate,
     window_handle: Option<RenderWindowHandle>,
     width: u32,
     height: u32,
     is_active: bool,
     frame_count: u32,
+    health: StreamHealth,
+    title: String,
+    /// The sharer paused; the window shows the dimmed frame it sent
+    paused: bool,
 }
 
-impl ViewerSession {
-    pub fn new(peer_ip: String, peer_name: String) -> Result<Self, StreamingError> {
-        let decoder = crate::decoder::create_decoder()
-            .map_err(|e| StreamingError::DecoderError(e.to_string()))?;
+impl DisplayView {
+    fn close(&mut self) {
+        self.is_active = false;
+        if let Some(ref handle) = self.window_handle {
+            handle.close();
+        }
+        self.window_handle = None;
+    }
 
-        Ok(Self {
+    /// Replace a hardware decoder that keeps failing with a software one.
+    /// Returns false if there was nothing to fall back to.
+    fn fall_back_to_software(&mut self) -> bool {
+        if !self.decoder.is_hardware() {
+            return false;
+        }
+        let config = DecoderConfig {
+            width: self.width,
+            height: self.height,
+            output_format: OutputFormat::BGRA,
+            color_space: self.color_space,
+        };
+        let decoder = crate::decoder::create_software_decoder_for(self.codec).and_then(|mut decoder| {
+            decoder.init(config)?;
+            Ok(decoder)
+        });
+        match decoder {
+            Ok(decoder) => {
+                log::warn!(
+                    "{} keeps failing, switching to {}",
+                    self.decoder.info(),
+                    decoder.info()
+                );
+                self.decoder = decoder;
+                self.gate = KeyframeGate::default();
+                true
+            }
+            Err(e) => {
+                log::error!("Software decoder fallback failed: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Viewer session for the receiving side
+/// Uses one native wgpu window per watched display for efficient GPU rendering
+pub struct ViewerSession {
+    peer_ip: String,
+    peer_name: String,
+    views: HashMap<u32, DisplayView>,
+}
+
+impl ViewerSession {
+    pub fn new(peer_ip: String, peer_name: String) -> Self {
+        Self {
             peer_ip,
             peer_name,
-            decoder,
-            window_handle: None,
-            width: 0,
-            height: 0,
-            is_active: false,
-            frame_count: 0,
-        })
+            views: HashMap::new(),
+        }
     }
 
-    /// Handle ScreenStart message - creates native render window
+    /// Handle ScreenStart message - creates a native render window for the display
     pub fn handle_screen_start(
         &mut self,
+        display_id: u32,
         width: u32,
         height: u32,
         _fps: u8,
-        _codec: &str,
+        codec: &str,
+        color_space: &str,
     ) -> Result<(), StreamingError> {
         log::info!(
-            "Viewer session started: {}x{} from {}",
+            "Viewer session started: display {} {}x{} from {}",
+            display_id,
             width,
             height,
             self.peer_ip
         );
 
-        self.width = width;
-        self.height = height;
-
         // Initialize decoder with BGRA output for direct GPU upload
-        log::debug!("Initializing decoder for {}x{} BGRA output", width, height);
+        log::debug!("Initializing {} decoder for {}x{} BGRA output", codec, width, height);
+        let codec = Codec::from_name(codec).unwrap_or_default();
+        let color_space = ColorSpace::from_name(color_space).unwrap_or_default();
+        recording::stream_started(&self.peer_ip, display_id, width, height, codec == Codec::H264);
+        let mut decoder = crate::decoder::create_decoder_for(codec)
+            .map_err(|e| StreamingError::DecoderError(e.to_string()))?;
         let config = DecoderConfig {
             width,
             height,
             output_format: OutputFormat::BGRA,
+            color_space,
         };
 
-        self.decoder
+        decoder
             .init(config)
             .map_err(|e| {
                 log::error!("Decoder init failed: {}", e);
@@ -376,122 +1192,306 @@ impl ViewerSession {
             })?;
         log::debug!("Decoder initialized successfully");
 
-        // Create native render window
-        let title = format!("{} 的屏幕 ({})", self.peer_name, self.peer_ip);
-        log::debug!("Creating native render window: '{}' ({}x{})", title, width, height);
-        let window_handle = RenderWindow::create(&title, width, height)
-            .map_err(|e| {
-                log::error!("RenderWindow::create failed: {}", e);
-                StreamingError::DecoderError(format!("Failed to create window: {}", e))
-            })?;
+        // A repeated start (the sharer switched displays) keeps the open
+        // window and only replaces the decoder
+        let existing_window = match self.views.remove(&display_id) {
+            Some(mut view) if view.window_handle.as_ref().is_some_and(|h| h.is_open()) => {
+                view.window_handle.take().map(|h| (h, view.title))
+            }
+            Some(mut view) => {
+                view.close();
+                None
+            }
+            None => None,
+        };
+
+        // Number the window when a second display opens
+        let title = match existing_window {
+            Some((_, ref title)) => title.clone(),
+            None if self.views.is_empty() => format!("{} 的屏幕 ({})", self.peer_name, self.peer_ip),
+            None => format!("{} 的屏幕 {} ({})", self.peer_name, self.views.len() + 1, self.peer_ip),
+        };
 
-        self.window_handle = Some(window_handle);
-        self.is_active = true;
-        self.frame_count = 0;
+        let window_handle = match existing_window {
+            Some((handle, _)) => handle,
+            None => {
+                log::debug!("Creating native render window: '{}' ({}x{})", title, width, height);
+                let handle = RenderWindow::create(&title, width, height).map_err(|e| {
+                    log::error!("RenderWindow::create failed: {}", e);
+                    StreamingError::DecoderError(format!("Failed to create window: {}", e))
+                })?;
+                crate::renderer::viewers::register(&self.peer_ip, &title, &handle);
+                handle
+            }
+        };
 
-        log::info!("Native render window created for {}", self.peer_ip);
+        self.views.insert(
+            display_id,
+            DisplayView {
+                decoder,
+                codec,
+                color_space,
+                failures: DecodeFailures::default(),
+                gate: KeyframeGate::default(),
+                window_handle: Some(window_handle),
+                width,
+                height,
+                is_active: true,
+                frame_count: 0,
+                health: StreamHealth::new(Instant::now()),
+                title,
+                paused: false,
+            },
+        );
+        start_stall_watchdog();
+
+        log::info!("Native render window ready for {} display {}", self.peer_ip, display_id);
         Ok(())
     }
 
-    /// Handle ScreenFrame message - decode and render to native window
+    /// Handle ScreenFrame message - decode and render to the display's window
     pub fn handle_screen_frame(
         &mut self,
+        display_id: u32,
         timestamp: u64,
+        keyframe: bool,
         data: &[u8],
     ) -> Result<(), StreamingError> {
-        if !self.is_active {
-            return Err(StreamingError::NotStreaming);
-        }
+        let view = match self.views.get_mut(&display_id) {
+            Some(view) if view.is_active => view,
+            _ => return Err(StreamingError::NotStreaming),
+        };
 
         // Check if window is still open
-        if let Some(ref handle) = self.window_handle {
+        if let Some(ref handle) = view.window_handle {
             if !handle.is_open() {
-                log::info!("Render window closed by user");
-                self.is_active = false;
+                log::info!("Render window for display {} closed by user", display_id);
+                view.is_active = false;
                 return Err(StreamingError::NotStreaming);
             }
         }
+        view.health.on_received(Instant::now());
+        stats::frame_received(data.len());
+
+        // A new decoder starts on a keyframe; delta frames before it would
+        // only decode to garbage
+        if !view.gate.admit(keyframe) {
+            if view.gate.should_ask() {
+                let peer_ip = self.peer_ip.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = request_keyframe(&peer_ip, display_id).await {
+                        log::warn!("Failed to request keyframe from {}: {}", peer_ip, e);
+                    }
+                });
+            }
+            return Ok(());
+        }
 
-        // Decode frame
-        if let Some(decoded) = self
-            .decoder
-            .decode(data, timestamp)
-            .map_err(|e| StreamingError::DecoderError(e.to_string()))?
-        {
-            // Convert DecodedFrame to RenderFrame based on data type
-            let render_frame = if let Some(cpu_data) = decoded.cpu_data() {
-                match decoded.format {
-                    OutputFormat::BGRA => RenderFrame::from_bgra(
-                        decoded.width,
-                        decoded.height,
-                        cpu_data.to_vec(),
-                    ),
-                    OutputFormat::YUV420 => RenderFrame::from_yuv420(
-                        decoded.width,
-                        decoded.height,
-                        cpu_data.to_vec(),
-                        decoded.strides().unwrap_or([decoded.width as usize, decoded.width as usize / 2, decoded.width as usize / 2]),
-                    ),
+        // A recording that just started wants to begin on a keyframe
+        recording::record_received(&self.peer_ip, display_id, data);
+        if recording::viewer_keyframe_wanted(&self.peer_ip) {
+            let peer_ip = self.peer_ip.clone();
+            tokio::spawn(async move {
+                if let Err(e) = request_keyframe(&peer_ip, display_id).await {
+                    log::warn!("Failed to request keyframe from {}: {}", peer_ip, e);
                 }
-            } else {
-                // GPU texture path - not yet implemented
-                // TODO: When vk-video updates to wgpu 28, add zero-copy texture rendering
-                log::warn!("GPU texture frames not yet supported");
-                return Ok(());
-            };
+            });
+        }
+
+        // Decode frame
+        let decode_start = Instant::now();
+        let decoded = view.decoder.decode(data, timestamp);
+        let decode_time = decode_start.elapsed();
+        timing::record(Stage::Decode, decode_time);
+        frame_dump::record(&self.peer_ip, data, decoded.as_ref().ok().and_then(Option::as_ref), view.color_space);
+        match &decoded {
+            Ok(picture) => stats::frame_decoded(decode_time, picture.is_some()),
+            Err(_) => stats::decode_failed(decode_time),
+        }
+        // The new decoder waits behind the gate for a keyframe
+        if view.failures.record(decoded.is_ok()) {
+            view.fall_back_to_software();
+        }
+        let decoded = decoded.map_err(|e| StreamingError::DecoderError(e.to_string()))?;
+
+        if let Some(decoded) = decoded {
+            // Hand the decoder's buffer (or texture) to the window as is
+            let render_frame = RenderFrame::from_decoded(decoded).with_color_space(view.color_space);
 
             // Send to native window for GPU rendering
-            if let Some(ref handle) = self.window_handle {
+            if let Some(ref handle) = view.window_handle {
                 if let Err(e) = handle.render_frame(render_frame) {
                     log::warn!("Failed to render frame: {}", e);
                 }
             }
 
-            self.frame_count += 1;
+            view.frame_count += 1;
+            if let Some(stalled) = view.health.on_rendered(Instant::now()) {
+                log::info!(
+                    "Stream from {} display {} recovered after {:?}",
+                    self.peer_ip,
+                    display_id,
+                    stalled
+                );
+                emit_stall_event("stream-recovered", &self.peer_ip, display_id, Some(stalled));
+            }
         }
 
         Ok(())
     }
 
-    /// Handle ScreenStop message
-    pub fn handle_screen_stop(&mut self) {
-        log::info!("Viewer session stopped for {}", self.peer_ip);
-        self.is_active = false;
+    /// Handle ScreenStop message for one display, or all of them when `None`
+    pub fn handle_screen_stop(&mut self, display_id: Option<u32>) {
+        log::info!(
+            "Viewer session stopped for {} (display {:?})",
+            self.peer_ip,
+            display_id
+        );
+        match display_id {
+            Some(id) => {
+                if let Some(mut view) = self.views.remove(&id) {
+                    view.close();
+                }
+            }
+            None => self.close(),
+        }
+    }
 
-        // Close the render window
-        if let Some(ref handle) = self.window_handle {
-            handle.close();
+    /// Close the window of one display. Returns false if it was not open.
+    pub fn close_display(&mut self, display_id: u32) -> bool {
+        match self.views.remove(&display_id) {
+            Some(mut view) => {
+                view.close();
+                true
+            }
+            None => false,
         }
     }
 
     /// Close the viewer session
     pub fn close(&mut self) {
-        self.is_active = false;
-        if let Some(ref handle) = self.window_handle {
-            handle.close();
+        for (_, mut view) in self.views.drain() {
+            view.close();
         }
-        self.window_handle = None;
+        recording::viewer_ended(&self.peer_ip);
     }
 
-    /// Check if active
+    /// Check if any display is being watched
     pub fn is_active(&self) -> bool {
-        self.is_active
+        self.views.values().any(|v| v.is_active)
+    }
+
+    /// Check if a display is being watched
+    pub fn is_display_active(&self, display_id: u32) -> bool {
+        self.views.get(&display_id).is_some_and(|v| v.is_active)
     }
 
-    /// Check if window is open
+    /// Check if any window is open
     pub fn is_window_open(&self) -> bool {
-        self.window_handle.as_ref().map(|h| h.is_open()).unwrap_or(false)
+        self.views
+            .values()
+            .any(|v| v.window_handle.as_ref().map(|h| h.is_open()).unwrap_or(false))
     }
 
-    /// Get dimensions
-    pub fn dimensions(&self) -> (u32, u32) {
-        (self.width, self.height)
+    /// Displays with a render window, in no particular order
+    pub fn display_ids(&self) -> Vec<u32> {
+        self.views.keys().copied().collect()
     }
 
-    /// Get frame count
+    /// Get dimensions of a watched display
+    pub fn dimensions(&self, display_id: u32) -> Option<(u32, u32)> {
+        self.views.get(&display_id).map(|v| (v.width, v.height))
+    }
+
+    /// Get frame count (all displays)
     pub fn frame_count(&self) -> u32 {
-        self.frame_count
+        self.views.values().map(|v| v.frame_count).sum()
     }
+
+    /// Handle ScreenPause message: mark every window paused or live again
+    pub fn set_paused(&mut self, paused: bool) {
+        log::info!(
+            "Stream from {} {}",
+            self.peer_ip,
+            if paused { "paused by the sharer" } else { "resumed" }
+        );
+        for view in self.views.values_mut() {
+            view.paused = paused;
+            if !paused {
+                // No frames were due while paused
+                view.health = StreamHealth::new(Instant::now());
+            }
+            if let Some(ref handle) = view.window_handle {
+                let title = if paused {
+                    format!("{} - 已暂停", view.title)
+                } else {
+                    view.title.clone()
+                };
+                let _ = handle.set_title(&title);
+            }
+        }
+    }
+
+    /// A frame of `display_id` failed to decode. Returns true if the sharer
+    /// should be asked for a keyframe right away.
+    pub fn on_decode_error(&mut self, display_id: u32) -> bool {
+        self.views
+            .get_mut(&display_id)
+            .is_some_and(|v| v.is_active && v.health.on_decode_error(Instant::now()))
+    }
+
+    /// Check every watched display for stalls
+    fn check_health(&mut self, now: Instant) -> Vec<(u32, HealthEvent)> {
+        self.views
+            .iter_mut()
+            .filter(|(_, v)| v.is_active && !v.paused && v.window_handle.as_ref().is_some_and(|h| h.is_open()))
+            .filter_map(|(&id, v)| v.health.check(now).map(|event| (id, event)))
+            .collect()
+    }
+
+    /// Frame and stall counts of every watched display
+    pub fn summary(&self) -> ViewerSummary {
+        let now = Instant::now();
+        let mut displays: Vec<DisplayViewSummary> = self
+            .views
+            .iter()
+            .map(|(&display_id, v)| DisplayViewSummary {
+                display_id,
+                width: v.width,
+                height: v.height,
+                frames: v.frame_count,
+                stalls: v.health.stall_count(),
+                stalled_ms: v.health.stalled_total(now).as_millis() as u64,
+                stalled: v.health.is_stalled(),
+            })
+            .collect();
+        displays.sort_by_key(|d| d.display_id);
+        ViewerSummary {
+            peer_ip: self.peer_ip.clone(),
+            peer_name: self.peer_name.clone(),
+            displays,
+        }
+    }
+}
+
+/// Playback statistics of one watched display
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DisplayViewSummary {
+    pub display_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub stalls: u32,
+    pub stalled_ms: u64,
+    pub stalled: bool,
+}
+
+/// Playback statistics of one viewer session
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ViewerSummary {
+    pub peer_ip: String,
+    pub peer_name: String,
+    pub displays: Vec<DisplayViewSummary>,
 }
 
 /// Global viewer sessions
@@ -503,24 +1503,147 @@ pub fn get_viewer_sessions() -> Arc<RwLock<HashMap<String, ViewerSession>>> {
     VIEWER_SESSIONS.clone()
 }
 
-/// Create a viewer session for a peer (window created on ScreenStart)
-pub fn create_viewer_session(
-    peer_ip: String,
-    peer_name: String,
-) -> Result<(), StreamingError> {
-    let session = ViewerSession::new(peer_ip.clone(), peer_name)?;
-    VIEWER_SESSIONS.write().insert(peer_ip, session);
-    Ok(())
+/// Create a viewer session for a peer (windows created on ScreenStart).
+/// An existing session is kept so another display opens alongside it.
+pub fn create_viewer_session(peer_ip: String, peer_name: String) {
+    VIEWER_SESSIONS
+        .write()
+        .entry(peer_ip.clone())
+        .or_insert_with(|| ViewerSession::new(peer_ip, peer_name));
 }
 
 /// Remove a viewer session
 pub fn remove_viewer_session(peer_ip: &str) {
     let mut sessions = VIEWER_SESSIONS.write();
     if let Some(mut session) = sessions.remove(peer_ip) {
+        log::info!("Viewer session summary: {:?}", session.summary());
         session.close();
     }
 }
 
+/// Playback statistics of every viewer session
+pub fn viewer_summaries() -> Vec<ViewerSummary> {
+    VIEWER_SESSIONS.read().values().map(|s| s.summary()).collect()
+}
+
+/// Tell the viewer's UI that a stream stalled or recovered
+fn emit_stall_event(event: &str, peer_ip: &str, display_id: u32, stalled: Option<Duration>) {
+    #[derive(serde::Serialize, Clone)]
+    struct StreamStallEvent {
+        peer_ip: String,
+        display_id: u32,
+        stalled_ms: Option<u64>,
+    }
+    if let Some(handle) = crate::APP_HANDLE.get() {
+        let _ = handle.emit(
+            event,
+            StreamStallEvent {
+                peer_ip: peer_ip.to_string(),
+                display_id,
+                stalled_ms: stalled.map(|d| d.as_millis() as u64),
+            },
+        );
+    }
+}
+
+static STALL_WATCHDOG: std::sync::Once = std::sync::Once::new();
+
+/// Start the task that watches viewed streams for stalls (once per process)
+fn start_stall_watchdog() {
+    STALL_WATCHDOG.call_once(|| {
+        tokio::spawn(async {
+            loop {
+                tokio::time::sleep(STALL_CHECK_INTERVAL).await;
+
+                let now = Instant::now();
+                let mut requests = Vec::new();
+                for session in VIEWER_SESSIONS.write().values_mut() {
+                    for (display_id, event) in session.check_health(now) {
+                        if event == HealthEvent::Stalled {
+                            log::warn!(
+                                "Stream from {} display {} stalled, requesting keyframe",
+                                session.peer_ip,
+                                display_id
+                            );
+                            emit_stall_event("stream-stalled", &session.peer_ip, display_id, None);
+                        }
+                        requests.push((session.peer_ip.clone(), display_id));
+                    }
+                }
+
+                for (peer_ip, display_id) in requests {
+                    if let Err(e) = request_keyframe(&peer_ip, display_id).await {
+                        log::warn!("Failed to request keyframe from {}: {}", peer_ip, e);
+                    }
+                }
+            }
+        });
+    });
+}
+
+/// Ask a sharer for a keyframe on one of its displays
+pub async fn request_keyframe(peer_ip: &str, display_id: u32) -> Result<(), StreamingError> {
+    let encoded = protocol::encode(&Message::KeyframeRequest { display_id })
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    quic::send_to_peer(peer_ip, &encoded)
+        .await
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Close the window of one display we are watching, keeping the others
+pub fn close_viewer_display(peer_ip: &str, display_id: u32) -> bool {
+    VIEWER_SESSIONS
+        .write()
+        .get_mut(peer_ip)
+        .map(|s| s.close_display(display_id))
+        .unwrap_or(false)
+}
+
+/// Tell a sharer we stopped watching one display, or all of them when
+/// `display_id` is `None`, so it can pause when nobody is left
+pub async fn notify_viewer_left(peer_ip: &str, display_id: Option<u32>) -> Result<(), StreamingError> {
+    let encoded = protocol::encode(&Message::ScreenLeave { display_id })
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    quic::send_to_peer(peer_ip, &encoded)
+        .await
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Tell the sharer `peer_ip` we started or stopped recording its screen
+pub async fn notify_recording(peer_ip: &str, recording: bool) -> Result<(), StreamingError> {
+    let encoded = protocol::encode(&Message::ScreenRecording { recording })
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    quic::send_to_peer(peer_ip, &encoded)
+        .await
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Turn down a peer's request to watch `display_id`
+pub async fn reject_viewer(
+    peer_ip: &str,
+    display_id: u32,
+    reason: protocol::ScreenRejectReason,
+) -> Result<(), StreamingError> {
+    log::info!("Rejecting screen request from {} for display {}: {:?}", peer_ip, display_id, reason);
+    let encoded = protocol::encode(&Message::ScreenReject { display_id, reason })
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    quic::send_to_peer(peer_ip, &encoded)
+        .await
+        .map_err(|e| StreamingError::NetworkError(e.to_string()))?;
+
+    Ok(())
+}
+
 /// Request screen stream from a peer
 pub async fn request_screen_stream(peer_ip: &str, display_id: u32) -> Result<(), StreamingError> {
     let request_msg = Message::ScreenRequest {
@@ -539,19 +1662,50 @@ pub async fn request_screen_stream(peer_ip: &str, display_id: u32) -> Result<(),
     Ok(())
 }
 
-/// Send frame data to all peers using persistent streams
-/// Reuses existing streams when possible, opens new ones for new peers
+/// Send frame data to the given viewers using persistent streams
+/// Reuses existing streams when possible, opens new ones for new peers.
+/// Returns how many viewers the data was sent to
+/// Send a frame to one viewer on its persistent frame stream
+async fn send_to_viewer(data: &[u8], peer_ip: &str, peer_streams: &mut HashMap<String, QuicStream>) {
+    let Some(conn) = quic::get_all_connections()
+        .into_iter()
+        .find(|c| c.is_alive() && c.peer_ip() == peer_ip)
+    else {
+        return;
+    };
+    let key = conn.remote_addr().to_string();
+    if !peer_streams.contains_key(&key) {
+        match conn.open_bi_stream().await {
+            Ok(stream) => {
+                peer_streams.insert(key.clone(), stream);
+            }
+            Err(e) => {
+                log::warn!("Failed to open stream to {}: {}", key, e);
+                return;
+            }
+        }
+    }
+    if let Some(stream) = peer_streams.get_mut(&key) {
+        if let Err(e) = stream.send_framed(data).await {
+            log::warn!("Failed to send keyframe to {}: {}", key, e);
+            peer_streams.remove(&key);
+        }
+    }
+}
+
 async fn broadcast_frame(
     data: &[u8],
     peer_streams: &mut HashMap<String, QuicStream>,
-) {
+    viewers: &HashSet<String>,
+) -> usize {
     let connections = quic::get_all_connections();
 
     // Track which peers we successfully sent to
     let mut failed_peers: Vec<String> = Vec::new();
+    let mut sent = 0;
 
     for conn in &connections {
-        if !conn.is_alive() {
+        if !conn.is_alive() || !viewers.contains(&conn.peer_ip()) {
             continue;
         }
 
@@ -572,9 +1726,12 @@ async fn broadcast_frame(
         }
 
         if let Some(stream) = peer_streams.get_mut(&key) {
-            if let Err(e) = stream.send_framed(data).await {
-                log::warn!("Failed to send frame to {}: {}, will reopen stream", key, e);
-                failed_peers.push(key);
+            match stream.send_framed(data).await {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    log::warn!("Failed to send frame to {}: {}, will reopen stream", key, e);
+                    failed_peers.push(key);
+                }
             }
         }
     }
@@ -584,10 +1741,12 @@ async fn broadcast_frame(
         peer_streams.remove(&key);
     }
 
-    // Remove streams for peers that are no longer connected
-    let active_keys: std::collections::HashSet<String> = connections
+    // Remove streams for peers that are no longer connected or watching
+    let active_keys: HashSet<String> = connections
         .iter()
+        .filter(|c| viewers.contains(&c.peer_ip()))
         .map(|c| c.remote_addr().to_string())
         .collect();
     peer_streams.retain(|key, _| active_keys.contains(key));
+    sent
 }
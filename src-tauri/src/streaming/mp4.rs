@@ -0,0 +1,387 @@
+//! Fragmented MP4 muxing of H.264
+//!
+//! Encoded frames go into the file as they are, without re-encoding: the
+//! first keyframe's SPS and PPS become the track's `avcC`, every frame is
+//! stored with length prefixes instead of start codes, and samples are
+//! written in `moof`/`mdat` fragments of up to a second (and at each
+//! keyframe), so a file cut short by a crash still plays up to its last
+//! fragment.
+
+use crate::encoder::validate::nal_units;
+use std::io::{self, Write};
+
+/// Track time units per second
+pub const TIMESCALE: u32 = 90_000;
+
+/// Longest fragment, in ticks (one second)
+const FRAGMENT_TICKS: u64 = TIMESCALE as u64;
+
+/// Duration given to the last sample, whose successor never came
+const LAST_SAMPLE_TICKS: u32 = TIMESCALE / 30;
+
+/// H.264 NAL unit types kept out of the samples: the parameter sets live
+/// in `avcC`, access unit delimiters are not needed
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+/// NAL unit type of IDR slices
+const NAL_IDR: u8 = 5;
+
+/// `trun` sample flags of keyframes and of frames that depend on others
+const SYNC_SAMPLE: u32 = 0x0200_0000;
+const NON_SYNC_SAMPLE: u32 = 0x0101_0000;
+
+/// Parameters of the video track, from the first keyframe
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Track {
+    width: u32,
+    height: u32,
+    sps: Vec<u8>,
+    pps: Vec<u8>,
+}
+
+struct Sample {
+    /// Length-prefixed NAL units
+    data: Vec<u8>,
+    ticks: u64,
+    keyframe: bool,
+}
+
+/// What became of a frame handed to `FragmentedMp4::push`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pushed {
+    /// Written or queued for the next fragment
+    Added,
+    /// Dropped: the file starts with a keyframe carrying SPS and PPS
+    WaitingForKeyframe,
+    /// The keyframe starts a stream with other parameters (the encoder was
+    /// reconfigured); finish this file and push it into a new one
+    NewStream,
+}
+
+/// Writes H.264 frames (Annex B) into a fragmented MP4
+pub struct FragmentedMp4<W: Write> {
+    out: W,
+    track: Option<Track>,
+    pending: Vec<Sample>,
+    /// Time of the first frame; the file starts at zero
+    origin: Option<u64>,
+    sequence: u32,
+    last_duration: u32,
+    samples: u64,
+}
+
+impl<W: Write> FragmentedMp4<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, track: None, pending: Vec::new(), origin: None, sequence: 0, last_duration: LAST_SAMPLE_TICKS, samples: 0 }
+    }
+
+    /// Add a frame of `width`×`height` encoded at `micros` (any clock, as
+    /// long as it keeps going up)
+    pub fn push(&mut self, frame: &[u8], keyframe: bool, micros: u64, width: u32, height: u32) -> io::Result<Pushed> {
+        let nals = nal_units(frame);
+        let find = |kind: u8| nals.iter().find(|n| n.first().is_some_and(|h| h & 0x1F == kind)).map(|n| n.to_vec());
+        let params = if keyframe { find(NAL_SPS).zip(find(NAL_PPS)) } else { None };
+        if let Some((sps, pps)) = params {
+            let track = Track { width, height, sps, pps };
+            match &self.track {
+                None => {
+                    self.out.write_all(&init_segment(&track))?;
+                    self.track = Some(track);
+                }
+                Some(current) if *current != track => return Ok(Pushed::NewStream),
+                Some(_) => {}
+            }
+        }
+        if self.track.is_none() {
+            return Ok(Pushed::WaitingForKeyframe);
+        }
+
+        let origin = *self.origin.get_or_insert(micros);
+        let ticks = micros.saturating_sub(origin) * TIMESCALE as u64 / 1_000_000;
+        let fragment_full = self
+            .pending
+            .first()
+            .is_some_and(|first| ticks.saturating_sub(first.ticks) >= FRAGMENT_TICKS);
+        if keyframe || fragment_full {
+            self.flush(Some(ticks))?;
+        }
+
+        let mut data = Vec::with_capacity(frame.len());
+        for nal in nals.iter().filter(|n| n.first().is_some_and(|h| !matches!(h & 0x1F, NAL_SPS | NAL_PPS | NAL_AUD))) {
+            data.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+            data.extend_from_slice(nal);
+        }
+        if !data.is_empty() {
+            self.pending.push(Sample { data, ticks, keyframe });
+        }
+        Ok(Pushed::Added)
+    }
+
+    /// Frames written so far
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Write what is queued and hand the output back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush(None)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Write the queued samples as a fragment; `next` is the time of the
+    /// frame after them, which gives the last one its duration
+    fn flush(&mut self, next: Option<u64>) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let samples = std::mem::take(&mut self.pending);
+        let mut durations = Vec::with_capacity(samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            let duration = match samples.get(i + 1).map(|s| s.ticks).or(next) {
+                Some(next) => next.saturating_sub(sample.ticks).max(1) as u32,
+                None => self.last_duration,
+            };
+            self.last_duration = duration;
+            durations.push(duration);
+        }
+        self.sequence += 1;
+        self.samples += samples.len() as u64;
+        self.out.write_all(&fragment(self.sequence, samples[0].ticks, &samples, &durations))
+    }
+}
+
+/// Whether an H.264 frame (Annex B) holds an IDR picture
+pub fn is_keyframe(frame: &[u8]) -> bool {
+    nal_units(frame).iter().any(|n| n.first().is_some_and(|h| h & 0x1F == NAL_IDR))
+}
+
+/// Box of `kind` holding what `body` writes
+fn mp4_box(out: &mut Vec<u8>, kind: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(kind);
+    body(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// Full box: a box starting with version and flags
+fn full_box(out: &mut Vec<u8>, kind: &[u8; 4], version: u8, flags: u32, body: impl FnOnce(&mut Vec<u8>)) {
+    mp4_box(out, kind, |out| {
+        out.extend_from_slice(&(((version as u32) << 24) | flags).to_be_bytes());
+        body(out);
+    });
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_be_bytes());
+}
+
+/// Identity transformation matrix of `mvhd` and `tkhd`
+fn put_matrix(out: &mut Vec<u8>) {
+    for v in [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        put_u32(out, v);
+    }
+}
+
+/// `ftyp` and `moov` with an empty sample table; the samples follow in
+/// fragments
+fn init_segment(track: &Track) -> Vec<u8> {
+    let mut out = Vec::new();
+    mp4_box(&mut out, b"ftyp", |out| {
+        out.extend_from_slice(b"isom");
+        put_u32(out, 0x200);
+        out.extend_from_slice(b"isomiso6avc1mp41");
+    });
+    mp4_box(&mut out, b"moov", |out| {
+        full_box(out, b"mvhd", 0, 0, |out| {
+            put_u32(out, 0); // creation time
+            put_u32(out, 0); // modification time
+            put_u32(out, 1000);
+            put_u32(out, 0); // duration, in the fragments
+            put_u32(out, 0x0001_0000); // rate 1.0
+            put_u16(out, 0x0100); // volume 1.0
+            out.extend_from_slice(&[0; 10]);
+            put_matrix(out);
+            out.extend_from_slice(&[0; 24]);
+            put_u32(out, 2); // next track id
+        });
+        mp4_box(out, b"trak", |out| {
+            full_box(out, b"tkhd", 0, 0x3, |out| {
+                put_u32(out, 0);
+                put_u32(out, 0);
+                put_u32(out, 1); // track id
+                put_u32(out, 0);
+                put_u32(out, 0); // duration
+                out.extend_from_slice(&[0; 16]); // reserved, layer, group, volume, reserved
+                put_matrix(out);
+                put_u32(out, track.width << 16);
+                put_u32(out, track.height << 16);
+            });
+            mp4_box(out, b"mdia", |out| {
+                full_box(out, b"mdhd", 0, 0, |out| {
+                    put_u32(out, 0);
+                    put_u32(out, 0);
+                    put_u32(out, TIMESCALE);
+                    put_u32(out, 0);
+                    put_u16(out, 0x55C4); // "und"
+                    put_u16(out, 0);
+                });
+                full_box(out, b"hdlr", 0, 0, |out| {
+                    put_u32(out, 0);
+                    out.extend_from_slice(b"vide");
+                    out.extend_from_slice(&[0; 12]);
+                    out.extend_from_slice(b"VideoHandler\0");
+                });
+                mp4_box(out, b"minf", |out| {
+                    full_box(out, b"vmhd", 0, 1, |out| out.extend_from_slice(&[0; 8]));
+                    mp4_box(out, b"dinf", |out| {
+                        full_box(out, b"dref", 0, 0, |out| {
+                            put_u32(out, 1);
+                            // Samples are in this file
+                            full_box(out, b"url ", 0, 1, |_| {});
+                        });
+                    });
+                    mp4_box(out, b"stbl", |out| {
+                        full_box(out, b"stsd", 0, 0, |out| {
+                            put_u32(out, 1);
+                            sample_entry(out, track);
+                        });
+                        for kind in [b"stts", b"stsc", b"stco"] {
+                            full_box(out, kind, 0, 0, |out| put_u32(out, 0));
+                        }
+                        full_box(out, b"stsz", 0, 0, |out| out.extend_from_slice(&[0; 8]));
+                    });
+                });
+            });
+        });
+        mp4_box(out, b"mvex", |out| {
+            full_box(out, b"trex", 0, 0, |out| {
+                put_u32(out, 1); // track id
+                put_u32(out, 1); // sample description
+                out.extend_from_slice(&[0; 12]); // default duration, size and flags
+            });
+        });
+    });
+    out
+}
+
+/// `avc1` sample entry with the parameter sets in `avcC`
+fn sample_entry(out: &mut Vec<u8>, track: &Track) {
+    mp4_box(out, b"avc1", |out| {
+        out.extend_from_slice(&[0; 6]);
+        put_u16(out, 1); // data reference
+        out.extend_from_slice(&[0; 16]);
+        put_u16(out, track.width as u16);
+        put_u16(out, track.height as u16);
+        put_u32(out, 0x0048_0000); // 72 dpi
+        put_u32(out, 0x0048_0000);
+        put_u32(out, 0);
+        put_u16(out, 1); // frames per sample
+        out.extend_from_slice(&[0; 32]); // compressor name
+        put_u16(out, 0x18); // depth
+        put_u16(out, 0xFFFF);
+        mp4_box(out, b"avcC", |out| {
+            // Profile, compatibility and level as the SPS has them
+            let sps_byte = |i: usize| track.sps.get(i).copied().unwrap_or(0);
+            out.extend_from_slice(&[1, sps_byte(1), sps_byte(2), sps_byte(3)]);
+            out.push(0xFF); // 4-byte lengths
+            out.push(0xE1); // one SPS
+            put_u16(out, track.sps.len() as u16);
+            out.extend_from_slice(&track.sps);
+            out.push(1); // one PPS
+            put_u16(out, track.pps.len() as u16);
+            out.extend_from_slice(&track.pps);
+        });
+    });
+}
+
+/// `moof` and `mdat` of fragment `sequence` starting at `base` ticks
+fn fragment(sequence: u32, base: u64, samples: &[Sample], durations: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut data_offset_at = 0;
+    mp4_box(&mut out, b"moof", |out| {
+        full_box(out, b"mfhd", 0, 0, |out| put_u32(out, sequence));
+        mp4_box(out, b"traf", |out| {
+            // Sample offsets count from the start of the moof
+            full_box(out, b"tfhd", 0, 0x02_0000, |out| put_u32(out, 1));
+            full_box(out, b"tfdt", 1, 0, |out| out.extend_from_slice(&base.to_be_bytes()));
+            // Data offset, and each sample's duration, size and flags
+            full_box(out, b"trun", 0, 0x000701, |out| {
+                put_u32(out, samples.len() as u32);
+                data_offset_at = out.len();
+                put_u32(out, 0);
+                for (sample, &duration) in samples.iter().zip(durations) {
+                    put_u32(out, duration);
+                    put_u32(out, sample.data.len() as u32);
+                    put_u32(out, if sample.keyframe { SYNC_SAMPLE } else { NON_SYNC_SAMPLE });
+                }
+            });
+        });
+    });
+    let data_offset = (out.len() + 8) as u32;
+    out[data_offset_at..data_offset_at + 4].copy_from_slice(&data_offset.to_be_bytes());
+    mp4_box(&mut out, b"mdat", |out| {
+        for sample in samples {
+            out.extend_from_slice(&sample.data);
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPS: &[u8] = &[0x67, 0x42, 0xC0, 0x1F, 0xDA];
+    const PPS: &[u8] = &[0x68, 0xCE, 0x3C, 0x80];
+
+    fn keyframe(sps: &[u8]) -> Vec<u8> {
+        [&[0, 0, 0, 1][..], sps, &[0, 0, 0, 1], PPS, &[0, 0, 1, 0x65, 0x88, 0x84]].concat()
+    }
+
+    /// Top-level boxes of `data`: kind and size
+    fn boxes(data: &[u8]) -> Vec<(String, usize)> {
+        let mut out = Vec::new();
+        let mut at = 0;
+        while at + 8 <= data.len() {
+            let size = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+            out.push((String::from_utf8_lossy(&data[at + 4..at + 8]).into_owned(), size));
+            at += size;
+        }
+        assert_eq!(at, data.len());
+        out
+    }
+
+    #[test]
+    fn test_fragments_start_at_keyframes() {
+        let mut mp4 = FragmentedMp4::new(Vec::new());
+        // Nothing is written before the first keyframe
+        assert_eq!(mp4.push(&[0, 0, 1, 0x41, 0x9A], false, 0, 1280, 720).unwrap(), Pushed::WaitingForKeyframe);
+        assert_eq!(mp4.push(&keyframe(SPS), true, 1_000_000, 1280, 720).unwrap(), Pushed::Added);
+        assert_eq!(mp4.push(&[0, 0, 1, 0x41, 0x9A], false, 1_033_333, 1280, 720).unwrap(), Pushed::Added);
+        assert_eq!(mp4.push(&keyframe(SPS), true, 1_066_666, 1280, 720).unwrap(), Pushed::Added);
+        // Another SPS means another stream
+        assert_eq!(mp4.push(&keyframe(&[0x67, 0x64, 0x00, 0x28]), true, 1_100_000, 1920, 1080).unwrap(), Pushed::NewStream);
+        assert_eq!(mp4.samples(), 2);
+
+        let data = mp4.finish().unwrap();
+        let kinds: Vec<_> = boxes(&data).into_iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, ["ftyp", "moov", "moof", "mdat", "moof", "mdat"]);
+
+        // The first fragment's frames: the keyframe without its parameter
+        // sets, then the delta frame, both length-prefixed
+        let first_mdat = boxes(&data).iter().take(3).map(|(_, size)| size).sum::<usize>();
+        assert_eq!(&data[first_mdat + 8..first_mdat + 8 + 7], &[0, 0, 0, 3, 0x65, 0x88, 0x84]);
+        assert!(data.windows(SPS.len()).any(|w| w == SPS));
+        assert!(is_keyframe(&keyframe(SPS)));
+        assert!(!is_keyframe(&[0, 0, 1, 0x41, 0x9A]));
+    }
+}
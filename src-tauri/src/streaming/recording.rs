@@ -0,0 +1,366 @@
+//! Recording to MP4
+//!
+//! `start_recording` tees the H.264 frames the sharer already encodes for
+//! viewers into a fragmented MP4 (see `mp4`) in the download folder, so a
+//! meeting can be archived without a second encoder. A viewer can record a
+//! screen it watches the same way with `start_viewer_recording`, from the
+//! frames as they arrive, before decoding; its window shows a REC badge and
+//! the sharer is told. The file is written on its own thread; a keyframe is
+//! asked for when recording starts, and if the encoder is reconfigured
+//! mid-recording (another resolution) the recording goes on in a new part
+//! file. With several displays shared, the first one to send a frame is
+//! recorded. A "recording-changed" event tells the UI to show or take off
+//! its recording indicator.
+
+use super::mp4::{FragmentedMp4, Pushed};
+use crossbeam_channel::Sender;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use tauri::Emitter;
+
+/// Payload of the "recording-changed" event
+#[derive(Debug, Clone, Serialize)]
+struct RecordingEvent {
+    /// Sharer of the screen a viewer recording is of; `None` for the screen
+    /// we share
+    peer_ip: Option<String>,
+    recording: bool,
+    path: String,
+    /// Seconds recorded, once it stopped
+    seconds: Option<u64>,
+}
+
+struct Frame {
+    data: Vec<u8>,
+    keyframe: bool,
+    micros: u64,
+    width: u32,
+    height: u32,
+}
+
+/// An MP4 file being written from encoded H.264 frames
+pub struct Recorder {
+    tx: Sender<Frame>,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Start writing to `path` (created now, so errors show up here)
+    pub fn start(path: PathBuf) -> Result<Self, String> {
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let (tx, rx) = crossbeam_channel::unbounded::<Frame>();
+        let writer_path = path.clone();
+        std::thread::spawn(move || write_frames(&writer_path, file, rx));
+        Ok(Self { tx, path, started: Instant::now() })
+    }
+
+    /// Add an encoded frame (Annex B) of `width`×`height`
+    pub fn push(&self, data: &[u8], keyframe: bool, width: u32, height: u32) {
+        let micros = self.started.elapsed().as_micros() as u64;
+        let _ = self.tx.send(Frame { data: data.to_vec(), keyframe, micros, width, height });
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How long it has been recording
+    pub fn seconds(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+/// Path of part `part` of a recording at `path`: `name-2.mp4` and so on
+fn part_path(path: &Path, part: u32) -> PathBuf {
+    if part <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-{}.mp4", stem, part))
+}
+
+/// Mux frames from `rx` until the recorder is dropped
+fn write_frames(path: &Path, file: File, rx: crossbeam_channel::Receiver<Frame>) {
+    let mut part = 1;
+    let mut mp4 = FragmentedMp4::new(BufWriter::new(file));
+    let mut samples = 0;
+    for frame in rx {
+        let pushed = mp4.push(&frame.data, frame.keyframe, frame.micros, frame.width, frame.height);
+        let result = match pushed {
+            Ok(Pushed::NewStream) => {
+                samples += mp4.samples();
+                part += 1;
+                let next = part_path(path, part);
+                log::info!("Stream changed to {}x{}, recording on in {}", frame.width, frame.height, next.display());
+                let finished = std::mem::replace(&mut mp4, match File::create(&next) {
+                    Ok(file) => FragmentedMp4::new(BufWriter::new(file)),
+                    Err(e) => {
+                        log::warn!("Failed to create {}: {}", next.display(), e);
+                        break;
+                    }
+                });
+                finished.finish().and_then(|_| mp4.push(&frame.data, frame.keyframe, frame.micros, frame.width, frame.height))
+            }
+            other => other,
+        };
+        if let Err(e) = result {
+            log::warn!("Recording to {} failed: {}", path.display(), e);
+            return;
+        }
+    }
+    samples += mp4.samples();
+    match mp4.finish() {
+        Ok(_) => log::info!("Recording finished: {} frames in {} part(s) at {}", samples, part, path.display()),
+        Err(e) => log::warn!("Failed to finish recording {}: {}", path.display(), e),
+    }
+}
+
+/// Recording of the screen we share
+struct SharedRecording {
+    recorder: Recorder,
+    /// Display recorded, once one sent a frame
+    display_id: Option<u32>,
+}
+
+static SHARED: Mutex<Option<SharedRecording>> = Mutex::new(None);
+
+/// Set while the shared screen is recorded, so the encode loops skip the
+/// lock otherwise
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Set when a recording starts, until an encode loop asked for a keyframe
+static KEYFRAME_WANTED: AtomicBool = AtomicBool::new(false);
+
+/// Where recordings go: the download folder of file transfers
+pub fn default_dir() -> PathBuf {
+    crate::transfer::get_transfer_manager().download_dir().to_path_buf()
+}
+
+fn emit(event: RecordingEvent) {
+    if let Some(app) = crate::APP_HANDLE.get() {
+        let _ = app.emit("recording-changed", event);
+    }
+}
+
+/// Start recording the screen we share into a new file under `dir`;
+/// returns the file
+pub fn start(dir: &Path) -> Result<PathBuf, String> {
+    let mut shared = SHARED.lock();
+    if let Some(recording) = shared.as_ref() {
+        return Err(format!("Already recording to {}", recording.recorder.path().display()));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stamp = crate::capture::archive::utc_stamp(crate::capture::archive::now_secs());
+    let recorder = Recorder::start(dir.join(format!("recording-{}.mp4", stamp)))?;
+    let path = recorder.path().to_path_buf();
+    log::info!("Recording the shared screen to {}", path.display());
+
+    *shared = Some(SharedRecording { recorder, display_id: None });
+    KEYFRAME_WANTED.store(true, Ordering::Relaxed);
+    ACTIVE.store(true, Ordering::Relaxed);
+    emit(RecordingEvent { peer_ip: None, recording: true, path: path.to_string_lossy().to_string(), seconds: None });
+    Ok(path)
+}
+
+/// Stop recording; returns the file, `None` if nothing was recorded
+pub fn stop() -> Option<PathBuf> {
+    ACTIVE.store(false, Ordering::Relaxed);
+    // Dropping the recorder lets its writer finish the file
+    let recorder = SHARED.lock().take()?.recorder;
+    let path = recorder.path().to_path_buf();
+    log::info!("Stopped recording after {}s", recorder.seconds());
+    emit(RecordingEvent {
+        peer_ip: None,
+        recording: false,
+        path: path.to_string_lossy().to_string(),
+        seconds: Some(recorder.seconds()),
+    });
+    Some(path)
+}
+
+/// File the shared screen is being recorded to
+pub fn current() -> Option<PathBuf> {
+    SHARED.lock().as_ref().map(|r| r.recorder.path().to_path_buf())
+}
+
+/// Offer an encoded H.264 frame of shared display `display_id`, from the
+/// encode loops
+pub fn record_shared(display_id: u32, data: &[u8], keyframe: bool, width: u32, height: u32) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(recording) = SHARED.lock().as_mut() {
+        if *recording.display_id.get_or_insert(display_id) == display_id {
+            recording.recorder.push(data, keyframe, width, height);
+        }
+    }
+}
+
+/// Whether a recording just started and needs a keyframe to begin with;
+/// true once
+pub fn keyframe_wanted() -> bool {
+    KEYFRAME_WANTED.swap(false, Ordering::Relaxed)
+}
+
+/// A stream we watch, as its last start message described it
+#[derive(Debug, Clone, Copy)]
+struct ReceivedStream {
+    width: u32,
+    height: u32,
+    h264: bool,
+}
+
+/// Recording of a screen we watch
+struct ViewerRecording {
+    recorder: Recorder,
+    display_id: u32,
+    keyframe_wanted: bool,
+}
+
+/// Streams we watch by sharer and display, and the recordings of them by
+/// sharer
+static RECEIVED: Lazy<Mutex<HashMap<(String, u32), ReceivedStream>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static VIEWING: Lazy<Mutex<HashMap<String, ViewerRecording>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Set while a watched screen is recorded, so the viewers skip the lock
+/// otherwise
+static VIEWING_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// File name of a recording (or clip, see `clip`) of `peer_ip`'s screen
+/// (IPv6 colons are not allowed in Windows file names)
+pub(super) fn peer_file_name(prefix: &str, peer_ip: &str, stamp: &str) -> String {
+    let peer: String = peer_ip.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' }).collect();
+    format!("{}-{}-{}.mp4", prefix, peer, stamp)
+}
+
+/// A viewer got the start message of `peer_ip`'s display `display_id`
+pub fn stream_started(peer_ip: &str, display_id: u32, width: u32, height: u32, h264: bool) {
+    RECEIVED.lock().insert((peer_ip.to_string(), display_id), ReceivedStream { width, height, h264 });
+}
+
+/// Start recording `peer_ip`'s screen into a new file under `dir`; returns
+/// the file. The sharer has to be told (see `start_viewer_recording`).
+pub fn start_viewer(peer_ip: &str, dir: &Path) -> Result<PathBuf, String> {
+    let mut viewing = VIEWING.lock();
+    if let Some(recording) = viewing.get(peer_ip) {
+        return Err(format!("Already recording to {}", recording.recorder.path().display()));
+    }
+    // The first display watched, if there are several
+    let display_id = RECEIVED
+        .lock()
+        .iter()
+        .filter(|((ip, _), _)| ip == peer_ip)
+        .map(|((_, display_id), stream)| (*display_id, stream.h264))
+        .min()
+        .ok_or_else(|| format!("Not watching {}", peer_ip))?;
+    if !display_id.1 {
+        return Err("Only H.264 streams can be recorded".to_string());
+    }
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let stamp = crate::capture::archive::utc_stamp(crate::capture::archive::now_secs());
+    let recorder = Recorder::start(dir.join(peer_file_name("recording", peer_ip, &stamp)))?;
+    let path = recorder.path().to_path_buf();
+    log::info!("Recording {}'s screen to {}", peer_ip, path.display());
+
+    viewing.insert(peer_ip.to_string(), ViewerRecording { recorder, display_id: display_id.0, keyframe_wanted: true });
+    VIEWING_ACTIVE.store(true, Ordering::Relaxed);
+    set_badge(peer_ip, true);
+    emit(RecordingEvent {
+        peer_ip: Some(peer_ip.to_string()),
+        recording: true,
+        path: path.to_string_lossy().to_string(),
+        seconds: None,
+    });
+    Ok(path)
+}
+
+/// Stop recording `peer_ip`'s screen; returns the file, `None` if it was
+/// not recorded
+pub fn stop_viewer(peer_ip: &str) -> Option<PathBuf> {
+    let recorder = {
+        let mut viewing = VIEWING.lock();
+        let recorder = viewing.remove(peer_ip)?.recorder;
+        VIEWING_ACTIVE.store(!viewing.is_empty(), Ordering::Relaxed);
+        recorder
+    };
+    let path = recorder.path().to_path_buf();
+    log::info!("Stopped recording {}'s screen after {}s", peer_ip, recorder.seconds());
+    set_badge(peer_ip, false);
+    emit(RecordingEvent {
+        peer_ip: Some(peer_ip.to_string()),
+        recording: false,
+        path: path.to_string_lossy().to_string(),
+        seconds: Some(recorder.seconds()),
+    });
+    Some(path)
+}
+
+/// The viewer of `peer_ip`'s screen stopped watching: the stream is gone
+/// and so is a recording of it. Returns whether there was one.
+pub fn viewer_ended(peer_ip: &str) -> bool {
+    RECEIVED.lock().retain(|(ip, _), _| ip != peer_ip);
+    super::clip::clear(peer_ip);
+    stop_viewer(peer_ip).is_some()
+}
+
+/// Files the screens we watch are being recorded to, by sharer
+pub fn viewer_recordings() -> HashMap<String, PathBuf> {
+    VIEWING.lock().iter().map(|(ip, r)| (ip.clone(), r.recorder.path().to_path_buf())).collect()
+}
+
+/// Show or take off the REC badge in the window of `peer_ip`'s screen
+fn set_badge(peer_ip: &str, recording: bool) {
+    if let Some(window) = crate::renderer::viewers::get(peer_ip) {
+        let _ = window.set_recording(recording);
+    }
+}
+
+/// Offer an encoded frame of `peer_ip`'s display `display_id` as it
+/// arrived, from the viewers; it is kept for clips too
+pub fn record_received(peer_ip: &str, display_id: u32, data: &[u8]) {
+    let Some(stream) = RECEIVED.lock().get(&(peer_ip.to_string(), display_id)).copied() else {
+        return;
+    };
+    if !stream.h264 {
+        return;
+    }
+    let keyframe = super::mp4::is_keyframe(data);
+    super::clip::push(peer_ip, display_id, data, keyframe, stream.width, stream.height);
+    if !VIEWING_ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(recording) = VIEWING.lock().get(peer_ip).filter(|r| r.display_id == display_id) {
+        recording.recorder.push(data, keyframe, stream.width, stream.height);
+    }
+}
+
+/// Whether a recording of `peer_ip`'s screen just started and the viewer
+/// should ask the sharer for a keyframe; true once
+pub fn viewer_keyframe_wanted(peer_ip: &str) -> bool {
+    if !VIEWING_ACTIVE.load(Ordering::Relaxed) {
+        return false;
+    }
+    VIEWING.lock().get_mut(peer_ip).is_some_and(|r| std::mem::take(&mut r.keyframe_wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_paths() {
+        let path = Path::new("/tmp/recording-20260101-120000.mp4");
+        assert_eq!(part_path(path, 1), path);
+        assert_eq!(part_path(path, 3), Path::new("/tmp/recording-20260101-120000-3.mp4"));
+        assert_eq!(peer_file_name("recording", "fe80::1", "20260101-120000"), "recording-fe80--1-20260101-120000.mp4");
+    }
+}
@@ -0,0 +1,329 @@
+//! Live streaming statistics
+//!
+//! Counters bumped along capture → encode → send on the sharer and
+//! receive → decode → render on the viewer, by both the full and the simple
+//! pipeline. Once a second they are turned into rates and averages over
+//! that second, kept for `get_sharer_stats` / `get_viewer_stats` and
+//! emitted as a "stream-stats" event while anything streams, so the UI can
+//! draw live graphs.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// How often rates are computed and emitted
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Running totals on the sharer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SharerTotals {
+    encoded: u64,
+    encoded_bytes: u64,
+    encode_us: u64,
+    sent: u64,
+    sent_bytes: u64,
+    dropped: u64,
+}
+
+/// Running totals on the viewer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ViewerTotals {
+    received: u64,
+    received_bytes: u64,
+    decode_calls: u64,
+    decoded: u64,
+    decode_us: u64,
+    decode_errors: u64,
+    rendered: u64,
+    lost: u64,
+}
+
+/// Sharer statistics over the last interval
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SharerStats {
+    /// Frames encoded per second
+    pub fps: f64,
+    /// Frame sends per second, summed over viewers
+    pub send_fps: f64,
+    /// Encoder output
+    pub bitrate_bps: u64,
+    /// Bytes written to viewers, summed over viewers
+    pub send_bps: u64,
+    /// Frames dropped from viewer queues
+    pub dropped_frames: u64,
+    pub avg_encode_ms: f64,
+    pub total_frames: u64,
+    pub total_dropped: u64,
+}
+
+/// Viewer statistics over the last interval
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ViewerStats {
+    /// Frames presented per second
+    pub fps: f64,
+    /// Frames received per second
+    pub receive_fps: f64,
+    pub bitrate_bps: u64,
+    /// Frames lost in transit or decoded but never shown
+    pub dropped_frames: u64,
+    pub decode_errors: u64,
+    pub avg_decode_ms: f64,
+    pub total_frames: u64,
+    pub total_dropped: u64,
+}
+
+/// Payload of the "stream-stats" event
+#[derive(Debug, Clone, Serialize)]
+struct StatsEvent {
+    sharer: SharerStats,
+    viewer: ViewerStats,
+}
+
+struct Counter(AtomicU64);
+
+impl Counter {
+    const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+static ENCODED: Counter = Counter::new();
+static ENCODED_BYTES: Counter = Counter::new();
+static ENCODE_US: Counter = Counter::new();
+static SENT: Counter = Counter::new();
+static SENT_BYTES: Counter = Counter::new();
+static SEND_DROPPED: Counter = Counter::new();
+
+static RECEIVED: Counter = Counter::new();
+static RECEIVED_BYTES: Counter = Counter::new();
+static DECODE_CALLS: Counter = Counter::new();
+static DECODED: Counter = Counter::new();
+static DECODE_US: Counter = Counter::new();
+static DECODE_ERRORS: Counter = Counter::new();
+static RENDERED: Counter = Counter::new();
+static LOST: Counter = Counter::new();
+
+static LATEST: Mutex<Option<(SharerStats, ViewerStats)>> = Mutex::new(None);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// One frame came out of the encoder
+pub fn frame_encoded(bytes: usize, took: Duration) {
+    ENCODED.add(1);
+    ENCODED_BYTES.add(bytes as u64);
+    ENCODE_US.add(took.as_micros() as u64);
+}
+
+/// A frame of `bytes` was written to `viewers` viewers
+pub fn frame_sent(bytes: usize, viewers: usize) {
+    SENT.add(viewers as u64);
+    SENT_BYTES.add((bytes * viewers) as u64);
+}
+
+/// Frames dropped from a viewer's send queue
+pub fn frames_dropped(count: u64) {
+    SEND_DROPPED.add(count);
+}
+
+/// One encoded frame arrived from a sharer
+pub fn frame_received(bytes: usize) {
+    RECEIVED.add(1);
+    RECEIVED_BYTES.add(bytes as u64);
+}
+
+/// One frame went through the decoder; `picture` is whether it produced
+/// one (it may still be buffering)
+pub fn frame_decoded(took: Duration, picture: bool) {
+    DECODE_CALLS.add(1);
+    DECODED.add(picture as u64);
+    DECODE_US.add(took.as_micros() as u64);
+}
+
+/// The decoder rejected a frame
+pub fn decode_failed(took: Duration) {
+    DECODE_CALLS.add(1);
+    DECODE_ERRORS.add(1);
+    DECODE_US.add(took.as_micros() as u64);
+}
+
+/// One frame was presented in a render window
+pub fn frame_rendered() {
+    RENDERED.add(1);
+}
+
+/// A frame never arrived completely
+pub fn frame_lost() {
+    LOST.add(1);
+}
+
+fn sharer_totals() -> SharerTotals {
+    SharerTotals {
+        encoded: ENCODED.get(),
+        encoded_bytes: ENCODED_BYTES.get(),
+        encode_us: ENCODE_US.get(),
+        sent: SENT.get(),
+        sent_bytes: SENT_BYTES.get(),
+        dropped: SEND_DROPPED.get(),
+    }
+}
+
+fn viewer_totals() -> ViewerTotals {
+    ViewerTotals {
+        received: RECEIVED.get(),
+        received_bytes: RECEIVED_BYTES.get(),
+        decode_calls: DECODE_CALLS.get(),
+        decoded: DECODED.get(),
+        decode_us: DECODE_US.get(),
+        decode_errors: DECODE_ERRORS.get(),
+        rendered: RENDERED.get(),
+        lost: LOST.get(),
+    }
+}
+
+fn per_second(count: u64, secs: f64) -> f64 {
+    if secs > 0.0 { count as f64 / secs } else { 0.0 }
+}
+
+fn average_ms(total_us: u64, count: u64) -> f64 {
+    if count > 0 { total_us as f64 / count as f64 / 1000.0 } else { 0.0 }
+}
+
+impl SharerTotals {
+    fn stats_since(&self, prev: &SharerTotals, elapsed: Duration) -> SharerStats {
+        let secs = elapsed.as_secs_f64();
+        let encoded = self.encoded - prev.encoded;
+        SharerStats {
+            fps: per_second(encoded, secs),
+            send_fps: per_second(self.sent - prev.sent, secs),
+            bitrate_bps: per_second((self.encoded_bytes - prev.encoded_bytes) * 8, secs) as u64,
+            send_bps: per_second((self.sent_bytes - prev.sent_bytes) * 8, secs) as u64,
+            dropped_frames: self.dropped - prev.dropped,
+            avg_encode_ms: average_ms(self.encode_us - prev.encode_us, encoded),
+            total_frames: self.encoded,
+            total_dropped: self.dropped,
+        }
+    }
+}
+
+impl ViewerTotals {
+    /// Frames lost in transit, or decoded but replaced by a newer one
+    /// before the window showed them
+    fn dropped(&self) -> u64 {
+        self.lost + self.decoded.saturating_sub(self.rendered)
+    }
+
+    fn stats_since(&self, prev: &ViewerTotals, elapsed: Duration) -> ViewerStats {
+        let secs = elapsed.as_secs_f64();
+        ViewerStats {
+            fps: per_second(self.rendered - prev.rendered, secs),
+            receive_fps: per_second(self.received - prev.received, secs),
+            bitrate_bps: per_second((self.received_bytes - prev.received_bytes) * 8, secs) as u64,
+            dropped_frames: self.dropped().saturating_sub(prev.dropped()),
+            decode_errors: self.decode_errors - prev.decode_errors,
+            avg_decode_ms: average_ms(self.decode_us - prev.decode_us, self.decode_calls - prev.decode_calls),
+            total_frames: self.rendered,
+            total_dropped: self.dropped(),
+        }
+    }
+}
+
+/// Sharer statistics of the last interval
+pub fn sharer_stats() -> SharerStats {
+    LATEST.lock().as_ref().map(|(s, _)| s.clone()).unwrap_or_default()
+}
+
+/// Viewer statistics of the last interval
+pub fn viewer_stats() -> ViewerStats {
+    LATEST.lock().as_ref().map(|(_, v)| v.clone()).unwrap_or_default()
+}
+
+/// Start computing rates and emitting "stream-stats"
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        tokio::spawn(stats_loop());
+    }
+}
+
+async fn stats_loop() {
+    let mut prev = (sharer_totals(), viewer_totals(), Instant::now());
+    let mut was_active = false;
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+        let (sharer, viewer, now) = (sharer_totals(), viewer_totals(), Instant::now());
+        let elapsed = now - prev.2;
+        let stats = (sharer.stats_since(&prev.0, elapsed), viewer.stats_since(&prev.1, elapsed));
+        let active = sharer != prev.0 || viewer != prev.1;
+        prev = (sharer, viewer, now);
+        *LATEST.lock() = Some(stats.clone());
+
+        // Keep quiet while idle, after one last all-zero update
+        if active || was_active {
+            if let Some(handle) = crate::APP_HANDLE.get() {
+                let _ = handle.emit("stream-stats", StatsEvent { sharer: stats.0, viewer: stats.1 });
+            }
+        }
+        was_active = active;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharer_rates() {
+        let prev = SharerTotals { encoded: 100, encoded_bytes: 1_000_000, encode_us: 500_000, ..Default::default() };
+        let cur = SharerTotals {
+            encoded: 160,
+            encoded_bytes: 1_500_000,
+            encode_us: 800_000,
+            sent: 120,
+            sent_bytes: 1_000_000,
+            dropped: 3,
+        };
+        let stats = cur.stats_since(&prev, Duration::from_secs(2));
+        assert_eq!(stats.fps, 30.0);
+        assert_eq!(stats.send_fps, 60.0);
+        assert_eq!(stats.bitrate_bps, 2_000_000);
+        assert_eq!(stats.send_bps, 4_000_000);
+        assert_eq!(stats.dropped_frames, 3);
+        assert!((stats.avg_encode_ms - 5.0).abs() < 1e-9);
+        assert_eq!(stats.total_frames, 160);
+    }
+
+    #[test]
+    fn test_viewer_drops_and_idle() {
+        let prev = ViewerTotals::default();
+        let cur = ViewerTotals {
+            received: 62,
+            received_bytes: 125_000,
+            decode_calls: 62,
+            decoded: 60,
+            decode_us: 120_000,
+            decode_errors: 2,
+            rendered: 55,
+            lost: 1,
+        };
+        let stats = cur.stats_since(&prev, Duration::from_secs(1));
+        assert_eq!(stats.fps, 55.0);
+        assert_eq!(stats.bitrate_bps, 1_000_000);
+        assert_eq!(stats.dropped_frames, 6);
+        assert_eq!(stats.decode_errors, 2);
+        assert!((stats.avg_decode_ms - 120.0 / 62.0).abs() < 1e-9);
+
+        let idle = cur.stats_since(&cur, Duration::from_secs(1));
+        assert_eq!(idle.fps, 0.0);
+        assert_eq!(idle.avg_decode_ms, 0.0);
+        assert_eq!(idle.total_dropped, 6);
+    }
+}
@@ -0,0 +1,225 @@
+//! Per-frame pipeline timing
+//!
+//! Every stage of the sending (capture → scale → encode → send) and
+//! receiving (receive → decode → upload → render) pipelines records how long
+//! it took into an HDR histogram, so latency regressions show up as shifted
+//! percentiles instead of a feeling that "it got laggy".
+
+use hdrhistogram::Histogram;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Longest duration tracked; slower samples are clamped to it
+const MAX_TRACKED_US: u64 = 60_000_000;
+
+/// Significant figures kept by each histogram
+const PRECISION: u8 = 3;
+
+/// Pipeline stage being timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Grabbing a frame from the capture backend
+    Capture,
+    /// Cropping or downscaling to the encoder's size
+    Scale,
+    /// Encoding one frame
+    Encode,
+    /// Writing the encoded frame to every viewer
+    Send,
+    /// Sharer timestamp to arrival on the viewer (needs roughly synced clocks)
+    Receive,
+    /// Decoding one frame
+    Decode,
+    /// Copying the decoded frame to the GPU
+    Upload,
+    /// Drawing and presenting the frame
+    Render,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 8] = [
+        Stage::Capture,
+        Stage::Scale,
+        Stage::Encode,
+        Stage::Send,
+        Stage::Receive,
+        Stage::Decode,
+        Stage::Upload,
+        Stage::Render,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Capture => "capture",
+            Stage::Scale => "scale",
+            Stage::Encode => "encode",
+            Stage::Send => "send",
+            Stage::Receive => "receive",
+            Stage::Decode => "decode",
+            Stage::Upload => "upload",
+            Stage::Render => "render",
+        }
+    }
+}
+
+/// Percentile summary of one stage, in microseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageTiming {
+    pub stage: Stage,
+    pub count: u64,
+    pub min_us: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+/// One histogram per stage
+pub struct TimingHistograms {
+    histograms: Vec<Histogram<u64>>,
+}
+
+impl TimingHistograms {
+    pub fn new() -> Self {
+        Self {
+            histograms: Stage::ALL
+                .iter()
+                .map(|_| {
+                    Histogram::new_with_bounds(1, MAX_TRACKED_US, PRECISION)
+                        .expect("valid histogram bounds")
+                })
+                .collect(),
+        }
+    }
+
+    pub fn record(&mut self, stage: Stage, elapsed: Duration) {
+        let us = (elapsed.as_micros() as u64).max(1);
+        self.histograms[stage as usize].saturating_record(us);
+    }
+
+    /// Summaries of the stages that recorded at least one sample
+    pub fn summary(&self) -> Vec<StageTiming> {
+        Stage::ALL
+            .iter()
+            .zip(&self.histograms)
+            .filter(|(_, h)| !h.is_empty())
+            .map(|(&stage, h)| StageTiming {
+                stage,
+                count: h.len(),
+                min_us: h.min(),
+                mean_us: h.mean(),
+                p50_us: h.value_at_quantile(0.5),
+                p90_us: h.value_at_quantile(0.9),
+                p99_us: h.value_at_quantile(0.99),
+                p999_us: h.value_at_quantile(0.999),
+                max_us: h.max(),
+            })
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        for histogram in &mut self.histograms {
+            histogram.reset();
+        }
+    }
+}
+
+impl Default for TimingHistograms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Render summaries as CSV, one row per stage
+pub fn to_csv(timings: &[StageTiming]) -> String {
+    let mut csv = String::from("stage,count,min_us,mean_us,p50_us,p90_us,p99_us,p999_us,max_us\n");
+    for t in timings {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{},{},{},{},{}\n",
+            t.stage.name(),
+            t.count,
+            t.min_us,
+            t.mean_us,
+            t.p50_us,
+            t.p90_us,
+            t.p99_us,
+            t.p999_us,
+            t.max_us
+        ));
+    }
+    csv
+}
+
+/// Histograms shared by every pipeline in the process
+static TIMINGS: once_cell::sync::Lazy<Mutex<TimingHistograms>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(TimingHistograms::new()));
+
+/// Record how long a stage took for one frame
+pub fn record(stage: Stage, elapsed: Duration) {
+    TIMINGS.lock().record(stage, elapsed);
+}
+
+/// Current summaries of every stage with samples
+pub fn summary() -> Vec<StageTiming> {
+    TIMINGS.lock().summary()
+}
+
+/// Record how long a frame stamped `sent_ms` (sharer wall clock, ms since
+/// the Unix epoch) took to arrive. Skipped when the clocks are too far apart
+/// to give a positive delay.
+pub fn record_transit(sent_ms: u64) {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    if let Some(delay) = now_ms.checked_sub(sent_ms) {
+        record(Stage::Receive, Duration::from_millis(delay));
+    }
+}
+
+/// Forget all samples
+pub fn reset() {
+    TIMINGS.lock().reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_percentiles() {
+        let mut timings = TimingHistograms::new();
+        for ms in 1..=100 {
+            timings.record(Stage::Encode, Duration::from_millis(ms));
+        }
+        timings.record(Stage::Capture, Duration::ZERO);
+
+        let summary = timings.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].stage, Stage::Capture);
+        assert_eq!(summary[0].min_us, 1);
+
+        let encode = &summary[1];
+        assert_eq!(encode.count, 100);
+        assert!((49_900..=50_100).contains(&encode.p50_us));
+        assert!((98_900..=99_100).contains(&encode.p99_us));
+
+        timings.reset();
+        assert!(timings.summary().is_empty());
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let mut timings = TimingHistograms::new();
+        timings.record(Stage::Decode, Duration::from_micros(500));
+        let csv = to_csv(&timings.summary());
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("stage,count"));
+        assert!(lines[1].starts_with("decode,1,"));
+    }
+}
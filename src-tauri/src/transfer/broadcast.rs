@@ -0,0 +1,204 @@
+//! Meeting file offers
+//!
+//! A file offered to a whole meeting goes to the members reachable at that
+//! moment. The sender keeps the offer for `OFFER_TTL` so members who join
+//! later can still get it: once a newcomer sees itself in the meeting
+//! roster it sends `MeetingFilesRequest` to the other members, and each
+//! re-offers the files the newcomer missed. The meeting ID is only handed
+//! to invitees, so knowing it is what entitles a peer to the files.
+
+use crate::meeting::{InviteResponse, ScheduledMeeting};
+use crate::network::discovery::{self, DeviceStatus};
+use crate::network::protocol::{self, Message};
+use crate::network::quic;
+use crate::transfer::FileTransfer;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a late joiner can still ask for a meeting file
+pub const OFFER_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A file offered to a meeting
+#[derive(Debug)]
+struct MeetingOffer {
+    meeting_id: String,
+    path: PathBuf,
+    offered_at: Instant,
+    /// Peer IPs the file was offered to
+    recipients: HashSet<String>,
+}
+
+/// Files we offered to meetings, kept until they expire
+#[derive(Debug, Default)]
+struct OfferRegistry {
+    offers: Vec<MeetingOffer>,
+}
+
+impl OfferRegistry {
+    fn register(&mut self, meeting_id: &str, path: &Path, recipients: HashSet<String>, now: Instant) {
+        self.prune(now);
+        self.offers.push(MeetingOffer {
+            meeting_id: meeting_id.to_string(),
+            path: path.to_path_buf(),
+            offered_at: now,
+            recipients,
+        });
+    }
+
+    /// Files of a meeting that `peer_ip` has not been offered yet; they are
+    /// marked offered, so asking twice does not send them twice
+    fn take_missed(&mut self, meeting_id: &str, peer_ip: &str, now: Instant) -> Vec<PathBuf> {
+        self.prune(now);
+        self.offers
+            .iter_mut()
+            .filter(|o| o.meeting_id == meeting_id)
+            .filter(|o| o.recipients.insert(peer_ip.to_string()))
+            .map(|o| o.path.clone())
+            .collect()
+    }
+
+    fn forget_meeting(&mut self, meeting_id: &str) {
+        self.offers.retain(|o| o.meeting_id != meeting_id);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        self.offers.retain(|o| now.duration_since(o.offered_at) < OFFER_TTL);
+    }
+}
+
+static REGISTRY: once_cell::sync::Lazy<Mutex<OfferRegistry>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(OfferRegistry::default()));
+
+/// (meeting, member) pairs we already asked for missed files
+static ASKED: once_cell::sync::Lazy<Mutex<HashSet<(String, String)>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Device IDs of everyone in a meeting but us: the host and accepted members
+fn other_members(meeting: &ScheduledMeeting) -> Vec<String> {
+    let our_id = discovery::get_our_device_id();
+    let mut members = meeting.accepted_members();
+    members.push(meeting.host_id.clone());
+    members.sort();
+    members.dedup();
+    members.retain(|id| id != our_id);
+    members
+}
+
+/// Offer a file to every member of a meeting that is online, and keep it
+/// for members who join later
+pub async fn offer_to_meeting(path: &Path, meeting: &ScheduledMeeting) -> Result<Vec<FileTransfer>, String> {
+    if !path.is_file() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+    let members = other_members(meeting);
+    let devices = discovery::get_devices();
+
+    let mut transfers = Vec::new();
+    let mut recipients = HashSet::new();
+    for id in &members {
+        let Some(device) = devices.iter().find(|d| &d.id == id && d.status != DeviceStatus::Offline) else {
+            log::debug!("Meeting member {} offline, file kept for later", id);
+            continue;
+        };
+        match crate::commands::offer_file(path.display().to_string(), device.ip.clone()).await {
+            Ok(transfer) => {
+                recipients.insert(device.ip.clone());
+                transfers.push(transfer);
+            }
+            Err(e) => log::warn!("Failed to offer {} to {}: {}", path.display(), device.name, e),
+        }
+    }
+
+    log::info!(
+        "Offered {} to {} of {} members of meeting '{}'",
+        path.display(),
+        recipients.len(),
+        members.len(),
+        meeting.name
+    );
+    REGISTRY.lock().register(&meeting.id, path, recipients, Instant::now());
+    Ok(transfers)
+}
+
+/// Ask the other members of a meeting we are in for files offered before
+/// we joined. Each member is asked once.
+pub async fn request_missed_files(meeting: &ScheduledMeeting) {
+    let our_id = discovery::get_our_device_id();
+    if meeting.is_local || !meeting.accepted_members().iter().any(|id| id == our_id) {
+        return;
+    }
+    let Ok(encoded) = protocol::encode(&Message::MeetingFilesRequest {
+        meeting_id: meeting.id.clone(),
+    }) else {
+        return;
+    };
+
+    let devices = discovery::get_devices();
+    for id in other_members(meeting) {
+        let Some(device) = devices.iter().find(|d| d.id == id) else {
+            continue;
+        };
+        if !ASKED.lock().insert((meeting.id.clone(), id)) {
+            continue;
+        }
+        if let Err(e) = quic::send_to_peer(&device.ip, &encoded).await {
+            log::debug!("Failed to ask {} for meeting files: {}", device.name, e);
+        }
+    }
+}
+
+/// A member that joined late asked for the files it missed
+pub async fn handle_files_request(peer_ip: &str, meeting_id: &str) {
+    let Some(meeting) = crate::meeting::get_meeting_manager().get_meeting(meeting_id) else {
+        log::debug!("{} asked for files of unknown meeting {}", peer_ip, meeting_id);
+        return;
+    };
+    let declined = discovery::get_devices()
+        .iter()
+        .find(|d| d.ip == peer_ip)
+        .is_some_and(|d| meeting.responses.get(&d.id) == Some(&InviteResponse::Declined));
+    if declined {
+        log::info!("{} declined meeting '{}', not sending its files", peer_ip, meeting.name);
+        return;
+    }
+
+    let missed = REGISTRY.lock().take_missed(meeting_id, peer_ip, Instant::now());
+    if !missed.is_empty() {
+        log::info!("Offering {} meeting files to late joiner {}", missed.len(), peer_ip);
+    }
+    for path in missed {
+        if let Err(e) = crate::commands::offer_file(path.display().to_string(), peer_ip.to_string()).await {
+            log::warn!("Failed to offer {} to {}: {}", path.display(), peer_ip, e);
+        }
+    }
+}
+
+/// Drop a cancelled meeting's files
+pub fn meeting_cancelled(meeting_id: &str) {
+    REGISTRY.lock().forget_meeting(meeting_id);
+    ASKED.lock().retain(|(id, _)| id != meeting_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_late_joiner_gets_missed_files_once() {
+        let start = Instant::now();
+        let mut registry = OfferRegistry::default();
+        let online: HashSet<String> = ["10.0.0.2".to_string()].into_iter().collect();
+        registry.register("m1", Path::new("a.pdf"), online, start);
+        registry.register("m2", Path::new("b.pdf"), HashSet::new(), start);
+
+        assert!(registry.take_missed("m1", "10.0.0.2", start).is_empty());
+        assert_eq!(registry.take_missed("m1", "10.0.0.3", start), vec![PathBuf::from("a.pdf")]);
+        assert!(registry.take_missed("m1", "10.0.0.3", start).is_empty());
+
+        // Expired
+        assert!(registry.take_missed("m2", "10.0.0.3", start + OFFER_TTL).is_empty());
+        assert!(registry.offers.is_empty());
+    }
+}
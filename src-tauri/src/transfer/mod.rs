@@ -1,6 +1,10 @@
 // File transfer module
 // P2P file sharing with resume support
 
+pub mod broadcast;
+pub mod watch;
+
+use crate::app_state::Slice;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -17,6 +21,11 @@ pub const CHUNK_SIZE: usize = 64 * 1024;
 /// Maximum concurrent transfers
 pub const MAX_CONCURRENT_TRANSFERS: usize = 5;
 
+/// Files up to this size are sent in one message and written to disk once
+/// on arrival, skipping the chunked pipeline (4MB, well under the protocol's
+/// `MAX_MESSAGE_SIZE`)
+pub const SMALL_FILE_MAX: u64 = 4 * 1024 * 1024;
+
 #[derive(Error, Debug)]
 pub enum TransferError {
     #[error("Transfer failed: {0}")]
@@ -79,6 +88,26 @@ impl FileInfo {
             mime_type,
         })
     }
+
+    /// Whether the file goes in a single message
+    pub fn is_small(&self) -> bool {
+        self.size <= SMALL_FILE_MAX
+    }
+
+    /// Check the contents of a small file received in one piece
+    pub fn verify_data(&self, data: &[u8]) -> Result<(), TransferError> {
+        if data.len() as u64 != self.size {
+            return Err(TransferError::TransferFailed(format!(
+                "Expected {} bytes, got {}",
+                self.size,
+                data.len()
+            )));
+        }
+        if format!("{:x}", Sha256::digest(data)) != self.checksum {
+            return Err(TransferError::ChecksumMismatch);
+        }
+        Ok(())
+    }
 }
 
 /// Calculate SHA-256 checksum of a file
@@ -248,6 +277,14 @@ impl FileSender {
         Ok(buffer)
     }
 
+    /// Read the whole file, for sending a small file in one message
+    pub fn read_all(&mut self) -> Result<Vec<u8>, TransferError> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::with_capacity(self.info.size as usize);
+        self.file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
     /// Get total number of chunks
     pub fn chunk_count(&self) -> u64 {
         (self.info.size + CHUNK_SIZE as u64 - 1) / CHUNK_SIZE as u64
@@ -400,18 +437,22 @@ impl TransferManager {
         // Store
         self.transfers.write().insert(file_id.clone(), transfer.clone());
         self.senders.write().insert(file_id, sender);
+        crate::app_state::changed(Slice::Transfers);
 
         Ok(transfer)
     }
 
-    /// Receive a file offer (incoming)
-    pub fn receive_offer(&self, info: FileInfo, peer_id: &str) -> FileTransfer {
+    /// Receive a file offer (incoming). The peer picks the name, so one
+    /// that would land outside the download directory is refused.
+    pub fn receive_offer(&self, mut info: FileInfo, peer_id: &str) -> Result<FileTransfer, TransferError> {
+        info.name = safe_file_name(&info.name)?;
         let file_id = info.id.clone();
 
         let transfer = FileTransfer::new_incoming(info, peer_id);
         self.transfers.write().insert(file_id, transfer.clone());
+        crate::app_state::changed(Slice::Transfers);
 
-        transfer
+        Ok(transfer)
     }
 
     /// Accept an incoming file transfer
@@ -432,13 +473,16 @@ impl TransferManager {
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| self.download_dir.join(&transfer.info.name));
 
-        // Create receiver
-        let receiver = FileReceiver::new(transfer.info.clone(), &dest)?;
+        // Small files arrive whole and are written in `receive_whole`;
+        // larger ones get a receiver that writes chunks as they come
+        if !transfer.info.is_small() {
+            let receiver = FileReceiver::new(transfer.info.clone(), &dest)?;
+            self.receivers.write().insert(file_id.to_string(), receiver);
+        }
 
         transfer.local_path = Some(dest.to_string_lossy().to_string());
         transfer.start();
-
-        self.receivers.write().insert(file_id.to_string(), receiver);
+        crate::app_state::changed(Slice::Transfers);
 
         Ok(())
     }
@@ -451,6 +495,7 @@ impl TransferManager {
             .ok_or_else(|| TransferError::TransferNotFound(file_id.to_string()))?;
 
         transfer.cancel();
+        crate::app_state::changed(Slice::Transfers);
         Ok(())
     }
 
@@ -479,11 +524,57 @@ impl TransferManager {
         let mut transfers = self.transfers.write();
         if let Some(transfer) = transfers.get_mut(file_id) {
             transfer.update_progress(bytes);
+            crate::app_state::changed(Slice::Transfers);
         }
 
         Ok(bytes)
     }
 
+    /// The contents of an outgoing small file, or `None` if the file is too
+    /// large and goes in chunks
+    pub fn small_file_data(&self, file_id: &str) -> Result<Option<Vec<u8>>, TransferError> {
+        let mut senders = self.senders.write();
+        let sender = senders
+            .get_mut(file_id)
+            .ok_or_else(|| TransferError::TransferNotFound(file_id.to_string()))?;
+        if !sender.info().is_small() {
+            return Ok(None);
+        }
+        sender.read_all().map(Some)
+    }
+
+    /// Save a small file that arrived in one message and complete its
+    /// transfer. Nothing touches the disk until the data checked out.
+    pub fn receive_whole(&self, file_id: &str, data: &[u8]) -> Result<(), TransferError> {
+        let mut transfers = self.transfers.write();
+        let transfer = transfers
+            .get_mut(file_id)
+            .ok_or_else(|| TransferError::TransferNotFound(file_id.to_string()))?;
+        if transfer.direction != TransferDirection::Incoming || transfer.status != TransferStatus::InProgress {
+            return Err(TransferError::TransferFailed(
+                "File data for a transfer that was not accepted".to_string(),
+            ));
+        }
+        let Some(dest) = transfer.local_path.clone() else {
+            return Err(TransferError::TransferFailed("No destination path".to_string()));
+        };
+
+        let result = transfer.info.verify_data(data).and_then(|()| {
+            let dest = Path::new(&dest);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(dest, data)?;
+            Ok(())
+        });
+        match &result {
+            Ok(()) => transfer.complete(),
+            Err(e) => transfer.fail(&e.to_string()),
+        }
+        crate::app_state::changed(Slice::Transfers);
+        result
+    }
+
     /// Complete a transfer
     pub fn complete_transfer(&self, file_id: &str) -> Result<(), TransferError> {
         // Finalize receiver if incoming
@@ -498,6 +589,7 @@ impl TransferManager {
         let mut transfers = self.transfers.write();
         if let Some(transfer) = transfers.get_mut(file_id) {
             transfer.complete();
+            crate::app_state::changed(Slice::Transfers);
         }
 
         // Clean up sender/receiver
@@ -512,6 +604,7 @@ impl TransferManager {
         let mut transfers = self.transfers.write();
         if let Some(transfer) = transfers.get_mut(file_id) {
             transfer.cancel();
+            crate::app_state::changed(Slice::Transfers);
         }
 
         // Clean up
@@ -550,6 +643,7 @@ impl TransferManager {
                 TransferStatus::Pending | TransferStatus::InProgress | TransferStatus::Offered
             )
         });
+        crate::app_state::changed(Slice::Transfers);
     }
 }
 
@@ -559,6 +653,21 @@ impl Default for TransferManager {
     }
 }
 
+/// The bare file name of an offered `name`; fails for one with a root or
+/// parent component, or nothing left to name a file by
+fn safe_file_name(name: &str) -> Result<String, TransferError> {
+    use std::path::Component;
+
+    let path = Path::new(name);
+    let escapes = path
+        .components()
+        .any(|c| matches!(c, Component::Prefix(_) | Component::RootDir | Component::ParentDir));
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(file_name) if !escapes && !matches!(file_name, "" | "." | "..") => Ok(file_name.to_string()),
+        _ => Err(TransferError::TransferFailed(format!("Unsafe file name: {}", name))),
+    }
+}
+
 /// Global transfer manager
 static TRANSFER_MANAGER: once_cell::sync::Lazy<Arc<TransferManager>> =
     once_cell::sync::Lazy::new(|| Arc::new(TransferManager::new()));
@@ -613,6 +722,47 @@ mod tests {
         assert_eq!(chunk.len(), 1000);
     }
 
+    #[test]
+    fn test_small_file_received_whole() {
+        let dir = tempdir().unwrap();
+        let src_path = dir.path().join("config.toml");
+        let dst_path = dir.path().join("out").join("config.toml");
+        std::fs::write(&src_path, "fps = 30\n").unwrap();
+
+        let mut sender = FileSender::new(&src_path).unwrap();
+        let info = sender.info().clone();
+        assert!(info.is_small());
+        let data = sender.read_all().unwrap();
+        assert!(matches!(info.verify_data(b"fps = 60\n"), Err(TransferError::ChecksumMismatch)));
+
+        let manager = TransferManager::new();
+        manager.receive_offer(info.clone(), "peer").unwrap();
+        manager.accept_transfer(&info.id, Some(&dst_path)).unwrap();
+        // Nothing written before the data arrives
+        assert!(!dst_path.exists());
+
+        manager.receive_whole(&info.id, &data).unwrap();
+        assert_eq!(std::fs::read(&dst_path).unwrap(), b"fps = 30\n");
+        assert_eq!(manager.get_transfer(&info.id).unwrap().status, TransferStatus::Completed);
+    }
+
+    #[test]
+    fn test_offered_name_stays_in_download_dir() {
+        let offer = |name: &str| FileInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            size: 4,
+            checksum: String::new(),
+            mime_type: None,
+        };
+        let manager = TransferManager::new();
+        for name in ["../../.bashrc", "/etc/passwd", "notes/../../x", "..", ".", ""] {
+            assert!(manager.receive_offer(offer(name), "peer").is_err(), "{} accepted", name);
+        }
+        assert_eq!(manager.receive_offer(offer("report.pdf"), "peer").unwrap().info.name, "report.pdf");
+        assert_eq!(manager.receive_offer(offer("docs/report.pdf"), "peer").unwrap().info.name, "report.pdf");
+    }
+
     #[test]
     fn test_file_receiver() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,209 @@
+//! Watch folder
+//!
+//! Files dropped into a configured folder are offered to a chosen device
+//! automatically, e.g. photos copied off a camera or build artifacts. The
+//! folder is polled rather than watched with OS notifications: a file is
+//! only sent once its size and modification time hold still between two
+//! polls, so files still being written are not picked up half-done. Files
+//! already in the folder when watching starts are left alone.
+
+use crate::network::discovery;
+use crate::network::quic;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+
+/// How often the folder is listed
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Extensions of files that are still being downloaded or written
+const PARTIAL_EXTENSIONS: [&str; 5] = ["part", "partial", "crdownload", "download", "tmp"];
+
+/// Folder to watch and the device that receives its files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchSettings {
+    pub folder: PathBuf,
+    pub device_id: String,
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+static WAKE: once_cell::sync::Lazy<Notify> = once_cell::sync::Lazy::new(Notify::new);
+
+/// Start the watch loop (idles while no folder is configured)
+pub fn start() {
+    if !RUNNING.swap(true, Ordering::SeqCst) {
+        tokio::spawn(watch_loop());
+    }
+}
+
+/// Apply changed watch folder settings
+pub fn settings_changed() {
+    WAKE.notify_one();
+}
+
+async fn watch_loop() {
+    let mut watched: Option<(WatchSettings, FolderScan)> = None;
+    loop {
+        let settings = crate::commands::watch_folder_settings();
+        if watched.as_ref().map(|(s, _)| s) != settings.as_ref() {
+            watched = settings.map(|settings| {
+                log::info!(
+                    "Watching {} for files to send to {}",
+                    settings.folder.display(),
+                    settings.device_id
+                );
+                let scan = FolderScan::new(list_files(&settings.folder));
+                (settings, scan)
+            });
+        }
+
+        if let Some((settings, scan)) = watched.as_mut() {
+            let ready = scan.update(list_files(&settings.folder));
+            for path in ready {
+                if send(&path, &settings.device_id).await {
+                    scan.mark_sent(&path);
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = WAKE.notified() => {}
+        }
+    }
+}
+
+/// Offer one file to the device. Returns false if it should be retried
+/// later (device not connected, offer not delivered, file still busy).
+async fn send(path: &Path, device_id: &str) -> bool {
+    let Some(device) = discovery::get_devices().into_iter().find(|d| d.id == device_id) else {
+        log::debug!("Watch folder device {} not found, will retry", device_id);
+        return false;
+    };
+    if quic::find_connection(&device.ip).is_none_or(|c| !c.is_alive()) {
+        log::debug!("Watch folder device {} not connected, will retry", device.name);
+        return false;
+    }
+
+    log::info!("Watch folder: sending {} to {}", path.display(), device.name);
+    match crate::commands::offer_file(path.display().to_string(), device.ip.clone()).await {
+        Ok(_) => true,
+        Err(e) => {
+            // Unreadable or vanished: not worth retrying
+            let retry = std::fs::File::open(path).is_ok();
+            log::warn!(
+                "Watch folder: failed to offer {}: {}{}",
+                path.display(),
+                e,
+                if retry { ", will retry" } else { "" }
+            );
+            !retry
+        }
+    }
+}
+
+/// Size and modification time of a file
+type Stamp = (u64, SystemTime);
+
+/// Regular, visible, finished-looking files directly in `folder`
+fn list_files(folder: &Path) -> HashMap<PathBuf, Stamp> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !name.starts_with('~')
+        })
+        .filter(|e| {
+            e.path()
+                .extension()
+                .is_none_or(|ext| !PARTIAL_EXTENSIONS.iter().any(|p| ext.eq_ignore_ascii_case(p)))
+        })
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            meta.is_file().then(|| (e.path(), (meta.len(), meta.modified().ok()?)))
+        })
+        .collect()
+}
+
+/// Finds files that appeared in the folder and finished being written
+#[derive(Debug, Default)]
+struct FolderScan {
+    /// Files already sent, or present when watching started
+    done: HashSet<PathBuf>,
+    /// New files and how they looked at the last poll
+    candidates: HashMap<PathBuf, Stamp>,
+}
+
+impl FolderScan {
+    fn new(existing: HashMap<PathBuf, Stamp>) -> Self {
+        Self { done: existing.into_keys().collect(), candidates: HashMap::new() }
+    }
+
+    /// Feed a new listing; returns files that are ready to send
+    fn update(&mut self, listing: HashMap<PathBuf, Stamp>) -> Vec<PathBuf> {
+        // A deleted file may come back with new contents
+        self.done.retain(|path| listing.contains_key(path));
+
+        let mut ready = Vec::new();
+        let mut candidates = HashMap::new();
+        for (path, stamp) in listing {
+            if self.done.contains(&path) {
+                continue;
+            }
+            if self.candidates.get(&path) == Some(&stamp) && stamp.0 > 0 {
+                ready.push(path.clone());
+            }
+            candidates.insert(path, stamp);
+        }
+        self.candidates = candidates;
+        ready.sort();
+        ready
+    }
+
+    fn mark_sent(&mut self, path: &Path) {
+        self.candidates.remove(path);
+        self.done.insert(path.to_path_buf());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(files: &[(&str, u64)]) -> HashMap<PathBuf, Stamp> {
+        files
+            .iter()
+            .map(|&(name, size)| (PathBuf::from(name), (size, SystemTime::UNIX_EPOCH)))
+            .collect()
+    }
+
+    #[test]
+    fn test_new_files_are_sent_once_stable() {
+        let mut scan = FolderScan::new(listing(&[("old.txt", 10)]));
+
+        assert!(scan.update(listing(&[("old.txt", 10), ("new.jpg", 100)])).is_empty());
+        // Still growing
+        assert!(scan.update(listing(&[("old.txt", 10), ("new.jpg", 200)])).is_empty());
+        let ready = scan.update(listing(&[("old.txt", 10), ("new.jpg", 200)]));
+        assert_eq!(ready, vec![PathBuf::from("new.jpg")]);
+
+        scan.mark_sent(&ready[0]);
+        assert!(scan.update(listing(&[("old.txt", 10), ("new.jpg", 200)])).is_empty());
+    }
+
+    #[test]
+    fn test_unsent_file_is_retried() {
+        let mut scan = FolderScan::new(HashMap::new());
+        scan.update(listing(&[("a.zip", 5)]));
+        assert_eq!(scan.update(listing(&[("a.zip", 5)])).len(), 1);
+        // Not marked sent (device offline): offered again next poll
+        assert_eq!(scan.update(listing(&[("a.zip", 5)])).len(), 1);
+    }
+}
@@ -1,5 +1,7 @@
-import { Component, createSignal, onMount, Show } from "solid-js";
+import { Component, createEffect, createSignal, onCleanup, onMount, Show } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { appStore } from "./stores/app";
 import { MeetingRoom } from "./components/MeetingRoom";
 import { Settings } from "./components/Settings";
 
@@ -7,6 +9,8 @@ export interface SelfInfo {
   id: string;
   name: string;
   ip: string;
+  avatar?: string | null;
+  fingerprint?: string | null;
 }
 
 const App: Component = () => {
@@ -16,15 +20,37 @@ const App: Component = () => {
   const [showSettings, setShowSettings] = createSignal(false);
   const [error, setError] = createSignal<string | null>(null);
 
-  // Fetch self info on mount
+  // Rebuild from the backend's state (the service may still be running
+  // after a webview reload) and follow its changes
+  let unlistenState: (() => void) | undefined;
   onMount(async () => {
     try {
-      const info = await invoke<SelfInfo>("get_self_info");
-      setSelfInfo(info);
+      unlistenState = await appStore.syncState();
     } catch (e) {
-      console.error("Failed to get self info:", e);
+      console.error("Failed to load app state:", e);
     }
   });
+  onCleanup(() => unlistenState?.());
+
+  // Read out which window a sharer switched to, for screen reader users
+  const [announcement, setAnnouncement] = createSignal("");
+  let unlistenMetadata: (() => void) | undefined;
+  onMount(async () => {
+    unlistenMetadata = await listen<{ peer_ip: string; window_title: string }>("screen-metadata", (event) => {
+      const { peer_ip, window_title } = event.payload;
+      const sharer = appStore.devices().find((d) => d.ip === peer_ip)?.name ?? peer_ip;
+      setAnnouncement(`${sharer} 切换到了 ${window_title}`);
+    });
+  });
+  onCleanup(() => unlistenMetadata?.());
+
+  createEffect(() => setIsServiceEnabled(appStore.serviceRunning()));
+
+  // Our address can change while running (DHCP renewal, VPN toggle)
+  createEffect(() => {
+    const info = appStore.selfInfo();
+    if (info) setSelfInfo({ ...info, ip: info.ip ?? "" });
+  });
 
   // Start service
   const handleStartService = async () => {
@@ -57,6 +83,11 @@ const App: Component = () => {
 
   return (
     <div class="h-full flex flex-col bg-gray-50">
+      {/* Screen reader announcements */}
+      <div class="sr-only" role="status" aria-live="polite">
+        {announcement()}
+      </div>
+
       {/* Settings Modal */}
       <Show when={showSettings()}>
         <Settings onClose={() => setShowSettings(false)} />
@@ -1,5 +1,7 @@
-import { Component, createSignal } from "solid-js";
+import { Component, createSignal, For, Show } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { connectErrorText, withMeetingPassword } from "../../stores/app";
 
 interface AddDeviceModalProps {
   onClose: () => void;
@@ -10,6 +12,40 @@ export const AddDeviceModal: Component<AddDeviceModalProps> = (props) => {
   const [ip, setIp] = createSignal("");
   const [isAdding, setIsAdding] = createSignal(false);
   const [error, setError] = createSignal<string | null>(null);
+  const [progress, setProgress] = createSignal<{ scanned: number; total: number; found: number } | null>(null);
+  // Addresses a scan found, for the user to pick the one to add
+  const [candidates, setCandidates] = createSignal<string[]>([]);
+
+  // A subnet (192.168.1.0/24) is scanned instead of added
+  const isSubnet = () => ip().includes("/");
+
+  const handleScan = async (cidr: string) => {
+    if (!/^(\d{1,3}\.){3}\d{1,3}\/\d{1,2}$/.test(cidr)) {
+      setError("子网格式不正确，例如 192.168.1.0/24");
+      return;
+    }
+    setIsAdding(true);
+    setError(null);
+    setCandidates([]);
+    const unlisten = await listen<{ scanned: number; total: number; found: number }>(
+      "subnet-scan-progress",
+      (event) => setProgress(event.payload),
+    );
+    try {
+      const found = await invoke<string[]>("scan_subnet", { cidr });
+      if (found.length === 0) {
+        setError("没有在该子网中找到设备");
+      }
+      setCandidates(found);
+    } catch (e) {
+      console.error("Failed to scan subnet:", e);
+      setError(`扫描失败: ${connectErrorText(e)}`);
+    } finally {
+      unlisten();
+      setProgress(null);
+      setIsAdding(false);
+    }
+  };
 
   const handleAdd = async () => {
     const ipValue = ip().trim();
@@ -17,23 +53,32 @@ export const AddDeviceModal: Component<AddDeviceModalProps> = (props) => {
       setError("请输入 IP 地址");
       return;
     }
+    if (isSubnet()) {
+      await handleScan(ipValue);
+      return;
+    }
 
-    // Basic IP validation
+    // Basic IP validation: IPv4, or IPv6 with an optional interface (fe80::1%3)
     const ipRegex = /^(\d{1,3}\.){3}\d{1,3}$/;
-    if (!ipRegex.test(ipValue)) {
+    const ipv6Regex = /^[0-9a-fA-F:]*:[0-9a-fA-F:.]*(%\d+)?$/;
+    if (!ipRegex.test(ipValue) && !ipv6Regex.test(ipValue)) {
       setError("IP 地址格式不正确");
       return;
     }
 
+    await addDevice(ipValue);
+  };
+
+  const addDevice = async (ipValue: string) => {
     setIsAdding(true);
     setError(null);
 
     try {
-      await invoke("add_manual_device", { ip: ipValue });
+      await withMeetingPassword((password) => invoke("add_manual_device", { ip: ipValue, password }));
       props.onAdded();
     } catch (e) {
       console.error("Failed to add device:", e);
-      setError(`添加失败: ${e}`);
+      setError(`添加失败: ${connectErrorText(e)}`);
     } finally {
       setIsAdding(false);
     }
@@ -84,8 +129,30 @@ export const AddDeviceModal: Component<AddDeviceModalProps> = (props) => {
             autofocus
           />
           <p class="text-xs text-gray-500 mt-2">
-            输入对方设备的局域网 IP 地址
+            输入对方设备的局域网 IP 地址，或输入子网（如 192.168.1.0/24）扫描其中的设备
           </p>
+          {progress() && (
+            <p class="text-xs text-gray-500 mt-1">
+              已扫描 {progress()!.scanned}/{progress()!.total}，找到 {progress()!.found} 台
+            </p>
+          )}
+          <Show when={candidates().length > 0}>
+            <p class="text-sm font-medium text-gray-700 mt-4 mb-2">选择要添加的设备</p>
+            <div class="max-h-48 overflow-y-auto border border-gray-200 rounded-lg divide-y divide-gray-100">
+              <For each={candidates()}>
+                {(candidate) => (
+                  <button
+                    class="w-full px-4 py-2 flex items-center justify-between text-sm font-mono text-gray-800 hover:bg-gray-50 disabled:opacity-50"
+                    onClick={() => addDevice(candidate)}
+                    disabled={isAdding()}
+                  >
+                    {candidate}
+                    <span class="i-lucide-plus text-gray-400"></span>
+                  </button>
+                )}
+              </For>
+            </div>
+          </Show>
         </div>
 
         {/* Footer */}
@@ -104,12 +171,12 @@ export const AddDeviceModal: Component<AddDeviceModalProps> = (props) => {
             {isAdding() ? (
               <>
                 <span class="i-lucide-loader-2 animate-spin"></span>
-                连接中...
+                {isSubnet() ? "扫描中..." : "连接中..."}
               </>
             ) : (
               <>
-                <span class="i-lucide-plus"></span>
-                添加
+                <span class={isSubnet() ? "i-lucide-radar" : "i-lucide-plus"}></span>
+                {isSubnet() ? "扫描" : "添加"}
               </>
             )}
           </button>
@@ -1,6 +1,7 @@
 import { Component, createSignal, For, onMount, onCleanup, createEffect } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { appStore } from "../../stores/app";
 
 interface ChatMessage {
   id: string;
@@ -28,6 +29,10 @@ export const Chat: Component = () => {
     });
   };
 
+  // Avatar of the device a message came from, if it sent one
+  const avatarOf = (deviceId: string) =>
+    appStore.devices().find((d) => d.id === deviceId)?.avatar;
+
   // Scroll to bottom when new messages arrive
   const scrollToBottom = () => {
     messagesEndRef?.scrollIntoView({ behavior: "smooth" });
@@ -138,7 +143,13 @@ export const Chat: Component = () => {
                 >
                   {/* Sender name for remote messages */}
                   {!message.is_local && message.message_type !== "System" && (
-                    <div class="text-xs text-gray-500 mb-1">
+                    <div class="text-xs text-gray-500 mb-1 flex items-center gap-1.5">
+                      {avatarOf(message.from_device_id) && (
+                        <img
+                          class="w-4 h-4 rounded-full object-cover"
+                          src={`data:image/png;base64,${avatarOf(message.from_device_id)}`}
+                        />
+                      )}
                       {message.from_name}
                     </div>
                   )}
@@ -0,0 +1,131 @@
+import { Component, createSignal, For, onCleanup, onMount, Show } from "solid-js";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+type ClipboardContent =
+  | { Text: string }
+  | { Image: { width: number; height: number; png_base64: string } }
+  | { Files: { file_id: string; name: string; size: number }[] };
+
+interface ClipboardItem {
+  id: string;
+  from_name: string;
+  content: ClipboardContent;
+  timestamp: number;
+  is_local: boolean;
+}
+
+interface ClipboardHistoryProps {
+  peerIp: string;
+  peerName: string;
+  onClose: () => void;
+}
+
+export const ClipboardHistory: Component<ClipboardHistoryProps> = (props) => {
+  const [items, setItems] = createSignal<ClipboardItem[]>([]);
+  const [copiedId, setCopiedId] = createSignal<string | null>(null);
+  const [error, setError] = createSignal<string | null>(null);
+  let unlistenHistory: UnlistenFn | undefined;
+
+  const formatTime = (timestamp: number) =>
+    new Date(timestamp).toLocaleTimeString("zh-CN", { hour: "2-digit", minute: "2-digit" });
+
+  const fetchHistory = async () => {
+    try {
+      setItems(await invoke<ClipboardItem[]>("get_clipboard_history", { peerIp: props.peerIp }));
+    } catch (e) {
+      console.error("Failed to get clipboard history:", e);
+    }
+  };
+
+  const copyItem = async (item: ClipboardItem) => {
+    setError(null);
+    try {
+      await invoke("copy_clipboard_item", { peerIp: props.peerIp, itemId: item.id });
+      setCopiedId(item.id);
+    } catch (e) {
+      setError(`复制失败: ${e}`);
+    }
+  };
+
+  const clearHistory = async () => {
+    await invoke("clear_clipboard_history", { peerIp: props.peerIp });
+    setItems([]);
+  };
+
+  onMount(async () => {
+    unlistenHistory = await listen<string>("clipboard-history", (event) => {
+      if (event.payload === props.peerIp) fetchHistory();
+    });
+    await fetchHistory();
+  });
+
+  onCleanup(() => unlistenHistory?.());
+
+  return (
+    <div class="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+      <div class="bg-white rounded-2xl shadow-xl w-full max-w-md mx-4 overflow-hidden flex flex-col max-h-[80vh]">
+        {/* Header */}
+        <div class="px-6 py-4 border-b border-gray-200 flex items-center justify-between">
+          <h2 class="text-lg font-semibold text-gray-900">与 {props.peerName} 的剪贴板</h2>
+          <button class="p-1 text-gray-400 hover:text-gray-600 rounded" onClick={props.onClose}>
+            <span class="i-lucide-x text-xl"></span>
+          </button>
+        </div>
+
+        {/* Items */}
+        <div class="flex-1 overflow-auto p-4 space-y-2">
+          {error() && (
+            <div class="bg-red-50 border border-red-200 text-red-700 px-4 py-3 rounded-lg text-sm">
+              {error()}
+            </div>
+          )}
+          <For each={items()}>
+            {(item) => (
+              <button
+                class="w-full text-left p-3 border border-gray-200 rounded-lg hover:bg-gray-50"
+                title="点击复制"
+                onClick={() => copyItem(item)}
+              >
+                <div class="flex items-center justify-between text-xs text-gray-500 mb-1">
+                  <span>{item.is_local ? "我" : item.from_name} · {formatTime(item.timestamp)}</span>
+                  <Show when={copiedId() === item.id}>
+                    <span class="text-primary-600">已复制</span>
+                  </Show>
+                </div>
+                {"Text" in item.content ? (
+                  <p class="text-sm text-gray-900 line-clamp-3 whitespace-pre-wrap break-all">{item.content.Text}</p>
+                ) : "Files" in item.content ? (
+                  <p class="text-sm text-gray-900 line-clamp-3 break-all">
+                    <span class="i-lucide-file mr-1 align-middle"></span>
+                    {item.content.Files.map((f) => f.name).join(", ")}
+                  </p>
+                ) : (
+                  <img
+                    class="max-h-32 rounded"
+                    src={`data:image/png;base64,${item.content.Image.png_base64}`}
+                    alt={`${item.content.Image.width}x${item.content.Image.height}`}
+                  />
+                )}
+              </button>
+            )}
+          </For>
+          <Show when={items().length === 0}>
+            <p class="text-center py-8 text-sm text-gray-500">暂无剪贴板记录</p>
+          </Show>
+        </div>
+
+        {/* Footer */}
+        <div class="px-6 py-3 border-t border-gray-200 flex justify-end">
+          <button
+            class="px-3 py-1.5 text-sm border border-gray-300 rounded-lg hover:bg-gray-50 disabled:opacity-50"
+            disabled={items().length === 0}
+            onClick={clearHistory}
+          >
+            清空记录
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
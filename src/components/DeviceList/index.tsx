@@ -1,62 +1,143 @@
 import { Component, createSignal, For, onMount, onCleanup } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
+import { connectErrorText, withMeetingPassword } from "../../stores/app";
 
 interface Device {
   id: string;
   name: string;
   ip: string;
   port: number;
-  status: "online" | "busy" | "offline";
+  status: "seen" | "online" | "busy" | "offline";
   last_seen: number;
+  /** Base64 PNG the device introduced itself with */
+  avatar?: string | null;
+  is_sharing?: boolean;
+  /** App version, OS and capabilities from its mDNS record or handshake */
+  version?: string | null;
+  platform?: string | null;
+  capabilities?: string[];
+  incompatible?: boolean;
+  /** Name the user gave it */
+  nickname?: string | null;
+  favorite?: boolean;
 }
 
+// Entry of the "network-stats" event
+interface ConnectionStats {
+  peer_ip: string;
+  device_id: string | null;
+  rtt_ms: number;
+  loss_percent: number;
+  send_bps: number;
+  receive_bps: number;
+}
+
+const platformText: Record<string, string> = {
+  windows: "Windows",
+  macos: "macOS",
+  linux: "Linux",
+};
+
 export const DeviceList: Component = () => {
   const [devices, setDevices] = createSignal<Device[]>([]);
   const [manualIp, setManualIp] = createSignal("");
+  const [query, setQuery] = createSignal("");
   const [isLoading, setIsLoading] = createSignal(true);
   const [error, setError] = createSignal<string | null>(null);
+  const [linkStats, setLinkStats] = createSignal<Record<string, ConnectionStats>>({});
 
   let unlistenDiscovered: UnlistenFn | undefined;
   let unlistenRemoved: UnlistenFn | undefined;
+  let unlistenStatus: UnlistenFn | undefined;
   let unlistenConnection: UnlistenFn | undefined;
+  let unlistenNetworkStats: UnlistenFn | undefined;
 
   const statusColors = {
+    seen: "bg-gray-300",
     online: "bg-green-500",
     busy: "bg-yellow-500",
     offline: "bg-gray-400",
   };
 
   const statusText = {
+    seen: "已发现",
     online: "在线",
     busy: "忙碌",
     offline: "离线",
   };
 
+  // Filtering and ordering happen in the backend
+  const searchDevices = async () => {
+    try {
+      setDevices(await invoke<Device[]>("search_devices", { query: query() }));
+    } catch (e) {
+      console.error("Failed to search devices:", e);
+      setError(String(e));
+    }
+  };
+
   // Fetch devices from backend
   const fetchDevices = async () => {
+    setIsLoading(true);
+    setError(null);
+    await searchDevices();
+    setIsLoading(false);
+  };
+
+  const toggleFavorite = async (device: Device) => {
     try {
-      setIsLoading(true);
-      setError(null);
-      const result = await invoke<Device[]>("get_devices");
-      setDevices(result);
+      await invoke("set_device_favorite", { deviceId: device.id, favorite: !device.favorite });
+      await searchDevices();
     } catch (e) {
-      console.error("Failed to get devices:", e);
-      setError(String(e));
-    } finally {
-      setIsLoading(false);
+      console.error("Failed to update favorite:", e);
     }
   };
 
-  // Handle device discovered event
-  const handleDeviceDiscovered = (device: Device) => {
-    setDevices((prev) => {
-      const existing = prev.find((d) => d.id === device.id);
-      if (existing) {
-        return prev.map((d) => (d.id === device.id ? device : d));
-      }
-      return [...prev, device];
-    });
+  const renameDevice = async (device: Device) => {
+    const nickname = prompt("设备备注名（留空则清除）", device.nickname ?? device.name);
+    if (nickname === null) return;
+    try {
+      await invoke("rename_device", { deviceId: device.id, nickname });
+      await searchDevices();
+    } catch (e) {
+      console.error("Failed to rename device:", e);
+    }
+  };
+
+  const forgetDevice = async (device: Device) => {
+    if (!confirm(`忘记设备“${device.nickname ?? device.name}”？它的信任和收藏也会一并移除`)) return;
+    try {
+      await invoke("forget_device", { deviceId: device.id });
+      await searchDevices();
+    } catch (e) {
+      console.error("Failed to forget device:", e);
+    }
+  };
+
+  const blockDevice = async (device: Device) => {
+    if (!confirm(`屏蔽设备“${device.nickname ?? device.name}”？它将从列表中消失且无法再连接本机，可在设置中解除`)) return;
+    try {
+      await invoke("block_device", { deviceId: device.id });
+      await searchDevices();
+    } catch (e) {
+      console.error("Failed to block device:", e);
+    }
+  };
+
+  // Re-query the network; newly found devices arrive as events
+  const handleRefresh = async () => {
+    try {
+      await invoke("refresh_devices_now");
+    } catch (e) {
+      console.warn("Failed to refresh devices:", e);
+    }
+    await fetchDevices();
+  };
+
+  // Search again so a new or changed device lands in its place in the order
+  const handleDeviceDiscovered = (_device: Device) => {
+    searchDevices();
   };
 
   // Handle device removed event
@@ -75,6 +156,11 @@ export const DeviceList: Component = () => {
       handleDeviceRemoved(event.payload);
     });
 
+    // Heartbeats found a device gone or back; its place in the order moves
+    unlistenStatus = await listen<{ id: string; status: Device["status"] }>("device-status", () => {
+      searchDevices();
+    });
+
     // Listen for incoming connections
     unlistenConnection = await listen<{ device_id: string; device_name: string; ip: string }>(
       "connection-received",
@@ -87,6 +173,11 @@ export const DeviceList: Component = () => {
       }
     );
 
+    // Link quality of connected devices, by IP
+    unlistenNetworkStats = await listen<ConnectionStats[]>("network-stats", (event) => {
+      setLinkStats(Object.fromEntries(event.payload.map((s) => [s.peer_ip, s])));
+    });
+
     // Initial fetch
     await fetchDevices();
   });
@@ -95,12 +186,14 @@ export const DeviceList: Component = () => {
   onCleanup(() => {
     unlistenDiscovered?.();
     unlistenRemoved?.();
+    unlistenStatus?.();
     unlistenConnection?.();
+    unlistenNetworkStats?.();
   });
 
   const handleConnect = async (device: Device) => {
     try {
-      await invoke("connect_to_device", { deviceId: device.id });
+      await withMeetingPassword((password) => invoke("connect_to_device", { deviceId: device.id, password }));
       console.log("Connected to:", device);
       // Update device status locally
       setDevices((prev) =>
@@ -108,7 +201,7 @@ export const DeviceList: Component = () => {
       );
     } catch (e) {
       console.error("Failed to connect:", e);
-      setError(`连接失败: ${e}`);
+      setError(`连接失败: ${connectErrorText(e)}`);
     }
   };
 
@@ -117,12 +210,12 @@ export const DeviceList: Component = () => {
     if (!ip) return;
 
     try {
-      const device = await invoke<Device>("add_manual_device", { ip });
+      const device = await withMeetingPassword((password) => invoke<Device>("add_manual_device", { ip, password }));
       handleDeviceDiscovered(device);
       setManualIp("");
     } catch (e) {
       console.error("Failed to add manual device:", e);
-      setError(`添加设备失败: ${e}`);
+      setError(`添加设备失败: ${connectErrorText(e)}`);
     }
   };
 
@@ -174,7 +267,7 @@ export const DeviceList: Component = () => {
           </h2>
           <button
             class="btn-secondary text-sm"
-            onClick={fetchDevices}
+            onClick={handleRefresh}
             disabled={isLoading()}
           >
             <span
@@ -184,23 +277,90 @@ export const DeviceList: Component = () => {
           </button>
         </div>
 
+        {/* Search by name, pinyin or IP */}
+        <input
+          type="text"
+          placeholder="搜索设备名称、拼音或 IP"
+          value={query()}
+          onInput={(e) => {
+            setQuery(e.currentTarget.value);
+            searchDevices();
+          }}
+          class="w-full mb-4 px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+        />
+
         <div class="space-y-3">
           <For each={devices()}>
             {(device) => (
               <div class="flex items-center justify-between p-4 bg-gray-50 rounded-lg hover:bg-gray-100 transition-colors">
                 <div class="flex items-center gap-4">
-                  <div class="w-12 h-12 bg-gray-200 rounded-xl flex items-center justify-center">
-                    <span class="i-lucide-monitor text-gray-600 text-xl"></span>
-                  </div>
+                  {device.avatar ? (
+                    <img class="w-12 h-12 rounded-xl object-cover" src={`data:image/png;base64,${device.avatar}`} />
+                  ) : (
+                    <div class="w-12 h-12 bg-gray-200 rounded-xl flex items-center justify-center">
+                      <span class="i-lucide-monitor text-gray-600 text-xl"></span>
+                    </div>
+                  )}
                   <div>
-                    <h3 class="font-medium text-gray-900">{device.name}</h3>
+                    <h3 class="font-medium text-gray-900 flex items-center gap-2">
+                      {device.nickname ?? device.name}
+                      {device.nickname && <span class="text-xs font-normal text-gray-500">{device.name}</span>}
+                      {device.is_sharing && (
+                        <span class="px-1.5 py-0.5 bg-red-100 text-red-600 text-xs rounded flex items-center gap-1">
+                          <span class="i-lucide-presentation"></span>
+                          演示中
+                        </span>
+                      )}
+                      {device.incompatible && (
+                        <span
+                          class="px-1.5 py-0.5 bg-yellow-100 text-yellow-700 text-xs rounded"
+                          title="对方版本与本机不兼容，请升级到相同版本"
+                        >
+                          版本不兼容
+                        </span>
+                      )}
+                    </h3>
                     <p class="text-sm text-gray-500">
                       {device.ip}:{device.port}
+                      {device.platform && ` · ${platformText[device.platform] ?? device.platform}`}
+                      {device.version && ` · v${device.version}`}
+                      {linkStats()[device.ip] &&
+                        ` · ${Math.round(linkStats()[device.ip].rtt_ms)} ms · 丢包 ${linkStats()[device.ip].loss_percent.toFixed(1)}%`}
                     </p>
                   </div>
                 </div>
 
                 <div class="flex items-center gap-4">
+                  <button
+                    class="p-1 rounded text-gray-300 hover:text-gray-500"
+                    title="备注名"
+                    onClick={() => renameDevice(device)}
+                  >
+                    <span class="i-lucide-pencil"></span>
+                  </button>
+                  <button
+                    class="p-1 rounded text-gray-300 hover:text-red-500"
+                    title="屏蔽设备"
+                    onClick={() => blockDevice(device)}
+                  >
+                    <span class="i-lucide-ban"></span>
+                  </button>
+                  {device.status === "offline" && (
+                    <button
+                      class="p-1 rounded text-gray-300 hover:text-red-500"
+                      title="忘记设备"
+                      onClick={() => forgetDevice(device)}
+                    >
+                      <span class="i-lucide-trash-2"></span>
+                    </button>
+                  )}
+                  <button
+                    class={`p-1 rounded ${device.favorite ? "text-yellow-500" : "text-gray-300 hover:text-gray-500"}`}
+                    title={device.favorite ? "取消收藏" : "收藏"}
+                    onClick={() => toggleFavorite(device)}
+                  >
+                    <span class="i-lucide-star"></span>
+                  </button>
                   <div class="flex items-center gap-2">
                     <span
                       class={`w-2 h-2 rounded-full ${statusColors[device.status]}`}
@@ -236,7 +396,13 @@ export const DeviceList: Component = () => {
             </div>
           )}
 
-          {!isLoading() && devices().length === 0 && (
+          {!isLoading() && devices().length === 0 && query().trim() && (
+            <div class="text-center py-12 text-gray-500">
+              <p>没有匹配“{query().trim()}”的设备</p>
+            </div>
+          )}
+
+          {!isLoading() && devices().length === 0 && !query().trim() && (
             <div class="text-center py-12 text-gray-500">
               <span class="i-lucide-wifi-off text-4xl mb-4 block opacity-50"></span>
               <p>未发现设备</p>
@@ -3,6 +3,18 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
 import { SelfInfo } from "../../App";
 import { AddDeviceModal } from "../AddDeviceModal";
+import { ClipboardHistory } from "../ClipboardHistory";
+import { ReceivedUrlPrompt, SendUrlModal } from "../OpenUrl";
+import { PairingPrompt } from "../Pairing";
+import { ScreenshotAnnotator, ScreenshotAnnotateEvent } from "../ScreenshotAnnotator";
+
+interface RemoteDisplay {
+  id: number;
+  name: string;
+  width: number;
+  height: number;
+  primary: boolean;
+}
 
 interface Member {
   id: string;
@@ -11,6 +23,8 @@ interface Member {
   port: number;
   is_self: boolean;
   is_sharing: boolean;
+  avatar?: string | null;
+  displays?: RemoteDisplay[];
 }
 
 interface MeetingRoomProps {
@@ -24,13 +38,22 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
   const [members, setMembers] = createSignal<Member[]>([]);
   const [isSharing, setIsSharing] = createSignal(false);
   const [isSimpleSharing, setIsSimpleSharing] = createSignal(false);
+  const [isSharingPaused, setIsSharingPaused] = createSignal(false);
+  const [isSharingFrozen, setIsSharingFrozen] = createSignal(false);
   const [isLoadingMembers, setIsLoadingMembers] = createSignal(true);
   const [showAddModal, setShowAddModal] = createSignal(false);
+  const [clipboardPeer, setClipboardPeer] = createSignal<Member | null>(null);
+  const [urlPeer, setUrlPeer] = createSignal<Member | null>(null);
+  const [screenshot, setScreenshot] = createSignal<ScreenshotAnnotateEvent | null>(null);
   const [error, setError] = createSignal<string | null>(null);
 
   let unlistenDiscovered: UnlistenFn | undefined;
   let unlistenRemoved: UnlistenFn | undefined;
   let unlistenSharingChanged: UnlistenFn | undefined;
+  let unlistenStreamingPaused: UnlistenFn | undefined;
+  let unlistenCaptureError: UnlistenFn | undefined;
+  let unlistenScreenRejected: UnlistenFn | undefined;
+  let unlistenScreenshot: UnlistenFn | undefined;
 
   // Fetch members list
   const fetchMembers = async () => {
@@ -46,6 +69,7 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
         port: d.port,
         is_self: false,
         is_sharing: d.is_sharing || false,
+        avatar: d.avatar,
       }));
 
       // Add self to the list
@@ -57,6 +81,7 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
           port: 19876,
           is_self: true,
           is_sharing: isSharing(),
+          avatar: props.selfInfo.avatar,
         });
       }
 
@@ -79,6 +104,7 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
           name: device.name,
           ip: device.ip,
           is_sharing: device.is_sharing || false,
+          avatar: device.avatar,
         } : m);
       }
       return [...prev, {
@@ -88,6 +114,7 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
         port: device.port,
         is_self: false,
         is_sharing: device.is_sharing || false,
+        avatar: device.avatar,
       }];
     });
   };
@@ -114,10 +141,10 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
         return;
       }
 
-      // For now, use first display. TODO: Show selection UI
-      const displayId = displays[0].id;
+      // Offer every display; each one only captures while someone watches it
+      const displayIds = displays.map(d => d.id);
       // broadcast_sharing_status handles capture start internally
-      await invoke("broadcast_sharing_status", { isSharing: true, displayId });
+      await invoke("broadcast_sharing_status", { isSharing: true, displayId: displayIds[0], displayIds });
       setIsSharing(true);
 
       // Update self in member list
@@ -130,12 +157,24 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     }
   };
 
+  // Re-query the network; newly found devices arrive as events
+  const handleRefresh = async () => {
+    try {
+      await invoke("refresh_devices_now");
+    } catch (e) {
+      console.warn("Failed to refresh devices:", e);
+    }
+    await fetchMembers();
+  };
+
   // Stop sharing
   const handleStopSharing = async () => {
     try {
       // broadcast_sharing_status handles capture stop internally
       await invoke("broadcast_sharing_status", { isSharing: false, displayId: null });
       setIsSharing(false);
+      setIsSharingPaused(false);
+      setIsSharingFrozen(false);
 
       // Update self in member list
       setMembers(prev => prev.map(m =>
@@ -147,13 +186,26 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     }
   };
 
-  // Watch member's screen (opens native GPU-rendered window)
-  const handleWatchScreen = async (member: Member) => {
+  // Pause/resume sharing; viewers keep their windows with a dimmed frame
+  const handleToggleFrozen = async () => {
+    const frozen = !isSharingFrozen();
+    try {
+      await invoke(frozen ? "pause_sharing" : "resume_sharing");
+      setIsSharingFrozen(frozen);
+    } catch (e) {
+      console.error("Failed to pause/resume sharing:", e);
+      setError(`${frozen ? "暂停" : "继续"}共享失败: ${e}`);
+    }
+  };
+
+  // Watch member's screen (opens native GPU-rendered window, one per display)
+  const handleWatchScreen = async (member: Member, displayId?: number) => {
     try {
       // Request stream - native wgpu window will be created when stream starts
       await invoke("request_screen_stream", {
         peerIp: member.ip,
         peerName: member.name,
+        displayId: displayId ?? null,
       });
     } catch (e) {
       console.error("Failed to request screen stream:", e);
@@ -161,6 +213,19 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     }
   };
 
+  // Lay every open viewer window out side by side to watch several screens
+  const handleTileViewers = async () => {
+    try {
+      const count = await invoke<number>("tile_viewer_windows");
+      if (count === 0) {
+        setError("没有打开的观看窗口");
+      }
+    } catch (e) {
+      console.error("Failed to tile viewer windows:", e);
+      setError(`平铺窗口失败: ${e}`);
+    }
+  };
+
   // Request control
   const handleRequestControl = async (member: Member) => {
     try {
@@ -171,6 +236,27 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     }
   };
 
+  // Screenshot our primary display and open it for annotation before sending
+  const handleSendScreenshot = async (member: Member) => {
+    try {
+      const hasPermission = await invoke<boolean>("check_screen_permission");
+      if (!hasPermission) {
+        await invoke("request_screen_permission");
+        return;
+      }
+      const displays = await invoke<any[]>("get_displays");
+      const display = displays.find(d => d.primary) ?? displays[0];
+      if (!display) {
+        setError("未找到可截图的显示器");
+        return;
+      }
+      await invoke("send_screenshot", { displayId: display.id, region: null, peerIp: member.ip, annotate: true });
+    } catch (e) {
+      console.error("Failed to take screenshot:", e);
+      setError(`截图失败: ${e}`);
+    }
+  };
+
   // ===== Simple streaming handlers (minimal pipeline for debugging) =====
 
   const handleSimpleStartSharing = async () => {
@@ -215,6 +301,15 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     }
   };
 
+  const handleSimpleSwitchDisplay = async (member: Member, displayId: number) => {
+    try {
+      await invoke("simple_switch_display", { peerIp: member.ip, displayId });
+    } catch (e) {
+      console.error("[SIMPLE] Failed to switch display:", e);
+      setError(`[Simple] 切换显示器失败: ${e}`);
+    }
+  };
+
   // Setup event listeners
   onMount(async () => {
     unlistenDiscovered = await listen<any>("device-discovered", (event) => {
@@ -225,17 +320,55 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
       handleMemberRemoved(event.payload);
     });
 
-    unlistenSharingChanged = await listen<{ device_id: string; is_sharing: boolean }>(
+    unlistenSharingChanged = await listen<{ device_id: string; is_sharing: boolean; displays: RemoteDisplay[] }>(
       "sharing-status-changed",
       (event) => {
         setMembers(prev => prev.map(m =>
           m.id === event.payload.device_id
-            ? { ...m, is_sharing: event.payload.is_sharing }
+            ? { ...m, is_sharing: event.payload.is_sharing, displays: event.payload.displays }
             : m
         ));
       }
     );
 
+    unlistenStreamingPaused = await listen<{ paused: boolean }>("streaming-paused", (event) => {
+      setIsSharingPaused(event.payload.paused);
+    });
+
+    unlistenScreenRejected = await listen<{ peer_ip: string; reason: string; max_viewers: number | null }>(
+      "screen-rejected",
+      (event) => {
+        const { peer_ip, reason, max_viewers } = event.payload;
+        const name = members().find(m => m.ip === peer_ip)?.name ?? peer_ip;
+        if (reason === "stream_full") {
+          setError(`${name} 的共享观看人数已满（最多 ${max_viewers} 人）`);
+        } else if (reason === "declined") {
+          setError(`${name} 没有同意你观看其屏幕`);
+        } else if (reason === "kicked") {
+          setError(`${name} 已将你移出观看`);
+        } else if (reason === "unsupported_codec") {
+          setError(`${name} 共享使用的视频编码本机不支持，请双方升级到相同版本`);
+        }
+      }
+    );
+
+    unlistenCaptureError = await listen<{ kind: string; message: string; hint: string | null; fatal: boolean }>(
+      "capture-error",
+      (event) => {
+        const { message, hint, fatal } = event.payload;
+        setError(hint ? `屏幕捕获出错: ${hint}` : `屏幕捕获出错: ${message}`);
+        // The backend has already stopped capturing; bring the UI in line
+        if (fatal) {
+          if (isSharing()) handleStopSharing();
+          if (isSimpleSharing()) handleSimpleStopSharing();
+        }
+      }
+    );
+
+    unlistenScreenshot = await listen<ScreenshotAnnotateEvent>("screenshot-annotate", (event) => {
+      setScreenshot(event.payload);
+    });
+
     await fetchMembers();
   });
 
@@ -243,6 +376,10 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
     unlistenDiscovered?.();
     unlistenRemoved?.();
     unlistenSharingChanged?.();
+    unlistenStreamingPaused?.();
+    unlistenCaptureError?.();
+    unlistenScreenRejected?.();
+    unlistenScreenshot?.();
   });
 
   return (
@@ -258,6 +395,39 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
         />
       </Show>
 
+      {/* Clipboard History */}
+      <Show when={clipboardPeer()}>
+        {(peer) => (
+          <ClipboardHistory
+            peerIp={peer().ip}
+            peerName={peer().name}
+            onClose={() => setClipboardPeer(null)}
+          />
+        )}
+      </Show>
+
+      {/* Open a link on a peer */}
+      <Show when={urlPeer()}>
+        {(peer) => (
+          <SendUrlModal peerIp={peer().ip} peerName={peer().name} onClose={() => setUrlPeer(null)} />
+        )}
+      </Show>
+      <ReceivedUrlPrompt />
+
+      {/* Pairing codes to compare */}
+      <PairingPrompt />
+
+      {/* Screenshot Annotator */}
+      <Show when={screenshot()}>
+        {(shot) => (
+          <ScreenshotAnnotator
+            screenshot={shot()}
+            peerName={members().find(m => m.ip === shot().peer_ip)?.name ?? shot().peer_ip}
+            onClose={() => setScreenshot(null)}
+          />
+        )}
+      </Show>
+
       {/* Header */}
       <header class="bg-white border-b border-gray-200 px-4 py-3">
         <div class="flex items-center justify-between">
@@ -273,13 +443,22 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
           <div class="flex items-center gap-2">
             {/* Share Button */}
             {isSharing() ? (
-              <button
-                class="px-4 py-2 bg-red-500 hover:bg-red-600 text-white text-sm font-medium rounded-lg flex items-center gap-2"
-                onClick={handleStopSharing}
-              >
-                <span class="w-2 h-2 bg-white rounded-full animate-pulse"></span>
-                停止共享
-              </button>
+              <>
+                <button
+                  class="px-4 py-2 bg-gray-100 hover:bg-gray-200 text-gray-700 text-sm font-medium rounded-lg flex items-center gap-2"
+                  onClick={handleToggleFrozen}
+                >
+                  <span class={isSharingFrozen() ? "i-lucide-play" : "i-lucide-pause"}></span>
+                  {isSharingFrozen() ? "继续共享" : "暂停共享"}
+                </button>
+                <button
+                  class="px-4 py-2 bg-red-500 hover:bg-red-600 text-white text-sm font-medium rounded-lg flex items-center gap-2"
+                  onClick={handleStopSharing}
+                >
+                  <span class="w-2 h-2 bg-white rounded-full animate-pulse"></span>
+                  停止共享
+                </button>
+              </>
             ) : (
               <button
                 class="px-4 py-2 bg-primary-500 hover:bg-primary-600 text-white text-sm font-medium rounded-lg flex items-center gap-2"
@@ -352,7 +531,14 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
           </button>
           <button
             class="p-2 text-gray-500 hover:text-gray-700 hover:bg-gray-100 rounded-lg"
-            onClick={fetchMembers}
+            onClick={handleTileViewers}
+            title="平铺观看窗口 (F7)"
+          >
+            <span class="i-lucide-layout-grid text-lg"></span>
+          </button>
+          <button
+            class="p-2 text-gray-500 hover:text-gray-700 hover:bg-gray-100 rounded-lg"
+            onClick={handleRefresh}
             disabled={isLoadingMembers()}
             title="刷新"
           >
@@ -370,9 +556,13 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
                 <div class="flex items-center justify-between">
                   <div class="flex items-center gap-3">
                     {/* Avatar */}
-                    <div class={`w-10 h-10 rounded-full flex items-center justify-center ${member.is_self ? 'bg-primary-100' : 'bg-gray-100'}`}>
-                      <span class={`i-lucide-user text-lg ${member.is_self ? 'text-primary-600' : 'text-gray-500'}`}></span>
-                    </div>
+                    {member.avatar ? (
+                      <img class="w-10 h-10 rounded-full object-cover" src={`data:image/png;base64,${member.avatar}`} />
+                    ) : (
+                      <div class={`w-10 h-10 rounded-full flex items-center justify-center ${member.is_self ? 'bg-primary-100' : 'bg-gray-100'}`}>
+                        <span class={`i-lucide-user text-lg ${member.is_self ? 'text-primary-600' : 'text-gray-500'}`}></span>
+                      </div>
+                    )}
 
                     {/* Info */}
                     <div>
@@ -390,11 +580,38 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
 
                   {/* Actions */}
                   <div class="flex items-center gap-2">
+                    {!member.is_self && (
+                      <button
+                        class="p-1.5 text-gray-400 hover:text-gray-600 rounded-lg"
+                        title="剪贴板记录"
+                        onClick={() => setClipboardPeer(member)}
+                      >
+                        <span class="i-lucide-clipboard-list text-lg"></span>
+                      </button>
+                    )}
+                    {!member.is_self && (
+                      <button
+                        class="p-1.5 text-gray-400 hover:text-gray-600 rounded-lg"
+                        title="在对方电脑上打开链接"
+                        onClick={() => setUrlPeer(member)}
+                      >
+                        <span class="i-lucide-external-link text-lg"></span>
+                      </button>
+                    )}
+                    {!member.is_self && (
+                      <button
+                        class="p-1.5 text-gray-400 hover:text-gray-600 rounded-lg"
+                        title="截图发送"
+                        onClick={() => handleSendScreenshot(member)}
+                      >
+                        <span class="i-lucide-camera text-lg"></span>
+                      </button>
+                    )}
                     {member.is_sharing ? (
                       <>
                         <span class="flex items-center gap-1.5 px-2 py-1 bg-red-100 text-red-700 text-xs rounded-full">
                           <span class="w-1.5 h-1.5 bg-red-500 rounded-full animate-pulse"></span>
-                          正在共享
+                          {member.is_self && isSharingPaused() ? "无人观看，已暂停" : "正在共享"}
                         </span>
                         {!member.is_self && (
                           <>
@@ -404,12 +621,40 @@ export const MeetingRoom: Component<MeetingRoomProps> = (props) => {
                             >
                               观看
                             </button>
+                            <Show when={(member.displays?.length ?? 0) > 1}>
+                              <For each={member.displays}>
+                                {(display) => (
+                                  <button
+                                    class="px-2 py-1.5 border border-primary-300 hover:bg-primary-50 text-primary-700 text-sm rounded-lg"
+                                    title="在新窗口中观看此显示器"
+                                    onClick={() => handleWatchScreen(member, display.id)}
+                                  >
+                                    {display.name}
+                                  </button>
+                                )}
+                              </For>
+                            </Show>
                             <button
                               class="px-3 py-1.5 bg-orange-400 hover:bg-orange-500 text-white text-sm rounded-lg"
                               onClick={() => handleSimpleWatch(member)}
                             >
                               Simple
                             </button>
+                            <Show when={(member.displays?.length ?? 0) > 1}>
+                              <select
+                                class="px-2 py-1.5 border border-gray-300 text-gray-700 text-sm rounded-lg"
+                                title="切换显示器"
+                                onChange={(e) => handleSimpleSwitchDisplay(member, Number(e.currentTarget.value))}
+                              >
+                                <For each={member.displays}>
+                                  {(display) => (
+                                    <option value={display.id} selected={display.primary}>
+                                      {display.name} ({display.width}x{display.height})
+                                    </option>
+                                  )}
+                                </For>
+                              </select>
+                            </Show>
                             <button
                               class="px-3 py-1.5 border border-gray-300 hover:bg-gray-50 text-gray-700 text-sm rounded-lg"
                               onClick={() => handleRequestControl(member)}
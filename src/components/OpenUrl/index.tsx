@@ -0,0 +1,146 @@
+import { Component, createSignal, For, onCleanup, onMount } from "solid-js";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+interface ReceivedUrl {
+  id: string;
+  peer_ip: string;
+  from_name: string;
+  url: string;
+}
+
+interface SendUrlModalProps {
+  peerIp: string;
+  peerName: string;
+  onClose: () => void;
+}
+
+// Send a link for a peer to open in its browser
+export const SendUrlModal: Component<SendUrlModalProps> = (props) => {
+  const [url, setUrl] = createSignal("");
+  const [isSending, setIsSending] = createSignal(false);
+  const [error, setError] = createSignal<string | null>(null);
+
+  const handleSend = async () => {
+    const value = url().trim();
+    if (!/^https?:\/\/\S+$/i.test(value)) {
+      setError("请输入 http:// 或 https:// 开头的链接");
+      return;
+    }
+    setIsSending(true);
+    setError(null);
+    try {
+      await invoke("send_url", { peerIp: props.peerIp, url: value });
+      props.onClose();
+    } catch (e) {
+      setError(`发送失败: ${e}`);
+    } finally {
+      setIsSending(false);
+    }
+  };
+
+  const handleKeyDown = (e: KeyboardEvent) => {
+    if (e.key === "Enter" && !isSending()) handleSend();
+    if (e.key === "Escape") props.onClose();
+  };
+
+  return (
+    <div class="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+      <div class="bg-white rounded-2xl shadow-xl w-full max-w-sm mx-4 overflow-hidden">
+        {/* Header */}
+        <div class="px-6 py-4 border-b border-gray-200 flex items-center justify-between">
+          <h2 class="text-lg font-semibold text-gray-900">在 {props.peerName} 上打开链接</h2>
+          <button class="p-1 text-gray-400 hover:text-gray-600 rounded" onClick={props.onClose}>
+            <span class="i-lucide-x text-xl"></span>
+          </button>
+        </div>
+
+        {/* Content */}
+        <div class="p-6">
+          {error() && (
+            <div class="bg-red-50 border border-red-200 text-red-700 px-4 py-3 rounded-lg text-sm mb-4">
+              {error()}
+            </div>
+          )}
+          <input
+            type="text"
+            value={url()}
+            onInput={(e) => setUrl(e.currentTarget.value)}
+            onKeyDown={handleKeyDown}
+            class="w-full px-4 py-3 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent font-mono text-sm"
+            placeholder="https://"
+            autofocus
+          />
+          <p class="text-xs text-gray-500 mt-2">对方确认后会在默认浏览器中打开</p>
+        </div>
+
+        {/* Footer */}
+        <div class="px-6 py-4 bg-gray-50 border-t border-gray-200 flex justify-end gap-3">
+          <button
+            class="px-4 py-2 text-gray-700 hover:bg-gray-100 rounded-lg text-sm font-medium"
+            onClick={props.onClose}
+          >
+            取消
+          </button>
+          <button
+            class="px-4 py-2 bg-primary-500 hover:bg-primary-600 text-white rounded-lg text-sm font-medium disabled:opacity-50"
+            onClick={handleSend}
+            disabled={isSending() || !url().trim()}
+          >
+            发送
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
+
+// Links peers sent, each waiting for the user to open or ignore it
+export const ReceivedUrlPrompt: Component = () => {
+  const [received, setReceived] = createSignal<ReceivedUrl[]>([]);
+  let unlistenUrl: UnlistenFn | undefined;
+
+  onMount(async () => {
+    unlistenUrl = await listen<ReceivedUrl>("url-received", (event) => {
+      setReceived((prev) => [...prev, event.payload]);
+    });
+  });
+
+  onCleanup(() => unlistenUrl?.());
+
+  const answer = async (item: ReceivedUrl, open: boolean) => {
+    setReceived((prev) => prev.filter((r) => r.id !== item.id));
+    try {
+      await invoke("answer_url", { id: item.id, open });
+    } catch (e) {
+      console.error("Failed to open link:", e);
+    }
+  };
+
+  return (
+    <div class="fixed bottom-4 right-4 z-50 space-y-2 w-80">
+      <For each={received()}>
+        {(item) => (
+          <div class="bg-white border border-gray-200 rounded-xl shadow-lg p-4">
+            <p class="text-sm text-gray-700 mb-1">{item.from_name} 请求打开链接：</p>
+            <p class="text-sm font-mono text-gray-900 break-all line-clamp-3 mb-3">{item.url}</p>
+            <div class="flex justify-end gap-2">
+              <button
+                class="px-3 py-1.5 text-sm text-gray-700 hover:bg-gray-100 rounded-lg"
+                onClick={() => answer(item, false)}
+              >
+                忽略
+              </button>
+              <button
+                class="px-3 py-1.5 text-sm bg-primary-500 hover:bg-primary-600 text-white rounded-lg"
+                onClick={() => answer(item, true)}
+              >
+                打开
+              </button>
+            </div>
+          </div>
+        )}
+      </For>
+    </div>
+  );
+};
@@ -0,0 +1,77 @@
+import { Component, createSignal, For, onCleanup, onMount } from "solid-js";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+interface PairingRequest {
+  id: string;
+  peer_ip: string;
+  name: string;
+  code: string;
+  key_changed: boolean;
+}
+
+// Pairing codes to compare with the other device, one card per request
+export const PairingPrompt: Component = () => {
+  const [requests, setRequests] = createSignal<PairingRequest[]>([]);
+  let unlistenRequest: UnlistenFn | undefined;
+  let unlistenClosed: UnlistenFn | undefined;
+
+  onMount(async () => {
+    unlistenRequest = await listen<PairingRequest>("pairing-request", (event) => {
+      setRequests((prev) => [...prev, event.payload]);
+    });
+    // Answered elsewhere, timed out or the connection went away
+    unlistenClosed = await listen<string>("pairing-closed", (event) => {
+      setRequests((prev) => prev.filter((r) => r.id !== event.payload));
+    });
+  });
+
+  onCleanup(() => {
+    unlistenRequest?.();
+    unlistenClosed?.();
+  });
+
+  const answer = async (request: PairingRequest, accept: boolean) => {
+    setRequests((prev) => prev.filter((r) => r.id !== request.id));
+    try {
+      await invoke("answer_pairing", { id: request.id, accept });
+    } catch (e) {
+      console.error("Failed to answer pairing:", e);
+    }
+  };
+
+  return (
+    <div class="fixed top-4 right-4 z-50 space-y-2 w-80">
+      <For each={requests()}>
+        {(request) => (
+          <div class="bg-white border border-gray-200 rounded-xl shadow-lg p-4">
+            <p class="text-sm text-gray-700 mb-1">
+              与 {request.name}（{request.peer_ip}）配对
+            </p>
+            {request.key_changed && (
+              <p class="text-xs text-yellow-700 bg-yellow-50 rounded px-2 py-1 mb-2">
+                该设备的证书与上次配对时不同，请确认对方身份
+              </p>
+            )}
+            <p class="text-3xl font-mono tracking-widest text-center text-gray-900 my-3">{request.code}</p>
+            <p class="text-xs text-gray-500 mb-3">请确认对方屏幕上显示的是同一个配对码</p>
+            <div class="flex justify-end gap-2">
+              <button
+                class="px-3 py-1.5 text-sm text-gray-700 hover:bg-gray-100 rounded-lg"
+                onClick={() => answer(request, false)}
+              >
+                不一致
+              </button>
+              <button
+                class="px-3 py-1.5 text-sm bg-primary-500 hover:bg-primary-600 text-white rounded-lg"
+                onClick={() => answer(request, true)}
+              >
+                一致，配对
+              </button>
+            </div>
+          </div>
+        )}
+      </For>
+    </div>
+  );
+};
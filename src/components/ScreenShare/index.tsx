@@ -1,5 +1,41 @@
-import { Component, createSignal, For, Show, onMount } from "solid-js";
+import { Component, createSignal, For, Show, onMount, onCleanup } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
+
+interface ControlRequest {
+  peer_ip: string;
+  from_user: string;
+  trusted: boolean;
+}
+
+type TrustScope = "share" | "app" | "permanent";
+
+interface ViewerRequest {
+  id: string;
+  peer_ip: string;
+  name: string;
+}
+
+interface RecordingChanged {
+  // Set for a screen we watch; null for the one we share
+  peer_ip: string | null;
+  recording: boolean;
+  path: string;
+  seconds: number | null;
+}
+
+interface ViewerRecording {
+  peer_ip: string;
+  recording: boolean;
+}
+
+interface Viewer {
+  peer_ip: string;
+  device_id: string | null;
+  name: string;
+  since: number;
+  streams: number;
+}
 
 interface DisplayInfo {
   id: number;
@@ -20,6 +56,24 @@ export const ScreenShare: Component = () => {
   const [fps, setFps] = createSignal(60);
   const [quality, setQuality] = createSignal("auto");
   const [allowRemoteControl, setAllowRemoteControl] = createSignal(true);
+  const [controlRequest, setControlRequest] = createSignal<ControlRequest | null>(null);
+  const [viewerRequests, setViewerRequests] = createSignal<ViewerRequest[]>([]);
+  const [viewers, setViewers] = createSignal<Viewer[]>([]);
+  const [recordingPath, setRecordingPath] = createSignal<string | null>(null);
+  // Viewers recording the screen we share
+  const [recordingViewers, setRecordingViewers] = createSignal<string[]>([]);
+
+  // Answer a pending control request; null rejects it
+  const answerControlRequest = async (grant: boolean, trust: TrustScope | null = null) => {
+    const request = controlRequest();
+    setControlRequest(null);
+    if (!request || !grant) return;
+    try {
+      await invoke("grant_control", { peerId: request.peer_ip, trust });
+    } catch (e) {
+      console.error("Failed to grant control:", e);
+    }
+  };
 
   // Check and fetch displays
   const fetchDisplays = async () => {
@@ -82,12 +136,28 @@ export const ScreenShare: Component = () => {
     }
   };
 
+  // Switch the running share to another display (viewers stay connected)
+  const handleSwitchDisplay = async (displayId: number) => {
+    if (displayId === selectedDisplay()) return;
+    try {
+      setError(null);
+      await invoke("switch_display", { displayId });
+      setSelectedDisplay(displayId);
+      console.log("Switched sharing to display:", displayId);
+    } catch (e) {
+      console.error("Failed to switch display:", e);
+      setError(`切换显示器失败: ${e}`);
+    }
+  };
+
   // Stop screen sharing
   const handleStopSharing = async () => {
     try {
       // Use broadcast_sharing_status which handles capture stop internally
       await invoke("broadcast_sharing_status", { isSharing: false, displayId: null });
+      await invoke("revoke_control");
       setIsSharing(false);
+      setRecordingViewers([]);
       console.log("Stopped sharing");
     } catch (e) {
       console.error("Failed to stop sharing:", e);
@@ -95,12 +165,166 @@ export const ScreenShare: Component = () => {
     }
   };
 
-  onMount(() => {
+  // Remove the shapes drawn over the shared screen, for every viewer
+  const handleClearAnnotations = async () => {
+    try {
+      await invoke("annotate_shared_screen", { annotation: { kind: "clear" } });
+    } catch (e) {
+      console.error("Failed to clear annotations:", e);
+      setError(`清除标注失败: ${e}`);
+    }
+  };
+
+  // Record the shared screen to MP4, or stop recording
+  const handleToggleRecording = async () => {
+    try {
+      if (recordingPath()) {
+        await invoke("stop_recording");
+      } else {
+        await invoke<string>("start_recording", { dir: null });
+      }
+    } catch (e) {
+      console.error("Failed to start/stop recording:", e);
+      setError(`录制失败: ${e}`);
+    }
+  };
+
+  // A viewer asks to type into this machine
+  let unlistenControlRequested: UnlistenFn | undefined;
+  let unlistenRecording: UnlistenFn | undefined;
+  let unlistenViewerRecording: UnlistenFn | undefined;
+  let unlistenViewerRequested: UnlistenFn | undefined;
+  let unlistenViewerRequestClosed: UnlistenFn | undefined;
+  let unlistenViewerJoined: UnlistenFn | undefined;
+  let unlistenViewerLeft: UnlistenFn | undefined;
+
+  const answerViewerRequest = async (request: ViewerRequest, allow: boolean) => {
+    setViewerRequests((prev) => prev.filter((r) => r.id !== request.id));
+    try {
+      await invoke("answer_viewer_request", { id: request.id, allow });
+    } catch (e) {
+      console.error("Failed to answer viewer request:", e);
+    }
+  };
+
+  const kickViewer = async (viewer: Viewer) => {
+    if (!confirm(`将 ${viewer.name} 移出？对方需重新获得允许才能再次观看`)) return;
+    try {
+      await invoke("kick_viewer", { peerIp: viewer.peer_ip });
+    } catch (e) {
+      console.error("Failed to kick viewer:", e);
+    }
+  };
+
+  onMount(async () => {
     fetchDisplays();
+
+    setViewers(await invoke<Viewer[]>("get_current_viewers"));
+    unlistenViewerJoined = await listen<Viewer>("viewer-joined", (event) => {
+      setViewers((prev) => [...prev.filter((v) => v.peer_ip !== event.payload.peer_ip), event.payload]);
+    });
+    unlistenViewerLeft = await listen<string>("viewer-left", (event) => {
+      setViewers((prev) => prev.filter((v) => v.peer_ip !== event.payload));
+    });
+
+    setRecordingPath(await invoke<string | null>("get_recording"));
+    unlistenRecording = await listen<RecordingChanged>("recording-changed", (event) => {
+      if (event.payload.peer_ip !== null) return;
+      setRecordingPath(event.payload.recording ? event.payload.path : null);
+    });
+    unlistenViewerRecording = await listen<ViewerRecording>("viewer-recording", (event) => {
+      const { peer_ip, recording } = event.payload;
+      setRecordingViewers((ips) => [...ips.filter((ip) => ip !== peer_ip), ...(recording ? [peer_ip] : [])]);
+    });
+
+    unlistenControlRequested = await listen<ControlRequest>("control-requested", async (event) => {
+      if (!isSharing() || !allowRemoteControl()) return;
+      setControlRequest(event.payload);
+      // Trusted devices are let in without asking
+      if (event.payload.trusted) await answerControlRequest(true);
+    });
+
+    unlistenViewerRequested = await listen<ViewerRequest>("viewer-requested", (event) => {
+      setViewerRequests((prev) => [...prev, event.payload]);
+    });
+    // Timed out unanswered
+    unlistenViewerRequestClosed = await listen<string>("viewer-request-closed", (event) => {
+      setViewerRequests((prev) => prev.filter((r) => r.id !== event.payload));
+    });
+  });
+
+  onCleanup(() => {
+    unlistenControlRequested?.();
+    unlistenViewerRequested?.();
+    unlistenViewerRequestClosed?.();
+    unlistenViewerJoined?.();
+    unlistenViewerLeft?.();
+    unlistenRecording?.();
+    unlistenViewerRecording?.();
   });
 
   return (
     <div class="max-w-4xl mx-auto space-y-6">
+      {/* Viewer Requests */}
+      <div class="fixed top-4 left-1/2 -translate-x-1/2 z-50 space-y-2 w-80">
+        <For each={viewerRequests()}>
+          {(request) => (
+            <div class="bg-white border border-gray-200 rounded-xl shadow-lg p-4">
+              <p class="text-sm text-gray-700 mb-3">
+                {request.name}（{request.peer_ip}）想观看你共享的屏幕
+              </p>
+              <div class="flex justify-end gap-2">
+                <button
+                  class="px-3 py-1.5 text-sm text-gray-700 hover:bg-gray-100 rounded-lg"
+                  onClick={() => answerViewerRequest(request, false)}
+                >
+                  拒绝
+                </button>
+                <button
+                  class="px-3 py-1.5 text-sm bg-primary-500 hover:bg-primary-600 text-white rounded-lg"
+                  onClick={() => answerViewerRequest(request, true)}
+                >
+                  允许
+                </button>
+              </div>
+            </div>
+          )}
+        </For>
+      </div>
+
+      {/* Control Request */}
+      <Show when={controlRequest()}>
+        {(request) => (
+          <div class="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+            <div class="bg-white rounded-2xl shadow-xl w-full max-w-sm mx-4 overflow-hidden">
+              <div class="px-6 py-4 border-b border-gray-200">
+                <h2 class="text-lg font-semibold text-gray-900">远程控制请求</h2>
+              </div>
+              <div class="p-6 space-y-2">
+                <p class="text-sm text-gray-700 mb-4">
+                  {request().from_user}（{request().peer_ip}）请求控制你的屏幕（输入文字），是否允许？
+                </p>
+                <button class="btn-primary w-full" onClick={() => answerControlRequest(true)}>
+                  仅允许本次
+                </button>
+                <button class="btn-secondary w-full" onClick={() => answerControlRequest(true, "share")}>
+                  本次共享内信任
+                </button>
+                <button class="btn-secondary w-full" onClick={() => answerControlRequest(true, "app")}>
+                  信任至应用重启
+                </button>
+                <button class="btn-secondary w-full" onClick={() => answerControlRequest(true, "permanent")}>
+                  始终信任
+                </button>
+                <button class="btn-secondary w-full" onClick={() => answerControlRequest(false)}>
+                  拒绝
+                </button>
+              </div>
+            </div>
+          </div>
+        )}
+      </Show>
+
       {/* Error Display */}
       {error() && (
         <div class="bg-red-50 border border-red-200 text-red-700 px-4 py-3 rounded-lg flex items-center justify-between">
@@ -140,19 +364,55 @@ export const ScreenShare: Component = () => {
             <div class="flex items-center gap-3">
               <span class="w-3 h-3 bg-red-500 rounded-full animate-pulse"></span>
               <div>
-                <span class="font-medium text-red-700">正在共享屏幕</span>
+                <span class="font-medium text-red-700">
+                  正在共享屏幕
+                  <Show when={recordingPath()}>
+                    <span class="ml-2 px-1.5 py-0.5 bg-red-600 text-white text-xs rounded">● 录制中</span>
+                  </Show>
+                </span>
                 <p class="text-sm text-red-600">
                   {displays().find((d) => d.id === selectedDisplay())?.name}
                 </p>
+                <Show when={recordingViewers().length > 0}>
+                  <p class="text-xs text-red-600">● {recordingViewers().join(", ")} 正在录制你的屏幕</p>
+                </Show>
               </div>
             </div>
-            <button
-              class="btn bg-red-500 text-white hover:bg-red-600"
-              onClick={handleStopSharing}
-            >
-              <span class="i-lucide-square mr-2"></span>
-              停止共享
-            </button>
+            <div class="flex items-center gap-2">
+              <button class="btn-secondary" onClick={handleToggleRecording} title={recordingPath() ?? "录制到 MP4"}>
+                <span class={`${recordingPath() ? "i-lucide-square" : "i-lucide-circle"} mr-2`}></span>
+                {recordingPath() ? "停止录制" : "录制"}
+              </button>
+              <button class="btn-secondary" onClick={handleClearAnnotations}>
+                <span class="i-lucide-eraser mr-2"></span>
+                清除标注
+              </button>
+              <button
+                class="btn bg-red-500 text-white hover:bg-red-600"
+                onClick={handleStopSharing}
+              >
+                <span class="i-lucide-square mr-2"></span>
+                停止共享
+              </button>
+            </div>
+          </div>
+          <div class="mt-3 pt-3 border-t border-red-200">
+            <p class="text-sm text-red-700 mb-1">
+              {viewers().length > 0 ? `${viewers().length} 人正在观看你的屏幕` : "暂无观看者"}
+            </p>
+            <For each={viewers()}>
+              {(viewer) => (
+                <div class="flex items-center justify-between text-sm text-red-700 py-1">
+                  <span>
+                    {viewer.name}
+                    <span class="text-xs text-red-500 ml-2">{viewer.peer_ip}</span>
+                  </span>
+                  <button class="text-xs text-red-600 hover:text-red-800" onClick={() => kickViewer(viewer)}>
+                    移出
+                  </button>
+                </div>
+              )}
+            </For>
           </div>
         </div>
       </Show>
@@ -190,8 +450,9 @@ export const ScreenShare: Component = () => {
                       ? "border-primary-500 bg-primary-50"
                       : "border-gray-200 hover:border-gray-300"
                   }`}
-                  onClick={() => setSelectedDisplay(display.id)}
-                  disabled={isSharing()}
+                  onClick={() =>
+                    isSharing() ? handleSwitchDisplay(display.id) : setSelectedDisplay(display.id)
+                  }
                 >
                   <div class="aspect-video bg-gray-200 rounded-lg mb-3 flex items-center justify-center relative overflow-hidden">
                     <span class="i-lucide-monitor text-4xl text-gray-400"></span>
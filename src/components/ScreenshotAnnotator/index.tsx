@@ -0,0 +1,170 @@
+import { Component, createSignal, For } from "solid-js";
+import { invoke } from "@tauri-apps/api/core";
+
+export interface ScreenshotAnnotateEvent {
+  path: string;
+  peer_ip: string;
+  image: string;
+}
+
+interface Stroke {
+  color: string;
+  width: number;
+  points: [number, number][];
+}
+
+interface ScreenshotAnnotatorProps {
+  screenshot: ScreenshotAnnotateEvent;
+  peerName: string;
+  onClose: () => void;
+}
+
+const COLORS = ["#ef4444", "#f59e0b", "#22c55e", "#3b82f6", "#111827"];
+const PEN_WIDTH = 6;
+
+export const ScreenshotAnnotator: Component<ScreenshotAnnotatorProps> = (props) => {
+  const [strokes, setStrokes] = createSignal<Stroke[]>([]);
+  const [color, setColor] = createSignal(COLORS[0]);
+  const [isSending, setIsSending] = createSignal(false);
+  const [error, setError] = createSignal<string | null>(null);
+  let canvas: HTMLCanvasElement | undefined;
+  let current: Stroke | null = null;
+
+  // Strokes are kept in image pixels, whatever size the preview is shown at
+  const toImage = (e: PointerEvent): [number, number] => {
+    const rect = canvas!.getBoundingClientRect();
+    return [
+      ((e.clientX - rect.left) / rect.width) * canvas!.width,
+      ((e.clientY - rect.top) / rect.height) * canvas!.height,
+    ];
+  };
+
+  const redraw = () => {
+    const ctx = canvas?.getContext("2d");
+    if (!ctx) return;
+    ctx.clearRect(0, 0, canvas!.width, canvas!.height);
+    ctx.lineCap = "round";
+    ctx.lineJoin = "round";
+    for (const stroke of current ? [...strokes(), current] : strokes()) {
+      ctx.strokeStyle = stroke.color;
+      ctx.lineWidth = stroke.width;
+      ctx.beginPath();
+      stroke.points.forEach(([x, y], i) => (i === 0 ? ctx.moveTo(x, y) : ctx.lineTo(x, y)));
+      if (stroke.points.length === 1) ctx.lineTo(stroke.points[0][0], stroke.points[0][1]);
+      ctx.stroke();
+    }
+  };
+
+  const onImageLoad = (e: Event) => {
+    const img = e.currentTarget as HTMLImageElement;
+    canvas!.width = img.naturalWidth;
+    canvas!.height = img.naturalHeight;
+    redraw();
+  };
+
+  const onPointerDown = (e: PointerEvent) => {
+    canvas!.setPointerCapture(e.pointerId);
+    // Scale the pen with the screenshot so it looks the same on any display
+    const scale = canvas!.width / canvas!.getBoundingClientRect().width;
+    current = { color: color(), width: PEN_WIDTH * scale, points: [toImage(e)] };
+    redraw();
+  };
+
+  const onPointerMove = (e: PointerEvent) => {
+    if (!current) return;
+    current.points.push(toImage(e));
+    redraw();
+  };
+
+  const onPointerUp = () => {
+    if (!current) return;
+    setStrokes((prev) => [...prev, current!]);
+    current = null;
+    redraw();
+  };
+
+  const undo = () => {
+    setStrokes((prev) => prev.slice(0, -1));
+    redraw();
+  };
+
+  const send = async () => {
+    setIsSending(true);
+    setError(null);
+    try {
+      await invoke("send_annotated_screenshot", {
+        path: props.screenshot.path,
+        peerIp: props.screenshot.peer_ip,
+        strokes: strokes(),
+      });
+      props.onClose();
+    } catch (e) {
+      console.error("Failed to send screenshot:", e);
+      setError(`发送失败: ${e}`);
+    } finally {
+      setIsSending(false);
+    }
+  };
+
+  return (
+    <div class="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+      <div class="bg-white rounded-2xl shadow-xl w-full max-w-4xl mx-4 overflow-hidden flex flex-col max-h-[90vh]">
+        {/* Header */}
+        <div class="px-6 py-4 border-b border-gray-200 flex items-center justify-between">
+          <h2 class="text-lg font-semibold text-gray-900">发送截图给 {props.peerName}</h2>
+          <button class="p-1 text-gray-400 hover:text-gray-600 rounded" onClick={props.onClose}>
+            <span class="i-lucide-x text-xl"></span>
+          </button>
+        </div>
+
+        {/* Screenshot with pen layer */}
+        <div class="flex-1 overflow-auto p-4 bg-gray-100 flex items-center justify-center">
+          <div class="relative inline-block">
+            <img class="max-h-[60vh] block select-none" src={props.screenshot.image} onLoad={onImageLoad} draggable={false} />
+            <canvas
+              ref={canvas}
+              class="absolute inset-0 w-full h-full cursor-crosshair touch-none"
+              onPointerDown={onPointerDown}
+              onPointerMove={onPointerMove}
+              onPointerUp={onPointerUp}
+              onPointerCancel={onPointerUp}
+            />
+          </div>
+        </div>
+
+        {/* Footer */}
+        <div class="px-6 py-3 border-t border-gray-200 flex items-center justify-between">
+          <div class="flex items-center gap-2">
+            <For each={COLORS}>
+              {(c) => (
+                <button
+                  class={`w-6 h-6 rounded-full border-2 ${color() === c ? "border-gray-900" : "border-transparent"}`}
+                  style={{ "background-color": c }}
+                  title="画笔颜色"
+                  onClick={() => setColor(c)}
+                />
+              )}
+            </For>
+            <button
+              class="ml-2 px-3 py-1.5 text-sm border border-gray-300 rounded-lg hover:bg-gray-50 disabled:opacity-50"
+              disabled={strokes().length === 0}
+              onClick={undo}
+            >
+              撤销
+            </button>
+          </div>
+          <div class="flex items-center gap-3">
+            {error() && <span class="text-sm text-red-600">{error()}</span>}
+            <button
+              class="px-4 py-2 bg-primary-500 hover:bg-primary-600 text-white text-sm font-medium rounded-lg disabled:opacity-50"
+              disabled={isSending()}
+              onClick={send}
+            >
+              {isSending() ? "发送中..." : "发送"}
+            </button>
+          </div>
+        </div>
+      </div>
+    </div>
+  );
+};
@@ -1,5 +1,7 @@
-import { Component, createSignal, onMount } from "solid-js";
+import { Component, For, createSignal, onMount } from "solid-js";
 import { invoke } from "@tauri-apps/api/core";
+import { open } from "@tauri-apps/plugin-dialog";
+import { appStore } from "../../stores/app";
 
 interface SettingsProps {
   onClose: () => void;
@@ -11,8 +13,77 @@ interface AppSettings {
   fps: number;
   default_resolution: number;
   default_bitrate: number;
+  redact_notifications: boolean;
+  show_cursor: boolean;
+  cursor_channel: boolean;
+  click_ripple: boolean;
+  safe_mode: boolean;
+  discovery_interval_secs: number;
+  discovery_passive: boolean;
+  uplink_cap_mbps: number;
+  max_viewers: number;
+  viewer_policy: string;
+  meeting_password: string;
+  archive_enabled: boolean;
+  archive_interval_secs: number;
+  archive_max_snapshots: number;
+  archive_max_age_days: number;
+  archive_ocr: boolean;
+  watch_folder: string;
+  watch_folder_device: string;
+  clipboard_sync: boolean;
+  clipboard_images: boolean;
+  clipboard_files: boolean;
+  clipboard_send: boolean;
+  clipboard_receive: boolean;
+  latency_hud: boolean;
+  video_codec: "h264" | "av1";
+  color_space: "bt601" | "bt601-full" | "bt709" | "bt709-full";
+  color_filter: string;
+  fit_mode: "" | "fill" | "original" | "stretch";
+  forced_encoder: string;
+  validate_bitstream: boolean;
+  gpu_preprocess: boolean;
+  encoder_preset: "ultrafast" | "fast" | "medium" | "quality";
+  keyframe_interval_secs: number;
+  max_bitrate_mbps: number;
+  cursor_roi: boolean;
+  decoder_preference: string[];
+  avatar: string;
+  favorite_devices: string[];
+  trusted_devices: string[];
 }
 
+interface TrustedDevice {
+  device_id: string;
+  peer_ip: string;
+  scope: "share" | "app" | "permanent";
+}
+
+interface KnownDevice {
+  id: string;
+  name: string;
+  last_ip: string;
+  nickname: string | null;
+  blocked: boolean;
+}
+
+const TRUST_SCOPE_LABELS: Record<TrustedDevice["scope"], string> = {
+  share: "本次共享",
+  app: "至应用重启",
+  permanent: "始终",
+};
+
+interface EncoderInfo {
+  name: string;
+  codec: "h264" | "av1";
+  hardware: boolean;
+  available: boolean;
+}
+
+// Same shape as encoders; `name` is what `decoder_preference` holds
+type DecoderInfo = EncoderInfo;
+
 export const Settings: Component<SettingsProps> = (props) => {
   const [settings, setSettings] = createSignal<AppSettings>({
     device_name: "",
@@ -20,16 +91,71 @@ export const Settings: Component<SettingsProps> = (props) => {
     fps: 30,
     default_resolution: 1,
     default_bitrate: 1,
+    redact_notifications: false,
+    show_cursor: true,
+    cursor_channel: true,
+    click_ripple: true,
+    safe_mode: false,
+    discovery_interval_secs: 0,
+    discovery_passive: false,
+    uplink_cap_mbps: 0,
+    max_viewers: 0,
+    viewer_policy: "ask",
+    meeting_password: "",
+    archive_enabled: false,
+    archive_interval_secs: 60,
+    archive_max_snapshots: 500,
+    archive_max_age_days: 30,
+    archive_ocr: false,
+    watch_folder: "",
+    watch_folder_device: "",
+    clipboard_sync: false,
+    clipboard_images: false,
+    clipboard_files: false,
+    clipboard_send: true,
+    clipboard_receive: true,
+    latency_hud: false,
+    video_codec: "h264",
+    color_space: "bt709",
+    color_filter: "",
+    fit_mode: "",
+    forced_encoder: "",
+    validate_bitstream: false,
+    gpu_preprocess: true,
+    encoder_preset: "ultrafast",
+    keyframe_interval_secs: 1,
+    max_bitrate_mbps: 0,
+    cursor_roi: false,
+    decoder_preference: [],
+    avatar: "",
+    favorite_devices: [],
+    trusted_devices: [],
   });
+  const [ocrAvailable, setOcrAvailable] = createSignal(false);
+  const [encoders, setEncoders] = createSignal<EncoderInfo[]>([]);
+  const [decoders, setDecoders] = createSignal<DecoderInfo[]>([]);
   const [isSaving, setIsSaving] = createSignal(false);
   const [error, setError] = createSignal<string | null>(null);
   const [success, setSuccess] = createSignal(false);
+  const [trustedDevices, setTrustedDevices] = createSignal<TrustedDevice[]>([]);
+  const [blockedDevices, setBlockedDevices] = createSignal<KnownDevice[]>([]);
 
   // Load settings on mount
   onMount(async () => {
+    // Probing opens every encoder once; don't hold up the rest of the form
+    invoke<EncoderInfo[]>("list_encoders")
+      .then(setEncoders)
+      .catch((e) => console.error("Failed to list encoders:", e));
+    invoke<DecoderInfo[]>("list_decoders")
+      .then(setDecoders)
+      .catch((e) => console.error("Failed to list decoders:", e));
+
     try {
       const saved = await invoke<AppSettings>("get_settings");
       setSettings(saved);
+      setOcrAvailable(await invoke<boolean>("is_archive_ocr_available"));
+      setTrustedDevices(await invoke<TrustedDevice[]>("list_trusted_devices"));
+      setBlockedDevices((await invoke<KnownDevice[]>("get_known_devices")).filter(d => d.blocked));
     } catch (e) {
       console.error("Failed to load settings:", e);
       // Use defaults
@@ -38,6 +164,58 @@ export const Settings: Component<SettingsProps> = (props) => {
     }
   });
 
+  const pickWatchFolder = async () => {
+    const selected = await open({ directory: true, multiple: false, title: "选择监视文件夹" });
+    if (typeof selected === "string") {
+      setSettings(prev => ({ ...prev, watch_folder: selected }));
+    }
+  };
+
+  const pickAvatar = async () => {
+    const selected = await open({
+      multiple: false,
+      title: "选择头像",
+      filters: [{ name: "图片", extensions: ["png", "jpg", "jpeg", "webp", "bmp", "gif"] }],
+    });
+    if (typeof selected !== "string") return;
+    try {
+      const avatar = await invoke<string>("set_avatar", { path: selected });
+      setSettings(prev => ({ ...prev, avatar }));
+    } catch (e) {
+      console.error("Failed to set avatar:", e);
+      setError(`头像设置失败: ${e}`);
+    }
+  };
+
+  const clearAvatar = async () => {
+    try {
+      await invoke("set_avatar", { path: null });
+      setSettings(prev => ({ ...prev, avatar: "" }));
+    } catch (e) {
+      console.error("Failed to clear avatar:", e);
+    }
+  };
+
+  const untrustDevice = async (deviceId: string) => {
+    try {
+      await invoke("untrust_device", { deviceId });
+      setTrustedDevices(prev => prev.filter(d => d.device_id !== deviceId));
+      // Keep a later save from trusting it again
+      setSettings(prev => ({ ...prev, trusted_devices: prev.trusted_devices.filter(id => id !== deviceId) }));
+    } catch (e) {
+      console.error("Failed to untrust device:", e);
+    }
+  };
+
+  const unblockDevice = async (deviceId: string) => {
+    try {
+      await invoke("unblock_device", { deviceId });
+      setBlockedDevices(prev => prev.filter(d => d.id !== deviceId));
+    } catch (e) {
+      console.error("Failed to unblock device:", e);
+    }
+  };
+
   // Save settings
   const handleSave = async () => {
     setIsSaving(true);
@@ -102,6 +280,53 @@ export const Settings: Component<SettingsProps> = (props) => {
             <p class="text-xs text-gray-500 mt-1">其他人将看到此名称</p>
           </div>
 
+          {/* Avatar */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              头像
+            </label>
+            <div class="flex items-center gap-3">
+              {settings().avatar ? (
+                <img
+                  class="w-12 h-12 rounded-full object-cover"
+                  src={`data:image/png;base64,${settings().avatar}`}
+                />
+              ) : (
+                <div class="w-12 h-12 rounded-full bg-gray-100 flex items-center justify-center">
+                  <span class="i-lucide-user text-xl text-gray-400"></span>
+                </div>
+              )}
+              <button
+                class="px-3 py-1.5 text-sm border border-gray-300 rounded-lg hover:bg-gray-50"
+                onClick={pickAvatar}
+              >
+                选择图片
+              </button>
+              {settings().avatar && (
+                <button
+                  class="px-3 py-1.5 text-sm text-gray-600 hover:text-gray-800"
+                  onClick={clearAvatar}
+                >
+                  移除
+                </button>
+              )}
+            </div>
+            <p class="text-xs text-gray-500 mt-1">图片会裁成正方形并缩小后发送给其他设备</p>
+          </div>
+
+          {/* Certificate fingerprint */}
+          {appStore.selfInfo()?.fingerprint && (
+            <div>
+              <label class="block text-sm font-medium text-gray-700 mb-2">
+                本机证书指纹
+              </label>
+              <p class="font-mono text-xs text-gray-600 break-all select-all">
+                {appStore.selfInfo()!.fingerprint!.match(/.{1,4}/g)!.join(" ")}
+              </p>
+              <p class="text-xs text-gray-500 mt-1">配对过的设备凭此识别本机，重启后保持不变</p>
+            </div>
+          )}
+
           {/* Quality */}
           <div>
             <label class="block text-sm font-medium text-gray-700 mb-2">
@@ -136,6 +361,184 @@ export const Settings: Component<SettingsProps> = (props) => {
             <p class="text-xs text-gray-500 mt-1">更高的帧率需要更多带宽</p>
           </div>
 
+          {/* Video Codec */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              视频编码
+            </label>
+            <select
+              value={settings().video_codec}
+              onChange={(e) => setSettings(prev => ({ ...prev, video_codec: e.currentTarget.value as AppSettings["video_codec"] }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="h264">H.264 (兼容性最好)</option>
+              <option value="av1">AV1 (低码率下更清晰)</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">AV1 在 2–4 Mbps 下文字更清晰，但编码更耗 CPU；不支持时自动使用 H.264</p>
+          </div>
+
+          {/* Color Space */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              色彩空间
+            </label>
+            <select
+              value={settings().color_space}
+              onChange={(e) => setSettings(prev => ({ ...prev, color_space: e.currentTarget.value as AppSettings["color_space"] }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="bt709">BT.709 (推荐)</option>
+              <option value="bt709-full">BT.709 全范围</option>
+              <option value="bt601">BT.601 (兼容旧版本)</option>
+              <option value="bt601-full">BT.601 全范围</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">全范围色彩更饱满，部分硬件解码器可能不支持；下次共享时生效</p>
+          </div>
+
+          {/* Forced Encoder */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              编码器
+            </label>
+            <select
+              value={settings().forced_encoder}
+              onChange={(e) => setSettings(prev => ({ ...prev, forced_encoder: e.currentTarget.value }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="">自动选择</option>
+              <For each={encoders().filter((enc) => enc.codec === settings().video_codec)}>
+                {(enc) => (
+                  <option value={enc.name} disabled={!enc.available}>
+                    {enc.name}{enc.hardware ? " (硬件)" : " (软件)"}{enc.available ? "" : " - 不可用"}
+                  </option>
+                )}
+              </For>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">用于排查画面或性能问题；所选编码器无法使用时自动选择其他编码器</p>
+          </div>
+
+          {/* Encoder Preset */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              编码预设
+            </label>
+            <select
+              value={settings().encoder_preset}
+              onChange={(e) => setSettings(prev => ({ ...prev, encoder_preset: e.currentTarget.value as AppSettings["encoder_preset"] }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="ultrafast">延迟优先 (推荐)</option>
+              <option value="fast">均衡</option>
+              <option value="medium">偏重画质</option>
+              <option value="quality">画质优先 (更耗 CPU)</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">共享中修改会立即生效，观看者画面会短暂重新加载</p>
+          </div>
+
+          {/* Keyframe Interval */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              关键帧间隔
+            </label>
+            <select
+              value={settings().keyframe_interval_secs}
+              onChange={(e) => setSettings(prev => ({ ...prev, keyframe_interval_secs: parseInt(e.currentTarget.value) }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="1">1 秒 (推荐)</option>
+              <option value="2">2 秒</option>
+              <option value="5">5 秒</option>
+              <option value="10">10 秒 (省流量)</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">间隔越短，新观看者加入和丢包后恢复越快，但占用更多带宽</p>
+          </div>
+
+          {/* Max Bitrate */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              最大码率
+            </label>
+            <select
+              value={settings().max_bitrate_mbps}
+              onChange={(e) => setSettings(prev => ({ ...prev, max_bitrate_mbps: parseInt(e.currentTarget.value) }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="0">自动 (目标码率的 2 倍)</option>
+              <option value="4">4 Mbps</option>
+              <option value="8">8 Mbps</option>
+              <option value="16">16 Mbps</option>
+              <option value="32">32 Mbps</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">限制画面剧烈变化时的码率峰值，网络较差时可调低</p>
+          </div>
+
+          {/* Cursor ROI */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().cursor_roi}
+                onChange={(e) => setSettings(prev => ({ ...prev, cursor_roi: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              光标附近画质增强
+            </label>
+            <p class="text-xs text-gray-500 mt-1">低码率时优先保证鼠标指向区域清晰；需要 NVENC 或 x264 编码器，其他编码器忽略此设置</p>
+          </div>
+
+          {/* Preferred Decoder */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              首选解码器
+            </label>
+            <select
+              value={settings().decoder_preference[0] ?? ""}
+              onChange={(e) => {
+                const name = e.currentTarget.value;
+                setSettings(prev => ({ ...prev, decoder_preference: name ? [name] : [] }));
+              }}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="">自动选择 (硬件优先)</option>
+              <For each={decoders().filter((dec) => dec.codec === "h264")}>
+                {(dec) => (
+                  <option value={dec.name} disabled={!dec.available}>
+                    {dec.name}{dec.hardware ? " (硬件)" : " (软件)"}{dec.available ? "" : " - 不可用"}
+                  </option>
+                )}
+              </For>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">对下次打开的画面生效；硬件解码连续出错时会自动切换到软件解码</p>
+          </div>
+
+          {/* GPU Preprocessing */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().gpu_preprocess}
+                onChange={(e) => setSettings(prev => ({ ...prev, gpu_preprocess: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              使用 GPU 缩放画面
+            </label>
+            <p class="text-xs text-gray-500 mt-1">高分辨率屏幕在显卡上缩小并转换颜色格式，降低共享时的 CPU 占用；显卡不可用时自动改用 CPU</p>
+          </div>
+
+          {/* Bitstream Validation */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().validate_bitstream}
+                onChange={(e) => setSettings(prev => ({ ...prev, validate_bitstream: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              校验编码输出
+            </label>
+            <p class="text-xs text-gray-500 mt-1">发送前检查每帧 H.264 数据的结构，并在日志中记录编码器输出的错误，用于排查对方花屏或无法解码的问题</p>
+          </div>
+
           {/* Default Resolution */}
           <div>
             <label class="block text-sm font-medium text-gray-700 mb-2">
@@ -168,9 +571,393 @@ export const Settings: Component<SettingsProps> = (props) => {
               <option value="1">4 Mbps</option>
               <option value="2">8 Mbps</option>
               <option value="3">12 Mbps</option>
+              <option value="4">文字清晰 (近无损，需高速局域网)</option>
             </select>
             <p class="text-xs text-gray-500 mt-1">观看他人屏幕时的初始码率</p>
           </div>
+
+          {/* Discovery */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              设备发现间隔
+            </label>
+            <select
+              value={settings().discovery_interval_secs}
+              onChange={(e) => setSettings(prev => ({ ...prev, discovery_interval_secs: parseInt(e.currentTarget.value) }))}
+              disabled={settings().discovery_passive}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent disabled:opacity-50"
+            >
+              <option value="0">自动</option>
+              <option value="30">30 秒</option>
+              <option value="60">1 分钟</option>
+              <option value="300">5 分钟</option>
+            </select>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().discovery_passive}
+                onChange={(e) => setSettings(prev => ({ ...prev, discovery_passive: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              被动模式
+            </label>
+            <p class="text-xs text-gray-500 mt-1">被动模式下不主动查询，仅在点击刷新时搜索设备，适合大型网络</p>
+          </div>
+
+          {/* Uplink Cap */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              上行带宽上限
+            </label>
+            <select
+              value={settings().uplink_cap_mbps}
+              onChange={(e) => setSettings(prev => ({ ...prev, uplink_cap_mbps: parseInt(e.currentTarget.value) }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="0">不限制</option>
+              <option value="10">10 Mbps</option>
+              <option value="20">20 Mbps</option>
+              <option value="50">50 Mbps</option>
+              <option value="100">100 Mbps</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">多人观看时按此上限分配带宽，网速慢的观看者优先降为低画质</p>
+          </div>
+
+          {/* Max Viewers */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              最多观看人数
+            </label>
+            <select
+              value={settings().max_viewers}
+              onChange={(e) => setSettings(prev => ({ ...prev, max_viewers: parseInt(e.currentTarget.value) }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="0">不限制</option>
+              <option value="2">2 人</option>
+              <option value="3">3 人</option>
+              <option value="5">5 人</option>
+              <option value="10">10 人</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">达到上限后，新的观看请求会被拒绝，避免占满上行带宽</p>
+          </div>
+
+          {/* Viewer Policy */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              观看与控制权限
+            </label>
+            <select
+              value={settings().viewer_policy || "ask"}
+              onChange={(e) => setSettings(prev => ({ ...prev, viewer_policy: e.currentTarget.value }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="ask">信任的设备直接加入，其他设备需确认</option>
+              <option value="trusted_only">仅信任的设备</option>
+              <option value="allow_all">所有设备</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">决定谁可以观看你共享的屏幕或请求远程控制</p>
+          </div>
+
+          {/* Meeting Password */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              会议密码
+            </label>
+            <input
+              type="password"
+              value={settings().meeting_password}
+              onInput={(e) => setSettings(prev => ({ ...prev, meeting_password: e.currentTarget.value }))}
+              placeholder="不设置"
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            />
+            <p class="text-xs text-gray-500 mt-1">设置后，其他设备需输入此密码才能连接本机；密码不会以明文发送</p>
+          </div>
+
+          {/* Notification Redaction */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().redact_notifications}
+                onChange={(e) => setSettings(prev => ({ ...prev, redact_notifications: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              共享时遮挡通知弹窗
+            </label>
+            <p class="text-xs text-gray-500 mt-1">对系统通知横幅区域打码，避免泄露私人消息</p>
+          </div>
+
+          {/* Cursor */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().show_cursor}
+                onChange={(e) => setSettings(prev => ({ ...prev, show_cursor: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              共享时显示鼠标指针
+            </label>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2 ml-6">
+              <input
+                type="checkbox"
+                checked={settings().cursor_channel}
+                disabled={!settings().show_cursor}
+                onChange={(e) => setSettings(prev => ({ ...prev, cursor_channel: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              单独发送指针位置
+            </label>
+            <p class="text-xs text-gray-500 mt-1 ml-6">由观看端绘制指针，不受视频压缩影响，移动更流畅</p>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().click_ripple}
+                onChange={(e) => setSettings(prev => ({ ...prev, click_ripple: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              观看时显示对方的点击波纹
+            </label>
+          </div>
+
+          {/* Archive */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().archive_enabled}
+                onChange={(e) => setSettings(prev => ({ ...prev, archive_enabled: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              共享时定期保存截图
+            </label>
+            <select
+              value={settings().archive_interval_secs}
+              onChange={(e) => setSettings(prev => ({ ...prev, archive_interval_secs: parseInt(e.currentTarget.value) }))}
+              disabled={!settings().archive_enabled}
+              class="w-full mt-2 px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent disabled:bg-gray-100"
+            >
+              <option value="10">每 10 秒</option>
+              <option value="30">每 30 秒</option>
+              <option value="60">每分钟</option>
+              <option value="300">每 5 分钟</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">
+              截图保存在本机，最多保留 {settings().archive_max_snapshots} 张、{settings().archive_max_age_days} 天，超出后自动删除最早的截图
+            </p>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().archive_ocr}
+                onChange={(e) => setSettings(prev => ({ ...prev, archive_ocr: e.currentTarget.checked }))}
+                disabled={!settings().archive_enabled || !ocrAvailable()}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              识别截图文字以便搜索
+            </label>
+            <p class="text-xs text-gray-500 mt-1">
+              {ocrAvailable() ? "在本机用 OCR 识别截图中的文字，可按关键字搜索历史会话" : "当前版本未包含 OCR 支持"}
+            </p>
+          </div>
+
+          {/* Watch Folder */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              自动发送文件夹
+            </label>
+            <div class="flex gap-2">
+              <input
+                type="text"
+                value={settings().watch_folder}
+                readOnly
+                placeholder="未设置"
+                class="flex-1 px-4 py-2 border border-gray-300 rounded-lg bg-gray-50 text-sm"
+              />
+              <button
+                onClick={pickWatchFolder}
+                class="px-3 py-2 text-sm border border-gray-300 rounded-lg hover:bg-gray-50"
+              >
+                选择
+              </button>
+              <button
+                onClick={() => setSettings(prev => ({ ...prev, watch_folder: "" }))}
+                class="px-3 py-2 text-sm border border-gray-300 rounded-lg hover:bg-gray-50"
+              >
+                清除
+              </button>
+            </div>
+            <select
+              value={settings().watch_folder_device}
+              onChange={(e) => setSettings(prev => ({ ...prev, watch_folder_device: e.currentTarget.value }))}
+              class="w-full mt-2 px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="">选择接收设备</option>
+              <For each={appStore.devices()}>
+                {(device) => <option value={device.id}>{device.name} ({device.ip})</option>}
+              </For>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">放入该文件夹的新文件会自动发送给所选设备（设备需在线并已连接）</p>
+          </div>
+
+          {/* Latency HUD */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().latency_hud}
+                onChange={(e) => setSettings(prev => ({ ...prev, latency_hud: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              显示延迟信息
+            </label>
+            <p class="text-xs text-gray-500 mt-1">在观看窗口左上角显示从对方截屏到本机显示的端到端延迟和网络往返时间</p>
+          </div>
+
+          {/* Viewer Color Filter */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              观看画面滤镜
+            </label>
+            <select
+              value={settings().color_filter}
+              onChange={(e) => setSettings(prev => ({ ...prev, color_filter: e.currentTarget.value }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="">原始画面</option>
+              <option value="invert">反色</option>
+              <option value="grayscale">灰度</option>
+              <option value="high_contrast">高对比度</option>
+              <option value="protanopia">红色盲校正</option>
+              <option value="deuteranopia">绿色盲校正</option>
+              <option value="tritanopia">蓝色盲校正</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">新打开的观看窗口默认使用此滤镜，只影响本机显示（macOS 可在窗口工具栏中随时切换）</p>
+          </div>
+
+          {/* Viewer Fit Mode */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">
+              画面缩放方式
+            </label>
+            <select
+              value={settings().fit_mode}
+              onChange={(e) => setSettings(prev => ({ ...prev, fit_mode: e.currentTarget.value as AppSettings["fit_mode"] }))}
+              class="w-full px-4 py-2 border border-gray-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-primary-500 focus:border-transparent"
+            >
+              <option value="">适应窗口（保持比例，留黑边）</option>
+              <option value="fill">填满窗口（保持比例，裁剪边缘）</option>
+              <option value="original">原始大小（1:1）</option>
+              <option value="stretch">拉伸填满</option>
+            </select>
+            <p class="text-xs text-gray-500 mt-1">对方屏幕与窗口比例不同时（如 16:10 屏幕显示在 16:9 窗口中）如何显示（macOS 可在窗口工具栏中随时切换）</p>
+          </div>
+
+          {/* Clipboard Sync */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().clipboard_sync}
+                onChange={(e) => setSettings(prev => ({ ...prev, clipboard_sync: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              同步剪贴板
+            </label>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().clipboard_images}
+                onChange={(e) => setSettings(prev => ({ ...prev, clipboard_images: e.currentTarget.checked }))}
+                disabled={!settings().clipboard_sync}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              包括图片
+            </label>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().clipboard_files}
+                onChange={(e) => setSettings(prev => ({ ...prev, clipboard_files: e.currentTarget.checked }))}
+                disabled={!settings().clipboard_sync}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              包括文件（以文件传输发送，信任的设备自动接收）
+            </label>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().clipboard_send}
+                onChange={(e) => setSettings(prev => ({ ...prev, clipboard_send: e.currentTarget.checked }))}
+                disabled={!settings().clipboard_sync}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              发送本机复制的内容
+            </label>
+            <label class="flex items-center gap-2 text-sm text-gray-700 mt-2">
+              <input
+                type="checkbox"
+                checked={settings().clipboard_receive}
+                onChange={(e) => setSettings(prev => ({ ...prev, clipboard_receive: e.currentTarget.checked }))}
+                disabled={!settings().clipboard_sync}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              接收对方复制的内容
+            </label>
+            <p class="text-xs text-gray-500 mt-1">开启后复制的内容会发送给已连接的设备，对方复制的文字可直接粘贴；每台设备保留最近 20 条记录（文字不超过 64 KB，图片不超过 2 MB）</p>
+          </div>
+
+          {/* Safe Mode */}
+          <div>
+            <label class="flex items-center gap-2 text-sm font-medium text-gray-700">
+              <input
+                type="checkbox"
+                checked={settings().safe_mode}
+                onChange={(e) => setSettings(prev => ({ ...prev, safe_mode: e.currentTarget.checked }))}
+                class="rounded border-gray-300 text-primary-500 focus:ring-primary-500"
+              />
+              安全模式
+            </label>
+            <p class="text-xs text-gray-500 mt-1">禁用硬件加速（采集、编解码、低延迟显示），显卡驱动异常时使用</p>
+          </div>
+
+          {/* Trusted Devices */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">受信任的设备</label>
+            <For each={trustedDevices()} fallback={<p class="text-xs text-gray-500">暂无</p>}>
+              {(device) => (
+                <div class="flex items-center justify-between text-sm text-gray-700 py-1">
+                  <span>
+                    {device.peer_ip || device.device_id}
+                    <span class="text-xs text-gray-500 ml-2">{TRUST_SCOPE_LABELS[device.scope]}</span>
+                  </span>
+                  <button class="btn-secondary text-sm" onClick={() => untrustDevice(device.device_id)}>
+                    移除
+                  </button>
+                </div>
+              )}
+            </For>
+            <p class="text-xs text-gray-500 mt-1">受信任设备的远程控制请求无需确认</p>
+          </div>
+
+          {/* Blocked Devices */}
+          <div>
+            <label class="block text-sm font-medium text-gray-700 mb-2">已屏蔽的设备</label>
+            <For each={blockedDevices()} fallback={<p class="text-xs text-gray-500">暂无</p>}>
+              {(device) => (
+                <div class="flex items-center justify-between text-sm text-gray-700 py-1">
+                  <span>
+                    {device.nickname ?? device.name}
+                    <span class="text-xs text-gray-500 ml-2">{device.last_ip}</span>
+                  </span>
+                  <button class="btn-secondary text-sm" onClick={() => unblockDevice(device.id)}>
+                    解除屏蔽
+                  </button>
+                </div>
+              )}
+            </For>
+            <p class="text-xs text-gray-500 mt-1">屏蔽的设备不会出现在设备列表中，也无法连接本机</p>
+          </div>
         </div>
 
         {/* Footer */}
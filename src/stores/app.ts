@@ -1,17 +1,97 @@
 import { createSignal, createRoot } from "solid-js";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
 
 export interface Device {
   id: string;
   name: string;
   ip: string;
   port: number;
-  status: "online" | "busy" | "offline";
+  status: "seen" | "online" | "busy" | "offline";
   last_seen: number;
+  /** Base64 PNG the device introduced itself with */
+  avatar?: string | null;
 }
 
 export interface SelfInfo {
   id: string;
   name: string;
+  ip?: string;
+  avatar?: string | null;
+  /** Fingerprint of our certificate, what paired devices pin */
+  fingerprint?: string | null;
+}
+
+// Error of connect_to_device / add_manual_device
+export interface ConnectFailure {
+  kind:
+    | "service_stopped"
+    | "invalid_address"
+    | "timeout"
+    | "refused"
+    | "certificate"
+    | "version_mismatch"
+    | "rejected"
+    | "password_required"
+    | "connection_lost"
+    | "retry_later"
+    | "other";
+  message: string;
+  detail: string;
+}
+
+// Hint for a connect error, with the peer's reason when it declined
+export function connectErrorText(e: unknown): string {
+  const failure = e as ConnectFailure;
+  if (typeof failure !== "object" || failure === null || !failure.kind) {
+    return String(e);
+  }
+  return failure.kind === "rejected" ? `${failure.message}: ${failure.detail}` : failure.message;
+}
+
+// Run a connect command, asking for the meeting password and trying again
+// for as long as the peer wants one and the user enters it
+export async function withMeetingPassword<T>(attempt: (password?: string) => Promise<T>): Promise<T> {
+  let password: string | undefined;
+  for (;;) {
+    try {
+      return await attempt(password);
+    } catch (e) {
+      if ((e as ConnectFailure)?.kind !== "password_required") throw e;
+      const entered = prompt(password === undefined ? "对方设置了会议密码，请输入" : "会议密码不正确，请重新输入");
+      if (entered === null) throw e;
+      password = entered;
+    }
+  }
+}
+
+export interface SharingState {
+  is_sharing: boolean;
+  display_ids: number[];
+  paused: boolean;
+  simple_sharing: boolean;
+}
+
+// Snapshot returned by get_app_state
+export interface AppStateSnapshot {
+  revision: number;
+  service_running: boolean;
+  self_info: SelfInfo | null;
+  devices: Device[];
+  transfers: any[];
+  settings: Record<string, unknown>;
+  sharing: SharingState;
+}
+
+// "state-delta" event: only the areas that changed are present
+export interface StateDelta {
+  revision: number;
+  devices?: Device[];
+  transfers?: any[];
+  settings?: Record<string, unknown>;
+  sharing?: SharingState;
+  service_running?: boolean;
+  self_info?: SelfInfo;
 }
 
 export interface ConnectionState {
@@ -38,6 +118,54 @@ function createAppStore() {
   // Screen sharing state
   const [isSharing, setIsSharing] = createSignal(false);
   const [sharingDisplayId, setSharingDisplayId] = createSignal<number | null>(null);
+  const [sharing, setSharing] = createSignal<SharingState | null>(null);
+
+  // Backend state mirrored by syncState
+  const [serviceRunning, setServiceRunning] = createSignal(false);
+  const [transfers, setTransfers] = createSignal<any[]>([]);
+  const [settings, setSettings] = createSignal<Record<string, unknown> | null>(null);
+  let revision = -1;
+
+  const applySharing = (state: SharingState) => {
+    setSharing(state);
+    setIsSharing(state.is_sharing || state.simple_sharing);
+    setSharingDisplayId(state.display_ids[0] ?? null);
+  };
+
+  const applyDelta = (delta: StateDelta) => {
+    // Deltas already contained in the snapshot
+    if (delta.revision <= revision) return;
+    revision = delta.revision;
+    if (delta.devices) setDevices(delta.devices);
+    if (delta.transfers) setTransfers(delta.transfers);
+    if (delta.settings) setSettings(delta.settings);
+    if (delta.sharing) applySharing(delta.sharing);
+    if (delta.service_running !== undefined) setServiceRunning(delta.service_running);
+    if (delta.self_info) setSelfInfo(delta.self_info);
+  };
+
+  // Load the full state once (e.g. after a webview reload), then follow
+  // deltas. Deltas arriving while the snapshot loads are applied after it.
+  const syncState = async (): Promise<UnlistenFn> => {
+    const pending: StateDelta[] = [];
+    let loaded = false;
+    const unlisten = await listen<StateDelta>("state-delta", (event) => {
+      if (loaded) applyDelta(event.payload);
+      else pending.push(event.payload);
+    });
+
+    const state = await invoke<AppStateSnapshot>("get_app_state");
+    revision = state.revision;
+    setSelfInfo(state.self_info);
+    setServiceRunning(state.service_running);
+    setDevices(state.devices);
+    setTransfers(state.transfers);
+    setSettings(state.settings);
+    applySharing(state.sharing);
+    loaded = true;
+    pending.forEach(applyDelta);
+    return unlisten;
+  };
 
   // Helper functions
   const addConnectedDevice = (deviceId: string) => {
@@ -90,6 +218,13 @@ function createAppStore() {
     setIsSharing,
     sharingDisplayId,
     setSharingDisplayId,
+    sharing,
+
+    // Backend state
+    serviceRunning,
+    transfers,
+    settings,
+    syncState,
   };
 }
 